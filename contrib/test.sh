@@ -1,6 +1,6 @@
 #!/bin/sh -ex
 
-FEATURES="base64 bitcoinconsensus use-serde rand secp-recovery"
+FEATURES="base64 bitcoinconsensus use-serde rand secp-recovery bip38"
 
 # Use toolchain if explicitly specified
 if [ -n "$TOOLCHAIN" ]
@@ -67,6 +67,13 @@ do
     cargo test --verbose --features="$feature"
 done
 
+# Build for WebAssembly if told to
+if [ "$DO_WASM" = true ]
+then
+    CC=clang cargo build --verbose --target wasm32-unknown-unknown
+    CC=clang cargo build --verbose --target wasm32-unknown-unknown --features="use-serde base64"
+fi
+
 # Build the docs if told to (this only works with the nightly toolchain)
 if [ "$DO_DOCS" = true ]; then
     RUSTDOCFLAGS="--cfg docsrs" cargo doc --all --features="$FEATURES"
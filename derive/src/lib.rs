@@ -0,0 +1,198 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Derive macros for consensus encoding.
+//!
+//! This crate implements `#[derive(ConsensusEncode, ConsensusDecode)]`, which
+//! the `bitcoin` crate re-exports from its `consensus` module when its
+//! `derive` feature is enabled. The derived implementations encode the
+//! fields of a struct one after the other, like the `impl_consensus_encoding!`
+//! macro used within the `bitcoin` crate.
+//!
+//! Fields can be annotated to change how they are encoded:
+//!
+//! - `#[consensus(length_prefixed)]` encodes a vector as its length as a
+//!   `VarInt` followed by its elements, for element types the `bitcoin` crate
+//!   has no `Vec` implementation for, such as types of the deriving crate.
+//! - `#[consensus(array)]` encodes a fixed-size array as its elements, without
+//!   a length. Decoding requires the array to implement `Default`.
+//!
+//! The generated code refers to the `bitcoin` crate as `::bitcoin`; crates
+//! renaming it set its path with `#[consensus(crate = "path")]` on the struct.
+//!
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index, LitStr, Member, Path, Type};
+
+/// How a field is encoded.
+enum Encoding {
+    /// With its own `Encodable` and `Decodable` implementations.
+    Plain,
+    /// As a `VarInt` length followed by the elements.
+    LengthPrefixed,
+    /// As the elements of a fixed-size array.
+    Array,
+}
+
+struct Field {
+    member: Member,
+    ty: Type,
+    encoding: Encoding,
+}
+
+struct Input {
+    krate: Path,
+    fields: Vec<Field>,
+    named: bool,
+}
+
+fn parse(input: &DeriveInput) -> Result<Input, Error> {
+    let mut krate: Path = syn::parse_quote!(::bitcoin);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("consensus")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("unknown consensus attribute, expected `crate`"))
+            }
+        })?;
+    }
+
+    let data = match input.data {
+        Data::Struct(ref data) => data,
+        _ => return Err(Error::new_spanned(&input.ident, "consensus encoding can only be derived for structs")),
+    };
+    let mut fields = vec![];
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let mut encoding = Encoding::Plain;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("consensus")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("length_prefixed") {
+                    encoding = Encoding::LengthPrefixed;
+                    Ok(())
+                } else if meta.path.is_ident("array") {
+                    encoding = Encoding::Array;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown consensus attribute, expected `length_prefixed` or `array`"))
+                }
+            })?;
+        }
+        fields.push(Field { member, ty: field.ty.clone(), encoding });
+    }
+    Ok(Input { krate, fields, named: matches!(data.fields, Fields::Named(_)) })
+}
+
+/// Derives `bitcoin::consensus::Encodable`, encoding the fields in order.
+#[proc_macro_derive(ConsensusEncode, attributes(consensus))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let parsed = match parse(&input) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let krate = &parsed.krate;
+    let encode_fields = parsed.fields.iter().map(|field| {
+        let member = &field.member;
+        match field.encoding {
+            Encoding::Plain => quote! {
+                len += #krate::consensus::Encodable::consensus_encode(&self.#member, w)?;
+            },
+            Encoding::LengthPrefixed => quote! {
+                len += #krate::consensus::Encodable::consensus_encode(&#krate::VarInt(self.#member.len() as u64), w)?;
+                for item in self.#member.iter() {
+                    len += #krate::consensus::Encodable::consensus_encode(item, w)?;
+                }
+            },
+            Encoding::Array => quote! {
+                for item in self.#member.iter() {
+                    len += #krate::consensus::Encodable::consensus_encode(item, w)?;
+                }
+            },
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let expanded = quote! {
+        impl #impl_generics #krate::consensus::Encodable for #name #ty_generics #where_clause {
+            fn consensus_encode<W: #krate::io::Write + ?Sized>(&self, w: &mut W) -> ::core::result::Result<usize, #krate::io::Error> {
+                let mut len = 0;
+                #(#encode_fields)*
+                Ok(len)
+            }
+        }
+    };
+    expanded.into()
+}
+
+/// Derives `bitcoin::consensus::Decodable`, decoding the fields in order.
+#[proc_macro_derive(ConsensusDecode, attributes(consensus))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let parsed = match parse(&input) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let krate = &parsed.krate;
+    let decode_fields: Vec<TokenStream> = parsed.fields.iter().map(|field| {
+        let ty = &field.ty;
+        let value = match field.encoding {
+            Encoding::Plain => quote! {
+                #krate::consensus::Decodable::consensus_decode_from_finite_reader(r)?
+            },
+            Encoding::LengthPrefixed => quote! {{
+                let len = <#krate::VarInt as #krate::consensus::Decodable>::consensus_decode_from_finite_reader(r)?.0;
+                (0..len)
+                    .map(|_| #krate::consensus::Decodable::consensus_decode_from_finite_reader(r))
+                    .collect::<::core::result::Result<_, _>>()?
+            }},
+            Encoding::Array => quote! {{
+                let mut array: #ty = ::core::default::Default::default();
+                for item in array.iter_mut() {
+                    *item = #krate::consensus::Decodable::consensus_decode_from_finite_reader(r)?;
+                }
+                array
+            }},
+        };
+        let member = &field.member;
+        if parsed.named {
+            quote! { #member: #value }
+        } else {
+            value
+        }
+    }).collect();
+
+    let name = &input.ident;
+    let construct = if parsed.named {
+        quote! { #name { #(#decode_fields),* } }
+    } else {
+        quote! { #name ( #(#decode_fields),* ) }
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let expanded = quote! {
+        impl #impl_generics #krate::consensus::Decodable for #name #ty_generics #where_clause {
+            fn consensus_decode_from_finite_reader<R: #krate::io::Read + ?Sized>(r: &mut R) -> ::core::result::Result<Self, #krate::consensus::encode::Error> {
+                Ok(#construct)
+            }
+        }
+    };
+    expanded.into()
+}
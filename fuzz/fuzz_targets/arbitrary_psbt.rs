@@ -0,0 +1,42 @@
+extern crate bitcoin;
+
+use bitcoin::arbitrary::{Arbitrary, Unstructured};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+fn do_test(data: &[u8]) {
+    let psbt = match PartiallySignedTransaction::arbitrary(&mut Unstructured::new(data)) {
+        Ok(psbt) => psbt,
+        Err(_) => return,
+    };
+    let ser = bitcoin::consensus::encode::serialize(&psbt);
+    let deser: PartiallySignedTransaction = bitcoin::consensus::encode::deserialize(&ser).unwrap();
+    assert_eq!(deser, psbt);
+}
+
+#[cfg(feature = "afl")]
+#[macro_use] extern crate afl;
+#[cfg(feature = "afl")]
+fn main() {
+    fuzz!(|data| {
+        do_test(&data);
+    });
+}
+
+#[cfg(feature = "honggfuzz")]
+#[macro_use] extern crate honggfuzz;
+#[cfg(feature = "honggfuzz")]
+fn main() {
+    loop {
+        fuzz!(|data| {
+            do_test(data);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn empty_input() {
+        super::do_test(&[]);
+    }
+}
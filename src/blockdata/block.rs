@@ -25,22 +25,33 @@ use prelude::*;
 use core::fmt;
 
 use util;
-use util::Error::{BlockBadTarget, BlockBadProofOfWork};
-use util::hash::bitcoin_merkle_root;
+use util::Error::{BlockBadTarget, BlockBadProofOfWork, UnsupportedPowAlgorithm};
+use util::hash::{bitcoin_merkle_root, Sha256dEngine};
 use hashes::{Hash, HashEngine};
-use hash_types::{Wtxid, BlockHash, TxMerkleNode, WitnessMerkleNode, WitnessCommitment};
+use hash_types::{Wtxid, BlockHash, PowHash, TxMerkleNode, WitnessMerkleNode, WitnessCommitment};
 use util::uint::Uint256;
+use util::pow::{CompactTarget, PowHasher, Target};
+use util::weight::Weight;
 use consensus::{encode, Decodable, Encodable};
+use consensus::encode::DecodeContext;
 use network::constants::Network;
-use blockdata::transaction::Transaction;
-use blockdata::constants::{max_target, WITNESS_SCALE_FACTOR};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use blockdata::witness::Witness;
+use consensus::params::{block_subsidy, Params};
+use blockdata::constants::{max_target, MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_SPAN, WITNESS_SCALE_FACTOR};
 use blockdata::script;
+use blockdata::opcodes::{self, ClassifyContext};
 use VarInt;
 use io::{self};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+
+/// The header of a BIP141 witness commitment output: `OP_RETURN`, a 36-byte push and the
+/// `0xaa21a9ed` tag.
+pub(crate) const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
 
 /// A block header, which contains all the block's information except
 /// the actual transactions
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BlockHeader {
     /// The protocol version. Should always be 1.
@@ -226,96 +237,121 @@ impl BlockHeader {
 
     /// Returns the block hash.
     pub fn block_hash(&self) -> BlockHash {
-        let mut engine = BlockHash::engine();
+        let mut engine = Sha256dEngine::new();
         self.to_simple_header().consensus_encode(&mut engine).expect("engines don't error");
-        BlockHash::from_engine(engine)
+        engine.finish()
+    }
+
+    /// Returns the compact proof-of-work target of the header.
+    pub fn compact_target(&self) -> CompactTarget {
+        CompactTarget::from_consensus(self.bits)
     }
 
     /// Computes the target [0, T] that a blockhash must land in to be valid.
-    pub fn target(&self) -> Uint256 {
-        Self::u256_from_compact_target(self.bits)
+    pub fn target(&self) -> Target {
+        Target::from_compact(self.compact_target())
     }
 
     /// Computes the target value in [`Uint256`] format, from a compact representation.
     ///
     /// [`Uint256`]: ../../util/uint/struct.Uint256.html
-    ///
-    /// ```
-    /// use bitcoin::blockdata::block::BlockHeader;
-    ///
-    /// assert_eq!(0x1d00ffff,
-    ///     BlockHeader::compact_target_from_u256(
-    ///         &BlockHeader::u256_from_compact_target(0x1d00ffff)
-    ///     )
-    /// );
-    /// ```
+    #[deprecated(since = "0.29.0", note = "Please use `Target::from_compact` instead.")]
     pub fn u256_from_compact_target(bits: u32) -> Uint256 {
-        // This is a floating-point "compact" encoding originally used by
-        // OpenSSL, which satoshi put into consensus code, so we're stuck
-        // with it. The exponent needs to have 3 subtracted from it, hence
-        // this goofy decoding code:
-        let (mant, expt) = {
-            let unshifted_expt = bits >> 24;
-            if unshifted_expt <= 3 {
-                ((bits & 0xFFFFFF) >> (8 * (3 - unshifted_expt as usize)), 0)
-            } else {
-                (bits & 0xFFFFFF, 8 * ((bits >> 24) - 3))
-            }
-        };
-
-        // The mantissa is signed but may not be negative
-        if mant > 0x7FFFFF {
-            Default::default()
-        } else {
-            Uint256::from_u64(mant as u64).unwrap() << (expt as usize)
-        }
+        Target::from_compact(CompactTarget::from_consensus(bits)).to_uint256()
     }
 
     /// Computes the target value in float format from Uint256 format.
+    #[deprecated(since = "0.29.0", note = "Please use `Target::to_compact_lossy` instead.")]
     pub fn compact_target_from_u256(value: &Uint256) -> u32 {
-        let mut size = (value.bits() + 7) / 8;
-        let mut compact = if size <= 3 {
-            (value.low_u64() << (8 * (3 - size))) as u32
-        } else {
-            let bn = *value >> (8 * (size - 3));
-            bn.low_u32()
-        };
-
-        if (compact & 0x00800000) != 0 {
-            compact >>= 8;
-            size += 1;
-        }
-
-        compact | (size << 24) as u32
+        Target::from_uint256(*value).to_compact_lossy().to_consensus()
     }
 
-    /// Computes the popular "difficulty" measure for mining.
+    /// Computes the popular "difficulty" measure for mining, rounded down.
+    #[deprecated(since = "0.29.0", note = "Please use `BlockHeader::difficulty_float` instead.")]
     pub fn difficulty(&self, network: Network) -> u64 {
-        (max_target(network) / self.target()).low_u64()
+        (max_target(network) / self.target().to_uint256()).low_u64()
+    }
+
+    /// Computes the popular "difficulty" measure for mining, as shown by block explorers.
+    ///
+    /// See [`Difficulty`](::util::pow::Difficulty) to compare difficulties or convert them back to targets.
+    pub fn difficulty_float(&self) -> f64 {
+        self.target().difficulty().to_f64()
     }
 
     /// Checks that the proof-of-work for the block is valid, returning the block hash.
-    pub fn validate_pow(&self, required_target: &Uint256) -> Result<BlockHash, util::Error> {
-        let target = &self.target();
+    ///
+    /// The header must commit to exactly `required_target`, and its hash must meet it.
+    pub fn validate_pow(&self, required_target: Target) -> Result<BlockHash, util::Error> {
+        let target = self.target();
         if target != required_target {
             return Err(BlockBadTarget);
         }
         let block_hash = self.block_hash();
-        let mut ret = [0u64; 4];
-        util::endian::bytes_to_u64_slice_le(block_hash.as_inner(), &mut ret);
-        let hash = &Uint256(ret);
-        if hash <= target { Ok(block_hash) } else { Err(BlockBadProofOfWork) }
+        if target.is_met_by(block_hash) { Ok(block_hash) } else { Err(BlockBadProofOfWork) }
+    }
+
+    /// Returns the proof-of-work hash of the header computed with `hasher`, e.g. the
+    /// [`Params::pow_algorithm`] of the chain, `None` if `hasher` isn't available in this build.
+    pub fn pow_hash<P: PowHasher + ?Sized>(&self, hasher: &P) -> Option<PowHash> {
+        hasher.pow_hash(&encode::serialize(&self.to_simple_header()))
+    }
+
+    /// Checks that the proof-of-work hash of the header computed with `hasher` meets the target
+    /// and that the target is the `required_target`.
+    ///
+    /// This is [`BlockHeader::validate_pow`] for chains using another hash function than double
+    /// SHA256; on success the proof-of-work hash is returned.
+    pub fn validate_pow_with<P: PowHasher + ?Sized>(&self, required_target: Target, hasher: &P) -> Result<PowHash, util::Error> {
+        let target = self.target();
+        if target != required_target {
+            return Err(BlockBadTarget);
+        }
+        let pow_hash = self.pow_hash(hasher).ok_or(UnsupportedPowAlgorithm)?;
+        if target.is_met_by(pow_hash) { Ok(pow_hash) } else { Err(BlockBadProofOfWork) }
+    }
+
+    /// Checks the header timestamp against the chain it extends.
+    ///
+    /// The timestamp must be strictly greater than `median_time_past`, the median time past of
+    /// the previous block (see [`median_time_past`]), and at most two hours ahead of the
+    /// network-adjusted time `adjusted_time`.
+    pub fn is_timestamp_valid(&self, median_time_past: u32, adjusted_time: u32) -> bool {
+        self.time > median_time_past && self.time <= adjusted_time.saturating_add(MAX_FUTURE_BLOCK_TIME)
+    }
+
+    /// Grinds the nonce until the block hash meets `target`, returning the hash found.
+    ///
+    /// The nonce is searched upwards from its current value. Once all nonces are exhausted the
+    /// timestamp is incremented and the search restarts from zero if `increment_time` is set,
+    /// otherwise `None` is returned. This is only practical for easy targets such as regtest's.
+    pub fn solve(&mut self, target: Target, increment_time: bool) -> Option<BlockHash> {
+        loop {
+            loop {
+                let block_hash = self.block_hash();
+                if target.is_met_by(block_hash) {
+                    return Some(block_hash);
+                }
+                if self.nonce == u32::max_value() {
+                    break;
+                }
+                self.nonce += 1;
+            }
+            if !increment_time {
+                return None;
+            }
+            self.time = self.time.checked_add(1)?;
+            self.nonce = 0;
+        }
     }
 
     /// Returns the total work of the block.
+    ///
+    /// See [`pow::chain_work`] to sum the work of a chain of headers.
+    ///
+    /// [`pow::chain_work`]: ../../util/pow/fn.chain_work.html
     pub fn work(&self) -> Uint256 {
-        // 2**256 / (target + 1) == ~target / (target+1) + 1    (eqn shamelessly stolen from bitcoind)
-        let mut ret = !self.target();
-        let mut ret1 = self.target();
-        ret1.increment();
-        ret = ret / ret1;
-        ret.increment();
-        ret
+        self.target().to_work()
     }
 
     pub fn get_size(&self) -> usize {
@@ -327,20 +363,170 @@ impl BlockHeader {
     }
 }
 
+/// Computes the median time past (BIP113) of the last header in `headers`.
+///
+/// `headers` must be in chain order; only the last 11 of them, or fewer close to the genesis
+/// block, are taken into account. Returns `None` if `headers` is empty.
+pub fn median_time_past(headers: &[BlockHeader]) -> Option<u32> {
+    let start = headers.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut times: Vec<u32> = headers[start..].iter().map(|h| h.time).collect();
+    times.sort_unstable();
+    times.get(times.len() / 2).cloned()
+}
+
+/// Mines a regtest block at `height` on top of `prev_header`.
+///
+/// The coinbase pays the full block subsidy, but none of the fees of `txdata`, to
+/// `script_pubkey`, encodes `height` as required by BIP34 and commits to the witnesses of
+/// `txdata`. The header keeps the target of `prev_header`, as regtest never retargets, and is
+/// solved before the block is returned.
+pub fn mine_block(prev_header: &BlockHeader, height: u32, txdata: Vec<Transaction>, script_pubkey: script::Script) -> Block {
+    const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+    let coinbase = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            // The script sig must be at least two bytes long.
+            script_sig: script::Builder::new()
+                .push_int(height as i64)
+                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                .into_script(),
+            sequence: 0xFFFFFFFF,
+            witness: Witness::from_vec(vec![WITNESS_RESERVED_VALUE.to_vec()]),
+        }],
+        output: vec![TxOut {
+            value: block_subsidy(height, &Params::new(Network::Regtest)).as_sat(),
+            script_pubkey,
+        }],
+    };
+    let mut block = Block {
+        header: BlockHeader {
+            version: Version::NO_SOFT_FORK_SIGNALLING,
+            prev_blockhash: prev_header.block_hash(),
+            merkle_root: Default::default(),
+            time: prev_header.time + 1,
+            bits: prev_header.bits,
+            nonce: 0,
+            aux_data: None,
+        },
+        txdata: Some(coinbase).into_iter().chain(txdata).collect(),
+        extension: None,
+    };
+
+    let witness_root = block.witness_root().expect("block has a coinbase");
+    let commitment = Block::compute_witness_commitment(&witness_root, &WITNESS_RESERVED_VALUE);
+    block.txdata[0].output.push(TxOut {
+        value: 0,
+        script_pubkey: Block::witness_commitment_script(&commitment),
+    });
+    block.header.merkle_root = block.compute_merkle_root().expect("block has a coinbase");
+
+    let target = block.header.target();
+    block.header.solve(target, true).expect("regtest target is easy to meet");
+    block
+}
+
 /// A Bitcoin block, which is a collection of transactions with an attached
 /// proof of work.
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Block {
     /// The block header
     pub header: BlockHeader,
     /// List of transactions contained in the block
-    pub txdata: Vec<Transaction>
+    pub txdata: Vec<Transaction>,
+    /// Data following the transactions, on chains with extension blocks. It is only captured by
+    /// [`Block::consensus_decode_with_extension`].
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub extension: Option<ExtensionPayload>,
+}
+
+impl Encodable for Block {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.header.consensus_encode(w)?;
+        len += self.txdata.consensus_encode(w)?;
+        if let Some(ref extension) = self.extension {
+            w.write_all(&extension.0)?;
+            len += extension.0.len();
+        }
+        Ok(len)
+    }
+
+    fn consensus_size(&self) -> usize {
+        // Unlike `size`, this counts the segwit marker and flag of transactions without inputs
+        let txs_size: usize = self.txdata.iter().map(Encodable::consensus_size).sum();
+        self.base_size() + txs_size
+    }
 }
 
-impl_consensus_encoding!(Block, header, txdata);
+impl Decodable for Block {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let mut r = io::Read::take(r, encode::MAX_VEC_SIZE as u64);
+        Ok(Block {
+            header: Decodable::consensus_decode(&mut r)?,
+            txdata: Decodable::consensus_decode(&mut r)?,
+            extension: None,
+        })
+    }
+
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, encode::Error> {
+        let mut r = io::Read::take(r, context.max_payload as u64);
+        Ok(Block {
+            header: Decodable::consensus_decode_with_context(&mut r, context)?,
+            txdata: Decodable::consensus_decode_with_context(&mut r, context)?,
+            extension: None,
+        })
+    }
+}
+
+/// Opaque data serialized after the transactions of a block, such as the extension block of
+/// chains with MWEB-style extension blocks.
+#[derive(PartialEq, Eq, Clone, Debug, Default, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExtensionPayload(
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
+    Vec<u8>
+);
+
+impl ExtensionPayload {
+    /// Creates an extension payload from its serialization.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        ExtensionPayload(bytes)
+    }
+
+    /// Returns the serialized payload.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the serialized payload, consuming the extension payload.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Reads everything left in `r`, `None` if nothing is left.
+    fn consensus_decode_trailing<R: io::Read + ?Sized>(r: &mut R) -> Result<Option<Self>, encode::Error> {
+        let extension = util::read_to_end(r)?;
+        Ok(if extension.is_empty() { None } else { Some(ExtensionPayload(extension)) })
+    }
+}
 
 impl Block {
+    /// Decodes a block, capturing everything the reader holds after the transactions as the
+    /// [`Block::extension`] so that it is serialized back unchanged.
+    ///
+    /// The reader must hold a single block, e.g. the payload of a `block` message; use
+    /// [`Decodable::consensus_decode`] to decode blocks from a stream.
+    pub fn consensus_decode_with_extension<R: io::Read + ?Sized>(r: &mut R) -> Result<Block, encode::Error> {
+        let mut r = io::Read::take(r, encode::MAX_VEC_SIZE as u64);
+        let mut block = Block::consensus_decode(&mut r)?;
+        block.extension = ExtensionPayload::consensus_decode_trailing(&mut r)?;
+        Ok(block)
+    }
+
     /// Returns the block hash.
     pub fn block_hash(&self) -> BlockHash {
         self.header.block_hash()
@@ -355,39 +541,47 @@ impl Block {
     }
 
     /// Checks if witness commitment in coinbase matches the transaction list.
+    ///
+    /// Following BIP141, a block without a commitment must not contain any witness data, while a
+    /// commitment that is present must always be valid, even if no transaction uses SegWit.
     pub fn check_witness_commitment(&self) -> bool {
-        const MAGIC: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
-        // Witness commitment is optional if there are no transactions using SegWit in the block.
-        if self.txdata.iter().all(|t| t.input.iter().all(|i| i.witness.is_empty())) {
-            return true;
-        }
-
-        if self.txdata.is_empty() {
-            return false;
-        }
-
-        let coinbase = &self.txdata[0];
-        if !coinbase.is_coin_base() {
-            return false;
-        }
+        let commitment = match self.witness_commitment() {
+            Some(commitment) => commitment,
+            None => return self.txdata.iter().all(|t| t.input.iter().all(|i| i.witness.is_empty())),
+        };
 
-        // Commitment is in the last output that starts with magic bytes.
-        if let Some(pos) = coinbase.output.iter()
-            .rposition(|o| o.script_pubkey.len () >= 38 && o.script_pubkey[0..6] ==  MAGIC)
-        {
-            let commitment = WitnessCommitment::from_slice(&coinbase.output[pos].script_pubkey.as_bytes()[6..38]).unwrap();
-            // Witness reserved value is in coinbase input witness.
-            let witness_vec: Vec<_> = coinbase.input[0].witness.iter().collect();
-            if witness_vec.len() == 1 && witness_vec[0].len() == 32 {
-                if let Some(witness_root) = self.witness_root() {
-                    return commitment == Self::compute_witness_commitment(&witness_root, witness_vec[0]);
-                }
+        // Witness reserved value is in coinbase input witness.
+        let witness_vec: Vec<_> = self.txdata[0].input[0].witness.iter().collect();
+        if witness_vec.len() == 1 && witness_vec[0].len() == 32 {
+            if let Some(witness_root) = self.witness_root() {
+                return commitment == Self::compute_witness_commitment(&witness_root, witness_vec[0]);
             }
         }
 
         false
     }
 
+    /// Returns the witness commitment stored in the coinbase, if any.
+    ///
+    /// The commitment is taken from the last coinbase output whose script starts with the BIP141
+    /// commitment header.
+    pub fn witness_commitment(&self) -> Option<WitnessCommitment> {
+        let coinbase = self.coinbase().filter(|cb| cb.is_coinbase())?;
+        coinbase.output.iter()
+            .rev()
+            .map(|o| o.script_pubkey.as_bytes())
+            .find(|s| s.len() >= 38 && s[0..6] == WITNESS_COMMITMENT_HEADER)
+            .map(|s| WitnessCommitment::from_slice(&s[6..38]).expect("32 bytes"))
+    }
+
+    /// Returns the coinbase output script committing to `commitment`, as described in BIP141.
+    pub fn witness_commitment_script(commitment: &WitnessCommitment) -> script::Script {
+        let mut bytes = Vec::with_capacity(38);
+        bytes.extend_from_slice(&WITNESS_COMMITMENT_HEADER);
+        bytes.extend_from_slice(&commitment[..]);
+        script::Script::from(bytes)
+    }
+
     /// Computes the transaction merkle root.
     pub fn compute_merkle_root(&self) -> Option<TxMerkleNode> {
         let hashes = self.txdata.iter().map(|obj| obj.txid().as_hash());
@@ -421,9 +615,11 @@ impl Block {
         bitcoin_merkle_root(hashes).map(|h| h.into())
     }
 
-    /// base_size == size of header + size of encoded transaction count.
+    /// base_size == size of header + size of encoded transaction count + size of the extension
+    /// payload.
     fn base_size(&self) -> usize {
-        self.header.get_size() + VarInt(self.txdata.len() as u64).len()
+        let extension_size = self.extension.as_ref().map_or(0, |extension| extension.0.len());
+        self.header.get_size() + VarInt(self.txdata.len() as u64).len() + extension_size
     }
 
     /// Returns the size of the block.
@@ -434,7 +630,8 @@ impl Block {
 
     /// Returns the size of the block.
     ///
-    /// size == size of header + size of encoded transaction count + total size of transactions.
+    /// size == size of header + size of encoded transaction count + total size of transactions
+    /// + size of the extension payload.
     pub fn size(&self) -> usize {
         let txs_size: usize = self.txdata.iter().map(Transaction::size).sum();
         self.base_size() + txs_size
@@ -455,13 +652,13 @@ impl Block {
     /// Returns the weight of the block.
     #[deprecated(since = "0.28.0", note = "Please use `transaction::weight` instead.")]
     pub fn get_weight(&self) -> usize {
-        self.weight()
+        self.weight().to_wu() as usize
     }
 
     /// Returns the weight of the block.
-    pub fn weight(&self) -> usize {
-        let base_weight = WITNESS_SCALE_FACTOR * self.base_size();
-        let txs_weight: usize = self.txdata.iter().map(Transaction::weight).sum();
+    pub fn weight(&self) -> Weight {
+        let base_weight = Weight::from_wu((WITNESS_SCALE_FACTOR * self.base_size()) as u64);
+        let txs_weight: Weight = self.txdata.iter().map(Transaction::weight).sum();
         base_weight + txs_weight
     }
 
@@ -488,18 +685,27 @@ impl Block {
         let cb = self.coinbase().ok_or(Bip34Error::NotPresent)?;
         let input = cb.input.first().ok_or(Bip34Error::NotPresent)?;
         let push = input.script_sig.instructions_minimal().next().ok_or(Bip34Error::NotPresent)?;
-        match push.map_err(|_| Bip34Error::NotPresent)? {
-            script::Instruction::PushBytes(b) if b.len() <= 8 => {
-                // Expand the push to exactly 8 bytes (LE).
-                let mut full = [0; 8];
-                full[0..b.len()].copy_from_slice(b);
-                Ok(util::endian::slice_to_u64_le(&full))
-            }
-            script::Instruction::PushBytes(b) if b.len() > 8 => {
-                Err(Bip34Error::UnexpectedPush(b.to_vec()))
+        let height = match push.map_err(|e| match e {
+            script::Error::NonMinimalPush => Bip34Error::NonMinimalPush,
+            _ => Bip34Error::NotPresent,
+        })? {
+            // The height is a `CScriptNum`, so it must be minimally encoded and
+            // heights 0 through 16 are pushed with the small integer opcodes.
+            script::Instruction::PushBytes(b) => {
+                script::read_scriptint_minimal(b).map_err(|e| match e {
+                    script::Error::NonMinimalPush => Bip34Error::NonMinimalPush,
+                    _ => Bip34Error::UnexpectedPush(b.to_vec()),
+                })?
             }
-            _ => Err(Bip34Error::NotPresent),
+            script::Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+                opcodes::Class::PushNum(n) => n as i64,
+                _ => return Err(Bip34Error::NotPresent),
+            },
+        };
+        if height < 0 {
+            return Err(Bip34Error::NegativeHeight);
         }
+        Ok(height as u64)
     }
 }
 
@@ -558,6 +764,47 @@ impl AuxPow {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Version {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Version(u.arbitrary()?))
+    }
+}
+
+arbitrary_struct_impl!(SimpleHeader, version, prev_blockhash, merkle_root, time, bits, nonce);
+
+// The auxiliary proof of work is only generated for versions flagging it, as only those
+// encode it.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for BlockHeader {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let base = SimpleHeader::arbitrary(u)?;
+        let aux_data = if (base.version.0 & 0x100) != 0 { Some(u.arbitrary()?) } else { None };
+        Ok(BlockHeader {
+            version: base.version,
+            prev_blockhash: base.prev_blockhash,
+            merkle_root: base.merkle_root,
+            time: base.time,
+            bits: base.bits,
+            nonce: base.nonce,
+            aux_data,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Block {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Block { header: u.arbitrary()?, txdata: u.arbitrary()?, extension: None })
+    }
+}
+
+arbitrary_struct_impl!(MerkleBranch, hashes, side_mask);
+arbitrary_struct_impl!(AuxPow, coinbase_tx, block_hash, coinbase_branch, blockchain_branch, parent_block);
+
 /// An error when looking up a BIP34 block height.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Bip34Error {
@@ -565,8 +812,12 @@ pub enum Bip34Error {
     Unsupported,
     /// No push was present where the BIP34 push was expected.
     NotPresent,
-    /// The BIP34 push was larger than 8 bytes.
+    /// The BIP34 push was larger than 4 bytes.
     UnexpectedPush(Vec<u8>),
+    /// The BIP34 push was not a minimally encoded number.
+    NonMinimalPush,
+    /// The BIP34 push encoded a negative number.
+    NegativeHeight,
 }
 
 impl fmt::Display for Bip34Error {
@@ -575,8 +826,10 @@ impl fmt::Display for Bip34Error {
             Bip34Error::Unsupported => write!(f, "block doesn't support BIP34"),
             Bip34Error::NotPresent => write!(f, "BIP34 push not present in block's coinbase"),
             Bip34Error::UnexpectedPush(ref p) => {
-                write!(f, "unexpected byte push of > 8 bytes: {:?}", p)
+                write!(f, "unexpected byte push of > 4 bytes: {:?}", p)
             }
+            Bip34Error::NonMinimalPush => write!(f, "non-minimal BIP34 height push"),
+            Bip34Error::NegativeHeight => write!(f, "negative BIP34 height"),
         }
     }
 }
@@ -588,9 +841,16 @@ impl ::std::error::Error for Bip34Error {}
 mod tests {
     use hashes::hex::FromHex;
 
-    use blockdata::block::{Block, BlockHeader};
-    use consensus::encode::{deserialize, serialize};
+    use blockdata::block::{median_time_past, mine_block, Block, BlockHeader, ExtensionPayload, Version};
+    use blockdata::constants::genesis_block;
+    use blockdata::script::Script;
+    use blockdata::transaction::{Transaction, TxOut};
+    use consensus::encode::{deserialize, serialize, Encodable};
     use util::uint::Uint256;
+    use util::pow::{PowAlgorithm, Target};
+    use util::weight::Weight;
+    #[cfg(not(feature = "scrypt-pow"))]
+    use util::Error::UnsupportedPowAlgorithm;
     use util::Error::{BlockBadTarget, BlockBadProofOfWork};
     use network::constants::Network;
 
@@ -612,9 +872,32 @@ mod tests {
 
         let push = Vec::<u8>::from_hex("a08601112233445566").unwrap();
         assert_eq!(bad.bip34_block_height(), Err(super::Bip34Error::UnexpectedPush(push)));
+
+        let with_script_sig = |hex: &str| {
+            let mut block = block.clone();
+            block.txdata[0].input[0].script_sig = Script::from(Vec::from_hex(hex).unwrap());
+            block.bip34_block_height()
+        };
+        // small heights use the OP_1..OP_16 opcodes
+        assert_eq!(with_script_sig("00"), Ok(0));
+        assert_eq!(with_script_sig("60"), Ok(16));
+        assert_eq!(with_script_sig("0111"), Ok(17));
+        // 100,000 padded with a redundant zero byte
+        assert_eq!(with_script_sig("04a0860100"), Err(super::Bip34Error::NonMinimalPush));
+        // 16 pushed as data instead of OP_16
+        assert_eq!(with_script_sig("0110"), Err(super::Bip34Error::NonMinimalPush));
+        assert_eq!(with_script_sig("4f"), Err(super::Bip34Error::NegativeHeight));
+        assert_eq!(with_script_sig("03a08681"), Err(super::Bip34Error::NegativeHeight));
+        assert_eq!(with_script_sig("6a"), Err(super::Bip34Error::NotPresent));
+        assert_eq!(with_script_sig(""), Err(super::Bip34Error::NotPresent));
+
+        let mut v1 = block.clone();
+        v1.header.version = Version::ONE;
+        assert_eq!(v1.bip34_block_height(), Err(super::Bip34Error::Unsupported));
     }
 
     #[test]
+    #[allow(deprecated)]
     fn block_test() {
         // Mainnet block 00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7
         let some_block = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b0201000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0804ffff001d026e04ffffffff0100f2052a0100000043410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5ac00000000010000000321f75f3139a013f50f315b23b0c9a2b6eac31e2bec98e5891c924664889942260000000049483045022100cb2c6b346a978ab8c61b18b5e9397755cbd17d6eb2fe0083ef32e067fa6c785a02206ce44e613f31d9a6b0517e46f3db1576e9812cc98d159bfdaf759a5014081b5c01ffffffff79cda0945903627c3da1f85fc95d0b8ee3e76ae0cfdc9a65d09744b1f8fc85430000000049483045022047957cdd957cfd0becd642f6b84d82f49b6cb4c51a91f49246908af7c3cfdf4a022100e96b46621f1bffcf5ea5982f88cef651e9354f5791602369bf5a82a6cd61a62501fffffffffe09f5fe3ffbf5ee97a54eb5e5069e9da6b4856ee86fc52938c2f979b0f38e82000000004847304402204165be9a4cbab8049e1af9723b96199bfd3e85f44c6b4c0177e3962686b26073022028f638da23fc003760861ad481ead4099312c60030d4cb57820ce4d33812a5ce01ffffffff01009d966b01000000434104ea1feff861b51fe3f5f8a3b12d0f4712db80e919548a80839fc47c6a21e66d957e9c5d8cd108c7a2d2324bad71f9904ac0ae7336507d785b17a2c115e427a32fac00000000").unwrap();
@@ -638,13 +921,15 @@ mod tests {
         assert_eq!(real_decode.header.bits, 486604799);
         assert_eq!(real_decode.header.nonce, 2067413810);
         assert_eq!(real_decode.header.work(), work);
-        assert_eq!(real_decode.header.validate_pow(&real_decode.header.target()).unwrap(), real_decode.block_hash());
+        assert_eq!(real_decode.header.validate_pow(real_decode.header.target()).unwrap(), real_decode.block_hash());
         assert_eq!(real_decode.header.difficulty(Network::Bitcoin), 1);
+        assert_eq!(real_decode.header.difficulty_float(), 1.0);
         // [test] TODO: check the transaction data
 
         assert_eq!(real_decode.size(), some_block.len());
+        assert_eq!(real_decode.consensus_size(), some_block.len());
         assert_eq!(real_decode.strippedsize(), some_block.len());
-        assert_eq!(real_decode.weight(), some_block.len() * 4);
+        assert_eq!(real_decode.weight(), Weight::from_wu(some_block.len() as u64 * 4));
 
         // should be also ok for a non-witness block as commitment is optional in that case
         assert!(real_decode.check_witness_commitment());
@@ -654,6 +939,7 @@ mod tests {
 
     // Check testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
     #[test]
+    #[allow(deprecated)]
     fn segwit_block_test() {
         let segwit_block = include_bytes!("../../test_data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw").to_vec();
 
@@ -673,19 +959,55 @@ mod tests {
         assert_eq!(real_decode.header.bits, 0x1a06d450);
         assert_eq!(real_decode.header.nonce, 1879759182);
         assert_eq!(real_decode.header.work(), work);
-        assert_eq!(real_decode.header.validate_pow(&real_decode.header.target()).unwrap(), real_decode.block_hash());
+        assert_eq!(real_decode.header.validate_pow(real_decode.header.target()).unwrap(), real_decode.block_hash());
         assert_eq!(real_decode.header.difficulty(Network::Testnet), 2456598);
+        assert_eq!(real_decode.header.difficulty_float() as u64, 2456598);
         // [test] TODO: check the transaction data
 
         assert_eq!(real_decode.size(), segwit_block.len());
+        assert_eq!(real_decode.consensus_size(), segwit_block.len());
         assert_eq!(real_decode.strippedsize(), 4283);
-        assert_eq!(real_decode.weight(), 17168);
+        assert_eq!(real_decode.weight(), Weight::from_wu(17168));
 
         assert!(real_decode.check_witness_commitment());
 
         assert_eq!(serialize(&real_decode), segwit_block);
     }
 
+    #[test]
+    fn witness_commitment_test() {
+        let segwit_block = include_bytes!("../../test_data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw").to_vec();
+        let block: Block = deserialize(&segwit_block).unwrap();
+
+        let commitment = block.witness_commitment().unwrap();
+        let witness_root = block.witness_root().unwrap();
+        assert_eq!(Block::compute_witness_commitment(&witness_root, &[0u8; 32]), commitment);
+        let script = Block::witness_commitment_script(&commitment);
+        assert!(block.txdata[0].output.iter().any(|o| o.script_pubkey == script));
+
+        // the witness reserved value is required when committing
+        let mut no_reserved_value = block.clone();
+        no_reserved_value.txdata[0].input[0].witness.clear();
+        assert!(!no_reserved_value.check_witness_commitment());
+
+        // a stale commitment is invalid even once no transaction has witness data
+        let mut stripped = block.clone();
+        for tx in stripped.txdata.iter_mut() {
+            for input in tx.input.iter_mut() {
+                input.witness.clear();
+            }
+        }
+        assert!(!stripped.check_witness_commitment());
+        stripped.txdata[0].output.retain(|o| o.script_pubkey != script);
+        assert_eq!(stripped.witness_commitment(), None);
+        assert!(stripped.check_witness_commitment());
+
+        // witness data without any commitment is not allowed
+        let mut uncommitted = block;
+        uncommitted.txdata[0].output.retain(|o| o.script_pubkey != script);
+        assert!(!uncommitted.check_witness_commitment());
+    }
+
     #[test]
     fn block_version_test() {
         let block = Vec::from_hex("ffffff7f0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
@@ -705,10 +1027,10 @@ mod tests {
     fn validate_pow_test() {
         let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
         let some_header: BlockHeader = deserialize(&some_header).expect("Can't deserialize correct block header");
-        assert_eq!(some_header.validate_pow(&some_header.target()).unwrap(), some_header.block_hash());
+        assert_eq!(some_header.validate_pow(some_header.target()).unwrap(), some_header.block_hash());
 
         // test with zero target
-        match some_header.validate_pow(&Uint256::default()) {
+        match some_header.validate_pow(Target::ZERO) {
             Err(BlockBadTarget) => (),
             _ => assert!(false)
         }
@@ -716,19 +1038,159 @@ mod tests {
         // test with modified header
         let mut invalid_header: BlockHeader = some_header.clone();
         invalid_header.version = invalid_header.version + 1;
-        match invalid_header.validate_pow(&invalid_header.target()) {
+        match invalid_header.validate_pow(invalid_header.target()) {
             Err(BlockBadProofOfWork) => (),
             _ => assert!(false)
         }
     }
 
     #[test]
+    fn extension_payload_test() {
+        let raw_block = include_bytes!("../../test_data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw").to_vec();
+        let block = Block::consensus_decode_with_extension(&mut &raw_block[..]).unwrap();
+        assert_eq!(block.extension, None);
+        assert_eq!(block, deserialize(&raw_block).unwrap());
+
+        let mut extended = raw_block.clone();
+        extended.extend_from_slice(&[0x01, 0xde, 0xad, 0xbe, 0xef]);
+        assert!(deserialize::<Block>(&extended).is_err());
+        let extended_block = Block::consensus_decode_with_extension(&mut &extended[..]).unwrap();
+        assert_eq!(extended_block.extension, Some(ExtensionPayload::new(vec![0x01, 0xde, 0xad, 0xbe, 0xef])));
+        assert_eq!(extended_block.txdata, block.txdata);
+        assert_eq!(serialize(&extended_block), extended);
+        assert_eq!(extended_block.block_hash(), block.block_hash());
+        assert_eq!(extended_block.size(), extended.len());
+        assert_eq!(extended_block.consensus_size(), extended.len());
+        assert_eq!(extended_block.strippedsize(), block.strippedsize() + 5);
+        assert_eq!(extended_block.weight(), block.weight() + Weight::from_wu(4 * 5));
+    }
+
+    #[test]
+    fn consensus_size_without_inputs_test() {
+        let mut block = genesis_block(Network::Bitcoin);
+        block.txdata.push(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut { value: 0, script_pubkey: Script::new() }],
+        });
+        assert_eq!(block.consensus_size(), serialize(&block).len());
+        assert_eq!(block.size() + 2, serialize(&block).len());
+
+        block.extension = Some(ExtensionPayload::new(vec![0xde, 0xad]));
+        assert_eq!(block.consensus_size(), serialize(&block).len());
+    }
+
+    #[test]
+    fn pow_hash_test() {
+        let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
+        let some_header: BlockHeader = deserialize(&some_header).unwrap();
+        let pow_hash = some_header.pow_hash(&PowAlgorithm::Sha256d).unwrap();
+        assert_eq!(pow_hash.as_hash(), some_header.block_hash().as_hash());
+        assert_eq!(some_header.validate_pow_with(some_header.target(), &PowAlgorithm::Sha256d).unwrap(), pow_hash);
+        match some_header.validate_pow_with(Target::ZERO, &PowAlgorithm::Sha256d) {
+            Err(BlockBadTarget) => (),
+            _ => panic!("the target must match the header bits"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "scrypt-pow")]
+    fn scrypt_pow_test() {
+        // Litecoin genesis block
+        let header = BlockHeader {
+            version: Version::ONE,
+            prev_blockhash: Default::default(),
+            merkle_root: "97ddfbbae6be97fd6cdf3e7ca13232a3afff2353e29badfab7f73011edd4ced9".parse().unwrap(),
+            time: 1317972665,
+            bits: 0x1e0ffff0,
+            nonce: 2084524493,
+            aux_data: None,
+        };
+        assert_eq!(header.block_hash().to_string(), "12a765e31ffd4059bada1e25190f6e98c99d9714d334efa41a195a7e7e04bfe2");
+        // the block hash doesn't meet the target, only the scrypt hash does
+        match header.validate_pow(header.target()) {
+            Err(BlockBadProofOfWork) => (),
+            _ => panic!("the block hash doesn't meet the target"),
+        }
+        assert!(header.validate_pow_with(header.target(), &PowAlgorithm::Scrypt).is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "scrypt-pow"))]
+    fn scrypt_pow_unsupported_test() {
+        let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
+        let some_header: BlockHeader = deserialize(&some_header).unwrap();
+        assert_eq!(some_header.pow_hash(&PowAlgorithm::Scrypt), None);
+        match some_header.validate_pow_with(some_header.target(), &PowAlgorithm::Scrypt) {
+            Err(UnsupportedPowAlgorithm) => (),
+            _ => panic!("scrypt hashing requires the scrypt-pow feature"),
+        }
+    }
+
+    #[test]
+    fn median_time_past_test() {
+        let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
+        let some_header: BlockHeader = deserialize(&some_header).unwrap();
+        let with_time = |time: u32| BlockHeader { time, ..some_header.clone() };
+
+        assert_eq!(median_time_past(&[]), None);
+        assert_eq!(median_time_past(&[with_time(10)]), Some(10));
+        assert_eq!(median_time_past(&[with_time(10), with_time(30), with_time(20)]), Some(20));
+        // only the last 11 headers count
+        let mut headers: Vec<_> = (0..11).map(|i| with_time(100 + i)).collect();
+        assert_eq!(median_time_past(&headers), Some(105));
+        headers.insert(0, with_time(1000));
+        assert_eq!(median_time_past(&headers), Some(105));
+        headers.push(with_time(200));
+        assert_eq!(median_time_past(&headers), Some(106));
+
+        let header = with_time(1_000_000);
+        assert!(header.is_timestamp_valid(999_999, 1_000_000));
+        assert!(!header.is_timestamp_valid(1_000_000, 1_000_000));
+        assert!(header.is_timestamp_valid(0, 1_000_000 - 7200));
+        assert!(!header.is_timestamp_valid(0, 1_000_000 - 7201));
+        assert!(with_time(u32::max_value()).is_timestamp_valid(0, u32::max_value() - 1));
+    }
+
+    #[test]
+    fn mine_block_test() {
+        let genesis = genesis_block(Network::Regtest);
+        let spk = Script::new_p2sh(&Default::default());
+        let block = mine_block(&genesis.header, 1, vec![], spk.clone());
+        assert_eq!(block.header.prev_blockhash, genesis.block_hash());
+        assert_eq!(block.header.validate_pow(genesis.header.target()).unwrap(), block.block_hash());
+        assert!(block.check_merkle_root());
+        assert!(block.check_witness_commitment());
+        assert_eq!(block.bip34_block_height(), Ok(1));
+        assert_eq!(block.txdata[0].output[0].value, 50 * 100_000_000);
+        assert_eq!(block.txdata[0].output[0].script_pubkey, spk);
+
+        let mut tx = genesis.txdata[0].clone();
+        tx.input[0].witness.push(vec![1]);
+        let next = mine_block(&block.header, 150, vec![tx.clone()], spk);
+        assert_eq!(next.txdata[1], tx);
+        assert!(next.check_witness_commitment());
+        assert_eq!(next.bip34_block_height(), Ok(150));
+        assert_eq!(next.txdata[0].output[0].value, 25 * 100_000_000);
+
+        // the time is bumped once the nonces are exhausted
+        let mut header = next.header.clone();
+        header.nonce = u32::max_value();
+        let target = header.target();
+        let hash = header.solve(target, true).unwrap();
+        assert!(target.is_met_by(hash));
+        assert!(header.time > next.header.time || header.nonce == u32::max_value());
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn compact_roundrtip_test() {
         let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
 
         let header: BlockHeader = deserialize(&some_header).expect("Can't deserialize correct block header");
 
-        assert_eq!(header.bits, BlockHeader::compact_target_from_u256(&header.target()));
+        assert_eq!(header.bits, BlockHeader::compact_target_from_u256(&header.target().to_uint256()));
     }
 }
 
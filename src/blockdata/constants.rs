@@ -26,11 +26,12 @@ use core::default::Default;
 use hashes::hex::{HexIterator, Error as HexError};
 use hashes::sha256d;
 use blockdata::opcodes;
-use blockdata::script;
+use blockdata::script::{self, Script};
 use blockdata::transaction::{OutPoint, Transaction, TxOut, TxIn};
 use blockdata::block::{Block, BlockHeader, Version};
 use blockdata::witness::Witness;
 use network::constants::Network;
+use consensus::Params;
 use util::uint::Uint256;
 
 /// The maximum allowable sequence number
@@ -43,24 +44,44 @@ pub const TARGET_BLOCK_SPACING: u32 = 600;
 pub const DIFFCHANGE_INTERVAL: u32 = 2016;
 /// How much time on average should occur between diffchanges
 pub const DIFFCHANGE_TIMESPAN: u32 = 14 * 24 * 3600;
+/// How many of the previous blocks the median time past is computed over
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// How far, in seconds, a block timestamp may be ahead of the network-adjusted time
+pub const MAX_FUTURE_BLOCK_TIME: u32 = 2 * 60 * 60;
 /// The maximum allowed weight for a block, see BIP 141 (network rule)
 pub const MAX_BLOCK_WEIGHT: u32 = 4_000_000;
+/// The maximum allowed size of a serialized block, including witness data
+pub const MAX_BLOCK_SERIALIZED_SIZE: u32 = 4_000_000;
 /// The minimum transaction weight for a valid serialized transaction
 pub const MIN_TRANSACTION_WEIGHT: u32 = 4 * 60;
 /// The factor that non-witness serialization data is multiplied by during weight calculation
 pub const WITNESS_SCALE_FACTOR: usize = 4;
 /// The maximum allowed number of signature check operations in a block
 pub const MAX_BLOCK_SIGOPS_COST: i64 = 80_000;
-/// Mainnet (bitcoin) pubkey address prefix.
-pub const PUBKEY_ADDRESS_PREFIX_MAIN: u8 = 0x42;
-/// Mainnet (bitcoin) script address prefix.
-pub const SCRIPT_ADDRESS_PREFIX_MAIN: u8 = 0x41;
+/// Mainnet (texitcoin) pubkey address prefix.
+pub const PUBKEY_ADDRESS_PREFIX_MAIN: u8 = 0x42; // 66, "T..."
+/// Mainnet (texitcoin) script address prefix.
+pub const SCRIPT_ADDRESS_PREFIX_MAIN: u8 = 0x41; // 65
 /// Test (tesnet, signet, regtest) pubkey address prefix.
 pub const PUBKEY_ADDRESS_PREFIX_TEST: u8 = 111; // 0x6f
 /// Test (tesnet, signet, regtest) script address prefix.
 pub const SCRIPT_ADDRESS_PREFIX_TEST: u8 = 196; // 0xc4
+/// Mainnet (texitcoin) WIF private key prefix.
+pub const SECRET_KEY_PREFIX_MAIN: u8 = 0xc1; // 193
+/// Test (tesnet, signet, regtest) WIF private key prefix.
+pub const SECRET_KEY_PREFIX_TEST: u8 = 239; // 0xef
+/// Mainnet (texitcoin) bech32 human-readable part.
+pub const BECH32_HRP_MAIN: &str = "txc";
+/// Test (testnet, signet) bech32 human-readable part.
+pub const BECH32_HRP_TEST: &str = "tb";
+/// Regtest bech32 human-readable part.
+pub const BECH32_HRP_REGTEST: &str = "bcrt";
 /// The maximum allowed script size.
 pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// The maximum size of a script that can be spent, larger outputs are unspendable.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// How many blocks must be built on top of a coinbase before its outputs can be spent.
+pub const COINBASE_MATURITY: u32 = 100;
 /// How may blocks between halvings.
 pub const SUBSIDY_HALVING_INTERVAL: u32 = 695_662;
 
@@ -76,8 +97,23 @@ pub fn max_money(_: Network) -> u64 {
     21_000_000 * COIN_VALUE
 }
 
-/// Constructs and returns the coinbase (and only) transaction of the Bitcoin genesis block
-fn bitcoin_genesis_tx() -> Transaction {
+/// The message committed to in the coinbase of the genesis block of every network.
+const GENESIS_MESSAGE: &[u8] = b"The Times 03/Jan/2009 Chancellor on brink of second bailout for banks";
+
+/// Returns the script paid to by the coinbase of the genesis block of every network.
+fn genesis_script_pubkey() -> Script {
+    let script_bytes: Result<Vec<u8>, HexError> =
+        HexIterator::new("04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f").unwrap()
+            .collect();
+    script::Builder::new()
+        .push_slice(script_bytes.unwrap().as_slice())
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Constructs and returns the coinbase (and only) transaction of a genesis block, committing to
+/// `message` in its script sig and paying `reward` to `script_pubkey`.
+fn genesis_tx(message: &[u8], script_pubkey: Script, reward: u64) -> Transaction {
     // Base
     let mut ret = Transaction {
         version: 1,
@@ -89,7 +125,7 @@ fn bitcoin_genesis_tx() -> Transaction {
     // Inputs
     let in_script = script::Builder::new().push_scriptint(486604799)
                                           .push_scriptint(4)
-                                          .push_slice(b"The Times 03/Jan/2009 Chancellor on brink of second bailout for banks")
+                                          .push_slice(message)
                                           .into_script();
     ret.input.push(TxIn {
         previous_output: OutPoint::null(),
@@ -99,87 +135,59 @@ fn bitcoin_genesis_tx() -> Transaction {
     });
 
     // Outputs
-    let script_bytes: Result<Vec<u8>, HexError> =
-        HexIterator::new("04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f").unwrap()
-            .collect();
-    let out_script = script::Builder::new()
-        .push_slice(script_bytes.unwrap().as_slice())
-        .push_opcode(opcodes::all::OP_CHECKSIG)
-        .into_script();
     ret.output.push(TxOut {
-        value: 50 * COIN_VALUE,
-        script_pubkey: out_script
+        value: reward,
+        script_pubkey,
     });
 
     // end
     ret
 }
 
-/// Constructs and returns the genesis block
-pub fn genesis_block(network: Network) -> Block {
-    let txdata = vec![bitcoin_genesis_tx()];
+/// Constructs a genesis block whose coinbase commits to `message` and pays `reward` satoshis to
+/// `script_pubkey`.
+///
+/// This mirrors Bitcoin Core's `CreateGenesisBlock` and is useful for bootstrapping custom
+/// networks; the header is not mined, so `nonce` must already satisfy `bits`.
+pub fn create_genesis_block(
+    message: &[u8],
+    script_pubkey: Script,
+    reward: u64,
+    version: Version,
+    time: u32,
+    bits: u32,
+    nonce: u32,
+) -> Block {
+    let txdata = vec![genesis_tx(message, script_pubkey, reward)];
     let hash: sha256d::Hash = txdata[0].txid().into();
-    let merkle_root = hash.into();
-    match network {
-        Network::Bitcoin => {
-            Block {
-                header: BlockHeader {
-                    version: Version::ONE,
-                    prev_blockhash: Default::default(),
-                    merkle_root,
-                    time: 1706236287,
-                    bits: 0x1e0ffff0,
-                    nonce: 481803,
-                    aux_data: None,
-                },
-                txdata,
-            }
-        }
-        Network::Testnet => {
-            Block {
-                header: BlockHeader {
-                    version: Version::ONE,
-                    prev_blockhash: Default::default(),
-                    merkle_root,
-                    time: 1296688602,
-                    bits: 0x1d00ffff,
-                    nonce: 414098458,
-                    aux_data: None
-                },
-                txdata,
-            }
-        }
-        Network::Signet => {
-            Block {
-                header: BlockHeader {
-                    version: Version::ONE,
-                    prev_blockhash: Default::default(),
-                    merkle_root,
-                    time: 1598918400,
-                    bits: 0x1e0377ae,
-                    nonce: 52613770,
-                    aux_data: None
-                },
-                txdata,
-            }
-        }
-        Network::Regtest => {
-            Block {
-                header: BlockHeader {
-                    version: Version::ONE,
-                    prev_blockhash: Default::default(),
-                    merkle_root,
-                    time: 1296688602,
-                    bits: 0x207fffff,
-                    nonce: 2,
-                    aux_data: None
-                },
-                txdata,
-            }
-        }
+    Block {
+        header: BlockHeader {
+            version,
+            prev_blockhash: Default::default(),
+            merkle_root: hash.into(),
+            time,
+            bits,
+            nonce,
+            aux_data: None,
+        },
+        txdata,
+        extension: None,
     }
 }
 
+/// Constructs and returns the genesis block of the network `params` belong to.
+///
+/// Accepts either a [`Network`] or a [`Params`].
+pub fn genesis_block<P: AsRef<Params>>(params: P) -> Block {
+    let (time, bits, nonce) = match params.as_ref().network {
+        Network::Bitcoin => (1706236287, 0x1e0ffff0, 481803),
+        Network::Testnet => (1296688602, 0x1d00ffff, 414098458),
+        Network::Signet => (1598918400, 0x1e0377ae, 52613770),
+        Network::Regtest => (1296688602, 0x207fffff, 2),
+    };
+    create_genesis_block(GENESIS_MESSAGE, genesis_script_pubkey(), 50 * COIN_VALUE, Version::ONE, time, bits, nonce)
+}
+
 #[cfg(test)]
 mod test {
     use core::default::Default;
@@ -187,12 +195,15 @@ mod test {
 
     use network::constants::Network;
     use consensus::encode::serialize;
-    use blockdata::constants::{genesis_block, bitcoin_genesis_tx};
+    use blockdata::constants::{genesis_block, genesis_tx, genesis_script_pubkey, create_genesis_block, GENESIS_MESSAGE};
+    use blockdata::block::Version;
+    use blockdata::script::Script;
+    use consensus::Params;
     use blockdata::constants::{MAX_SEQUENCE, COIN_VALUE};
 
     #[test]
     fn bitcoin_genesis_first_transaction() {
-        let gen = bitcoin_genesis_tx();
+        let gen = genesis_tx(GENESIS_MESSAGE, genesis_script_pubkey(), 50 * COIN_VALUE);
 
         assert_eq!(gen.version, 1);
         assert_eq!(gen.input.len(), 1);
@@ -254,5 +265,23 @@ mod test {
         assert_eq!(format!("{:x}", gen.header.block_hash()),
                    "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6".to_string());
     }
-}
 
+    #[test]
+    fn genesis_block_from_params() {
+        assert_eq!(genesis_block(&Params::REGTEST), genesis_block(Network::Regtest));
+        assert_eq!(genesis_block(Params::new(Network::Signet)), genesis_block(Network::Signet));
+    }
+
+    #[test]
+    fn custom_genesis_block() {
+        let gen = create_genesis_block(b"hello", Script::new(), 21 * COIN_VALUE, Version::ONE, 1700000000, 0x207fffff, 7);
+        assert_eq!(gen.txdata.len(), 1);
+        assert_eq!(gen.txdata[0].output[0].value, 21 * COIN_VALUE);
+        assert_eq!(gen.txdata[0].output[0].script_pubkey, Script::new());
+        assert_eq!(serialize(&gen.txdata[0].input[0].script_sig), Vec::from_hex("0d04ffff001d01040568656c6c6f").unwrap());
+        assert_eq!(gen.header.time, 1700000000);
+        assert_eq!(gen.header.bits, 0x207fffff);
+        assert_eq!(gen.header.nonce, 7);
+        assert!(gen.check_merkle_root());
+    }
+}
@@ -0,0 +1,286 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Human-readable dumps.
+//!
+//! This module formats transactions, blocks and block headers as multi-line
+//! dumps, with decoded scripts, amounts in BTC and the meaning of sequence
+//! numbers and lock times spelt out. The `Debug` output of these types stays
+//! unchanged, dumps are opt-in through [`dump`]:
+//!
+//! ```rust
+//! use bitcoin::blockdata::constants::genesis_block;
+//! use bitcoin::blockdata::fmt::dump;
+//! use bitcoin::network::constants::Network;
+//!
+//! let genesis = genesis_block(Network::Bitcoin);
+//! println!("{}", dump(&genesis.txdata[0]));
+//! ```
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hashes::Hash;
+use hashes::hex::ToHex;
+use hash_types::BlockHash;
+use consensus::encode;
+use blockdata::block::{Block, BlockHeader};
+use blockdata::script::Script;
+use blockdata::transaction::{Transaction, TxIn};
+use util::amount::{Amount, Denomination};
+
+/// Lock times below this value are block heights, others are UNIX timestamps.
+const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+/// Sequence flag disabling the BIP68 relative lock time.
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+/// Sequence flag making the BIP68 relative lock time count units of 512 seconds.
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+/// Mask of the BIP68 relative lock time value.
+const SEQUENCE_MASK: u32 = 0xffff;
+
+/// A value displayed as a human-readable multi-line dump, see [`dump`].
+#[derive(Clone, Copy, Debug)]
+pub struct Dump<'a, T: 'a>(&'a T);
+
+/// Wraps a [`Transaction`], [`Block`] or [`BlockHeader`] so that it displays as a
+/// human-readable multi-line dump.
+pub fn dump<'a, T>(value: &'a T) -> Dump<'a, T> {
+    Dump(value)
+}
+
+impl<'a> fmt::Display for Dump<'a, Transaction> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_transaction(f, self.0, "")
+    }
+}
+
+impl<'a> fmt::Display for Dump<'a, BlockHeader> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_header(f, self.0, "")
+    }
+}
+
+impl<'a> fmt::Display for Dump<'a, Block> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let block = self.0;
+        fmt_header(f, &block.header, "")?;
+        writeln!(f, "  size: {} bytes, weight: {}", block.size(), block.weight())?;
+        if let Some(ref extension) = block.extension {
+            writeln!(f, "  extension: {} bytes", extension.as_bytes().len())?;
+        }
+        writeln!(f, "  transactions: {}", block.txdata.len())?;
+        for (i, tx) in block.txdata.iter().enumerate() {
+            writeln!(f, "  #{}", i)?;
+            fmt_transaction(f, tx, "    ")?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_header(f: &mut fmt::Formatter, header: &BlockHeader, pad: &str) -> fmt::Result {
+    writeln!(f, "{}block {}", pad, header.block_hash())?;
+    writeln!(f, "{}  version: {:#010x}", pad, header.version.to_consensus())?;
+    writeln!(f, "{}  previous block: {}", pad, header.prev_blockhash)?;
+    writeln!(f, "{}  merkle root: {}", pad, header.merkle_root)?;
+    writeln!(f, "{}  time: {}", pad, header.time)?;
+    writeln!(f, "{}  bits: {:#010x}", pad, header.bits)?;
+    writeln!(f, "{}  nonce: {}", pad, header.nonce)?;
+    if let Some(ref aux) = header.aux_data {
+        writeln!(f, "{}  auxpow parent block: {}", pad, BlockHash::hash(&encode::serialize(&aux.parent_block)))?;
+    }
+    Ok(())
+}
+
+fn fmt_transaction(f: &mut fmt::Formatter, tx: &Transaction, pad: &str) -> fmt::Result {
+    writeln!(f, "{}transaction {}", pad, tx.txid())?;
+    if tx.input.iter().any(|input| !input.witness.is_empty()) {
+        writeln!(f, "{}  wtxid: {}", pad, tx.wtxid())?;
+    }
+    writeln!(f, "{}  version: {}", pad, tx.version)?;
+    writeln!(f, "{}  size: {} bytes, vsize: {} vB, weight: {}", pad, tx.size(), tx.vsize(), tx.weight())?;
+    write!(f, "{}  lock time: ", pad)?;
+    fmt_lock_time(f, tx)?;
+    writeln!(f)?;
+
+    for (i, input) in tx.input.iter().enumerate() {
+        writeln!(f, "{}  input #{}", pad, i)?;
+        if tx.is_coinbase() {
+            writeln!(f, "{}    coinbase: {}", pad, input.script_sig.as_bytes().to_hex())?;
+        } else {
+            writeln!(f, "{}    outpoint: {}", pad, input.previous_output)?;
+            write!(f, "{}    script sig: ", pad)?;
+            fmt_script(f, &input.script_sig)?;
+            writeln!(f)?;
+        }
+        if !input.witness.is_empty() {
+            writeln!(f, "{}    witness:", pad)?;
+            for element in input.witness.iter() {
+                writeln!(f, "{}      {}", pad, element.to_hex())?;
+            }
+        }
+        write!(f, "{}    sequence: {:#010x} ", pad, input.sequence)?;
+        fmt_sequence(f, tx, input)?;
+        writeln!(f)?;
+    }
+
+    let mut total = Some(0u64);
+    for (i, output) in tx.output.iter().enumerate() {
+        total = total.and_then(|total| total.checked_add(output.value));
+        write!(f, "{}  output #{}: ", pad, i)?;
+        fmt_amount(f, output.value)?;
+        writeln!(f)?;
+        write!(f, "{}    script pubkey ({}): ", pad, script_type(&output.script_pubkey))?;
+        fmt_script(f, &output.script_pubkey)?;
+        writeln!(f)?;
+    }
+    write!(f, "{}  total output: ", pad)?;
+    match total {
+        Some(total) => fmt_amount(f, total)?,
+        None => f.write_str("overflow")?,
+    }
+    writeln!(f)
+}
+
+fn fmt_amount(f: &mut fmt::Formatter, sat: u64) -> fmt::Result {
+    Amount::from_sat(sat).fmt_value_in(f, Denomination::Bitcoin)?;
+    write!(f, " {}", Denomination::Bitcoin)
+}
+
+fn fmt_script(f: &mut fmt::Formatter, script: &Script) -> fmt::Result {
+    if script.is_empty() {
+        f.write_str("(empty)")
+    } else {
+        script.fmt_asm(f)
+    }
+}
+
+fn fmt_lock_time(f: &mut fmt::Formatter, tx: &Transaction) -> fmt::Result {
+    if tx.lock_time == 0 {
+        return f.write_str("0 (none)");
+    }
+    if tx.lock_time < LOCK_TIME_THRESHOLD {
+        write!(f, "{} (block height)", tx.lock_time)?;
+    } else {
+        write!(f, "{} (UNIX time)", tx.lock_time)?;
+    }
+    if tx.input.iter().all(|input| input.sequence == 0xffffffff) {
+        f.write_str(", disabled by final sequences")?;
+    }
+    Ok(())
+}
+
+fn fmt_sequence(f: &mut fmt::Formatter, tx: &Transaction, input: &TxIn) -> fmt::Result {
+    match input.sequence {
+        0xffffffff => return f.write_str("(final)"),
+        0xfffffffe => return f.write_str("(lock time enabled)"),
+        _ => f.write_str("(replaceable")?,
+    }
+    if tx.version >= 2 && input.sequence & SEQUENCE_DISABLE_FLAG == 0 {
+        let value = input.sequence & SEQUENCE_MASK;
+        if input.sequence & SEQUENCE_TYPE_FLAG != 0 {
+            write!(f, ", relative lock time {} seconds", value * 512)?;
+        } else {
+            write!(f, ", relative lock time {} blocks", value)?;
+        }
+    }
+    f.write_str(")")
+}
+
+fn script_type(script: &Script) -> String {
+    let name = if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_v0_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_v0_p2wsh() {
+        "p2wsh"
+    } else if script.is_v1_p2tr() {
+        "p2tr"
+    } else if let Some(version) = script.witness_version() {
+        return format!("witness v{}", version.into_num());
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else if script.is_op_return() {
+        "op_return"
+    } else {
+        "nonstandard"
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash_types::Txid;
+    use blockdata::constants::genesis_block;
+    use blockdata::transaction::{OutPoint, TxOut};
+    use blockdata::witness::Witness;
+    use network::constants::Network;
+
+    #[test]
+    fn dump_genesis() {
+        let genesis = genesis_block(Network::Bitcoin);
+        let coinbase = format!("{}", dump(&genesis.txdata[0]));
+        assert_eq!(coinbase, "\
+transaction 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b
+  version: 1
+  size: 204 bytes, vsize: 204 vB, weight: 816 wu
+  lock time: 0 (none)
+  input #0
+    coinbase: 04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73
+    sequence: 0xffffffff (final)
+  output #0: 50.00000000 BTC
+    script pubkey (p2pk): OP_PUSHBYTES_65 04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f OP_CHECKSIG
+  total output: 50.00000000 BTC
+");
+
+        let block = format!("{}", dump(&genesis));
+        assert!(block.starts_with(&format!("block {}\n", genesis.block_hash())));
+        assert!(block.contains("\n  transactions: 1\n  #0\n    transaction 4a5e1e4b"));
+        assert!(block.ends_with("\n      total output: 50.00000000 BTC\n"));
+        assert_eq!(format!("{}", dump(&genesis.header)).lines().count(), 7);
+    }
+
+    #[test]
+    fn dump_sequences() {
+        let input = |sequence| TxIn {
+            previous_output: OutPoint::new(Txid::from_inner([1; 32]), 0),
+            script_sig: Script::new(),
+            sequence,
+            witness: Witness::from_vec(vec![vec![0xab; 2]]),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 600_000,
+            input: vec![input(0xfffffffe), input(10), input(SEQUENCE_TYPE_FLAG | 3), input(SEQUENCE_DISABLE_FLAG)],
+            output: vec![TxOut { value: 1, script_pubkey: Script::new() }],
+        };
+        let dumped = format!("{}", dump(&tx));
+        assert!(dumped.contains("\n  lock time: 600000 (block height)\n"));
+        assert!(dumped.contains("\n    witness:\n      abab\n"));
+        assert!(dumped.contains("\n    sequence: 0xfffffffe (lock time enabled)\n"));
+        assert!(dumped.contains("\n    sequence: 0x0000000a (replaceable, relative lock time 10 blocks)\n"));
+        assert!(dumped.contains("\n    sequence: 0x00400003 (replaceable, relative lock time 1536 seconds)\n"));
+        assert!(dumped.contains("\n    sequence: 0x80000000 (replaceable)\n"));
+        assert!(dumped.contains("\n    script sig: (empty)\n"));
+        assert!(dumped.contains("\n  output #0: 0.00000001 BTC\n    script pubkey (nonstandard): (empty)\n"));
+
+        let mut tx = tx;
+        tx.output.push(TxOut { value: u64::max_value(), script_pubkey: Script::new() });
+        assert!(format!("{}", dump(&tx)).ends_with("\n  total output: overflow\n"));
+    }
+}
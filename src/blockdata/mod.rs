@@ -23,5 +23,6 @@ pub mod opcodes;
 pub mod script;
 pub mod transaction;
 pub mod block;
+pub mod fmt;
 pub mod witness;
 
@@ -30,10 +30,12 @@ use core::{fmt, default::Default};
 use core::ops::Index;
 
 #[cfg(feature = "serde")] use serde;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
-use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash};
+use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash, ElectrumScriptHash};
 use blockdata::opcodes;
 use consensus::{encode, Decodable, Encodable};
+use consensus::encode::DecodeContext;
 use hashes::{Hash, hex};
 use policy::DUST_RELAY_TX_FEE;
 #[cfg(feature="bitcoinconsensus")] use bitcoinconsensus;
@@ -68,6 +70,14 @@ impl AsRef<[u8]> for Script {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Script {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Script::from(Vec::<u8>::arbitrary(u)?))
+    }
+}
+
 impl fmt::Debug for Script {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str("Script(")?;
@@ -267,6 +277,21 @@ pub fn read_scriptint(v: &[u8]) -> Result<i64, Error> {
     Ok(ret)
 }
 
+/// Helper to decode an integer in script format, additionally requiring that
+/// it is minimally encoded.
+///
+/// This matches the behaviour of Bitcoin Core's `CScriptNum` when
+/// `fRequireMinimal` is set: the most significant byte must not be a bare
+/// sign byte unless it is needed to hold the sign bit of the byte before it.
+pub fn read_scriptint_minimal(v: &[u8]) -> Result<i64, Error> {
+    if let Some((last, rest)) = v.split_last() {
+        if last & 0x7f == 0 && rest.last().map_or(true, |b| b & 0x80 == 0) {
+            return Err(Error::NonMinimalPush);
+        }
+    }
+    read_scriptint(v)
+}
+
 /// This is like "`read_scriptint` then map 0 to false and everything
 /// else as true", except that the overflow rules don't apply.
 #[inline]
@@ -407,6 +432,11 @@ impl Script {
         WScriptHash::hash(self.as_bytes())
     }
 
+    /// Returns the hash identifying the script to Electrum servers.
+    pub fn electrum_script_hash(&self) -> ElectrumScriptHash {
+        ElectrumScriptHash::hash(self.as_bytes())
+    }
+
     /// Returns the length in bytes of the script.
     pub fn len(&self) -> usize { self.0.len() }
 
@@ -1071,6 +1101,11 @@ impl Decodable for Script {
     fn consensus_decode<D: io::Read>(d: D) -> Result<Self, encode::Error> {
         Ok(Script(Decodable::consensus_decode(d)?))
     }
+
+    #[inline]
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, encode::Error> {
+        Ok(Script(Decodable::consensus_decode_with_context(r, context)?))
+    }
 }
 
 #[cfg(test)]
@@ -1273,11 +1308,29 @@ mod test {
         assert!(read_scriptint(&build_scriptint(-(1 << 31))).is_err());
     }
 
+    #[test]
+    fn scriptint_minimal() {
+        for &i in [1, 127, 128, 255, 256, 100000, (1 << 31) - 1].iter() {
+            assert_eq!(Ok(i), read_scriptint_minimal(&build_scriptint(i)));
+            assert_eq!(Ok(-i), read_scriptint_minimal(&build_scriptint(-i)));
+        }
+        assert_eq!(Ok(0), read_scriptint_minimal(&[]));
+        assert_eq!(Err(Error::NonMinimalPush), read_scriptint_minimal(&[0x00]));
+        assert_eq!(Err(Error::NonMinimalPush), read_scriptint_minimal(&[0x80]));
+        assert_eq!(Err(Error::NonMinimalPush), read_scriptint_minimal(&[0x01, 0x00]));
+        assert_eq!(Err(Error::NonMinimalPush), read_scriptint_minimal(&[0x01, 0x80]));
+        assert_eq!(Ok(-0xff), read_scriptint_minimal(&[0xff, 0x80]));
+    }
+
     #[test]
     fn script_hashes() {
         let script = hex_script!("410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5ac");
         assert_eq!(script.script_hash().to_hex(), "8292bcfbef1884f73c813dfe9c82fd7e814291ea");
         assert_eq!(script.wscript_hash().to_hex(), "3e1525eb183ad4f9b3c5fa3175bdca2a52e947b135bbb90383bf9f6408e2c324");
+
+        // example from the Electrum protocol documentation
+        let script = hex_script!("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(script.electrum_script_hash().to_hex(), "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161");
     }
 
     #[test]
@@ -26,18 +26,24 @@
 use prelude::*;
 
 use io;
-use core::{fmt, str, default::Default};
+use core::{cmp, fmt, str, default::Default};
 #[cfg(feature = "std")] use std::error;
 
 use hashes::{self, Hash, sha256d};
 use hashes::hex::FromHex;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 use util::endian;
+use util::hash::Sha256dEngine;
+use util::amount::SignedAmount;
+use util::fee_rate::FeeRate;
+use util::weight::Weight;
 use blockdata::constants::WITNESS_SCALE_FACTOR;
 #[cfg(feature="bitcoinconsensus")] use blockdata::script;
 use blockdata::script::Script;
 use blockdata::witness::Witness;
 use consensus::{encode, Decodable, Encodable};
+use consensus::encode::DecodeContext;
 use hash_types::{Sighash, Txid, Wtxid};
 use VarInt;
 
@@ -52,6 +58,10 @@ const UINT256_ONE: [u8; 32] = [
     0, 0, 0, 0, 0, 0, 0, 0
 ];
 
+/// The default maximum fee rate, in satoshis per 1000 virtual bytes, of
+/// transactions broadcast by Bitcoin Core (`-maxfeerate`, 0.10 BTC/kvB).
+pub const DEFAULT_MAX_FEE_RATE: u64 = 10_000_000;
+
 /// A reference to a transaction output.
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub struct OutPoint {
@@ -235,6 +245,113 @@ impl Default for TxOut {
     }
 }
 
+impl TxOut {
+    /// Returns the value of the output minus the fee of spending it at `fee_rate`, with an input
+    /// weighing as predicted by `input_weight_prediction`.
+    ///
+    /// The result is negative for outputs costing more to spend than they are worth.
+    pub fn effective_value(&self, fee_rate: FeeRate, input_weight_prediction: InputWeightPrediction) -> SignedAmount {
+        let max = i64::max_value() as u64;
+        let fee = fee_rate.fee_wu(input_weight_prediction.input_weight()).map_or(max, |fee| cmp::min(fee.as_sat(), max));
+        // both are positive, so the difference can't overflow
+        SignedAmount::from_sat(cmp::min(self.value, max) as i64 - fee as i64)
+    }
+}
+
+/// A prediction of the size of the script sig and witness of an input, before it is signed.
+///
+/// The constants assume low-R signatures are not enforced, so ECDSA signatures take at most
+/// 72 bytes including the sighash type.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InputWeightPrediction {
+    script_size: usize,
+    witness_size: usize,
+}
+
+impl InputWeightPrediction {
+    /// Input spending a P2WPKH output.
+    pub const P2WPKH_MAX: InputWeightPrediction = InputWeightPrediction { script_size: 1, witness_size: 108 };
+    /// Input spending a P2WPKH output nested in a P2SH output.
+    pub const NESTED_P2WPKH_MAX: InputWeightPrediction = InputWeightPrediction { script_size: 24, witness_size: 108 };
+    /// Input spending a P2PKH output with a compressed public key.
+    pub const P2PKH_COMPRESSED_MAX: InputWeightPrediction = InputWeightPrediction { script_size: 108, witness_size: 0 };
+    /// Input spending a P2PKH output with an uncompressed public key.
+    pub const P2PKH_UNCOMPRESSED_MAX: InputWeightPrediction = InputWeightPrediction { script_size: 140, witness_size: 0 };
+    /// Input spending a P2TR output along the key path, signing with the default sighash type.
+    pub const P2TR_KEY_DEFAULT_SIGHASH: InputWeightPrediction = InputWeightPrediction { script_size: 1, witness_size: 66 };
+    /// Input spending a P2TR output along the key path, signing with another sighash type.
+    pub const P2TR_KEY_NON_DEFAULT_SIGHASH: InputWeightPrediction = InputWeightPrediction { script_size: 1, witness_size: 67 };
+
+    /// Predicts the weight of an input with a script sig of `input_script_len` bytes and a
+    /// witness made of elements of `witness_element_lengths` bytes.
+    pub fn new<I: IntoIterator<Item = usize>>(input_script_len: usize, witness_element_lengths: I) -> InputWeightPrediction {
+        let mut count = 0;
+        let mut witness_size = 0;
+        for len in witness_element_lengths {
+            count += 1;
+            witness_size += VarInt(len as u64).len() + len;
+        }
+        if count > 0 {
+            witness_size += VarInt(count).len();
+        }
+        InputWeightPrediction {
+            script_size: VarInt(input_script_len as u64).len() + input_script_len,
+            witness_size,
+        }
+    }
+
+    /// Returns the weight of the script sig, with its length prefix, and of the witness.
+    pub fn weight(&self) -> Weight {
+        Weight::from_wu((self.script_size * WITNESS_SCALE_FACTOR + self.witness_size) as u64)
+    }
+
+    /// Returns the weight of the whole input: the outpoint, sequence, script sig and witness.
+    pub fn input_weight(&self) -> Weight {
+        // 32 bytes txid, 4 bytes vout and 4 bytes sequence
+        Weight::from_wu((40 * WITNESS_SCALE_FACTOR) as u64) + self.weight()
+    }
+}
+
+/// Predicts the weight of a transaction once signed, from the predicted weights of its inputs
+/// and the lengths of its output scripts.
+///
+/// The prediction matches the weight of the signed transaction as long as the inputs weigh as
+/// predicted; it is an upper bound when they are predicted with the `_MAX` constants of
+/// [`InputWeightPrediction`].
+pub fn predict_weight<I, O>(inputs: I, output_script_lens: O) -> Weight
+where
+    I: IntoIterator<Item = InputWeightPrediction>,
+    O: IntoIterator<Item = usize>,
+{
+    let mut input_count = 0;
+    let mut inputs_with_witnesses = 0;
+    let mut input_weight = 0;
+    for prediction in inputs {
+        input_count += 1;
+        if prediction.witness_size > 0 {
+            inputs_with_witnesses += 1;
+        }
+        input_weight += prediction.input_weight().to_wu() as usize;
+    }
+
+    let mut output_count = 0;
+    let mut output_size = 0;
+    for script_len in output_script_lens {
+        output_count += 1;
+        // 8 bytes value
+        output_size += 8 + VarInt(script_len as u64).len() + script_len;
+    }
+
+    // 4 bytes version and 4 bytes lock time
+    let non_input_size = 4 + VarInt(input_count as u64).len() + VarInt(output_count as u64).len() + output_size + 4;
+    let mut weight = non_input_size * WITNESS_SCALE_FACTOR + input_weight;
+    if inputs_with_witnesses > 0 {
+        // segwit marker and flag, and the empty witnesses of the other inputs
+        weight += 2 + input_count - inputs_with_witnesses;
+    }
+    Weight::from_wu(weight as u64)
+}
+
 /// A Bitcoin transaction, which describes an authenticated movement of coins.
 ///
 /// If any inputs have nonempty witnesses, the entire transaction is serialized
@@ -297,21 +414,21 @@ impl Transaction {
     /// this will give the correct txid (not including witnesses) while `wtxid`
     /// will also hash witnesses.
     pub fn txid(&self) -> Txid {
-        let mut enc = Txid::engine();
+        let mut enc = Sha256dEngine::new();
         self.version.consensus_encode(&mut enc).expect("engines don't error");
         self.input.consensus_encode(&mut enc).expect("engines don't error");
         self.output.consensus_encode(&mut enc).expect("engines don't error");
         self.lock_time.consensus_encode(&mut enc).expect("engines don't error");
-        Txid::from_engine(enc)
+        enc.finish()
     }
 
     /// Computes SegWit-version of the transaction id (wtxid). For transaction with the witness
     /// data this hash includes witness, for pre-witness transaction it is equal to the normal
     /// value returned by txid() function.
     pub fn wtxid(&self) -> Wtxid {
-        let mut enc = Wtxid::engine();
+        let mut enc = Sha256dEngine::new();
         self.consensus_encode(&mut enc).expect("engines don't error");
-        Wtxid::from_engine(enc)
+        enc.finish()
     }
 
     /// Encodes the signing data from which a signature hash for a given input index with a given
@@ -451,7 +568,7 @@ impl Transaction {
     #[inline]
     #[deprecated(since = "0.28.0", note = "Please use `transaction::weight` instead.")]
     pub fn get_weight(&self) -> usize {
-        self.weight()
+        self.weight().to_wu() as usize
     }
 
     /// Returns the "weight" of this transaction, as defined by BIP141.
@@ -460,8 +577,8 @@ impl Transaction {
     /// four. For transactions with a witness, this is the non-witness consensus-serialized size
     /// multiplied by three plus the with-witness consensus-serialized size.
     #[inline]
-    pub fn weight(&self) -> usize {
-        self.scaled_size(WITNESS_SCALE_FACTOR)
+    pub fn weight(&self) -> Weight {
+        Weight::from_wu(self.scaled_size(WITNESS_SCALE_FACTOR) as u64)
     }
 
     /// Returns the regular byte-wise consensus-serialized size of this transaction.
@@ -495,8 +612,7 @@ impl Transaction {
     /// [`policy`]: ../policy/mod.rs.html
     #[inline]
     pub fn vsize(&self) -> usize {
-        let weight = self.weight();
-        (weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR
+        self.weight().to_vbytes_ceil() as usize
     }
 
     /// Returns the size of this transaction excluding the witness data.
@@ -598,16 +714,84 @@ impl Transaction {
     }
 
     /// Is this a coin base transaction?
+    #[deprecated(since = "0.29.0", note = "Please use `Transaction::is_coinbase` instead")]
     pub fn is_coin_base(&self) -> bool {
+        self.is_coinbase()
+    }
+
+    /// Checks if this is a coinbase transaction, i.e. it has a single input
+    /// spending the null outpoint.
+    pub fn is_coinbase(&self) -> bool {
         self.input.len() == 1 && self.input[0].previous_output.is_null()
     }
 
+    /// Returns `true` if the lock time of this transaction is enforced, i.e. it
+    /// is non-zero and at least one input has a non-final sequence number.
+    pub fn is_lock_time_enabled(&self) -> bool {
+        self.lock_time != 0 && self.input.iter().any(|input| input.sequence != 0xffffffff)
+    }
+
+    /// Returns `true` if the fee paid by this transaction exceeds
+    /// [`DEFAULT_MAX_FEE_RATE`], the fee rate above which Bitcoin Core refuses
+    /// to broadcast transactions by default.
+    ///
+    /// `prevouts` are the outputs spent by the inputs, in input order. Returns
+    /// `false` if their number does not match the number of inputs or if the
+    /// outputs exceed the inputs.
+    pub fn is_absurdly_high_fee(&self, prevouts: &[TxOut]) -> bool {
+        if prevouts.len() != self.input.len() {
+            return false;
+        }
+        let input_value = prevouts.iter().fold(0u64, |sum, out| sum.saturating_add(out.value));
+        let output_value = self.output.iter().fold(0u64, |sum, out| sum.saturating_add(out.value));
+        match input_value.checked_sub(output_value) {
+            Some(fee) => fee.saturating_mul(1000) > DEFAULT_MAX_FEE_RATE.saturating_mul(self.vsize() as u64),
+            None => false,
+        }
+    }
+
+    /// Returns `true` if this transaction is serialized in the BIP141 format
+    /// with marker, flag and witnesses.
+    ///
+    /// This is the case if any input has a witness and, as explained in the
+    /// [`Transaction`] docs, for transactions without inputs.
+    pub fn uses_segwit_serialization(&self) -> bool {
+        self.input.is_empty() || self.input.iter().any(|input| !input.witness.is_empty())
+    }
+
     /// Returns `true` if the transaction itself opted in to be BIP-125-replaceable (RBF). This
     /// **does not** cover the case where a transaction becomes replaceable due to ancestors being
     /// RBF.
     pub fn is_explicitly_rbf(&self) -> bool {
         self.input.iter().any(|input| input.sequence < (0xffffffff - 1))
     }
+
+    /// Sorts the inputs and outputs as specified by BIP69: inputs by the txid of the output they
+    /// spend, compared as displayed, then by output index; outputs by value, then by script
+    /// pubkey.
+    pub fn sort_bip69(&mut self) {
+        self.input.sort_by(bip69_input_order);
+        self.output.sort_by(bip69_output_order);
+    }
+
+    /// Returns whether the inputs and outputs are sorted as specified by BIP69, see
+    /// [`Transaction::sort_bip69`].
+    pub fn is_bip69_sorted(&self) -> bool {
+        self.input.windows(2).all(|pair| bip69_input_order(&pair[0], &pair[1]) != cmp::Ordering::Greater)
+            && self.output.windows(2).all(|pair| bip69_output_order(&pair[0], &pair[1]) != cmp::Ordering::Greater)
+    }
+}
+
+/// Compares inputs in BIP69 order.
+pub(crate) fn bip69_input_order(a: &TxIn, b: &TxIn) -> cmp::Ordering {
+    // txids are displayed in reverse byte order
+    a.previous_output.txid[..].iter().rev().cmp(b.previous_output.txid[..].iter().rev())
+        .then(a.previous_output.vout.cmp(&b.previous_output.vout))
+}
+
+/// Compares outputs in BIP69 order.
+pub(crate) fn bip69_output_order(a: &TxOut, b: &TxOut) -> cmp::Ordering {
+    a.value.cmp(&b.value).then_with(|| a.script_pubkey.cmp(&b.script_pubkey))
 }
 
 impl_consensus_encoding!(TxOut, value, script_pubkey);
@@ -637,11 +821,15 @@ impl Encodable for TxIn {
     }
 }
 impl Decodable for TxIn {
-    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        Self::consensus_decode_with_context(r, &DecodeContext::default())
+    }
+
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, encode::Error> {
         Ok(TxIn {
-            previous_output: Decodable::consensus_decode(&mut d)?,
-            script_sig: Decodable::consensus_decode(&mut d)?,
-            sequence: Decodable::consensus_decode(d)?,
+            previous_output: Decodable::consensus_decode_with_context(r, context)?,
+            script_sig: Decodable::consensus_decode_with_context(r, context)?,
+            sequence: Decodable::consensus_decode_with_context(r, context)?,
             witness: Witness::default(),
         })
     }
@@ -653,14 +841,7 @@ impl Encodable for Transaction {
         len += self.version.consensus_encode(&mut s)?;
         // To avoid serialization ambiguity, no inputs means we use BIP141 serialization (see
         // `Transaction` docs for full explanation).
-        let mut have_witness = self.input.is_empty();
-        for input in &self.input {
-            if !input.witness.is_empty() {
-                have_witness = true;
-                break;
-            }
-        }
-        if !have_witness {
+        if !self.uses_segwit_serialization() {
             len += self.input.consensus_encode(&mut s)?;
             len += self.output.consensus_encode(&mut s)?;
         } else {
@@ -675,23 +856,40 @@ impl Encodable for Transaction {
         len += self.lock_time.consensus_encode(s)?;
         Ok(len)
     }
+
+    fn consensus_size(&self) -> usize {
+        // `size` leaves out the segwit marker and flag written for transactions without inputs
+        if self.input.is_empty() {
+            self.size() + 2
+        } else {
+            self.size()
+        }
+    }
 }
 
 impl Decodable for Transaction {
     fn consensus_decode_from_finite_reader<R: io::Read + ?Sized>(
-        d: &mut R,
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Self::consensus_decode_with_context(r, &DecodeContext::default())
+    }
+
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(
+        r: &mut R,
+        context: &DecodeContext,
     ) -> Result<Self, encode::Error> {
-        let version = i32::consensus_decode(d)?;
-        let input = Vec::<TxIn>::consensus_decode( d)?;
+        let mut r = io::Read::take(r, context.max_payload as u64);
+        let version = i32::consensus_decode_with_context(&mut r, context)?;
+        let input = Vec::<TxIn>::consensus_decode_with_context(&mut r, context)?;
         if input.is_empty() {
-            let segwit_flag = u8::consensus_decode_from_finite_reader(r)?;
+            let segwit_flag = u8::consensus_decode_with_context(&mut r, context)?;
             match segwit_flag {
                 // BIP144 input witnesses
                 1 => {
-                    let mut input = Vec::<TxIn>::consensus_decode_from_finite_reader(r)?;
-                    let output = Vec::<TxOut>::consensus_decode_from_finite_reader(r)?;
+                    let mut input = Vec::<TxIn>::consensus_decode_with_context(&mut r, context)?;
+                    let output = Vec::<TxOut>::consensus_decode_with_context(&mut r, context)?;
                     for txin in input.iter_mut() {
-                        txin.witness = Decodable::consensus_decode_from_finite_reader(r)?;
+                        txin.witness = Decodable::consensus_decode_with_context(&mut r, context)?;
                     }
                     if !input.is_empty() && input.iter().all(|input| input.witness.is_empty()) {
                         Err(encode::Error::ParseFailed("witness flag set but no witnesses present"))
@@ -700,7 +898,7 @@ impl Decodable for Transaction {
                             version,
                             input,
                             output,
-                            lock_time: Decodable::consensus_decode_from_finite_reader(r)?,
+                            lock_time: Decodable::consensus_decode_with_context(&mut r, context)?,
                         })
                     }
                 }
@@ -712,13 +910,18 @@ impl Decodable for Transaction {
             Ok(Transaction {
                 version,
                 input,
-                output: Decodable::consensus_decode_from_finite_reader(r)?,
-                lock_time: Decodable::consensus_decode_from_finite_reader(r)?,
+                output: Decodable::consensus_decode_with_context(&mut r, context)?,
+                lock_time: Decodable::consensus_decode_with_context(&mut r, context)?,
             })
         }
     }
 }
 
+arbitrary_struct_impl!(OutPoint, txid, vout);
+arbitrary_struct_impl!(TxIn, previous_output, script_sig, sequence, witness);
+arbitrary_struct_impl!(TxOut, value, script_pubkey);
+arbitrary_struct_impl!(Transaction, version, lock_time, input, output);
+
 /// This type is consensus valid but an input including it would prevent the transaction from
 /// being relayed on today's Bitcoin network.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -776,6 +979,21 @@ impl fmt::Display for EcdsaSighashType {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for EcdsaSighashType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            EcdsaSighashType::All,
+            EcdsaSighashType::None,
+            EcdsaSighashType::Single,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+            EcdsaSighashType::NonePlusAnyoneCanPay,
+            EcdsaSighashType::SinglePlusAnyoneCanPay,
+        ])?)
+    }
+}
+
 impl str::FromStr for EcdsaSighashType {
     type Err = SighashTypeParseError;
 
@@ -961,10 +1179,37 @@ mod tests {
         use blockdata::constants;
 
         let genesis = constants::genesis_block(Network::Bitcoin);
-        assert! (genesis.txdata[0].is_coin_base());
+        assert! (genesis.txdata[0].is_coinbase());
         let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
         let tx: Transaction = deserialize(&tx_bytes).unwrap();
-        assert!(!tx.is_coin_base());
+        assert!(!tx.is_coinbase());
+    }
+
+    #[test]
+    fn test_predicates() {
+        let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let mut tx: Transaction = deserialize(&tx_bytes).unwrap();
+        assert!(!tx.uses_segwit_serialization());
+        assert!(!tx.is_lock_time_enabled());
+
+        tx.lock_time = 500_000;
+        assert!(!tx.is_lock_time_enabled());
+        tx.input[0].sequence = 0xfffffffe;
+        assert!(tx.is_lock_time_enabled());
+
+        // 193 vB, so the maximum fee is 1_930_000 satoshis
+        assert_eq!(tx.vsize(), 193);
+        let prevout = |value: u64| vec![TxOut { value: 100_000_000 + value, script_pubkey: Script::new() }];
+        assert!(!tx.is_absurdly_high_fee(&prevout(1_930_000)));
+        assert!(tx.is_absurdly_high_fee(&prevout(1_930_001)));
+        assert!(!tx.is_absurdly_high_fee(&[]));
+        assert!(!tx.is_absurdly_high_fee(&[TxOut { value: 0, script_pubkey: Script::new() }]));
+
+        tx.input[0].witness.push(vec![1]);
+        assert!(tx.uses_segwit_serialization());
+        tx.input.clear();
+        assert!(tx.uses_segwit_serialization());
+        assert!(!tx.is_lock_time_enabled());
     }
 
     #[test]
@@ -989,10 +1234,11 @@ mod tests {
                    "a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7".to_string());
         assert_eq!(format!("{:x}", realtx.wtxid()),
                    "a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7".to_string());
-        assert_eq!(realtx.weight(), tx_bytes.len()*WITNESS_SCALE_FACTOR);
+        assert_eq!(realtx.weight().to_wu() as usize, tx_bytes.len()*WITNESS_SCALE_FACTOR);
         assert_eq!(realtx.size(), tx_bytes.len());
         assert_eq!(realtx.vsize(), tx_bytes.len());
         assert_eq!(realtx.strippedsize(), tx_bytes.len());
+        assert_eq!(realtx.consensus_size(), tx_bytes.len());
     }
 
     #[test]
@@ -1024,8 +1270,10 @@ mod tests {
         assert_eq!(format!("{:x}", realtx.wtxid()),
                    "80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5".to_string());
         const EXPECTED_WEIGHT: usize = 442;
-        assert_eq!(realtx.weight(), EXPECTED_WEIGHT);
+        assert_eq!(realtx.weight().to_wu() as usize, EXPECTED_WEIGHT);
         assert_eq!(realtx.size(), tx_bytes.len());
+        assert_eq!(realtx.consensus_size(), tx_bytes.len());
+        assert_eq!(realtx.input[0].witness.consensus_size(), serialize(&realtx.input[0].witness).len());
         assert_eq!(realtx.vsize(), 111);
         // Since
         //     size   =                        stripped_size + witness_size
@@ -1037,12 +1285,21 @@ mod tests {
         // Construct a transaction without the witness data.
         let mut tx_without_witness = realtx.clone();
         tx_without_witness.input.iter_mut().for_each(|input| input.witness.clear());
-        assert_eq!(tx_without_witness.weight(), expected_strippedsize*WITNESS_SCALE_FACTOR);
+        assert_eq!(tx_without_witness.weight().to_wu() as usize, expected_strippedsize*WITNESS_SCALE_FACTOR);
         assert_eq!(tx_without_witness.size(), expected_strippedsize);
         assert_eq!(tx_without_witness.vsize(), expected_strippedsize);
         assert_eq!(tx_without_witness.strippedsize(), expected_strippedsize);
     }
 
+    #[test]
+    fn test_consensus_size_without_inputs() {
+        let mut tx = Transaction { version: 2, lock_time: 0, input: vec![], output: vec![] };
+        assert_eq!(tx.consensus_size(), serialize(&tx).len());
+        tx.output.push(TxOut { value: 1000, script_pubkey: Script::new_op_return(&[0; 40]) });
+        assert_eq!(tx.consensus_size(), serialize(&tx).len());
+        assert_eq!(tx.output.consensus_size(), serialize(&tx.output).len());
+    }
+
     #[test]
     fn test_transaction_version() {
         let tx_bytes = Vec::from_hex("ffffff7f0100000000000000000000000000000000000000000000000000000000000000000000000000ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000").unwrap();
@@ -1124,7 +1381,7 @@ mod tests {
 
         assert_eq!(format!("{:x}", tx.wtxid()), "d6ac4a5e61657c4c604dcde855a1db74ec6b3e54f32695d72c5e11c7761ea1b4");
         assert_eq!(format!("{:x}", tx.txid()), "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec");
-        assert_eq!(tx.weight(), 2718);
+        assert_eq!(tx.weight(), Weight::from_wu(2718));
 
         // non-segwit tx from my mempool
         let tx_bytes = Vec::from_hex(
@@ -1156,7 +1413,7 @@ mod tests {
     fn test_segwit_tx_decode() {
         let tx_bytes = Vec::from_hex("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
         let tx: Transaction = deserialize(&tx_bytes).unwrap();
-        assert_eq!(tx.weight(), 780);
+        assert_eq!(tx.weight(), Weight::from_wu(780));
         serde_round_trip!(tx);
 
         let consensus_encoded = serialize(&tx);
@@ -1600,6 +1857,87 @@ mod tests {
             _ => panic!("Wrong error type"),
         }
     }
+
+    #[test]
+    fn input_weight_prediction() {
+        assert_eq!(InputWeightPrediction::new(0, vec![72, 33]), InputWeightPrediction::P2WPKH_MAX);
+        assert_eq!(InputWeightPrediction::new(23, vec![72, 33]), InputWeightPrediction::NESTED_P2WPKH_MAX);
+        assert_eq!(InputWeightPrediction::new(107, vec![]), InputWeightPrediction::P2PKH_COMPRESSED_MAX);
+        assert_eq!(InputWeightPrediction::new(139, vec![]), InputWeightPrediction::P2PKH_UNCOMPRESSED_MAX);
+        assert_eq!(InputWeightPrediction::new(0, vec![64]), InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH);
+        assert_eq!(InputWeightPrediction::new(0, vec![65]), InputWeightPrediction::P2TR_KEY_NON_DEFAULT_SIGHASH);
+        assert_eq!(InputWeightPrediction::P2WPKH_MAX.weight(), Weight::from_wu(112));
+        assert_eq!(InputWeightPrediction::P2WPKH_MAX.input_weight(), Weight::from_wu(272));
+        assert_eq!(InputWeightPrediction::new(300, vec![0; 300]).weight(), Weight::from_wu(303 * 4 + 3 + 300));
+    }
+
+    #[test]
+    fn effective_value() {
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let output = TxOut { value: 10_000, script_pubkey: Script::new() };
+        // 272 wu at 250 sat/kwu
+        assert_eq!(output.effective_value(fee_rate, InputWeightPrediction::P2WPKH_MAX), SignedAmount::from_sat(9_932));
+        let dust = TxOut { value: 50, script_pubkey: Script::new() };
+        assert_eq!(dust.effective_value(fee_rate, InputWeightPrediction::P2WPKH_MAX), SignedAmount::from_sat(-18));
+        assert_eq!(
+            output.effective_value(FeeRate::MAX, InputWeightPrediction::P2PKH_UNCOMPRESSED_MAX),
+            SignedAmount::from_sat(10_000 - i64::max_value())
+        );
+    }
+
+    #[test]
+    fn predict_weight() {
+        let input = |script_sig: Vec<u8>, witness: Vec<Vec<u8>>| TxIn {
+            previous_output: OutPoint::default(),
+            script_sig: Script::from(script_sig),
+            sequence: 0xffffffff,
+            witness: Witness::from_vec(witness),
+        };
+        let output = |len: usize| TxOut { value: 1_000, script_pubkey: Script::from(vec![0; len]) };
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![input(vec![0; 107], vec![])],
+            output: vec![output(25), output(22)],
+        };
+        let inputs = vec![InputWeightPrediction::P2PKH_COMPRESSED_MAX];
+        assert_eq!(super::predict_weight(inputs.clone(), vec![25, 22]), tx.weight());
+
+        tx.input.push(input(vec![], vec![vec![0; 72], vec![0; 33]]));
+        tx.input.push(input(vec![], vec![vec![0; 64]]));
+        tx.output.push(output(300));
+        let inputs = vec![
+            InputWeightPrediction::P2PKH_COMPRESSED_MAX,
+            InputWeightPrediction::P2WPKH_MAX,
+            InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH,
+        ];
+        assert_eq!(super::predict_weight(inputs.clone(), vec![25, 22, 300]), tx.weight());
+
+        // shorter signatures make the prediction an upper bound
+        tx.input[1].witness = Witness::from_vec(vec![vec![0; 71], vec![0; 33]]);
+        assert!(super::predict_weight(inputs, vec![25, 22, 300]) > tx.weight());
+    }
+
+    #[test]
+    fn sort_bip69() {
+        let low = Txid::from_hex("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        let high = Txid::from_hex("0100000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let input = |txid, vout| TxIn { previous_output: OutPoint::new(txid, vout), ..Default::default() };
+        let output = |value, script: &str| TxOut { value, script_pubkey: Script::from(Vec::from_hex(script).unwrap()) };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![input(high, 0), input(low, 2), input(low, 1)],
+            output: vec![output(2, "00"), output(1, "51"), output(1, "0051")],
+        };
+        assert!(!tx.is_bip69_sorted());
+
+        tx.sort_bip69();
+        assert!(tx.is_bip69_sorted());
+        assert_eq!(tx.input, vec![input(low, 1), input(low, 2), input(high, 0)]);
+        assert_eq!(tx.output, vec![output(1, "0051"), output(1, "51"), output(2, "00")]);
+    }
 }
 
 #[cfg(all(test, feature = "unstable"))]
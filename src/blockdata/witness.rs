@@ -3,7 +3,7 @@
 //! This module contains the [`Witness`] struct and related methods to operate on it
 //!
 
-use consensus::encode::{Error, MAX_VEC_SIZE};
+use consensus::encode::{DecodeContext, Error, MAX_VEC_SIZE};
 use consensus::{Decodable, Encodable, WriteExt};
 use io::{self, Read, Write};
 use prelude::*;
@@ -11,6 +11,8 @@ use VarInt;
 
 #[cfg(feature = "serde")]
 use serde;
+#[cfg(feature = "arbitrary")]
+use arbitrary::{self, Arbitrary, Unstructured};
 
 /// The Witness is the data used to unlock bitcoins since the [segwit upgrade](https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki)
 ///
@@ -45,7 +47,18 @@ pub struct Iter<'a>(::core::slice::Iter<'a, u8>);
 
 impl Decodable for Witness {
     fn consensus_decode<D: Read>(mut d: D) -> Result<Self, Error> {
-        let witness_elements = VarInt::consensus_decode(&mut d)?.0 as usize;
+        Witness::decode(&mut d, MAX_VEC_SIZE)
+    }
+
+    fn consensus_decode_with_context<R: Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, Error> {
+        Witness::decode(r, context.max_vec_size)
+    }
+}
+
+impl Witness {
+    /// Decodes a witness whose serialized elements take at most `max_size` bytes.
+    fn decode<R: Read + ?Sized>(d: &mut R, max_size: usize) -> Result<Self, Error> {
+        let witness_elements = VarInt::consensus_decode(d)?.0 as usize;
         if witness_elements == 0 {
             Ok(Witness::default())
         } else {
@@ -60,25 +73,25 @@ impl Decodable for Witness {
             for _ in 0..witness_elements {
                 second_to_last = last;
                 last = cursor;
-                let element_size_varint = VarInt::consensus_decode(&mut d)?;
+                let element_size_varint = VarInt::consensus_decode(d)?;
                 let element_size_varint_len = element_size_varint.len();
                 let element_size = element_size_varint.0 as usize;
                 let required_len = cursor
                     .checked_add(element_size)
                     .ok_or_else(|| self::Error::OversizedVectorAllocation {
                         requested: usize::max_value(),
-                        max: MAX_VEC_SIZE,
+                        max: max_size,
                     })?
                     .checked_add(element_size_varint_len)
                     .ok_or_else(|| self::Error::OversizedVectorAllocation {
                         requested: usize::max_value(),
-                        max: MAX_VEC_SIZE,
+                        max: max_size,
                     })?;
 
-                if required_len > MAX_VEC_SIZE {
+                if required_len > max_size {
                     return Err(self::Error::OversizedVectorAllocation {
                         requested: required_len,
-                        max: MAX_VEC_SIZE,
+                        max: max_size,
                     });
                 }
 
@@ -117,6 +130,10 @@ impl Encodable for Witness {
         writer.emit_slice(&self.content[..])?;
         Ok(self.content.len() + len.len())
     }
+
+    fn consensus_size(&self) -> usize {
+        VarInt(self.witness_elements as u64).len() + self.content.len()
+    }
 }
 
 impl Witness {
@@ -249,6 +266,14 @@ impl Default for Witness {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Witness {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Witness::from_vec(Vec::<Vec<u8>>::arbitrary(u)?))
+    }
+}
+
 impl<'a> Iterator for Iter<'a> {
     type Item = &'a [u8];
 
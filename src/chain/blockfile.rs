@@ -0,0 +1,298 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Block files.
+//!
+//! This module reads the `blk*.dat` files Bitcoin Core stores blocks in. Each
+//! block is framed by the network magic and its length, and files are
+//! preallocated so records may be followed by zero padding.
+//!
+//! Since version 28, Bitcoin Core obfuscates block files by XORing them with
+//! a random key stored in the `xor.dat` file of the blocks directory, see
+//! [`XorReader`].
+//!
+
+use prelude::*;
+
+use core::cmp;
+
+use io;
+
+use blockdata::block::Block;
+use blockdata::constants::MAX_BLOCK_SERIALIZED_SIZE;
+use consensus::encode::{self, deserialize};
+use network::constants::Network;
+use util::endian;
+
+/// Iterates over the blocks of a block file, yielding each together with the file offset its
+/// serialization starts at.
+///
+/// The file is scanned for the network magic, so zero padding and corrupted regions between
+/// records are skipped. Iteration ends at the end of the file, including in the middle of a
+/// partially written trailing record. The reader is read a byte at a time while scanning, so
+/// wrap files in a buffered reader.
+#[derive(Debug)]
+pub struct BlockFileReader<R> {
+    reader: R,
+    magic: [u8; 4],
+    offset: u64,
+    /// Bytes read past the scanning position, which are scanned again before reading more.
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl<R: io::Read> BlockFileReader<R> {
+    /// Creates a reader of a block file of `network`.
+    pub fn new(reader: R, network: Network) -> BlockFileReader<R> {
+        BlockFileReader::with_magic(reader, network.magic())
+    }
+
+    /// Creates a reader of a block file framing its records with `magic`.
+    pub fn with_magic(reader: R, magic: u32) -> BlockFileReader<R> {
+        BlockFileReader {
+            reader,
+            magic: [magic as u8, (magic >> 8) as u8, (magic >> 16) as u8, (magic >> 24) as u8],
+            offset: 0,
+            pending: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the offset in the file the reader is at.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Fills `buf`, returning false if the file ends first.
+    fn fill(&mut self, buf: &mut [u8]) -> Result<bool, io::Error> {
+        let mut read = cmp::min(buf.len(), self.pending.len());
+        buf[..read].copy_from_slice(&self.pending[..read]);
+        self.pending.drain(..read);
+        self.offset += read as u64;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) => return Ok(false),
+                Ok(n) => {
+                    read += n;
+                    self.offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads the next record, returning `None` at the end of the file.
+    fn next_record(&mut self) -> Result<Option<(u64, Vec<u8>)>, io::Error> {
+        loop {
+            let mut window = [0u8; 4];
+            if !self.fill(&mut window)? {
+                return Ok(None);
+            }
+            while window != self.magic {
+                let mut byte = [0u8; 1];
+                if !self.fill(&mut byte)? {
+                    return Ok(None);
+                }
+                window = [window[1], window[2], window[3], byte[0]];
+            }
+
+            let mut size = [0u8; 4];
+            if !self.fill(&mut size)? {
+                return Ok(None);
+            }
+            let size_value = endian::slice_to_u32_le(&size);
+            if size_value < 80 || size_value > MAX_BLOCK_SERIALIZED_SIZE {
+                // not a record after all, so resume scanning right after the start of the false
+                // magic, as the real one may begin in the bytes already read
+                let mut rescan = window[1..].to_vec();
+                rescan.extend_from_slice(&size);
+                self.offset -= rescan.len() as u64;
+                rescan.append(&mut self.pending);
+                self.pending = rescan;
+                continue;
+            }
+
+            let offset = self.offset;
+            let mut data = vec![0u8; size_value as usize];
+            if !self.fill(&mut data)? {
+                return Ok(None);
+            }
+            return Ok(Some((offset, data)));
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for BlockFileReader<R> {
+    type Item = Result<(u64, Block), encode::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some((offset, data))) => Some(deserialize(&data).map(|block| (offset, block))),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(encode::Error::Io(e)))
+            }
+        }
+    }
+}
+
+/// Reads the key block files are obfuscated with from the contents of `xor.dat`.
+pub fn read_xor_key<R: io::Read>(mut reader: R) -> Result<[u8; 8], io::Error> {
+    let mut key = [0u8; 8];
+    reader.read_exact(&mut key)?;
+    Ok(key)
+}
+
+/// Reverses the XOR obfuscation of a block file.
+///
+/// An all-zero key, which Bitcoin Core uses when obfuscation is disabled, leaves the data as
+/// is, so the adapter can be used unconditionally.
+#[derive(Debug)]
+pub struct XorReader<R> {
+    reader: R,
+    key: [u8; 8],
+    position: u64,
+}
+
+impl<R: io::Read> XorReader<R> {
+    /// Creates a reader deobfuscating a file read from its start.
+    pub fn new(reader: R, key: [u8; 8]) -> XorReader<R> {
+        XorReader::with_position(reader, key, 0)
+    }
+
+    /// Creates a reader deobfuscating a file read from `position` on, e.g. after seeking.
+    pub fn with_position(reader: R, key: [u8; 8], position: u64) -> XorReader<R> {
+        XorReader { reader, key, position }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: io::Read> io::Read for XorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let n = self.reader.read(buf)?;
+        for (i, byte) in buf[..n].iter_mut().enumerate() {
+            *byte ^= self.key[((self.position + i as u64) % 8) as usize];
+        }
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use consensus::encode::serialize;
+
+    fn record(network: Network, block: &[u8]) -> Vec<u8> {
+        let mut ret = serialize(&network.magic());
+        ret.extend_from_slice(&serialize(&(block.len() as u32)));
+        ret.extend_from_slice(block);
+        ret
+    }
+
+    #[test]
+    fn block_file_reader() {
+        let genesis = genesis_block(Network::Regtest);
+        let block = serialize(&genesis);
+
+        let mut file = record(Network::Regtest, &block);
+        file.extend_from_slice(&[0u8; 13]);
+        let second = file.len() as u64 + 8;
+        file.extend_from_slice(&record(Network::Regtest, &block));
+        // a record of another network and a block that fails to decode
+        file.extend_from_slice(&record(Network::Testnet, &block));
+        let corrupt = file.len() as u64 + 8;
+        file.extend_from_slice(&record(Network::Regtest, &[0xff; 80]));
+        // partially written trailing record
+        let full = file.len();
+        file.extend_from_slice(&record(Network::Regtest, &block)[..40]);
+
+        let mut reader = BlockFileReader::new(&file[..], Network::Regtest);
+        assert_eq!(reader.next().unwrap().unwrap(), (8, genesis.clone()));
+        assert_eq!(reader.next().unwrap().unwrap(), (second, genesis));
+        assert_eq!(reader.offset(), second + block.len() as u64);
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(reader.offset(), corrupt + 80);
+        assert!(reader.next().is_none());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), full as u64 + 40);
+    }
+
+    #[test]
+    fn block_file_reader_resync() {
+        let genesis = genesis_block(Network::Regtest);
+        let block = serialize(&genesis);
+
+        // a stray magic followed by a record, whose own magic is read as an out-of-range size
+        let mut file = serialize(&Network::Regtest.magic());
+        let first = file.len() as u64 + 8;
+        file.extend_from_slice(&record(Network::Regtest, &block));
+        // a stray magic whose size bytes hold the first half of the magic of a record
+        file.extend_from_slice(&serialize(&Network::Regtest.magic()));
+        file.extend_from_slice(&[0, 0]);
+        let second = file.len() as u64 + 8;
+        file.extend_from_slice(&record(Network::Regtest, &block));
+
+        let mut reader = BlockFileReader::new(&file[..], Network::Regtest);
+        assert_eq!(reader.next().unwrap().unwrap(), (first, genesis.clone()));
+        assert_eq!(reader.next().unwrap().unwrap(), (second, genesis));
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), file.len() as u64);
+    }
+
+    #[test]
+    fn xor_reader() {
+        let genesis = genesis_block(Network::Regtest);
+        let mut file = record(Network::Regtest, &serialize(&genesis));
+        file.extend_from_slice(&file.clone());
+
+        let key = read_xor_key(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09][..]).unwrap();
+        assert_eq!(key, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert!(read_xor_key(&[0x01; 7][..]).is_err());
+        let obfuscated: Vec<u8> = file.iter().enumerate().map(|(i, b)| b ^ key[i % 8]).collect();
+
+        let blocks: Vec<_> = BlockFileReader::new(XorReader::new(&obfuscated[..], key), Network::Regtest)
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(blocks, vec![genesis.clone(), genesis.clone()]);
+        assert!(BlockFileReader::new(&obfuscated[..], Network::Regtest).next().is_none());
+
+        let mut reader = XorReader::with_position(&obfuscated[3..], key, 3);
+        let mut buf = [0u8; 5];
+        io::Read::read_exact(&mut reader, &mut buf).unwrap();
+        assert_eq!(buf[..], file[3..8]);
+
+        let mut reader = XorReader::new(&file[..], [0; 8]);
+        assert_eq!(BlockFileReader::new(&mut reader, Network::Regtest).count(), 2);
+    }
+}
@@ -0,0 +1,200 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Coins.
+//!
+//! This module defines unspent transaction outputs together with the context
+//! they were created in, and a common interface to look them up by outpoint.
+//!
+
+use prelude::*;
+
+use io;
+
+use blockdata::constants::{COINBASE_MATURITY, MAX_SCRIPT_SIZE};
+use blockdata::transaction::{OutPoint, Transaction, TxOut};
+use consensus::encode::{self, Decodable, Encodable};
+use chain::compress::{self, CompressedTxOut};
+
+/// An unspent transaction output and the block it was created in.
+///
+/// Coins are encoded as in Bitcoin Core's UTXO database and snapshots: the height and the
+/// coinbase flag packed into a `VARINT`, followed by the [`CompressedTxOut`].
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Coin {
+    /// The output itself.
+    pub txout: TxOut,
+    /// Height of the block containing the transaction that created the output.
+    pub height: u32,
+    /// Whether the output was created by a coinbase transaction.
+    pub is_coinbase: bool,
+}
+
+impl Coin {
+    /// Creates a new coin.
+    pub fn new(txout: TxOut, height: u32, is_coinbase: bool) -> Coin {
+        Coin { txout, height, is_coinbase }
+    }
+
+    /// Returns whether the coin may be spent in a block at `spend_height`.
+    ///
+    /// Coinbase outputs only mature after [`COINBASE_MATURITY`] blocks, all other coins
+    /// are spendable right away.
+    pub fn is_mature(&self, spend_height: u32) -> bool {
+        !self.is_coinbase || spend_height.saturating_sub(self.height) >= COINBASE_MATURITY
+    }
+}
+
+impl Encodable for Coin {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let len = compress::write_varint(w, self.height as u64 * 2 + self.is_coinbase as u64)?;
+        Ok(len + CompressedTxOut(self.txout.clone()).consensus_encode(w)?)
+    }
+}
+
+impl Decodable for Coin {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let code = compress::read_varint(r)?;
+        if code > u32::max_value() as u64 {
+            return Err(encode::Error::ParseFailed("coin height out of range"));
+        }
+        let txout = CompressedTxOut::consensus_decode(r)?.0;
+        Ok(Coin::new(txout, (code >> 1) as u32, code & 1 == 1))
+    }
+}
+
+/// A set of coins, indexed by the outpoint spending them.
+pub trait CoinsView {
+    /// Returns the unspent coin at `outpoint`.
+    fn get(&self, outpoint: &OutPoint) -> Option<Coin>;
+
+    /// Adds a coin, returning the unspent coin it replaces if any.
+    fn add(&mut self, outpoint: OutPoint, coin: Coin) -> Option<Coin>;
+
+    /// Removes the coin at `outpoint` from the set, returning it if it was unspent.
+    fn spend(&mut self, outpoint: &OutPoint) -> Option<Coin>;
+
+    /// Returns whether an unspent coin exists at `outpoint`.
+    fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.get(outpoint).is_some()
+    }
+
+    /// Adds the outputs of `tx`, confirmed at `height`, skipping those that can never be spent.
+    fn add_transaction(&mut self, tx: &Transaction, height: u32) {
+        let txid = tx.txid();
+        let is_coinbase = tx.is_coinbase();
+        for (vout, txout) in tx.output.iter().enumerate() {
+            let script = &txout.script_pubkey;
+            if script.is_op_return() || script.len() > MAX_SCRIPT_SIZE {
+                continue;
+            }
+            self.add(OutPoint::new(txid, vout as u32), Coin::new(txout.clone(), height, is_coinbase));
+        }
+    }
+}
+
+/// A [`CoinsView`] held in memory.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MemoryCoinsView(HashMap<OutPoint, Coin>);
+
+impl MemoryCoinsView {
+    /// Creates an empty coin set.
+    pub fn new() -> MemoryCoinsView {
+        MemoryCoinsView(HashMap::new())
+    }
+
+    /// Returns the number of unspent coins.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether there are no unspent coins.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the unspent coins in arbitrary order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a OutPoint, &'a Coin)> + 'a {
+        self.0.iter()
+    }
+}
+
+impl CoinsView for MemoryCoinsView {
+    fn get(&self, outpoint: &OutPoint) -> Option<Coin> {
+        self.0.get(outpoint).cloned()
+    }
+
+    fn add(&mut self, outpoint: OutPoint, coin: Coin) -> Option<Coin> {
+        self.0.insert(outpoint, coin)
+    }
+
+    fn spend(&mut self, outpoint: &OutPoint) -> Option<Coin> {
+        self.0.remove(outpoint)
+    }
+
+    fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.0.contains_key(outpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use blockdata::script::{Builder, Script};
+    use consensus::encode::{deserialize, serialize};
+    use hashes::hex::{FromHex, ToHex};
+    use blockdata::opcodes::all::OP_RETURN;
+    use network::constants::Network;
+
+    #[test]
+    fn memory_coins_view() {
+        let mut coinbase = genesis_block(Network::Regtest).txdata[0].clone();
+        coinbase.output.push(TxOut {
+            value: 0,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).push_slice(b"data").into_script(),
+        });
+        let txid = coinbase.txid();
+
+        let mut view = MemoryCoinsView::new();
+        assert!(view.is_empty());
+        view.add_transaction(&coinbase, 7);
+        assert_eq!(view.len(), 1);
+        assert!(!view.contains(&OutPoint::new(txid, 1)));
+
+        let outpoint = OutPoint::new(txid, 0);
+        let coin = view.get(&outpoint).unwrap();
+        assert_eq!(coin, Coin::new(coinbase.output[0].clone(), 7, true));
+        assert!(!coin.is_mature(106));
+        assert!(coin.is_mature(107));
+        assert!(Coin::new(coin.txout.clone(), 7, false).is_mature(7));
+
+        assert_eq!(view.spend(&outpoint), Some(coin.clone()));
+        assert_eq!(view.spend(&outpoint), None);
+        assert!(!view.contains(&outpoint));
+        assert_eq!(view.add(outpoint, coin.clone()), None);
+        assert_eq!(view.iter().next(), Some((&outpoint, &coin)));
+    }
+
+    #[test]
+    fn coin_encoding() {
+        let p2pkh = Script::from(Vec::from_hex("76a914000102030405060708090a0b0c0d0e0f1011121388ac").unwrap());
+        let coin = Coin::new(TxOut { value: 5_000_000_000, script_pubkey: p2pkh }, 0x80, true);
+        let encoded = serialize(&coin);
+        assert_eq!(encoded.to_hex(), "81013200000102030405060708090a0b0c0d0e0f10111213");
+        assert_eq!(deserialize::<Coin>(&encoded).unwrap(), coin);
+        assert!(deserialize::<Coin>(&encoded[..5]).is_err());
+    }
+}
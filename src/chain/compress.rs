@@ -0,0 +1,323 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Chainstate compression.
+//!
+//! This module implements the compact encodings Bitcoin Core uses for coins
+//! in its UTXO database, undo files and UTXO snapshots: its base-128 `VARINT`,
+//! amounts with their trailing decimal zeros folded into an exponent, and
+//! scripts with the common P2PKH, P2SH and P2PK forms reduced to their hash or
+//! key.
+//!
+
+use prelude::*;
+
+use io;
+use secp256k1;
+
+use blockdata::constants::MAX_SCRIPT_SIZE;
+use blockdata::opcodes::all::{OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN};
+use blockdata::script::Script;
+use blockdata::transaction::TxOut;
+use consensus::encode::{self, Decodable, Encodable, ReadExt, WriteExt};
+
+/// The number of script sizes reserved for the special script forms.
+const SPECIAL_SCRIPTS: u64 = 6;
+
+/// Writes `n` as Bitcoin Core's `VARINT`, a base-128 encoding distinct from the compact size.
+pub fn write_varint<W: io::Write + ?Sized>(w: &mut W, mut n: u64) -> Result<usize, io::Error> {
+    let mut tmp = [0u8; 10];
+    let mut len = 0;
+    loop {
+        tmp[len] = (n & 0x7f) as u8 | if len > 0 { 0x80 } else { 0x00 };
+        if n <= 0x7f {
+            break;
+        }
+        n = (n >> 7) - 1;
+        len += 1;
+    }
+    for byte in tmp[..=len].iter().rev() {
+        w.emit_u8(*byte)?;
+    }
+    Ok(len + 1)
+}
+
+/// Reads a `VARINT` written by [`write_varint`].
+pub fn read_varint<R: io::Read + ?Sized>(r: &mut R) -> Result<u64, encode::Error> {
+    let mut n = 0u64;
+    loop {
+        let byte = r.read_u8()?;
+        if n > u64::max_value() >> 7 {
+            return Err(encode::Error::ParseFailed("VARINT too large"));
+        }
+        n = (n << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        if n == u64::max_value() {
+            return Err(encode::Error::ParseFailed("VARINT too large"));
+        }
+        n += 1;
+    }
+}
+
+/// Compresses an amount of satoshis, exploiting that most amounts are round decimal numbers.
+///
+/// Returns `None` if the compressed amount doesn't fit in 64 bits, which only happens for
+/// amounts far above the money supply.
+pub fn compress_amount(mut n: u64) -> Option<u64> {
+    if n == 0 {
+        return Some(0);
+    }
+    let mut e = 0;
+    while n % 10 == 0 && e < 9 {
+        n /= 10;
+        e += 1;
+    }
+    if e < 9 {
+        let d = n % 10;
+        n /= 10;
+        n.checked_mul(9)?.checked_add(d - 1)?.checked_mul(10)?.checked_add(1 + e)
+    } else {
+        (n - 1).checked_mul(10)?.checked_add(10)
+    }
+}
+
+/// Writes the compressed `amount` as a `VARINT`, failing if it can't be compressed.
+pub fn write_amount<W: io::Write + ?Sized>(w: &mut W, amount: u64) -> Result<usize, io::Error> {
+    let compressed = compress_amount(amount)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "amount too large to compress"))?;
+    write_varint(w, compressed)
+}
+
+/// Reads an amount written by [`write_amount`].
+pub fn read_amount<R: io::Read + ?Sized>(r: &mut R) -> Result<u64, encode::Error> {
+    decompress_amount(read_varint(r)?).ok_or(encode::Error::ParseFailed("invalid compressed amount"))
+}
+
+/// Reverses [`compress_amount`], `None` if the amount doesn't fit in 64 bits, which only
+/// happens for invalid data.
+pub fn decompress_amount(mut x: u64) -> Option<u64> {
+    if x == 0 {
+        return Some(0);
+    }
+    x -= 1;
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = x % 9 + 1;
+        x /= 9;
+        x.checked_mul(10)?.checked_add(d)?
+    } else {
+        x.checked_add(1)?
+    };
+    while e > 0 {
+        n = n.checked_mul(10)?;
+        e -= 1;
+    }
+    Some(n)
+}
+
+/// Returns the special form of `script`, tagged with its form number, if it has one.
+///
+/// Uncompressed P2PK scripts only have a special form if their key is valid.
+pub fn compress_script(script: &Script) -> Option<Vec<u8>> {
+    let bytes = script.as_bytes();
+    if script.is_p2pkh() {
+        let mut ret = vec![0x00];
+        ret.extend_from_slice(&bytes[3..23]);
+        return Some(ret);
+    }
+    if script.is_p2sh() {
+        let mut ret = vec![0x01];
+        ret.extend_from_slice(&bytes[2..22]);
+        return Some(ret);
+    }
+    if bytes.len() == 35 && bytes[0] == 33 && bytes[34] == OP_CHECKSIG.into_u8()
+        && (bytes[1] == 0x02 || bytes[1] == 0x03) {
+        return Some(bytes[1..34].to_vec());
+    }
+    if bytes.len() == 67 && bytes[0] == 65 && bytes[66] == OP_CHECKSIG.into_u8() && bytes[1] == 0x04
+        && secp256k1::PublicKey::from_slice(&bytes[1..66]).is_ok() {
+        let mut ret = vec![0x04 | (bytes[65] & 0x01)];
+        ret.extend_from_slice(&bytes[2..34]);
+        return Some(ret);
+    }
+    None
+}
+
+/// Writes `script` in its compressed form, i.e. its special form if it has one and
+/// otherwise its length, offset by the number of special forms, and its bytes.
+pub fn write_script<W: io::Write + ?Sized>(w: &mut W, script: &Script) -> Result<usize, io::Error> {
+    if let Some(compressed) = compress_script(script) {
+        w.emit_slice(&compressed)?;
+        return Ok(compressed.len());
+    }
+    let len = write_varint(w, script.len() as u64 + SPECIAL_SCRIPTS)?;
+    w.emit_slice(script.as_bytes())?;
+    Ok(len + script.len())
+}
+
+/// Reads a script written by [`write_script`].
+///
+/// Scripts longer than [`MAX_SCRIPT_SIZE`] are unspendable and read as a bare `OP_RETURN`,
+/// as Bitcoin Core does.
+pub fn read_script<R: io::Read + ?Sized>(r: &mut R) -> Result<Script, encode::Error> {
+    let size = read_varint(r)?;
+    if size < SPECIAL_SCRIPTS {
+        let mut data = [0u8; 32];
+        let data = if size < 2 { &mut data[..20] } else { &mut data[..] };
+        r.read_slice(data)?;
+        let mut ret = Vec::with_capacity(67);
+        match size {
+            0x00 => {
+                ret.extend_from_slice(&[OP_DUP.into_u8(), OP_HASH160.into_u8(), 20]);
+                ret.extend_from_slice(data);
+                ret.extend_from_slice(&[OP_EQUALVERIFY.into_u8(), OP_CHECKSIG.into_u8()]);
+            }
+            0x01 => {
+                ret.extend_from_slice(&[OP_HASH160.into_u8(), 20]);
+                ret.extend_from_slice(data);
+                ret.push(OP_EQUAL.into_u8());
+            }
+            0x02 | 0x03 => {
+                ret.extend_from_slice(&[33, size as u8]);
+                ret.extend_from_slice(data);
+                ret.push(OP_CHECKSIG.into_u8());
+            }
+            _ => {
+                let mut compressed = [0u8; 33];
+                compressed[0] = size as u8 - 2;
+                compressed[1..].copy_from_slice(data);
+                let key = secp256k1::PublicKey::from_slice(&compressed)
+                    .map_err(|_| encode::Error::ParseFailed("invalid compressed public key"))?;
+                ret.push(65);
+                ret.extend_from_slice(&key.serialize_uncompressed());
+                ret.push(OP_CHECKSIG.into_u8());
+            }
+        }
+        return Ok(Script::from(ret));
+    }
+    let size = (size - SPECIAL_SCRIPTS) as usize;
+    if size > MAX_SCRIPT_SIZE {
+        // Overly long scripts are unspendable, Bitcoin Core stores them as a bare OP_RETURN.
+        let mut remaining = size;
+        let mut buf = [0u8; 512];
+        while remaining > 0 {
+            let chunk = if remaining < buf.len() { remaining } else { buf.len() };
+            r.read_slice(&mut buf[..chunk])?;
+            remaining -= chunk;
+        }
+        return Ok(Script::from(vec![OP_RETURN.into_u8()]));
+    }
+    let mut ret = vec![0u8; size];
+    r.read_slice(&mut ret)?;
+    Ok(Script::from(ret))
+}
+
+/// A transaction output encoded with its amount and script compressed.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct CompressedTxOut(pub TxOut);
+
+impl Encodable for CompressedTxOut {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let len = write_amount(w, self.0.value)?;
+        Ok(len + write_script(w, &self.0.script_pubkey)?)
+    }
+}
+
+impl Decodable for CompressedTxOut {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let value = read_amount(r)?;
+        Ok(CompressedTxOut(TxOut { value, script_pubkey: read_script(r)? }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::{FromHex, ToHex};
+
+    #[test]
+    fn varint() {
+        // Test vectors from Bitcoin Core's serialize_tests.cpp.
+        let vectors: &[(u64, &str)] = &[
+            (0, "00"), (0x7f, "7f"), (0x80, "8000"), (0x1234, "a334"), (0xffff, "82fe7f"),
+            (0x123456, "c7e756"), (0x80123456, "86ffc7e756"), (0xffffffff, "8efefefe7f"),
+            (u64::max_value(), "80fefefefefefefefe7f"),
+        ];
+        for &(n, hex) in vectors {
+            let mut encoded = Vec::new();
+            assert_eq!(write_varint(&mut encoded, n).unwrap(), hex.len() / 2);
+            assert_eq!(encoded.to_hex(), hex);
+            assert_eq!(read_varint(&mut &encoded[..]).unwrap(), n);
+        }
+        assert!(read_varint(&mut &Vec::from_hex("80fefefefefefefefe80").unwrap()[..]).is_err());
+    }
+
+    #[test]
+    fn amount() {
+        // Test vectors from Bitcoin Core's compress_tests.cpp.
+        let vectors: &[(u64, u64)] = &[
+            (0, 0x0), (1, 0x1), (1_000_000, 0x7), (100_000_000, 0x9), (5_000_000_000, 0x32),
+            (2_100_000_000_000_000, 0x1406f40),
+        ];
+        for &(amount, compressed) in vectors {
+            assert_eq!(compress_amount(amount), Some(compressed));
+            assert_eq!(decompress_amount(compressed), Some(amount));
+        }
+        for amount in (0..100_000).chain(2_099_999_999_990_000..2_100_000_000_000_001) {
+            assert_eq!(decompress_amount(compress_amount(amount).unwrap()), Some(amount));
+        }
+        assert_eq!(decompress_amount(1_000_000_000_000), None);
+        assert_eq!(decompress_amount(u64::max_value()), None);
+        assert_eq!(compress_amount(u64::max_value()), None);
+        assert_eq!(compress_amount(5_000_000_000_000_000_001), None);
+        assert!(write_amount(&mut Vec::new(), u64::max_value()).is_err());
+
+        let mut overflowing = Vec::new();
+        write_varint(&mut overflowing, 1_000_000_000_000).unwrap();
+        write_script(&mut overflowing, &Script::new()).unwrap();
+        match CompressedTxOut::consensus_decode(&mut &overflowing[..]) {
+            Err(encode::Error::ParseFailed("invalid compressed amount")) => {}
+            ret => panic!("unexpected result {:?}", ret),
+        }
+    }
+
+    #[test]
+    fn script() {
+        let scripts = [
+            ("76a914000102030405060708090a0b0c0d0e0f1011121388ac", "00000102030405060708090a0b0c0d0e0f10111213"),
+            ("a914000102030405060708090a0b0c0d0e0f1011121387", "01000102030405060708090a0b0c0d0e0f10111213"),
+            ("2102678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb6ac", "02678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb6"),
+            ("4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+             "05678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb6"),
+            ("0014000102030405060708090a0b0c0d0e0f10111213", "1c0014000102030405060708090a0b0c0d0e0f10111213"),
+        ];
+        for &(script, compressed) in scripts.iter() {
+            let script = Script::from(Vec::from_hex(script).unwrap());
+            let mut encoded = Vec::new();
+            assert_eq!(write_script(&mut encoded, &script).unwrap(), encoded.len());
+            assert_eq!(encoded.to_hex(), compressed);
+            assert_eq!(read_script(&mut &encoded[..]).unwrap(), script);
+        }
+
+        let mut oversized = Vec::new();
+        write_varint(&mut oversized, (MAX_SCRIPT_SIZE + 1) as u64 + SPECIAL_SCRIPTS).unwrap();
+        oversized.extend_from_slice(&[0x51; MAX_SCRIPT_SIZE + 1][..]);
+        let mut reader = &oversized[..];
+        assert_eq!(read_script(&mut reader).unwrap(), Script::from(vec![OP_RETURN.into_u8()]));
+        assert!(reader.is_empty());
+    }
+}
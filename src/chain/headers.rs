@@ -0,0 +1,403 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Header chain.
+//!
+//! This module implements a store of block headers which follows the chain
+//! with the most work, as needed both by SPV clients and by full nodes
+//! syncing headers first.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hash_types::BlockHash;
+use blockdata::block::BlockHeader;
+use consensus::checkpoints::Checkpoints;
+use util::uint::Uint256;
+
+/// A header chain error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The parent of the header is not in the chain.
+    UnknownParent(BlockHash),
+    /// The header is at the height of a checkpoint but is not the checkpointed block.
+    CheckpointMismatch {
+        /// The height of the header.
+        height: u32,
+        /// The hash of the header.
+        hash: BlockHash,
+    },
+    /// The header is on a fork from the active chain that reorganizes a checkpoint.
+    ForkBelowCheckpoint {
+        /// The height of the first block of the fork.
+        height: u32,
+        /// The hash of the header.
+        hash: BlockHash,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownParent(ref hash) => write!(f, "header parent {} is unknown", hash),
+            Error::CheckpointMismatch { height, ref hash } => write!(f, "header {} conflicts with the checkpoint at height {}", hash, height),
+            Error::ForkBelowCheckpoint { height, ref hash } => write!(f, "header {} is on a fork at height {}, below the last checkpoint", hash, height),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {}
+
+/// A header stored in a [`HeaderChain`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HeaderEntry {
+    /// The header.
+    pub header: BlockHeader,
+    /// The height of the header.
+    pub height: u32,
+    /// The total work of the chain up to and including the header.
+    pub chain_work: Uint256,
+}
+
+/// The blocks to disconnect and connect to move from one tip to another.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Reorg {
+    /// The last block both tips have in common.
+    pub fork_point: BlockHash,
+    /// The blocks of the old tip's branch, from the old tip down.
+    pub disconnect: Vec<BlockHash>,
+    /// The blocks of the new tip's branch, from the fork point up.
+    pub connect: Vec<BlockHash>,
+}
+
+/// Headers indexed by hash and, on the chain with the most work, by height.
+///
+/// Headers are not validated beyond connecting to a known parent and matching the checkpoints;
+/// check their proof of work and difficulty before inserting them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HeaderChain {
+    headers: BTreeMap<BlockHash, HeaderEntry>,
+    active: Vec<BlockHash>,
+    checkpoints: Checkpoints,
+}
+
+impl HeaderChain {
+    /// Creates a chain starting at `genesis`.
+    pub fn new(genesis: BlockHeader) -> HeaderChain {
+        HeaderChain::with_checkpoints(genesis, Checkpoints::empty())
+    }
+
+    /// Creates a chain starting at `genesis`, only following chains through `checkpoints`.
+    pub fn with_checkpoints(genesis: BlockHeader, checkpoints: Checkpoints) -> HeaderChain {
+        let hash = genesis.block_hash();
+        let mut headers = BTreeMap::new();
+        let chain_work = genesis.work();
+        headers.insert(hash, HeaderEntry { header: genesis, height: 0, chain_work });
+        HeaderChain { headers, active: vec![hash], checkpoints }
+    }
+
+    /// Returns the header with the most work.
+    pub fn tip(&self) -> &HeaderEntry {
+        &self.headers[self.active.last().expect("chains are not empty")]
+    }
+
+    /// Returns the height of the tip.
+    pub fn height(&self) -> u32 {
+        self.active.len() as u32 - 1
+    }
+
+    /// Returns the number of stored headers, including those off the active chain.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns whether the chain only has its genesis header.
+    pub fn is_empty(&self) -> bool {
+        self.headers.len() == 1
+    }
+
+    /// Returns the header with hash `hash`.
+    pub fn get(&self, hash: &BlockHash) -> Option<&HeaderEntry> {
+        self.headers.get(hash)
+    }
+
+    /// Returns the header at `height` on the active chain.
+    pub fn at_height(&self, height: u32) -> Option<&HeaderEntry> {
+        self.active.get(height as usize).map(|hash| &self.headers[hash])
+    }
+
+    /// Returns whether the header with hash `hash` is on the active chain.
+    pub fn is_active(&self, hash: &BlockHash) -> bool {
+        self.headers.get(hash).map_or(false, |entry| self.active.get(entry.height as usize) == Some(hash))
+    }
+
+    /// Adds `header`, returning the path to the new tip if the header gave the most work chain.
+    ///
+    /// Adding a known header does nothing. Ties in work are resolved in favor of the current tip.
+    pub fn insert(&mut self, header: BlockHeader) -> Result<Option<Reorg>, Error> {
+        let hash = header.block_hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(None);
+        }
+        let (height, chain_work) = match self.headers.get(&header.prev_blockhash) {
+            Some(parent) => (parent.height + 1, parent.chain_work + header.work()),
+            None => return Err(Error::UnknownParent(header.prev_blockhash)),
+        };
+
+        if !self.checkpoints.verify(height, &hash) {
+            return Err(Error::CheckpointMismatch { height, hash });
+        }
+        let tip = self.tip().header.block_hash();
+        if header.prev_blockhash != tip {
+            let fork_point = self.fork_point(&header.prev_blockhash, &tip).expect("both headers are known");
+            let fork_height = self.headers[&fork_point].height + 1;
+            // checkpoints the active chain has not reached yet can't be forked from
+            let tip_height = self.height();
+            if self.checkpoints.iter().any(|(h, _)| *h >= fork_height && *h <= tip_height) {
+                return Err(Error::ForkBelowCheckpoint { height: fork_height, hash });
+            }
+        }
+
+        self.headers.insert(hash, HeaderEntry { header, height, chain_work });
+        if chain_work <= self.tip().chain_work {
+            return Ok(None);
+        }
+        let reorg = self.reorg(&tip, &hash).expect("both tips are known");
+        self.active.truncate(self.headers[&reorg.fork_point].height as usize + 1);
+        self.active.extend_from_slice(&reorg.connect);
+        Ok(Some(reorg))
+    }
+
+    /// Returns the last common ancestor of two headers, `None` if either is unknown.
+    pub fn fork_point(&self, a: &BlockHash, b: &BlockHash) -> Option<BlockHash> {
+        let mut a = self.headers.get(a)?;
+        let mut b = self.headers.get(b)?;
+        while a.height > b.height {
+            a = &self.headers[&a.header.prev_blockhash];
+        }
+        while b.height > a.height {
+            b = &self.headers[&b.header.prev_blockhash];
+        }
+        while a.header.block_hash() != b.header.block_hash() {
+            a = &self.headers[&a.header.prev_blockhash];
+            b = &self.headers[&b.header.prev_blockhash];
+        }
+        Some(a.header.block_hash())
+    }
+
+    /// Returns the blocks to disconnect and connect to move from `from` to `to`, `None` if
+    /// either is unknown.
+    pub fn reorg(&self, from: &BlockHash, to: &BlockHash) -> Option<Reorg> {
+        let fork_point = self.fork_point(from, to)?;
+        let branch = |tip: &BlockHash| {
+            let mut hashes = vec![];
+            let mut hash = *tip;
+            while hash != fork_point {
+                hashes.push(hash);
+                hash = self.headers[&hash].header.prev_blockhash;
+            }
+            hashes
+        };
+        let mut connect = branch(to);
+        connect.reverse();
+        Some(Reorg { fork_point, disconnect: branch(from), connect })
+    }
+
+    /// Returns the ancestor of the header with hash `hash` at `height`, `None` if the header is
+    /// unknown or lower.
+    pub fn ancestor(&self, hash: &BlockHash, height: u32) -> Option<&HeaderEntry> {
+        let mut entry = self.headers.get(hash)?;
+        if entry.height < height {
+            return None;
+        }
+        while entry.height > height {
+            if self.is_active(&entry.header.block_hash()) {
+                return self.at_height(height);
+            }
+            entry = &self.headers[&entry.header.prev_blockhash];
+        }
+        Some(entry)
+    }
+
+    /// Returns the block locator of the header with hash `hash`, as sent in `getheaders`
+    /// messages: the header, its 9 previous ancestors, then ancestors exponentially further
+    /// back down to the genesis. Returns `None` if the header is unknown.
+    pub fn locator(&self, hash: &BlockHash) -> Option<Vec<BlockHash>> {
+        let mut height = self.headers.get(hash)?.height;
+        let mut locator = vec![*hash];
+        let mut step = 1;
+        while height > 0 {
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+            let ancestor = self.ancestor(hash, height).expect("ancestors are known");
+            locator.push(ancestor.header.block_hash());
+        }
+        Some(locator)
+    }
+
+    /// Returns the last header of the active chain in `locator`, the genesis if none is, as a
+    /// peer sending it in a `getheaders` message wants the headers following it.
+    pub fn find_fork(&self, locator: &[BlockHash]) -> &HeaderEntry {
+        locator.iter()
+            .find(|hash| self.is_active(hash))
+            .map_or_else(|| self.at_height(0).expect("chains are not empty"), |hash| &self.headers[hash])
+    }
+
+    /// Returns the blocks to disconnect and connect to move from the chain of `locator` to `to`,
+    /// `None` if `to` or all the locator hashes are unknown.
+    ///
+    /// The first known locator hash is taken as the tip of the locator's chain, so blocks of
+    /// that chain unknown to this one are missing from [`Reorg::disconnect`].
+    pub fn reorg_from_locator(&self, locator: &[BlockHash], to: &BlockHash) -> Option<Reorg> {
+        let from = locator.iter().find(|&hash| self.headers.contains_key(hash))?;
+        self.reorg(from, to)
+    }
+}
+
+/// Returns the last block two block locators have in common, `None` if they have none.
+///
+/// Locators only list some of the ancestors of their tip, so the actual fork point of their
+/// chains may be higher; a [`HeaderChain`] knowing both chains finds the exact one.
+pub fn locator_fork_point(a: &[BlockHash], b: &[BlockHash]) -> Option<BlockHash> {
+    let b: BTreeSet<&BlockHash> = b.iter().collect();
+    a.iter().find(|hash| b.contains(hash)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use network::constants::Network;
+
+    fn child(parent: &BlockHeader, nonce: u32) -> BlockHeader {
+        BlockHeader { prev_blockhash: parent.block_hash(), nonce, ..parent.clone() }
+    }
+
+    #[test]
+    fn header_chain() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(genesis.clone());
+        assert!(chain.is_empty());
+
+        let a1 = child(&genesis, 1);
+        let a2 = child(&a1, 2);
+        let b2 = child(&a1, 3);
+        let b3 = child(&b2, 4);
+
+        let reorg = chain.insert(a1.clone()).unwrap().unwrap();
+        assert_eq!(reorg, Reorg { fork_point: genesis.block_hash(), disconnect: vec![], connect: vec![a1.block_hash()] });
+        chain.insert(a2.clone()).unwrap().unwrap();
+        assert_eq!(chain.insert(a2.clone()), Ok(None));
+        // equal work doesn't move the tip
+        assert_eq!(chain.insert(b2.clone()), Ok(None));
+        assert_eq!(chain.tip().header, a2);
+        assert!(!chain.is_active(&b2.block_hash()));
+        assert_eq!(chain.fork_point(&a2.block_hash(), &b2.block_hash()), Some(a1.block_hash()));
+
+        let reorg = chain.insert(b3.clone()).unwrap().unwrap();
+        assert_eq!(reorg, Reorg {
+            fork_point: a1.block_hash(),
+            disconnect: vec![a2.block_hash()],
+            connect: vec![b2.block_hash(), b3.block_hash()],
+        });
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.len(), 5);
+        assert_eq!(chain.at_height(2).unwrap().header, b2);
+        assert_eq!(chain.get(&a2.block_hash()).unwrap().height, 2);
+        assert_eq!(chain.tip().chain_work, genesis.work() + genesis.work() + genesis.work() + genesis.work());
+        assert_eq!(chain.reorg(&b3.block_hash(), &a2.block_hash()).unwrap().disconnect, vec![b3.block_hash(), b2.block_hash()]);
+
+        let orphan = child(&child(&b3, 5), 6);
+        assert_eq!(chain.insert(orphan.clone()), Err(Error::UnknownParent(orphan.prev_blockhash)));
+    }
+
+    #[test]
+    fn locators() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(genesis.clone());
+        let mut active = vec![genesis.clone()];
+        for nonce in 1..=30 {
+            let header = child(active.last().unwrap(), nonce);
+            chain.insert(header.clone()).unwrap();
+            active.push(header);
+        }
+        // a fork of 3 blocks at height 21
+        let mut fork = vec![child(&active[20], 100)];
+        for nonce in 101..103 {
+            let header = child(fork.last().unwrap(), nonce);
+            fork.push(header);
+        }
+        for header in &fork {
+            assert_eq!(chain.insert(header.clone()), Ok(None));
+        }
+        let tip = active[30].block_hash();
+        let fork_tip = fork[2].block_hash();
+
+        let heights: Vec<u32> = chain.locator(&tip).unwrap().iter().map(|hash| chain.get(hash).unwrap().height).collect();
+        assert_eq!(heights, vec![30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 19, 15, 7, 0]);
+        let locator = chain.locator(&fork_tip).unwrap();
+        let heights: Vec<u32> = locator.iter().map(|hash| chain.get(hash).unwrap().height).collect();
+        assert_eq!(heights, vec![23, 22, 21, 20, 19, 18, 17, 16, 15, 14, 12, 8, 0]);
+        assert_eq!(locator[3], active[20].block_hash());
+        assert_eq!(chain.locator(&genesis.block_hash()), Some(vec![genesis.block_hash()]));
+        assert_eq!(chain.locator(&BlockHash::default()), None);
+        assert_eq!(chain.ancestor(&fork_tip, 22).unwrap().header, fork[1]);
+        assert_eq!(chain.ancestor(&fork_tip, 5).unwrap().header, active[5]);
+        assert!(chain.ancestor(&fork_tip, 24).is_none());
+
+        assert_eq!(chain.find_fork(&locator).header, active[20]);
+        assert_eq!(chain.find_fork(&[BlockHash::default()]).header, genesis);
+        assert_eq!(locator_fork_point(&locator, &chain.locator(&tip).unwrap()), Some(active[19].block_hash()));
+        assert_eq!(locator_fork_point(&locator, &[BlockHash::default()]), None);
+
+        let reorg = chain.reorg_from_locator(&locator, &tip).unwrap();
+        assert_eq!(reorg.fork_point, active[20].block_hash());
+        assert_eq!(reorg.disconnect, fork.iter().rev().map(BlockHeader::block_hash).collect::<Vec<_>>());
+        assert_eq!(reorg.connect, active[21..].iter().map(BlockHeader::block_hash).collect::<Vec<_>>());
+        assert_eq!(chain.reorg_from_locator(&[BlockHash::default()], &tip), None);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let a1 = child(&genesis, 1);
+        let a2 = child(&a1, 2);
+        let b1 = child(&genesis, 3);
+        let b2 = child(&a1, 4);
+        let c1 = child(&genesis, 5);
+
+        let checkpoints = vec![(0, genesis.block_hash()), (1, a1.block_hash())].into_iter().collect();
+        let mut chain = HeaderChain::with_checkpoints(genesis.clone(), checkpoints);
+        assert_eq!(chain.insert(b1.clone()), Err(Error::CheckpointMismatch { height: 1, hash: b1.block_hash() }));
+        chain.insert(a1.clone()).unwrap();
+        chain.insert(a2.clone()).unwrap();
+        // forks above the last checkpoint are fine
+        assert_eq!(chain.insert(b2.clone()), Ok(None));
+
+        let checkpoints = vec![(2, a2.block_hash())].into_iter().collect();
+        let mut chain = HeaderChain::with_checkpoints(genesis.clone(), checkpoints);
+        chain.insert(a1.clone()).unwrap();
+        // the checkpoint has not been reached yet
+        assert_eq!(chain.insert(b1.clone()), Ok(None));
+        chain.insert(a2.clone()).unwrap();
+        assert_eq!(chain.insert(c1.clone()), Err(Error::ForkBelowCheckpoint { height: 1, hash: c1.block_hash() }));
+        assert!(chain.get(&c1.block_hash()).is_none());
+    }
+}
@@ -0,0 +1,34 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Chain state.
+//!
+//! This module contains the types describing a node's view of the chain
+//! and the on-disk formats it is stored in.
+//!
+
+pub mod blockfile;
+pub mod coins;
+pub mod compress;
+pub mod headers;
+pub mod snapshot;
+pub mod stats;
+pub mod undo;
+
+pub use self::blockfile::{BlockFileReader, XorReader};
+pub use self::coins::{Coin, CoinsView, MemoryCoinsView};
+pub use self::headers::{HeaderChain, Reorg};
+pub use self::snapshot::SnapshotMetadata;
+pub use self::stats::BlockStats;
+pub use self::undo::{BlockUndo, TxUndo};
@@ -0,0 +1,122 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! UTXO snapshots.
+//!
+//! This module implements the metadata heading the UTXO set snapshots
+//! written by Bitcoin Core's `dumptxoutset` and loaded through
+//! `loadtxoutset` to bootstrap a node with AssumeUTXO.
+//!
+//! The metadata is followed by `coins_count` coins grouped by transaction:
+//! the txid, the number of its unspent outputs as a compact size, then for
+//! each output its index as a compact size followed by the coin in the
+//! compressed chainstate format.
+//!
+
+use io;
+
+use hash_types::BlockHash;
+use consensus::encode::{self, Decodable, Encodable, ReadExt, WriteExt};
+use network::constants::Network;
+
+/// The magic bytes every snapshot starts with.
+pub const SNAPSHOT_MAGIC: [u8; 5] = [b'u', b't', b'x', b'o', 0xff];
+
+/// The snapshot format version written by this library.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+/// The metadata heading a UTXO set snapshot.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SnapshotMetadata {
+    /// The magic of the network the snapshot was taken on.
+    pub network_magic: u32,
+    /// The hash of the block the snapshot reflects the UTXO set at.
+    pub base_blockhash: BlockHash,
+    /// The number of coins in the snapshot.
+    pub coins_count: u64,
+}
+
+impl SnapshotMetadata {
+    /// Creates the metadata of a snapshot of `network` at `base_blockhash`.
+    pub fn new(network: Network, base_blockhash: BlockHash, coins_count: u64) -> SnapshotMetadata {
+        SnapshotMetadata {
+            network_magic: network.magic(),
+            base_blockhash,
+            coins_count,
+        }
+    }
+
+    /// Returns the network the snapshot was taken on, if known.
+    pub fn network(&self) -> Option<Network> {
+        Network::from_magic(self.network_magic)
+    }
+}
+
+impl Encodable for SnapshotMetadata {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        w.emit_slice(&SNAPSHOT_MAGIC)?;
+        w.emit_u16(SNAPSHOT_VERSION)?;
+        let mut len = SNAPSHOT_MAGIC.len() + 2;
+        len += self.network_magic.consensus_encode(w)?;
+        len += self.base_blockhash.consensus_encode(w)?;
+        len += self.coins_count.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for SnapshotMetadata {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let mut magic = [0u8; 5];
+        r.read_slice(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(encode::Error::ParseFailed("invalid UTXO snapshot magic bytes"));
+        }
+        if r.read_u16()? != SNAPSHOT_VERSION {
+            return Err(encode::Error::ParseFailed("unsupported UTXO snapshot version"));
+        }
+        Ok(SnapshotMetadata {
+            network_magic: Decodable::consensus_decode(r)?,
+            base_blockhash: Decodable::consensus_decode(r)?,
+            coins_count: Decodable::consensus_decode(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::{FromHex, ToHex};
+    use consensus::encode::{deserialize, serialize};
+
+    #[test]
+    fn snapshot_metadata() {
+        let hash = BlockHash::from_hex("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943").unwrap();
+        let metadata = SnapshotMetadata::new(Network::Testnet, hash, 0x0102);
+        let encoded = serialize(&metadata);
+        assert_eq!(
+            encoded.to_hex(),
+            "7574786fff02000b11090743497fd7f826957108f4a30fd9cec3aeba79972084e90ead01ea330900000000\
+             0201000000000000"
+        );
+        assert_eq!(deserialize::<SnapshotMetadata>(&encoded).unwrap(), metadata);
+        assert_eq!(metadata.network(), Some(Network::Testnet));
+
+        let mut bad = encoded.clone();
+        bad[0] = b'U';
+        assert!(deserialize::<SnapshotMetadata>(&bad).is_err());
+        let mut bad = encoded;
+        bad[5] = 1;
+        assert!(deserialize::<SnapshotMetadata>(&bad).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Block statistics.
+//!
+//! This module computes the statistics Bitcoin Core's `getblockstats` RPC
+//! returns, from a block and the coins it spent, as found in its undo data.
+//!
+
+use prelude::*;
+
+use core::fmt;
+#[cfg(feature = "std")] use std::error;
+
+use hash_types::Txid;
+use blockdata::block::Block;
+use blockdata::transaction::Transaction;
+use chain::undo::{BlockUndo, TxUndo};
+use network::constants::Network;
+use util::address::{Address, AddressType};
+use util::amount::Amount;
+use util::fee_rate::FeeRate;
+use util::weight::Weight;
+
+/// The percentiles of [`BlockStats::fee_rate_percentiles`], as fractions.
+const PERCENTILES: [(u64, u64); 5] = [(1, 10), (1, 4), (1, 2), (3, 4), (9, 10)];
+
+/// Error computing the statistics of a block.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The undo data doesn't hold one entry per transaction except the coinbase.
+    TransactionCountMismatch {
+        /// Number of non-coinbase transactions in the block
+        expected: usize,
+        /// Number of transactions in the undo data
+        found: usize,
+    },
+    /// The undo data of a transaction doesn't hold one coin per input.
+    InputCountMismatch(Txid),
+    /// A transaction spends more than its inputs are worth.
+    NegativeFee(Txid),
+    /// The input or output values of a transaction, or the totals including them, overflow.
+    ValueOverflow(Txid),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TransactionCountMismatch { expected, found } => write!(f, "undo data has {} transactions, expected {}", found, expected),
+            Error::InputCountMismatch(ref txid) => write!(f, "undo data of transaction {} doesn't match its inputs", txid),
+            Error::NegativeFee(ref txid) => write!(f, "transaction {} spends more than its inputs", txid),
+            Error::ValueOverflow(ref txid) => write!(f, "values of transaction {} overflow", txid),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {}
+
+/// Statistics of a block, as returned by Bitcoin Core's `getblockstats` RPC.
+///
+/// Like Bitcoin Core, the totals and fee statistics leave out the coinbase transaction, and fee
+/// statistics of a block without other transactions are zero.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockStats {
+    /// Number of transactions, including the coinbase.
+    pub txs: usize,
+    /// Number of inputs, excluding the coinbase input.
+    pub ins: usize,
+    /// Number of outputs, including the coinbase outputs.
+    pub outs: usize,
+    /// Weight of the block.
+    pub weight: Weight,
+    /// Total size of the transactions.
+    pub total_size: usize,
+    /// Total weight of the transactions.
+    pub total_weight: Weight,
+    /// Total value of the outputs of the transactions.
+    pub total_out: Amount,
+    /// Total fee paid by the transactions.
+    pub total_fee: Amount,
+    /// Lowest fee paid by a transaction.
+    pub min_fee: Amount,
+    /// Highest fee paid by a transaction.
+    pub max_fee: Amount,
+    /// Lowest fee rate paid by a transaction.
+    pub min_fee_rate: FeeRate,
+    /// Highest fee rate paid by a transaction.
+    pub max_fee_rate: FeeRate,
+    /// Total fee divided by total weight.
+    pub avg_fee_rate: FeeRate,
+    /// Fee rates at the 10th, 25th, 50th, 75th and 90th percentiles, by weight.
+    pub fee_rate_percentiles: [FeeRate; 5],
+    /// Number of transactions with witness data.
+    pub segwit_txs: usize,
+    /// Total size of the transactions with witness data.
+    pub segwit_total_size: usize,
+    /// Total weight of the transactions with witness data.
+    pub segwit_total_weight: Weight,
+    /// Number of outputs of each standard address type, including the coinbase outputs.
+    pub output_types: BTreeMap<AddressType, usize>,
+    /// Number of `OP_RETURN` outputs, including the coinbase outputs.
+    pub op_return_outs: usize,
+    /// Number of other outputs, e.g. bare multisig or pay-to-pubkey, including the coinbase
+    /// outputs.
+    pub other_outs: usize,
+}
+
+impl BlockStats {
+    /// Computes the statistics of `block`, given the coins it spent.
+    pub fn from_block(block: &Block, prevouts: &BlockUndo) -> Result<BlockStats, Error> {
+        let txs = block.txdata.iter().skip(1);
+        let expected = block.txdata.len().saturating_sub(1);
+        if prevouts.txundo.len() != expected {
+            return Err(Error::TransactionCountMismatch { expected, found: prevouts.txundo.len() });
+        }
+
+        let mut stats = BlockStats {
+            txs: block.txdata.len(),
+            ins: 0,
+            outs: 0,
+            weight: block.weight(),
+            total_size: 0,
+            total_weight: Weight::ZERO,
+            total_out: Amount::ZERO,
+            total_fee: Amount::ZERO,
+            min_fee: Amount::ZERO,
+            max_fee: Amount::ZERO,
+            min_fee_rate: FeeRate::ZERO,
+            max_fee_rate: FeeRate::ZERO,
+            avg_fee_rate: FeeRate::ZERO,
+            fee_rate_percentiles: [FeeRate::ZERO; 5],
+            segwit_txs: 0,
+            segwit_total_size: 0,
+            segwit_total_weight: Weight::ZERO,
+            output_types: BTreeMap::new(),
+            op_return_outs: 0,
+            other_outs: 0,
+        };
+        if let Some(coinbase) = block.txdata.first() {
+            stats.count_outputs(coinbase);
+        }
+
+        let mut fee_rates = Vec::with_capacity(expected);
+        for (tx, undo) in txs.zip(&prevouts.txundo) {
+            let fee = fee(tx, undo)?;
+            let out = sum_values(tx.output.iter().map(|out| out.value)).ok_or_else(|| Error::ValueOverflow(tx.txid()))?;
+            let weight = tx.weight();
+            let fee_rate = FeeRate::from_fee_and_weight(fee, weight).unwrap_or(FeeRate::ZERO);
+            if fee_rates.is_empty() {
+                stats.min_fee = fee;
+                stats.min_fee_rate = fee_rate;
+            }
+            fee_rates.push((fee_rate, weight));
+
+            stats.ins += tx.input.len();
+            stats.total_size += tx.size();
+            stats.total_weight += weight;
+            stats.total_out = stats.total_out.checked_add(Amount::from_sat(out)).ok_or_else(|| Error::ValueOverflow(tx.txid()))?;
+            stats.total_fee = stats.total_fee.checked_add(fee).ok_or_else(|| Error::ValueOverflow(tx.txid()))?;
+            stats.min_fee = stats.min_fee.min(fee);
+            stats.max_fee = stats.max_fee.max(fee);
+            stats.min_fee_rate = stats.min_fee_rate.min(fee_rate);
+            stats.max_fee_rate = stats.max_fee_rate.max(fee_rate);
+            if tx.input.iter().any(|input| !input.witness.is_empty()) {
+                stats.segwit_txs += 1;
+                stats.segwit_total_size += tx.size();
+                stats.segwit_total_weight += weight;
+            }
+            stats.count_outputs(tx);
+        }
+        stats.avg_fee_rate = FeeRate::from_fee_and_weight(stats.total_fee, stats.total_weight).unwrap_or(FeeRate::ZERO);
+        stats.fee_rate_percentiles = percentiles(fee_rates, stats.total_weight);
+        Ok(stats)
+    }
+
+    /// Returns the fraction of the transactions, excluding the coinbase, with witness data.
+    pub fn segwit_ratio(&self) -> f64 {
+        if self.txs <= 1 {
+            return 0.0;
+        }
+        self.segwit_txs as f64 / (self.txs - 1) as f64
+    }
+
+    fn count_outputs(&mut self, tx: &Transaction) {
+        self.outs += tx.output.len();
+        for output in &tx.output {
+            let script = &output.script_pubkey;
+            // the network doesn't change the type of an address
+            match Address::from_script(script, Network::Bitcoin).and_then(|address| address.address_type()) {
+                Some(address_type) => *self.output_types.entry(address_type).or_insert(0) += 1,
+                None if script.is_op_return() => self.op_return_outs += 1,
+                None => self.other_outs += 1,
+            }
+        }
+    }
+}
+
+/// Returns the fee paid by `tx`, spending the coins of `undo`.
+fn fee(tx: &Transaction, undo: &TxUndo) -> Result<Amount, Error> {
+    if undo.prevouts.len() != tx.input.len() {
+        return Err(Error::InputCountMismatch(tx.txid()));
+    }
+    let input = sum_values(undo.prevouts.iter().map(|coin| coin.txout.value));
+    let output = sum_values(tx.output.iter().map(|out| out.value));
+    let (input, output) = match (input, output) {
+        (Some(input), Some(output)) => (input, output),
+        _ => return Err(Error::ValueOverflow(tx.txid())),
+    };
+    input.checked_sub(output).map(Amount::from_sat).ok_or_else(|| Error::NegativeFee(tx.txid()))
+}
+
+/// Returns the sum of `values`, `None` on overflow.
+fn sum_values<I: Iterator<Item = u64>>(mut values: I) -> Option<u64> {
+    values.try_fold(0u64, u64::checked_add)
+}
+
+/// Computes the fee rate percentiles the way Bitcoin Core does: the fee rate of the transaction
+/// reaching each percentile of the total weight, once sorted by fee rate.
+fn percentiles(mut fee_rates: Vec<(FeeRate, Weight)>, total_weight: Weight) -> [FeeRate; 5] {
+    let mut result = [FeeRate::ZERO; 5];
+    fee_rates.sort();
+    let last = match fee_rates.last() {
+        Some(&(fee_rate, _)) => fee_rate,
+        None => return result,
+    };
+
+    let mut next = 0;
+    let mut cumulative_weight = Weight::ZERO;
+    for &(fee_rate, weight) in &fee_rates {
+        cumulative_weight += weight;
+        while next < PERCENTILES.len() {
+            let (num, den) = PERCENTILES[next];
+            if cumulative_weight * den < total_weight * num {
+                break;
+            }
+            result[next] = fee_rate;
+            next += 1;
+        }
+    }
+    for fee_rate in &mut result[next..] {
+        *fee_rate = last;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use blockdata::opcodes::all::OP_RETURN;
+    use blockdata::script::{Builder, Script};
+    use blockdata::transaction::{OutPoint, TxIn, TxOut};
+    use blockdata::witness::Witness;
+    use chain::coins::Coin;
+    use hashes::Hash;
+    use hash_types::{PubkeyHash, WPubkeyHash};
+
+    fn spend(vout: u32, witness: Vec<Vec<u8>>, outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_inner([1; 32]), vout),
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: Witness::from_vec(witness),
+            }],
+            output: outputs,
+        }
+    }
+
+    fn undo(value: u64) -> TxUndo {
+        TxUndo { prevouts: vec![Coin::new(TxOut { value, script_pubkey: Script::new() }, 1, false)] }
+    }
+
+    #[test]
+    fn block_stats() {
+        let mut block = genesis_block(Network::Regtest);
+        let p2pkh = Script::new_p2pkh(&PubkeyHash::from_inner([2; 20]));
+        let p2wpkh = Script::new_v0_p2wpkh(&WPubkeyHash::from_inner([3; 20]));
+        let op_return = Builder::new().push_opcode(OP_RETURN).push_slice(b"stats").into_script();
+        let legacy = spend(0, vec![], vec![TxOut { value: 9_000, script_pubkey: p2wpkh.clone() }]);
+        let segwit = spend(1, vec![vec![4; 72], vec![5; 33]], vec![
+            TxOut { value: 40_000, script_pubkey: p2pkh.clone() },
+            TxOut { value: 0, script_pubkey: op_return },
+        ]);
+        block.txdata.push(legacy.clone());
+        block.txdata.push(segwit.clone());
+        let prevouts = BlockUndo { txundo: vec![undo(10_000), undo(50_000)] };
+
+        let stats = BlockStats::from_block(&block, &prevouts).unwrap();
+        assert_eq!(stats.txs, 3);
+        assert_eq!(stats.ins, 2);
+        assert_eq!(stats.outs, 4);
+        assert_eq!(stats.weight, block.weight());
+        assert_eq!(stats.total_size, legacy.size() + segwit.size());
+        assert_eq!(stats.total_weight, legacy.weight() + segwit.weight());
+        assert_eq!(stats.total_out, Amount::from_sat(49_000));
+        assert_eq!(stats.total_fee, Amount::from_sat(11_000));
+        assert_eq!(stats.min_fee, Amount::from_sat(1_000));
+        assert_eq!(stats.max_fee, Amount::from_sat(10_000));
+
+        let legacy_rate = FeeRate::from_fee_and_weight(Amount::from_sat(1_000), legacy.weight()).unwrap();
+        let segwit_rate = FeeRate::from_fee_and_weight(Amount::from_sat(10_000), segwit.weight()).unwrap();
+        assert_eq!(stats.min_fee_rate, legacy_rate);
+        assert_eq!(stats.max_fee_rate, segwit_rate);
+        assert_eq!(stats.avg_fee_rate, FeeRate::from_fee_and_weight(Amount::from_sat(11_000), stats.total_weight).unwrap());
+        // the legacy transaction weighs between a quarter and half of the total
+        assert!(legacy.weight() * 4 >= stats.total_weight && legacy.weight() * 2 < stats.total_weight);
+        assert_eq!(stats.fee_rate_percentiles, [legacy_rate, legacy_rate, segwit_rate, segwit_rate, segwit_rate]);
+
+        assert_eq!(stats.segwit_txs, 1);
+        assert_eq!(stats.segwit_total_weight, segwit.weight());
+        assert_eq!(stats.segwit_ratio(), 0.5);
+
+        // the genesis coinbase pays to a public key
+        assert_eq!(stats.output_types.get(&AddressType::P2pkh), Some(&1));
+        assert_eq!(stats.output_types.get(&AddressType::P2wpkh), Some(&1));
+        assert_eq!(stats.op_return_outs, 1);
+        assert_eq!(stats.other_outs, 1);
+    }
+
+    #[test]
+    fn block_stats_errors() {
+        let mut block = genesis_block(Network::Regtest);
+        let stats = BlockStats::from_block(&block, &BlockUndo::default()).unwrap();
+        assert_eq!(stats.txs, 1);
+        assert_eq!(stats.total_fee, Amount::ZERO);
+        assert_eq!(stats.fee_rate_percentiles, [FeeRate::ZERO; 5]);
+        assert_eq!(stats.segwit_ratio(), 0.0);
+
+        let tx = spend(0, vec![], vec![TxOut { value: 2_000, script_pubkey: Script::new() }]);
+        block.txdata.push(tx.clone());
+        assert_eq!(
+            BlockStats::from_block(&block, &BlockUndo::default()),
+            Err(Error::TransactionCountMismatch { expected: 1, found: 0 })
+        );
+        assert_eq!(
+            BlockStats::from_block(&block, &BlockUndo { txundo: vec![TxUndo::default()] }),
+            Err(Error::InputCountMismatch(tx.txid()))
+        );
+        assert_eq!(
+            BlockStats::from_block(&block, &BlockUndo { txundo: vec![undo(1_000)] }),
+            Err(Error::NegativeFee(tx.txid()))
+        );
+
+        let overflow = spend(0, vec![], vec![
+            TxOut { value: u64::max_value(), script_pubkey: Script::new() },
+            TxOut { value: 1, script_pubkey: Script::new() },
+        ]);
+        block.txdata[1] = overflow.clone();
+        assert_eq!(
+            BlockStats::from_block(&block, &BlockUndo { txundo: vec![undo(1_000)] }),
+            Err(Error::ValueOverflow(overflow.txid()))
+        );
+
+        // each fee fits but the total doesn't
+        let half = u64::max_value() / 2 + 1;
+        block.txdata[1] = tx.clone();
+        block.txdata.push(spend(1, vec![], vec![]));
+        let prevouts = BlockUndo { txundo: vec![undo(half + 2_000), undo(half)] };
+        assert_eq!(BlockStats::from_block(&block, &prevouts), Err(Error::ValueOverflow(block.txdata[2].txid())));
+    }
+}
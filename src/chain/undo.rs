@@ -0,0 +1,151 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Block undo data.
+//!
+//! This module implements the undo data Bitcoin Core keeps in its `rev*.dat`
+//! files: the coins each block spent, which are needed to disconnect it.
+//!
+
+use prelude::*;
+
+use io;
+
+use hashes::{sha256d, Hash, HashEngine};
+use hash_types::BlockHash;
+use consensus::encode::{self, Decodable, Encodable, VarInt};
+use chain::coins::Coin;
+use chain::compress::{self, CompressedTxOut};
+
+/// The coins spent by the inputs of a transaction, in input order.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TxUndo {
+    /// The spent coins.
+    pub prevouts: Vec<Coin>,
+}
+
+/// The coins spent by a block, one [`TxUndo`] per transaction except the coinbase.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct BlockUndo {
+    /// The undo data of the block's transactions, in block order.
+    pub txundo: Vec<TxUndo>,
+}
+
+impl BlockUndo {
+    /// Computes the checksum following the undo data in a `rev*.dat` record, which commits
+    /// to the hash of the block's parent.
+    pub fn checksum(&self, prev_blockhash: &BlockHash) -> sha256d::Hash {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(&prev_blockhash[..]);
+        self.consensus_encode(&mut engine).expect("engines don't error");
+        sha256d::Hash::from_engine(engine)
+    }
+}
+
+fn encode_coin<W: io::Write + ?Sized>(coin: &Coin, w: &mut W) -> Result<usize, io::Error> {
+    let mut len = compress::write_varint(w, coin.height as u64 * 2 + coin.is_coinbase as u64)?;
+    if coin.height > 0 {
+        // Older versions stored the version of the spent transaction, it is kept for compatibility.
+        len += compress::write_varint(w, 0)?;
+    }
+    len += compress::write_amount(w, coin.txout.value)?;
+    len += compress::write_script(w, &coin.txout.script_pubkey)?;
+    Ok(len)
+}
+
+fn decode_coin<R: io::Read + ?Sized>(r: &mut R) -> Result<Coin, encode::Error> {
+    let code = compress::read_varint(r)?;
+    if code > u32::max_value() as u64 {
+        return Err(encode::Error::ParseFailed("coin height out of range"));
+    }
+    let height = (code >> 1) as u32;
+    if height > 0 {
+        compress::read_varint(r)?;
+    }
+    let txout = CompressedTxOut::consensus_decode(r)?.0;
+    Ok(Coin::new(txout, height, code & 1 == 1))
+}
+
+impl Encodable for TxUndo {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = VarInt(self.prevouts.len() as u64).consensus_encode(w)?;
+        for coin in &self.prevouts {
+            len += encode_coin(coin, w)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for TxUndo {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let count = VarInt::consensus_decode(r)?.0;
+        let mut prevouts = Vec::new();
+        for _ in 0..count {
+            prevouts.push(decode_coin(r)?);
+        }
+        Ok(TxUndo { prevouts })
+    }
+}
+
+impl Encodable for BlockUndo {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = VarInt(self.txundo.len() as u64).consensus_encode(w)?;
+        for txundo in &self.txundo {
+            len += txundo.consensus_encode(w)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for BlockUndo {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let count = VarInt::consensus_decode(r)?.0;
+        let mut txundo = Vec::new();
+        for _ in 0..count {
+            txundo.push(TxUndo::consensus_decode(r)?);
+        }
+        Ok(BlockUndo { txundo })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::{FromHex, ToHex};
+    use blockdata::script::Script;
+    use blockdata::transaction::TxOut;
+    use consensus::encode::{deserialize, serialize};
+
+    #[test]
+    fn block_undo() {
+        let p2pkh = Script::from(Vec::from_hex("76a914000102030405060708090a0b0c0d0e0f1011121388ac").unwrap());
+        let undo = BlockUndo {
+            txundo: vec![TxUndo {
+                prevouts: vec![
+                    Coin::new(TxOut { value: 5_000_000_000, script_pubkey: p2pkh }, 1, true),
+                    Coin::new(TxOut { value: 1, script_pubkey: Script::from(vec![0x51]) }, 0, false),
+                ],
+            }],
+        };
+        let encoded = serialize(&undo);
+        assert_eq!(encoded.to_hex(), "010203003200000102030405060708090a0b0c0d0e0f1011121300010751");
+        assert_eq!(deserialize::<BlockUndo>(&encoded).unwrap(), undo);
+
+        let prev = BlockHash::from_hex("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943").unwrap();
+        let mut data = prev[..].to_vec();
+        data.extend_from_slice(&encoded);
+        assert_eq!(undo.checksum(&prev), sha256d::Hash::hash(&data));
+        assert!(deserialize::<BlockUndo>(&encoded[..encoded.len() - 1]).is_err());
+    }
+}
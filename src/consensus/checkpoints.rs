@@ -0,0 +1,119 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Checkpoints.
+//!
+//! This module provides the hard-coded block hashes a chain must pass
+//! through, which header sync uses to reject forks burying known history.
+//!
+
+use prelude::*;
+
+use core::iter::FromIterator;
+
+use hash_types::BlockHash;
+use blockdata::constants::genesis_block;
+use network::constants::Network;
+
+/// Known blocks of a chain, keyed by height.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Checkpoints(BTreeMap<u32, BlockHash>);
+
+impl Checkpoints {
+    /// Creates an empty checkpoint set.
+    pub fn empty() -> Checkpoints {
+        Checkpoints(BTreeMap::new())
+    }
+
+    /// Returns the checkpoints of `network`, currently only its genesis block.
+    pub fn new(network: Network) -> Checkpoints {
+        let mut checkpoints = Checkpoints::empty();
+        checkpoints.insert(0, genesis_block(network).block_hash());
+        checkpoints
+    }
+
+    /// Adds a checkpoint, returning the hash previously checkpointed at `height` if any.
+    pub fn insert(&mut self, height: u32, hash: BlockHash) -> Option<BlockHash> {
+        self.0.insert(height, hash)
+    }
+
+    /// Returns the hash checkpointed at `height`.
+    pub fn get(&self, height: u32) -> Option<&BlockHash> {
+        self.0.get(&height)
+    }
+
+    /// Returns the highest checkpoint.
+    pub fn last(&self) -> Option<(u32, BlockHash)> {
+        self.0.iter().next_back().map(|(height, hash)| (*height, *hash))
+    }
+
+    /// Iterates over the checkpoints in ascending height order.
+    pub fn iter(&self) -> btree_map::Iter<'_, u32, BlockHash> {
+        self.0.iter()
+    }
+
+    /// Checks a block at `height` against the checkpoints, returning false only if a different
+    /// block is checkpointed at that height.
+    pub fn verify(&self, height: u32, hash: &BlockHash) -> bool {
+        self.get(height).map_or(true, |checkpoint| checkpoint == hash)
+    }
+
+    /// Returns whether a fork whose first block is at `height` may be accepted.
+    ///
+    /// Header sync must reject forks that would reorganize the last checkpoint or anything
+    /// below it, regardless of the work they carry.
+    pub fn allows_fork_at(&self, height: u32) -> bool {
+        self.last().map_or(true, |(last, _)| height > last)
+    }
+}
+
+impl FromIterator<(u32, BlockHash)> for Checkpoints {
+    fn from_iter<I: IntoIterator<Item = (u32, BlockHash)>>(iter: I) -> Self {
+        Checkpoints(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hashes::Hash;
+
+    #[test]
+    fn checkpoints() {
+        let genesis = genesis_block(Network::Testnet).block_hash();
+        let mut checkpoints = Checkpoints::new(Network::Testnet);
+        assert_eq!(checkpoints.last(), Some((0, genesis)));
+        assert_eq!(checkpoints.insert(546, BlockHash::from_inner([1; 32])), None);
+        let (height, hash) = checkpoints.last().unwrap();
+        assert_eq!(height, 546);
+        assert_eq!(checkpoints.iter().count(), 2);
+
+        assert!(checkpoints.verify(0, &genesis));
+        assert!(checkpoints.verify(546, &hash));
+        assert!(!checkpoints.verify(546, &genesis));
+        // heights without a checkpoint accept any block
+        assert!(checkpoints.verify(545, &genesis));
+
+        assert!(!checkpoints.allows_fork_at(1));
+        assert!(!checkpoints.allows_fork_at(546));
+        assert!(checkpoints.allows_fork_at(547));
+        assert!(Checkpoints::empty().allows_fork_at(0));
+
+        let regtest = Checkpoints::new(Network::Regtest);
+        assert_eq!(regtest.last(), Some((0, genesis_block(Network::Regtest).block_hash())));
+        let collected: Checkpoints = regtest.iter().map(|(h, b)| (*h, *b)).collect();
+        assert_eq!(collected, regtest);
+    }
+}
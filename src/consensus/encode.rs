@@ -30,6 +30,7 @@
 use prelude::*;
 
 use core::{fmt, mem, u32, convert::From};
+use core::marker::PhantomData;
 #[cfg(feature = "std")] use std::error;
 
 use hashes::{sha256d, Hash, sha256};
@@ -38,13 +39,16 @@ use hash_types::{BlockHash, FilterHash, TxMerkleNode, FilterHeader};
 use io::{self, Cursor, Read};
 
 use util::endian;
+use util::hash;
 use util::psbt;
 use util::taproot::TapLeafHash;
-use hashes::hex::ToHex;
+use hashes::hex::{self, FromHex, ToHex};
 
-use blockdata::transaction::{TxOut, Transaction, TxIn};
+use blockdata::transaction::{OutPoint, TxOut, Transaction, TxIn};
 #[cfg(feature = "std")]
-use network::{message_blockdata::Inventory, address::{Address, AddrV2Message}};
+use network::{message_blockdata::Inventory, message_utxo::Utxo, address::{Address, AddrV2Message}};
+#[cfg(feature = "std")]
+use network::message_compact_blocks::{PrefilledTransaction, ShortId};
 
 /// Encoding error
 #[derive(Debug)]
@@ -82,6 +86,8 @@ pub enum Error {
     ParseFailed(&'static str),
     /// Unsupported Segwit flag
     UnsupportedSegwitFlag(u8),
+    /// Invalid hex string
+    Hex(hex::Error),
 }
 
 impl fmt::Display for Error {
@@ -100,6 +106,7 @@ impl fmt::Display for Error {
             Error::ParseFailed(ref e) => write!(f, "parse failed: {}", e),
             Error::UnsupportedSegwitFlag(ref swflag) => write!(f,
                 "unsupported segwit version: {}", swflag),
+            Error::Hex(ref e) => write!(f, "hex error: {}", e),
         }
     }
 }
@@ -111,6 +118,7 @@ impl ::std::error::Error for Error {
         match *self {
             Error::Io(ref e) => Some(e),
             Error::Psbt(ref e) => Some(e),
+            Error::Hex(ref e) => Some(e),
             Error::UnexpectedNetworkMagic { .. }
             | Error::OversizedVectorAllocation { .. }
             | Error::InvalidChecksum { .. }
@@ -136,6 +144,13 @@ impl From<psbt::Error> for Error {
     }
 }
 
+#[doc(hidden)]
+impl From<hex::Error> for Error {
+    fn from(e: hex::Error) -> Error {
+        Error::Hex(e)
+    }
+}
+
 /// Encode an object into a vector
 pub fn serialize<T: Encodable + ?Sized>(data: &T) -> Vec<u8> {
     let mut encoder = Vec::new();
@@ -151,7 +166,15 @@ pub fn serialize_hex<T: Encodable + ?Sized>(data: &T) -> String {
 
 /// Deserialize an object from a vector, will error if said deserialization
 /// doesn't consume the entire vector.
+///
+/// This is the same as [`deserialize_exact`].
 pub fn deserialize<T: Decodable>(data: &[u8]) -> Result<T, Error> {
+    deserialize_exact(data)
+}
+
+/// Deserialize an object from a vector, will error if there are bytes left
+/// after the object.
+pub fn deserialize_exact<T: Decodable>(data: &[u8]) -> Result<T, Error> {
     let (rv, consumed) = deserialize_partial(data)?;
 
     // Fail if data are not consumed entirely.
@@ -162,6 +185,12 @@ pub fn deserialize<T: Decodable>(data: &[u8]) -> Result<T, Error> {
     }
 }
 
+/// Deserialize an object from a hex-encoded string, will error if said deserialization
+/// doesn't consume the entire string.
+pub fn deserialize_hex<T: Decodable>(hex: &str) -> Result<T, Error> {
+    deserialize(&Vec::<u8>::from_hex(hex)?)
+}
+
 /// Deserialize an object from a vector, but will not report an error if said deserialization
 /// doesn't consume the entire vector.
 pub fn deserialize_partial<T: Decodable>(data: &[u8]) -> Result<(T, usize), Error> {
@@ -172,8 +201,83 @@ pub fn deserialize_partial<T: Decodable>(data: &[u8]) -> Result<(T, usize), Erro
     Ok((rv, consumed))
 }
 
+/// Deserialize an object from a vector within the limits of `context`, will error if said
+/// deserialization doesn't consume the entire vector.
+pub fn deserialize_with_context<T: Decodable>(data: &[u8], context: &DecodeContext) -> Result<T, Error> {
+    if data.len() > context.max_payload {
+        return Err(Error::OversizedVectorAllocation { requested: data.len(), max: context.max_payload });
+    }
+    let mut decoder = Cursor::new(data);
+    let rv = T::consensus_decode_with_context(&mut decoder, context)?;
+    if decoder.position() as usize == data.len() {
+        Ok(rv)
+    } else {
+        Err(Error::ParseFailed("data not consumed entirely when explicitly deserializing"))
+    }
+}
+
+/// Deserialize back-to-back objects from a vector, such as a file of
+/// transactions or headers, see [`DeserializeIter`].
+pub fn deserialize_iter<'a, T: Decodable>(data: &'a [u8]) -> DeserializeIter<'a, T> {
+    DeserializeIter { data, _marker: PhantomData }
+}
+
+/// An iterator over the objects concatenated in a byte slice, created by
+/// [`deserialize_iter`].
+///
+/// Yields the objects until the slice is consumed. After an error, which
+/// includes a truncated last object, the iterator yields nothing more.
+#[derive(Clone, Debug)]
+pub struct DeserializeIter<'a, T> {
+    data: &'a [u8],
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T> DeserializeIter<'a, T> {
+    /// Returns the bytes not deserialized yet.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl<'a, T: Decodable> Iterator for DeserializeIter<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        match deserialize_partial(self.data) {
+            Ok((rv, consumed)) => {
+                self.data = &self.data[consumed..];
+                Some(Ok(rv))
+            }
+            Err(e) => {
+                self.data = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 
 /// Extensions of `Write` to encode data as per Bitcoin consensus
+///
+/// Integers are written little-endian, as in Bitcoin Core's serialization, so formats built on
+/// it, e.g. the ones of forks or of Core's own files, can be written field by field. This trait
+/// is implemented for every [`io::Write`].
+///
+/// # Examples
+///
+/// ```
+/// use bitcoin::consensus::WriteExt;
+///
+/// let mut buf = Vec::new();
+/// buf.emit_u32(0xd9b4bef9).unwrap();
+/// buf.emit_i16(-2).unwrap();
+/// buf.emit_bool(true).unwrap();
+/// assert_eq!(buf, [0xf9, 0xbe, 0xb4, 0xd9, 0xfe, 0xff, 0x01]);
+/// ```
 pub trait WriteExt {
     /// Output a 64-bit uint
     fn emit_u64(&mut self, v: u64) -> Result<(), io::Error>;
@@ -201,6 +305,21 @@ pub trait WriteExt {
 }
 
 /// Extensions of `Read` to decode data as per Bitcoin consensus
+///
+/// The counterpart of [`WriteExt`], reading little-endian integers. Reading past the end of the
+/// data returns an [`Error::Io`]. This trait is implemented for every [`io::Read`].
+///
+/// # Examples
+///
+/// ```
+/// use bitcoin::consensus::ReadExt;
+///
+/// let mut data = &[0xf9, 0xbe, 0xb4, 0xd9, 0xfe, 0xff, 0x01][..];
+/// assert_eq!(data.read_u32().unwrap(), 0xd9b4bef9);
+/// assert_eq!(data.read_i16().unwrap(), -2);
+/// assert!(data.read_bool().unwrap());
+/// assert!(data.read_u8().is_err());
+/// ```
 pub trait ReadExt {
     /// Read a 64-bit uint
     fn read_u64(&mut self) -> Result<u64, Error>;
@@ -307,6 +426,59 @@ impl<R: Read> ReadExt for R {
 /// Maximum size, in bytes, of a vector we are allowed to decode
 pub const MAX_VEC_SIZE: usize = 4_000_000;
 
+/// Default maximum nesting depth of vectors of structured objects, see [`DecodeContext`].
+pub const MAX_DECODE_DEPTH: usize = 32;
+
+/// Limits applied while decoding untrusted data.
+///
+/// The defaults are the limits of [`Decodable::consensus_decode`]. Consumers
+/// with tighter memory budgets lower them and decode with
+/// [`deserialize_with_context`] or [`Decodable::consensus_decode_with_context`].
+///
+/// Types which don't override [`Decodable::consensus_decode_with_context`]
+/// decode their contents with the default limits, bounded by `max_payload`.
+/// The vector, script, witness, transaction and block types of this crate
+/// apply the context throughout.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DecodeContext {
+    /// Maximum size, in bytes, of a single vector.
+    pub max_vec_size: usize,
+    /// Maximum nesting depth of vectors of structured objects, such as the
+    /// inputs in the transactions of a block.
+    pub max_depth: usize,
+    /// Maximum number of bytes read while decoding an object.
+    pub max_payload: usize,
+}
+
+impl Default for DecodeContext {
+    fn default() -> Self {
+        DecodeContext {
+            max_vec_size: MAX_VEC_SIZE,
+            max_depth: MAX_DECODE_DEPTH,
+            max_payload: MAX_VEC_SIZE,
+        }
+    }
+}
+
+impl DecodeContext {
+    /// Returns the context to decode the elements of a vector with, one level deeper.
+    pub fn nested(&self) -> Result<DecodeContext, Error> {
+        match self.max_depth.checked_sub(1) {
+            Some(max_depth) => Ok(DecodeContext { max_depth, ..*self }),
+            None => Err(Error::ParseFailed("maximum decoding depth exceeded")),
+        }
+    }
+
+    /// Checks that a vector of `size` bytes is within the limits.
+    pub fn check_vec_size(&self, size: usize) -> Result<(), Error> {
+        if size > self.max_vec_size {
+            Err(Error::OversizedVectorAllocation { requested: size, max: self.max_vec_size })
+        } else {
+            Ok(())
+        }
+    }
+}
+
 /// Data which can be encoded in a consensus-consistent way
 pub trait Encodable {
     /// Encode an object with a well-defined format.
@@ -314,6 +486,15 @@ pub trait Encodable {
     ///
     /// The only errors returned are errors propagated from the writer.
     fn consensus_encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error>;
+
+    /// Returns the number of bytes [`Encodable::consensus_encode`] writes.
+    ///
+    /// The default implementation encodes the object into a writer discarding the data, so no
+    /// buffer is allocated; transactions, blocks and network messages compute it directly.
+    #[inline]
+    fn consensus_size(&self) -> usize {
+        self.consensus_encode(&mut sink()).expect("writing to a sink never fails")
+    }
 }
 
 /// Data which can be encoded in a consensus-consistent way
@@ -342,6 +523,19 @@ pub trait Decodable: Sized {
     fn consensus_decode<R: io::Read + ?Sized>(reader: &mut R) -> Result<Self, Error> {
         Self::consensus_decode_from_finite_reader(reader.take(MAX_VEC_SIZE as u64).by_ref())
     }
+
+    /// Decode an object within the limits of `context`.
+    ///
+    /// The default implementation limits the input size to the context's `max_payload` and
+    /// forwards the call to [`Self::consensus_decode_from_finite_reader`]. Types containing
+    /// vectors override it to pass the context on to their fields.
+    #[inline]
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(
+        reader: &mut R,
+        context: &DecodeContext,
+    ) -> Result<Self, Error> {
+        Self::consensus_decode_from_finite_reader(io::Read::take(reader, context.max_payload as u64).by_ref())
+    }
 }
 
 /// A variable-length unsigned integer
@@ -383,21 +577,67 @@ impl_int_encodable!(i16, read_i16, emit_i16);
 impl_int_encodable!(i32, read_i32, emit_i32);
 impl_int_encodable!(i64, read_i64, emit_i64);
 
+/// The name Bitcoin Core gives [`VarInt`], the variable-length integer prefixing vectors.
+pub type CompactSize = VarInt;
+
 impl VarInt {
     /// Gets the length of this VarInt when encoded.
     /// Returns 1 for 0..=0xFC, 3 for 0xFD..=(2^16-1), 5 for 0x10000..=(2^32-1),
     /// and 9 otherwise.
     #[inline]
     pub fn len(&self) -> usize {
-        match self.0 {
-            0..=0xFC             => { 1 }
-            0xFD..=0xFFFF        => { 3 }
-            0x10000..=0xFFFFFFFF => { 5 }
-            _                    => { 9 }
+        self.size()
+    }
+
+    /// Gets the length of this VarInt when encoded, like [`VarInt::len`] but usable in constants.
+    #[inline]
+    pub const fn size(&self) -> usize {
+        1 + 2 * (self.0 > 0xFC) as usize + 2 * (self.0 > 0xFFFF) as usize + 4 * (self.0 > 0xFFFFFFFF) as usize
+    }
+
+    /// Decodes a VarInt, accepting non-minimal encodings.
+    ///
+    /// The [`Decodable`] implementation is strict and rejects them with
+    /// [`Error::NonMinimalVarInt`], as Bitcoin Core does. Some non-consensus
+    /// formats were written by encoders which weren't.
+    pub fn consensus_decode_lax<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, Error> {
+        VarInt::decode(r, false)
+    }
+
+    fn decode<R: io::Read + ?Sized>(d: &mut R, strict: bool) -> Result<Self, Error> {
+        let n = ReadExt::read_u8(d)?;
+        let (value, min) = match n {
+            0xFF => (ReadExt::read_u64(d)?, 0x100000000),
+            0xFE => (ReadExt::read_u32(d)? as u64, 0x10000),
+            0xFD => (ReadExt::read_u16(d)? as u64, 0xFD),
+            n => return Ok(VarInt(n as u64)),
+        };
+        if strict && value < min {
+            Err(self::Error::NonMinimalVarInt)
+        } else {
+            Ok(VarInt(value))
         }
     }
 }
 
+impl From<usize> for VarInt {
+    fn from(n: usize) -> Self {
+        VarInt(n as u64)
+    }
+}
+
+impl From<u64> for VarInt {
+    fn from(n: u64) -> Self {
+        VarInt(n)
+    }
+}
+
+impl From<VarInt> for u64 {
+    fn from(n: VarInt) -> Self {
+        n.0
+    }
+}
+
 impl Encodable for VarInt {
     #[inline]
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
@@ -428,34 +668,7 @@ impl Encodable for VarInt {
 impl Decodable for VarInt {
     #[inline]
     fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, Error> {
-        let n = ReadExt::read_u8(&mut d)?;
-        match n {
-            0xFF => {
-                let x = ReadExt::read_u64(&mut d)?;
-                if x < 0x100000000 {
-                    Err(self::Error::NonMinimalVarInt)
-                } else {
-                    Ok(VarInt(x))
-                }
-            }
-            0xFE => {
-                let x = ReadExt::read_u32(&mut d)?;
-                if x < 0x10000 {
-                    Err(self::Error::NonMinimalVarInt)
-                } else {
-                    Ok(VarInt(x as u64))
-                }
-            }
-            0xFD => {
-                let x = ReadExt::read_u16(&mut d)?;
-                if x < 0xFD {
-                    Err(self::Error::NonMinimalVarInt)
-                } else {
-                    Ok(VarInt(x as u64))
-                }
-            }
-            n => Ok(VarInt(n as u64))
-        }
+        VarInt::decode(&mut d, true)
     }
 }
 
@@ -539,6 +752,7 @@ macro_rules! impl_array {
 
 impl_array!(2);
 impl_array!(4);
+impl_array!(6);
 impl_array!(8);
 impl_array!(10);
 impl_array!(12);
@@ -578,6 +792,11 @@ macro_rules! impl_vec {
                 }
                 Ok(len)
             }
+
+            #[inline]
+            fn consensus_size(&self) -> usize {
+                VarInt(self.len() as u64).len() + self.iter().map(Encodable::consensus_size).sum::<usize>()
+            }
         }
         impl Decodable for Vec<$type> {
             #[inline]
@@ -596,6 +815,25 @@ macro_rules! impl_vec {
                 }
                 Ok(ret)
             }
+
+            #[inline]
+            fn consensus_decode_with_context<R: io::Read + ?Sized>(
+                r: &mut R,
+                context: &DecodeContext,
+            ) -> Result<Self, Error> {
+                let mut r = io::Read::take(r, context.max_payload as u64);
+                let len = VarInt::consensus_decode(&mut r)?.0;
+                let byte_size = (len as usize)
+                    .checked_mul(mem::size_of::<$type>())
+                    .ok_or(self::Error::ParseFailed("Invalid length"))?;
+                context.check_vec_size(byte_size)?;
+                let nested = context.nested()?;
+                let mut ret = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    ret.push(Decodable::consensus_decode_with_context(&mut r, &nested)?);
+                }
+                Ok(ret)
+            }
         }
     }
 }
@@ -606,13 +844,19 @@ impl_vec!(TxMerkleNode);
 impl_vec!(Transaction);
 impl_vec!(TxOut);
 impl_vec!(TxIn);
+impl_vec!(OutPoint);
 impl_vec!(Vec<u8>);
 impl_vec!(u64);
+impl_vec!(i32);
+impl_vec!(String);
 impl_vec!(TapLeafHash);
 
 #[cfg(feature = "std")] impl_vec!(Inventory);
 #[cfg(feature = "std")] impl_vec!((u32, Address));
 #[cfg(feature = "std")] impl_vec!(AddrV2Message);
+#[cfg(feature = "std")] impl_vec!(Utxo);
+#[cfg(feature = "std")] impl_vec!(ShortId);
+#[cfg(feature = "std")] impl_vec!(PrefilledTransaction);
 
 pub(crate) fn consensus_encode_with_size<S: io::Write>(data: &[u8], mut s: S) -> Result<usize, io::Error> {
     let vi_len = VarInt(data.len() as u64).consensus_encode(s)?;
@@ -626,6 +870,11 @@ impl Encodable for Vec<u8> {
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
         consensus_encode_with_size(self, s)
     }
+
+    #[inline]
+    fn consensus_size(&self) -> usize {
+        VarInt(self.len() as u64).len() + self.len()
+    }
 }
 
 impl Decodable for Vec<u8> {
@@ -639,6 +888,15 @@ impl Decodable for Vec<u8> {
         d.read_slice(&mut ret)?;
         Ok(ret)
     }
+
+    #[inline]
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, Error> {
+        let len = VarInt::consensus_decode(r)?.0 as usize;
+        context.check_vec_size(len)?;
+        let mut ret = vec![0u8; len];
+        r.read_slice(&mut ret)?;
+        Ok(ret)
+    }
 }
 
 impl Encodable for Box<[u8]> {
@@ -653,12 +911,17 @@ impl Decodable for Box<[u8]> {
     fn consensus_decode<D: io::Read>(d: D) -> Result<Self, Error> {
         <Vec<u8>>::consensus_decode(d).map(From::from)
     }
+
+    #[inline]
+    fn consensus_decode_with_context<R: io::Read + ?Sized>(r: &mut R, context: &DecodeContext) -> Result<Self, Error> {
+        <Vec<u8>>::consensus_decode_with_context(r, context).map(From::from)
+    }
 }
 
 
 /// Do a double-SHA256 on some data and return the first 4 bytes
 fn sha2_checksum(data: &[u8]) -> [u8; 4] {
-    let checksum = <sha256d::Hash as Hash>::hash(data);
+    let checksum: sha256d::Hash = hash::sha256d(data);
     [checksum[0], checksum[1], checksum[2], checksum[3]]
 }
 
@@ -698,14 +961,46 @@ impl Decodable for CheckedData {
     }
 }
 
+// Slices
+impl<T: Encodable> Encodable for [T] {
+    #[inline]
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        consensus_encode_iter(self.len(), self.iter(), s)
+    }
+}
+
+/// Encodes `len` as a [`VarInt`] followed by the items of `iter`, as vectors
+/// are encoded, without collecting the items into a vector first.
+///
+/// Returns an [`io::ErrorKind::InvalidInput`] error if `iter` doesn't yield
+/// exactly `len` items, in which case the data written is invalid.
+pub fn consensus_encode_iter<W, I>(len: usize, iter: I, s: &mut W) -> Result<usize, io::Error>
+where
+    W: io::Write + ?Sized,
+    I: IntoIterator,
+    I::Item: Encodable,
+{
+    let mut written = VarInt(len as u64).consensus_encode(s)?;
+    let mut count = 0;
+    for item in iter {
+        written += item.consensus_encode(s)?;
+        count += 1;
+    }
+    if count == len {
+        Ok(written)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "iterator length does not match the encoded length"))
+    }
+}
+
 // References
-impl<'a, T: Encodable> Encodable for &'a T {
+impl<T: Encodable + ?Sized> Encodable for &T {
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
         (&**self).consensus_encode(s)
     }
 }
 
-impl<'a, T: Encodable> Encodable for &'a mut T {
+impl<T: Encodable + ?Sized> Encodable for &mut T {
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
         (&**self).consensus_encode(s)
     }
@@ -799,14 +1094,51 @@ impl Decodable for TapLeafHash {
 mod tests {
     use super::*;
     use core::{mem::{self, discriminant}, fmt};
-    use super::{deserialize, serialize, Error, CheckedData, VarInt};
+    use super::{consensus_encode_iter, deserialize, deserialize_exact, deserialize_iter, deserialize_hex, deserialize_with_context, DecodeContext, CompactSize, serialize, serialize_hex, Error, CheckedData, VarInt};
     use super::{Transaction, BlockHash, FilterHash, TxMerkleNode, TxOut, TxIn};
+    use blockdata::script::Script;
     use consensus::{Encodable, deserialize_partial, Decodable};
     use util::endian::{u64_to_array_le, u32_to_array_le, u16_to_array_le};
     use secp256k1::rand::{thread_rng, Rng};
     #[cfg(feature = "std")]
     use network::{Address, message_blockdata::Inventory};
 
+    #[test]
+    fn read_write_ext() {
+        let mut buf = Vec::new();
+        buf.emit_u64(0x0102030405060708).unwrap();
+        buf.emit_u32(0x090a0b0c).unwrap();
+        buf.emit_u16(0x0d0e).unwrap();
+        buf.emit_u8(0x0f).unwrap();
+        buf.emit_i64(-1).unwrap();
+        buf.emit_i32(-2).unwrap();
+        buf.emit_i16(-3).unwrap();
+        buf.emit_i8(-4).unwrap();
+        buf.emit_bool(false).unwrap();
+        buf.emit_slice(&[0xab, 0xcd]).unwrap();
+        assert_eq!(buf.len(), 8 + 4 + 2 + 1 + 8 + 4 + 2 + 1 + 1 + 2);
+        assert_eq!(&buf[..4], &[0x08, 0x07, 0x06, 0x05]);
+
+        let mut data = &buf[..];
+        assert_eq!(data.read_u64().unwrap(), 0x0102030405060708);
+        assert_eq!(data.read_u32().unwrap(), 0x090a0b0c);
+        assert_eq!(data.read_u16().unwrap(), 0x0d0e);
+        assert_eq!(data.read_u8().unwrap(), 0x0f);
+        assert_eq!(data.read_i64().unwrap(), -1);
+        assert_eq!(data.read_i32().unwrap(), -2);
+        assert_eq!(data.read_i16().unwrap(), -3);
+        assert_eq!(data.read_i8().unwrap(), -4);
+        assert!(!data.read_bool().unwrap());
+        let mut slice = [0; 2];
+        data.read_slice(&mut slice).unwrap();
+        assert_eq!(slice, [0xab, 0xcd]);
+        assert!(data.is_empty());
+        match data.read_u32() {
+            Err(Error::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            ref other => panic!("unexpected result {:?}", other),
+        }
+    }
+
     #[test]
     fn serialize_int_test() {
         // bool
@@ -937,6 +1269,131 @@ mod tests {
         assert_eq!(serialize(&cd), vec![5, 0, 0, 0, 162, 107, 175, 90, 1, 2, 3, 4, 5]);
     }
 
+    #[test]
+    #[cfg(feature = "derive")]
+    fn derive_test() {
+        use consensus::{ConsensusDecode, ConsensusEncode};
+
+        #[derive(ConsensusEncode, ConsensusDecode, Default, PartialEq, Debug)]
+        #[consensus(crate = "crate")]
+        struct Item(u16, [u8; 2]);
+
+        #[derive(ConsensusEncode, ConsensusDecode, PartialEq, Debug)]
+        #[consensus(crate = "crate")]
+        struct Message {
+            version: u32,
+            #[consensus(length_prefixed)]
+            items: Vec<Item>,
+            #[consensus(array)]
+            extra: [Item; 2],
+        }
+
+        let message = Message {
+            version: 2,
+            items: vec![Item(1, [2, 3])],
+            extra: [Item(4, [5, 6]), Item(7, [8, 9])],
+        };
+        let data = serialize(&message);
+        assert_eq!(data, vec![2, 0, 0, 0, 1, 1, 0, 2, 3, 4, 0, 5, 6, 7, 0, 8, 9]);
+        assert_eq!(deserialize::<Message>(&data).unwrap(), message);
+        assert!(deserialize::<Message>(&data[..16]).is_err());
+    }
+
+    #[test]
+    fn deserialize_hex_test() {
+        assert_eq!(deserialize_hex::<u16>("0a01").unwrap(), 266);
+        assert_eq!(serialize_hex(&266u16), "0a01");
+        assert!(deserialize_hex::<u16>("0a0100").is_err());
+        assert!(deserialize_hex::<u16>("0a").is_err());
+        match deserialize_hex::<u16>("0a0") {
+            Err(Error::Hex(_)) => {},
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn deserialize_exact_and_iter_test() {
+        let data = [1u8, 0, 2, 0, 3, 0, 4];
+        assert_eq!(deserialize_exact::<u16>(&data[..2]).unwrap(), 1);
+        assert!(deserialize_exact::<u16>(&data[..3]).is_err());
+
+        let mut iter = deserialize_iter::<u16>(&data);
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert_eq!(iter.remainder(), &data[2..]);
+        assert_eq!(iter.by_ref().take(2).map(Result::unwrap).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+        assert_eq!(deserialize_iter::<u16>(&data[..6]).collect::<Result<Vec<_>, _>>().unwrap(), vec![1, 2, 3]);
+        assert_eq!(deserialize_iter::<u16>(&[]).count(), 0);
+    }
+
+    #[test]
+    fn deserialize_with_context_test() {
+        let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let tx: Transaction = deserialize(&tx_bytes).unwrap();
+        let context = DecodeContext::default();
+        assert_eq!(deserialize_with_context::<Transaction>(&tx_bytes, &context).unwrap(), tx);
+
+        let small = DecodeContext { max_vec_size: 100, ..context };
+        assert!(deserialize_with_context::<Transaction>(&tx_bytes, &small).is_err());
+        let script_sig = serialize(&tx.input[0].script_sig);
+        match deserialize_with_context::<Script>(&script_sig, &small) {
+            Err(Error::OversizedVectorAllocation { requested: 108, max: 100 }) => {},
+            x => panic!("{:?}", x),
+        }
+        let shallow = DecodeContext { max_depth: 0, ..context };
+        assert!(deserialize_with_context::<Transaction>(&tx_bytes, &shallow).is_err());
+        let txs = serialize(&vec![tx.clone()]);
+        let shallow = DecodeContext { max_depth: 1, ..context };
+        assert!(deserialize_with_context::<Vec<Transaction>>(&txs, &shallow).is_err());
+        let deep = DecodeContext { max_depth: 2, ..context };
+        assert_eq!(deserialize_with_context::<Vec<Transaction>>(&txs, &deep).unwrap(), vec![tx]);
+        let short = DecodeContext { max_payload: tx_bytes.len() - 1, ..context };
+        assert!(deserialize_with_context::<Transaction>(&tx_bytes, &short).is_err());
+
+        // the payload limit also applies to readers
+        let mut reader = &tx_bytes[..];
+        assert!(Transaction::consensus_decode_with_context(&mut reader, &short).is_err());
+        // a vector claiming more bytes than allowed is rejected before allocating
+        let claim = serialize(&VarInt(1_000_000));
+        match deserialize_with_context::<Vec<u8>>(&claim, &small) {
+            Err(Error::OversizedVectorAllocation { requested: 1_000_000, max: 100 }) => {},
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn encode_slice_and_iter_test() {
+        let hashes = vec![BlockHash::hash(&[1]), BlockHash::hash(&[2])];
+        assert_eq!(serialize(&hashes[..]), serialize(&hashes));
+        assert_eq!(serialize(&&hashes[..1]), serialize(&vec![hashes[0]]));
+        assert_eq!(serialize(&[1u16, 2][..]), vec![2, 1, 0, 2, 0]);
+
+        let mut data = vec![];
+        assert_eq!(consensus_encode_iter(3, (0..3u8).map(|i| i * 2), &mut data).unwrap(), 4);
+        assert_eq!(data, vec![3, 0, 2, 4]);
+        let mut data = vec![];
+        assert_eq!(consensus_encode_iter(0, Vec::<u8>::new(), &mut data).unwrap(), 1);
+        assert!(consensus_encode_iter(2, hashes.iter().take(1), &mut data).is_err());
+    }
+
+    #[test]
+    fn varint_api_test() {
+        const SIZE: usize = VarInt(0x10000).size();
+        assert_eq!(SIZE, 5);
+        for &(n, size) in &[(0u64, 1), (0xFC, 1), (0xFD, 3), (0xFFFF, 3), (0x10000, 5), (0xFFFFFFFF, 5), (0x100000000, 9), (u64::max_value(), 9)] {
+            assert_eq!(VarInt(n).size(), size);
+            assert_eq!(VarInt(n).len(), serialize(&VarInt(n)).len());
+        }
+        assert_eq!(VarInt::from(300usize), VarInt(300));
+        assert_eq!(u64::from(CompactSize::from(7u64)), 7);
+
+        let non_minimal = [0xFEu8, 0xFD, 0, 0, 0];
+        assert!(deserialize::<VarInt>(&non_minimal).is_err());
+        assert_eq!(VarInt::consensus_decode_lax(&mut &non_minimal[..]).unwrap(), VarInt(0xFD));
+        assert_eq!(VarInt::consensus_decode_lax(&mut &[0xFDu8, 0xFF, 0xFF][..]).unwrap(), VarInt(0xFFFF));
+    }
+
     #[test]
     fn serialize_vector_test() {
         assert_eq!(serialize(&vec![1u8, 2, 3]), vec![3u8, 1, 2, 3]);
@@ -18,9 +18,19 @@
 //! conform to Bitcoin consensus.
 //!
 
+pub mod checkpoints;
 pub mod encode;
 pub mod params;
+pub mod versionbits;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod serde;
 
 pub use self::encode::{Encodable, Decodable, WriteExt, ReadExt};
-pub use self::encode::{serialize, deserialize, deserialize_partial};
+pub use self::encode::{serialize, serialize_hex, deserialize, deserialize_exact, deserialize_hex, deserialize_partial, deserialize_iter, deserialize_with_context};
+pub use self::encode::DecodeContext;
 pub use self::params::Params;
+pub use self::checkpoints::Checkpoints;
+#[cfg(feature = "derive")]
+#[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
+pub use bitcoin_derive::{ConsensusDecode, ConsensusEncode};
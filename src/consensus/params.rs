@@ -18,37 +18,41 @@
 //! chains (such as mainnet, testnet).
 //!
 
+use blockdata::block::BlockHeader;
+use blockdata::constants::{COIN_VALUE, SUBSIDY_HALVING_INTERVAL};
 use network::constants::Network;
+use util::amount::Amount;
+use util::pow::{CompactTarget, PowAlgorithm, Target};
 use util::uint::Uint256;
 
 /// Lowest possible difficulty for Mainnet. See comment on Params::pow_limit for more info.
-const MAX_BITS_BITCOIN: Uint256 = Uint256([
+const MAX_BITS_BITCOIN: Target = Target(Uint256([
     0x00000fffffffffffu64,
     0xffffffffffffffffu64,
     0xffffffffffffffffu64,
     0xffffffffffffffffu64,
-]);
+]));
 /// Lowest possible difficulty for Testnet. See comment on Params::pow_limit for more info.
-const MAX_BITS_TESTNET: Uint256 = Uint256([
+const MAX_BITS_TESTNET: Target = Target(Uint256([
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x00000000ffff0000u64,
-]);
+]));
 /// Lowest possible difficulty for Signet. See comment on Params::pow_limit for more info.
-const MAX_BITS_SIGNET: Uint256 = Uint256([
+const MAX_BITS_SIGNET: Target = Target(Uint256([
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x00000377ae000000u64,
-]);
+]));
 /// Lowest possible difficulty for Regtest. See comment on Params::pow_limit for more info.
-const MAX_BITS_REGTEST: Uint256 = Uint256([
+const MAX_BITS_REGTEST: Target = Target(Uint256([
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x0000000000000000u64,
     0x7fffff0000000000u64,
-]);
+]));
 
 /// Parameters that influence chain consensus.
 #[derive(Debug, Clone)]
@@ -63,6 +67,10 @@ pub struct Params {
     pub bip65_height: u32,
     /// Block height at which BIP66 becomes active.
     pub bip66_height: u32,
+    /// Block height at which segwit (BIPs 141, 143 and 147) becomes active.
+    pub segwit_height: u32,
+    /// Block height at which taproot (BIPs 340, 341 and 342) becomes active.
+    pub taproot_height: u32,
     /// Minimum blocks including miner confirmation of the total of 2016 blocks in a retargeting period,
     /// (nPowTargetTimespan / nPowTargetSpacing) which is also used for BIP9 deployments.
     /// Examples: 1916 for 95%, 1512 for testchains.
@@ -77,7 +85,7 @@ pub struct Params {
     /// Still, this should not affect consensus as the only place where the non-compact form of
     /// this is used in Bitcoin Core's consensus algorithm is in comparison and there are no
     /// compact-expressible values between Bitcoin Core's and the limit expressed here.
-    pub pow_limit: Uint256,
+    pub pow_limit: Target,
     /// Expected amount of time to mine one block.
     pub pow_target_spacing: u64,
     /// Difficulty recalculation interval.
@@ -86,68 +94,107 @@ pub struct Params {
     pub allow_min_difficulty_blocks: bool,
     /// Determines whether retargeting is disabled for this network or not.
     pub no_pow_retargeting: bool,
+    /// Number of blocks after which the block subsidy halves.
+    pub subsidy_halving_interval: u32,
+    /// The hash function block headers prove their work with.
+    pub pow_algorithm: PowAlgorithm,
+    /// The fork id committed to by the sighash of replay protected transactions, `None` for
+    /// chains without `SIGHASH_FORKID` replay protection.
+    pub fork_id: Option<u32>,
 }
 
 impl Params {
+    /// The texitcoin mainnet parameters.
+    pub const MAINNET: Params = Params {
+        network: Network::Bitcoin,
+        bip16_time: 1706236287,
+        bip34_height: 0, // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
+        bip65_height: 0, // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
+        bip66_height: 0, // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
+        segwit_height: 0,
+        taproot_height: 0,
+        rule_change_activation_threshold: 3,
+        miner_confirmation_window: 16,
+        pow_limit: MAX_BITS_BITCOIN,
+        pow_target_spacing: 3 * 60,            // 3 minutes.
+        pow_target_timespan: 12 * 60, // 12 minutes.
+        allow_min_difficulty_blocks: false,
+        no_pow_retargeting: false,
+        subsidy_halving_interval: SUBSIDY_HALVING_INTERVAL,
+        pow_algorithm: PowAlgorithm::Sha256d,
+        fork_id: None,
+    };
+
+    /// The testnet parameters.
+    pub const TESTNET: Params = Params {
+        network: Network::Testnet,
+        bip16_time: 1333238400,                 // Apr 1 2012
+        bip34_height: 21111, // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
+        bip65_height: 581885, // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
+        bip66_height: 330776, // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+        segwit_height: 834624,
+        taproot_height: 2011968,
+        rule_change_activation_threshold: 1512, // 75%
+        miner_confirmation_window: 2016,
+        pow_limit: MAX_BITS_TESTNET,
+        pow_target_spacing: 10 * 60,            // 10 minutes.
+        pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
+        allow_min_difficulty_blocks: true,
+        no_pow_retargeting: false,
+        subsidy_halving_interval: 210_000,
+        pow_algorithm: PowAlgorithm::Sha256d,
+        fork_id: None,
+    };
+
+    /// The signet parameters.
+    pub const SIGNET: Params = Params {
+        network: Network::Signet,
+        bip16_time: 1333238400,                 // Apr 1 2012
+        bip34_height: 1,
+        bip65_height: 1,
+        bip66_height: 1,
+        segwit_height: 1,
+        taproot_height: 1,
+        rule_change_activation_threshold: 1916, // 95%
+        miner_confirmation_window: 2016,
+        pow_limit: MAX_BITS_SIGNET,
+        pow_target_spacing: 10 * 60,            // 10 minutes.
+        pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
+        allow_min_difficulty_blocks: false,
+        no_pow_retargeting: false,
+        subsidy_halving_interval: 210_000,
+        pow_algorithm: PowAlgorithm::Sha256d,
+        fork_id: None,
+    };
+
+    /// The regtest parameters.
+    pub const REGTEST: Params = Params {
+        network: Network::Regtest,
+        bip16_time: 1333238400,  // Apr 1 2012
+        bip34_height: 100000000, // not activated on regtest
+        bip65_height: 1351,
+        bip66_height: 1251,                    // used only in rpc tests
+        segwit_height: 0,
+        taproot_height: 0,
+        rule_change_activation_threshold: 108, // 75%
+        miner_confirmation_window: 144,
+        pow_limit: MAX_BITS_REGTEST,
+        pow_target_spacing: 10 * 60,            // 10 minutes.
+        pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
+        allow_min_difficulty_blocks: true,
+        no_pow_retargeting: true,
+        subsidy_halving_interval: 150,
+        pow_algorithm: PowAlgorithm::Sha256d,
+        fork_id: None,
+    };
+
     /// Creates parameters set for the given network.
     pub fn new(network: Network) -> Self {
         match network {
-            Network::Bitcoin => Params {
-                network: Network::Bitcoin,
-                bip16_time: 1706236287,
-                bip34_height: 0, // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
-                bip65_height: 0, // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
-                bip66_height: 0, // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
-                rule_change_activation_threshold: 3,
-                miner_confirmation_window: 16,
-                pow_limit: MAX_BITS_BITCOIN,
-                pow_target_spacing: 3 * 60,            // 3 minutes.
-                pow_target_timespan: 12 * 60, // 12 minutes.
-                allow_min_difficulty_blocks: false,
-                no_pow_retargeting: false,
-            },
-            Network::Testnet => Params {
-                network: Network::Testnet,
-                bip16_time: 1333238400,                 // Apr 1 2012
-                bip34_height: 21111, // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
-                bip65_height: 581885, // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
-                bip66_height: 330776, // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
-                rule_change_activation_threshold: 1512, // 75%
-                miner_confirmation_window: 2016,
-                pow_limit: MAX_BITS_TESTNET,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
-                pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
-                allow_min_difficulty_blocks: true,
-                no_pow_retargeting: false,
-            },
-            Network::Signet => Params {
-                network: Network::Signet,
-                bip16_time: 1333238400,                 // Apr 1 2012
-                bip34_height: 1,
-                bip65_height: 1,
-                bip66_height: 1,
-                rule_change_activation_threshold: 1916, // 95%
-                miner_confirmation_window: 2016,
-                pow_limit: MAX_BITS_SIGNET,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
-                pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
-                allow_min_difficulty_blocks: false,
-                no_pow_retargeting: false,
-            },
-            Network::Regtest => Params {
-                network: Network::Regtest,
-                bip16_time: 1333238400,  // Apr 1 2012
-                bip34_height: 100000000, // not activated on regtest
-                bip65_height: 1351,
-                bip66_height: 1251,                    // used only in rpc tests
-                rule_change_activation_threshold: 108, // 75%
-                miner_confirmation_window: 144,
-                pow_limit: MAX_BITS_REGTEST,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
-                pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
-                allow_min_difficulty_blocks: true,
-                no_pow_retargeting: true,
-            },
+            Network::Bitcoin => Params::MAINNET,
+            Network::Testnet => Params::TESTNET,
+            Network::Signet => Params::SIGNET,
+            Network::Regtest => Params::REGTEST,
         }
     }
 
@@ -155,4 +202,199 @@ impl Params {
     pub fn difficulty_adjustment_interval(&self) -> u64 {
         self.pow_target_timespan / self.pow_target_spacing
     }
+
+    /// Returns whether segwit rules apply to the block at `height`.
+    pub fn is_segwit_active(&self, height: u32) -> bool {
+        height >= self.segwit_height
+    }
+
+    /// Returns whether taproot rules apply to the block at `height`.
+    pub fn is_taproot_active(&self, height: u32) -> bool {
+        height >= self.taproot_height
+    }
+
+    /// Returns whether a block mined at `time` on top of a block mined at `prev_time` may use
+    /// the minimum difficulty, i.e. [`Params::pow_limit`], instead of the regular target.
+    ///
+    /// On networks with [`Params::allow_min_difficulty_blocks`] this is the case when no block
+    /// has been found for more than twice the target spacing.
+    pub fn allows_min_difficulty_block(&self, prev_time: u32, time: u32) -> bool {
+        self.allow_min_difficulty_blocks && time as u64 > prev_time as u64 + 2 * self.pow_target_spacing
+    }
+}
+
+impl AsRef<Params> for Params {
+    fn as_ref(&self) -> &Params {
+        self
+    }
+}
+
+impl AsRef<Params> for Network {
+    fn as_ref(&self) -> &Params {
+        match *self {
+            Network::Bitcoin => &Params::MAINNET,
+            Network::Testnet => &Params::TESTNET,
+            Network::Signet => &Params::SIGNET,
+            Network::Regtest => &Params::REGTEST,
+        }
+    }
+}
+
+/// Calculates the compact target required for the block following `last`, which must be the last
+/// block of a difficulty adjustment period whose first block was mined at `first_block_time`.
+///
+/// This is Bitcoin Core's `CalculateNextWorkRequired`: the target of `last` is scaled by the time
+/// the period actually took relative to [`Params::pow_target_timespan`], with the adjustment
+/// clamped to a factor of four in either direction and the result capped at
+/// [`Params::pow_limit`]. Networks with [`Params::no_pow_retargeting`] keep the target of `last`.
+pub fn calculate_next_work_required(params: &Params, last: &BlockHeader, first_block_time: u32) -> CompactTarget {
+    if params.no_pow_retargeting {
+        return last.compact_target();
+    }
+
+    // Limit adjustment step
+    let timespan = params.pow_target_timespan as i64;
+    let mut actual_timespan = last.time as i64 - first_block_time as i64;
+    if actual_timespan < timespan / 4 {
+        actual_timespan = timespan / 4;
+    }
+    if actual_timespan > timespan * 4 {
+        actual_timespan = timespan * 4;
+    }
+
+    // Retarget
+    let target = last.target().to_uint256().mul_u32(actual_timespan as u32) / Uint256::from_u64(timespan as u64).unwrap();
+    let target = Target::from_uint256(target);
+    let target = if target > params.pow_limit { params.pow_limit } else { target };
+    target.to_compact_lossy()
+}
+
+/// The subsidy of the blocks in the first halving epoch, matching the genesis coinbase.
+const INITIAL_SUBSIDY: u64 = 50 * COIN_VALUE;
+
+/// Returns the halving epoch of the block at `height`, i.e. how many times the subsidy has
+/// halved by then.
+pub fn halving_epoch(height: u32, params: &Params) -> u32 {
+    height / params.subsidy_halving_interval
+}
+
+/// Returns the height of the first block after `height` whose subsidy is halved.
+pub fn next_halving_height(height: u32, params: &Params) -> u32 {
+    (halving_epoch(height, params) + 1).saturating_mul(params.subsidy_halving_interval)
+}
+
+/// Returns the newly created coins a coinbase at `height` may claim, excluding fees.
+///
+/// This is Bitcoin Core's `GetBlockSubsidy`: the subsidy halves every
+/// [`Params::subsidy_halving_interval`] blocks until it reaches zero.
+pub fn block_subsidy(height: u32, params: &Params) -> Amount {
+    let halvings = halving_epoch(height, params);
+    // Force block reward to zero when right shift is undefined.
+    if halvings >= 64 {
+        return Amount::ZERO;
+    }
+    Amount::from_sat(INITIAL_SUBSIDY >> halvings)
+}
+
+/// Returns the total amount of coins issued by the subsidies of the blocks up to and including
+/// the one at `height`.
+pub fn total_subsidy(height: u32, params: &Params) -> Amount {
+    let interval = params.subsidy_halving_interval as u64;
+    let blocks = height as u64 + 1;
+    let mut total = 0;
+    let mut epoch = 0;
+    while epoch < 64 && epoch * interval < blocks {
+        let epoch_blocks = ::core::cmp::min(blocks - epoch * interval, interval);
+        total += epoch_blocks * (INITIAL_SUBSIDY >> epoch);
+        epoch += 1;
+    }
+    Amount::from_sat(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::block::Version;
+
+    fn header(time: u32, bits: u32) -> BlockHeader {
+        BlockHeader {
+            version: Version::ONE,
+            prev_blockhash: Default::default(),
+            merkle_root: Default::default(),
+            time,
+            bits,
+            nonce: 0,
+            aux_data: None,
+        }
+    }
+
+    fn next_bits(params: &Params, first_block_time: u32, last_time: u32, last_bits: u32) -> u32 {
+        calculate_next_work_required(params, &header(last_time, last_bits), first_block_time).to_consensus()
+    }
+
+    #[test]
+    fn activation() {
+        let params = Params::new(Network::Bitcoin);
+        assert!(params.is_segwit_active(0));
+        assert!(params.is_taproot_active(0));
+        assert!(!params.allows_min_difficulty_block(0, 1_000_000));
+
+        let params = Params::new(Network::Testnet);
+        assert!(!params.is_segwit_active(params.segwit_height - 1));
+        assert!(params.is_segwit_active(params.segwit_height));
+        assert!(!params.is_taproot_active(params.taproot_height - 1));
+        assert!(params.is_taproot_active(params.taproot_height));
+        assert!(!params.allows_min_difficulty_block(1_000_000, 1_001_200));
+        assert!(params.allows_min_difficulty_block(1_000_000, 1_001_201));
+    }
+
+    #[test]
+    fn subsidy() {
+        let params = Params::new(Network::Bitcoin);
+        let interval = SUBSIDY_HALVING_INTERVAL;
+        assert_eq!(block_subsidy(0, &params), Amount::from_sat(50 * COIN_VALUE));
+        assert_eq!(block_subsidy(interval - 1, &params), Amount::from_sat(50 * COIN_VALUE));
+        assert_eq!(block_subsidy(interval, &params), Amount::from_sat(25 * COIN_VALUE));
+        assert_eq!(block_subsidy(33 * interval - 1, &params), Amount::from_sat(1));
+        assert_eq!(block_subsidy(33 * interval, &params), Amount::ZERO);
+        assert_eq!(halving_epoch(2 * interval + 5, &params), 2);
+        assert_eq!(next_halving_height(0, &params), interval);
+        assert_eq!(next_halving_height(interval, &params), 2 * interval);
+        assert_eq!(next_halving_height(u32::max_value(), &params), u32::max_value());
+
+        let params = Params::new(Network::Regtest);
+        assert_eq!(block_subsidy(150 * 64, &params), Amount::ZERO);
+        assert_eq!(total_subsidy(0, &params), Amount::from_sat(50 * COIN_VALUE));
+        assert_eq!(total_subsidy(151, &params), Amount::from_sat(150 * 50 * COIN_VALUE + 2 * 25 * COIN_VALUE));
+        // every epoch issues half the previous one, approaching 2 * 150 * 50 coins
+        let cap = total_subsidy(u32::max_value(), &params);
+        assert_eq!(cap, total_subsidy(150 * 64, &params));
+        assert!(cap < Amount::from_sat(2 * 150 * 50 * COIN_VALUE));
+        assert_eq!(cap, (0..150 * 64).map(|h| block_subsidy(h, &params)).fold(Amount::ZERO, |a, b| a + b));
+    }
+
+    #[test]
+    fn retarget() {
+        // Test vectors from Bitcoin Core's pow_tests.cpp, which share the testnet timespan and limit.
+        let params = Params::new(Network::Testnet);
+        assert_eq!(next_bits(&params, 1261130161, 1262152739, 0x1d00ffff), 0x1d00d86a);
+        // pow limit
+        assert_eq!(next_bits(&params, 1231006505, 1233061996, 0x1d00ffff), 0x1d00ffff);
+        // lower limit on the actual timespan
+        assert_eq!(next_bits(&params, 1279008237, 1279297671, 0x1c05a3f4), 0x1c0168fd);
+        // upper limit on the actual timespan
+        assert_eq!(next_bits(&params, 1263163443, 1269211443, 0x1c387f6f), 0x1d00e1fd);
+
+        // texitcoin retargets every four 3-minute blocks
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(params.difficulty_adjustment_interval(), 4);
+        assert_eq!(next_bits(&params, 1_000_000, 1_000_720, 0x1d00ffff), 0x1d00ffff);
+        assert_eq!(next_bits(&params, 1_000_000, 1_001_440, 0x1d00ffff), 0x1d01fffe);
+        assert_eq!(next_bits(&params, 1_000_000, 1_000_000, 0x1d00ffff), 0x1c3fffc0);
+        assert_eq!(next_bits(&params, 1_000_720, 1_000_000, 0x1d00ffff), 0x1c3fffc0);
+
+        // no retargeting on regtest
+        let params = Params::new(Network::Regtest);
+        assert_eq!(next_bits(&params, 0, 1_000_000, 0x207fffff), 0x207fffff);
+    }
 }
@@ -0,0 +1,149 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Serde support for consensus-encoded values.
+//!
+//! RPC APIs represent transactions, blocks and headers as hex strings of their
+//! consensus encoding. This module lets such fields be (de)serialized the same
+//! way with serde's `with` attribute:
+//!
+//! ```rust,ignore
+//! use serde::{Serialize, Deserialize};
+//! use bitcoin::Transaction;
+//! use bitcoin::consensus::serde::Hex;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! pub struct SignedTransaction {
+//!     #[serde(with = "bitcoin::consensus::serde::With::<Hex>")]
+//!     pub hex: Transaction,
+//! }
+//! ```
+//!
+//! Serializers which are not human readable get the consensus encoding as
+//! bytes instead.
+//!
+
+use prelude::*;
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use hashes::hex::ToHex;
+use consensus::encode::{self, Decodable, Encodable};
+use serde::{de, Deserializer, Serializer};
+
+/// Lowercase hex, the text encoding of consensus-encoded values in RPC APIs.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Hex {}
+
+/// Serializes and deserializes consensus-encodable values in the text encoding `E`, for use
+/// with serde's `with` attribute.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct With<E>(PhantomData<E>);
+
+impl With<Hex> {
+    /// Serializes `value` as the hex string of its consensus encoding.
+    pub fn serialize<T, S>(value: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        T: Encodable + ?Sized,
+        S: Serializer,
+    {
+        let bytes = encode::serialize(value);
+        if s.is_human_readable() {
+            s.serialize_str(&bytes.to_hex())
+        } else {
+            s.serialize_bytes(&bytes)
+        }
+    }
+
+    /// Deserializes a value from the hex string of its consensus encoding.
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: Decodable,
+        D: Deserializer<'de>,
+    {
+        if d.is_human_readable() {
+            d.deserialize_str(HexVisitor(PhantomData))
+        } else {
+            d.deserialize_bytes(BytesVisitor(PhantomData))
+        }
+    }
+}
+
+struct HexVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Decodable> de::Visitor<'de> for HexVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a hex string of consensus-encoded data")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        encode::deserialize_hex(v).map_err(E::custom)
+    }
+}
+
+struct BytesVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Decodable> de::Visitor<'de> for BytesVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("consensus-encoded bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<T, E> {
+        encode::deserialize(v).map_err(E::custom)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        encode::deserialize(&bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::transaction::Transaction;
+    use serde_json;
+    use bincode;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Wrapper {
+        #[serde(with = "::consensus::serde::With::<Hex>")]
+        tx: Transaction,
+    }
+
+    #[test]
+    fn with_hex() {
+        let hex = "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000";
+        let tx: Transaction = encode::deserialize_hex(hex).unwrap();
+        let wrapper = Wrapper { tx };
+
+        let json = serde_json::to_string(&wrapper).unwrap();
+        assert_eq!(json, format!("{{\"tx\":\"{}\"}}", hex));
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap(), wrapper);
+        assert!(serde_json::from_str::<Wrapper>("{\"tx\":\"0100\"}").is_err());
+        assert!(serde_json::from_str::<Wrapper>("{\"tx\":\"zz\"}").is_err());
+
+        let bytes = bincode::serialize(&wrapper).unwrap();
+        assert_eq!(&bytes[8..], &encode::serialize(&wrapper.tx)[..]);
+        assert_eq!(bincode::deserialize::<Wrapper>(&bytes).unwrap(), wrapper);
+    }
+}
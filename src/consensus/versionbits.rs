@@ -0,0 +1,261 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP9 version bits.
+//!
+//! Tracks the state of soft fork deployments signalled through block header
+//! version bits, following Bitcoin Core's `versionbits` implementation
+//! (including the `min_activation_height` extension used by Speedy Trial).
+//!
+
+use core::fmt;
+
+use blockdata::block::{median_time_past, BlockHeader};
+use consensus::params::Params;
+
+/// The state of a deployment for the blocks of a retarget period.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ThresholdState {
+    /// The first state of every deployment; the start time has not been reached yet.
+    Defined,
+    /// Miners may signal for the deployment.
+    Started,
+    /// The threshold was reached in the previous period; the deployment activates once the
+    /// minimum activation height is reached.
+    LockedIn,
+    /// The new consensus rules are enforced.
+    Active,
+    /// The timeout passed without the threshold being reached.
+    Failed,
+}
+
+impl fmt::Display for ThresholdState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ThresholdState::Defined => "defined",
+            ThresholdState::Started => "started",
+            ThresholdState::LockedIn => "locked_in",
+            ThresholdState::Active => "active",
+            ThresholdState::Failed => "failed",
+        })
+    }
+}
+
+/// Parameters of a single BIP9 deployment.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Deployment {
+    /// The version bit miners set to signal for the deployment, in `0..=28`.
+    pub bit: u8,
+    /// Median time past from which signalling counts, or one of [`Deployment::ALWAYS_ACTIVE`]
+    /// and [`Deployment::NEVER_ACTIVE`].
+    pub start_time: i64,
+    /// Median time past after which the deployment fails if it has not locked in.
+    pub timeout: i64,
+    /// The height of the first block at which a locked in deployment may become active.
+    pub min_activation_height: u32,
+}
+
+impl Deployment {
+    /// Special `start_time` making the deployment active from the genesis block.
+    pub const ALWAYS_ACTIVE: i64 = -1;
+    /// Special `start_time` making the deployment fail from the genesis block.
+    pub const NEVER_ACTIVE: i64 = -2;
+    /// Special `timeout` for deployments which never time out.
+    pub const NO_TIMEOUT: i64 = 0x7fff_ffff_ffff_ffff;
+}
+
+/// Signalling statistics for the retarget period a new block would belong to.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Stats {
+    /// Length of the signalling period.
+    pub period: u32,
+    /// Number of signalling blocks required to lock in.
+    pub threshold: u32,
+    /// Number of blocks of the period mined so far.
+    pub elapsed: u32,
+    /// Number of blocks of the period mined so far that signal.
+    pub count: u32,
+    /// Whether the threshold can still be reached in this period.
+    pub possible: bool,
+}
+
+/// Computes the state of `deployment` for the block following the last of `headers`.
+///
+/// `headers` is the chain from the genesis block onwards, so the header at index `i` has
+/// height `i`.
+pub fn deployment_state(params: &Params, deployment: &Deployment, headers: &[BlockHeader]) -> ThresholdState {
+    // Deployments may be set to be always active or never active.
+    if deployment.start_time == Deployment::ALWAYS_ACTIVE {
+        return ThresholdState::Active;
+    }
+    if deployment.start_time == Deployment::NEVER_ACTIVE {
+        return ThresholdState::Failed;
+    }
+
+    let period = params.miner_confirmation_window as usize;
+    let threshold = params.rule_change_activation_threshold as usize;
+    let mut state = ThresholdState::Defined;
+    // The state only changes at period boundaries, based on the last block of each period.
+    let mut end = period;
+    while end <= headers.len() {
+        let mtp = median_time_past(&headers[..end]).expect("period is not empty") as i64;
+        state = match state {
+            ThresholdState::Defined if mtp >= deployment.start_time => ThresholdState::Started,
+            ThresholdState::Started => {
+                let count = headers[end - period..end]
+                    .iter()
+                    .filter(|h| h.version.is_signalling_soft_fork(deployment.bit))
+                    .count();
+                if count >= threshold {
+                    ThresholdState::LockedIn
+                } else if mtp >= deployment.timeout {
+                    ThresholdState::Failed
+                } else {
+                    ThresholdState::Started
+                }
+            }
+            ThresholdState::LockedIn if end as u64 >= deployment.min_activation_height as u64 => ThresholdState::Active,
+            state => state,
+        };
+        end += period;
+    }
+    state
+}
+
+/// Returns the signalling statistics of `deployment` for the period the block following the last
+/// of `headers` belongs to.
+///
+/// `headers` is the chain from the genesis block onwards, as for [`deployment_state`].
+pub fn statistics(params: &Params, deployment: &Deployment, headers: &[BlockHeader]) -> Stats {
+    let period = params.miner_confirmation_window;
+    let threshold = params.rule_change_activation_threshold;
+    let elapsed = (headers.len() % period as usize) as u32;
+    let count = headers[headers.len() - elapsed as usize..]
+        .iter()
+        .filter(|h| h.version.is_signalling_soft_fork(deployment.bit))
+        .count() as u32;
+    Stats {
+        period,
+        threshold,
+        elapsed,
+        count,
+        possible: period - threshold >= elapsed - count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::block::Version;
+    use network::constants::Network;
+
+    const SIGNAL: Version = Version(0x2000_0004);
+
+    fn params() -> Params {
+        let mut params = Params::new(Network::Regtest);
+        params.miner_confirmation_window = 4;
+        params.rule_change_activation_threshold = 3;
+        params
+    }
+
+    fn deployment() -> Deployment {
+        Deployment { bit: 2, start_time: 1000, timeout: 2000, min_activation_height: 0 }
+    }
+
+    fn header(time: u32, version: Version) -> BlockHeader {
+        BlockHeader {
+            version,
+            prev_blockhash: Default::default(),
+            merkle_root: Default::default(),
+            time,
+            bits: 0x207fffff,
+            nonce: 0,
+            aux_data: None,
+        }
+    }
+
+    fn push(headers: &mut Vec<BlockHeader>, time: u32, versions: &[Version]) {
+        for version in versions {
+            headers.push(header(time, *version));
+        }
+    }
+
+    #[test]
+    fn activation() {
+        let params = params();
+        let deployment = deployment();
+        let none = Version::NO_SOFT_FORK_SIGNALLING;
+        let mut headers = vec![];
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Defined);
+
+        // signalling before the start time doesn't count
+        push(&mut headers, 500, &[SIGNAL, SIGNAL, SIGNAL, SIGNAL]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Defined);
+        push(&mut headers, 1000, &[SIGNAL, SIGNAL, SIGNAL, SIGNAL]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Started);
+
+        // two out of four is not enough
+        push(&mut headers, 1100, &[SIGNAL, none, SIGNAL]);
+        assert_eq!(
+            statistics(&params, &deployment, &headers),
+            Stats { period: 4, threshold: 3, elapsed: 3, count: 2, possible: true }
+        );
+        push(&mut headers, 1100, &[none]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Started);
+        push(&mut headers, 1200, &[none, none]);
+        assert!(!statistics(&params, &deployment, &headers).possible);
+
+        push(&mut headers, 1200, &[none, none, SIGNAL, SIGNAL, SIGNAL, none]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::LockedIn);
+        // the locked in period doesn't need any signalling
+        push(&mut headers, 1300, &[none, none, none]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::LockedIn);
+        push(&mut headers, 1300, &[none]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Active);
+
+        // a minimum activation height delays activation
+        let delayed = Deployment { min_activation_height: 32, ..deployment };
+        assert_eq!(deployment_state(&params, &delayed, &headers), ThresholdState::LockedIn);
+        push(&mut headers, 1400, &[none, none, none, none]);
+        assert_eq!(deployment_state(&params, &delayed, &headers), ThresholdState::LockedIn);
+        push(&mut headers, 1400, &[none, none, none, none]);
+        assert_eq!(deployment_state(&params, &delayed, &headers), ThresholdState::Active);
+
+        // other bits are tracked independently
+        let other = Deployment { bit: 3, ..deployment };
+        assert_eq!(deployment_state(&params, &other, &headers), ThresholdState::Started);
+    }
+
+    #[test]
+    fn timeout() {
+        let params = params();
+        let deployment = deployment();
+        let mut headers = vec![];
+        push(&mut headers, 1000, &[SIGNAL; 4]);
+        push(&mut headers, 2000, &[SIGNAL, SIGNAL, Version::ONE, Version::ONE]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Failed);
+        push(&mut headers, 2000, &[SIGNAL; 4]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::Failed);
+
+        // reaching the threshold in the period the timeout passes still locks in
+        headers.truncate(4);
+        push(&mut headers, 2000, &[SIGNAL; 4]);
+        assert_eq!(deployment_state(&params, &deployment, &headers), ThresholdState::LockedIn);
+
+        let always = Deployment { start_time: Deployment::ALWAYS_ACTIVE, ..deployment };
+        assert_eq!(deployment_state(&params, &always, &[]), ThresholdState::Active);
+        let never = Deployment { start_time: Deployment::NEVER_ACTIVE, ..deployment };
+        assert_eq!(deployment_state(&params, &never, &headers), ThresholdState::Failed);
+    }
+}
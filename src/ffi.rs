@@ -0,0 +1,542 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! C interface.
+//!
+//! This module exposes the (de)serialization of transactions, blocks and
+//! network messages, address parsing and signature hashing as `extern "C"`
+//! functions, for node components written in other languages. They are
+//! exported by `staticlib` and `cdylib` crates depending on this crate with
+//! the `ffi` feature.
+//!
+//! Transactions, blocks and messages are opaque handles created by the
+//! `*_parse` functions and released with the matching `*_free` function.
+//! Byte strings returned to the caller are [`BtcBuffer`]s, released with
+//! [`btc_buffer_free`]. Hashes are written to 32-byte arrays in their
+//! internal byte order, the reverse of their hex representation.
+//!
+//! Every function returns a [`BtcStatus`] unless it can't fail. Pointer
+//! arguments must be valid for the documented lengths; null pointers are
+//! reported with [`BtcStatus::NullPointer`].
+//!
+
+#![allow(unsafe_code)]
+
+use prelude::*;
+
+use core::{ptr, slice, str};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use hashes::Hash;
+use blockdata::block::Block;
+use blockdata::script::Script;
+use blockdata::transaction::{EcdsaSighashType, Transaction};
+use consensus::encode::{deserialize, deserialize_partial, serialize};
+use network::constants::Network;
+use network::message::RawNetworkMessage;
+use util::address::Address;
+use util::sighash::SighashCache;
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BtcStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// The data could not be parsed.
+    InvalidData = 2,
+    /// An input index was out of bounds.
+    InvalidIndex = 3,
+}
+
+/// A byte string owned by this library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct BtcBuffer {
+    /// The bytes.
+    pub data: *mut u8,
+    /// The number of bytes.
+    pub len: usize,
+}
+
+impl BtcBuffer {
+    fn new(bytes: Vec<u8>) -> BtcBuffer {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        BtcBuffer { data, len }
+    }
+}
+
+/// A network.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BtcNetwork {
+    /// Mainnet.
+    Bitcoin = 0,
+    /// Testnet.
+    Testnet = 1,
+    /// Signet.
+    Signet = 2,
+    /// Regtest.
+    Regtest = 3,
+}
+
+impl From<Network> for BtcNetwork {
+    fn from(network: Network) -> BtcNetwork {
+        match network {
+            Network::Bitcoin => BtcNetwork::Bitcoin,
+            Network::Testnet => BtcNetwork::Testnet,
+            Network::Signet => BtcNetwork::Signet,
+            Network::Regtest => BtcNetwork::Regtest,
+        }
+    }
+}
+
+/// A parsed address.
+#[repr(C)]
+#[derive(Debug)]
+pub struct BtcAddress {
+    /// The network of the address.
+    pub network: BtcNetwork,
+    /// The script paying to the address.
+    pub script_pubkey: BtcBuffer,
+}
+
+macro_rules! try_status {
+    ($e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(status) => return status,
+        }
+    };
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], BtcStatus> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(BtcStatus::NullPointer)
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn reference<'a, T>(handle: *const T) -> Result<&'a T, BtcStatus> {
+    handle.as_ref().ok_or(BtcStatus::NullPointer)
+}
+
+unsafe fn write<T>(out: *mut T, value: T) -> BtcStatus {
+    if out.is_null() {
+        BtcStatus::NullPointer
+    } else {
+        ptr::write(out, value);
+        BtcStatus::Ok
+    }
+}
+
+unsafe fn parse<T>(data: *const u8, len: usize, out: *mut *mut T) -> BtcStatus
+    where T: ::consensus::Decodable
+{
+    let data = try_status!(bytes(data, len));
+    match deserialize::<T>(data) {
+        Ok(value) => write(out, Box::into_raw(Box::new(value))),
+        Err(_) => BtcStatus::InvalidData,
+    }
+}
+
+unsafe fn free<T>(handle: *mut T) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Releases a buffer returned by this library.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn btc_buffer_free(buffer: BtcBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(slice::from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Parses a serialized transaction of `len` bytes.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes and `tx` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_parse(data: *const u8, len: usize, tx: *mut *mut Transaction) -> BtcStatus {
+    parse(data, len, tx)
+}
+
+/// Releases a transaction.
+///
+/// # Safety
+///
+/// `tx` must be null or a handle returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_free(tx: *mut Transaction) {
+    free(tx)
+}
+
+/// Serializes a transaction.
+///
+/// # Safety
+///
+/// `tx` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_serialize(tx: *const Transaction, out: *mut BtcBuffer) -> BtcStatus {
+    let tx = try_status!(reference(tx));
+    write(out, BtcBuffer::new(serialize(tx)))
+}
+
+/// Computes the txid of a transaction.
+///
+/// # Safety
+///
+/// `tx` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_txid(tx: *const Transaction, out: *mut [u8; 32]) -> BtcStatus {
+    let tx = try_status!(reference(tx));
+    write(out, tx.txid().into_inner())
+}
+
+/// Computes the wtxid of a transaction.
+///
+/// # Safety
+///
+/// `tx` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_wtxid(tx: *const Transaction, out: *mut [u8; 32]) -> BtcStatus {
+    let tx = try_status!(reference(tx));
+    write(out, tx.wtxid().into_inner())
+}
+
+/// Computes the legacy signature hash of an input spending an output with the `script_len`
+/// bytes long `script_pubkey`.
+///
+/// # Safety
+///
+/// `tx` must be null or a live handle, `script_pubkey` must be valid for reads of
+/// `script_len` bytes and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_legacy_sighash(
+    tx: *const Transaction,
+    input_index: usize,
+    script_pubkey: *const u8,
+    script_len: usize,
+    sighash_type: u32,
+    out: *mut [u8; 32],
+) -> BtcStatus {
+    let tx = try_status!(reference(tx));
+    let script = Script::from(try_status!(bytes(script_pubkey, script_len)).to_vec());
+    match SighashCache::new(tx).legacy_signature_hash(input_index, &script, sighash_type) {
+        Ok(sighash) => write(out, sighash.into_inner()),
+        Err(_) => BtcStatus::InvalidIndex,
+    }
+}
+
+/// Computes the BIP143 signature hash of an input spending `value` satoshis with the
+/// `script_len` bytes long `script_code`.
+///
+/// # Safety
+///
+/// `tx` must be null or a live handle, `script_code` must be valid for reads of
+/// `script_len` bytes and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_transaction_segwit_v0_sighash(
+    tx: *const Transaction,
+    input_index: usize,
+    script_code: *const u8,
+    script_len: usize,
+    value: u64,
+    sighash_type: u32,
+    out: *mut [u8; 32],
+) -> BtcStatus {
+    let tx = try_status!(reference(tx));
+    let script = Script::from(try_status!(bytes(script_code, script_len)).to_vec());
+    let sighash_type = EcdsaSighashType::from_consensus(sighash_type);
+    match SighashCache::new(tx).segwit_signature_hash(input_index, &script, value, sighash_type) {
+        Ok(sighash) => write(out, sighash.into_inner()),
+        Err(_) => BtcStatus::InvalidIndex,
+    }
+}
+
+/// Parses a serialized block of `len` bytes.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes and `block` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_parse(data: *const u8, len: usize, block: *mut *mut Block) -> BtcStatus {
+    parse(data, len, block)
+}
+
+/// Releases a block.
+///
+/// # Safety
+///
+/// `block` must be null or a handle returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_free(block: *mut Block) {
+    free(block)
+}
+
+/// Serializes a block.
+///
+/// # Safety
+///
+/// `block` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_serialize(block: *const Block, out: *mut BtcBuffer) -> BtcStatus {
+    let block = try_status!(reference(block));
+    write(out, BtcBuffer::new(serialize(block)))
+}
+
+/// Computes the hash of a block.
+///
+/// # Safety
+///
+/// `block` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_hash(block: *const Block, out: *mut [u8; 32]) -> BtcStatus {
+    let block = try_status!(reference(block));
+    write(out, block.block_hash().into_inner())
+}
+
+/// Returns the number of transactions of a block, 0 for a null block.
+///
+/// # Safety
+///
+/// `block` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_transaction_count(block: *const Block) -> usize {
+    reference(block).map(|block| block.txdata.len()).unwrap_or(0)
+}
+
+/// Returns a copy of the transaction at `index` in a block, to be released with
+/// [`btc_transaction_free`].
+///
+/// # Safety
+///
+/// `block` must be null or a live handle and `tx` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_block_transaction(block: *const Block, index: usize, tx: *mut *mut Transaction) -> BtcStatus {
+    let block = try_status!(reference(block));
+    match block.txdata.get(index) {
+        Some(found) => write(tx, Box::into_raw(Box::new(found.clone()))),
+        None => BtcStatus::InvalidIndex,
+    }
+}
+
+/// Parses the network message at the start of `len` bytes, writing the number of bytes it
+/// takes to `consumed`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, `message` and `consumed` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_message_parse(
+    data: *const u8,
+    len: usize,
+    message: *mut *mut RawNetworkMessage,
+    consumed: *mut usize,
+) -> BtcStatus {
+    let data = try_status!(bytes(data, len));
+    if consumed.is_null() {
+        return BtcStatus::NullPointer;
+    }
+    match deserialize_partial::<RawNetworkMessage>(data) {
+        Ok((value, used)) => {
+            let status = write(message, Box::into_raw(Box::new(value)));
+            if status == BtcStatus::Ok {
+                *consumed = used;
+            }
+            status
+        }
+        Err(_) => BtcStatus::InvalidData,
+    }
+}
+
+/// Releases a network message.
+///
+/// # Safety
+///
+/// `message` must be null or a handle returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn btc_message_free(message: *mut RawNetworkMessage) {
+    free(message)
+}
+
+/// Serializes a network message.
+///
+/// # Safety
+///
+/// `message` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_message_serialize(message: *const RawNetworkMessage, out: *mut BtcBuffer) -> BtcStatus {
+    let message = try_status!(reference(message));
+    write(out, BtcBuffer::new(serialize(message)))
+}
+
+/// Returns the network magic of a network message, 0 for a null message.
+///
+/// # Safety
+///
+/// `message` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn btc_message_magic(message: *const RawNetworkMessage) -> u32 {
+    reference(message).map(|message| message.magic).unwrap_or(0)
+}
+
+/// Writes the command of a network message, padded with NUL bytes.
+///
+/// # Safety
+///
+/// `message` must be null or a live handle and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_message_command(message: *const RawNetworkMessage, out: *mut [u8; 12]) -> BtcStatus {
+    let message = try_status!(reference(message));
+    let mut command = [0u8; 12];
+    let cmd = message.command();
+    let cmd = cmd.as_ref().as_bytes();
+    command[..cmd.len()].copy_from_slice(cmd);
+    write(out, command)
+}
+
+/// Parses a NUL-terminated address string.
+///
+/// # Safety
+///
+/// `address` must be null or a NUL-terminated string and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn btc_address_parse(address: *const c_char, out: *mut BtcAddress) -> BtcStatus {
+    if address.is_null() {
+        return BtcStatus::NullPointer;
+    }
+    let address = match CStr::from_ptr(address).to_str().map(str::parse::<Address>) {
+        Ok(Ok(address)) => address,
+        _ => return BtcStatus::InvalidData,
+    };
+    write(out, BtcAddress {
+        network: address.network.into(),
+        script_pubkey: BtcBuffer::new(address.script_pubkey().into_bytes()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use hashes::hex::FromHex;
+    use blockdata::constants::genesis_block;
+    use network::message::NetworkMessage;
+
+    unsafe fn buffer_bytes(buffer: BtcBuffer) -> Vec<u8> {
+        let bytes = slice::from_raw_parts(buffer.data, buffer.len).to_vec();
+        btc_buffer_free(buffer);
+        bytes
+    }
+
+    #[test]
+    fn transaction() {
+        let data = Vec::<u8>::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let expected: Transaction = deserialize(&data).unwrap();
+        unsafe {
+            let mut tx = ptr::null_mut();
+            assert_eq!(btc_transaction_parse(data.as_ptr(), data.len() - 1, &mut tx), BtcStatus::InvalidData);
+            assert_eq!(btc_transaction_parse(ptr::null(), 1, &mut tx), BtcStatus::NullPointer);
+            assert_eq!(btc_transaction_parse(data.as_ptr(), data.len(), &mut tx), BtcStatus::Ok);
+
+            let mut buffer = BtcBuffer { data: ptr::null_mut(), len: 0 };
+            assert_eq!(btc_transaction_serialize(tx, &mut buffer), BtcStatus::Ok);
+            assert_eq!(buffer_bytes(buffer), data);
+            let mut hash = [0u8; 32];
+            assert_eq!(btc_transaction_txid(tx, &mut hash), BtcStatus::Ok);
+            assert_eq!(hash, expected.txid().into_inner());
+
+            let script = &expected.output[0].script_pubkey;
+            assert_eq!(btc_transaction_legacy_sighash(tx, 0, script.as_bytes().as_ptr(), script.len(), 1, &mut hash), BtcStatus::Ok);
+            assert_eq!(hash, expected.signature_hash(0, script, 1).into_inner());
+            assert_eq!(btc_transaction_legacy_sighash(tx, 1, script.as_bytes().as_ptr(), script.len(), 1, &mut hash), BtcStatus::InvalidIndex);
+            assert_eq!(btc_transaction_segwit_v0_sighash(tx, 0, script.as_bytes().as_ptr(), script.len(), 1000, 1, &mut hash), BtcStatus::Ok);
+            let segwit = SighashCache::new(&expected).segwit_signature_hash(0, script, 1000, EcdsaSighashType::All).unwrap();
+            assert_eq!(hash, segwit.into_inner());
+
+            btc_transaction_free(tx);
+            assert_eq!(btc_transaction_txid(ptr::null(), &mut hash), BtcStatus::NullPointer);
+        }
+    }
+
+    #[test]
+    fn block_and_message() {
+        let genesis = genesis_block(Network::Bitcoin);
+        let data = serialize(&genesis);
+        let message_data = serialize(&RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(7) });
+        unsafe {
+            let mut block = ptr::null_mut();
+            assert_eq!(btc_block_parse(data.as_ptr(), data.len(), &mut block), BtcStatus::Ok);
+            let mut hash = [0u8; 32];
+            assert_eq!(btc_block_hash(block, &mut hash), BtcStatus::Ok);
+            assert_eq!(hash, genesis.block_hash().into_inner());
+            assert_eq!(btc_block_transaction_count(block), 1);
+            let mut tx = ptr::null_mut();
+            assert_eq!(btc_block_transaction(block, 1, &mut tx), BtcStatus::InvalidIndex);
+            assert_eq!(btc_block_transaction(block, 0, &mut tx), BtcStatus::Ok);
+            assert_eq!(*tx, genesis.txdata[0]);
+            btc_transaction_free(tx);
+            btc_block_free(block);
+
+            let mut stream = message_data.clone();
+            stream.extend(&message_data);
+            let mut message = ptr::null_mut();
+            let mut consumed = 0;
+            assert_eq!(btc_message_parse(stream.as_ptr(), stream.len(), &mut message, &mut consumed), BtcStatus::Ok);
+            assert_eq!(consumed, message_data.len());
+            assert_eq!(btc_message_magic(message), 0xd9b4bef9);
+            let mut command = [0xffu8; 12];
+            assert_eq!(btc_message_command(message, &mut command), BtcStatus::Ok);
+            assert_eq!(&command, b"ping\0\0\0\0\0\0\0\0");
+            let mut buffer = BtcBuffer { data: ptr::null_mut(), len: 0 };
+            assert_eq!(btc_message_serialize(message, &mut buffer), BtcStatus::Ok);
+            assert_eq!(buffer_bytes(buffer), message_data);
+            btc_message_free(message);
+        }
+    }
+
+    #[test]
+    fn address() {
+        let address = CString::new("txc1qvzvkjn4q3nszqxrv3nraga2r822xjty3a2quqh").unwrap();
+        let expected: Address = address.to_str().unwrap().parse().unwrap();
+        unsafe {
+            let mut out = BtcAddress { network: BtcNetwork::Regtest, script_pubkey: BtcBuffer { data: ptr::null_mut(), len: 0 } };
+            assert_eq!(btc_address_parse(address.as_ptr(), &mut out), BtcStatus::Ok);
+            assert_eq!(out.network, BtcNetwork::Bitcoin);
+            assert_eq!(buffer_bytes(out.script_pubkey), expected.script_pubkey().into_bytes());
+
+            let invalid = CString::new("txc1qinvalid").unwrap();
+            let mut out = BtcAddress { network: BtcNetwork::Regtest, script_pubkey: BtcBuffer { data: ptr::null_mut(), len: 0 } };
+            assert_eq!(btc_address_parse(invalid.as_ptr(), &mut out), BtcStatus::InvalidData);
+            assert_eq!(btc_address_parse(ptr::null(), &mut out), BtcStatus::NullPointer);
+        }
+    }
+}
@@ -42,12 +42,14 @@ macro_rules! impl_hashencode {
 hash_newtype!(Txid, sha256d::Hash, 32, doc="A bitcoin transaction hash/transaction ID.");
 hash_newtype!(Wtxid, sha256d::Hash, 32, doc="A bitcoin witness transaction ID.");
 hash_newtype!(BlockHash, sha256d::Hash, 32, doc="A bitcoin block hash.");
+hash_newtype!(PowHash, sha256d::Hash, 32, doc="The proof-of-work hash of a block header, which is its block hash on chains using double SHA256.");
 hash_newtype!(Sighash, sha256d::Hash, 32, doc="Hash of the transaction according to the signature algorithm");
 
 hash_newtype!(PubkeyHash, hash160::Hash, 20, doc="A hash of a public key.");
 hash_newtype!(ScriptHash, hash160::Hash, 20, doc="A hash of Bitcoin Script bytecode.");
 hash_newtype!(WPubkeyHash, hash160::Hash, 20, doc="SegWit version of a public key hash.");
 hash_newtype!(WScriptHash, sha256::Hash, 32, doc="SegWit version of a Bitcoin Script bytecode hash.");
+hash_newtype!(ElectrumScriptHash, sha256::Hash, 32, doc="Script hash identifying a script to Electrum servers, displayed reversed.", true);
 
 hash_newtype!(TxMerkleNode, sha256d::Hash, 32, doc="A hash of the Merkle tree branch or root for transactions");
 hash_newtype!(WitnessMerkleNode, sha256d::Hash, 32, doc="A hash corresponding to the Merkle tree root for witness data");
@@ -68,3 +70,23 @@ impl_hashencode!(WitnessMerkleNode);
 
 impl_hashencode!(FilterHash);
 impl_hashencode!(FilterHeader);
+
+#[cfg(feature = "arbitrary")]
+macro_rules! impl_arbitrary {
+    ($($hashtype:ident),*) => {
+        $(
+            impl<'a> ::arbitrary::Arbitrary<'a> for $hashtype {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(Self::from_inner(u.arbitrary()?))
+                }
+            }
+        )*
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl_arbitrary!(
+    Txid, Wtxid, BlockHash, PowHash, Sighash, PubkeyHash, ScriptHash, WPubkeyHash, WScriptHash,
+    ElectrumScriptHash, TxMerkleNode, WitnessMerkleNode, WitnessCommitment, XpubIdentifier, FilterHash,
+    FilterHeader
+);
@@ -41,6 +41,34 @@ macro_rules! impl_consensus_encoding {
                     $($field: $crate::consensus::Decodable::consensus_decode(&mut d)?),+
                 })
             }
+
+            #[inline]
+            fn consensus_decode_with_context<R: $crate::io::Read + ?Sized>(
+                r: &mut R,
+                context: &$crate::consensus::encode::DecodeContext,
+            ) -> Result<$thing, $crate::consensus::encode::Error> {
+                let mut r = $crate::io::Read::take(r, context.max_payload as u64);
+                Ok($thing {
+                    $($field: $crate::consensus::Decodable::consensus_decode_with_context(&mut r, context)?),+
+                })
+            }
+        }
+    );
+}
+
+/// Implements `arbitrary::Arbitrary` for a struct by generating its fields in order.
+macro_rules! arbitrary_struct_impl {
+    ($thing:ident, $($field:ident),+) => (
+        #[cfg(feature = "arbitrary")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+        impl<'a> $crate::arbitrary::Arbitrary<'a> for $thing {
+            fn arbitrary(
+                u: &mut $crate::arbitrary::Unstructured<'a>,
+            ) -> $crate::arbitrary::Result<$thing> {
+                Ok($thing {
+                    $($field: $crate::arbitrary::Arbitrary::arbitrary(u)?),+
+                })
+            }
         }
     );
 }
@@ -29,6 +29,8 @@
 //!
 //! * `std` - the usual dependency on `std` (default).
 //! * `secp-recovery` - enables calculating public key from a signature and message.
+//! * `global-context` - adds `_global` variants of key, signing and verification
+//!   methods using a lazily-initialized shared secp256k1 context. Implies `std`.
 //! * `base64` - (dependency), enables encoding of PSBTs and message signatures.
 //! * `unstable` - enables unstable features for testing.
 //! * `rand` - (dependency), makes it more convenient to generate random values.
@@ -38,6 +40,17 @@
 //! * `no-std` - enables additional features required for this crate to be usable
 //!              without std. Does **not** disable `std`. Depends on `hashbrown`
 //!              and `core2`.
+//! * `bip38` - (dependency), enables BIP38 encryption and decryption of private keys.
+//! * `scrypt-pow` - (dependency), enables hashing block headers with the scrypt
+//!   proof-of-work algorithm.
+//! * `hw-sha256` - (dependency), uses the SHA extensions of the CPU, when
+//!   available, for consensus hashing.
+//! * `derive` - (dependency), re-exports the `ConsensusEncode` and
+//!   `ConsensusDecode` derive macros.
+//! * `ffi` - exposes a C interface to serialization, address parsing and
+//!   signature hashing. Implies `std`.
+//! * `arbitrary` - (dependency), implements `arbitrary::Arbitrary` for
+//!   structured fuzzing.
 //!
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
@@ -48,7 +61,8 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 // Coding conventions
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
 #![deny(non_upper_case_globals)]
 #![deny(non_camel_case_types)]
 #![deny(non_snake_case)]
@@ -89,7 +103,15 @@ extern crate hashbrown;
 #[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
 pub extern crate base64;
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+pub extern crate arbitrary;
+
 #[cfg(feature="bitcoinconsensus")] extern crate bitcoinconsensus;
+#[cfg(any(feature = "bip38", feature = "scrypt-pow"))] extern crate scrypt;
+#[cfg(feature = "bip38")] extern crate aes;
+#[cfg(feature = "hw-sha256")] extern crate sha2;
+#[cfg(feature = "derive")] extern crate bitcoin_derive;
 #[cfg(feature = "serde")] #[macro_use] extern crate serde;
 #[cfg(all(test, feature = "serde"))] extern crate serde_json;
 #[cfg(all(test, feature = "serde"))] extern crate serde_test;
@@ -114,6 +136,14 @@ pub mod util;
 pub mod consensus;
 pub mod hash_types;
 pub mod policy;
+pub mod mining;
+pub mod chain;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod rpc;
+#[cfg(feature = "ffi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ffi")))]
+pub mod ffi;
 
 pub use hash_types::*;
 pub use blockdata::block::Block;
@@ -133,6 +163,8 @@ pub use util::address::AddressType;
 pub use util::amount::Amount;
 pub use util::amount::Denomination;
 pub use util::amount::SignedAmount;
+pub use util::fee_rate::FeeRate;
+pub use util::weight::Weight;
 pub use util::merkleblock::MerkleBlock;
 pub use util::sighash::SchnorrSighashType;
 
@@ -143,10 +175,12 @@ pub use util::psbt;
 #[allow(deprecated)]
 pub use blockdata::transaction::SigHashType;
 
+/// The I/O traits consensus encoding reads and writes with.
 #[cfg(feature = "std")]
-use std::io;
+pub use std::io;
+/// The I/O traits consensus encoding reads and writes with.
 #[cfg(not(feature = "std"))]
-use core2::io;
+pub use core2::io;
 
 #[cfg(not(feature = "std"))]
 mod io_extras {
@@ -193,10 +227,10 @@ mod prelude {
     pub use io_extras::sink;
 
     #[cfg(feature = "hashbrown")]
-    pub use hashbrown::HashSet;
+    pub use hashbrown::{HashMap, HashSet};
 
     #[cfg(not(feature = "hashbrown"))]
-    pub use std::collections::HashSet;
+    pub use std::collections::{HashMap, HashSet};
 }
 
 #[cfg(all(test, feature = "unstable"))] use tests::EmptyWrite;
@@ -0,0 +1,124 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Header midstates.
+//!
+//! The first SHA256 pass over a block header processes a 64-byte chunk
+//! holding the version, the previous block hash and most of the merkle root,
+//! which don't change while grinding the nonce and time. This module caches
+//! the SHA256 state after that chunk, the midstate, so each candidate only
+//! costs the compression of the last 16 bytes and the second hash.
+//!
+
+use core::ops::Range;
+
+use hashes::{sha256, Hash, HashEngine};
+use hash_types::BlockHash;
+use blockdata::block::BlockHeader;
+use consensus::encode::serialize;
+use util::pow::Target;
+
+/// Computes the hashes of headers differing only in their last 16 bytes, the end of the merkle
+/// root, the time, the bits and the nonce.
+///
+/// The hashes are double SHA256 block hashes, which are the proof-of-work hashes of chains
+/// using [`PowAlgorithm::Sha256d`](::util::pow::PowAlgorithm::Sha256d).
+#[derive(Clone)]
+pub struct HeaderHasher {
+    midstate: sha256::HashEngine,
+    tail: [u8; 16],
+}
+
+impl HeaderHasher {
+    /// Creates a hasher of `header` with its nonce replaced.
+    pub fn new(header: &BlockHeader) -> HeaderHasher {
+        let data = serialize(&header.to_simple_header());
+        let mut midstate = sha256::Hash::engine();
+        midstate.input(&data[..64]);
+        let mut tail = [0u8; 16];
+        tail.copy_from_slice(&data[64..80]);
+        HeaderHasher { midstate, tail }
+    }
+
+    /// Returns the SHA256 state after the first 64 bytes of the header, as mining devices take
+    /// it.
+    pub fn midstate(&self) -> sha256::Midstate {
+        self.midstate.midstate()
+    }
+
+    /// Returns the last 16 bytes of the header, with the nonce of the last hashed header.
+    pub fn tail(&self) -> [u8; 16] {
+        self.tail
+    }
+
+    /// Sets the time of the hashed headers.
+    pub fn set_time(&mut self, time: u32) {
+        self.tail[4..8].copy_from_slice(&serialize(&time));
+    }
+
+    /// Computes the hash of the header with `nonce`.
+    pub fn block_hash(&mut self, nonce: u32) -> BlockHash {
+        self.tail[12..16].copy_from_slice(&serialize(&nonce));
+        let mut engine = self.midstate.clone();
+        engine.input(&self.tail);
+        let first = sha256::Hash::from_engine(engine);
+        BlockHash::from_inner(sha256::Hash::hash(&first[..]).into_inner())
+    }
+
+    /// Returns the first nonce of `nonces` for which the hash of the header meets `target`,
+    /// along with the hash.
+    pub fn find_nonce(&mut self, nonces: Range<u32>, target: &Target) -> Option<(u32, BlockHash)> {
+        for nonce in nonces {
+            let hash = self.block_hash(nonce);
+            if target.is_met_by(hash) {
+                return Some((nonce, hash));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use network::constants::Network;
+
+    #[test]
+    fn header_hasher() {
+        let mut header = genesis_block(Network::Bitcoin).header;
+        let mut hasher = HeaderHasher::new(&header);
+        assert_eq!(hasher.block_hash(header.nonce), header.block_hash());
+        assert_eq!(&hasher.tail()[..4], &serialize(&header.merkle_root)[28..]);
+
+        let mut engine = sha256::HashEngine::from_midstate(hasher.midstate(), 64);
+        engine.input(&hasher.tail());
+        let first = sha256::Hash::from_engine(engine);
+        assert_eq!(sha256::Hash::hash(&first[..]).into_inner(), header.block_hash().into_inner());
+
+        header.time += 1;
+        hasher.set_time(header.time);
+        for nonce in 0..10 {
+            header.nonce = nonce;
+            assert_eq!(hasher.block_hash(nonce), header.block_hash());
+        }
+
+        let header = genesis_block(Network::Regtest).header;
+        let mut hasher = HeaderHasher::new(&header);
+        let (nonce, hash) = hasher.find_nonce(0..1000, &header.target()).unwrap();
+        assert!(header.target().is_met_by(hash));
+        assert_eq!(hash, BlockHeader { nonce, ..header.clone() }.block_hash());
+        assert_eq!(hasher.find_nonce(0..1000, &Target::ZERO), None);
+    }
+}
@@ -0,0 +1,29 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Mining.
+//!
+//! This module contains the types used to talk to a node or a pool when
+//! producing blocks.
+//!
+
+pub mod midstate;
+pub mod template;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod stratum;
+pub mod stratum_v2;
+
+pub use self::midstate::HeaderHasher;
+pub use self::template::{BlockTemplate, TemplateTransaction};
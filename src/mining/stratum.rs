@@ -0,0 +1,516 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Stratum v1.
+//!
+//! Types for the JSON-RPC messages of the stratum v1 mining protocol spoken
+//! between miners and pools, and the reconstruction of the block header a
+//! miner hashed from a `mining.notify` job and a `mining.submit` share.
+//!
+//! Stratum encodes most of its values as hex strings in unusual byte orders;
+//! the types here hold the decoded values and take care of the encoding.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hashes::{sha256d, Hash, HashEngine};
+use hashes::hex::{FromHex, ToHex};
+use hash_types::{BlockHash, TxMerkleNode, Txid};
+use blockdata::block::{BlockHeader, Version};
+use util::pow::{CompactTarget, Difficulty, Target};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
+/// Method name of [`Subscribe`] requests.
+pub const SUBSCRIBE: &str = "mining.subscribe";
+/// Method name of [`Authorize`] requests.
+pub const AUTHORIZE: &str = "mining.authorize";
+/// Method name of [`Notify`] notifications.
+pub const NOTIFY: &str = "mining.notify";
+/// Method name of [`Submit`] requests.
+pub const SUBMIT: &str = "mining.submit";
+/// Method name of [`SetDifficulty`] notifications.
+pub const SET_DIFFICULTY: &str = "mining.set_difficulty";
+
+/// A stratum request, or a notification when it has no id.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Request<P> {
+    /// The request id, `None` for notifications.
+    pub id: Option<u64>,
+    /// The method name, e.g. [`SUBMIT`].
+    pub method: String,
+    /// The method parameters.
+    pub params: P,
+}
+
+/// A response to a stratum [`Request`].
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Response<R> {
+    /// The id of the request this responds to.
+    pub id: Option<u64>,
+    /// The result, if the request succeeded.
+    pub result: Option<R>,
+    /// The error, if the request failed.
+    pub error: Option<RpcError>,
+}
+
+/// An error returned by a stratum server, encoded as `[code, message, traceback]`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RpcError {
+    /// The error code, e.g. 21 for a stale job.
+    pub code: i32,
+    /// A human readable error message.
+    pub message: String,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stratum error {}: {}", self.code, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for RpcError {}
+
+impl Serialize for RpcError {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        (self.code, &self.message, None::<String>).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for RpcError {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (code, message, _traceback): (i32, String, Option<String>) = Deserialize::deserialize(d)?;
+        Ok(RpcError { code, message })
+    }
+}
+
+/// The parameters of a `mining.subscribe` request: `[user_agent, session_id]`, both optional.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct Subscribe {
+    /// The name and version of the mining software.
+    pub user_agent: Option<String>,
+    /// The id of a previous session to resume, keeping its extranonce.
+    pub session_id: Option<String>,
+}
+
+impl Serialize for Subscribe {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut params = vec![self.user_agent.as_ref()];
+        if self.session_id.is_some() {
+            params.push(self.session_id.as_ref());
+        } else if self.user_agent.is_none() {
+            params.clear();
+        }
+        params.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Subscribe {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let mut params: Vec<Option<String>> = Deserialize::deserialize(d)?;
+        params.resize(2, None);
+        Ok(Subscribe {
+            user_agent: params[0].take(),
+            session_id: params[1].take(),
+        })
+    }
+}
+
+/// The result of a `mining.subscribe` request.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SubscribeResult {
+    /// The subscribed notifications as `(method, subscription id)` pairs.
+    pub subscriptions: Vec<(String, String)>,
+    /// The extranonce prefix assigned to the connection.
+    pub extranonce1: Vec<u8>,
+    /// The number of extranonce bytes the miner rolls.
+    pub extranonce2_size: usize,
+}
+
+impl Serialize for SubscribeResult {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        (&self.subscriptions, self.extranonce1.to_hex(), self.extranonce2_size).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for SubscribeResult {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (subscriptions, extranonce1, extranonce2_size): (_, String, _) = Deserialize::deserialize(d)?;
+        Ok(SubscribeResult {
+            subscriptions,
+            extranonce1: FromHex::from_hex(&extranonce1).map_err(D::Error::custom)?,
+            extranonce2_size,
+        })
+    }
+}
+
+/// The parameters of a `mining.authorize` request: `[username, password]`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Authorize(pub String, pub String);
+
+/// The parameters of a `mining.set_difficulty` notification: `[difficulty]`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SetDifficulty(pub f64);
+
+impl SetDifficulty {
+    /// Returns the target shares must meet at this difficulty.
+    ///
+    /// Share difficulty is relative to the target `0x1d00ffff`; difficulties of zero or less
+    /// yield the maximum target.
+    pub fn target(self) -> Target {
+        Difficulty::from_f64(self.0).to_target()
+    }
+}
+
+impl Serialize for SetDifficulty {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        [self.0].serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for SetDifficulty {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (difficulty,): (f64,) = Deserialize::deserialize(d)?;
+        Ok(SetDifficulty(difficulty))
+    }
+}
+
+/// The parameters of a `mining.notify` notification, describing a job.
+///
+/// The coinbase transaction of the job is `coinbase1 || extranonce1 || extranonce2 || coinbase2`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Notify {
+    /// The job id.
+    pub job_id: String,
+    /// The hash of the block the job builds on.
+    pub prev_blockhash: BlockHash,
+    /// The coinbase transaction up to the extranonce.
+    pub coinbase1: Vec<u8>,
+    /// The coinbase transaction after the extranonce.
+    pub coinbase2: Vec<u8>,
+    /// The merkle branch linking the coinbase transaction to the merkle root.
+    pub merkle_branch: Vec<TxMerkleNode>,
+    /// The block version.
+    pub version: Version,
+    /// The compact proof-of-work target of the block.
+    pub bits: CompactTarget,
+    /// The block timestamp.
+    pub time: u32,
+    /// Whether previous jobs must be abandoned.
+    pub clean_jobs: bool,
+}
+
+impl Notify {
+    /// Returns the serialized coinbase transaction for the given extranonces.
+    pub fn coinbase(&self, extranonce1: &[u8], extranonce2: &[u8]) -> Vec<u8> {
+        let mut coinbase = Vec::with_capacity(
+            self.coinbase1.len() + extranonce1.len() + extranonce2.len() + self.coinbase2.len()
+        );
+        coinbase.extend_from_slice(&self.coinbase1);
+        coinbase.extend_from_slice(extranonce1);
+        coinbase.extend_from_slice(extranonce2);
+        coinbase.extend_from_slice(&self.coinbase2);
+        coinbase
+    }
+
+    /// Computes the merkle root of the block for the given extranonces.
+    pub fn merkle_root(&self, extranonce1: &[u8], extranonce2: &[u8]) -> TxMerkleNode {
+        let coinbase = self.coinbase(extranonce1, extranonce2);
+        let root = self.merkle_branch.iter().fold(sha256d::Hash::hash(&coinbase), |acc, node| {
+            let mut engine = sha256d::Hash::engine();
+            engine.input(&acc[..]);
+            engine.input(&node[..]);
+            sha256d::Hash::from_engine(engine)
+        });
+        TxMerkleNode::from_hash(root)
+    }
+
+    /// Builds the block header of the job for the given extranonces, time and nonce.
+    pub fn header(&self, extranonce1: &[u8], extranonce2: &[u8], time: u32, nonce: u32) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            prev_blockhash: self.prev_blockhash,
+            merkle_root: self.merkle_root(extranonce1, extranonce2),
+            time,
+            bits: self.bits.to_consensus(),
+            nonce,
+            aux_data: None,
+        }
+    }
+}
+
+impl Serialize for Notify {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let branch: Vec<String> = self.merkle_branch.iter().map(|node| node[..].to_hex()).collect();
+        (
+            &self.job_id,
+            prev_hash_to_hex(&self.prev_blockhash),
+            self.coinbase1.to_hex(),
+            self.coinbase2.to_hex(),
+            branch,
+            format!("{:08x}", self.version.0 as u32),
+            format!("{:08x}", self.bits),
+            format!("{:08x}", self.time),
+            self.clean_jobs,
+        ).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Notify {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (job_id, prev_blockhash, coinbase1, coinbase2, branch, version, bits, time, clean_jobs):
+            (String, String, String, String, Vec<String>, String, String, String, bool) = Deserialize::deserialize(d)?;
+        let merkle_branch = branch
+            .iter()
+            .map(|node| Vec::from_hex(node).map_err(D::Error::custom).and_then(|bytes| {
+                TxMerkleNode::from_slice(&bytes).map_err(D::Error::custom)
+            }))
+            .collect::<Result<_, _>>()?;
+        Ok(Notify {
+            job_id,
+            prev_blockhash: prev_hash_from_hex(&prev_blockhash).map_err(D::Error::custom)?,
+            coinbase1: FromHex::from_hex(&coinbase1).map_err(D::Error::custom)?,
+            coinbase2: FromHex::from_hex(&coinbase2).map_err(D::Error::custom)?,
+            merkle_branch,
+            version: Version(u32_from_hex::<D>(&version)? as i32),
+            bits: CompactTarget::from_consensus(u32_from_hex::<D>(&bits)?),
+            time: u32_from_hex::<D>(&time)?,
+            clean_jobs,
+        })
+    }
+}
+
+/// The parameters of a `mining.submit` request, submitting a share for a job.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Submit {
+    /// The authorized worker name.
+    pub username: String,
+    /// The id of the job the share is for.
+    pub job_id: String,
+    /// The extranonce rolled by the miner.
+    pub extranonce2: Vec<u8>,
+    /// The block timestamp.
+    pub time: u32,
+    /// The header nonce.
+    pub nonce: u32,
+    /// The rolled version bits, when version rolling (BIP310) is in use.
+    pub version_bits: Option<u32>,
+}
+
+impl Submit {
+    /// Builds the block header the share was hashed from.
+    ///
+    /// `version_mask` is the version rolling mask negotiated with `mining.configure`; the
+    /// [`Submit::version_bits`] outside of it are ignored.
+    pub fn header(&self, job: &Notify, extranonce1: &[u8], version_mask: u32) -> BlockHeader {
+        let mut header = job.header(extranonce1, &self.extranonce2, self.time, self.nonce);
+        if let Some(bits) = self.version_bits {
+            let version = (job.version.0 as u32 & !version_mask) | (bits & version_mask);
+            header.version = Version(version as i32);
+        }
+        header
+    }
+}
+
+impl Serialize for Submit {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut params = vec![
+            self.username.clone(),
+            self.job_id.clone(),
+            self.extranonce2.to_hex(),
+            format!("{:08x}", self.time),
+            format!("{:08x}", self.nonce),
+        ];
+        if let Some(bits) = self.version_bits {
+            params.push(format!("{:08x}", bits));
+        }
+        params.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Submit {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let params: Vec<String> = Deserialize::deserialize(d)?;
+        if params.len() != 5 && params.len() != 6 {
+            return Err(D::Error::invalid_length(params.len(), &"5 or 6 submit parameters"));
+        }
+        Ok(Submit {
+            username: params[0].clone(),
+            job_id: params[1].clone(),
+            extranonce2: FromHex::from_hex(&params[2]).map_err(D::Error::custom)?,
+            time: u32_from_hex::<D>(&params[3])?,
+            nonce: u32_from_hex::<D>(&params[4])?,
+            version_bits: match params.get(5) {
+                Some(bits) => Some(u32_from_hex::<D>(bits)?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// Computes the merkle branch of the coinbase transaction of a block, for a [`Notify`] job.
+///
+/// `txids` are the ids of the transactions of the block after the coinbase.
+pub fn merkle_branch(txids: &[Txid]) -> Vec<TxMerkleNode> {
+    let mut level: Vec<sha256d::Hash> = txids.iter().map(|txid| txid.as_hash()).collect();
+    let mut branch = Vec::new();
+    // The coinbase is always the left-most node of each level, paired with the first other node.
+    while !level.is_empty() {
+        branch.push(TxMerkleNode::from_hash(level[0]));
+        let mut rest = level.split_off(1);
+        if rest.len() % 2 == 1 {
+            let last = rest[rest.len() - 1];
+            rest.push(last);
+        }
+        level = rest
+            .chunks(2)
+            .map(|pair| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(&pair[0][..]);
+                engine.input(&pair[1][..]);
+                sha256d::Hash::from_engine(engine)
+            })
+            .collect();
+    }
+    branch
+}
+
+fn u32_from_hex<'de, D: Deserializer<'de>>(hex: &str) -> Result<u32, D::Error> {
+    u32::from_str_radix(hex, 16).map_err(D::Error::custom)
+}
+
+/// Stratum sends the previous block hash in its internal byte order, but with every 4-byte word
+/// byte-swapped.
+fn prev_hash_to_hex(hash: &BlockHash) -> String {
+    let mut bytes = hash.into_inner();
+    for word in bytes.chunks_mut(4) {
+        word.reverse();
+    }
+    bytes.to_hex()
+}
+
+fn prev_hash_from_hex(hex: &str) -> Result<BlockHash, ::hashes::hex::Error> {
+    let mut bytes = <[u8; 32]>::from_hex(hex)?;
+    for word in bytes.chunks_mut(4) {
+        word.reverse();
+    }
+    Ok(BlockHash::from_inner(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use consensus::encode::deserialize;
+    use blockdata::transaction::Transaction;
+    use util::hash::bitcoin_merkle_root;
+
+    const NOTIFY_JSON: &str = r#"{"id":null,"method":"mining.notify","params":["bf","4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000","01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20020862062f503253482f04b8864e5008","072f736c7573682f000000000100f2052a010000001976a914d23fcdf86f7e756a64a7a9688ef9903327048ed988ac00000000",[],"00000002","1c2ac4af","504e86b9",false]}"#;
+
+    #[test]
+    fn notify() {
+        let request: Request<Notify> = serde_json::from_str(NOTIFY_JSON).unwrap();
+        assert_eq!(request.method, NOTIFY);
+        let job = request.params;
+        assert_eq!(job.job_id, "bf");
+        assert_eq!(
+            job.prev_blockhash.to_string(),
+            "00000000440b921e1b77c6c0487ae5616de67f788f44ae2a5af6e2194d16b6f8"
+        );
+        assert_eq!(job.version, Version(2));
+        assert_eq!(job.bits, CompactTarget::from_consensus(0x1c2ac4af));
+        assert_eq!(job.time, 0x504e86b9);
+        assert!(!job.clean_jobs);
+        assert_eq!(serde_json::to_string(&Request { id: None, method: NOTIFY.to_owned(), params: job.clone() }).unwrap(), NOTIFY_JSON);
+
+        // the coinbase is a valid transaction once the extranonces are filled in
+        let extranonce1 = [0x08, 0x00, 0x00, 0x02];
+        let extranonce2 = [0x00, 0x00, 0x00, 0x01];
+        let coinbase: Transaction = deserialize(&job.coinbase(&extranonce1, &extranonce2)).unwrap();
+        assert!(coinbase.is_coinbase());
+
+        let submit: Request<Submit> = serde_json::from_str(
+            r#"{"id":4,"method":"mining.submit","params":["slush.miner1","bf","00000001","504e86ed","b2957c02"]}"#
+        ).unwrap();
+        let share = submit.params;
+        assert_eq!(share.nonce, 0xb2957c02);
+        let header = share.header(&job, &extranonce1, 0);
+        assert_eq!(header.merkle_root.as_hash(), coinbase.txid().as_hash());
+        assert_eq!(header.time, 0x504e86ed);
+        assert_eq!(header.prev_blockhash, job.prev_blockhash);
+
+        // version rolling only touches the negotiated bits
+        let rolled = Submit { version_bits: Some(0x1fffe000), ..share.clone() };
+        assert_eq!(rolled.header(&job, &extranonce1, 0x00ffe000).version, Version(0x00ffe002));
+        assert_eq!(serde_json::from_str::<Submit>(&serde_json::to_string(&rolled).unwrap()).unwrap(), rolled);
+    }
+
+    #[test]
+    fn coinbase_merkle_branch() {
+        let coinbase = Txid::hash(b"coinbase");
+        for n in 0..8 {
+            let txids: Vec<Txid> = (0..n).map(|i: u8| Txid::hash(&[i])).collect();
+            let job = Notify {
+                job_id: String::new(),
+                prev_blockhash: Default::default(),
+                coinbase1: b"coinbase".to_vec(),
+                coinbase2: vec![],
+                merkle_branch: merkle_branch(&txids),
+                version: Version::ONE,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                time: 0,
+                clean_jobs: true,
+            };
+            let expected: TxMerkleNode = bitcoin_merkle_root(
+                Some(coinbase).into_iter().chain(txids).map(|txid| TxMerkleNode::from_hash(txid.as_hash()))
+            ).unwrap();
+            assert_eq!(job.merkle_root(&[], &[]), expected);
+        }
+    }
+
+    #[test]
+    fn subscribe_and_difficulty() {
+        let subscribe: Subscribe = serde_json::from_str(r#"["cgminer/4.10.0"]"#).unwrap();
+        assert_eq!(subscribe.user_agent, Some("cgminer/4.10.0".to_owned()));
+        assert_eq!(subscribe.session_id, None);
+        assert_eq!(serde_json::to_string(&subscribe).unwrap(), r#"["cgminer/4.10.0"]"#);
+        assert_eq!(serde_json::to_string(&Subscribe::default()).unwrap(), "[]");
+
+        let response: Response<SubscribeResult> = serde_json::from_str(
+            r#"{"id":1,"result":[[["mining.set_difficulty","b4b6693b"],["mining.notify","ae6812eb"]],"08000002",4],"error":null}"#
+        ).unwrap();
+        let result = response.result.unwrap();
+        assert_eq!(result.extranonce1, vec![0x08, 0x00, 0x00, 0x02]);
+        assert_eq!(result.extranonce2_size, 4);
+        assert_eq!(result.subscriptions[1].0, NOTIFY);
+
+        let response: Response<bool> = serde_json::from_str(r#"{"id":4,"result":null,"error":[21,"Job not found",null]}"#).unwrap();
+        assert_eq!(response.error, Some(RpcError { code: 21, message: "Job not found".to_owned() }));
+
+        let difficulty: Request<SetDifficulty> = serde_json::from_str(
+            r#"{"id":null,"method":"mining.set_difficulty","params":[2]}"#
+        ).unwrap();
+        let one = Target::from_compact(CompactTarget::from_consensus(0x1d00ffff));
+        assert_eq!(SetDifficulty(1.0).target(), one);
+        assert_eq!(difficulty.params.target(), Target::from_uint256(one.to_uint256() >> 1));
+        assert_eq!(SetDifficulty(0.0).target(), Target::MAX);
+        assert_eq!(SetDifficulty(-1.0).target(), Target::MAX);
+        assert!(SetDifficulty(1e-3).target() > one);
+    }
+}
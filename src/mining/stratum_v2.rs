@@ -0,0 +1,667 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Stratum v2.
+//!
+//! The binary framing of the stratum v2 protocol and the messages of its
+//! Mining Protocol used by standard channels, the ones header-only mining
+//! devices open. Frames are plaintext; the Noise encryption layer is left to
+//! the transport.
+//!
+
+use prelude::*;
+
+use core::cmp;
+
+use io;
+use hash_types::{BlockHash, TxMerkleNode};
+use blockdata::block::{BlockHeader, Version};
+use consensus::encode::{self, Decodable, Encodable};
+use util::pow::{CompactTarget, Target};
+use util::uint::Uint256;
+
+/// Bit of the frame extension type set for messages addressed to a channel.
+pub const CHANNEL_BIT: u16 = 0x8000;
+
+/// The maximum payload length of a frame, which is encoded in three bytes.
+pub const MAX_PAYLOAD_LENGTH: usize = 0xff_ffff;
+
+/// Protocol value of [`SetupConnection`] for the Mining Protocol.
+pub const MINING_PROTOCOL: u8 = 0;
+
+/// A value which can be a field of a stratum v2 message.
+///
+/// Integers, hashes and targets share their consensus encoding, while strings and byte vectors
+/// carry a one byte length prefix.
+trait Field: Sized {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error>;
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error>;
+}
+
+macro_rules! impl_consensus_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for $ty {
+                fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+                    self.consensus_encode(w)
+                }
+
+                fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+                    Decodable::consensus_decode(r)
+                }
+            }
+        )*
+    }
+}
+impl_consensus_field!(u8, u16, u32, u64, bool, Version, CompactTarget, BlockHash, TxMerkleNode);
+
+impl Field for f32 {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.to_bits().consensus_encode(w)
+    }
+
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        u32::consensus_decode(r).map(f32::from_bits)
+    }
+}
+
+impl Field for Target {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.to_uint256().consensus_encode(w)
+    }
+
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        Uint256::consensus_decode(r).map(Target::from_uint256)
+    }
+}
+
+/// `STR0_255`: a UTF-8 string of up to 255 bytes.
+impl Field for String {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        encode_bytes(self.as_bytes(), 255, w)
+    }
+
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        String::from_utf8(decode_bytes(r)?)
+            .map_err(|_| encode::Error::ParseFailed("STR0_255 is not valid UTF-8"))
+    }
+}
+
+/// `B0_32`: up to 32 bytes.
+impl Field for Vec<u8> {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        encode_bytes(self, 32, w)
+    }
+
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let bytes = decode_bytes(r)?;
+        if bytes.len() > 32 {
+            return Err(encode::Error::ParseFailed("B0_32 is longer than 32 bytes"));
+        }
+        Ok(bytes)
+    }
+}
+
+/// `OPTION[U32]`: a sequence of zero or one values.
+impl Field for Option<u32> {
+    fn encode_field<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        match *self {
+            Some(value) => Ok(1u8.consensus_encode(w)? + value.consensus_encode(w)?),
+            None => 0u8.consensus_encode(w),
+        }
+    }
+
+    fn decode_field<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        match u8::consensus_decode(r)? {
+            0 => Ok(None),
+            1 => Ok(Some(u32::consensus_decode(r)?)),
+            _ => Err(encode::Error::ParseFailed("OPTION holds more than one value")),
+        }
+    }
+}
+
+fn encode_bytes<W: io::Write + ?Sized>(bytes: &[u8], max: usize, w: &mut W) -> Result<usize, io::Error> {
+    if bytes.len() > max {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "field too long for its stratum v2 type"));
+    }
+    (bytes.len() as u8).consensus_encode(w)?;
+    w.write_all(bytes)?;
+    Ok(1 + bytes.len())
+}
+
+fn decode_bytes<R: io::Read + ?Sized>(r: &mut R) -> Result<Vec<u8>, encode::Error> {
+    let len = u8::consensus_decode(r)? as usize;
+    let mut bytes = vec![0; len];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+macro_rules! impl_message_encoding {
+    ($thing:ident, $($field:ident),+) => (
+        impl Encodable for $thing {
+            fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+                let mut len = 0;
+                $(len += self.$field.encode_field(w)?;)+
+                Ok(len)
+            }
+        }
+
+        impl Decodable for $thing {
+            fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<$thing, encode::Error> {
+                Ok($thing {
+                    $($field: Field::decode_field(r)?),+
+                })
+            }
+        }
+    );
+}
+
+/// The first message of a connection, sent by the client.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetupConnection {
+    /// The protocol the client wants to speak, [`MINING_PROTOCOL`] for mining devices.
+    pub protocol: u8,
+    /// The minimum protocol version the client supports.
+    pub min_version: u16,
+    /// The maximum protocol version the client supports.
+    pub max_version: u16,
+    /// Protocol specific feature flags.
+    pub flags: u32,
+    /// The host name the client connected to.
+    pub endpoint_host: String,
+    /// The port the client connected to.
+    pub endpoint_port: u16,
+    /// The vendor of the device.
+    pub vendor: String,
+    /// The hardware version of the device.
+    pub hardware_version: String,
+    /// The firmware of the device.
+    pub firmware: String,
+    /// A device identifier chosen by the operator.
+    pub device_id: String,
+}
+impl_message_encoding!(SetupConnection, protocol, min_version, max_version, flags, endpoint_host,
+    endpoint_port, vendor, hardware_version, firmware, device_id);
+
+/// Accepts a [`SetupConnection`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetupConnectionSuccess {
+    /// The protocol version both sides use from now on.
+    pub used_version: u16,
+    /// The features the server does not support, out of the flags the client requested.
+    pub flags: u32,
+}
+impl_message_encoding!(SetupConnectionSuccess, used_version, flags);
+
+/// Rejects a [`SetupConnection`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetupConnectionError {
+    /// The requested features the server does not support.
+    pub flags: u32,
+    /// The reason of the rejection, e.g. `unsupported-protocol`.
+    pub error_code: String,
+}
+impl_message_encoding!(SetupConnectionError, flags, error_code);
+
+/// Opens a standard channel, over which the client receives jobs with a fixed merkle root.
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpenStandardMiningChannel {
+    /// The id the response refers to.
+    pub request_id: u32,
+    /// The user the shares are credited to.
+    pub user_identity: String,
+    /// The expected hash rate of the device, in hashes per second.
+    pub nominal_hash_rate: f32,
+    /// The maximum target the client accepts for its shares.
+    pub max_target: Target,
+}
+impl_message_encoding!(OpenStandardMiningChannel, request_id, user_identity, nominal_hash_rate, max_target);
+
+/// Confirms an [`OpenStandardMiningChannel`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OpenStandardMiningChannelSuccess {
+    /// The id of the request.
+    pub request_id: u32,
+    /// The id of the new channel.
+    pub channel_id: u32,
+    /// The initial target of the shares of the channel.
+    pub target: Target,
+    /// The extranonce bytes reserved for the channel.
+    pub extranonce_prefix: Vec<u8>,
+    /// The id of the group channel the channel belongs to.
+    pub group_channel_id: u32,
+}
+impl_message_encoding!(OpenStandardMiningChannelSuccess, request_id, channel_id, target, extranonce_prefix,
+    group_channel_id);
+
+/// Rejects an [`OpenStandardMiningChannel`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OpenMiningChannelError {
+    /// The id of the request.
+    pub request_id: u32,
+    /// The reason of the rejection, e.g. `unknown-user`.
+    pub error_code: String,
+}
+impl_message_encoding!(OpenMiningChannelError, request_id, error_code);
+
+/// A new job for a standard channel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NewMiningJob {
+    /// The channel the job is for.
+    pub channel_id: u32,
+    /// The id of the job, referenced by shares.
+    pub job_id: u32,
+    /// The minimum timestamp of the job, or `None` for a future job which may only be mined once
+    /// a [`SetNewPrevHash`] refers to it.
+    pub min_ntime: Option<u32>,
+    /// The block version.
+    pub version: Version,
+    /// The merkle root of the block.
+    pub merkle_root: TxMerkleNode,
+}
+impl_message_encoding!(NewMiningJob, channel_id, job_id, min_ntime, version, merkle_root);
+
+/// Makes a job the current one, built on a new previous block.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetNewPrevHash {
+    /// The channel the message is for.
+    pub channel_id: u32,
+    /// The job to mine from now on.
+    pub job_id: u32,
+    /// The hash of the block the job builds on.
+    pub prev_hash: BlockHash,
+    /// The minimum timestamp of the block.
+    pub min_ntime: u32,
+    /// The compact proof-of-work target of the block.
+    pub nbits: CompactTarget,
+}
+impl_message_encoding!(SetNewPrevHash, channel_id, job_id, prev_hash, min_ntime, nbits);
+
+/// Changes the target shares of a channel must meet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetTarget {
+    /// The channel the message is for.
+    pub channel_id: u32,
+    /// The new target.
+    pub maximum_target: Target,
+}
+impl_message_encoding!(SetTarget, channel_id, maximum_target);
+
+/// A share found on a standard channel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SubmitSharesStandard {
+    /// The channel the share was found on.
+    pub channel_id: u32,
+    /// A sequence number, acknowledged by [`SubmitSharesSuccess`].
+    pub sequence_number: u32,
+    /// The job the share is for.
+    pub job_id: u32,
+    /// The header nonce.
+    pub nonce: u32,
+    /// The header timestamp.
+    pub ntime: u32,
+    /// The header version, including any rolled bits.
+    pub version: Version,
+}
+impl_message_encoding!(SubmitSharesStandard, channel_id, sequence_number, job_id, nonce, ntime, version);
+
+impl SubmitSharesStandard {
+    /// Builds the block header the share was hashed from, given its job and the current
+    /// previous block.
+    pub fn header(&self, job: &NewMiningJob, prev_hash: &SetNewPrevHash) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            prev_blockhash: prev_hash.prev_hash,
+            merkle_root: job.merkle_root,
+            time: self.ntime,
+            bits: prev_hash.nbits.to_consensus(),
+            nonce: self.nonce,
+            aux_data: None,
+        }
+    }
+}
+
+/// Acknowledges the shares of a channel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SubmitSharesSuccess {
+    /// The channel the shares were found on.
+    pub channel_id: u32,
+    /// The sequence number of the last acknowledged share.
+    pub last_sequence_number: u32,
+    /// The number of shares accepted since the previous acknowledgement.
+    pub new_submits_accepted_count: u32,
+    /// The sum of the difficulties of the accepted shares.
+    pub new_shares_sum: u64,
+}
+impl_message_encoding!(SubmitSharesSuccess, channel_id, last_sequence_number, new_submits_accepted_count,
+    new_shares_sum);
+
+/// Rejects a share.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SubmitSharesError {
+    /// The channel the share was found on.
+    pub channel_id: u32,
+    /// The sequence number of the rejected share.
+    pub sequence_number: u32,
+    /// The reason of the rejection, e.g. `stale-share`.
+    pub error_code: String,
+}
+impl_message_encoding!(SubmitSharesError, channel_id, sequence_number, error_code);
+
+/// A stratum v2 message, encoded as a frame.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    /// `SetupConnection`
+    SetupConnection(SetupConnection),
+    /// `SetupConnection.Success`
+    SetupConnectionSuccess(SetupConnectionSuccess),
+    /// `SetupConnection.Error`
+    SetupConnectionError(SetupConnectionError),
+    /// `OpenStandardMiningChannel`
+    OpenStandardMiningChannel(OpenStandardMiningChannel),
+    /// `OpenStandardMiningChannel.Success`
+    OpenStandardMiningChannelSuccess(OpenStandardMiningChannelSuccess),
+    /// `OpenMiningChannel.Error`
+    OpenMiningChannelError(OpenMiningChannelError),
+    /// `NewMiningJob`
+    NewMiningJob(NewMiningJob),
+    /// `SetNewPrevHash`
+    SetNewPrevHash(SetNewPrevHash),
+    /// `SetTarget`
+    SetTarget(SetTarget),
+    /// `SubmitSharesStandard`
+    SubmitSharesStandard(SubmitSharesStandard),
+    /// `SubmitShares.Success`
+    SubmitSharesSuccess(SubmitSharesSuccess),
+    /// `SubmitShares.Error`
+    SubmitSharesError(SubmitSharesError),
+    /// Any other message, including those of protocol extensions.
+    Unknown {
+        /// The extension type of the frame, including the [`CHANNEL_BIT`].
+        extension_type: u16,
+        /// The message type.
+        msg_type: u8,
+        /// The payload of the frame.
+        payload: Vec<u8>,
+    },
+}
+
+impl Message {
+    /// Returns the message type of the frame.
+    pub fn msg_type(&self) -> u8 {
+        match *self {
+            Message::SetupConnection(_) => 0x00,
+            Message::SetupConnectionSuccess(_) => 0x01,
+            Message::SetupConnectionError(_) => 0x02,
+            Message::OpenStandardMiningChannel(_) => 0x10,
+            Message::OpenStandardMiningChannelSuccess(_) => 0x11,
+            Message::OpenMiningChannelError(_) => 0x12,
+            Message::NewMiningJob(_) => 0x15,
+            Message::SubmitSharesStandard(_) => 0x1a,
+            Message::SubmitSharesSuccess(_) => 0x1c,
+            Message::SubmitSharesError(_) => 0x1d,
+            Message::SetNewPrevHash(_) => 0x20,
+            Message::SetTarget(_) => 0x21,
+            Message::Unknown { msg_type, .. } => msg_type,
+        }
+    }
+
+    /// Returns the extension type of the frame, with the [`CHANNEL_BIT`] set for messages
+    /// addressed to a channel.
+    pub fn extension_type(&self) -> u16 {
+        match *self {
+            Message::NewMiningJob(_)
+            | Message::SetNewPrevHash(_)
+            | Message::SetTarget(_)
+            | Message::SubmitSharesStandard(_)
+            | Message::SubmitSharesSuccess(_)
+            | Message::SubmitSharesError(_) => CHANNEL_BIT,
+            Message::Unknown { extension_type, .. } => extension_type,
+            _ => 0,
+        }
+    }
+
+    fn encode_payload<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        match *self {
+            Message::SetupConnection(ref msg) => msg.consensus_encode(w),
+            Message::SetupConnectionSuccess(ref msg) => msg.consensus_encode(w),
+            Message::SetupConnectionError(ref msg) => msg.consensus_encode(w),
+            Message::OpenStandardMiningChannel(ref msg) => msg.consensus_encode(w),
+            Message::OpenStandardMiningChannelSuccess(ref msg) => msg.consensus_encode(w),
+            Message::OpenMiningChannelError(ref msg) => msg.consensus_encode(w),
+            Message::NewMiningJob(ref msg) => msg.consensus_encode(w),
+            Message::SetNewPrevHash(ref msg) => msg.consensus_encode(w),
+            Message::SetTarget(ref msg) => msg.consensus_encode(w),
+            Message::SubmitSharesStandard(ref msg) => msg.consensus_encode(w),
+            Message::SubmitSharesSuccess(ref msg) => msg.consensus_encode(w),
+            Message::SubmitSharesError(ref msg) => msg.consensus_encode(w),
+            Message::Unknown { ref payload, .. } => {
+                w.write_all(payload)?;
+                Ok(payload.len())
+            }
+        }
+    }
+}
+
+impl Encodable for Message {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut payload = Vec::new();
+        self.encode_payload(&mut payload)?;
+        if payload.len() > MAX_PAYLOAD_LENGTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "stratum v2 payload too long"));
+        }
+        let mut len = 0;
+        len += self.extension_type().consensus_encode(w)?;
+        len += self.msg_type().consensus_encode(w)?;
+        let length = payload.len();
+        w.write_all(&[length as u8, (length >> 8) as u8, (length >> 16) as u8])?;
+        w.write_all(&payload)?;
+        Ok(len + 3 + payload.len())
+    }
+}
+
+/// Reads a payload of `length` bytes, growing the buffer as the data arrives instead of
+/// allocating up to [`MAX_PAYLOAD_LENGTH`] bytes upfront on the word of the peer.
+fn read_payload<R: io::Read + ?Sized>(r: &mut R, length: usize) -> Result<Vec<u8>, encode::Error> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+    let mut payload = Vec::with_capacity(cmp::min(length, CHUNK_SIZE));
+    while payload.len() < length {
+        let start = payload.len();
+        payload.resize(start + cmp::min(length - start, CHUNK_SIZE), 0);
+        r.read_exact(&mut payload[start..])?;
+    }
+    Ok(payload)
+}
+
+impl Decodable for Message {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let extension_type = u16::consensus_decode(r)?;
+        let msg_type = u8::consensus_decode(r)?;
+        let mut length = [0u8; 3];
+        r.read_exact(&mut length)?;
+        let length = length[0] as usize | (length[1] as usize) << 8 | (length[2] as usize) << 16;
+        let payload = read_payload(r, length)?;
+
+        // Extensions reuse the message types of the base protocol.
+        if extension_type & !CHANNEL_BIT != 0 {
+            return Ok(Message::Unknown { extension_type, msg_type, payload });
+        }
+        let mut d = io::Cursor::new(&payload[..]);
+        let message = match msg_type {
+            0x00 => Message::SetupConnection(Decodable::consensus_decode(&mut d)?),
+            0x01 => Message::SetupConnectionSuccess(Decodable::consensus_decode(&mut d)?),
+            0x02 => Message::SetupConnectionError(Decodable::consensus_decode(&mut d)?),
+            0x10 => Message::OpenStandardMiningChannel(Decodable::consensus_decode(&mut d)?),
+            0x11 => Message::OpenStandardMiningChannelSuccess(Decodable::consensus_decode(&mut d)?),
+            0x12 => Message::OpenMiningChannelError(Decodable::consensus_decode(&mut d)?),
+            0x15 => Message::NewMiningJob(Decodable::consensus_decode(&mut d)?),
+            0x1a => Message::SubmitSharesStandard(Decodable::consensus_decode(&mut d)?),
+            0x1c => Message::SubmitSharesSuccess(Decodable::consensus_decode(&mut d)?),
+            0x1d => Message::SubmitSharesError(Decodable::consensus_decode(&mut d)?),
+            0x20 => Message::SetNewPrevHash(Decodable::consensus_decode(&mut d)?),
+            0x21 => Message::SetTarget(Decodable::consensus_decode(&mut d)?),
+            _ => return Ok(Message::Unknown { extension_type, msg_type, payload }),
+        };
+        if d.position() != length as u64 {
+            return Err(encode::Error::ParseFailed("data not consumed entirely when decoding stratum v2 payload"));
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::FromHex;
+    use consensus::encode::{deserialize, serialize};
+
+    fn messages() -> Vec<Message> {
+        let target = Target::from_compact(CompactTarget::from_consensus(0x1d00ffff));
+        vec![
+            Message::SetupConnectionSuccess(SetupConnectionSuccess { used_version: 2, flags: 0 }),
+            Message::SetupConnectionError(SetupConnectionError { flags: 1, error_code: "unsupported-protocol".to_owned() }),
+            Message::OpenStandardMiningChannel(OpenStandardMiningChannel {
+                request_id: 1,
+                user_identity: "miner.1".to_owned(),
+                nominal_hash_rate: 1.5e14,
+                max_target: target,
+            }),
+            Message::OpenStandardMiningChannelSuccess(OpenStandardMiningChannelSuccess {
+                request_id: 1,
+                channel_id: 7,
+                target,
+                extranonce_prefix: vec![1, 2, 3, 4],
+                group_channel_id: 0,
+            }),
+            Message::OpenMiningChannelError(OpenMiningChannelError { request_id: 2, error_code: "unknown-user".to_owned() }),
+            Message::NewMiningJob(NewMiningJob {
+                channel_id: 7,
+                job_id: 1,
+                min_ntime: None,
+                version: Version::NO_SOFT_FORK_SIGNALLING,
+                merkle_root: Default::default(),
+            }),
+            Message::SetNewPrevHash(SetNewPrevHash {
+                channel_id: 7,
+                job_id: 1,
+                prev_hash: Default::default(),
+                min_ntime: 1_600_000_000,
+                nbits: CompactTarget::from_consensus(0x1d00ffff),
+            }),
+            Message::SetTarget(SetTarget { channel_id: 7, maximum_target: target }),
+            Message::SubmitSharesStandard(SubmitSharesStandard {
+                channel_id: 7,
+                sequence_number: 0,
+                job_id: 1,
+                nonce: 42,
+                ntime: 1_600_000_001,
+                version: Version::NO_SOFT_FORK_SIGNALLING,
+            }),
+            Message::SubmitSharesSuccess(SubmitSharesSuccess {
+                channel_id: 7,
+                last_sequence_number: 0,
+                new_submits_accepted_count: 1,
+                new_shares_sum: 1,
+            }),
+            Message::SubmitSharesError(SubmitSharesError { channel_id: 7, sequence_number: 1, error_code: "stale-share".to_owned() }),
+            Message::Unknown { extension_type: 0x8001, msg_type: 0x10, payload: vec![0xab] },
+        ]
+    }
+
+    #[test]
+    fn frame_roundtrip() {
+        for message in messages() {
+            let encoded = serialize(&message);
+            let extension_type = message.extension_type();
+            assert_eq!(encoded[0..2], [extension_type as u8, (extension_type >> 8) as u8]);
+            assert_eq!(encoded[2], message.msg_type());
+            assert_eq!(encoded.len() - 6, encoded[3] as usize);
+            assert_eq!(deserialize::<Message>(&encoded).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn setup_connection() {
+        let message = Message::SetupConnection(SetupConnection {
+            protocol: MINING_PROTOCOL,
+            min_version: 2,
+            max_version: 2,
+            flags: 1,
+            endpoint_host: "a".to_owned(),
+            endpoint_port: 3333,
+            vendor: "b".to_owned(),
+            hardware_version: String::new(),
+            firmware: String::new(),
+            device_id: String::new(),
+        });
+        let encoded = Vec::from_hex("0000001200000002000200010000000161050d0162000000").unwrap();
+        assert_eq!(serialize(&message), encoded);
+        assert_eq!(deserialize::<Message>(&encoded).unwrap(), message);
+
+        // strings are limited to 255 bytes
+        let mut long = match message {
+            Message::SetupConnection(msg) => msg,
+            _ => unreachable!(),
+        };
+        long.vendor = "x".repeat(256);
+        assert!(Message::SetupConnection(long).consensus_encode(&mut Vec::new()).is_err());
+
+        // trailing payload bytes are rejected
+        let trailing = Vec::from_hex("00000107000002000000000000ff").unwrap();
+        assert!(deserialize::<Message>(&trailing).is_err());
+
+        // payloads shorter than their length are rejected, and longer ones are read in chunks
+        let truncated = Vec::from_hex("000000ffffff0000000000").unwrap();
+        assert!(deserialize::<Message>(&truncated).is_err());
+        let unknown = Message::Unknown { extension_type: 0x8001, msg_type: 0x10, payload: vec![0xab; 100_000] };
+        assert_eq!(deserialize::<Message>(&serialize(&unknown)).unwrap(), unknown);
+    }
+
+    #[test]
+    fn share_header() {
+        let job = NewMiningJob {
+            channel_id: 1,
+            job_id: 3,
+            min_ntime: Some(1_600_000_000),
+            version: Version(0x2000_0000),
+            merkle_root: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".parse().unwrap(),
+        };
+        let prev_hash = SetNewPrevHash {
+            channel_id: 1,
+            job_id: 3,
+            prev_hash: "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f".parse().unwrap(),
+            min_ntime: 1_600_000_000,
+            nbits: CompactTarget::from_consensus(0x1d00ffff),
+        };
+        let share = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 5,
+            job_id: 3,
+            nonce: 0xdeadbeef,
+            ntime: 1_600_000_030,
+            version: Version(0x2000_2000),
+        };
+        let header = share.header(&job, &prev_hash);
+        assert_eq!(header.version, share.version);
+        assert_eq!(header.prev_blockhash, prev_hash.prev_hash);
+        assert_eq!(header.merkle_root, job.merkle_root);
+        assert_eq!(header.time, 1_600_000_030);
+        assert_eq!(header.bits, 0x1d00ffff);
+        assert_eq!(header.nonce, 0xdeadbeef);
+
+        let encoded = serialize(&Message::NewMiningJob(job.clone()));
+        assert_eq!(encoded[14..19], [1, 0x00, 0x10, 0x5e, 0x5f]);
+        assert_eq!(deserialize::<Message>(&encoded).unwrap(), Message::NewMiningJob(job));
+    }
+}
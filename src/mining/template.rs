@@ -0,0 +1,362 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Block templates.
+//!
+//! Types mirroring the result of the `getblocktemplate` RPC (BIP22/BIP23 and
+//! the BIP9/BIP145 extensions), and the assembly of a template into a block
+//! that only needs its nonce and time rolled before being submitted.
+//!
+
+use prelude::*;
+
+use hash_types::{BlockHash, Txid, Wtxid};
+use blockdata::block::{Block, BlockHeader, Version};
+use blockdata::script::{self, Script};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use blockdata::witness::Witness;
+use util::amount::Amount;
+use util::pow::{CompactTarget, Target};
+
+/// The witness reserved value committed to by the `default_witness_commitment` of a template.
+const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+/// A transaction of a [`BlockTemplate`], to be included in the block after the coinbase.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TemplateTransaction {
+    /// The transaction itself.
+    #[cfg_attr(feature = "serde", serde(with = "::consensus::serde::With::<::consensus::serde::Hex>"))]
+    pub data: Transaction,
+    /// The transaction id.
+    pub txid: Txid,
+    /// The witness transaction id.
+    #[cfg_attr(feature = "serde", serde(rename = "hash"))]
+    pub wtxid: Wtxid,
+    /// Indices, starting at 1, of the template transactions this transaction spends outputs of.
+    pub depends: Vec<u32>,
+    /// The fee paid by the transaction.
+    #[cfg_attr(feature = "serde", serde(with = "::util::amount::serde::as_sat"))]
+    pub fee: Amount,
+    /// The signature operations cost of the transaction.
+    pub sigops: u32,
+    /// The weight of the transaction.
+    pub weight: u32,
+}
+
+/// A block template as returned by the `getblocktemplate` RPC.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockTemplate {
+    /// The features supported by the server, e.g. `proposal`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub capabilities: Vec<String>,
+    /// The block version to use.
+    pub version: Version,
+    /// The deployment rules the block must follow; rules prefixed with `!` must be understood
+    /// by the client.
+    pub rules: Vec<String>,
+    /// Pending deployments the client may signal for, by name, with their version bit.
+    #[cfg_attr(feature = "serde", serde(rename = "vbavailable", default))]
+    pub version_bits_available: BTreeMap<String, u8>,
+    /// The version bits the server requires to be set.
+    #[cfg_attr(feature = "serde", serde(rename = "vbrequired", default))]
+    pub version_bits_required: u32,
+    /// The hash of the block the new block builds on.
+    #[cfg_attr(feature = "serde", serde(rename = "previousblockhash"))]
+    pub previous_block_hash: BlockHash,
+    /// The transactions to include after the coinbase, in order.
+    pub transactions: Vec<TemplateTransaction>,
+    /// Data to include in the coinbase script sig.
+    #[cfg_attr(feature = "serde", serde(rename = "coinbaseaux", default, with = "::serde_utils::btreemap_byte_values"))]
+    pub coinbase_aux: BTreeMap<String, Vec<u8>>,
+    /// The total value the coinbase outputs may pay: the block subsidy plus the fees.
+    #[cfg_attr(feature = "serde", serde(rename = "coinbasevalue", with = "::util::amount::serde::as_sat"))]
+    pub coinbase_value: Amount,
+    /// Identifier to pass to a long polling request for the next template.
+    #[cfg_attr(feature = "serde", serde(rename = "longpollid", default, skip_serializing_if = "Option::is_none"))]
+    pub long_poll_id: Option<String>,
+    /// The proof-of-work target.
+    #[cfg_attr(feature = "serde", serde(with = "self::serde_hex::target"))]
+    pub target: Target,
+    /// The minimum timestamp of the block.
+    #[cfg_attr(feature = "serde", serde(rename = "mintime"))]
+    pub min_time: u32,
+    /// The ways the client may alter the template, e.g. `time` or `transactions`.
+    pub mutable: Vec<String>,
+    /// The maximum signature operations cost of the block.
+    #[cfg_attr(feature = "serde", serde(rename = "sigoplimit"))]
+    pub sigop_limit: u32,
+    /// The maximum serialized size of the block.
+    #[cfg_attr(feature = "serde", serde(rename = "sizelimit"))]
+    pub size_limit: u32,
+    /// The maximum weight of the block.
+    #[cfg_attr(feature = "serde", serde(rename = "weightlimit"))]
+    pub weight_limit: u32,
+    /// The current time as seen by the server, to use as the block timestamp.
+    #[cfg_attr(feature = "serde", serde(rename = "curtime"))]
+    pub current_time: u32,
+    /// The compact proof-of-work target of the block.
+    #[cfg_attr(feature = "serde", serde(with = "self::serde_hex::bits"))]
+    pub bits: CompactTarget,
+    /// The height of the block.
+    pub height: u32,
+    /// The witness commitment output script for the template transactions, assuming a witness
+    /// reserved value of all zeros.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub default_witness_commitment: Option<Script>,
+    /// The challenge blocks must solve, on signet.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub signet_challenge: Option<Script>,
+}
+
+impl BlockTemplate {
+    /// Builds the coinbase transaction paying [`BlockTemplate::coinbase_value`] to `script_pubkey`.
+    ///
+    /// The script sig holds the BIP34 block height, the `coinbaseaux` data and `extranonce`,
+    /// which must be kept short enough for the script sig to stay within the 100 bytes consensus
+    /// allows. If the template has a default witness commitment, it is added as a second output
+    /// and the witness reserved value is set accordingly.
+    pub fn coinbase(&self, script_pubkey: Script, extranonce: &[u8]) -> Transaction {
+        let mut script_sig = script::Builder::new().push_int(self.height as i64);
+        for aux in self.coinbase_aux.values() {
+            script_sig = script_sig.push_slice(aux);
+        }
+        // An empty extranonce pushes OP_0, which also keeps the script sig at least two bytes long.
+        script_sig = script_sig.push_slice(extranonce);
+
+        let mut output = vec![TxOut {
+            value: self.coinbase_value.as_sat(),
+            script_pubkey,
+        }];
+        let witness = match self.default_witness_commitment {
+            Some(ref commitment) => {
+                output.push(TxOut { value: 0, script_pubkey: commitment.clone() });
+                Witness::from_vec(vec![WITNESS_RESERVED_VALUE.to_vec()])
+            }
+            None => Witness::default(),
+        };
+
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: script_sig.into_script(),
+                sequence: 0xFFFFFFFF,
+                witness,
+            }],
+            output,
+        }
+    }
+
+    /// Assembles the template into a block, with a coinbase built by [`BlockTemplate::coinbase`].
+    ///
+    /// The header takes its version, timestamp and target from the template and has a zero nonce;
+    /// only the nonce, time and `extranonce` remain to be rolled to find a proof of work.
+    pub fn assemble(&self, script_pubkey: Script, extranonce: &[u8]) -> Block {
+        let coinbase = self.coinbase(script_pubkey, extranonce);
+        let txdata = Some(coinbase)
+            .into_iter()
+            .chain(self.transactions.iter().map(|tx| tx.data.clone()))
+            .collect();
+        let mut block = Block {
+            header: BlockHeader {
+                version: self.version,
+                prev_blockhash: self.previous_block_hash,
+                merkle_root: Default::default(),
+                time: self.current_time,
+                bits: self.bits.to_consensus(),
+                nonce: 0,
+                aux_data: None,
+            },
+            txdata,
+            extension: None,
+        };
+        block.header.merkle_root = block.compute_merkle_root().expect("block has a coinbase");
+        block
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod serde_hex {
+    //! The template encodes its targets as big-endian hex strings.
+
+    pub mod bits {
+        use prelude::*;
+        use serde::{self, Deserialize, Serialize};
+        use util::pow::CompactTarget;
+
+        pub fn serialize<S: serde::Serializer>(bits: &CompactTarget, s: S) -> Result<S::Ok, S::Error> {
+            if !s.is_human_readable() {
+                bits.serialize(s)
+            } else {
+                s.serialize_str(&format!("{:08x}", bits))
+            }
+        }
+
+        pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<CompactTarget, D::Error> {
+            if !d.is_human_readable() {
+                CompactTarget::deserialize(d)
+            } else {
+                let hex = String::deserialize(d)?;
+                u32::from_str_radix(&hex, 16)
+                    .map(CompactTarget::from_consensus)
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    }
+
+    pub mod target {
+        use prelude::*;
+        use hashes::hex::FromHex;
+        use serde::{self, Deserialize, Serialize};
+        use util::pow::Target;
+        use util::uint::Uint256;
+
+        pub fn serialize<S: serde::Serializer>(target: &Target, s: S) -> Result<S::Ok, S::Error> {
+            if !s.is_human_readable() {
+                target.serialize(s)
+            } else {
+                s.serialize_str(&format!("{:x}", target))
+            }
+        }
+
+        pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Target, D::Error> {
+            if !d.is_human_readable() {
+                Target::deserialize(d)
+            } else {
+                let hex = String::deserialize(d)?;
+                let bytes = <[u8; 32]>::from_hex(&hex).map_err(serde::de::Error::custom)?;
+                Ok(Target::from_uint256(Uint256::from_be_bytes(bytes)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::FromHex;
+    use consensus::encode::deserialize;
+
+    fn template() -> BlockTemplate {
+        let bits = CompactTarget::from_consensus(0x207fffff);
+        BlockTemplate {
+            capabilities: vec!["proposal".to_owned()],
+            version: Version(0x20000000),
+            rules: vec!["csv".to_owned(), "!segwit".to_owned(), "taproot".to_owned()],
+            version_bits_available: BTreeMap::new(),
+            version_bits_required: 0,
+            previous_block_hash: "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206".parse().unwrap(),
+            transactions: vec![],
+            coinbase_aux: BTreeMap::new(),
+            coinbase_value: Amount::from_sat(5_000_000_000),
+            long_poll_id: None,
+            target: Target::from_compact(bits),
+            min_time: 1296688603,
+            mutable: vec!["time".to_owned(), "transactions".to_owned(), "prevblock".to_owned()],
+            sigop_limit: 80_000,
+            size_limit: 4_000_000,
+            weight_limit: 4_000_000,
+            current_time: 1296688700,
+            bits,
+            height: 1,
+            default_witness_commitment: Some(Script::from(Vec::from_hex(
+                "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9"
+            ).unwrap())),
+            signet_challenge: None,
+        }
+    }
+
+    #[test]
+    fn assemble() {
+        let script_pubkey = Script::new_op_return(&[]);
+        let mut template = template();
+
+        let mut block = template.assemble(script_pubkey.clone(), &[1, 2, 3, 4]);
+        assert!(block.check_merkle_root());
+        assert!(block.check_witness_commitment());
+        assert_eq!(block.bip34_block_height(), Ok(1));
+        assert_eq!(block.txdata[0].output[0].value, 5_000_000_000);
+        assert_eq!(block.header.time, 1296688700);
+        assert_eq!(block.header.prev_blockhash, template.previous_block_hash);
+        assert!(block.header.solve(template.target, false).is_some());
+
+        // a different extranonce yields a different merkle root
+        let other = template.assemble(script_pubkey.clone(), &[1, 2, 3, 5]);
+        assert_ne!(other.header.merkle_root, block.header.merkle_root);
+        // a one byte height and no extranonce still make a valid coinbase script sig
+        assert_eq!(template.coinbase(script_pubkey.clone(), &[]).input[0].script_sig.len(), 2);
+
+        // template transactions follow the coinbase
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        template.default_witness_commitment = None;
+        template.transactions.push(TemplateTransaction {
+            txid: tx.txid(),
+            wtxid: tx.wtxid(),
+            depends: vec![],
+            fee: Amount::from_sat(1000),
+            sigops: 4,
+            weight: tx.weight().to_wu() as u32,
+            data: tx.clone(),
+        });
+        let block = template.assemble(script_pubkey, &[]);
+        assert_eq!(block.txdata.len(), 2);
+        assert_eq!(block.txdata[1], tx);
+        assert_eq!(block.txdata[0].output.len(), 1);
+        assert!(block.check_merkle_root());
+        assert!(block.check_witness_commitment());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_getblocktemplate() {
+        use serde_json;
+
+        // regtest node at height 0
+        let json = r#"{
+            "capabilities": ["proposal"],
+            "version": 536870912,
+            "rules": ["csv", "!segwit", "taproot"],
+            "vbavailable": {},
+            "vbrequired": 0,
+            "previousblockhash": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+            "transactions": [],
+            "coinbaseaux": {},
+            "coinbasevalue": 5000000000,
+            "longpollid": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e22060",
+            "target": "7fffff0000000000000000000000000000000000000000000000000000000000",
+            "mintime": 1296688603,
+            "mutable": ["time", "transactions", "prevblock"],
+            "noncerange": "00000000ffffffff",
+            "sigoplimit": 80000,
+            "sizelimit": 4000000,
+            "weightlimit": 4000000,
+            "curtime": 1296688700,
+            "bits": "207fffff",
+            "height": 1,
+            "default_witness_commitment": "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9"
+        }"#;
+        let parsed: BlockTemplate = serde_json::from_str(json).unwrap();
+        let mut expected = template();
+        expected.long_poll_id = Some("0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e22060".to_owned());
+        assert_eq!(parsed, expected);
+
+        let json = serde_json::to_string(&parsed).unwrap();
+        assert_eq!(serde_json::from_str::<BlockTemplate>(&json).unwrap(), parsed);
+    }
+}
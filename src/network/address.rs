@@ -26,9 +26,10 @@ use std::net::{SocketAddr, Ipv6Addr, SocketAddrV4, SocketAddrV6, Ipv4Addr, ToSoc
 use io;
 use network::constants::ServiceFlags;
 use consensus::encode::{self, Decodable, Encodable, VarInt, ReadExt, WriteExt};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// A message which can be sent on the Bitcoin network
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Address {
     /// Services provided by the peer whose address this is
     pub services: ServiceFlags,
@@ -38,6 +39,8 @@ pub struct Address {
     pub port: u16
 }
 
+arbitrary_struct_impl!(Address, services, address, port);
+
 const ONION: [u16; 3] = [0xFD87, 0xD87E, 0xEB43];
 
 impl Address {
@@ -122,7 +125,7 @@ impl ToSocketAddrs for Address {
 }
 
 /// Supported networks for use in BIP155 addrv2 message
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum AddrV2 {
     /// IPV4
     Ipv4(Ipv4Addr),
@@ -140,6 +143,31 @@ pub enum AddrV2 {
     Unknown(u8, Vec<u8>),
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for AddrV2 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=6u8)? {
+            0 => AddrV2::Ipv4(u.arbitrary()?),
+            1 => AddrV2::Ipv6(u.arbitrary()?),
+            2 => AddrV2::TorV2(u.arbitrary()?),
+            3 => AddrV2::TorV3(u.arbitrary()?),
+            4 => AddrV2::I2p(u.arbitrary()?),
+            5 => {
+                // CJDNS addresses start with 0xFC
+                let mut addr: [u8; 16] = u.arbitrary()?;
+                addr[0] = 0xFC;
+                AddrV2::Cjdns(Ipv6Addr::from(addr))
+            },
+            _ => {
+                let network_id = u.int_in_range(7..=255)?;
+                let len = u.int_in_range(0..=512)?;
+                AddrV2::Unknown(network_id, u.bytes(len)?.to_vec())
+            }
+        })
+    }
+}
+
 impl Encodable for AddrV2 {
     fn consensus_encode<W: io::Write + ?Sized>(&self, e: &mut W) -> Result<usize, io::Error> {
         fn encode_addr<W: io::Write>(mut e: W, network: u8, bytes: &[u8]) -> Result<usize, io::Error> {
@@ -233,7 +261,7 @@ impl Decodable for AddrV2 {
 }
 
 /// Address received from BIP155 addrv2 message
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct AddrV2Message {
     /// Time that this node was last seen as connected to the network
     pub time: u32,
@@ -245,6 +273,8 @@ pub struct AddrV2Message {
     pub port: u16
 }
 
+arbitrary_struct_impl!(AddrV2Message, time, services, addr, port);
+
 impl AddrV2Message {
     /// Extract socket address from an [AddrV2Message] message.
     /// This will return [io::Error] [io::ErrorKind::AddrNotAvailable]
@@ -485,4 +515,24 @@ mod test {
 
         assert_eq!(serialize(&addresses), raw);
     }
+
+    #[test]
+    fn ordering_test() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let addr = |ip: &str, port| Address::new(&SocketAddr::new(IpAddr::from_str(ip).unwrap(), port), ServiceFlags::NETWORK);
+        let addresses = vec![addr("10.0.0.2", 8333), addr("10.0.0.1", 8334), addr("10.0.0.1", 8333), addr("10.0.0.1", 8333)];
+        let sorted: Vec<_> = addresses.iter().cloned().collect::<BTreeSet<_>>().into_iter().collect();
+        assert_eq!(sorted, vec![addr("10.0.0.1", 8333), addr("10.0.0.1", 8334), addr("10.0.0.2", 8333)]);
+        assert_eq!(addresses.iter().collect::<HashSet<_>>().len(), 3);
+
+        // addrv2 addresses are ordered by network id first
+        let ipv4 = AddrV2::Ipv4(Ipv4Addr::new(9, 9, 9, 9));
+        let ipv6 = AddrV2::Ipv6(Ipv6Addr::from_str("::1").unwrap());
+        assert!(ipv4 < ipv6);
+        assert!(ipv6 < AddrV2::TorV3([0; 32]));
+        let message = |time, addr| AddrV2Message { time, services: ServiceFlags::NONE, addr, port: 8333 };
+        let messages: BTreeSet<_> = vec![message(2, ipv4.clone()), message(1, ipv6), message(1, ipv4)].into_iter().collect();
+        assert_eq!(messages.iter().map(|m| m.time).collect::<Vec<_>>(), vec![1, 1, 2]);
+    }
 }
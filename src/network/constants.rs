@@ -41,6 +41,7 @@ use core::{fmt, ops, convert::From};
 
 use io;
 use consensus::encode::{self, Encodable, Decodable};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// Version of the protocol as appearing in network message headers
 /// This constant is used to signal to other peers which features you support.
@@ -74,6 +75,14 @@ user_enum! {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Network {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest])?)
+    }
+}
+
 impl Network {
     /// Creates a `Network` from the magic bytes.
     ///
@@ -286,6 +295,14 @@ impl Decodable for ServiceFlags {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for ServiceFlags {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ServiceFlags(u.arbitrary()?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Network, ServiceFlags};
@@ -0,0 +1,277 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Socket reads and writes with deadlines.
+//!
+//! This module reads and writes network messages over a TCP socket, failing
+//! with [`Error::Timeout`] when a whole message isn't transferred in time,
+//! rather than only bounding each individual socket operation. A
+//! [`StallDetector`] additionally tracks progress across messages, such as
+//! blocks being downloaded, so a peer which keeps the connection alive
+//! without sending the requested data can be detected and evicted.
+//!
+
+use core::fmt;
+use std::error;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use io::{self, Read, Write};
+
+use consensus::encode::{self, MAX_VEC_SIZE};
+use network::framing::HEADER_SIZE;
+use network::message::RawNetworkMessage;
+use util::endian;
+
+/// Seconds after which Bitcoin Core disconnects a peer stalling the download of a block.
+pub const BLOCK_STALLING_TIMEOUT_SECS: u64 = 2;
+
+/// Seconds after which Bitcoin Core disconnects a peer which didn't answer a ping.
+pub const PING_TIMEOUT_SECS: u64 = 20 * 60;
+
+/// Deadline-aware read and write error
+#[derive(Debug)]
+pub enum Error {
+    /// A message wasn't read or written within the given timeout
+    Timeout(Duration),
+    /// No progress was made within the given timeout
+    Stalled(Duration),
+    /// An I/O error
+    Io(io::Error),
+    /// The received message couldn't be decoded
+    Decode(encode::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Timeout(ref t) => write!(f, "message not transferred within {:?}", t),
+            Error::Stalled(ref t) => write!(f, "no progress made within {:?}", t),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Decode(ref e) => write!(f, "decoding error: {}", e),
+        }
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Decode(ref e) => Some(e),
+            Error::Timeout(_) | Error::Stalled(_) => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[doc(hidden)]
+impl From<encode::Error> for Error {
+    fn from(err: encode::Error) -> Self {
+        Error::Decode(err)
+    }
+}
+
+/// Reads the next message from `stream`, failing with [`Error::Timeout`] unless all its bytes
+/// are received within `timeout`.
+///
+/// The read timeout of the stream is changed, and left set to the time which remained when the
+/// last bytes were received.
+pub fn read_message(stream: &mut TcpStream, timeout: Duration) -> Result<RawNetworkMessage, Error> {
+    let deadline = Instant::now() + timeout;
+    let mut message = vec![0u8; HEADER_SIZE];
+    read_exact_by(stream, &mut message, deadline, timeout)?;
+
+    let payload_len = endian::slice_to_u32_le(&message[16..20]) as usize;
+    if payload_len > MAX_VEC_SIZE {
+        return Err(encode::Error::OversizedVectorAllocation { requested: payload_len, max: MAX_VEC_SIZE }.into());
+    }
+    message.resize(HEADER_SIZE + payload_len, 0);
+    read_exact_by(stream, &mut message[HEADER_SIZE..], deadline, timeout)?;
+    Ok(encode::deserialize(&message)?)
+}
+
+/// Writes `message` to `stream`, failing with [`Error::Timeout`] unless all its bytes are sent
+/// within `timeout`.
+///
+/// The write timeout of the stream is changed, and left set to the time which remained when the
+/// last bytes were sent.
+pub fn write_message(stream: &mut TcpStream, message: &RawNetworkMessage, timeout: Duration) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    let data = encode::serialize(message);
+    let mut written = 0;
+    while written < data.len() {
+        stream.set_write_timeout(Some(remaining(deadline, timeout)?))?;
+        match stream.write(&data[written..]) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+            Ok(n) => written += n,
+            Err(e) => check_timeout(e, timeout)?,
+        }
+    }
+    Ok(())
+}
+
+fn read_exact_by(stream: &mut TcpStream, buf: &mut [u8], deadline: Instant, timeout: Duration) -> Result<(), Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        stream.set_read_timeout(Some(remaining(deadline, timeout)?))?;
+        match stream.read(&mut buf[read..]) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => read += n,
+            Err(e) => check_timeout(e, timeout)?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the time left before `deadline`, a zero duration being an invalid socket timeout.
+fn remaining(deadline: Instant, timeout: Duration) -> Result<Duration, Error> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(Error::Timeout(timeout));
+    }
+    Ok(deadline - now)
+}
+
+/// Maps socket timeouts to [`Error::Timeout`], ignores interruptions and returns other errors.
+fn check_timeout(err: io::Error, timeout: Duration) -> Result<(), Error> {
+    match err.kind() {
+        io::ErrorKind::Interrupted => Ok(()),
+        // Unix platforms report an expired socket timeout as `WouldBlock`, Windows as `TimedOut`
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Err(Error::Timeout(timeout)),
+        _ => Err(err.into()),
+    }
+}
+
+/// Detects a peer making no progress, e.g. not delivering a requested block, for longer than a
+/// timeout.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StallDetector {
+    timeout: Duration,
+    last_progress: Instant,
+}
+
+impl StallDetector {
+    /// Creates a detector, counting the time without progress from now.
+    pub fn new(timeout: Duration) -> StallDetector {
+        StallDetector { timeout, last_progress: Instant::now() }
+    }
+
+    /// Records that progress was made, restarting the time without progress.
+    pub fn progress(&mut self) {
+        self.last_progress = Instant::now();
+    }
+
+    /// Returns the time since the last progress.
+    pub fn elapsed(&self) -> Duration {
+        self.last_progress.elapsed()
+    }
+
+    /// Returns the timeout of the detector.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Fails with [`Error::Stalled`] if no progress was made within the timeout.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.elapsed() > self.timeout {
+            return Err(Error::Stalled(self.timeout));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    #[cfg(not(target_arch = "wasm32"))]
+    use std::net::TcpListener;
+    use network::message::NetworkMessage;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ping(nonce: u64) -> RawNetworkMessage {
+        RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(nonce) }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_write_message() {
+        let (mut client, mut server) = connect();
+        write_message(&mut client, &ping(1), Duration::from_secs(10)).unwrap();
+        write_message(&mut client, &ping(2), Duration::from_secs(10)).unwrap();
+        assert_eq!(read_message(&mut server, Duration::from_secs(10)).unwrap(), ping(1));
+        assert_eq!(read_message(&mut server, Duration::from_secs(10)).unwrap(), ping(2));
+
+        // the peer closing the connection isn't a timeout
+        drop(client);
+        match read_message(&mut server, Duration::from_secs(10)) {
+            Err(Error::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_timeout() {
+        let (mut client, mut server) = connect();
+        let timeout = Duration::from_millis(200);
+        match read_message(&mut server, timeout) {
+            Err(Error::Timeout(t)) => assert_eq!(t, timeout),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // bytes trickling in keep each socket read short, but the whole message is still late
+        let data = encode::serialize(&ping(3));
+        let writer = thread::spawn(move || {
+            for byte in data {
+                client.write_all(&[byte]).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+            client
+        });
+        match read_message(&mut server, timeout) {
+            Err(Error::Timeout(t)) => assert_eq!(t, timeout),
+            other => panic!("unexpected result {:?}", other),
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn stall_detector() {
+        let mut detector = StallDetector::new(Duration::from_millis(50));
+        assert!(detector.check().is_ok());
+        thread::sleep(Duration::from_millis(100));
+        match detector.check() {
+            Err(Error::Stalled(t)) => assert_eq!(t, detector.timeout()),
+            other => panic!("unexpected result {:?}", other),
+        }
+        detector.progress();
+        assert!(detector.check().is_ok());
+    }
+}
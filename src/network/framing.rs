@@ -0,0 +1,131 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Message framing.
+//!
+//! This module splits received bytes into network messages without a
+//! blocking reader, for transports which hand over data in chunks, such as
+//! WebSockets in browsers, where sockets and threads aren't available.
+//!
+//! Received chunks are pushed into a [`MessageDecoder`], which yields each
+//! message once all its bytes arrived, whether a chunk holds part of a
+//! message or several messages. Sent messages are framed by
+//! [`serialize`](::consensus::encode::serialize)-ing them.
+//!
+
+use prelude::*;
+
+use consensus::encode::{self, MAX_VEC_SIZE};
+use network::message::RawNetworkMessage;
+use util::endian;
+
+/// Size of a message header: the network magic, the command, the payload length and the
+/// payload checksum.
+pub const HEADER_SIZE: usize = 24;
+
+/// Decodes network messages from chunks of received bytes.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MessageDecoder {
+    buffer: Vec<u8>,
+}
+
+impl MessageDecoder {
+    /// Creates a decoder with no buffered bytes.
+    pub fn new() -> MessageDecoder {
+        MessageDecoder::default()
+    }
+
+    /// Appends received bytes to the buffer.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the number of buffered bytes which are not part of a decoded message yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the next message if all its bytes are buffered, `None` if more bytes are needed.
+    ///
+    /// A message failing to decode is dropped from the buffer, so the following messages can
+    /// still be decoded. A header announcing a payload too large to be buffered is an error
+    /// the decoder doesn't recover from, as the stream is likely corrupted.
+    pub fn next_message(&mut self) -> Result<Option<RawNetworkMessage>, encode::Error> {
+        if self.buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let payload_len = endian::slice_to_u32_le(&self.buffer[16..20]) as usize;
+        if payload_len > MAX_VEC_SIZE {
+            return Err(encode::Error::OversizedVectorAllocation { requested: payload_len, max: MAX_VEC_SIZE });
+        }
+        let message_len = HEADER_SIZE + payload_len;
+        if self.buffer.len() < message_len {
+            return Ok(None);
+        }
+        let message = encode::deserialize(&self.buffer[..message_len]);
+        self.buffer.drain(..message_len);
+        message.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::encode::serialize;
+    use network::message::NetworkMessage;
+
+    fn ping(nonce: u64) -> Vec<u8> {
+        serialize(&RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(nonce) })
+    }
+
+    #[test]
+    fn message_decoder() {
+        let mut decoder = MessageDecoder::new();
+        assert_eq!(decoder.next_message().unwrap(), None);
+
+        // one message in two chunks
+        let message = ping(1);
+        decoder.push(&message[..10]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+        decoder.push(&message[10..]);
+        assert_eq!(decoder.next_message().unwrap().unwrap().payload, NetworkMessage::Ping(1));
+        assert_eq!(decoder.buffered(), 0);
+
+        // two messages and a part of a third in one chunk
+        let mut chunk = ping(2);
+        chunk.extend(ping(3));
+        chunk.extend(&ping(4)[..HEADER_SIZE]);
+        decoder.push(&chunk);
+        assert_eq!(decoder.next_message().unwrap().unwrap().payload, NetworkMessage::Ping(2));
+        assert_eq!(decoder.next_message().unwrap().unwrap().payload, NetworkMessage::Ping(3));
+        assert_eq!(decoder.next_message().unwrap(), None);
+        assert_eq!(decoder.buffered(), HEADER_SIZE);
+        decoder.push(&ping(4)[HEADER_SIZE..]);
+        assert_eq!(decoder.next_message().unwrap().unwrap().payload, NetworkMessage::Ping(4));
+
+        // a corrupted message is skipped
+        let mut corrupted = ping(5);
+        corrupted[HEADER_SIZE] ^= 1;
+        decoder.push(&corrupted);
+        decoder.push(&ping(6));
+        assert!(decoder.next_message().is_err());
+        assert_eq!(decoder.next_message().unwrap().unwrap().payload, NetworkMessage::Ping(6));
+
+        // an oversized payload is rejected before it is buffered
+        let mut oversized = ping(7);
+        oversized[16..20].copy_from_slice(&endian::u32_to_array_le(MAX_VEC_SIZE as u32 + 1));
+        decoder.push(&oversized[..HEADER_SIZE]);
+        assert!(decoder.next_message().is_err());
+    }
+}
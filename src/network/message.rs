@@ -29,9 +29,12 @@ use network::address::{Address, AddrV2Message};
 use network::{message_network, message_bloom};
 use network::message_blockdata;
 use network::message_filter;
+use network::message_utxo;
+use network::message_compact_blocks;
 use consensus::encode::{CheckedData, Decodable, Encodable, VarInt, MAX_VEC_SIZE};
 use consensus::{encode, serialize};
 use util::merkleblock::MerkleBlock;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// The maximum number of [super::message_blockdata::Inventory] items in an `inv` message.
 ///
@@ -73,6 +76,19 @@ impl AsRef<str> for CommandString {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for CommandString {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(0..=12)?;
+        let mut command = String::with_capacity(len);
+        for _ in 0..len {
+            command.push(*u.choose(b"abcdefghijklmnopqrstuvwxyz0123456789")? as char);
+        }
+        Ok(CommandString(command.into()))
+    }
+}
+
 impl Encodable for CommandString {
     #[inline]
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
@@ -126,6 +142,9 @@ pub struct RawNetworkMessage {
 
 /// A Network message payload. Proper documentation is available on at
 /// [Bitcoin Wiki: Protocol Specification](https://en.bitcoin.it/wiki/Protocol_specification)
+///
+/// Variants are added as the protocol evolves, so matches need a wildcard arm; the accessors
+/// such as [`NetworkMessage::as_tx`] cover the common cases without one.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum NetworkMessage {
     /// `version`
@@ -183,8 +202,20 @@ pub enum NetworkMessage {
     GetCFCheckpt(message_filter::GetCFCheckpt),
     /// BIP157 cfcheckpt
     CFCheckpt(message_filter::CFCheckpt),
+    /// BIP64 `getutxos`
+    GetUtxos(message_utxo::GetUtxos),
+    /// BIP64 `utxos`
+    Utxos(message_utxo::Utxos),
+    /// BIP152 `sendcmpct`
+    SendCmpct(message_compact_blocks::SendCmpct),
+    /// BIP152 `cmpctblock`
+    CmpctBlock(message_compact_blocks::HeaderAndShortIds),
+    /// BIP152 `getblocktxn`
+    GetBlockTxn(message_compact_blocks::BlockTransactionsRequest),
+    /// BIP152 `blocktxn`
+    BlockTxn(message_compact_blocks::BlockTransactions),
     /// `alert`
-    Alert(Vec<u8>),
+    Alert(message_network::Alert),
     /// `reject`
     Reject(message_network::Reject),
     /// `feefilter`
@@ -202,9 +233,18 @@ pub enum NetworkMessage {
         command: CommandString,
         /// The payload of this message.
         payload: Vec<u8>,
-    }
+    },
+
+    /// Reserved for future use, `#[non_exhaustive]` is not available with current MSRV
+    #[doc(hidden)]
+    __Reserved(Reserved),
 }
 
+/// Uninhabited, so that [`NetworkMessage::__Reserved`] can't be constructed.
+#[doc(hidden)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reserved {}
+
 impl NetworkMessage {
     /// Return the message command as a static string reference.
     ///
@@ -239,6 +279,12 @@ impl NetworkMessage {
             NetworkMessage::CFHeaders(_) => "cfheaders",
             NetworkMessage::GetCFCheckpt(_) => "getcfcheckpt",
             NetworkMessage::CFCheckpt(_) => "cfcheckpt",
+            NetworkMessage::GetUtxos(_) => "getutxos",
+            NetworkMessage::Utxos(_) => "utxos",
+            NetworkMessage::SendCmpct(_) => "sendcmpct",
+            NetworkMessage::CmpctBlock(_) => "cmpctblock",
+            NetworkMessage::GetBlockTxn(_) => "getblocktxn",
+            NetworkMessage::BlockTxn(_) => "blocktxn",
             NetworkMessage::Alert(_)    => "alert",
             NetworkMessage::Reject(_)    => "reject",
             NetworkMessage::FeeFilter(_) => "feefilter",
@@ -246,6 +292,7 @@ impl NetworkMessage {
             NetworkMessage::AddrV2(_) => "addrv2",
             NetworkMessage::SendAddrV2 => "sendaddrv2",
             NetworkMessage::Unknown { .. } => "unknown",
+            NetworkMessage::__Reserved(never) => match never {},
         }
     }
 
@@ -256,6 +303,136 @@ impl NetworkMessage {
             _ => CommandString::try_from(self.cmd()).expect("cmd returns valid commands")
         }
     }
+
+    /// Returns the announced inventory if this is an `inv` message.
+    pub fn as_inv(&self) -> Option<&[message_blockdata::Inventory]> {
+        match *self {
+            NetworkMessage::Inv(ref inv) => Some(inv),
+            _ => None,
+        }
+    }
+
+    /// Returns the transaction if this is a `tx` message.
+    pub fn as_tx(&self) -> Option<&transaction::Transaction> {
+        match *self {
+            NetworkMessage::Tx(ref tx) => Some(tx),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this message manages the connection rather than relaying data: the
+    /// handshake, keep-alives, rejections and the announcement of relay preferences, including
+    /// BIP 37 filters.
+    pub fn is_control_message(&self) -> bool {
+        match *self {
+            NetworkMessage::Version(_)
+            | NetworkMessage::Verack
+            | NetworkMessage::Ping(_)
+            | NetworkMessage::Pong(_)
+            | NetworkMessage::Reject(_)
+            | NetworkMessage::SendHeaders
+            | NetworkMessage::FeeFilter(_)
+            | NetworkMessage::WtxidRelay
+            | NetworkMessage::SendAddrV2
+            | NetworkMessage::SendCmpct(_)
+            | NetworkMessage::FilterLoad(_)
+            | NetworkMessage::FilterAdd(_)
+            | NetworkMessage::FilterClear => true,
+            _ => false,
+        }
+    }
+}
+
+/// The serde representation of a [`NetworkMessage`]: its command and hex-encoded payload.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerdeNetworkMessage {
+    command: String,
+    #[serde(with = "::serde_utils::hex_bytes")]
+    payload: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl ::serde::Serialize for NetworkMessage {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let payload = match *self {
+            NetworkMessage::Unknown { ref payload, .. } => payload.clone(),
+            _ => self.payload_bytes(),
+        };
+        SerdeNetworkMessage { command: self.command().to_string(), payload }.serialize(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> ::serde::Deserialize<'de> for NetworkMessage {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let message = SerdeNetworkMessage::deserialize(d)?;
+        let command = CommandString::try_from(message.command).map_err(D::Error::custom)?;
+        match RawNetworkMessage::decode_payload(&command, &message.payload).map_err(D::Error::custom)? {
+            Some(payload) => Ok(payload),
+            None => Ok(NetworkMessage::Unknown { command, payload: message.payload }),
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for NetworkMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=38u8)? {
+            0 => NetworkMessage::Version(u.arbitrary()?),
+            1 => NetworkMessage::Verack,
+            2 => NetworkMessage::Addr(u.arbitrary()?),
+            3 => NetworkMessage::Inv(u.arbitrary()?),
+            4 => NetworkMessage::GetData(u.arbitrary()?),
+            5 => NetworkMessage::NotFound(u.arbitrary()?),
+            6 => NetworkMessage::GetBlocks(u.arbitrary()?),
+            7 => NetworkMessage::GetHeaders(u.arbitrary()?),
+            8 => NetworkMessage::MemPool,
+            9 => NetworkMessage::Tx(u.arbitrary()?),
+            10 => NetworkMessage::Block(u.arbitrary()?),
+            11 => NetworkMessage::Headers(u.arbitrary()?),
+            12 => NetworkMessage::SendHeaders,
+            13 => NetworkMessage::GetAddr,
+            14 => NetworkMessage::Ping(u.arbitrary()?),
+            15 => NetworkMessage::Pong(u.arbitrary()?),
+            16 => NetworkMessage::MerkleBlock(u.arbitrary()?),
+            17 => NetworkMessage::FilterLoad(u.arbitrary()?),
+            18 => NetworkMessage::FilterAdd(u.arbitrary()?),
+            19 => NetworkMessage::FilterClear,
+            20 => NetworkMessage::GetCFilters(u.arbitrary()?),
+            21 => NetworkMessage::CFilter(u.arbitrary()?),
+            22 => NetworkMessage::GetCFHeaders(u.arbitrary()?),
+            23 => NetworkMessage::CFHeaders(u.arbitrary()?),
+            24 => NetworkMessage::GetCFCheckpt(u.arbitrary()?),
+            25 => NetworkMessage::CFCheckpt(u.arbitrary()?),
+            26 => NetworkMessage::Alert(u.arbitrary()?),
+            27 => NetworkMessage::Reject(u.arbitrary()?),
+            28 => NetworkMessage::FeeFilter(u.arbitrary()?),
+            29 => NetworkMessage::WtxidRelay,
+            30 => NetworkMessage::AddrV2(u.arbitrary()?),
+            31 => NetworkMessage::SendAddrV2,
+            32 => NetworkMessage::GetUtxos(u.arbitrary()?),
+            33 => NetworkMessage::Utxos(u.arbitrary()?),
+            34 => NetworkMessage::SendCmpct(u.arbitrary()?),
+            35 => NetworkMessage::CmpctBlock(u.arbitrary()?),
+            36 => NetworkMessage::GetBlockTxn(u.arbitrary()?),
+            37 => NetworkMessage::BlockTxn(u.arbitrary()?),
+            _ => {
+                // Known commands are lowercase, so an uppercase one is never taken for them.
+                let len = u.int_in_range(1..=12)?;
+                let mut command = String::with_capacity(len);
+                for _ in 0..len {
+                    command.push(*u.choose(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789")? as char);
+                }
+                NetworkMessage::Unknown { command: CommandString(command.into()), payload: u.arbitrary()? }
+            }
+        })
+    }
 }
 
 impl RawNetworkMessage {
@@ -274,27 +451,72 @@ impl RawNetworkMessage {
     }
 }
 
-struct HeaderSerializationWrapper<'a>(&'a Vec<block::BlockHeader>);
+arbitrary_struct_impl!(RawNetworkMessage, magic, payload);
+
+struct HeaderSerializationWrapper<'a>(&'a [block::BlockHeader]);
 
 impl<'a> Encodable for HeaderSerializationWrapper<'a> {
     #[inline]
-    fn consensus_encode<S: io::Write>(&self, mut s: S) -> Result<usize, io::Error> {
-        let mut len = 0;
-        len += VarInt(self.0.len() as u64).consensus_encode(&mut s)?;
-        for header in self.0.iter() {
-            len += header.consensus_encode(&mut s)?;
-            len += 0u8.consensus_encode(&mut s)?;
-        }
-        Ok(len)
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        // headers messages have an empty transaction count after each header
+        encode::consensus_encode_iter(self.0.len(), self.0.iter().map(|header| (header, 0u8)), w)
+    }
+
+    fn consensus_size(&self) -> usize {
+        VarInt(self.0.len() as u64).len() + self.0.iter().map(|header| header.consensus_size() + 1).sum::<usize>()
     }
 }
 
-impl Encodable for RawNetworkMessage {
-    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
-        let mut len = 0;
-        len += self.magic.consensus_encode(&mut s)?;
-        len += self.command().consensus_encode(&mut s)?;
-        len += CheckedData(match self.payload {
+impl NetworkMessage {
+    /// Returns the size of the encoded payload, without encoding it.
+    pub(crate) fn payload_size(&self) -> usize {
+        match *self {
+            NetworkMessage::Version(ref dat) => dat.consensus_size(),
+            NetworkMessage::Addr(ref dat)    => dat.consensus_size(),
+            NetworkMessage::Inv(ref dat)     => dat.consensus_size(),
+            NetworkMessage::GetData(ref dat) => dat.consensus_size(),
+            NetworkMessage::NotFound(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetBlocks(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetHeaders(ref dat) => dat.consensus_size(),
+            NetworkMessage::Tx(ref dat)      => dat.consensus_size(),
+            NetworkMessage::Block(ref dat)   => dat.consensus_size(),
+            NetworkMessage::Headers(ref dat) => HeaderSerializationWrapper(dat).consensus_size(),
+            NetworkMessage::Ping(ref dat)    => dat.consensus_size(),
+            NetworkMessage::Pong(ref dat)    => dat.consensus_size(),
+            NetworkMessage::MerkleBlock(ref dat)    => dat.consensus_size(),
+            NetworkMessage::FilterLoad(ref dat)    => dat.consensus_size(),
+            NetworkMessage::FilterAdd(ref dat)    => dat.consensus_size(),
+            NetworkMessage::GetCFilters(ref dat) => dat.consensus_size(),
+            NetworkMessage::CFilter(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetCFHeaders(ref dat) => dat.consensus_size(),
+            NetworkMessage::CFHeaders(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetCFCheckpt(ref dat) => dat.consensus_size(),
+            NetworkMessage::CFCheckpt(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetUtxos(ref dat) => dat.consensus_size(),
+            NetworkMessage::Utxos(ref dat) => dat.consensus_size(),
+            NetworkMessage::SendCmpct(ref dat) => dat.consensus_size(),
+            NetworkMessage::CmpctBlock(ref dat) => dat.consensus_size(),
+            NetworkMessage::GetBlockTxn(ref dat) => dat.consensus_size(),
+            NetworkMessage::BlockTxn(ref dat) => dat.consensus_size(),
+            NetworkMessage::Alert(ref dat)    => dat.consensus_size(),
+            NetworkMessage::Reject(ref dat) => dat.consensus_size(),
+            NetworkMessage::FeeFilter(ref data) => data.consensus_size(),
+            NetworkMessage::AddrV2(ref dat) => dat.consensus_size(),
+            NetworkMessage::Verack
+            | NetworkMessage::SendHeaders
+            | NetworkMessage::MemPool
+            | NetworkMessage::GetAddr
+            | NetworkMessage::WtxidRelay
+            | NetworkMessage::FilterClear
+            | NetworkMessage::SendAddrV2 => 0,
+            NetworkMessage::Unknown { payload: ref data, .. } => data.consensus_size(),
+            NetworkMessage::__Reserved(never) => match never {},
+        }
+    }
+
+    /// Returns the encoded payload.
+    fn payload_bytes(&self) -> Vec<u8> {
+        match *self {
             NetworkMessage::Version(ref dat) => serialize(dat),
             NetworkMessage::Addr(ref dat)    => serialize(dat),
             NetworkMessage::Inv(ref dat)     => serialize(dat),
@@ -316,6 +538,12 @@ impl Encodable for RawNetworkMessage {
             NetworkMessage::CFHeaders(ref dat) => serialize(dat),
             NetworkMessage::GetCFCheckpt(ref dat) => serialize(dat),
             NetworkMessage::CFCheckpt(ref dat) => serialize(dat),
+            NetworkMessage::GetUtxos(ref dat) => serialize(dat),
+            NetworkMessage::Utxos(ref dat) => serialize(dat),
+            NetworkMessage::SendCmpct(ref dat) => serialize(dat),
+            NetworkMessage::CmpctBlock(ref dat) => serialize(dat),
+            NetworkMessage::GetBlockTxn(ref dat) => serialize(dat),
+            NetworkMessage::BlockTxn(ref dat) => serialize(dat),
             NetworkMessage::Alert(ref dat)    => serialize(dat),
             NetworkMessage::Reject(ref dat) => serialize(dat),
             NetworkMessage::FeeFilter(ref data) => serialize(data),
@@ -328,9 +556,24 @@ impl Encodable for RawNetworkMessage {
             | NetworkMessage::FilterClear
             | NetworkMessage::SendAddrV2 => vec![],
             NetworkMessage::Unknown { payload: ref data, .. } => serialize(data),
-        }).consensus_encode(&mut s)?;
+            NetworkMessage::__Reserved(never) => match never {},
+        }
+    }
+}
+
+impl Encodable for RawNetworkMessage {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.magic.consensus_encode(&mut s)?;
+        len += self.command().consensus_encode(&mut s)?;
+        len += CheckedData(self.payload.payload_bytes()).consensus_encode(&mut s)?;
         Ok(len)
     }
+
+    fn consensus_size(&self) -> usize {
+        // magic, command, payload length and checksum
+        4 + 12 + 4 + 4 + self.payload.payload_size()
+    }
 }
 
 struct HeaderDeserializationWrapper(Vec<block::BlockHeader>);
@@ -356,13 +599,28 @@ impl Decodable for HeaderDeserializationWrapper {
     }
 }
 
-impl Decodable for RawNetworkMessage {
-    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
-        let magic = Decodable::consensus_decode(&mut d)?;
-        let cmd = CommandString::consensus_decode(&mut d)?;
-        let raw_payload = CheckedData::consensus_decode(&mut d)?.0;
+impl RawNetworkMessage {
+    /// Decodes a message like [`Decodable::consensus_decode`], but keeps the payload of a known
+    /// command which fails to decode as [`NetworkMessage::Unknown`] instead of erroring.
+    ///
+    /// This lets crawlers log peers sending malformed messages and go on reading the stream.
+    /// Errors in the header or the checksum, which mean the stream itself is corrupted, are still
+    /// returned. Use [`NetworkMessage::command`] to get the command of a kept payload.
+    pub fn consensus_decode_lenient<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let magic = Decodable::consensus_decode(r)?;
+        let cmd = CommandString::consensus_decode(r)?;
+        let raw_payload = CheckedData::consensus_decode(r)?.0;
+
+        let payload = match RawNetworkMessage::decode_payload(&cmd, &raw_payload) {
+            Ok(Some(payload)) => payload,
+            Ok(None) | Err(_) => NetworkMessage::Unknown { command: cmd, payload: raw_payload },
+        };
+        Ok(RawNetworkMessage { magic, payload })
+    }
 
-        let mut mem_d = io::Cursor::new(raw_payload);
+    /// Decodes the payload of a message, `None` if the command is unknown.
+    fn decode_payload(cmd: &CommandString, raw_payload: &[u8]) -> Result<Option<NetworkMessage>, encode::Error> {
+        let mut mem_d = raw_payload;
         let payload = match &cmd.0[..] {
             "version" => NetworkMessage::Version(Decodable::consensus_decode(&mut mem_d)?),
             "verack"  => NetworkMessage::Verack,
@@ -392,16 +650,33 @@ impl Decodable for RawNetworkMessage {
             "cfheaders" => NetworkMessage::CFHeaders(Decodable::consensus_decode(&mut mem_d)?),
             "getcfcheckpt" => NetworkMessage::GetCFCheckpt(Decodable::consensus_decode(&mut mem_d)?),
             "cfcheckpt" => NetworkMessage::CFCheckpt(Decodable::consensus_decode(&mut mem_d)?),
+            "getutxos" => NetworkMessage::GetUtxos(Decodable::consensus_decode(&mut mem_d)?),
+            "utxos" => NetworkMessage::Utxos(Decodable::consensus_decode(&mut mem_d)?),
+            "sendcmpct" => NetworkMessage::SendCmpct(Decodable::consensus_decode(&mut mem_d)?),
+            "cmpctblock" => NetworkMessage::CmpctBlock(Decodable::consensus_decode(&mut mem_d)?),
+            "getblocktxn" => NetworkMessage::GetBlockTxn(Decodable::consensus_decode(&mut mem_d)?),
+            "blocktxn" => NetworkMessage::BlockTxn(Decodable::consensus_decode(&mut mem_d)?),
             "reject" => NetworkMessage::Reject(Decodable::consensus_decode(&mut mem_d)?),
             "alert"   => NetworkMessage::Alert(Decodable::consensus_decode(&mut mem_d)?),
             "feefilter" => NetworkMessage::FeeFilter(Decodable::consensus_decode(&mut mem_d)?),
             "wtxidrelay" => NetworkMessage::WtxidRelay,
             "addrv2" => NetworkMessage::AddrV2(Decodable::consensus_decode(&mut mem_d)?),
             "sendaddrv2" => NetworkMessage::SendAddrV2,
-            _ => NetworkMessage::Unknown {
-                command: cmd,
-                payload: mem_d.into_inner(),
-            }
+            _ => return Ok(None),
+        };
+        Ok(Some(payload))
+    }
+}
+
+impl Decodable for RawNetworkMessage {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let magic = Decodable::consensus_decode(r)?;
+        let cmd = CommandString::consensus_decode(r)?;
+        let raw_payload = CheckedData::consensus_decode(r)?.0;
+
+        let payload = match RawNetworkMessage::decode_payload(&cmd, &raw_payload)? {
+            Some(payload) => payload,
+            None => NetworkMessage::Unknown { command: cmd, payload: raw_payload },
         };
         Ok(RawNetworkMessage {
             magic,
@@ -415,18 +690,20 @@ mod test {
     use std::net::Ipv4Addr;
     use super::{RawNetworkMessage, NetworkMessage, CommandString};
     use network::constants::ServiceFlags;
-    use consensus::encode::{deserialize, deserialize_partial, serialize};
+    use consensus::encode::{deserialize, deserialize_partial, serialize, CheckedData, Encodable};
     use hashes::hex::FromHex;
     use hashes::sha256d::Hash;
     use hashes::Hash as HashTrait;
     use network::address::{Address, AddrV2, AddrV2Message};
-    use super::message_network::{Reject, RejectReason, VersionMessage};
+    use super::message_network::{Alert, Reject, RejectReason, VersionMessage};
     use network::message_blockdata::{Inventory, GetBlocksMessage, GetHeadersMessage};
     use blockdata::block::{Block, BlockHeader};
-    use network::message_filter::{GetCFilters, CFilter, GetCFHeaders, CFHeaders, GetCFCheckpt, CFCheckpt};
-    use blockdata::transaction::Transaction;
+    use network::message_filter::{GetCFilters, CFilter, GetCFHeaders, CFHeaders, GetCFCheckpt, CFCheckpt, FilterType};
+    use blockdata::transaction::{OutPoint, Transaction, TxOut};
     use blockdata::script::Script;
     use network::message_bloom::{FilterAdd, FilterLoad, BloomFlags};
+    use network::message_utxo::{GetUtxos, Utxo, Utxos};
+    use network::message_compact_blocks::{BlockTransactions, BlockTransactionsRequest, HeaderAndShortIds, SendCmpct};
     use MerkleBlock;
 
     fn hash(slice: [u8;32]) -> Hash {
@@ -453,8 +730,8 @@ mod test {
             NetworkMessage::GetBlocks(GetBlocksMessage::new(vec![hash([1u8; 32]).into(), hash([4u8; 32]).into()], hash([5u8; 32]).into())),
             NetworkMessage::GetHeaders(GetHeadersMessage::new(vec![hash([10u8; 32]).into(), hash([40u8; 32]).into()], hash([50u8; 32]).into())),
             NetworkMessage::MemPool,
-            NetworkMessage::Tx(tx),
-            NetworkMessage::Block(block),
+            NetworkMessage::Tx(tx.clone()),
+            NetworkMessage::Block(block.clone()),
             NetworkMessage::Headers(vec![header]),
             NetworkMessage::SendHeaders,
             NetworkMessage::GetAddr,
@@ -465,13 +742,19 @@ mod test {
             NetworkMessage::FilterAdd(FilterAdd {data: script.as_bytes().to_vec()}),
             NetworkMessage::FilterAdd(FilterAdd {data: hash([29u8; 32]).to_vec()}),
             NetworkMessage::FilterClear,
-            NetworkMessage::GetCFilters(GetCFilters{filter_type: 2, start_height: 52, stop_hash: hash([42u8; 32]).into()}),
-            NetworkMessage::CFilter(CFilter{filter_type: 7, block_hash: hash([25u8; 32]).into(), filter: vec![1,2,3]}),
-            NetworkMessage::GetCFHeaders(GetCFHeaders{filter_type: 4, start_height: 102, stop_hash: hash([47u8; 32]).into()}),
-            NetworkMessage::CFHeaders(CFHeaders{filter_type: 13, stop_hash: hash([53u8; 32]).into(), previous_filter_header: hash([12u8; 32]).into(), filter_hashes: vec![hash([4u8; 32]).into(), hash([12u8; 32]).into()]}),
-            NetworkMessage::GetCFCheckpt(GetCFCheckpt{filter_type: 17, stop_hash: hash([25u8; 32]).into()}),
-            NetworkMessage::CFCheckpt(CFCheckpt{filter_type: 27, stop_hash: hash([77u8; 32]).into(), filter_headers: vec![hash([3u8; 32]).into(), hash([99u8; 32]).into()]}),
-            NetworkMessage::Alert(vec![45,66,3,2,6,8,9,12,3,130]),
+            NetworkMessage::GetCFilters(GetCFilters{filter_type: FilterType::Basic, start_height: 52, stop_hash: hash([42u8; 32]).into()}),
+            NetworkMessage::CFilter(CFilter{filter_type: FilterType::Unknown(7), block_hash: hash([25u8; 32]).into(), filter: vec![1,2,3]}),
+            NetworkMessage::GetCFHeaders(GetCFHeaders{filter_type: FilterType::Unknown(4), start_height: 102, stop_hash: hash([47u8; 32]).into()}),
+            NetworkMessage::CFHeaders(CFHeaders{filter_type: FilterType::Unknown(13), stop_hash: hash([53u8; 32]).into(), previous_filter_header: hash([12u8; 32]).into(), filter_hashes: vec![hash([4u8; 32]).into(), hash([12u8; 32]).into()]}),
+            NetworkMessage::GetCFCheckpt(GetCFCheckpt{filter_type: FilterType::Unknown(17), stop_hash: hash([25u8; 32]).into()}),
+            NetworkMessage::CFCheckpt(CFCheckpt{filter_type: FilterType::Unknown(27), stop_hash: hash([77u8; 32]).into(), filter_headers: vec![hash([3u8; 32]).into(), hash([99u8; 32]).into()]}),
+            NetworkMessage::GetUtxos(GetUtxos{check_mempool: true, outpoints: vec![OutPoint::new(hash([6u8; 32]).into(), 2)]}),
+            NetworkMessage::SendCmpct(SendCmpct{send_compact: true, version: 2}),
+            NetworkMessage::CmpctBlock(HeaderAndShortIds::from_block(&block, 5, 2, &[1]).unwrap()),
+            NetworkMessage::GetBlockTxn(BlockTransactionsRequest{block_hash: hash([11u8; 32]).into(), indexes: vec![2, 3, 10]}),
+            NetworkMessage::BlockTxn(BlockTransactions{block_hash: hash([11u8; 32]).into(), transactions: vec![tx.clone()]}),
+            NetworkMessage::Utxos(Utxos::new(84, hash([14u8; 32]).into(), vec![None, Some(Utxo{tx_version: 0, height: 83, output: TxOut{value: 5000, script_pubkey: script.clone()}})])),
+            NetworkMessage::Alert(Alert::new(vec![45,66,3,2,6,8,9,12,3,130], vec![1,2,3])),
             NetworkMessage::Reject(Reject{message: CommandString::try_from("Test reject").unwrap(), ccode: RejectReason::Duplicate, reason: "Cause".into(), hash: hash([255u8; 32])}),
             NetworkMessage::FeeFilter(1000),
             NetworkMessage::WtxidRelay,
@@ -482,10 +765,69 @@ mod test {
         for msg in msgs {
             let raw_msg = RawNetworkMessage {magic: 57, payload: msg};
             assert_eq!(deserialize::<RawNetworkMessage>(&serialize(&raw_msg)).unwrap(), raw_msg);
+            assert_eq!(raw_msg.consensus_size(), serialize(&raw_msg).len());
         }
 
     }
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn network_message_serde() {
+        let ping = NetworkMessage::Ping(7);
+        let json = ::serde_json::to_string(&ping).unwrap();
+        assert_eq!(json, r#"{"command":"ping","payload":"0700000000000000"}"#);
+        assert_eq!(::serde_json::from_str::<NetworkMessage>(&json).unwrap(), ping);
+
+        let unknown = NetworkMessage::Unknown { command: CommandString::try_from("custom").unwrap(), payload: vec![0xde, 0xad] };
+        let json = ::serde_json::to_string(&unknown).unwrap();
+        assert_eq!(json, r#"{"command":"custom","payload":"dead"}"#);
+        assert_eq!(::serde_json::from_str::<NetworkMessage>(&json).unwrap(), unknown);
+
+        assert!(::serde_json::from_str::<NetworkMessage>(r#"{"command":"ping","payload":"07"}"#).is_err());
+        assert!(::serde_json::from_str::<NetworkMessage>(r#"{"command":"commandtoolong","payload":""}"#).is_err());
+    }
+
+    #[test]
+    fn accessors_test() {
+        let tx = Transaction { version: 2, lock_time: 0, input: vec![], output: vec![] };
+        let inv = vec![Inventory::Transaction(tx.txid())];
+
+        assert_eq!(NetworkMessage::Inv(inv.clone()).as_inv(), Some(&inv[..]));
+        assert_eq!(NetworkMessage::GetData(inv).as_inv(), None);
+        assert_eq!(NetworkMessage::Tx(tx.clone()).as_tx(), Some(&tx));
+        assert_eq!(NetworkMessage::Verack.as_tx(), None);
+
+        assert!(NetworkMessage::Verack.is_control_message());
+        assert!(NetworkMessage::Ping(1).is_control_message());
+        assert!(NetworkMessage::FilterClear.is_control_message());
+        assert!(!NetworkMessage::Tx(tx).is_control_message());
+        assert!(!NetworkMessage::GetAddr.is_control_message());
+        assert!(!NetworkMessage::Unknown { command: CommandString::try_from("custom").unwrap(), payload: vec![] }.is_control_message());
+    }
+
+    #[test]
+    fn lenient_decode_test() {
+        let mut raw = Vec::new();
+        0xd9b4bef9u32.consensus_encode(&mut raw).unwrap();
+        CommandString::try_from("tx").unwrap().consensus_encode(&mut raw).unwrap();
+        CheckedData(vec![1, 2, 3]).consensus_encode(&mut raw).unwrap();
+
+        assert!(deserialize::<RawNetworkMessage>(&raw).is_err());
+        let msg = RawNetworkMessage::consensus_decode_lenient(&mut &raw[..]).unwrap();
+        assert_eq!(msg.magic, 0xd9b4bef9);
+        assert_eq!(msg.payload, NetworkMessage::Unknown { command: CommandString::try_from("tx").unwrap(), payload: vec![1, 2, 3] });
+        assert_eq!(msg.payload.command().as_ref(), "tx");
+
+        // well-formed messages decode as usual
+        let ping = serialize(&RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(7) });
+        assert_eq!(RawNetworkMessage::consensus_decode_lenient(&mut &ping[..]).unwrap().payload, NetworkMessage::Ping(7));
+
+        // a corrupted stream is still an error
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        assert!(RawNetworkMessage::consensus_decode_lenient(&mut &raw[..]).is_err());
+    }
+
     #[test]
     fn commandstring_test() {
         // Test converting.
@@ -625,4 +967,34 @@ mod test {
             panic!("Wrong message type");
         }
     }
+
+    #[test]
+    #[cfg(feature = "arbitrary")]
+    fn arbitrary_roundtrip() {
+        use arbitrary::{Arbitrary, Unstructured};
+        use hashes::sha256;
+
+        let mut generated = 0;
+        for seed in 0..500u32 {
+            let data: Vec<u8> = (0..16u32)
+                .flat_map(|i| sha256::Hash::hash(&serialize(&(seed, i))).into_inner().to_vec())
+                .collect();
+            let msg = match RawNetworkMessage::arbitrary(&mut Unstructured::new(&data)) {
+                Ok(msg) => msg,
+                Err(_) => continue,
+            };
+            // These don't round trip through their encoding: transactions without inputs are
+            // decoded as segwit ones, error inventories don't decode their hash and unknown
+            // payloads are encoded with a length prefix which decoding keeps.
+            match msg.payload {
+                NetworkMessage::Tx(_) | NetworkMessage::Block(_) | NetworkMessage::Headers(_)
+                | NetworkMessage::MerkleBlock(_) | NetworkMessage::Inv(_) | NetworkMessage::GetData(_)
+                | NetworkMessage::NotFound(_) | NetworkMessage::Unknown { .. } => continue,
+                _ => {}
+            }
+            assert_eq!(deserialize::<RawNetworkMessage>(&serialize(&msg)).unwrap(), msg);
+            generated += 1;
+        }
+        assert!(generated > 200);
+    }
 }
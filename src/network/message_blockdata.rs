@@ -27,6 +27,7 @@ use hashes::sha256d;
 use network::constants;
 use consensus::encode::{self, Decodable, Encodable};
 use hash_types::{BlockHash, Txid, Wtxid};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// An inventory item.
 #[derive(PartialEq, Eq, Clone, Debug, Copy, Hash, PartialOrd, Ord)]
@@ -91,6 +92,23 @@ impl Decodable for Inventory {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Inventory {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=6u8)? {
+            0 => Inventory::Error,
+            1 => Inventory::Transaction(u.arbitrary()?),
+            2 => Inventory::Block(u.arbitrary()?),
+            3 => Inventory::WTx(u.arbitrary()?),
+            4 => Inventory::WitnessTransaction(u.arbitrary()?),
+            5 => Inventory::WitnessBlock(u.arbitrary()?),
+            // skip the types with a variant of their own
+            _ => Inventory::Unknown { inv_type: u.int_in_range(6..=0x3FFF_FFFF)?, hash: u.arbitrary()? },
+        })
+    }
+}
+
 // Some simple messages
 
 /// The `getblocks` message
@@ -131,6 +149,7 @@ impl GetBlocksMessage {
 }
 
 impl_consensus_encoding!(GetBlocksMessage, version, locator_hashes, stop_hash);
+arbitrary_struct_impl!(GetBlocksMessage, version, locator_hashes, stop_hash);
 
 impl GetHeadersMessage {
     /// Construct a new `getheaders` message
@@ -144,6 +163,7 @@ impl GetHeadersMessage {
 }
 
 impl_consensus_encoding!(GetHeadersMessage, version, locator_hashes, stop_hash);
+arbitrary_struct_impl!(GetHeadersMessage, version, locator_hashes, stop_hash);
 
 #[cfg(test)]
 mod tests {
@@ -3,14 +3,20 @@
 //! This module describes BIP37 Connection Bloom filtering network messages.
 //!
 
+use core::convert::TryFrom;
+use core::fmt;
+
 use consensus::encode;
 use consensus::{Decodable, Encodable, ReadExt};
 use std::io;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// `filterload` message sets the current bloom filter
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FilterLoad {
     /// The filter itself
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
     pub filter: Vec<u8>,
     /// The number of hash functions to use
     pub hash_funcs: u32,
@@ -21,9 +27,28 @@ pub struct FilterLoad {
 }
 
 impl_consensus_encoding!(FilterLoad, filter, hash_funcs, tweak, flags);
+arbitrary_struct_impl!(FilterLoad, filter, hash_funcs, tweak, flags);
+
+impl FilterLoad {
+    /// Decodes a `filterload` message accepting undefined flags, as Bitcoin Core does, and
+    /// returns it with the raw flags byte. The flags of the message are decoded with
+    /// [`BloomFlags::from_u8_lenient`].
+    pub fn consensus_decode_lenient<D: io::Read>(mut d: D) -> Result<(FilterLoad, u8), encode::Error> {
+        let filter = Decodable::consensus_decode(&mut d)?;
+        let hash_funcs = Decodable::consensus_decode(&mut d)?;
+        let tweak = Decodable::consensus_decode(&mut d)?;
+        let raw_flags = d.read_u8()?;
+        let filter_load = FilterLoad { filter, hash_funcs, tweak, flags: BloomFlags::from_u8_lenient(raw_flags) };
+        Ok((filter_load, raw_flags))
+    }
+}
 
 /// Bloom filter update flags
+///
+/// Decoding is strict: values other than 0, 1 and 2 are rejected. Peers accepting any value
+/// can use [`FilterLoad::consensus_decode_lenient`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BloomFlags {
     /// Never update the filter with outpoints.
     None,
@@ -33,33 +58,124 @@ pub enum BloomFlags {
     PubkeyOnly,
 }
 
-impl Encodable for BloomFlags {
-    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
-        e.write_all(&[match self {
+impl BloomFlags {
+    /// The bits of the flags byte Bitcoin Core looks at, the other bits are ignored
+    pub const UPDATE_MASK: u8 = 3;
+
+    /// Returns the flags with byte value `flags`, `None` if undefined
+    pub fn from_u8(flags: u8) -> Option<BloomFlags> {
+        match flags {
+            0 => Some(BloomFlags::None),
+            1 => Some(BloomFlags::All),
+            2 => Some(BloomFlags::PubkeyOnly),
+            _ => None,
+        }
+    }
+
+    /// Returns the flags with byte value `flags` the way Bitcoin Core interprets them: bits
+    /// outside [`BloomFlags::UPDATE_MASK`] are ignored and the undefined value 3 never updates
+    /// the filter
+    pub fn from_u8_lenient(flags: u8) -> BloomFlags {
+        BloomFlags::from_u8(flags & BloomFlags::UPDATE_MASK).unwrap_or(BloomFlags::None)
+    }
+
+    /// Returns the byte value of the flags
+    pub fn to_u8(self) -> u8 {
+        match self {
             BloomFlags::None => 0,
             BloomFlags::All => 1,
             BloomFlags::PubkeyOnly => 2,
-        }])?;
+        }
+    }
+}
+
+impl From<BloomFlags> for u8 {
+    fn from(flags: BloomFlags) -> u8 {
+        flags.to_u8()
+    }
+}
+
+impl TryFrom<u8> for BloomFlags {
+    type Error = UnknownBloomFlags;
+
+    fn try_from(flags: u8) -> Result<BloomFlags, UnknownBloomFlags> {
+        BloomFlags::from_u8(flags).ok_or(UnknownBloomFlags(flags))
+    }
+}
+
+/// Error converting an undefined byte value to [`BloomFlags`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnknownBloomFlags(pub u8);
+
+impl fmt::Display for UnknownBloomFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown bloom flags {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for UnknownBloomFlags {}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for BloomFlags {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[BloomFlags::None, BloomFlags::All, BloomFlags::PubkeyOnly])?)
+    }
+}
+
+impl Encodable for BloomFlags {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        s.write_all(&[self.to_u8()])?;
         Ok(1)
     }
 }
 
 impl Decodable for BloomFlags {
     fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
-        Ok(match d.read_u8()? {
-            0 => BloomFlags::None,
-            1 => BloomFlags::All,
-            2 => BloomFlags::PubkeyOnly,
-            _ => return Err(encode::Error::ParseFailed("unknown bloom flag")),
-        })
+        BloomFlags::from_u8(d.read_u8()?).ok_or(encode::Error::ParseFailed("unknown bloom flag"))
     }
 }
 
 /// `filteradd` message updates the current filter with new data
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FilterAdd {
     /// The data element to add to the current filter.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
     pub data: Vec<u8>,
 }
 
 impl_consensus_encoding!(FilterAdd, data);
+arbitrary_struct_impl!(FilterAdd, data);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::encode::{deserialize, serialize};
+
+    #[test]
+    fn bloom_flags() {
+        for flags in &[BloomFlags::None, BloomFlags::All, BloomFlags::PubkeyOnly] {
+            assert_eq!(BloomFlags::from_u8(u8::from(*flags)), Some(*flags));
+            assert_eq!(deserialize::<BloomFlags>(&serialize(flags)).unwrap(), *flags);
+        }
+        assert_eq!(BloomFlags::from_u8(3), None);
+        assert_eq!(BloomFlags::try_from(2), Ok(BloomFlags::PubkeyOnly));
+        assert_eq!(BloomFlags::try_from(3), Err(UnknownBloomFlags(3)));
+        assert_eq!(UnknownBloomFlags(3).to_string(), "unknown bloom flags 3");
+        assert!(deserialize::<BloomFlags>(&[3]).is_err());
+        assert_eq!(BloomFlags::from_u8_lenient(3), BloomFlags::None);
+        assert_eq!(BloomFlags::from_u8_lenient(0x81), BloomFlags::All);
+
+        let raw = [0x01, 0xff, 0x05, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x06];
+        assert!(deserialize::<FilterLoad>(&raw).is_err());
+        let (filter_load, flags) = FilterLoad::consensus_decode_lenient(&raw[..]).unwrap();
+        assert_eq!(flags, 6);
+        assert_eq!(filter_load.flags, BloomFlags::PubkeyOnly);
+        assert_eq!(filter_load.filter, vec![0xff]);
+        assert_eq!((filter_load.hash_funcs, filter_load.tweak), (5, 42));
+        assert!(FilterLoad::consensus_decode_lenient(&raw[..10]).is_err());
+    }
+}
@@ -0,0 +1,572 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP152 compact block network messages.
+//!
+//! A compact block relays a block as its header and a 6-byte short ID per
+//! transaction, which the receiver matches against the transactions in its
+//! mempool. Transactions it doesn't have are then fetched with a
+//! `getblocktxn` message and returned in a `blocktxn` message.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use io;
+use hashes::{sha256, Hash};
+use hash_types::BlockHash;
+use blockdata::block::{Block, BlockHeader};
+use blockdata::constants::{MAX_BLOCK_WEIGHT, WITNESS_SCALE_FACTOR};
+use blockdata::transaction::Transaction;
+use consensus::encode::{self, Decodable, Encodable, VarInt, MAX_VEC_SIZE};
+use util::endian;
+use util::siphash::SipHasher;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+
+/// The maximum number of transactions of a compact block: a block full of the smallest
+/// transactions which can be serialized.
+pub const MAX_TRANSACTIONS: usize = MAX_BLOCK_WEIGHT as usize / (WITNESS_SCALE_FACTOR * 10);
+
+/// A compact block error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The compact block version is neither 1 nor 2.
+    UnknownVersion(u64),
+    /// A transaction to prefill is not in the block, or is more than 65535 transactions after
+    /// the previous prefilled one.
+    IndexOutOfRange(usize),
+    /// The block has an extension payload, which compact blocks can't relay.
+    ExtensionPayload,
+    /// The compact block has neither short IDs nor prefilled transactions.
+    Empty,
+    /// The compact block has more than [`MAX_TRANSACTIONS`] transactions.
+    TooManyTransactions(usize),
+    /// A prefilled transaction is past the end of the block.
+    InvalidPrefilledIndex(usize),
+    /// Two transactions have the same short ID, so the full block must be requested.
+    ShortIdCollision(ShortId),
+    /// The `blocktxn` message is for another block.
+    BlockHashMismatch(BlockHash),
+    /// The `blocktxn` message doesn't have a transaction for each missing one.
+    WrongTransactionCount {
+        /// The number of missing transactions.
+        expected: usize,
+        /// The number of transactions of the message.
+        found: usize,
+    },
+    /// The block still misses transactions.
+    Incomplete(usize),
+    /// The transactions don't match the merkle root of the header, most likely because of a
+    /// short ID collision with a mempool transaction, so the full block must be requested.
+    MerkleRootMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownVersion(version) => write!(f, "unknown compact block version {}", version),
+            Error::IndexOutOfRange(index) => write!(f, "transaction {} to prefill is out of range", index),
+            Error::ExtensionPayload => f.write_str("compact blocks can't relay extension payloads"),
+            Error::Empty => f.write_str("compact block has no transactions"),
+            Error::TooManyTransactions(n) => write!(f, "compact block has {} transactions, more than the maximum of {}", n, MAX_TRANSACTIONS),
+            Error::InvalidPrefilledIndex(index) => write!(f, "prefilled transaction index {} is past the end of the block", index),
+            Error::ShortIdCollision(ref id) => write!(f, "compact block has colliding short ID {}", id),
+            Error::BlockHashMismatch(ref hash) => write!(f, "block transactions are for another block {}", hash),
+            Error::WrongTransactionCount { expected, found } => write!(f, "expected {} block transactions, found {}", expected, found),
+            Error::Incomplete(n) => write!(f, "block misses {} transactions", n),
+            Error::MerkleRootMismatch => f.write_str("reconstructed block doesn't match the merkle root"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {}
+
+/// `sendcmpct` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SendCmpct {
+    /// Whether new blocks are announced with `cmpctblock` messages rather than `inv` or
+    /// `headers` messages
+    pub send_compact: bool,
+    /// The compact block version, 1 for short IDs of txids, 2 for short IDs of wtxids
+    pub version: u64,
+}
+impl_consensus_encoding!(SendCmpct, send_compact, version);
+arbitrary_struct_impl!(SendCmpct, send_compact, version);
+
+/// The 6-byte short ID of a transaction in a compact block.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct ShortId(pub [u8; 6]);
+
+impl ShortId {
+    /// Computes the SipHash keys of the short IDs of a compact block from its header and nonce.
+    pub fn siphash_keys(header: &BlockHeader, nonce: u64) -> (u64, u64) {
+        let mut engine = sha256::Hash::engine();
+        header.consensus_encode(&mut engine).expect("engines don't error");
+        nonce.consensus_encode(&mut engine).expect("engines don't error");
+        let hash = sha256::Hash::from_engine(engine);
+        (endian::slice_to_u64_le(&hash[0..8]), endian::slice_to_u64_le(&hash[8..16]))
+    }
+
+    /// Computes the short ID of a transaction from its txid, for version 1 compact blocks, or
+    /// its wtxid, for version 2.
+    pub fn with_siphash_keys<T: AsRef<[u8]>>(hash: &T, siphash_keys: (u64, u64)) -> ShortId {
+        let hash = SipHasher::hash(siphash_keys.0, siphash_keys.1, hash.as_ref());
+        let mut id = ShortId([0; 6]);
+        id.0.copy_from_slice(&endian::u64_to_array_le(hash)[..6]);
+        id
+    }
+}
+
+impl fmt::Display for ShortId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for ShortId {
+    #[inline]
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(s)
+    }
+}
+
+impl Decodable for ShortId {
+    #[inline]
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        Ok(ShortId(Decodable::consensus_decode(r)?))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for ShortId {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ShortId(u.arbitrary()?))
+    }
+}
+
+/// A transaction sent in full in a compact block
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PrefilledTransaction {
+    /// The index of the transaction in the block, minus the index of the previous prefilled
+    /// transaction plus one
+    pub idx: u16,
+    /// The transaction
+    pub tx: Transaction,
+}
+arbitrary_struct_impl!(PrefilledTransaction, idx, tx);
+
+impl Encodable for PrefilledTransaction {
+    #[inline]
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        Ok(VarInt(self.idx as u64).consensus_encode(s)? + self.tx.consensus_encode(s)?)
+    }
+}
+
+impl Decodable for PrefilledTransaction {
+    #[inline]
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let idx = VarInt::consensus_decode(r)?.0;
+        if idx > u16::max_value() as u64 {
+            return Err(encode::Error::ParseFailed("BIP152 prefilled transaction index out of bounds"));
+        }
+        Ok(PrefilledTransaction { idx: idx as u16, tx: Decodable::consensus_decode(r)? })
+    }
+}
+
+/// `cmpctblock` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct HeaderAndShortIds {
+    /// The header of the block
+    pub header: BlockHeader,
+    /// The nonce keying the short IDs with the header
+    pub nonce: u64,
+    /// The short IDs of the transactions not prefilled, in block order
+    pub short_ids: Vec<ShortId>,
+    /// The transactions sent in full, at least the coinbase
+    pub prefilled_txs: Vec<PrefilledTransaction>,
+}
+impl_consensus_encoding!(HeaderAndShortIds, header, nonce, short_ids, prefilled_txs);
+arbitrary_struct_impl!(HeaderAndShortIds, header, nonce, short_ids, prefilled_txs);
+
+impl HeaderAndShortIds {
+    /// Creates a compact block of `version` for `block`, keyed with `nonce`, sending the
+    /// coinbase and the transactions at `prefill` in full.
+    ///
+    /// Blocks with an extension payload must be relayed in full.
+    pub fn from_block(block: &Block, nonce: u64, version: u64, prefill: &[usize]) -> Result<HeaderAndShortIds, Error> {
+        if version != 1 && version != 2 {
+            return Err(Error::UnknownVersion(version));
+        }
+        if block.extension.is_some() {
+            return Err(Error::ExtensionPayload);
+        }
+        if let Some(&index) = prefill.iter().find(|&&index| index >= block.txdata.len()) {
+            return Err(Error::IndexOutOfRange(index));
+        }
+        let siphash_keys = ShortId::siphash_keys(&block.header, nonce);
+        let mut short_ids = Vec::new();
+        let mut prefilled_txs = Vec::new();
+        let mut last_prefilled = None;
+        for (index, tx) in block.txdata.iter().enumerate() {
+            if index == 0 || prefill.contains(&index) {
+                let idx = last_prefilled.map_or(index, |last| index - last - 1);
+                if idx > u16::max_value() as usize {
+                    return Err(Error::IndexOutOfRange(index));
+                }
+                prefilled_txs.push(PrefilledTransaction { idx: idx as u16, tx: tx.clone() });
+                last_prefilled = Some(index);
+            } else if version == 1 {
+                short_ids.push(ShortId::with_siphash_keys(&tx.txid(), siphash_keys));
+            } else {
+                short_ids.push(ShortId::with_siphash_keys(&tx.wtxid(), siphash_keys));
+            }
+        }
+        Ok(HeaderAndShortIds { header: block.header.clone(), nonce, short_ids, prefilled_txs })
+    }
+
+    /// Returns the SipHash keys of the short IDs.
+    pub fn siphash_keys(&self) -> (u64, u64) {
+        ShortId::siphash_keys(&self.header, self.nonce)
+    }
+
+    /// Returns the short ID of a transaction with txid, or wtxid for version 2, `hash`.
+    pub fn short_id<T: AsRef<[u8]>>(&self, hash: &T) -> ShortId {
+        ShortId::with_siphash_keys(hash, self.siphash_keys())
+    }
+
+    /// Reconstructs the block from the prefilled transactions and the transactions `lookup`
+    /// finds by short ID, typically in the mempool, see [`HeaderAndShortIds::short_id`].
+    pub fn reconstruct<F>(&self, mut lookup: F) -> Result<PartialBlock, Error>
+    where
+        F: FnMut(&ShortId) -> Option<Transaction>,
+    {
+        if self.short_ids.is_empty() && self.prefilled_txs.is_empty() {
+            return Err(Error::Empty);
+        }
+        let count = self.short_ids.len() + self.prefilled_txs.len();
+        if count > MAX_TRANSACTIONS {
+            return Err(Error::TooManyTransactions(count));
+        }
+
+        let mut txdata = vec![None; count];
+        let mut next = 0;
+        for prefilled in &self.prefilled_txs {
+            let index = next + prefilled.idx as usize;
+            if index >= count {
+                return Err(Error::InvalidPrefilledIndex(index));
+            }
+            txdata[index] = Some(prefilled.tx.clone());
+            next = index + 1;
+        }
+
+        let mut seen = BTreeSet::new();
+        if let Some(id) = self.short_ids.iter().find(|&id| !seen.insert(id)) {
+            return Err(Error::ShortIdCollision(*id));
+        }
+        let mut short_ids = self.short_ids.iter();
+        for tx in txdata.iter_mut().filter(|tx| tx.is_none()) {
+            *tx = lookup(short_ids.next().expect("a short ID per missing transaction"));
+        }
+        Ok(PartialBlock { header: self.header.clone(), txdata })
+    }
+}
+
+/// A block reconstructed from a compact block, possibly missing transactions.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PartialBlock {
+    header: BlockHeader,
+    txdata: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    /// Returns the header of the block.
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Returns the indexes of the missing transactions.
+    pub fn missing(&self) -> Vec<usize> {
+        self.txdata.iter().enumerate().filter(|&(_, tx)| tx.is_none()).map(|(index, _)| index).collect()
+    }
+
+    /// Returns whether no transactions are missing.
+    pub fn is_complete(&self) -> bool {
+        self.txdata.iter().all(Option::is_some)
+    }
+
+    /// Returns the `getblocktxn` request for the missing transactions.
+    pub fn request(&self) -> BlockTransactionsRequest {
+        BlockTransactionsRequest {
+            block_hash: self.header.block_hash(),
+            indexes: self.missing().into_iter().map(|index| index as u64).collect(),
+        }
+    }
+
+    /// Fills the missing transactions with those of the `blocktxn` answer to
+    /// [`PartialBlock::request`].
+    pub fn fill(&mut self, transactions: BlockTransactions) -> Result<(), Error> {
+        if transactions.block_hash != self.header.block_hash() {
+            return Err(Error::BlockHashMismatch(transactions.block_hash));
+        }
+        let expected = self.txdata.iter().filter(|tx| tx.is_none()).count();
+        if transactions.transactions.len() != expected {
+            return Err(Error::WrongTransactionCount { expected, found: transactions.transactions.len() });
+        }
+        let missing = self.txdata.iter_mut().filter(|tx| tx.is_none());
+        for (slot, tx) in missing.zip(transactions.transactions) {
+            *slot = Some(tx);
+        }
+        Ok(())
+    }
+
+    /// Returns the block once complete, checking its transactions against the merkle root.
+    pub fn into_block(self) -> Result<Block, Error> {
+        let missing = self.txdata.iter().filter(|tx| tx.is_none()).count();
+        if missing > 0 {
+            return Err(Error::Incomplete(missing));
+        }
+        let block = Block {
+            header: self.header,
+            txdata: self.txdata.into_iter().map(|tx| tx.expect("complete")).collect(),
+            // compact blocks are never created for blocks with an extension payload
+            extension: None,
+        };
+        if !block.check_merkle_root() {
+            return Err(Error::MerkleRootMismatch);
+        }
+        Ok(block)
+    }
+}
+
+/// `getblocktxn` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BlockTransactionsRequest {
+    /// The hash of the block
+    pub block_hash: BlockHash,
+    /// The indexes of the requested transactions in the block, in strictly ascending order and
+    /// at most 65535, as Bitcoin Core doesn't accept larger ones
+    pub indexes: Vec<u64>,
+}
+
+impl Encodable for BlockTransactionsRequest {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        let ascending = self.indexes.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending || self.indexes.last().map_or(false, |&index| index > u16::max_value() as u64) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "BIP152 block transaction indexes must be ascending 16-bit integers"));
+        }
+        let mut len = self.block_hash.consensus_encode(s)?;
+        len += VarInt(self.indexes.len() as u64).consensus_encode(s)?;
+        // indexes are sent as the difference with the previous one plus one
+        let mut next = 0u64;
+        for &index in &self.indexes {
+            len += VarInt(index - next).consensus_encode(s)?;
+            next = index + 1;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for BlockTransactionsRequest {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let block_hash = Decodable::consensus_decode(r)?;
+        let len = VarInt::consensus_decode(r)?.0;
+        let byte_size = (len as usize)
+            .checked_mul(8)
+            .ok_or(encode::Error::ParseFailed("Invalid length"))?;
+        if byte_size > MAX_VEC_SIZE {
+            return Err(encode::Error::OversizedVectorAllocation { requested: byte_size, max: MAX_VEC_SIZE });
+        }
+        let mut indexes = Vec::with_capacity(len as usize);
+        let mut next = 0u64;
+        for _ in 0..len {
+            let index = VarInt::consensus_decode(r)?.0.checked_add(next)
+                .filter(|&index| index <= u16::max_value() as u64)
+                .ok_or(encode::Error::ParseFailed("BIP152 block transaction index out of bounds"))?;
+            indexes.push(index);
+            next = index + 1;
+        }
+        Ok(BlockTransactionsRequest { block_hash, indexes })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for BlockTransactionsRequest {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut indexes: Vec<u16> = u.arbitrary()?;
+        indexes.sort_unstable();
+        indexes.dedup();
+        Ok(BlockTransactionsRequest {
+            block_hash: u.arbitrary()?,
+            indexes: indexes.into_iter().map(u64::from).collect(),
+        })
+    }
+}
+
+/// `blocktxn` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BlockTransactions {
+    /// The hash of the block
+    pub block_hash: BlockHash,
+    /// The requested transactions, in the order of the request
+    pub transactions: Vec<Transaction>,
+}
+impl_consensus_encoding!(BlockTransactions, block_hash, transactions);
+arbitrary_struct_impl!(BlockTransactions, block_hash, transactions);
+
+impl BlockTransactions {
+    /// Answers `request` with the transactions of `block`, `None` if the request is for another
+    /// block or past its end.
+    pub fn from_request(request: &BlockTransactionsRequest, block: &Block) -> Option<BlockTransactions> {
+        if request.block_hash != block.block_hash() {
+            return None;
+        }
+        let transactions = request.indexes.iter()
+            .map(|&index| block.txdata.get(index as usize).cloned())
+            .collect::<Option<Vec<_>>>()?;
+        Some(BlockTransactions { block_hash: request.block_hash, transactions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::{FromHex, ToHex};
+    use blockdata::block::ExtensionPayload;
+    use consensus::encode::{deserialize, serialize};
+
+    fn block() -> Block {
+        // testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
+        deserialize(&include_bytes!("../../test_data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw")[..]).unwrap()
+    }
+
+    #[test]
+    fn short_ids() {
+        let block = block();
+        let keys = ShortId::siphash_keys(&block.header, 42);
+        let hash = sha256::Hash::hash(&[serialize(&block.header), serialize(&42u64)].concat());
+        assert_eq!(serialize(&keys.0)[..], hash[..8]);
+        assert_eq!(serialize(&keys.1)[..], hash[8..16]);
+
+        let wtxid = block.txdata[1].wtxid();
+        let id = ShortId::with_siphash_keys(&wtxid, keys);
+        let full = SipHasher::new(keys.0, keys.1).write(&wtxid[..]).finalize();
+        assert_eq!(serialize(&id), serialize(&full)[..6].to_vec());
+        assert_eq!(id.to_string(), serialize(&full)[..6].to_hex());
+
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[3, 4]).unwrap();
+        assert_eq!(compact.short_ids.len(), block.txdata.len() - 3);
+        assert_eq!(compact.short_ids[0], id);
+        assert_eq!(compact.short_id(&wtxid), id);
+        assert_eq!(compact.prefilled_txs.iter().map(|tx| tx.idx).collect::<Vec<_>>(), vec![0, 2, 0]);
+        assert_eq!(deserialize::<HeaderAndShortIds>(&serialize(&compact)).unwrap(), compact);
+        assert_eq!(serialize(&compact).len(), 80 + 8 + 1 + 6 * 12 + 1 + 3 + compact.prefilled_txs.iter().map(|tx| tx.tx.consensus_size()).sum::<usize>());
+
+        let v1 = HeaderAndShortIds::from_block(&block, 42, 1, &[]).unwrap();
+        assert_eq!(v1.short_ids[0], ShortId::with_siphash_keys(&block.txdata[1].txid(), keys));
+        assert_eq!(HeaderAndShortIds::from_block(&block, 42, 3, &[]), Err(Error::UnknownVersion(3)));
+        assert_eq!(HeaderAndShortIds::from_block(&block, 42, 2, &[15]), Err(Error::IndexOutOfRange(15)));
+
+        let mut extended = block.clone();
+        extended.extension = Some(ExtensionPayload::new(vec![0x01]));
+        assert_eq!(HeaderAndShortIds::from_block(&extended, 42, 2, &[]), Err(Error::ExtensionPayload));
+
+        // the differential index of a prefilled transaction must fit in 16 bits
+        let mut large = block.clone();
+        large.txdata.resize(70_000, block.txdata[1].clone());
+        let far = u16::max_value() as usize + 1;
+        assert_eq!(HeaderAndShortIds::from_block(&large, 42, 2, &[far]).unwrap().prefilled_txs[1].idx, u16::max_value());
+        assert_eq!(HeaderAndShortIds::from_block(&large, 42, 2, &[far + 1]), Err(Error::IndexOutOfRange(far + 1)));
+    }
+
+    #[test]
+    fn reconstruct() {
+        let block = block();
+        let compact = HeaderAndShortIds::from_block(&block, 7, 2, &[5]).unwrap();
+        // the mempool misses transactions 2 and 9
+        let mempool: BTreeMap<ShortId, Transaction> = block.txdata.iter().enumerate()
+            .filter(|&(index, _)| index != 2 && index != 9)
+            .map(|(_, tx)| (compact.short_id(&tx.wtxid()), tx.clone()))
+            .collect();
+
+        let mut partial = compact.reconstruct(|id| mempool.get(id).cloned()).unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.missing(), vec![2, 9]);
+        let request = partial.request();
+        assert_eq!(request.indexes, vec![2, 9]);
+        assert_eq!(partial.clone().into_block(), Err(Error::Incomplete(2)));
+
+        let answer = BlockTransactions::from_request(&request, &block).unwrap();
+        let mut wrong = answer.clone();
+        wrong.transactions.pop();
+        assert_eq!(partial.fill(wrong), Err(Error::WrongTransactionCount { expected: 2, found: 1 }));
+        let mut swapped = answer.clone();
+        swapped.transactions.swap(0, 1);
+        let mut collided = partial.clone();
+        collided.fill(swapped).unwrap();
+        assert_eq!(collided.into_block(), Err(Error::MerkleRootMismatch));
+
+        partial.fill(answer).unwrap();
+        assert!(partial.is_complete());
+        assert_eq!(partial.header(), &block.header);
+        assert_eq!(partial.into_block().unwrap(), block);
+
+        let mut colliding = compact.clone();
+        colliding.short_ids[3] = colliding.short_ids[1];
+        assert_eq!(colliding.reconstruct(|_| None), Err(Error::ShortIdCollision(compact.short_ids[1])));
+        let mut past_end = compact.clone();
+        past_end.prefilled_txs[1].idx = 14;
+        assert_eq!(past_end.reconstruct(|_| None), Err(Error::InvalidPrefilledIndex(15)));
+        let empty = HeaderAndShortIds { header: block.header.clone(), nonce: 0, short_ids: vec![], prefilled_txs: vec![] };
+        assert_eq!(empty.reconstruct(|_| None), Err(Error::Empty));
+    }
+
+    #[test]
+    fn block_transactions() {
+        let block = block();
+        let request = BlockTransactionsRequest { block_hash: block.block_hash(), indexes: vec![1, 2, 5, 300] };
+        let raw = serialize(&request);
+        assert_eq!(raw[32..].to_hex(), "04010002fd2601");
+        assert_eq!(deserialize::<BlockTransactionsRequest>(&raw).unwrap(), request);
+        assert!(BlockTransactions::from_request(&request, &block).is_none());
+
+        let overflow = Vec::from_hex(&format!("{}02ffffffffffffffffff00", block.block_hash()[..].to_hex())).unwrap();
+        assert!(deserialize::<BlockTransactionsRequest>(&overflow).is_err());
+        let past_u16 = Vec::from_hex(&format!("{}02fdff7ffd0080", block.block_hash()[..].to_hex())).unwrap();
+        assert!(deserialize::<BlockTransactionsRequest>(&past_u16).is_err());
+
+        // indexes that aren't strictly ascending or don't fit in 16 bits can't be encoded
+        for indexes in vec![vec![5, 2], vec![2, 2], vec![0x1_0000]] {
+            let invalid = BlockTransactionsRequest { block_hash: block.block_hash(), indexes };
+            assert!(invalid.consensus_encode(&mut Vec::new()).is_err());
+        }
+        let last = BlockTransactionsRequest { block_hash: block.block_hash(), indexes: vec![0, 0xffff] };
+        assert_eq!(deserialize::<BlockTransactionsRequest>(&serialize(&last)).unwrap(), last);
+
+        let request = BlockTransactionsRequest { block_hash: block.block_hash(), indexes: vec![0, 14] };
+        let answer = BlockTransactions::from_request(&request, &block).unwrap();
+        assert_eq!(answer.transactions, vec![block.txdata[0].clone(), block.txdata[14].clone()]);
+        assert_eq!(deserialize::<BlockTransactions>(&serialize(&answer)).unwrap(), answer);
+
+        let send = SendCmpct { send_compact: true, version: 2 };
+        assert_eq!(serialize(&send), vec![1, 2, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -3,49 +3,134 @@
 //! This module describes BIP157 Client Side Block Filtering network messages.
 //!
 
+use core::fmt;
+
+use io;
 use hash_types::{BlockHash, FilterHash, FilterHeader};
+use consensus::encode::{self, Decodable, Encodable};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+
+/// The type of a compact block filter
+///
+/// `FilterType::Unknown(0)` is invalid: it encodes as [`FilterType::Basic`] and decodes back as
+/// such. Build filter types from their byte with [`FilterType::from`], which never returns it.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FilterType {
+    /// The basic filter of BIP158, indexing the scripts spent and created by a block
+    Basic,
+    /// A filter type this library doesn't support, never 0, see above
+    Unknown(u8),
+}
+
+impl FilterType {
+    /// Returns the byte identifying the filter type
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FilterType::Basic => 0,
+            FilterType::Unknown(filter_type) => filter_type,
+        }
+    }
+
+    /// Returns whether the filter type is supported, i.e. not [`FilterType::Unknown`]
+    pub fn is_known(self) -> bool {
+        match self {
+            FilterType::Basic => true,
+            FilterType::Unknown(_) => false,
+        }
+    }
+}
+
+impl From<u8> for FilterType {
+    fn from(filter_type: u8) -> FilterType {
+        match filter_type {
+            0 => FilterType::Basic,
+            filter_type => FilterType::Unknown(filter_type),
+        }
+    }
+}
+
+impl From<FilterType> for u8 {
+    fn from(filter_type: FilterType) -> u8 {
+        filter_type.to_u8()
+    }
+}
+
+impl fmt::Display for FilterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FilterType::Basic => f.write_str("basic"),
+            FilterType::Unknown(filter_type) => write!(f, "unknown ({})", filter_type),
+        }
+    }
+}
+
+impl Encodable for FilterType {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        self.to_u8().consensus_encode(s)
+    }
+}
+
+impl Decodable for FilterType {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        u8::consensus_decode(r).map(FilterType::from)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for FilterType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        u8::arbitrary(u).map(FilterType::from)
+    }
+}
 
 /// getcfilters message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetCFilters {
     /// Filter type for which headers are requested
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// The height of the first block in the requested range
     pub start_height: u32,
     /// The hash of the last block in the requested range
     pub stop_hash: BlockHash,
 }
 impl_consensus_encoding!(GetCFilters, filter_type, start_height, stop_hash);
+arbitrary_struct_impl!(GetCFilters, filter_type, start_height, stop_hash);
 
 /// cfilter message
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CFilter {
     /// Byte identifying the type of filter being returned
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// Block hash of the Bitcoin block for which the filter is being returned
     pub block_hash: BlockHash,
     /// The serialized compact filter for this block
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
     pub filter: Vec<u8>,
 }
 impl_consensus_encoding!(CFilter, filter_type, block_hash, filter);
+arbitrary_struct_impl!(CFilter, filter_type, block_hash, filter);
 
 /// getcfheaders message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetCFHeaders {
     /// Byte identifying the type of filter being returned
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// The height of the first block in the requested range
     pub start_height: u32,
     /// The hash of the last block in the requested range
     pub stop_hash: BlockHash,
 }
 impl_consensus_encoding!(GetCFHeaders, filter_type, start_height, stop_hash);
+arbitrary_struct_impl!(GetCFHeaders, filter_type, start_height, stop_hash);
 
 /// cfheaders message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CFHeaders {
     /// Filter type for which headers are requested
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// The hash of the last block in the requested range
     pub stop_hash: BlockHash,
     /// The filter header preceding the first block in the requested range
@@ -54,25 +139,58 @@ pub struct CFHeaders {
     pub filter_hashes: Vec<FilterHash>,
 }
 impl_consensus_encoding!(CFHeaders, filter_type, stop_hash, previous_filter_header, filter_hashes);
+arbitrary_struct_impl!(CFHeaders, filter_type, stop_hash, previous_filter_header, filter_hashes);
 
 /// getcfcheckpt message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetCFCheckpt {
     /// Filter type for which headers are requested
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// The hash of the last block in the requested range
     pub stop_hash: BlockHash,
 }
 impl_consensus_encoding!(GetCFCheckpt, filter_type, stop_hash);
+arbitrary_struct_impl!(GetCFCheckpt, filter_type, stop_hash);
 
 /// cfcheckpt message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CFCheckpt {
     /// Filter type for which headers are requested
-    pub filter_type: u8,
+    pub filter_type: FilterType,
     /// The hash of the last block in the requested range
     pub stop_hash: BlockHash,
     /// The filter headers at intervals of 1,000
     pub filter_headers: Vec<FilterHeader>,
 }
 impl_consensus_encoding!(CFCheckpt, filter_type, stop_hash, filter_headers);
+arbitrary_struct_impl!(CFCheckpt, filter_type, stop_hash, filter_headers);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::encode::{deserialize, serialize};
+
+    #[test]
+    fn filter_type() {
+        assert_eq!(deserialize::<FilterType>(&[0]).unwrap(), FilterType::Basic);
+        assert_eq!(deserialize::<FilterType>(&[0x2a]).unwrap(), FilterType::Unknown(0x2a));
+        assert_eq!(serialize(&FilterType::Basic), vec![0]);
+        assert_eq!(serialize(&FilterType::Unknown(0x2a)), vec![0x2a]);
+        assert!(FilterType::Basic.is_known());
+        assert!(!FilterType::from(1).is_known());
+        assert_eq!(u8::from(FilterType::from(1)), 1);
+
+        // `Unknown(0)` is invalid, the conversion never returns it
+        assert_eq!(FilterType::from(0), FilterType::Basic);
+        assert_eq!(serialize(&FilterType::Unknown(0)), vec![0]);
+        assert_eq!(deserialize::<FilterType>(&serialize(&FilterType::Unknown(0))).unwrap(), FilterType::Basic);
+
+        let getcfilters: GetCFilters = deserialize(&[
+            0x01, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00,
+        ]).unwrap();
+        assert_eq!(getcfilters.filter_type, FilterType::Unknown(1));
+        assert_eq!(getcfilters.start_height, 100);
+    }
+}
@@ -28,6 +28,8 @@ use consensus::{Encodable, Decodable, ReadExt};
 use consensus::encode;
 use network::message::CommandString;
 use hashes::sha256d;
+#[cfg(feature = "arbitrary")] use hashes::Hash;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// Some simple messages
 
@@ -84,6 +86,9 @@ impl VersionMessage {
 impl_consensus_encoding!(VersionMessage, version, services, timestamp,
                          receiver, sender, nonce,
                          user_agent, start_height, relay);
+arbitrary_struct_impl!(VersionMessage, version, services, timestamp,
+                       receiver, sender, nonce,
+                       user_agent, start_height, relay);
 
 /// message rejection reason as a code
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -129,6 +134,23 @@ impl Decodable for RejectReason {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for RejectReason {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            RejectReason::Malformed,
+            RejectReason::Invalid,
+            RejectReason::Obsolete,
+            RejectReason::Duplicate,
+            RejectReason::NonStandard,
+            RejectReason::Dust,
+            RejectReason::Fee,
+            RejectReason::Checkpoint,
+        ])?)
+    }
+}
+
 /// Reject message might be sent by peers rejecting one of our messages
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Reject {
@@ -144,9 +166,108 @@ pub struct Reject {
 
 impl_consensus_encoding!(Reject, message, ccode, reason, hash);
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Reject {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Reject {
+            message: u.arbitrary()?,
+            ccode: u.arbitrary()?,
+            reason: Cow::Owned(u.arbitrary()?),
+            hash: sha256d::Hash::from_inner(u.arbitrary()?),
+        })
+    }
+}
+
+/// The contents of a legacy `alert` message, as signed by the since retired alert key.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlertMessage {
+    /// The alert format version.
+    pub version: i32,
+    /// Time until which nodes relay the alert.
+    pub relay_until: i64,
+    /// Time after which the alert no longer applies.
+    pub expiration: i64,
+    /// The alert ID.
+    pub id: i32,
+    /// Alerts with an ID up to and including this one are cancelled.
+    pub cancel: i32,
+    /// IDs of further cancelled alerts.
+    pub set_cancel: Vec<i32>,
+    /// Lowest protocol version the alert applies to.
+    pub min_ver: i32,
+    /// Highest protocol version the alert applies to.
+    pub max_ver: i32,
+    /// User agents the alert applies to, all if empty.
+    pub set_sub_ver: Vec<String>,
+    /// Priority of the alert over the other ones.
+    pub priority: i32,
+    /// Comment not meant to be displayed.
+    pub comment: String,
+    /// Message displayed to the user.
+    pub status_bar: String,
+    /// Reserved.
+    pub reserved: String,
+}
+
+impl_consensus_encoding!(AlertMessage, version, relay_until, expiration, id, cancel, set_cancel,
+    min_ver, max_ver, set_sub_ver, priority, comment, status_bar, reserved);
+
+/// The legacy `alert` message.
+///
+/// The alert system was retired, but alerts are still found in historical network data. The
+/// signed bytes are kept as received, so the message is re-encoded unchanged even if they don't
+/// parse as an [`AlertMessage`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Alert {
+    /// The parsed alert, `None` if `payload` isn't a well-formed alert.
+    pub message: Option<AlertMessage>,
+    /// The serialized alert.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
+    pub payload: Vec<u8>,
+    /// The signature of the alert key over the double SHA256 of `payload`.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
+    pub signature: Vec<u8>,
+}
+
+impl Alert {
+    /// Creates an alert from its serialized contents and their signature, parsing the contents.
+    pub fn new(payload: Vec<u8>, signature: Vec<u8>) -> Alert {
+        Alert {
+            message: encode::deserialize(&payload).ok(),
+            payload,
+            signature,
+        }
+    }
+}
+
+impl Encodable for Alert {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
+        Ok(self.payload.consensus_encode(s)? + self.signature.consensus_encode(s)?)
+    }
+}
+
+impl Decodable for Alert {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let payload = Decodable::consensus_decode(r)?;
+        let signature = Decodable::consensus_decode(r)?;
+        Ok(Alert::new(payload, signature))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Alert {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Alert::new(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::VersionMessage;
+    use super::{Alert, AlertMessage, VersionMessage};
 
     use hashes::hex::FromHex;
     use network::constants::ServiceFlags;
@@ -172,4 +293,45 @@ mod tests {
 
         assert_eq!(serialize(&real_decode), from_sat);
     }
+
+    #[test]
+    fn alert_message_test() {
+        let message = AlertMessage {
+            version: 1,
+            relay_until: 1_400_000_000,
+            expiration: 1_500_000_000,
+            id: 1010,
+            cancel: 1009,
+            set_cancel: vec![1005, 1007],
+            min_ver: 70001,
+            max_ver: 70015,
+            set_sub_ver: vec!["/Satoshi:0.9.0/".to_string()],
+            priority: 5000,
+            comment: String::new(),
+            status_bar: "Upgrade required".to_string(),
+            reserved: String::new(),
+        };
+        let payload = serialize(&message);
+        let alert = Alert::new(payload.clone(), vec![0x30, 0x44]);
+        assert_eq!(alert.message, Some(message));
+        let decoded: Alert = deserialize(&serialize(&alert)).unwrap();
+        assert_eq!(decoded, alert);
+
+        // an unparseable payload is kept as is
+        let alert = Alert::new(payload[..10].to_vec(), vec![]);
+        assert_eq!(alert.message, None);
+        let decoded: Alert = deserialize(&serialize(&alert)).unwrap();
+        assert_eq!(decoded.payload, payload[..10].to_vec());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn alert_serde() {
+        let alert = Alert::new(vec![0x01, 0xff], vec![0x30, 0x44]);
+        let json = ::serde_json::to_string(&alert).unwrap();
+        assert_eq!(json, r#"{"message":null,"payload":"01ff","signature":"3044"}"#);
+        assert_eq!(::serde_json::from_str::<Alert>(&json).unwrap(), alert);
+        let bincode = ::bincode::serialize(&alert).unwrap();
+        assert_eq!(::bincode::deserialize::<Alert>(&bincode).unwrap(), alert);
+    }
 }
@@ -0,0 +1,184 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Bitcoin UTXO query network messages.
+//!
+//! This module describes the BIP64 `getutxos` and `utxos` messages, served by
+//! peers advertising [`ServiceFlags::GETUTXO`](::network::constants::ServiceFlags::GETUTXO).
+//! The `utxos` payload is also the binary response of the REST
+//! `/rest/getutxos` query, see [`GetUtxos::rest_path`].
+//!
+
+use prelude::*;
+
+use core::fmt::Write;
+
+use hash_types::BlockHash;
+use blockdata::transaction::{OutPoint, TxOut};
+
+/// Maximum number of outpoints a `getutxos` message may query.
+pub const MAX_GETUTXOS_OUTPOINTS: usize = 15;
+
+/// `getutxos` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GetUtxos {
+    /// Whether outputs created and spent by mempool transactions are taken into account
+    pub check_mempool: bool,
+    /// The outpoints queried
+    pub outpoints: Vec<OutPoint>,
+}
+impl_consensus_encoding!(GetUtxos, check_mempool, outpoints);
+arbitrary_struct_impl!(GetUtxos, check_mempool, outpoints);
+
+impl GetUtxos {
+    /// Returns the path of the equivalent REST query, asking for a binary response which
+    /// decodes as a [`Utxos`] message.
+    pub fn rest_path(&self) -> String {
+        let mut path = String::from("/rest/getutxos");
+        if self.check_mempool {
+            path.push_str("/checkmempool");
+        }
+        for outpoint in &self.outpoints {
+            write!(path, "/{}-{}", outpoint.txid, outpoint.vout).expect("writing to a string");
+        }
+        path.push_str(".bin");
+        path
+    }
+}
+
+/// An unspent output returned by a `utxos` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Utxo {
+    /// The version of the transaction creating the output, always 0 in recent Bitcoin Core
+    /// versions
+    pub tx_version: u32,
+    /// The height of the block creating the output, `0x7fffffff` for mempool outputs
+    pub height: u32,
+    /// The output itself
+    pub output: TxOut,
+}
+impl_consensus_encoding!(Utxo, tx_version, height, output);
+arbitrary_struct_impl!(Utxo, tx_version, height, output);
+
+/// `utxos` message
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Utxos {
+    /// The height of the chain tip
+    pub chain_height: u32,
+    /// The hash of the chain tip
+    pub chain_tip_hash: BlockHash,
+    /// Bit `i` of the bitmap is set if the `i`-th queried outpoint is unspent
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::hex_bytes"))]
+    pub bitmap: Vec<u8>,
+    /// The unspent outputs, in the order they were queried
+    pub utxos: Vec<Utxo>,
+}
+impl_consensus_encoding!(Utxos, chain_height, chain_tip_hash, bitmap, utxos);
+arbitrary_struct_impl!(Utxos, chain_height, chain_tip_hash, bitmap, utxos);
+
+impl Utxos {
+    /// Creates the answer to a query from the outputs found for each queried outpoint, `None`
+    /// for spent or unknown outputs.
+    pub fn new(chain_height: u32, chain_tip_hash: BlockHash, results: Vec<Option<Utxo>>) -> Utxos {
+        let mut bitmap = vec![0u8; (results.len() + 7) / 8];
+        let mut utxos = Vec::new();
+        for (i, result) in results.into_iter().enumerate() {
+            if let Some(utxo) = result {
+                bitmap[i / 8] |= 1 << (i % 8);
+                utxos.push(utxo);
+            }
+        }
+        Utxos { chain_height, chain_tip_hash, bitmap, utxos }
+    }
+
+    /// Returns whether the `index`-th queried outpoint is unspent.
+    pub fn is_unspent(&self, index: usize) -> bool {
+        self.bitmap.get(index / 8).map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Returns the output found for each of the `queried` outpoints, `None` for spent or
+    /// unknown outputs, or `None` if the bitmap doesn't match the number of returned outputs.
+    pub fn results(&self, queried: usize) -> Option<Vec<Option<&Utxo>>> {
+        let unspent = (0..queried).filter(|&i| self.is_unspent(i)).count();
+        if self.bitmap.len() != (queried + 7) / 8 || unspent != self.utxos.len() {
+            return None;
+        }
+        let mut utxos = self.utxos.iter();
+        Some((0..queried).map(|i| if self.is_unspent(i) { utxos.next() } else { None }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::FromHex;
+    use hashes::Hash;
+    use hash_types::Txid;
+    use blockdata::script::Script;
+    use consensus::encode::{deserialize, serialize};
+
+    fn utxo(value: u64) -> Utxo {
+        Utxo { tx_version: 0, height: 100, output: TxOut { value, script_pubkey: Script::new() } }
+    }
+
+    #[test]
+    fn getutxos() {
+        let txid = Txid::from_hex("9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec").unwrap();
+        let query = GetUtxos { check_mempool: true, outpoints: vec![OutPoint::new(txid, 0), OutPoint::new(txid, 3)] };
+        let raw = serialize(&query);
+        assert_eq!(raw.len(), 1 + 1 + 2 * 36);
+        assert_eq!(raw[..2], [1, 2]);
+        assert_eq!(deserialize::<GetUtxos>(&raw).unwrap(), query);
+        assert_eq!(
+            query.rest_path(),
+            "/rest/getutxos/checkmempool\
+             /9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec-0\
+             /9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec-3.bin"
+        );
+        assert_eq!(GetUtxos { check_mempool: false, outpoints: vec![] }.rest_path(), "/rest/getutxos.bin");
+    }
+
+    #[test]
+    fn utxos() {
+        let mut results = vec![None; 10];
+        results[1] = Some(utxo(1));
+        results[8] = Some(utxo(8));
+        let answer = Utxos::new(200, BlockHash::from_inner([7; 32]), results.clone());
+        assert_eq!(answer.bitmap, vec![0b10, 0b1]);
+        assert!(answer.is_unspent(1));
+        assert!(!answer.is_unspent(2));
+        assert!(!answer.is_unspent(100));
+        assert_eq!(answer.results(10).unwrap(), results.iter().map(Option::as_ref).collect::<Vec<_>>());
+        assert_eq!(answer.results(8), None);
+        assert_eq!(answer.results(17), None);
+
+        let raw = serialize(&answer);
+        assert_eq!(raw.len(), 4 + 32 + 3 + 1 + 2 * (4 + 4 + 8 + 1));
+        assert_eq!(deserialize::<Utxos>(&raw).unwrap(), answer);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn utxos_serde() {
+        let answer = Utxos::new(200, BlockHash::from_inner([7; 32]), vec![None, Some(utxo(1)), None]);
+        let json = ::serde_json::to_string(&answer).unwrap();
+        assert!(json.contains(r#""bitmap":"02""#));
+        assert_eq!(::serde_json::from_str::<Utxos>(&json).unwrap(), answer);
+        let bincode = ::bincode::serialize(&answer).unwrap();
+        assert_eq!(::bincode::deserialize::<Utxos>(&bincode).unwrap(), answer);
+    }
+}
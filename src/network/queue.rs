@@ -0,0 +1,305 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Outbound message scheduling.
+//!
+//! This module orders the messages sent to a peer the way Bitcoin Core does:
+//! messages managing the connection, such as `pong`, go out before anything
+//! else and bulk data such as blocks goes out last. Sending stops being
+//! accepted once the queued messages exceed a send buffer cap, and
+//! transaction announcements are held back to be sent in batches (trickled)
+//! at times chosen by the caller, which hides the order in which they were
+//! received.
+//!
+
+use prelude::*;
+
+use core::fmt;
+use std::collections::VecDeque;
+#[cfg(feature = "std")] use std::error;
+
+use network::framing::HEADER_SIZE;
+use network::message::{NetworkMessage, MAX_INV_SIZE};
+use network::message_blockdata::Inventory;
+
+/// Default cap on the bytes queued for a peer, matching Bitcoin Core's `-maxsendbuffer`.
+pub const DEFAULT_MAX_QUEUED_BYTES: usize = 1_000_000;
+
+/// Maximum number of transactions announced in one trickle.
+pub const INVENTORY_BROADCAST_MAX: usize = 1_000;
+
+/// Order in which queued messages are sent, highest priority first.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Priority {
+    /// Messages managing the connection, see [`NetworkMessage::is_control_message`].
+    Control,
+    /// Announcements, requests and transactions.
+    Normal,
+    /// Blocks and compact block filter data.
+    Bulk,
+}
+
+impl Priority {
+    /// Returns the priority a message is queued with by [`MessageQueue::push`].
+    pub fn of(message: &NetworkMessage) -> Priority {
+        if message.is_control_message() {
+            return Priority::Control;
+        }
+        match *message {
+            NetworkMessage::Block(_)
+            | NetworkMessage::MerkleBlock(_)
+            | NetworkMessage::CFilter(_)
+            | NetworkMessage::CFHeaders(_)
+            | NetworkMessage::CFCheckpt(_) => Priority::Bulk,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+/// Error returned when the send buffer of a [`MessageQueue`] is full, holding the message which
+/// wasn't queued.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QueueFull(pub Box<NetworkMessage>);
+
+impl fmt::Display for QueueFull {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "send buffer full, `{}` message not queued", self.0.cmd())
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for QueueFull {}
+
+/// Queue of the messages to send to a peer.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MessageQueue {
+    control: VecDeque<NetworkMessage>,
+    normal: VecDeque<NetworkMessage>,
+    bulk: VecDeque<NetworkMessage>,
+    pending_inv: BTreeSet<Inventory>,
+    queued_bytes: usize,
+    max_queued_bytes: usize,
+}
+
+impl Default for MessageQueue {
+    fn default() -> Self {
+        MessageQueue::new(DEFAULT_MAX_QUEUED_BYTES)
+    }
+}
+
+impl MessageQueue {
+    /// Creates an empty queue which stops accepting messages once `max_queued_bytes` are queued.
+    pub fn new(max_queued_bytes: usize) -> MessageQueue {
+        MessageQueue {
+            control: VecDeque::new(),
+            normal: VecDeque::new(),
+            bulk: VecDeque::new(),
+            pending_inv: BTreeSet::new(),
+            queued_bytes: 0,
+            max_queued_bytes,
+        }
+    }
+
+    /// Queues a message with the priority given by [`Priority::of`].
+    ///
+    /// An `inv` message only announcing transactions isn't queued, its items are held back until
+    /// the next [`MessageQueue::trickle`].
+    pub fn push(&mut self, message: NetworkMessage) -> Result<(), QueueFull> {
+        if let NetworkMessage::Inv(ref inv) = message {
+            if !inv.is_empty() && inv.iter().all(is_transaction) {
+                self.pending_inv.extend(inv.iter().cloned());
+                return Ok(());
+            }
+        }
+        let priority = Priority::of(&message);
+        self.push_with_priority(message, priority)
+    }
+
+    /// Queues a message with the given priority.
+    ///
+    /// Control messages are always queued. Other messages are refused while the queued messages
+    /// exceed the send buffer cap; like Bitcoin Core, a message is accepted as long as the cap
+    /// isn't reached yet, even if it makes the queue exceed the cap.
+    pub fn push_with_priority(&mut self, message: NetworkMessage, priority: Priority) -> Result<(), QueueFull> {
+        if priority != Priority::Control && self.is_full() {
+            return Err(QueueFull(Box::new(message)));
+        }
+        self.queued_bytes += HEADER_SIZE + message.payload_size();
+        match priority {
+            Priority::Control => self.control.push_back(message),
+            Priority::Normal => self.normal.push_back(message),
+            Priority::Bulk => self.bulk.push_back(message),
+        }
+        Ok(())
+    }
+
+    /// Queues the transaction announcements held back, in `inv` messages announcing at most
+    /// [`INVENTORY_BROADCAST_MAX`] transactions in total. Returns the number of announced
+    /// transactions.
+    ///
+    /// The announcements are sorted, so their order doesn't reveal the order the transactions were
+    /// received in. Trickles are meant to be scheduled at random intervals, as Bitcoin Core does
+    /// with a Poisson process averaging 5 seconds for inbound peers and 2 seconds for outbound ones.
+    pub fn trickle(&mut self) -> usize {
+        let count = self.pending_inv.len().min(INVENTORY_BROADCAST_MAX);
+        let batch: Vec<Inventory> = self.pending_inv.iter().take(count).cloned().collect();
+        for inv in &batch {
+            self.pending_inv.remove(inv);
+        }
+        for chunk in batch.chunks(MAX_INV_SIZE) {
+            let message = NetworkMessage::Inv(chunk.to_vec());
+            self.queued_bytes += HEADER_SIZE + message.payload_size();
+            self.normal.push_back(message);
+        }
+        count
+    }
+
+    /// Removes the next message to send.
+    pub fn pop(&mut self) -> Option<NetworkMessage> {
+        let message = self.control.pop_front()
+            .or_else(|| self.normal.pop_front())
+            .or_else(|| self.bulk.pop_front());
+        if let Some(ref message) = message {
+            self.queued_bytes -= HEADER_SIZE + message.payload_size();
+        }
+        message
+    }
+
+    /// Returns the number of queued messages, not counting held back announcements.
+    pub fn len(&self) -> usize {
+        self.control.len() + self.normal.len() + self.bulk.len()
+    }
+
+    /// Returns whether no message is queued, not counting held back announcements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of transaction announcements held back until the next trickle.
+    pub fn pending_inv(&self) -> usize {
+        self.pending_inv.len()
+    }
+
+    /// Returns the number of bytes the queued messages take on the wire.
+    pub fn queued_bytes(&self) -> usize {
+        self.queued_bytes
+    }
+
+    /// Returns whether the queued messages reached the send buffer cap, so only control
+    /// messages are accepted.
+    pub fn is_full(&self) -> bool {
+        self.queued_bytes >= self.max_queued_bytes
+    }
+}
+
+fn is_transaction(inv: &Inventory) -> bool {
+    match *inv {
+        Inventory::Transaction(_) | Inventory::WTx(_) | Inventory::WitnessTransaction(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::encode::serialize;
+    use hashes::Hash;
+    use hash_types::{BlockHash, Txid};
+    use network::message::RawNetworkMessage;
+    use blockdata::constants::genesis_block;
+    use network::constants::Network;
+    use util::endian;
+
+    fn txid(n: u8) -> Inventory {
+        Inventory::Transaction(Txid::from_inner([n; 32]))
+    }
+
+    #[test]
+    fn priorities() {
+        let block = NetworkMessage::Block(genesis_block(Network::Bitcoin));
+        let getdata = NetworkMessage::GetData(vec![Inventory::Block(BlockHash::from_inner([1; 32]))]);
+        assert_eq!(Priority::of(&NetworkMessage::Pong(1)), Priority::Control);
+        assert_eq!(Priority::of(&getdata), Priority::Normal);
+        assert_eq!(Priority::of(&block), Priority::Bulk);
+
+        let mut queue = MessageQueue::default();
+        queue.push(block.clone()).unwrap();
+        queue.push(getdata.clone()).unwrap();
+        queue.push(NetworkMessage::Pong(1)).unwrap();
+        queue.push(NetworkMessage::Ping(2)).unwrap();
+        assert_eq!(queue.len(), 4);
+
+        let raw_size = |payload: &NetworkMessage| serialize(&RawNetworkMessage { magic: 0, payload: payload.clone() }).len();
+        let total: usize = [&block, &getdata, &NetworkMessage::Pong(1), &NetworkMessage::Ping(2)].iter().map(|&m| raw_size(m)).sum();
+        assert_eq!(queue.queued_bytes(), total);
+
+        assert_eq!(queue.pop(), Some(NetworkMessage::Pong(1)));
+        assert_eq!(queue.pop(), Some(NetworkMessage::Ping(2)));
+        assert_eq!(queue.pop(), Some(getdata));
+        assert_eq!(queue.pop(), Some(block));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+        assert_eq!(queue.queued_bytes(), 0);
+    }
+
+    #[test]
+    fn send_buffer_cap() {
+        let block = NetworkMessage::Block(genesis_block(Network::Bitcoin));
+        let mut queue = MessageQueue::new(100);
+        // the first message is accepted even though it exceeds the cap
+        queue.push(block.clone()).unwrap();
+        assert!(queue.is_full());
+        assert_eq!(queue.push(block.clone()), Err(QueueFull(Box::new(block.clone()))));
+        // control messages are still accepted
+        queue.push(NetworkMessage::Verack).unwrap();
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop(), Some(NetworkMessage::Verack));
+        assert_eq!(queue.pop(), Some(block.clone()));
+        assert!(!queue.is_full());
+        queue.push(block).unwrap();
+    }
+
+    #[test]
+    fn trickle() {
+        let mut queue = MessageQueue::default();
+        assert_eq!(queue.trickle(), 0);
+        assert!(queue.is_empty());
+
+        // transaction announcements are held back, block announcements are not
+        queue.push(NetworkMessage::Inv(vec![txid(3), txid(1)])).unwrap();
+        queue.push(NetworkMessage::Inv(vec![txid(2), txid(1)])).unwrap();
+        let block_inv = NetworkMessage::Inv(vec![Inventory::Block(BlockHash::from_inner([9; 32]))]);
+        queue.push(block_inv.clone()).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pending_inv(), 3);
+
+        assert_eq!(queue.trickle(), 3);
+        assert_eq!(queue.pending_inv(), 0);
+        assert_eq!(queue.pop(), Some(block_inv));
+        assert_eq!(queue.pop(), Some(NetworkMessage::Inv(vec![txid(1), txid(2), txid(3)])));
+        assert!(queue.is_empty());
+
+        // trickles announce a limited number of transactions
+        let many: Vec<Inventory> = (0..INVENTORY_BROADCAST_MAX as u32 + 10)
+            .map(|n| Inventory::Transaction(Txid::hash(&endian::u32_to_array_le(n))))
+            .collect();
+        queue.push(NetworkMessage::Inv(many)).unwrap();
+        assert_eq!(queue.trickle(), INVENTORY_BROADCAST_MAX);
+        assert_eq!(queue.pending_inv(), 10);
+        assert_eq!(queue.trickle(), 10);
+        assert_eq!(queue.len(), 2);
+    }
+}
@@ -0,0 +1,401 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Message transports.
+//!
+//! This module defines the [`Transport`] trait sending and receiving network
+//! messages, so code exchanging messages with peers doesn't depend on how
+//! they are carried:
+//!
+//! * [`StreamTransport`] sends messages as is over a byte stream, such as a
+//!   plain TCP socket or a TLS stream from any TLS library,
+//! * [`WebSocketTransport`] sends each message in a binary WebSocket frame,
+//!   letting peers be reached through proxies and firewalls only allowing
+//!   HTTP(S) traffic.
+//!
+//! Browsers don't give access to sockets, the data they receive from a
+//! WebSocket can be decoded with a [`MessageDecoder`] instead.
+//!
+
+use prelude::*;
+
+use io::{self, Read, Write};
+
+use hashes::{sha256, Hash, HashEngine};
+use consensus::encode::{self, MAX_VEC_SIZE};
+use network::framing::{MessageDecoder, HEADER_SIZE};
+use network::message::RawNetworkMessage;
+use util::endian;
+
+/// Sends and receives network messages.
+pub trait Transport {
+    /// Sends a message.
+    fn send_message(&mut self, message: &RawNetworkMessage) -> Result<(), encode::Error>;
+
+    /// Receives the next message, blocking until it arrives.
+    fn receive_message(&mut self) -> Result<RawNetworkMessage, encode::Error>;
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send_message(&mut self, message: &RawNetworkMessage) -> Result<(), encode::Error> {
+        (**self).send_message(message)
+    }
+
+    fn receive_message(&mut self) -> Result<RawNetworkMessage, encode::Error> {
+        (**self).receive_message()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send_message(&mut self, message: &RawNetworkMessage) -> Result<(), encode::Error> {
+        (**self).send_message(message)
+    }
+
+    fn receive_message(&mut self) -> Result<RawNetworkMessage, encode::Error> {
+        (**self).receive_message()
+    }
+}
+
+/// Transport sending messages as is over a byte stream, such as a `TcpStream` or a TLS stream.
+#[derive(Debug)]
+pub struct StreamTransport<S> {
+    stream: S,
+}
+
+impl<S: Read + Write> StreamTransport<S> {
+    /// Creates a transport over a connected stream.
+    pub fn new(stream: S) -> StreamTransport<S> {
+        StreamTransport { stream }
+    }
+
+    /// Returns a reference to the stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Write> Transport for StreamTransport<S> {
+    fn send_message(&mut self, message: &RawNetworkMessage) -> Result<(), encode::Error> {
+        self.stream.write_all(&encode::serialize(message))?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn receive_message(&mut self) -> Result<RawNetworkMessage, encode::Error> {
+        let mut message = vec![0u8; HEADER_SIZE];
+        self.stream.read_exact(&mut message)?;
+        let payload_len = endian::slice_to_u32_le(&message[16..20]) as usize;
+        if payload_len > MAX_VEC_SIZE {
+            return Err(encode::Error::OversizedVectorAllocation { requested: payload_len, max: MAX_VEC_SIZE });
+        }
+        message.resize(HEADER_SIZE + payload_len, 0);
+        self.stream.read_exact(&mut message[HEADER_SIZE..])?;
+        encode::deserialize(&message)
+    }
+}
+
+/// WebSocket frame opcodes, see RFC 6455 section 5.2.
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+const FIN: u8 = 0x80;
+const MASKED: u8 = 0x80;
+
+/// Transport sending each message in a binary WebSocket frame over a byte stream.
+///
+/// The opening handshake, an HTTP upgrade request, is done by the caller before creating the
+/// transport. Messages split across several frames, or several messages in one frame, are
+/// received too; pings from the other side are answered and a close frame is answered and
+/// reported as an unexpected end of the stream.
+#[derive(Debug)]
+pub struct WebSocketTransport<S> {
+    stream: S,
+    decoder: MessageDecoder,
+    /// Seed the masking keys of a client are derived from, `None` for a server.
+    mask_seed: Option<[u8; 32]>,
+    frame_count: u64,
+}
+
+impl<S: Read + Write> WebSocketTransport<S> {
+    /// Creates the transport of the client side of a WebSocket connection.
+    ///
+    /// Clients mask the frames they send, with keys derived from `mask_seed`, which must be
+    /// random: predictable masking keys let malicious pages attack the proxies between the
+    /// client and the server.
+    pub fn client(stream: S, mask_seed: [u8; 32]) -> WebSocketTransport<S> {
+        WebSocketTransport { stream, decoder: MessageDecoder::new(), mask_seed: Some(mask_seed), frame_count: 0 }
+    }
+
+    /// Creates the transport of the server side of a WebSocket connection, whose frames aren't
+    /// masked.
+    pub fn server(stream: S) -> WebSocketTransport<S> {
+        WebSocketTransport { stream, decoder: MessageDecoder::new(), mask_seed: None, frame_count: 0 }
+    }
+
+    /// Returns a reference to the stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the stream, dropping the received bytes not forming a whole message yet.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn next_mask_key(&mut self) -> Option<[u8; 4]> {
+        let seed = self.mask_seed?;
+        let mut engine = sha256::Hash::engine();
+        engine.input(&seed);
+        engine.input(&endian::u64_to_array_le(self.frame_count));
+        self.frame_count += 1;
+        let hash = sha256::Hash::from_engine(engine);
+        Some([hash[0], hash[1], hash[2], hash[3]])
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), encode::Error> {
+        let mask_key = self.next_mask_key();
+        let mask_bit = if mask_key.is_some() { MASKED } else { 0 };
+
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(FIN | opcode);
+        if payload.len() < 126 {
+            frame.push(mask_bit | payload.len() as u8);
+        } else if payload.len() <= u16::max_value() as usize {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&endian::u16_to_array_be(payload.len() as u16));
+        } else {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&endian::u64_to_array_be(payload.len() as u64));
+        }
+        match mask_key {
+            Some(key) => {
+                frame.extend_from_slice(&key);
+                frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+            }
+            None => frame.extend_from_slice(payload),
+        }
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Reads a frame, returning its FIN bit, opcode and unmasked payload.
+    fn read_frame(&mut self) -> Result<(bool, u8, Vec<u8>), encode::Error> {
+        let mut head = [0u8; 2];
+        self.stream.read_exact(&mut head)?;
+        let fin = head[0] & FIN != 0;
+        if head[0] & 0x70 != 0 {
+            return Err(encode::Error::ParseFailed("WebSocket frame with reserved bits set"));
+        }
+        let opcode = head[0] & 0x0f;
+
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                self.stream.read_exact(&mut len)?;
+                endian::slice_to_u16_be(&len) as u64
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                self.stream.read_exact(&mut len)?;
+                endian::slice_to_u64_be(&len)
+            }
+            len => len as u64,
+        };
+        if len > MAX_VEC_SIZE as u64 {
+            return Err(encode::Error::OversizedVectorAllocation { requested: len as usize, max: MAX_VEC_SIZE });
+        }
+
+        let mut key = [0u8; 4];
+        let masked = head[1] & MASKED != 0;
+        if masked {
+            self.stream.read_exact(&mut key)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.stream.read_exact(&mut payload)?;
+        if masked {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= key[i % 4];
+            }
+        }
+        Ok((fin, opcode, payload))
+    }
+}
+
+impl<S: Read + Write> Transport for WebSocketTransport<S> {
+    fn send_message(&mut self, message: &RawNetworkMessage) -> Result<(), encode::Error> {
+        self.write_frame(OPCODE_BINARY, &encode::serialize(message))
+    }
+
+    fn receive_message(&mut self) -> Result<RawNetworkMessage, encode::Error> {
+        loop {
+            if let Some(message) = self.decoder.next_message()? {
+                return Ok(message);
+            }
+            let (_, opcode, payload) = self.read_frame()?;
+            match opcode {
+                // fragmented messages are reassembled by the decoder
+                OPCODE_BINARY | OPCODE_CONTINUATION => self.decoder.push(&payload),
+                OPCODE_PING => self.write_frame(OPCODE_PONG, &payload)?,
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    self.write_frame(OPCODE_CLOSE, &payload[..payload.len().min(2)])?;
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                OPCODE_TEXT => return Err(encode::Error::ParseFailed("unexpected WebSocket text frame")),
+                _ => return Err(encode::Error::ParseFailed("unknown WebSocket opcode")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::Cursor;
+    use network::message::{CommandString, NetworkMessage};
+
+    /// Stream reading from a buffer and writing to another one.
+    #[derive(Debug)]
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Duplex {
+        fn new(input: Vec<u8>) -> Duplex {
+            Duplex { input: Cursor::new(input), output: Vec::new() }
+        }
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn ping(nonce: u64) -> RawNetworkMessage {
+        RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(nonce) }
+    }
+
+    fn assert_eof(result: Result<RawNetworkMessage, encode::Error>) {
+        match result {
+            Err(encode::Error::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stream_transport() {
+        let mut sender = StreamTransport::new(Duplex::new(Vec::new()));
+        sender.send_message(&ping(1)).unwrap();
+        sender.send_message(&ping(2)).unwrap();
+        let data = sender.into_inner().output;
+
+        // used through a trait object
+        let mut receiver: Box<dyn Transport> = Box::new(StreamTransport::new(Duplex::new(data)));
+        assert_eq!(receiver.receive_message().unwrap(), ping(1));
+        assert_eq!(receiver.receive_message().unwrap(), ping(2));
+        assert_eof(receiver.receive_message());
+    }
+
+    #[test]
+    fn websocket_frames() {
+        let message = encode::serialize(&ping(1));
+
+        // clients mask their frames
+        let mut client = WebSocketTransport::client(Duplex::new(Vec::new()), [7; 32]);
+        client.send_message(&ping(1)).unwrap();
+        let frame = client.into_inner().output;
+        assert_eq!(frame[0], FIN | OPCODE_BINARY);
+        assert_eq!(frame[1], MASKED | message.len() as u8);
+        assert_eq!(frame.len(), 2 + 4 + message.len());
+        assert_ne!(&frame[6..], &message[..]);
+        let mut server = WebSocketTransport::server(Duplex::new(frame));
+        assert_eq!(server.receive_message().unwrap(), ping(1));
+
+        // servers don't
+        server.send_message(&ping(1)).unwrap();
+        let frame = server.into_inner().output;
+        assert_eq!(frame[1], message.len() as u8);
+        assert_eq!(&frame[2..], &message[..]);
+        let mut client = WebSocketTransport::client(Duplex::new(frame), [7; 32]);
+        assert_eq!(client.receive_message().unwrap(), ping(1));
+
+        // large messages have an extended payload length
+        for &(size, len_byte) in &[(1_000, 126), (70_000, 127)] {
+            let large = RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Unknown {
+                command: CommandString::try_from("large").unwrap(),
+                payload: vec![0; size],
+            }};
+            let mut server = WebSocketTransport::server(Duplex::new(Vec::new()));
+            server.send_message(&large).unwrap();
+            let frame = server.into_inner().output;
+            assert_eq!(frame[1], len_byte);
+            let header_len = if len_byte == 126 { 4 } else { 10 };
+            assert_eq!(&frame[header_len..], &encode::serialize(&large)[..]);
+        }
+    }
+
+    #[test]
+    fn websocket_receive() {
+        let message = encode::serialize(&ping(3));
+        let mut data = Vec::new();
+        // a ping frame, then the message split across a binary and a continuation frame
+        data.extend_from_slice(&[FIN | OPCODE_PING, 2, 0xab, 0xcd]);
+        data.extend_from_slice(&[OPCODE_BINARY, 10]);
+        data.extend_from_slice(&message[..10]);
+        data.extend_from_slice(&[FIN | OPCODE_CONTINUATION, (message.len() - 10) as u8]);
+        data.extend_from_slice(&message[10..]);
+        data.extend_from_slice(&[FIN | OPCODE_CLOSE, 0]);
+
+        let mut transport = WebSocketTransport::server(Duplex::new(data));
+        assert_eq!(transport.receive_message().unwrap(), ping(3));
+        assert_eof(transport.receive_message());
+        // the ping and the close frames were answered
+        assert_eq!(transport.into_inner().output, vec![FIN | OPCODE_PONG, 2, 0xab, 0xcd, FIN | OPCODE_CLOSE, 0]);
+
+        let mut transport = WebSocketTransport::server(Duplex::new(vec![FIN | OPCODE_TEXT, 0]));
+        assert!(transport.receive_message().is_err());
+    }
+}
@@ -0,0 +1,302 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Fee estimation.
+//!
+//! This module implements a fee estimator in the spirit of Bitcoin Core's
+//! `estimatesmartfee`: transactions are sorted into exponentially spaced
+//! fee rate buckets, and for each bucket the estimator tracks how many of its
+//! transactions confirmed within each number of blocks. Older data decays so
+//! estimates follow the current fee market.
+//!
+
+use prelude::*;
+
+use hash_types::Txid;
+use blockdata::block::Block;
+use blockdata::transaction::{OutPoint, TxOut};
+use util::amount::Amount;
+use util::fee_rate::FeeRate;
+
+/// The highest confirmation target estimates can be made for.
+pub const MAX_CONFIRMATION_TARGET: u32 = 48;
+
+/// The lower bound of the lowest bucket, 1 sat/vB.
+const MIN_BUCKET_FEE_RATE: f64 = 250.0;
+/// The upper bound of the highest bucket, 10000 sat/vB.
+const MAX_BUCKET_FEE_RATE: f64 = 2_500_000.0;
+/// The ratio between the bounds of consecutive buckets.
+const BUCKET_SPACING: f64 = 1.05;
+/// The factor data is scaled by each block, giving a half-life of about 350 blocks.
+const DECAY: f64 = 0.998;
+/// The share of transactions of a bucket range that must have confirmed within the target.
+const SUCCESS_THRESHOLD: f64 = 0.85;
+/// The (decayed) number of transactions a bucket range needs for its success rate to count.
+const SUFFICIENT_TXS: f64 = 1.0;
+
+/// A transaction seen in a mempool snapshot and not confirmed yet.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Tracked {
+    height: u32,
+    bucket: usize,
+}
+
+/// Estimates the fee rate a transaction needs to confirm within a number of blocks.
+///
+/// Feed it every block through [`FeeEstimator::process_block`]. Mempool snapshots, passed to
+/// [`FeeEstimator::process_mempool`] after the block they were taken on top of, tell how long
+/// transactions waited. Transactions of a block which were not seen in a snapshot are assumed
+/// to have been broadcast just before it, so estimates from blocks alone tend to be optimistic.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FeeEstimator {
+    /// Lower bounds of the buckets, in sat/kwu.
+    buckets: Vec<u64>,
+    /// Per bucket, the transactions confirmed within `1 + index` blocks.
+    confirmed: Vec<Vec<f64>>,
+    /// Per bucket, all confirmed transactions.
+    total: Vec<f64>,
+    /// Per bucket, the transactions dropped from the mempool unconfirmed after waiting at least
+    /// `1 + index` blocks.
+    failed: Vec<Vec<f64>>,
+    tracked: BTreeMap<Txid, Tracked>,
+    height: u32,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        FeeEstimator::new()
+    }
+}
+
+impl FeeEstimator {
+    /// Creates an estimator without data.
+    pub fn new() -> FeeEstimator {
+        let mut buckets = vec![];
+        let mut bound = MIN_BUCKET_FEE_RATE;
+        while bound < MAX_BUCKET_FEE_RATE {
+            // round up, so a bucket's bound is a fee rate of that bucket
+            let sat_kwu = bound as u64;
+            buckets.push(if (sat_kwu as f64) < bound { sat_kwu + 1 } else { sat_kwu });
+            bound *= BUCKET_SPACING;
+        }
+        let targets = MAX_CONFIRMATION_TARGET as usize;
+        FeeEstimator {
+            confirmed: vec![vec![0.0; targets]; buckets.len()],
+            total: vec![0.0; buckets.len()],
+            failed: vec![vec![0.0; targets]; buckets.len()],
+            buckets,
+            tracked: BTreeMap::new(),
+            height: 0,
+        }
+    }
+
+    /// Returns the height of the last processed block.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn bucket(&self, fee_rate: FeeRate) -> usize {
+        let rate = fee_rate.to_sat_per_kwu();
+        self.buckets.iter().rposition(|bound| *bound <= rate).unwrap_or(0)
+    }
+
+    /// Records the transactions of the block at `height`, looking up the outputs they spend from
+    /// outside the block with `spent`.
+    ///
+    /// Transactions whose fee can't be computed are ignored.
+    pub fn process_block<S>(&mut self, height: u32, block: &Block, mut spent: S)
+        where S: FnMut(&OutPoint) -> Option<TxOut>
+    {
+        for bucket in 0..self.buckets.len() {
+            self.total[bucket] *= DECAY;
+            for target in 0..MAX_CONFIRMATION_TARGET as usize {
+                self.confirmed[bucket][target] *= DECAY;
+                self.failed[bucket][target] *= DECAY;
+            }
+        }
+        self.height = height;
+
+        let txids: Vec<Txid> = block.txdata.iter().map(|tx| tx.txid()).collect();
+        for (tx, txid) in block.txdata.iter().zip(txids.iter()).skip(1) {
+            let mut input_value = 0u64;
+            let mut known = true;
+            for input in &tx.input {
+                let prevout = &input.previous_output;
+                let value = match txids.iter().position(|txid| *txid == prevout.txid) {
+                    Some(i) => block.txdata[i].output.get(prevout.vout as usize).map(|out| out.value),
+                    None => spent(prevout).map(|out| out.value),
+                };
+                match value {
+                    Some(value) => input_value = input_value.saturating_add(value),
+                    None => known = false,
+                }
+            }
+            let output_value = tx.output.iter().fold(0u64, |sum, out| sum.saturating_add(out.value));
+            let tracked = self.tracked.remove(txid);
+            if !known || input_value < output_value {
+                continue;
+            }
+            let fee_rate = match FeeRate::from_fee_and_weight(Amount::from_sat(input_value - output_value), tx.weight()) {
+                Some(fee_rate) => fee_rate,
+                None => continue,
+            };
+
+            let bucket = self.bucket(fee_rate);
+            let blocks = tracked.map_or(1, |tracked| height.saturating_sub(tracked.height).max(1));
+            self.total[bucket] += 1.0;
+            for target in (blocks as usize - 1)..MAX_CONFIRMATION_TARGET as usize {
+                self.confirmed[bucket][target] += 1.0;
+            }
+        }
+    }
+
+    /// Records a snapshot of the mempool on top of the last processed block, as pairs of txids
+    /// and fee rates.
+    ///
+    /// Transactions of the previous snapshot which neither confirmed nor are part of this one
+    /// were dropped from the mempool and count as failures to confirm.
+    pub fn process_mempool<I>(&mut self, entries: I)
+        where I: IntoIterator<Item = (Txid, FeeRate)>
+    {
+        let mut tracked = BTreeMap::new();
+        for (txid, fee_rate) in entries {
+            let entry = match self.tracked.remove(&txid) {
+                Some(entry) => entry,
+                None => Tracked { height: self.height, bucket: self.bucket(fee_rate) },
+            };
+            tracked.insert(txid, entry);
+        }
+
+        for dropped in self.tracked.values() {
+            let waited = self.height.saturating_sub(dropped.height) as usize;
+            for target in 0..waited.min(MAX_CONFIRMATION_TARGET as usize) {
+                self.failed[dropped.bucket][target] += 1.0;
+            }
+        }
+        self.tracked = tracked;
+    }
+
+    /// Estimates the lowest fee rate at which transactions confirmed within `target_blocks`
+    /// blocks, `None` if there is not enough data.
+    ///
+    /// Targets are clamped to `1..=MAX_CONFIRMATION_TARGET`.
+    pub fn estimate(&self, target_blocks: u32) -> Option<FeeRate> {
+        let target = target_blocks.max(1).min(MAX_CONFIRMATION_TARGET) as usize - 1;
+
+        // transactions still waiting longer than the target already failed it
+        let mut pending = vec![0.0; self.buckets.len()];
+        for tracked in self.tracked.values() {
+            if self.height.saturating_sub(tracked.height) as usize > target {
+                pending[tracked.bucket] += 1.0;
+            }
+        }
+
+        // Walk down from the highest bucket, grouping buckets until they have enough data. The
+        // estimate is the lowest bucket of the last group meeting the success threshold.
+        let mut estimate = None;
+        let mut confirmed = 0.0;
+        let mut total = 0.0;
+        for bucket in (0..self.buckets.len()).rev() {
+            confirmed += self.confirmed[bucket][target];
+            total += self.total[bucket] + self.failed[bucket][target] + pending[bucket];
+            if total < SUFFICIENT_TXS {
+                continue;
+            }
+            if confirmed / total < SUCCESS_THRESHOLD {
+                break;
+            }
+            estimate = Some(FeeRate::from_sat_per_kwu(self.buckets[bucket]));
+            confirmed = 0.0;
+            total = 0.0;
+        }
+        estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::constants::genesis_block;
+    use blockdata::script::Script;
+    use blockdata::transaction::{Transaction, TxIn};
+    use blockdata::witness::Witness;
+    use network::constants::Network;
+    use util::weight::Weight;
+
+    fn tx(n: u32, fee: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Default::default(), n),
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut { value: 100_000 - fee, script_pubkey: Script::new() }],
+        }
+    }
+
+    fn block(txdata: Vec<Transaction>) -> Block {
+        let mut block = genesis_block(Network::Regtest);
+        block.txdata.extend(txdata);
+        block
+    }
+
+    fn funding(_: &OutPoint) -> Option<TxOut> {
+        Some(TxOut { value: 100_000, script_pubkey: Script::new() })
+    }
+
+    #[test]
+    fn fee_estimator() {
+        let mut estimator = FeeEstimator::new();
+        assert_eq!(estimator.estimate(1), None);
+
+        // transactions paying 10 sat/vB confirm in the next block, those paying 2 sat/vB take 6
+        let fast: Vec<Transaction> = (0..20).map(|n| tx(n, 10 * 60)).collect();
+        let slow: Vec<Transaction> = (20..40).map(|n| tx(n, 2 * 60)).collect();
+        assert_eq!(fast[0].weight(), Weight::from_wu(240));
+        estimator.process_mempool(slow.iter().chain(fast.iter()).map(|tx| {
+            (tx.txid(), FeeRate::from_fee_and_weight(Amount::from_sat(100_000 - tx.output[0].value), Weight::from_wu(240)).unwrap())
+        }));
+        estimator.process_block(1, &block(fast), funding);
+        assert_eq!(estimator.height(), 1);
+        for height in 2..6 {
+            estimator.process_block(height, &block(vec![]), funding);
+        }
+        estimator.process_block(6, &block(slow), funding);
+
+        let fast_rate = estimator.estimate(1).unwrap();
+        assert!(fast_rate <= FeeRate::from_sat_per_vb(10).unwrap());
+        assert!(fast_rate > FeeRate::from_sat_per_vb(9).unwrap());
+        let slow_rate = estimator.estimate(6).unwrap();
+        assert!(slow_rate <= FeeRate::from_sat_per_vb(2).unwrap());
+        assert!(slow_rate > FeeRate::from_sat_per_vb(1).unwrap());
+        assert_eq!(estimator.estimate(0), estimator.estimate(1));
+        assert_eq!(estimator.estimate(100), estimator.estimate(MAX_CONFIRMATION_TARGET));
+
+        // transactions waiting or dropped from the mempool count against their bucket
+        let stuck: Vec<Transaction> = (40..80).map(|n| tx(n, 2 * 60)).collect();
+        estimator.process_mempool(stuck.iter().map(|tx| (tx.txid(), FeeRate::from_sat_per_vb(2).unwrap())));
+        for height in 7..12 {
+            estimator.process_block(height, &block(vec![]), funding);
+        }
+        assert_eq!(estimator.estimate(6), Some(slow_rate));
+        estimator.process_block(12, &block(vec![]), funding);
+        assert_eq!(estimator.estimate(6), Some(fast_rate));
+        estimator.process_mempool(vec![]);
+        assert_eq!(estimator.estimate(6), Some(fast_rate));
+        assert_eq!(estimator.estimate(7), Some(slow_rate));
+    }
+}
@@ -0,0 +1,190 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Anti-fee-sniping.
+//!
+//! Wallets discourage miners from reorganizing the tip to steal its fees by
+//! making their transactions valid only after the tip: Bitcoin Core sets the
+//! lock time to the tip height, and BIP326 proposes the equivalent relative
+//! lock time on taproot spends so they look like contract spends.
+//!
+
+use blockdata::transaction::Transaction;
+use chain::coins::Coin;
+
+/// Maximum age of the tip, in seconds, for a wallet to consider itself synced enough to
+/// discourage fee sniping.
+pub const MAX_TIP_AGE: u32 = 8 * 60 * 60;
+
+/// Sequence number of inputs enabling the lock time without signaling replaceability or a
+/// relative lock time.
+const MAX_SEQUENCE_NONFINAL: u32 = 0xfffffffe;
+
+/// Returns whether a tip mined at `tip_time` is recent enough at `now` to discourage fee
+/// sniping; wallets lagging behind set a lock time of 0 to avoid a unique fingerprint.
+pub fn is_tip_current(tip_time: u32, now: u32) -> bool {
+    tip_time >= now.saturating_sub(MAX_TIP_AGE)
+}
+
+/// Discourages fee sniping on the new transaction `tx`, once all its inputs are added.
+///
+/// If the tip at `tip_height` is current, see [`is_tip_current`], the lock time is set to the
+/// tip height, or one of the 100 previous heights once in 10 times, as Bitcoin Core does.
+/// Otherwise it is set to 0. Final input sequence numbers are lowered so the lock time is
+/// enforced. Transactions with a lock time already set are left unchanged.
+///
+/// If `prevouts` are the coins spent by the inputs, all confirmed taproot outputs, and `tx` is
+/// version 2 or higher, the relative lock time of a random input is used instead half of the
+/// time, as specified by BIP326.
+///
+/// `random` must be uniformly random, e.g. drawn from a thread RNG.
+pub fn discourage_fee_sniping(tx: &mut Transaction, tip_height: u32, tip_is_current: bool, prevouts: Option<&[Coin]>, random: u64) {
+    if tx.lock_time != 0 || tx.input.is_empty() {
+        return;
+    }
+    // split the random value into a coin flip, a 1 in 10 chance, a distance and an input index
+    let use_sequence = random % 2 == 1;
+    let further_back = random / 2 % 10 == 0;
+    let distance = (random / 20 % 100) as u32;
+
+    let confirmations = prevouts.filter(|coins| coins.len() == tx.input.len()).and_then(|coins| {
+        coins.iter().map(|coin| {
+            let confirmations = tip_height.checked_sub(coin.height)? + 1;
+            if coin.txout.script_pubkey.is_v1_p2tr() && confirmations <= 0xffff {
+                Some(confirmations)
+            } else {
+                None
+            }
+        }).collect::<Option<Vec<u32>>>()
+    });
+    if let Some(confirmations) = confirmations {
+        if tip_is_current && tx.version >= 2 && use_sequence {
+            let index = (random / 2000 % tx.input.len() as u64) as usize;
+            let mut sequence = confirmations[index];
+            if further_back {
+                sequence = sequence.saturating_sub(distance).max(1);
+            }
+            tx.input[index].sequence = sequence;
+            return;
+        }
+    }
+
+    if tip_is_current {
+        tx.lock_time = tip_height;
+        if further_back {
+            tx.lock_time = tx.lock_time.saturating_sub(distance);
+        }
+    }
+    for input in &mut tx.input {
+        if input.sequence == 0xffffffff {
+            input.sequence = MAX_SEQUENCE_NONFINAL;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::Hash;
+    use blockdata::script::Script;
+    use blockdata::transaction::{TxIn, TxOut};
+    use util::schnorr::TweakedPublicKey;
+    use XOnlyPublicKey;
+
+    fn tx(inputs: usize) -> Transaction {
+        Transaction { version: 2, lock_time: 0, input: vec![TxIn::default(); inputs], output: vec![] }
+    }
+
+    fn coin(script_pubkey: Script, height: u32) -> Coin {
+        Coin::new(TxOut { value: 10_000, script_pubkey }, height, false)
+    }
+
+    fn random(use_sequence: bool, one_in_ten: u64, distance: u64, index: u64) -> u64 {
+        use_sequence as u64 + 2 * one_in_ten + 20 * distance + 2000 * index
+    }
+
+    #[test]
+    fn tip_age() {
+        assert!(is_tip_current(1_000_000, 1_000_000 + MAX_TIP_AGE));
+        assert!(!is_tip_current(1_000_000, 1_000_001 + MAX_TIP_AGE));
+        assert!(is_tip_current(100, 200));
+    }
+
+    #[test]
+    fn lock_time() {
+        // the relative lock time is only used for taproot inputs
+        let random = random(true, 5, 42, 0);
+        let mut lagging = tx(2);
+        discourage_fee_sniping(&mut lagging, 800_000, false, None, random);
+        assert_eq!(lagging.lock_time, 0);
+        assert!(lagging.input.iter().all(|input| input.sequence == MAX_SEQUENCE_NONFINAL));
+
+        let mut current = tx(2);
+        current.input[1].sequence = 0xfffffffd;
+        discourage_fee_sniping(&mut current, 800_000, true, None, random);
+        assert_eq!(current.lock_time, 800_000);
+        assert_eq!(current.input[0].sequence, MAX_SEQUENCE_NONFINAL);
+        assert_eq!(current.input[1].sequence, 0xfffffffd);
+
+        let mut further_back = tx(1);
+        discourage_fee_sniping(&mut further_back, 800_000, true, None, random - 10);
+        assert_eq!(further_back.lock_time, 800_000 - 42);
+
+        let mut set = tx(1);
+        set.lock_time = 1_700_000_000;
+        discourage_fee_sniping(&mut set, 800_000, true, None, random);
+        assert_eq!(set.lock_time, 1_700_000_000);
+        assert_eq!(set.input[0].sequence, 0xffffffff);
+    }
+
+    #[test]
+    fn relative_lock_time() {
+        let key = XOnlyPublicKey::from_slice(&[
+            0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b, 0x07,
+            0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17, 0x98,
+        ]).unwrap();
+        let p2tr = Script::new_v1_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(key));
+        let coins = [coin(p2tr.clone(), 799_901), coin(p2tr.clone(), 799_000)];
+
+        let mut spend = tx(2);
+        discourage_fee_sniping(&mut spend, 800_000, true, Some(&coins), random(true, 1, 42, 1));
+        assert_eq!(spend.lock_time, 0);
+        assert_eq!(spend.input[0].sequence, 0xffffffff);
+        assert_eq!(spend.input[1].sequence, 1_001);
+
+        let mut further_back = tx(2);
+        discourage_fee_sniping(&mut further_back, 800_000, true, Some(&coins), random(true, 0, 42, 0));
+        assert_eq!(further_back.input[0].sequence, 100 - 42);
+        let mut clamped = tx(2);
+        discourage_fee_sniping(&mut clamped, 800_000, true, Some(&coins), random(true, 0, 99, 0));
+        assert_eq!(clamped.input[0].sequence, 1);
+
+        // coin flips, legacy inputs, old coins and lagging tips fall back to the lock time
+        let mut flip = tx(2);
+        discourage_fee_sniping(&mut flip, 800_000, true, Some(&coins), random(false, 1, 42, 1));
+        assert_eq!(flip.lock_time, 800_000);
+        let legacy = [coin(p2tr.clone(), 799_901), coin(Script::new_p2pkh(&Hash::hash(&[])), 799_901)];
+        let old = [coin(p2tr.clone(), 799_901), coin(p2tr.clone(), 700_000)];
+        for coins in &[&legacy, &old] {
+            let mut spend = tx(2);
+            discourage_fee_sniping(&mut spend, 800_000, true, Some(&coins[..]), random(true, 1, 42, 1));
+            assert_eq!(spend.lock_time, 800_000);
+            assert_eq!(spend.input[1].sequence, MAX_SEQUENCE_NONFINAL);
+        }
+        let mut lagging = tx(2);
+        discourage_fee_sniping(&mut lagging, 800_000, false, Some(&coins), random(true, 1, 42, 1));
+        assert_eq!(lagging.lock_time, 0);
+        assert_eq!(lagging.input[1].sequence, MAX_SEQUENCE_NONFINAL);
+    }
+}
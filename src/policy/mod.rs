@@ -27,6 +27,14 @@
 use super::blockdata::constants::{MAX_BLOCK_SIGOPS_COST, WITNESS_SCALE_FACTOR};
 use core::cmp;
 
+pub mod fee_estimator;
+pub mod fee_sniping;
+pub mod op_return;
+pub mod package;
+
+pub use self::fee_estimator::FeeEstimator;
+pub use self::package::Package;
+
 /// Maximum weight of a transaction for it to be relayed by most nodes on the network
 pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
 
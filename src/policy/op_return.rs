@@ -0,0 +1,173 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! OP_RETURN data outputs.
+//!
+//! This module builds the provably unspendable `OP_RETURN` outputs used to
+//! anchor data in the chain, within the size relayed by default by Bitcoin
+//! Core's `-datacarriersize` policy, and decodes the data they carry.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use blockdata::opcodes;
+use blockdata::script::{self, Builder, Instruction, Script};
+
+/// Maximum size of a standard OP_RETURN script pubkey: the `OP_RETURN` opcode, a push opcode
+/// and 81 more bytes, enough for 80 bytes of data in a single push.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// An OP_RETURN output error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The script pubkey is larger than [`MAX_OP_RETURN_RELAY`].
+    TooLarge(usize),
+    /// The script doesn't start with `OP_RETURN`.
+    NotOpReturn,
+    /// The script has an opcode other than data pushes after `OP_RETURN`.
+    NonPushOpcode(opcodes::All),
+    /// The script can't be parsed.
+    Script(script::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TooLarge(size) => write!(f, "OP_RETURN script of {} bytes exceeds the maximum of {}", size, MAX_OP_RETURN_RELAY),
+            Error::NotOpReturn => f.write_str("script is not an OP_RETURN script"),
+            Error::NonPushOpcode(op) => write!(f, "OP_RETURN script has non-push opcode {:?}", op),
+            Error::Script(ref e) => write!(f, "invalid OP_RETURN script: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {}
+
+/// Creates a standard OP_RETURN script pubkey pushing `data`, at most 80 bytes.
+pub fn new_op_return(data: &[u8]) -> Result<Script, Error> {
+    new_op_return_multi(Some(data))
+}
+
+/// Creates a standard OP_RETURN script pubkey with a push for each of `pushes`, as some
+/// protocols tag their payload with a separate push. Push opcodes count towards the
+/// [`MAX_OP_RETURN_RELAY`] limit.
+pub fn new_op_return_multi<I, T>(pushes: I) -> Result<Script, Error>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut builder = Builder::new().push_opcode(opcodes::all::OP_RETURN);
+    for push in pushes {
+        let push = push.as_ref();
+        // avoid building huge scripts only to reject them
+        if push.len() > MAX_OP_RETURN_RELAY {
+            return Err(Error::TooLarge(builder.len() + push.len()));
+        }
+        builder = builder.push_slice(push);
+    }
+    let script = builder.into_script();
+    if script.len() > MAX_OP_RETURN_RELAY {
+        return Err(Error::TooLarge(script.len()));
+    }
+    Ok(script)
+}
+
+/// Returns whether `script` is an OP_RETURN script pubkey relayed by default: only data
+/// pushes after `OP_RETURN`, within [`MAX_OP_RETURN_RELAY`].
+pub fn is_standard_op_return(script: &Script) -> bool {
+    script.len() <= MAX_OP_RETURN_RELAY && decode_op_return(script).is_ok()
+}
+
+/// Returns the data pushed by the OP_RETURN script pubkey `script`, one item per push.
+///
+/// Small integer opcodes decode to the byte vector they push on the stack, e.g. `OP_1` to
+/// `[0x01]`. The size of the script isn't checked, so non-standard scripts decode too.
+pub fn decode_op_return(script: &Script) -> Result<Vec<Vec<u8>>, Error> {
+    if !script.is_op_return() {
+        return Err(Error::NotOpReturn);
+    }
+    let mut pushes = Vec::new();
+    for instruction in Script::from(script[1..].to_vec()).instructions() {
+        match instruction.map_err(Error::Script)? {
+            Instruction::PushBytes(data) => pushes.push(data.to_vec()),
+            Instruction::Op(op) => match op.classify(opcodes::ClassifyContext::Legacy) {
+                opcodes::Class::PushNum(-1) => pushes.push(vec![0x81]),
+                opcodes::Class::PushNum(n) => pushes.push(vec![n as u8]),
+                _ => return Err(Error::NonPushOpcode(op)),
+            },
+        }
+    }
+    Ok(pushes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::ToHex;
+
+    #[test]
+    fn build() {
+        let script = new_op_return(b"texitcoin").unwrap();
+        assert_eq!(script.to_hex(), "6a097465786974636f696e");
+        assert_eq!(script, Script::new_op_return(b"texitcoin"));
+        assert_eq!(new_op_return(&[]).unwrap().to_hex(), "6a00");
+
+        let largest = new_op_return(&[0xab; 80]).unwrap();
+        assert_eq!(largest.len(), MAX_OP_RETURN_RELAY);
+        assert_eq!(largest[..3], [0x6a, 0x4c, 80]);
+        assert_eq!(new_op_return(&[0xab; 81]), Err(Error::TooLarge(84)));
+        assert_eq!(new_op_return(&[0xab; 10_000]), Err(Error::TooLarge(10_001)));
+
+        let multi = new_op_return_multi(&[&b"tag"[..], &[0xcd; 32]]).unwrap();
+        assert_eq!(multi.len(), 1 + 4 + 33);
+        assert_eq!(multi[..5].to_hex(), "6a03746167");
+        assert_eq!(new_op_return_multi(vec![vec![0; 40], vec![0; 41]]), Err(Error::TooLarge(84)));
+        assert_eq!(new_op_return_multi(Vec::<Vec<u8>>::new()).unwrap().to_hex(), "6a");
+    }
+
+    #[test]
+    fn decode() {
+        let multi = new_op_return_multi(&[&b"tag"[..], &[], &[0xcd; 75]]).unwrap();
+        assert_eq!(decode_op_return(&multi).unwrap(), vec![b"tag".to_vec(), vec![], vec![0xcd; 75]]);
+        assert!(is_standard_op_return(&multi));
+
+        let numbers = Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_int(-1)
+            .push_int(16)
+            .into_script();
+        assert_eq!(decode_op_return(&numbers).unwrap(), vec![vec![0x81], vec![16]]);
+
+        let large = Builder::new().push_opcode(opcodes::all::OP_RETURN).push_slice(&[1; 200]).into_script();
+        assert_eq!(decode_op_return(&large).unwrap(), vec![vec![1; 200]]);
+        assert!(!is_standard_op_return(&large));
+
+        let non_push = Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(b"tag")
+            .push_opcode(opcodes::all::OP_CHECKSIG)
+            .into_script();
+        assert_eq!(decode_op_return(&non_push), Err(Error::NonPushOpcode(opcodes::all::OP_CHECKSIG)));
+        assert!(!is_standard_op_return(&non_push));
+
+        let truncated = Script::from(vec![0x6a, 0x05, 0x01]);
+        assert_eq!(decode_op_return(&truncated), Err(Error::Script(script::Error::EarlyEndOfScript)));
+        assert_eq!(decode_op_return(&Script::new()), Err(Error::NotOpReturn));
+        assert_eq!(decode_op_return(&Script::from(vec![0x51, 0x6a])), Err(Error::NotOpReturn));
+    }
+}
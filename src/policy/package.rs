@@ -0,0 +1,259 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Transaction packages.
+//!
+//! This module implements packages of related unconfirmed transactions,
+//! which are relayed and evaluated together so that a child can pay for its
+//! parents (CPFP). The rules mirror Bitcoin Core's `packages.cpp`.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hash_types::Txid;
+use blockdata::transaction::{OutPoint, Transaction, TxOut};
+use util::amount::Amount;
+use util::weight::Weight;
+
+/// The maximum number of transactions in a package.
+pub const MAX_PACKAGE_COUNT: usize = 25;
+
+/// The maximum total weight of the transactions in a package.
+pub const MAX_PACKAGE_WEIGHT: usize = 404_000;
+
+/// A package policy error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The package has no transactions.
+    Empty,
+    /// The package has more than [`MAX_PACKAGE_COUNT`] transactions.
+    TooManyTransactions(usize),
+    /// The package weighs more than [`MAX_PACKAGE_WEIGHT`].
+    TooLarge(Weight),
+    /// Two transactions of the package spend the same output.
+    ConflictingInputs(OutPoint),
+    /// The transaction is not a parent of the last transaction of a child-with-parents package.
+    NotParentOfChild(Txid),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Empty => f.write_str("package is empty"),
+            Error::TooManyTransactions(n) => write!(f, "package has {} transactions, more than the maximum of {}", n, MAX_PACKAGE_COUNT),
+            Error::TooLarge(weight) => write!(f, "package weight {} exceeds the maximum of {} wu", weight, MAX_PACKAGE_WEIGHT),
+            Error::ConflictingInputs(ref outpoint) => write!(f, "package transactions conflict on {}", outpoint),
+            Error::NotParentOfChild(ref txid) => write!(f, "package transaction {} is not a parent of the child", txid),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {}
+
+/// A well-formed package: deduplicated, free of conflicts and topologically sorted, so every
+/// transaction comes after the package transactions it spends.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Package {
+    txs: Vec<Transaction>,
+}
+
+impl Package {
+    /// Creates a package from `txs`, dropping duplicate transactions and sorting the rest
+    /// topologically while otherwise preserving their order.
+    pub fn new(txs: Vec<Transaction>) -> Result<Package, Error> {
+        let mut seen = BTreeSet::new();
+        let txs: Vec<Transaction> = txs.into_iter().filter(|tx| seen.insert(tx.txid())).collect();
+
+        if txs.is_empty() {
+            return Err(Error::Empty);
+        }
+        if txs.len() > MAX_PACKAGE_COUNT {
+            return Err(Error::TooManyTransactions(txs.len()));
+        }
+        let weight: Weight = txs.iter().map(Transaction::weight).sum();
+        if weight.to_wu() > MAX_PACKAGE_WEIGHT as u64 {
+            return Err(Error::TooLarge(weight));
+        }
+
+        let mut spent = BTreeSet::new();
+        for input in txs.iter().flat_map(|tx| tx.input.iter()) {
+            if !spent.insert(input.previous_output) {
+                return Err(Error::ConflictingInputs(input.previous_output));
+            }
+        }
+
+        Ok(Package { txs: topological_sort(txs) })
+    }
+
+    /// Creates a child-with-parents package, in which the last transaction after sorting spends
+    /// an output of each other transaction.
+    pub fn child_with_parents(txs: Vec<Transaction>) -> Result<Package, Error> {
+        let package = Package::new(txs)?;
+        if let Some(txid) = package.parents().iter().map(Transaction::txid).find(|txid| !package.child_spends(txid)) {
+            return Err(Error::NotParentOfChild(txid));
+        }
+        Ok(package)
+    }
+
+    /// Returns whether the last transaction spends an output of each other transaction.
+    pub fn is_child_with_parents(&self) -> bool {
+        self.parents().iter().all(|parent| self.child_spends(&parent.txid()))
+    }
+
+    /// Returns the transactions in topological order.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.txs
+    }
+
+    /// Returns the transactions in topological order.
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        self.txs
+    }
+
+    /// Returns the last transaction, the child of a child-with-parents package.
+    pub fn child(&self) -> &Transaction {
+        self.txs.last().expect("packages are not empty")
+    }
+
+    /// Returns all transactions but the last.
+    pub fn parents(&self) -> &[Transaction] {
+        &self.txs[..self.txs.len() - 1]
+    }
+
+    /// Returns the combined weight of the transactions.
+    pub fn weight(&self) -> Weight {
+        self.txs.iter().map(Transaction::weight).sum()
+    }
+
+    /// Computes the combined fee of the transactions, looking up outputs spent from outside the
+    /// package with `spent`.
+    ///
+    /// Returns `None` if an output is unknown or the package creates more value than it spends.
+    pub fn fee<S>(&self, mut spent: S) -> Option<Amount>
+        where S: FnMut(&OutPoint) -> Option<TxOut>
+    {
+        let txids: BTreeSet<Txid> = self.txs.iter().map(Transaction::txid).collect();
+        let mut input_value = Amount::ZERO;
+        let mut output_value = Amount::ZERO;
+        for tx in &self.txs {
+            for input in &tx.input {
+                // outputs created and spent within the package cancel out
+                if !txids.contains(&input.previous_output.txid) {
+                    let value = spent(&input.previous_output)?.value;
+                    input_value = input_value.checked_add(Amount::from_sat(value))?;
+                }
+            }
+            for (vout, output) in tx.output.iter().enumerate() {
+                let outpoint = OutPoint::new(tx.txid(), vout as u32);
+                if !self.txs.iter().any(|tx| tx.input.iter().any(|input| input.previous_output == outpoint)) {
+                    output_value = output_value.checked_add(Amount::from_sat(output.value))?;
+                }
+            }
+        }
+        input_value.checked_sub(output_value)
+    }
+
+    fn child_spends(&self, txid: &Txid) -> bool {
+        self.child().input.iter().any(|input| input.previous_output.txid == *txid)
+    }
+}
+
+/// Sorts `txs` so every transaction comes after the transactions of `txs` it spends, keeping
+/// the relative order of otherwise independent transactions.
+pub fn topological_sort(txs: Vec<Transaction>) -> Vec<Transaction> {
+    let txids: Vec<Txid> = txs.iter().map(Transaction::txid).collect();
+    let parents: Vec<Vec<usize>> = txs.iter().map(|tx| {
+        tx.input.iter()
+            .filter_map(|input| txids.iter().position(|txid| *txid == input.previous_output.txid))
+            .collect()
+    }).collect();
+
+    let mut order = Vec::with_capacity(txs.len());
+    let mut placed = vec![false; txs.len()];
+    while order.len() < txs.len() {
+        let ready = (0..txs.len())
+            .find(|&i| !placed[i] && parents[i].iter().all(|&p| placed[p]));
+        // txids commit to the spent outpoints so transactions can't spend each other in a cycle
+        let i = ready.expect("transactions form no cycles");
+        placed[i] = true;
+        order.push(i);
+    }
+
+    let mut txs: Vec<Option<Transaction>> = txs.into_iter().map(Some).collect();
+    order.into_iter().map(|i| txs[i].take().expect("each index is placed once")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::script::Script;
+    use blockdata::transaction::TxIn;
+    use blockdata::witness::Witness;
+
+    fn tx(inputs: &[OutPoint], values: &[u64]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: inputs.iter().map(|outpoint| TxIn {
+                previous_output: *outpoint,
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: Witness::default(),
+            }).collect(),
+            output: values.iter().map(|value| TxOut { value: *value, script_pubkey: Script::new() }).collect(),
+        }
+    }
+
+    #[test]
+    fn package() {
+        let funding = tx(&[], &[50_000, 30_000]);
+        let parent_a = tx(&[OutPoint::new(funding.txid(), 0)], &[20_000, 29_000]);
+        let parent_b = tx(&[OutPoint::new(funding.txid(), 1)], &[29_500]);
+        let child = tx(&[OutPoint::new(parent_a.txid(), 1), OutPoint::new(parent_b.txid(), 0)], &[57_000]);
+
+        let package = Package::child_with_parents(vec![
+            child.clone(), parent_b.clone(), parent_a.clone(), parent_b.clone(),
+        ]).unwrap();
+        assert_eq!(package.transactions(), &[parent_b.clone(), parent_a.clone(), child.clone()]);
+        assert_eq!(package.child(), &child);
+        assert!(package.is_child_with_parents());
+        assert_eq!(package.weight(), parent_a.weight() + parent_b.weight() + child.weight());
+
+        let lookup = |outpoint: &OutPoint| {
+            if outpoint.txid == funding.txid() { funding.output.get(outpoint.vout as usize).cloned() } else { None }
+        };
+        assert_eq!(package.fee(lookup), Some(Amount::from_sat(80_000 - 20_000 - 57_000)));
+        assert_eq!(package.fee(|_| None), None);
+
+        let unrelated = tx(&[OutPoint::new(funding.txid(), 2)], &[1]);
+        assert_eq!(
+            Package::child_with_parents(vec![parent_a.clone(), unrelated.clone(), child.clone()]),
+            Err(Error::NotParentOfChild(unrelated.txid()))
+        );
+        assert!(!Package::new(vec![child.clone(), unrelated]).unwrap().is_child_with_parents());
+
+        let conflict = tx(&[OutPoint::new(funding.txid(), 0)], &[10_000]);
+        assert_eq!(
+            Package::new(vec![parent_a, conflict]),
+            Err(Error::ConflictingInputs(OutPoint::new(funding.txid(), 0)))
+        );
+        assert_eq!(Package::new(vec![]), Err(Error::Empty));
+        let many = (0..26).map(|i| tx(&[OutPoint::new(funding.txid(), i)], &[1])).collect();
+        assert_eq!(Package::new(many), Err(Error::TooManyTransactions(26)));
+    }
+}
@@ -0,0 +1,730 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! RPC results.
+//!
+//! Types mirroring the results of the most common node RPCs, with hex fields
+//! decoded into this crate's types: hashes, scripts, transactions, amounts
+//! and targets. Fields the node may add in newer versions are ignored when
+//! deserializing.
+//!
+
+use prelude::*;
+
+use hashes::hex::ToHex;
+use hash_types::{BlockHash, TxMerkleNode, Txid, Wtxid};
+use blockdata::block::Version;
+use blockdata::opcodes::all;
+use blockdata::script::{self, Instruction, Script};
+use blockdata::transaction::Transaction;
+use blockdata::witness::Witness;
+use chain::coins::Coin;
+use network::constants::Network;
+use util::address::Address;
+use util::amount::Amount;
+use util::ecdsa::EcdsaSig;
+use util::pow::CompactTarget;
+use util::uint::Uint256;
+
+/// Result of the `getblockchaininfo` RPC.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GetBlockchainInfoResult {
+    /// The chain the node follows.
+    #[serde(with = "self::chain_name")]
+    pub chain: Network,
+    /// The height of the best validated block.
+    pub blocks: u32,
+    /// The height of the best known header.
+    pub headers: u32,
+    /// The hash of the best validated block.
+    #[serde(rename = "bestblockhash")]
+    pub best_block_hash: BlockHash,
+    /// The difficulty of the best validated block.
+    pub difficulty: f64,
+    /// The median time of the best validated block.
+    #[serde(rename = "mediantime")]
+    pub median_time: u32,
+    /// The estimated fraction of the chain validated, between 0 and 1.
+    #[serde(rename = "verificationprogress")]
+    pub verification_progress: f64,
+    /// Whether the node is still downloading the chain.
+    #[serde(rename = "initialblockdownload")]
+    pub initial_block_download: bool,
+    /// The total work of the best validated chain.
+    #[serde(rename = "chainwork")]
+    pub chain_work: Uint256,
+    /// The size of the block and undo files on disk.
+    pub size_on_disk: u64,
+    /// Whether old blocks are pruned.
+    pub pruned: bool,
+    /// The height of the first block still stored, if pruned.
+    #[serde(rename = "pruneheight", default, skip_serializing_if = "Option::is_none")]
+    pub prune_height: Option<u32>,
+    /// Whether blocks are pruned automatically, if pruned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automatic_pruning: Option<bool>,
+    /// The target size of the block and undo files, if pruned automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_target_size: Option<u64>,
+}
+
+/// Result of the `getblockheader` RPC with `verbose` set.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GetBlockHeaderResult {
+    /// The block hash.
+    pub hash: BlockHash,
+    /// The number of confirmations of the block, -1 if it isn't in the best chain.
+    pub confirmations: i32,
+    /// The height of the block.
+    pub height: u32,
+    /// The block version.
+    pub version: Version,
+    /// The merkle root of the block transactions.
+    #[serde(rename = "merkleroot")]
+    pub merkle_root: TxMerkleNode,
+    /// The block timestamp.
+    pub time: u32,
+    /// The median time of the block.
+    #[serde(rename = "mediantime")]
+    pub median_time: u32,
+    /// The block nonce.
+    pub nonce: u32,
+    /// The compact proof-of-work target of the block.
+    #[serde(with = "::mining::template::serde_hex::bits")]
+    pub bits: CompactTarget,
+    /// The difficulty of the block.
+    pub difficulty: f64,
+    /// The total work of the chain up to the block.
+    #[serde(rename = "chainwork")]
+    pub chain_work: Uint256,
+    /// The number of transactions in the block.
+    #[serde(rename = "nTx")]
+    pub n_tx: u32,
+    /// The hash of the previous block, absent for the genesis block.
+    #[serde(rename = "previousblockhash", default, skip_serializing_if = "Option::is_none")]
+    pub previous_block_hash: Option<BlockHash>,
+    /// The hash of the next block in the best chain, if any.
+    #[serde(rename = "nextblockhash", default, skip_serializing_if = "Option::is_none")]
+    pub next_block_hash: Option<BlockHash>,
+}
+
+/// Result of the `getrawtransaction` RPC with `verbose` set.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResult {
+    /// Whether the transaction is in the best chain, only set when a block hash was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_active_chain: Option<bool>,
+    /// The transaction itself.
+    #[serde(rename = "hex", with = "::consensus::serde::With::<::consensus::serde::Hex>")]
+    pub transaction: Transaction,
+    /// The transaction id.
+    pub txid: Txid,
+    /// The witness transaction id.
+    #[serde(rename = "hash")]
+    pub wtxid: Wtxid,
+    /// The serialized size of the transaction.
+    pub size: usize,
+    /// The virtual size of the transaction.
+    pub vsize: usize,
+    /// The weight of the transaction.
+    pub weight: usize,
+    /// The transaction version.
+    pub version: i32,
+    /// The transaction lock time.
+    #[serde(rename = "locktime")]
+    pub lock_time: u32,
+    /// The transaction inputs.
+    pub vin: Vec<GetRawTransactionResultVin>,
+    /// The transaction outputs.
+    pub vout: Vec<GetRawTransactionResultVout>,
+    /// The fee paid by the transaction, only returned with `verbosity` 2 when the spent coins
+    /// are known.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "::util::amount::serde::as_btc::opt")]
+    pub fee: Option<Amount>,
+    /// The hash of the block containing the transaction, if confirmed.
+    #[serde(rename = "blockhash", default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    /// The number of confirmations of the transaction, if confirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<u32>,
+    /// The timestamp of the block containing the transaction, if confirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<u64>,
+    /// The timestamp of the block containing the transaction, if confirmed.
+    #[serde(rename = "blocktime", default, skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<u64>,
+}
+
+/// An input of a [`GetRawTransactionResult`].
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResultVin {
+    /// The script sig of a coinbase input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Script>,
+    /// The txid of the spent output, absent for a coinbase input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+    /// The index of the spent output, absent for a coinbase input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    /// The script sig, absent for a coinbase input.
+    #[serde(rename = "scriptSig", default, skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<GetRawTransactionResultScript>,
+    /// The witness of the input.
+    #[serde(rename = "txinwitness", default, skip_serializing_if = "Witness::is_empty", with = "self::witness_hex")]
+    pub witness: Witness,
+    /// The coin spent by the input, only returned with `verbosity` 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prevout: Option<GetRawTransactionResultVinPrevout>,
+    /// The sequence number of the input.
+    pub sequence: u32,
+}
+
+/// The coin spent by a [`GetRawTransactionResultVin`].
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResultVinPrevout {
+    /// Whether the coin was created by a coinbase transaction.
+    pub generated: bool,
+    /// The height of the block creating the coin.
+    pub height: u32,
+    /// The value of the coin.
+    #[serde(with = "::util::amount::serde::as_btc")]
+    pub value: Amount,
+    /// The script pubkey of the coin.
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: GetRawTransactionResultScriptPubKey,
+}
+
+/// An output of a [`GetRawTransactionResult`].
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResultVout {
+    /// The value of the output.
+    #[serde(with = "::util::amount::serde::as_btc")]
+    pub value: Amount,
+    /// The index of the output in the transaction.
+    pub n: u32,
+    /// The script pubkey of the output.
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: GetRawTransactionResultScriptPubKey,
+}
+
+/// A script sig as returned by the RPCs.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResultScript {
+    /// The disassembled script.
+    pub asm: String,
+    /// The script itself.
+    #[serde(rename = "hex")]
+    pub script: Script,
+}
+
+/// A script pubkey as returned by the RPCs.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetRawTransactionResultScriptPubKey {
+    /// The disassembled script.
+    pub asm: String,
+    /// The script itself.
+    #[serde(rename = "hex")]
+    pub script: Script,
+    /// The address paid by the script, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+    /// The type of the script, e.g. `witness_v0_keyhash`.
+    #[serde(rename = "type")]
+    pub script_type: String,
+}
+
+impl GetRawTransactionResultScriptPubKey {
+    /// Describes `script` the way the RPCs do, with addresses for `network`.
+    pub fn new(script: &Script, network: Network) -> GetRawTransactionResultScriptPubKey {
+        GetRawTransactionResultScriptPubKey {
+            asm: core_asm(script, false),
+            script: script.clone(),
+            address: Address::from_script(script, network),
+            script_type: core_script_type(script).to_owned(),
+        }
+    }
+}
+
+/// Result of the `gettxout` RPC, for an unspent output.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GetTxOutResult {
+    /// The hash of the best block.
+    #[serde(rename = "bestblock")]
+    pub best_block: BlockHash,
+    /// The number of confirmations of the output, 0 if unconfirmed.
+    pub confirmations: u32,
+    /// The value of the output.
+    #[serde(with = "::util::amount::serde::as_btc")]
+    pub value: Amount,
+    /// The script pubkey of the output.
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: GetRawTransactionResultScriptPubKey,
+    /// Whether the output belongs to a coinbase transaction.
+    pub coinbase: bool,
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl Transaction {
+    /// Describes the transaction the way the `decoderawtransaction` and `getrawtransaction`
+    /// RPCs do, with addresses for `network`.
+    ///
+    /// The spent coins and the fee, returned by `getrawtransaction` with `verbosity` 2, are
+    /// only included if `prevouts` holds one coin per input of a non-coinbase transaction.
+    /// The fields describing the block containing the transaction are left empty.
+    pub fn to_core_json(&self, network: Network, prevouts: Option<&[Coin]>) -> GetRawTransactionResult {
+        let prevouts = prevouts.filter(|coins| !self.is_coinbase() && coins.len() == self.input.len());
+        let vin = self.input.iter().enumerate().map(|(i, input)| {
+            let prevout = prevouts.map(|coins| GetRawTransactionResultVinPrevout {
+                generated: coins[i].is_coinbase,
+                height: coins[i].height,
+                value: Amount::from_sat(coins[i].txout.value),
+                script_pubkey: GetRawTransactionResultScriptPubKey::new(&coins[i].txout.script_pubkey, network),
+            });
+            if self.is_coinbase() {
+                GetRawTransactionResultVin {
+                    coinbase: Some(input.script_sig.clone()),
+                    txid: None,
+                    vout: None,
+                    script_sig: None,
+                    witness: input.witness.clone(),
+                    prevout,
+                    sequence: input.sequence,
+                }
+            } else {
+                GetRawTransactionResultVin {
+                    coinbase: None,
+                    txid: Some(input.previous_output.txid),
+                    vout: Some(input.previous_output.vout),
+                    script_sig: Some(GetRawTransactionResultScript {
+                        asm: core_asm(&input.script_sig, true),
+                        script: input.script_sig.clone(),
+                    }),
+                    witness: input.witness.clone(),
+                    prevout,
+                    sequence: input.sequence,
+                }
+            }
+        }).collect();
+        let vout = self.output.iter().enumerate().map(|(n, output)| GetRawTransactionResultVout {
+            value: Amount::from_sat(output.value),
+            n: n as u32,
+            script_pubkey: GetRawTransactionResultScriptPubKey::new(&output.script_pubkey, network),
+        }).collect();
+        // omitted if the values overflow, like a negative fee
+        let fee = prevouts.and_then(|coins| {
+            let input = coins.iter().try_fold(0u64, |sum, coin| sum.checked_add(coin.txout.value))?;
+            let output = self.output.iter().try_fold(0u64, |sum, output| sum.checked_add(output.value))?;
+            input.checked_sub(output).map(Amount::from_sat)
+        });
+
+        GetRawTransactionResult {
+            in_active_chain: None,
+            transaction: self.clone(),
+            txid: self.txid(),
+            wtxid: self.wtxid(),
+            size: self.size(),
+            vsize: self.vsize(),
+            weight: self.weight().to_wu() as usize,
+            version: self.version,
+            lock_time: self.lock_time,
+            vin,
+            vout,
+            fee,
+            block_hash: None,
+            confirmations: None,
+            time: None,
+            block_time: None,
+        }
+    }
+}
+
+/// Disassembles `script` the way Bitcoin Core does: small pushes as numbers, other pushes in
+/// hex, and, in script sigs, signatures with their sighash type spelt out.
+fn core_asm(script: &Script, decode_sighash: bool) -> String {
+    let mut asm = String::new();
+    for instruction in script.instructions() {
+        if !asm.is_empty() {
+            asm.push(' ');
+        }
+        match instruction {
+            Ok(Instruction::PushBytes(bytes)) if bytes.len() <= 4 => {
+                asm.push_str(&script::read_scriptint(bytes).expect("at most 4 bytes").to_string());
+            }
+            Ok(Instruction::PushBytes(bytes)) => {
+                match EcdsaSig::from_slice(bytes) {
+                    Ok(sig) if decode_sighash => {
+                        asm.push_str(&bytes[..bytes.len() - 1].to_hex());
+                        asm.push_str(&format!("[{}]", sig.hash_ty).replace("SIGHASH_", ""));
+                    }
+                    _ => asm.push_str(&bytes.to_hex()),
+                }
+            }
+            Ok(Instruction::Op(op)) => {
+                let code = op.into_u8();
+                let name = match op {
+                    all::OP_PUSHNUM_NEG1 => "-1".to_owned(),
+                    _ if code >= all::OP_PUSHNUM_1.into_u8() && code <= all::OP_PUSHNUM_16.into_u8() => {
+                        (code - all::OP_PUSHNUM_1.into_u8() + 1).to_string()
+                    }
+                    all::OP_CLTV => "OP_CHECKLOCKTIMEVERIFY".to_owned(),
+                    all::OP_CSV => "OP_CHECKSEQUENCEVERIFY".to_owned(),
+                    _ if code > all::OP_CHECKSIGADD.into_u8() => "OP_UNKNOWN".to_owned(),
+                    _ => format!("{:?}", op),
+                };
+                asm.push_str(&name);
+            }
+            Err(_) => {
+                asm.push_str("[error]");
+                break;
+            }
+        }
+    }
+    asm
+}
+
+/// Returns the name Bitcoin Core gives to the type of `script`.
+fn core_script_type(script: &Script) -> &'static str {
+    if script.is_p2pk() {
+        "pubkey"
+    } else if script.is_p2pkh() {
+        "pubkeyhash"
+    } else if script.is_p2sh() {
+        "scripthash"
+    } else if is_multisig(script) {
+        "multisig"
+    } else if script.is_op_return() {
+        "nulldata"
+    } else if script.is_v0_p2wpkh() {
+        "witness_v0_keyhash"
+    } else if script.is_v0_p2wsh() {
+        "witness_v0_scripthash"
+    } else if script.is_v1_p2tr() {
+        "witness_v1_taproot"
+    } else if script.is_witness_program() {
+        "witness_unknown"
+    } else {
+        "nonstandard"
+    }
+}
+
+/// Returns whether `script` is a bare `m`-of-`n` multisig script.
+fn is_multisig(script: &Script) -> bool {
+    let small_int = |instruction: Option<Result<Instruction, script::Error>>| match instruction {
+        Some(Ok(Instruction::Op(op))) if op.into_u8() >= all::OP_PUSHNUM_1.into_u8() && op.into_u8() <= all::OP_PUSHNUM_16.into_u8() => {
+            Some(op.into_u8() - all::OP_PUSHNUM_1.into_u8() + 1)
+        }
+        _ => None,
+    };
+    let instructions = script.instructions().collect::<Vec<_>>();
+    if instructions.len() < 4 || instructions.last() != Some(&Ok(Instruction::Op(all::OP_CHECKMULTISIG))) {
+        return false;
+    }
+    let keys = &instructions[1..instructions.len() - 2];
+    let keys_valid = keys.iter().all(|key| match *key {
+        Ok(Instruction::PushBytes(key)) => key.len() == 33 || key.len() == 65,
+        _ => false,
+    });
+    match (small_int(instructions.first().cloned()), small_int(instructions.get(instructions.len() - 2).cloned())) {
+        (Some(m), Some(n)) => keys_valid && m <= n && n as usize == keys.len(),
+        _ => false,
+    }
+}
+
+mod chain_name {
+    //! The RPCs name the chains differently from [`Network`].
+
+    use prelude::*;
+    use serde::{self, Deserialize};
+    use network::constants::Network;
+
+    pub fn serialize<S: serde::Serializer>(network: &Network, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(match *network {
+            Network::Bitcoin => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        })
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Network, D::Error> {
+        let chain = String::deserialize(d)?;
+        match chain.as_str() {
+            "main" => Ok(Network::Bitcoin),
+            "test" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(serde::de::Error::unknown_variant(&chain, &["main", "test", "signet", "regtest"])),
+        }
+    }
+}
+
+mod witness_hex {
+    //! The RPCs encode witnesses as lists of hex strings.
+
+    use prelude::*;
+    use hashes::hex::{FromHex, ToHex};
+    use serde::{self, Deserialize, Serialize};
+    use blockdata::witness::Witness;
+
+    pub fn serialize<S: serde::Serializer>(witness: &Witness, s: S) -> Result<S::Ok, S::Error> {
+        witness.iter().map(|item| item.to_hex()).collect::<Vec<_>>().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Witness, D::Error> {
+        let items = Vec::<String>::deserialize(d)?;
+        let items = items.iter()
+            .map(|item| Vec::from_hex(item))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(Witness::from_vec(items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use blockdata::constants::genesis_block;
+    use consensus::encode::serialize_hex;
+    use hashes::Hash;
+    use secp256k1::{Message, Secp256k1};
+    use secp256k1::ecdsa::Signature;
+    use blockdata::script::Builder;
+    use blockdata::transaction::{OutPoint, TxIn, TxOut};
+    use util::key::PrivateKey;
+
+    #[test]
+    fn get_blockchain_info() {
+        let json = r#"{
+            "chain": "regtest",
+            "blocks": 101,
+            "headers": 101,
+            "bestblockhash": "6fcb5e9d84ee0a12ea75fae6ee8ff0bd2dd2e3a5fcfa1a1b6bd0883eb6e4aa3c",
+            "difficulty": 4.656542373906925e-10,
+            "time": 1700000000,
+            "mediantime": 1699999000,
+            "verificationprogress": 1,
+            "initialblockdownload": false,
+            "chainwork": "00000000000000000000000000000000000000000000000000000000000000cc",
+            "size_on_disk": 30654,
+            "pruned": false,
+            "warnings": ""
+        }"#;
+        let info: GetBlockchainInfoResult = serde_json::from_str(json).unwrap();
+        assert_eq!(info.chain, Network::Regtest);
+        assert_eq!(info.blocks, 101);
+        assert_eq!(info.chain_work, Uint256::from_u64(0xcc).unwrap());
+        assert_eq!(info.prune_height, None);
+        assert!(!info.initial_block_download);
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(json.starts_with(r#"{"chain":"regtest","#));
+        assert_eq!(serde_json::from_str::<GetBlockchainInfoResult>(&json).unwrap(), info);
+
+        let json = r#"{"chain":"moon"}"#;
+        assert!(serde_json::from_str::<GetBlockchainInfoResult>(json).is_err());
+    }
+
+    #[test]
+    fn get_block_header() {
+        let header = genesis_block(Network::Bitcoin).header;
+        let json = format!(r#"{{
+            "hash": "{}",
+            "confirmations": 1,
+            "height": 0,
+            "version": 1,
+            "versionHex": "00000001",
+            "merkleroot": "{}",
+            "time": {},
+            "mediantime": {},
+            "nonce": {},
+            "bits": "{:08x}",
+            "difficulty": 1,
+            "chainwork": "0000000000000000000000000000000000000000000000000000000100010001",
+            "nTx": 1
+        }}"#, header.block_hash(), header.merkle_root, header.time, header.time, header.nonce, header.bits);
+        let result: GetBlockHeaderResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(result.hash, header.block_hash());
+        assert_eq!(result.version, Version::ONE);
+        assert_eq!(result.merkle_root, header.merkle_root);
+        assert_eq!(result.bits.to_consensus(), header.bits);
+        assert_eq!(result.previous_block_hash, None);
+        assert_eq!(serde_json::from_str::<GetBlockHeaderResult>(&serde_json::to_string(&result).unwrap()).unwrap(), result);
+    }
+
+    #[test]
+    fn get_raw_transaction() {
+        let block = genesis_block(Network::Bitcoin);
+        let tx = &block.txdata[0];
+        let json = format!(r#"{{
+            "txid": "{txid}",
+            "hash": "{txid}",
+            "version": 1,
+            "size": 204,
+            "vsize": 204,
+            "weight": 816,
+            "locktime": 0,
+            "vin": [{{
+                "coinbase": "{coinbase}",
+                "sequence": 4294967295
+            }}],
+            "vout": [{{
+                "value": 50.00000000,
+                "n": 0,
+                "scriptPubKey": {{
+                    "asm": "",
+                    "hex": "{script_pubkey}",
+                    "type": "pubkey"
+                }}
+            }}],
+            "hex": "{hex}",
+            "blockhash": "{block_hash}",
+            "confirmations": 10,
+            "time": 1231006505,
+            "blocktime": 1231006505
+        }}"#,
+            txid = tx.txid(),
+            coinbase = tx.input[0].script_sig.to_hex(),
+            script_pubkey = tx.output[0].script_pubkey.to_hex(),
+            hex = serialize_hex(tx),
+            block_hash = block.block_hash(),
+        );
+        let result: GetRawTransactionResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(&result.transaction, tx);
+        assert_eq!(result.txid, tx.txid());
+        assert_eq!(result.vin[0].coinbase.as_ref(), Some(&tx.input[0].script_sig));
+        assert_eq!(result.vin[0].txid, None);
+        assert!(result.vin[0].witness.is_empty());
+        assert_eq!(result.vout[0].value, Amount::from_sat(tx.output[0].value));
+        assert_eq!(result.vout[0].script_pubkey.script, tx.output[0].script_pubkey);
+        assert_eq!(result.block_hash, Some(block.block_hash()));
+        assert_eq!(serde_json::from_str::<GetRawTransactionResult>(&serde_json::to_string(&result).unwrap()).unwrap(), result);
+
+        let json = r#"{
+            "txid": "9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec",
+            "vout": 1,
+            "scriptSig": {"asm": "", "hex": ""},
+            "txinwitness": ["3044", "02aa"],
+            "sequence": 4294967293
+        }"#;
+        let vin: GetRawTransactionResultVin = serde_json::from_str(json).unwrap();
+        assert_eq!(vin.vout, Some(1));
+        assert_eq!(vin.witness.to_vec(), vec![vec![0x30, 0x44], vec![0x02, 0xaa]]);
+        let json = serde_json::to_string(&vin).unwrap();
+        assert!(json.contains(r#""txinwitness":["3044","02aa"]"#));
+    }
+
+    #[test]
+    fn get_tx_out() {
+        let script: Script = "0014751e76e8199196d454941c45d1b3a323f1433bd6".parse().unwrap();
+        let address = Address::from_script(&script, Network::Bitcoin).unwrap();
+        let json = format!(r#"{{
+            "bestblock": "6fcb5e9d84ee0a12ea75fae6ee8ff0bd2dd2e3a5fcfa1a1b6bd0883eb6e4aa3c",
+            "confirmations": 3,
+            "value": 0.00120000,
+            "scriptPubKey": {{
+                "asm": "0 751e76e8199196d454941c45d1b3a323f1433bd6",
+                "hex": "{}",
+                "address": "{}",
+                "type": "witness_v0_keyhash"
+            }},
+            "coinbase": false
+        }}"#, script.to_hex(), address);
+        let result: GetTxOutResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(result.value, Amount::from_sat(120_000));
+        assert_eq!(result.script_pubkey.script, script);
+        assert_eq!(result.script_pubkey.address, Some(address));
+        assert_eq!(result.script_pubkey.script_type, "witness_v0_keyhash");
+        assert_eq!(serde_json::from_str::<GetTxOutResult>(&serde_json::to_string(&result).unwrap()).unwrap(), result);
+    }
+
+    #[test]
+    fn to_core_json() {
+        let secp = Secp256k1::new();
+        let key = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let public_key = key.public_key(&secp);
+        let sig = secp.sign_ecdsa(&Message::from_slice(&[1; 32]).unwrap(), &key.inner).serialize_der();
+        let script_sig = Builder::new()
+            .push_slice(&EcdsaSig::sighash_all(Signature::from_der(&sig).unwrap()).to_vec())
+            .push_key(&public_key)
+            .into_script();
+        let multisig = Builder::new()
+            .push_int(1)
+            .push_key(&public_key)
+            .push_key(&public_key)
+            .push_int(2)
+            .push_opcode(all::OP_CHECKMULTISIG)
+            .into_script();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_inner([1; 32]), 3),
+                script_sig,
+                sequence: 0xfffffffd,
+                witness: Witness::default(),
+            }],
+            output: vec![
+                TxOut { value: 40_000, script_pubkey: multisig.clone() },
+                TxOut { value: 0, script_pubkey: Builder::new().push_opcode(all::OP_RETURN).push_slice(&[0xab; 5]).into_script() },
+                TxOut { value: 1_000, script_pubkey: Builder::new().push_opcode(all::OP_CSV).push_int(-1).into_script() },
+            ],
+        };
+
+        let result = tx.to_core_json(Network::Regtest, None);
+        assert_eq!(result.txid, tx.txid());
+        assert_eq!(result.vin[0].txid, Some(Txid::from_inner([1; 32])));
+        assert_eq!(result.vin[0].vout, Some(3));
+        assert_eq!(
+            result.vin[0].script_sig.as_ref().unwrap().asm,
+            format!("{}[ALL] {}", sig.to_hex(), public_key.to_bytes().to_hex())
+        );
+        assert_eq!(result.vin[0].prevout, None);
+        assert_eq!(result.fee, None);
+        assert_eq!(result.vout[0].script_pubkey.script_type, "multisig");
+        assert_eq!(result.vout[0].script_pubkey.asm, format!("1 {0} {0} 2 OP_CHECKMULTISIG", public_key.to_bytes().to_hex()));
+        assert_eq!(result.vout[0].script_pubkey.address, None);
+        assert_eq!(result.vout[1].script_pubkey.script_type, "nulldata");
+        assert_eq!(result.vout[1].script_pubkey.asm, "OP_RETURN ababababab");
+        assert_eq!(result.vout[2].script_pubkey.script_type, "nonstandard");
+        assert_eq!(result.vout[2].script_pubkey.asm, "OP_CHECKSEQUENCEVERIFY -1");
+
+        let p2wpkh = Script::new_v0_p2wpkh(&public_key.wpubkey_hash().unwrap());
+        let coins = [Coin::new(TxOut { value: 50_000, script_pubkey: p2wpkh.clone() }, 7, true)];
+        let result = tx.to_core_json(Network::Regtest, Some(&coins));
+        let prevout = result.vin[0].prevout.as_ref().unwrap();
+        assert!(prevout.generated);
+        assert_eq!(prevout.height, 7);
+        assert_eq!(prevout.value, Amount::from_sat(50_000));
+        assert_eq!(prevout.script_pubkey.script_type, "witness_v0_keyhash");
+        assert_eq!(prevout.script_pubkey.address, Address::from_script(&p2wpkh, Network::Regtest));
+        assert_eq!(result.fee, Some(Amount::from_sat(9_000)));
+        assert_eq!(tx.to_core_json(Network::Regtest, Some(&[])).fee, None);
+        let mut overflow = tx.clone();
+        overflow.output[0].value = u64::max_value();
+        assert_eq!(overflow.to_core_json(Network::Regtest, Some(&coins)).fee, None);
+
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(json.contains(r#""fee":0.00009"#));
+        assert_eq!(serde_json::from_str::<GetRawTransactionResult>(&json).unwrap(), result);
+
+        let block = genesis_block(Network::Bitcoin);
+        let result = block.txdata[0].to_core_json(Network::Bitcoin, Some(&coins));
+        assert_eq!(result.vin[0].coinbase.as_ref(), Some(&block.txdata[0].input[0].script_sig));
+        assert_eq!(result.vin[0].prevout, None);
+        assert_eq!(result.vout[0].script_pubkey.script_type, "pubkey");
+        assert_eq!(result.vout[0].script_pubkey.asm, format!("{} OP_CHECKSIG", block.txdata[0].output[0].script_pubkey.as_bytes()[1..66].to_hex()));
+    }
+}
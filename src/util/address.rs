@@ -45,12 +45,15 @@ use hashes::{sha256, Hash, HashEngine};
 use hash_types::{PubkeyHash, ScriptHash};
 use blockdata::{script, opcodes};
 use blockdata::constants::{PUBKEY_ADDRESS_PREFIX_MAIN, SCRIPT_ADDRESS_PREFIX_MAIN, PUBKEY_ADDRESS_PREFIX_TEST, SCRIPT_ADDRESS_PREFIX_TEST, MAX_SCRIPT_ELEMENT_SIZE};
+use blockdata::constants::{BECH32_HRP_MAIN, BECH32_HRP_TEST, BECH32_HRP_REGTEST};
 use network::constants::Network;
 use util::base58;
+use util::bip21::URI_SCHEME;
 use util::taproot::TapBranchHash;
 use util::key::PublicKey;
 use blockdata::script::Instruction;
 use util::schnorr::{TapTweak, UntweakedPublicKey, TweakedPublicKey};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// Address error.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -142,6 +145,9 @@ pub enum AddressType {
     P2wsh,
     /// Pay to taproot.
     P2tr,
+    /// Segwit output with a witness version or program length which has no meaning assigned
+    /// yet, kept for forward compatibility.
+    WitnessUnknown,
 }
 
 impl fmt::Display for AddressType {
@@ -152,6 +158,7 @@ impl fmt::Display for AddressType {
             AddressType::P2wpkh => "p2wpkh",
             AddressType::P2wsh => "p2wsh",
             AddressType::P2tr => "p2tr",
+            AddressType::WitnessUnknown => "witness_unknown",
         })
     }
 }
@@ -165,6 +172,7 @@ impl FromStr for AddressType {
             "p2wpkh" => Ok(AddressType::P2wpkh),
             "p2wsh" => Ok(AddressType::P2wsh),
             "p2tr" => Ok(AddressType::P2tr),
+            "witness_unknown" => Ok(AddressType::WitnessUnknown),
             _ => Err(()),
         }
     }
@@ -331,6 +339,14 @@ impl WitnessVersion {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for WitnessVersion {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(WitnessVersion::from_num(u.int_in_range(0..=16)?).expect("versions 0 to 16 are valid"))
+    }
+}
+
 impl From<WitnessVersion> for ::bech32::u5 {
     /// Converts [`WitnessVersion`] instance into corresponding Bech32(m) u5-value ([`bech32::u5`]).
     fn from(version: WitnessVersion) -> Self {
@@ -364,6 +380,28 @@ pub enum Payload {
     },
 }
 
+// Witness programs are generated with the lengths their version allows, so that they can be
+// encoded as addresses.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Payload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=2u8)? {
+            0 => Payload::PubkeyHash(u.arbitrary()?),
+            1 => Payload::ScriptHash(u.arbitrary()?),
+            _ => {
+                let version: WitnessVersion = u.arbitrary()?;
+                let len = if version == WitnessVersion::V0 {
+                    *u.choose(&[20, 32])?
+                } else {
+                    u.int_in_range(2..=40)?
+                };
+                Payload::WitnessProgram { version, program: u.bytes(len)?.to_vec() }
+            }
+        })
+    }
+}
+
 impl Payload {
     /// Constructs a [Payload] from an output script (`scriptPubkey`).
     pub fn from_script(script: &script::Script) -> Option<Payload> {
@@ -396,6 +434,23 @@ impl Payload {
         }
     }
 
+    /// Creates a witness program payload, checking the program length is valid for `version`.
+    ///
+    /// Programs of any valid length are accepted for witness versions without assigned meaning,
+    /// see [`AddressType::WitnessUnknown`].
+    pub fn witness_program(version: WitnessVersion, program: Vec<u8>) -> Result<Payload, Error> {
+        if program.len() < 2 || program.len() > 40 {
+            return Err(Error::InvalidWitnessProgramLength(program.len()));
+        }
+
+        // Specific segwit v0 check.
+        if version == WitnessVersion::V0 && (program.len() != 20 && program.len() != 32) {
+            return Err(Error::InvalidSegwitV0ProgramLength(program.len()));
+        }
+
+        Ok(Payload::WitnessProgram { version, program })
+    }
+
     /// Creates a pay to (compressed) public key hash payload from a public key
     #[inline]
     pub fn p2pkh(pk: &PublicKey) -> Payload {
@@ -527,6 +582,7 @@ pub struct Address {
     pub network: Network,
 }
 serde_string_impl!(Address, "a Bitcoin address");
+arbitrary_struct_impl!(Address, payload, network);
 
 impl Address {
     /// Creates a pay to (compressed) public key hash address from a public key.
@@ -619,10 +675,23 @@ impl Address {
         }
     }
 
+    /// Creates a segwit address from a witness program, checking the program length is valid
+    /// for `version`.
+    ///
+    /// This allows creating addresses for witness versions without assigned meaning, which are
+    /// encoded with bech32m like any other version above 0.
+    pub fn from_witness_program(version: WitnessVersion, program: Vec<u8>, network: Network) -> Result<Address, Error> {
+        Ok(Address {
+            network,
+            payload: Payload::witness_program(version, program)?,
+        })
+    }
+
     /// Gets the address type of the address.
     ///
     /// # Returns
-    /// None if unknown, non-standard or related to the future witness version.
+    /// None if unknown or non-standard, [`AddressType::WitnessUnknown`] for segwit programs
+    /// whose version and length have no assigned meaning yet.
     pub fn address_type(&self) -> Option<AddressType> {
         match self.payload {
             Payload::PubkeyHash(_) => Some(AddressType::P2pkh),
@@ -639,7 +708,7 @@ impl Address {
                         _ => None,
                     },
                     WitnessVersion::V1 if prog.len() == 32 => Some(AddressType::P2tr),
-                    _ => None,
+                    _ => Some(AddressType::WitnessUnknown),
                 }
             }
         }
@@ -650,7 +719,10 @@ impl Address {
     /// SegWit addresses with unassigned witness versions or non-standard program sizes are
     /// considered non-standard.
     pub fn is_standard(&self) -> bool {
-        self.address_type().is_some()
+        match self.address_type() {
+            Some(AddressType::WitnessUnknown) | None => false,
+            Some(_) => true,
+        }
     }
 
     /// Constructs an [`Address`] from an output script (`scriptPubkey`).
@@ -666,19 +738,20 @@ impl Address {
         self.payload.script_pubkey()
     }
 
-    /// Creates a URI string *bitcoin:address* optimized to be encoded in QR codes.
+    /// Creates a URI string *texitcoin:address* optimized to be encoded in QR codes.
     ///
     /// If the address is bech32, both the schema and the address become uppercase.
     /// If the address is base58, the schema is lowercase and the address is left mixed case.
     ///
     /// Quoting BIP 173 "inside QR codes uppercase SHOULD be used, as those permit the use of
     /// alphanumeric mode, which is 45% more compact than the normal byte mode."
+    ///
+    /// See [`::util::bip21::Uri`] for URIs with payment parameters.
     pub fn to_qr_uri(&self) -> String {
-        let schema = match self.payload {
-            Payload::WitnessProgram { .. } => "BITCOIN",
-            _ => "bitcoin",
-        };
-        format!("{}:{:#}", schema, self)
+        match self.payload {
+            Payload::WitnessProgram { .. } => format!("{}:{:#}", URI_SCHEME.to_ascii_uppercase(), self),
+            _ => format!("{}:{}", URI_SCHEME, self),
+        }
     }
 
     /// Parsed addresses do not always have *one* network. The problem is that legacy testnet,
@@ -697,7 +770,7 @@ impl Address {
     ///
     /// assert_eq!(address.is_valid_for_network(Network::Bitcoin), false);
     ///
-    /// let address: Address = "32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf".parse().unwrap();
+    /// let address: Address = "TAzgFknMEnZ3UkWpmmYboi3cQ3SRGXCm8n".parse().unwrap();
     /// assert!(address.is_valid_for_network(Network::Bitcoin));
     /// assert_eq!(address.is_valid_for_network(Network::Testnet), false);
     /// ```
@@ -737,6 +810,21 @@ impl Address {
         payload == xonly_pubkey.serialize()
     }
 
+    /// Returns true if the address creates the given script pubkey.
+    ///
+    /// This is equivalent to `self.script_pubkey() == *script_pubkey` but doesn't allocate.
+    pub fn matches_script_pubkey(&self, script_pubkey: &script::Script) -> bool {
+        let bytes = script_pubkey.as_bytes();
+        match self.payload {
+            Payload::PubkeyHash(ref hash) if script_pubkey.is_p2pkh() => bytes[3..23] == hash[..],
+            Payload::ScriptHash(ref hash) if script_pubkey.is_p2sh() => bytes[2..22] == hash[..],
+            Payload::WitnessProgram { version, ref program } if script_pubkey.is_witness_program() => {
+                script_pubkey.witness_version() == Some(version) && bytes[2..] == program[..]
+            }
+            _ => false,
+        }
+    }
+
     /// Return the address payload as a byte slice
     fn payload_as_bytes(&self) -> &[u8] {
         match &self.payload {
@@ -760,9 +848,9 @@ impl fmt::Display for Address {
             Network::Testnet | Network::Signet | Network::Regtest => SCRIPT_ADDRESS_PREFIX_TEST,
         };
         let bech32_hrp = match self.network {
-            Network::Bitcoin => "bc",
-            Network::Testnet | Network::Signet => "tb",
-            Network::Regtest => "bcrt",
+            Network::Bitcoin => BECH32_HRP_MAIN,
+            Network::Testnet | Network::Signet => BECH32_HRP_TEST,
+            Network::Regtest => BECH32_HRP_REGTEST,
         };
         let encoding = AddressEncoding {
             payload: &self.payload,
@@ -802,12 +890,17 @@ impl FromStr for Address {
 
     fn from_str(s: &str) -> Result<Address, Error> {
         // try bech32
-        let bech32_network = match find_bech32_prefix(s) {
-            // note that upper or lowercase is allowed but NOT mixed case
-            "bc" | "BC" => Some(Network::Bitcoin),
-            "tb" | "TB" => Some(Network::Testnet), // this may also be signet
-            "bcrt" | "BCRT" => Some(Network::Regtest),
-            _ => None,
+        // note that upper or lowercase is allowed but NOT mixed case; mixed case is rejected by
+        // the bech32 decoder itself, so we can compare case-insensitively here
+        let prefix = find_bech32_prefix(s);
+        let bech32_network = if prefix.eq_ignore_ascii_case(BECH32_HRP_MAIN) {
+            Some(Network::Bitcoin)
+        } else if prefix.eq_ignore_ascii_case(BECH32_HRP_TEST) {
+            Some(Network::Testnet) // this may also be signet
+        } else if prefix.eq_ignore_ascii_case(BECH32_HRP_REGTEST) {
+            Some(Network::Regtest)
+        } else {
+            None
         };
         if let Some(network) = bech32_network {
             // decode as bech32
@@ -822,28 +915,15 @@ impl FromStr for Address {
                 (WitnessVersion::from_u5(v[0])?, bech32::FromBase32::from_base32(p5)?)
             };
 
-            if program.len() < 2 || program.len() > 40 {
-                return Err(Error::InvalidWitnessProgramLength(program.len()));
-            }
+            let payload = Payload::witness_program(version, program)?;
 
-            // Specific segwit v0 check.
-            if version == WitnessVersion::V0 && (program.len() != 20 && program.len() != 32) {
-                return Err(Error::InvalidSegwitV0ProgramLength(program.len()));
-            }
-
-            // Encoding check
+            // Encoding check: v0 programs must use bech32 and later versions bech32m
             let expected = version.bech32_variant();
             if expected != variant {
                 return Err(Error::InvalidBech32Variant { expected, found: variant });
             }
 
-            return Ok(Address {
-                payload: Payload::WitnessProgram {
-                    version,
-                    program,
-                },
-                network,
-            });
+            return Ok(Address { payload, network });
         }
 
         // Base58
@@ -942,7 +1022,7 @@ mod tests {
             addr.script_pubkey(),
             hex_script!("76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac")
         );
-        assert_eq!(&addr.to_string(), "132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM");
+        assert_eq!(&addr.to_string(), "TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod");
         assert_eq!(addr.address_type(), Some(AddressType::P2pkh));
         roundtrips(&addr);
     }
@@ -951,7 +1031,7 @@ mod tests {
     fn test_p2pkh_from_key() {
         let key = hex_key!("048d5141948c1702e8c95f438815794b87f706a8d4cd2bffad1dc1570971032c9b6042a0431ded2478b5c9cf2d81c124a5e57347a3c63ef0e7716cf54d613ba183");
         let addr = Address::p2pkh(&key, Bitcoin);
-        assert_eq!(&addr.to_string(), "1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY");
+        assert_eq!(&addr.to_string(), "TxcJDAJrSv5eZpWqWue1Vow5Ji2N5YkPVm");
 
         let key = hex_key!(&"03df154ebfcf29d29cc10d5c2565018bce2d9edbab267c31d2caf44a63056cf99f");
         let addr = Address::p2pkh(&key, Testnet);
@@ -971,7 +1051,7 @@ mod tests {
             addr.script_pubkey(),
             hex_script!("a914162c5ea71c0b23f5b9022ef047c4a86470a5b07087")
         );
-        assert_eq!(&addr.to_string(), "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k");
+        assert_eq!(&addr.to_string(), "TBzT29EC2eJayKNcvbpzuEU4BKqQt4QGvz");
         assert_eq!(addr.address_type(), Some(AddressType::P2sh));
         roundtrips(&addr);
     }
@@ -996,7 +1076,7 @@ mod tests {
         // stolen from Bitcoin transaction: b3c8c2b6cfc335abbcb2c7823a8453f55d64b2b5125a9a61e8737230cdb8ce20
         let mut key = hex_key!("033bc8c83c52df5712229a2f72206d90192366c36428cb0c12b6af98324d97bfbc");
         let addr = Address::p2wpkh(&key, Bitcoin).unwrap();
-        assert_eq!(&addr.to_string(), "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw");
+        assert_eq!(&addr.to_string(), "txc1qvzvkjn4q3nszqxrv3nraga2r822xjty3a2quqh");
         assert_eq!(addr.address_type(), Some(AddressType::P2wpkh));
         roundtrips(&addr);
 
@@ -1012,7 +1092,7 @@ mod tests {
         let addr = Address::p2wsh(&script, Bitcoin);
         assert_eq!(
             &addr.to_string(),
-            "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej"
+            "txc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxs46clc7"
         );
         assert_eq!(addr.address_type(), Some(AddressType::P2wsh));
         roundtrips(&addr);
@@ -1023,7 +1103,7 @@ mod tests {
         // stolen from Bitcoin transaction: ad3fd9c6b52e752ba21425435ff3dd361d6ac271531fc1d2144843a9f550ad01
         let mut key = hex_key!("026c468be64d22761c30cd2f12cbc7de255d592d7904b1bab07236897cc4c2e766");
         let addr = Address::p2shwpkh(&key, Bitcoin).unwrap();
-        assert_eq!(&addr.to_string(), "3QBRmWNqqBGme9er7fMkGqtZtp4gjMFxhE");
+        assert_eq!(&addr.to_string(), "TYTcr2G8S15LiA23aqLsNNDmf5XHTKBZ4p");
         assert_eq!(addr.address_type(), Some(AddressType::P2sh));
         roundtrips(&addr);
 
@@ -1037,7 +1117,7 @@ mod tests {
         // stolen from Bitcoin transaction f9ee2be4df05041d0e0a35d7caa3157495ca4f93b233234c9967b6901dacf7a9
         let script = hex_script!("522103e5529d8eaa3d559903adb2e881eb06c86ac2574ffa503c45f4e942e2a693b33e2102e5f10fcdcdbab211e0af6a481f5532536ec61a5fdbf7183770cf8680fe729d8152ae");
         let addr = Address::p2shwsh(&script, Bitcoin);
-        assert_eq!(&addr.to_string(), "36EqgNnsWW94SreZgBWc1ANC6wpFZwirHr");
+        assert_eq!(&addr.to_string(), "TEX2ktgA7KwdWs1m9MVj6ghPsDGr9sUBkH");
         assert_eq!(addr.address_type(), Some(AddressType::P2sh));
         roundtrips(&addr);
     }
@@ -1061,35 +1141,87 @@ mod tests {
     #[test]
     fn test_address_type() {
         let addresses = [
-            ("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY", Some(AddressType::P2pkh)),
-            ("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k", Some(AddressType::P2sh)),
-            ("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw", Some(AddressType::P2wpkh)),
-            ("bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej", Some(AddressType::P2wsh)),
-            ("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr", Some(AddressType::P2tr)),
-            // Related to future extensions, addresses are valid but have no type
+            ("TxcJDAJrSv5eZpWqWue1Vow5Ji2N5YkPVm", Some(AddressType::P2pkh)),
+            ("TBzT29EC2eJayKNcvbpzuEU4BKqQt4QGvz", Some(AddressType::P2sh)),
+            ("txc1qvzvkjn4q3nszqxrv3nraga2r822xjty3a2quqh", Some(AddressType::P2wpkh)),
+            ("txc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxs46clc7", Some(AddressType::P2wsh)),
+            ("txc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqd0e7e0", Some(AddressType::P2tr)),
+            // Related to future extensions, addresses are valid but have no assigned meaning
             // segwit v1 and len != 32
-            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", None),
+            ("txc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k8el6rj", Some(AddressType::WitnessUnknown)),
             // segwit v2
-            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", None),
+            ("txc1zw508d6qejxtdg4y5r3zarvaryvklfe06", Some(AddressType::WitnessUnknown)),
         ];
         for (address, expected_type) in &addresses {
             let addr = Address::from_str(&address).unwrap();
             assert_eq!(&addr.address_type(), expected_type);
+            let is_standard = expected_type.map_or(false, |t| t != AddressType::WitnessUnknown);
+            assert_eq!(addr.is_standard(), is_standard);
+        }
+
+        for address_type in &[AddressType::P2pkh, AddressType::P2tr, AddressType::WitnessUnknown] {
+            assert_eq!(address_type.to_string().parse::<AddressType>(), Ok(*address_type));
         }
     }
 
+    #[test]
+    fn test_witness_unknown() {
+        use bech32::{ToBase32, Variant};
+
+        let program = hex!("751e76e8199196d454941c45d1b3a323");
+        let addr = Address::from_witness_program(WitnessVersion::V16, program.clone(), Bitcoin).unwrap();
+        assert_eq!(addr.address_type(), Some(AddressType::WitnessUnknown));
+        assert_eq!(addr.script_pubkey(), hex_script!("6010751e76e8199196d454941c45d1b3a323"));
+        roundtrips(&addr);
+
+        // v1+ programs are encoded with bech32m, v0 ones with bech32
+        let (hrp, _, variant) = bech32::decode(&addr.to_string()).unwrap();
+        assert_eq!((hrp.as_str(), variant), ("txc", Variant::Bech32m));
+        let p2wpkh = Address::from_str("txc1qvzvkjn4q3nszqxrv3nraga2r822xjty3a2quqh").unwrap();
+        assert_eq!(bech32::decode(&p2wpkh.to_string()).unwrap().2, Variant::Bech32);
+
+        // mixed encodings are rejected
+        let encode = |version: WitnessVersion, program: &[u8], variant| {
+            let mut data = vec![version.into()];
+            data.extend(program.to_base32());
+            bech32::encode("txc", data, variant).unwrap()
+        };
+        assert_eq!(
+            Address::from_str(&encode(WitnessVersion::V16, &program, Variant::Bech32)),
+            Err(Error::InvalidBech32Variant { expected: Variant::Bech32m, found: Variant::Bech32 })
+        );
+        assert_eq!(
+            Address::from_str(&encode(WitnessVersion::V0, &[0; 20], Variant::Bech32m)),
+            Err(Error::InvalidBech32Variant { expected: Variant::Bech32, found: Variant::Bech32m })
+        );
+        assert_eq!(
+            Address::from_str(&encode(WitnessVersion::V2, &program, Variant::Bech32m)).unwrap().payload,
+            Payload::WitnessProgram { version: WitnessVersion::V2, program: program.clone() }
+        );
+
+        // program lengths are still checked
+        assert_eq!(
+            Address::from_witness_program(WitnessVersion::V2, vec![0; 41], Bitcoin),
+            Err(Error::InvalidWitnessProgramLength(41))
+        );
+        assert_eq!(
+            Address::from_witness_program(WitnessVersion::V0, vec![0; 16], Bitcoin),
+            Err(Error::InvalidSegwitV0ProgramLength(16))
+        );
+    }
+
     #[test]
     fn test_bip173_350_vectors() {
         // Test vectors valid under both BIP-173 and BIP-350
         let valid_vectors = [
-            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("TXC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7K4M9MHV", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
             ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
-            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
-            ("BC1SW50QGDZ25J", "6002751e"),
-            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", "5210751e76e8199196d454941c45d1b3a323"),
+            ("txc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k8el6rj", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("TXC1SW50QX0DRHN", "6002751e"),
+            ("txc1zw508d6qejxtdg4y5r3zarvaryvklfe06", "5210751e76e8199196d454941c45d1b3a323"),
             ("tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy", "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
             ("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
-            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+            ("txc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqeqq0nr", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
         ];
         for vector in &valid_vectors {
             let addr: Address = vector.0.parse().unwrap();
@@ -1157,16 +1289,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_foreign_network_rejected() {
+        // Valid addresses on the Bitcoin main chain are not valid texitcoin addresses.
+        let bitcoin_addresses = [
+            "132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM",
+            "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k",
+            "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw",
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+        ];
+        for address in &bitcoin_addresses {
+            assert!(address.parse::<Address>().is_err());
+        }
+        assert_eq!(
+            "132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM".parse::<Address>(),
+            Err(Error::Base58(base58::Error::InvalidAddressVersion(0)))
+        );
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn test_json_serialize() {
         use serde_json;
 
-        let addr = Address::from_str("132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM").unwrap();
+        let addr = Address::from_str("TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod").unwrap();
         let json = serde_json::to_value(&addr).unwrap();
         assert_eq!(
             json,
-            serde_json::Value::String("132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM".to_owned())
+            serde_json::Value::String("TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod".to_owned())
         );
         let into: Address = serde_json::from_value(json).unwrap();
         assert_eq!(addr.to_string(), into.to_string());
@@ -1175,11 +1326,11 @@ mod tests {
             hex_script!("76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac")
         );
 
-        let addr = Address::from_str("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k").unwrap();
+        let addr = Address::from_str("TBzT29EC2eJayKNcvbpzuEU4BKqQt4QGvz").unwrap();
         let json = serde_json::to_value(&addr).unwrap();
         assert_eq!(
             json,
-            serde_json::Value::String("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k".to_owned())
+            serde_json::Value::String("TBzT29EC2eJayKNcvbpzuEU4BKqQt4QGvz".to_owned())
         );
         let into: Address = serde_json::from_value(json).unwrap();
         assert_eq!(addr.to_string(), into.to_string());
@@ -1221,14 +1372,14 @@ mod tests {
 
     #[test]
     fn test_qr_string() {
-        for el in  ["132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM", "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k"].iter() {
+        for el in  ["TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod", "TBzT29EC2eJayKNcvbpzuEU4BKqQt4QGvz"].iter() {
             let addr = Address::from_str(el).unwrap();
-            assert_eq!(addr.to_qr_uri(), format!("bitcoin:{}", el));
+            assert_eq!(addr.to_qr_uri(), format!("texitcoin:{}", el));
         }
 
-        for el in ["bcrt1q2nfxmhd4n3c8834pj72xagvyr9gl57n5r94fsl", "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej"].iter() {
+        for el in ["bcrt1q2nfxmhd4n3c8834pj72xagvyr9gl57n5r94fsl", "txc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxs46clc7"].iter() {
             let addr = Address::from_str(el).unwrap();
-            assert_eq!(addr.to_qr_uri(), format!("BITCOIN:{}", el.to_ascii_uppercase()) );
+            assert_eq!(addr.to_qr_uri(), format!("TEXITCOIN:{}", el.to_ascii_uppercase()) );
         }
     }
 
@@ -1295,14 +1446,14 @@ mod tests {
         let internal_key = XOnlyPublicKey::from_str("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115").unwrap();
         let secp = Secp256k1::verification_only();
         let address = Address::p2tr(&secp, internal_key, None, Network::Bitcoin);
-        assert_eq!(address.to_string(), "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+        assert_eq!(address.to_string(), "txc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqd0e7e0");
         assert_eq!(address.address_type(), Some(AddressType::P2tr));
         roundtrips(&address);
     }
 
     #[test]
     fn test_is_related_to_pubkey_p2wpkh() {
-        let address_string = "bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4";
+        let address_string = "txc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2meun7jv";
         let address = Address::from_str(address_string).expect("address");
 
         let pubkey_string = "0347ff3dacd07a1f43805ec6808e801505a6e18245178609972a68afbc2777ff2b";
@@ -1317,7 +1468,7 @@ mod tests {
 
     #[test]
     fn test_is_related_to_pubkey_p2shwpkh() {
-        let address_string = "3EZQk4F8GURH5sqVMLTFisD17yNeKa7Dfs";
+        let address_string = "TNqbpa8QsJDr9tCgpWSNpPYCtEqEtH519y";
         let address = Address::from_str(address_string).expect("address");
 
         let pubkey_string = "0347ff3dacd07a1f43805ec6808e801505a6e18245178609972a68afbc2777ff2b";
@@ -1332,7 +1483,7 @@ mod tests {
 
     #[test]
     fn test_is_related_to_pubkey_p2pkh() {
-        let address_string = "1J4LVanjHMu3JkXbVrahNuQCTGCRRgfWWx";
+        let address_string = "TrN9UkTk9GUsHMjK7YZjPBN7zZCgQxv9XR";
         let address = Address::from_str(address_string).expect("address");
 
         let pubkey_string = "0347ff3dacd07a1f43805ec6808e801505a6e18245178609972a68afbc2777ff2b";
@@ -1368,7 +1519,7 @@ mod tests {
         let tweaked_pubkey = TweakedPublicKey::dangerous_assume_tweaked(xonly_pubkey);
         let address = Address::p2tr_tweaked(tweaked_pubkey, Network::Bitcoin);
 
-        assert_eq!(address, Address::from_str("bc1pgllnmtxs0g058qz7c6qgaqq4qknwrqj9z7rqn9e2dzhmcfmhlu4sfadf5e").expect("address"));
+        assert_eq!(address, Address::from_str("txc1pgllnmtxs0g058qz7c6qgaqq4qknwrqj9z7rqn9e2dzhmcfmhlu4sjte544").expect("address"));
 
         let result = address.is_related_to_pubkey(&pubkey);
         assert!(result);
@@ -1385,9 +1536,31 @@ mod tests {
         let tweaked_pubkey = TweakedPublicKey::dangerous_assume_tweaked(xonly_pubkey);
         let address = Address::p2tr_tweaked(tweaked_pubkey, Network::Bitcoin);
 
-        assert_eq!(address, Address::from_str("bc1pgllnmtxs0g058qz7c6qgaqq4qknwrqj9z7rqn9e2dzhmcfmhlu4sfadf5e").expect("address"));
+        assert_eq!(address, Address::from_str("txc1pgllnmtxs0g058qz7c6qgaqq4qknwrqj9z7rqn9e2dzhmcfmhlu4sjte544").expect("address"));
 
         let result = address.is_related_to_xonly_pubkey(&xonly_pubkey);
         assert!(result);
     }
+
+    #[test]
+    fn test_matches_script_pubkey() {
+        let pubkey = PublicKey::from_str("0347ff3dacd07a1f43805ec6808e801505a6e18245178609972a68afbc2777ff2b").expect("pubkey");
+        let xonly_pubkey = XOnlyPublicKey::from(pubkey.inner);
+        let addresses = [
+            Address::p2pkh(&pubkey, Network::Bitcoin),
+            Address::p2sh(&Address::p2pkh(&pubkey, Network::Bitcoin).script_pubkey(), Network::Bitcoin).unwrap(),
+            Address::p2wpkh(&pubkey, Network::Bitcoin).unwrap(),
+            Address::p2shwpkh(&pubkey, Network::Bitcoin).unwrap(),
+            Address::p2wsh(&Address::p2pkh(&pubkey, Network::Bitcoin).script_pubkey(), Network::Bitcoin),
+            Address::p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(xonly_pubkey), Network::Bitcoin),
+        ];
+
+        for (i, a) in addresses.iter().enumerate() {
+            for (j, b) in addresses.iter().enumerate() {
+                assert_eq!(a.matches_script_pubkey(&b.script_pubkey()), i == j);
+            }
+            assert_eq!(Address::from_script(&a.script_pubkey(), Network::Bitcoin).as_ref(), Some(a));
+        }
+        assert!(!addresses[0].matches_script_pubkey(&Script::new()));
+    }
 }
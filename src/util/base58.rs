@@ -31,7 +31,12 @@ use util::{endian, key};
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub enum Error {
     /// Invalid character encountered
-    BadByte(u8),
+    InvalidCharacter {
+        /// The character which is not in the base58 alphabet
+        character: char,
+        /// The byte offset of the character in the decoded string
+        position: usize,
+    },
     /// Checksum was not correct (expected, actual)
     BadChecksum(u32, u32),
     /// The length (in bytes) of the object was not correct
@@ -54,7 +59,7 @@ pub enum Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::BadByte(b) => write!(f, "invalid base58 character 0x{:x}", b),
+            Error::InvalidCharacter { character, position } => write!(f, "invalid base58 character {:?} at position {}", character, position),
             Error::BadChecksum(exp, actual) => write!(f, "base58ck checksum 0x{:x} does not match expected 0x{:x}", actual, exp),
             Error::InvalidLength(ell) => write!(f, "length {} invalid for this base58 type", ell),
             Error::InvalidAddressVersion(ref v) => write!(f, "address version {} is invalid for this base58 type", v),
@@ -128,34 +133,57 @@ static BASE58_DIGITS: [Option<u8>; 128] = [
     Some(55), Some(56), Some(57), None,     None,     None,     None,     None,     // 120-127
 ];
 
+/// 58^5, the largest power of 58 which fits in a `u32`: digits are decoded five at a time.
+const LIMB_BASE: u32 = 58 * 58 * 58 * 58 * 58;
+
+/// Computes `limbs = limbs * mul + add`, growing `limbs` (little-endian base 2^32) as needed.
+fn mul_add(limbs: &mut Vec<u32>, mul: u32, add: u32) {
+    let mut carry = add as u64;
+    for limb in limbs.iter_mut() {
+        let value = *limb as u64 * mul as u64 + carry;
+        *limb = value as u32;
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+}
+
 /// Decode base58-encoded string into a byte vector
 pub fn from(data: &str) -> Result<Vec<u8>, Error> {
-    // 11/15 is just over log_256(58)
-    let mut scratch = vec![0u8; 1 + data.len() * 11 / 15];
-    // Build in base 256
-    for d58 in data.bytes() {
-        // Compute "X = X * 58 + next_digit" in base 256
-        if d58 as usize >= BASE58_DIGITS.len() {
-            return Err(Error::BadByte(d58));
-        }
-        let mut carry = match BASE58_DIGITS[d58 as usize] {
-            Some(d58) => d58 as u32,
-            None => { return Err(Error::BadByte(d58)); }
+    // Build the number in base 2^32, five base58 digits at a time rather than one byte at a time.
+    // 183/1000 limbs per digit is just over log_2^32(58).
+    let mut limbs = Vec::with_capacity(1 + data.len() * 183 / 1000);
+    let (mut acc, mut mul) = (0u32, 1u32);
+    for (position, character) in data.char_indices() {
+        let digit = BASE58_DIGITS.get(character as usize).and_then(|digit| *digit);
+        let digit = match digit {
+            Some(digit) => digit as u32,
+            None => return Err(Error::InvalidCharacter { character, position }),
         };
-        for d256 in scratch.iter_mut().rev() {
-            carry += *d256 as u32 * 58;
-            *d256 = carry as u8;
-            carry /= 256;
+        acc = acc * 58 + digit;
+        mul *= 58;
+        if mul == LIMB_BASE {
+            mul_add(&mut limbs, mul, acc);
+            acc = 0;
+            mul = 1;
         }
-        assert_eq!(carry, 0);
+    }
+    if mul > 1 {
+        mul_add(&mut limbs, mul, acc);
     }
 
     // Copy leading zeroes directly
     let mut ret: Vec<u8> = data.bytes().take_while(|&x| x == BASE58_CHARS[0])
                                        .map(|_| 0)
                                        .collect();
-    // Copy rest of string
-    ret.extend(scratch.into_iter().skip_while(|&x| x == 0));
+    // Copy rest of string, the most significant limb being the only one with leading zeroes
+    if let Some((&top, rest)) = limbs.split_last() {
+        ret.extend(endian::u32_to_array_be(top).iter().skip_while(|&&x| x == 0));
+        for &limb in rest.iter().rev() {
+            ret.extend_from_slice(&endian::u32_to_array_be(limb));
+        }
+    }
     Ok(ret)
 }
 
@@ -311,7 +339,10 @@ mod tests {
         assert_eq!(from_check("1PfJpZsjreyVrqeoAfabrRwwjQyoSQMmHH").ok(),
                    Some(Vec::from_hex("00f8917303bfa8ef24f292e8fa1419b20460ba064d").unwrap()));
         // Non Base58 char.
-        assert_eq!(from("¢").unwrap_err(), Error::BadByte(194));
+        assert_eq!(from("¢").unwrap_err(), Error::InvalidCharacter { character: '¢', position: 0 });
+        assert_eq!(from("1PfJ0pZs").unwrap_err(), Error::InvalidCharacter { character: '0', position: 4 });
+        assert_eq!(from("1Pf€Jl").unwrap_err(), Error::InvalidCharacter { character: '€', position: 3 });
+        assert_eq!(from("1Pf€Jl").unwrap_err().to_string(), "invalid base58 character '€' at position 3");
     }
 
     #[test]
@@ -327,5 +358,33 @@ mod tests {
         assert_eq!(from_check(&encode_slice(&[1,2,3])), Err(Error::TooShort(3)));
 
     }
+
+    #[test]
+    fn test_base58_roundtrip_lengths() {
+        // Cover every number of digits in the last limb, and leading zeroes.
+        let mut data = vec![0u8, 0];
+        for i in 0..100u8 {
+            data.push(i.wrapping_mul(151) | 1);
+            assert_eq!(from(&encode_slice(&data)).unwrap(), data);
+            assert_eq!(from(&encode_slice(&data[2..])).unwrap(), &data[2..]);
+        }
+        assert_eq!(from("").unwrap(), Vec::<u8>::new());
+        assert_eq!(from("1111").unwrap(), vec![0u8; 4]);
+        assert_eq!(from("zzzzzzzzzzz").unwrap(), Vec::from_hex("015ac264554f0327ff").unwrap());
+    }
+}
+
+#[cfg(all(test, feature = "unstable"))]
+mod benches {
+    use super::*;
+    use test::{black_box, Bencher};
+
+    #[bench]
+    pub fn bench_from_check(bh: &mut Bencher) {
+        let wif = "L1HKVVLHXiUhecWnwFYF6L3shkf1E12HUmuZTESvBXUdx3yqVP1D";
+        bh.iter(|| {
+            black_box(from_check(wif).unwrap());
+        });
+    }
 }
 
@@ -0,0 +1,205 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP157 Client Side Block Filtering.
+//!
+//! This module verifies the filter headers and filters peers serve, as
+//! described in [BIP157](https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki).
+//!
+//! Filter headers form a chain committing to every filter, so a client
+//! checks `cfheaders` responses against `cfcheckpt` checkpoints with
+//! [`check_cfheaders`], and each `cfilter` against its header with
+//! [`check_cfilter`]. When peers disagree, [`first_conflict`] finds the first
+//! checkpoint, then the first header, they disagree on. The client then
+//! downloads that block, builds its filter and [`find_faulty_peers`] tells
+//! which peers lied.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hash_types::{BlockHash, FilterHash, FilterHeader};
+use network::message_filter::{CFCheckpt, CFHeaders, CFilter, FilterType};
+use util::bip158::BlockFilter;
+
+/// The number of blocks between consecutive filter header checkpoints.
+pub const CHECKPOINT_INTERVAL: u32 = 1000;
+
+/// A filter verification error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The responses are for different filter types.
+    FilterTypeMismatch {
+        /// The filter type of the reference response.
+        expected: FilterType,
+        /// The filter type of the checked response.
+        actual: FilterType,
+    },
+    /// The filter header at the height does not match the checkpoint.
+    CheckpointMismatch(u32),
+    /// The filter of the block does not match its filter header.
+    FilterMismatch(BlockHash),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::FilterTypeMismatch { expected, actual } => write!(f, "filter type {} does not match the expected type {}", actual, expected),
+            Error::CheckpointMismatch(height) => write!(f, "filter header at height {} does not match its checkpoint", height),
+            Error::FilterMismatch(ref hash) => write!(f, "filter of block {} does not match its filter header", hash),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {}
+
+/// Computes the filter headers of the filters with `filter_hashes`, following `previous`.
+pub fn filter_headers(previous: &FilterHeader, filter_hashes: &[FilterHash]) -> Vec<FilterHeader> {
+    let mut headers = Vec::with_capacity(filter_hashes.len());
+    let mut previous = *previous;
+    for filter_hash in filter_hashes {
+        previous = filter_hash.filter_header(&previous);
+        headers.push(previous);
+    }
+    headers
+}
+
+/// Computes the filter headers of a `cfheaders` response for the blocks from `start_height`
+/// on, checking them against the checkpoints of a `cfcheckpt` response.
+///
+/// Headers at heights beyond the last checkpoint can't be checked.
+pub fn check_cfheaders(start_height: u32, cfheaders: &CFHeaders, checkpoints: &CFCheckpt) -> Result<Vec<FilterHeader>, Error> {
+    if cfheaders.filter_type != checkpoints.filter_type {
+        return Err(Error::FilterTypeMismatch { expected: checkpoints.filter_type, actual: cfheaders.filter_type });
+    }
+    let checkpoint = |height: u32| {
+        if height == 0 || height % CHECKPOINT_INTERVAL != 0 {
+            return None;
+        }
+        checkpoints.filter_headers.get((height / CHECKPOINT_INTERVAL - 1) as usize)
+    };
+
+    let previous = &cfheaders.previous_filter_header;
+    if let Some(height) = start_height.checked_sub(1) {
+        if checkpoint(height).map_or(false, |header| header != previous) {
+            return Err(Error::CheckpointMismatch(height));
+        }
+    }
+    let headers = filter_headers(previous, &cfheaders.filter_hashes);
+    for (height, header) in (start_height..).zip(headers.iter()) {
+        if checkpoint(height).map_or(false, |checkpoint| checkpoint != header) {
+            return Err(Error::CheckpointMismatch(height));
+        }
+    }
+    Ok(headers)
+}
+
+/// Checks the filter of a `cfilter` response against its filter header.
+pub fn check_cfilter(cfilter: &CFilter, previous: &FilterHeader, header: &FilterHeader) -> Result<BlockFilter, Error> {
+    let filter = BlockFilter::new(&cfilter.filter);
+    if filter.filter_header(previous) == *header {
+        Ok(filter)
+    } else {
+        Err(Error::FilterMismatch(cfilter.block_hash))
+    }
+}
+
+/// Returns the index of the first filter header two peers disagree on, `None` if they agree
+/// on all headers both sent.
+///
+/// Works for `cfcheckpt` checkpoints as well as `cfheaders` headers.
+pub fn first_conflict(a: &[FilterHeader], b: &[FilterHeader]) -> Option<usize> {
+    a.iter().zip(b.iter()).position(|(a, b)| a != b)
+}
+
+/// Returns the peers which claimed a filter header other than the one of `filter`, which the
+/// client built from the block itself.
+///
+/// `previous` is the header of the previous block's filter, which the peers must agree on, see
+/// [`first_conflict`].
+pub fn find_faulty_peers<P, I>(previous: &FilterHeader, filter: &BlockFilter, claims: I) -> Vec<P>
+    where I: IntoIterator<Item = (P, FilterHeader)>
+{
+    let header = filter.filter_header(previous);
+    claims.into_iter().filter(|(_, claim)| *claim != header).map(|(peer, _)| peer).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::Hash;
+
+    fn filter(n: u32) -> BlockFilter {
+        BlockFilter::new(&[n as u8, (n >> 8) as u8])
+    }
+
+    #[test]
+    fn filter_header_chain() {
+        let genesis = FilterHeader::default();
+        let hashes: Vec<FilterHash> = (1..2500).map(|n| FilterHash::hash(&filter(n).content)).collect();
+        let all_headers = filter_headers(&genesis, &hashes);
+        assert_eq!(all_headers[1], filter(2).filter_header(&filter(1).filter_header(&genesis)));
+        let checkpoints = CFCheckpt {
+            filter_type: FilterType::Basic,
+            stop_hash: BlockHash::default(),
+            filter_headers: vec![all_headers[999], all_headers[1999]],
+        };
+
+        // heights 1 to 1500
+        let mut cfheaders = CFHeaders {
+            filter_type: FilterType::Basic,
+            stop_hash: BlockHash::default(),
+            previous_filter_header: genesis,
+            filter_hashes: hashes[..1500].to_vec(),
+        };
+        assert_eq!(check_cfheaders(1, &cfheaders, &checkpoints), Ok(all_headers[..1500].to_vec()));
+
+        // heights 1001 to 2499, starting right after a checkpoint
+        let honest = CFHeaders {
+            previous_filter_header: all_headers[999],
+            filter_hashes: hashes[1000..].to_vec(),
+            ..cfheaders.clone()
+        };
+        assert_eq!(check_cfheaders(1001, &honest, &checkpoints), Ok(all_headers[1000..].to_vec()));
+        let mut lying = honest.clone();
+        lying.previous_filter_header = all_headers[998];
+        assert_eq!(check_cfheaders(1001, &lying, &checkpoints), Err(Error::CheckpointMismatch(1000)));
+
+        // a different filter commits to a different chain
+        cfheaders.filter_hashes[5] = FilterHash::hash(&[0xff]);
+        assert_eq!(check_cfheaders(1, &cfheaders, &checkpoints), Err(Error::CheckpointMismatch(1000)));
+        let mut lying = honest.clone();
+        lying.filter_hashes[1498] = FilterHash::hash(&[0xff]);
+        assert!(check_cfheaders(1001, &lying, &checkpoints).is_ok());
+        cfheaders.filter_type = FilterType::Unknown(1);
+        assert_eq!(check_cfheaders(1, &cfheaders, &checkpoints), Err(Error::FilterTypeMismatch { expected: FilterType::Basic, actual: FilterType::Unknown(1) }));
+
+        // resolving a conflict
+        let lying_headers = filter_headers(&all_headers[999], &lying.filter_hashes);
+        let conflict = first_conflict(&all_headers[1000..], &lying_headers).unwrap();
+        assert_eq!(conflict, 1498);
+        assert_eq!(first_conflict(&all_headers, &all_headers[..10]), None);
+        let previous = &all_headers[1000 + conflict - 1];
+        let block_filter = filter(2499);
+        let claims = vec![("honest", all_headers[1000 + conflict]), ("lying", lying_headers[conflict])];
+        assert_eq!(find_faulty_peers(previous, &block_filter, claims), vec!["lying"]);
+
+        let cfilter = CFilter { filter_type: FilterType::Basic, block_hash: BlockHash::default(), filter: block_filter.content.clone() };
+        assert_eq!(check_cfilter(&cfilter, previous, &all_headers[2498]), Ok(block_filter));
+        assert_eq!(check_cfilter(&cfilter, previous, &lying_headers[1498]), Err(Error::FilterMismatch(BlockHash::default())));
+    }
+}
@@ -51,19 +51,21 @@ use io::{self as io, Cursor};
 use core::fmt::{self, Display, Formatter};
 use core::cmp::{self, Ordering};
 
-use hashes::{Hash, siphash24};
+use hashes::Hash;
 use hash_types::{BlockHash, FilterHash, FilterHeader};
 
 use blockdata::block::Block;
 use blockdata::script::Script;
 use blockdata::transaction::OutPoint;
 use consensus::{Decodable, Encodable};
+use util::siphash::SipHasher;
 use consensus::encode::VarInt;
 use util::endian;
 
-/// Golomb encoding parameter as in BIP-158, see also https://gist.github.com/sipa/576d5f09c3b86c3b1b75598d799fc845
-const P: u8 = 19;
-const M: u64 = 784931;
+/// Golomb-Rice parameter P of basic filters, as in BIP-158, see also https://gist.github.com/sipa/576d5f09c3b86c3b1b75598d799fc845
+pub const P: u8 = 19;
+/// Inverse false positive rate M of basic filters, as in BIP-158
+pub const M: u64 = 784931;
 
 /// Errors for blockfilter
 #[derive(Debug)]
@@ -72,6 +74,8 @@ pub enum Error {
     UtxoMissing(OutPoint),
     /// some IO error reading or writing binary serialization of the filter
     Io(io::Error),
+    /// the Golomb-Rice parameter P is 64 or more
+    InvalidParameter(u8),
 }
 
 #[cfg(feature = "std")]
@@ -82,7 +86,8 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         match *self {
             Error::UtxoMissing(ref coin) => write!(f, "unresolved UTXO {}", coin),
-            Error::Io(ref io) => write!(f, "{}", io)
+            Error::Io(ref io) => write!(f, "{}", io),
+            Error::InvalidParameter(p) => write!(f, "Golomb-Rice parameter {} is not below 64", p),
         }
     }
 }
@@ -125,7 +130,7 @@ impl BlockFilter {
 
     /// Compute a SCRIPT_FILTER that contains spent and output scripts
     pub fn new_script_filter<M>(block: &Block, script_for_coin: M) -> Result<BlockFilter, Error>
-        where M: Fn(&OutPoint) -> Result<Script, Error> {
+        where M: FnMut(&OutPoint) -> Result<Script, Error> {
         let mut out = Vec::new();
         {
             let mut writer = BlockFilterWriter::new(&mut out, block);
@@ -147,6 +152,16 @@ impl BlockFilter {
         let filter_reader = BlockFilterReader::new(block_hash);
         filter_reader.match_all(&mut Cursor::new(self.content.as_slice()), query)
     }
+
+    /// match any of the scripts, e.g. the script pubkeys of a wallet
+    pub fn match_any_script(&self, block_hash: &BlockHash, scripts: &[Script]) -> Result<bool, Error> {
+        self.match_any(block_hash, &mut scripts.iter().map(Script::as_bytes))
+    }
+
+    /// match all of the scripts
+    pub fn match_all_script(&self, block_hash: &BlockHash, scripts: &[Script]) -> Result<bool, Error> {
+        self.match_all(block_hash, &mut scripts.iter().map(Script::as_bytes))
+    }
 }
 
 /// Compiles and writes a block filter
@@ -158,11 +173,16 @@ pub struct BlockFilterWriter<'a> {
 impl<'a> BlockFilterWriter<'a> {
     /// Create a block filter writer
     pub fn new(writer: &'a mut dyn io::Write, block: &'a Block) -> BlockFilterWriter<'a> {
-        let block_hash_as_int = block.block_hash().into_inner();
-        let k0 = endian::slice_to_u64_le(&block_hash_as_int[0..8]);
-        let k1 = endian::slice_to_u64_le(&block_hash_as_int[8..16]);
-        let writer = GCSFilterWriter::new(writer, k0, k1, M, P);
-        BlockFilterWriter { block, writer }
+        let (k0, k1) = filter_keys(&block.block_hash());
+        BlockFilterWriter { block, writer: GCSFilterWriter::new(writer, k0, k1, M, P) }
+    }
+
+    /// Create a block filter writer with custom parameters `m` and `p`, e.g. for a filter type
+    /// indexing other elements than scripts
+    pub fn with_params(writer: &'a mut dyn io::Write, block: &'a Block, m: u64, p: u8) -> Result<BlockFilterWriter<'a>, Error> {
+        let (k0, k1) = filter_keys(&block.block_hash());
+        let writer = GCSFilterWriter::with_filter(writer, GCSFilter::new(k0, k1, p)?, m);
+        Ok(BlockFilterWriter { block, writer })
     }
 
     /// Add output scripts of the block - excluding OP_RETURN scripts
@@ -178,7 +198,7 @@ impl<'a> BlockFilterWriter<'a> {
 
     /// Add consumed output scripts of a block to filter
     pub fn add_input_scripts<M>(&mut self, script_for_coin: M) -> Result<(), Error>
-        where M: Fn(&OutPoint) -> Result<Script, Error> {
+        where M: FnMut(&OutPoint) -> Result<Script, Error> {
         for script in self.block.txdata.iter()
             .skip(1) // skip coinbase
             .flat_map(|t| t.input.iter().map(|i| &i.previous_output))
@@ -211,12 +231,16 @@ pub struct BlockFilterReader {
 impl BlockFilterReader {
     /// Create a block filter reader
     pub fn new(block_hash: &BlockHash) -> BlockFilterReader {
-        let block_hash_as_int = block_hash.into_inner();
-        let k0 = endian::slice_to_u64_le(&block_hash_as_int[0..8]);
-        let k1 = endian::slice_to_u64_le(&block_hash_as_int[8..16]);
+        let (k0, k1) = filter_keys(block_hash);
         BlockFilterReader { reader: GCSFilterReader::new(k0, k1, M, P) }
     }
 
+    /// Create a block filter reader for a filter written with custom parameters `m` and `p`
+    pub fn with_params(block_hash: &BlockHash, m: u64, p: u8) -> Result<BlockFilterReader, Error> {
+        let (k0, k1) = filter_keys(block_hash);
+        Ok(BlockFilterReader { reader: GCSFilterReader::with_filter(GCSFilter::new(k0, k1, p)?, m) })
+    }
+
     /// match any query pattern
     pub fn match_any(&self, reader: &mut dyn io::Read, query: &mut dyn Iterator<Item=&[u8]>) -> Result<bool, Error> {
         self.reader.match_any(reader, query)
@@ -228,6 +252,13 @@ impl BlockFilterReader {
     }
 }
 
+/// The siphash keys of the filters of the block with `block_hash`: the first 16 bytes of the hash
+pub fn filter_keys(block_hash: &BlockHash) -> (u64, u64) {
+    let block_hash_as_int = block_hash.into_inner();
+    let k0 = endian::slice_to_u64_le(&block_hash_as_int[0..8]);
+    let k1 = endian::slice_to_u64_le(&block_hash_as_int[8..16]);
+    (k0, k1)
+}
 
 /// Golomb-Rice encoded filter reader
 pub struct GCSFilterReader {
@@ -237,8 +268,17 @@ pub struct GCSFilterReader {
 
 impl GCSFilterReader {
     /// Create a new filter reader with specific seed to siphash
+    ///
+    /// # Panics
+    ///
+    /// If `p` is 64 or more, see [`GCSFilter::new`].
     pub fn new(k0: u64, k1: u64, m: u64, p: u8) -> GCSFilterReader {
-        GCSFilterReader { filter: GCSFilter::new(k0, k1, p), m }
+        GCSFilterReader::with_filter(GCSFilter::new(k0, k1, p).expect("invalid Golomb-Rice parameter"), m)
+    }
+
+    /// Create a new filter reader decoding with `filter`
+    pub fn with_filter(filter: GCSFilter, m: u64) -> GCSFilterReader {
+        GCSFilterReader { filter, m }
     }
 
     /// match any query pattern
@@ -248,7 +288,7 @@ impl GCSFilterReader {
         let reader = &mut decoder;
         // map hashes to [0, n_elements << grp]
         let nm = n_elements.0 * self.m;
-        let mut mapped = query.map(|e| map_to_range(self.filter.hash(e), nm)).collect::<Vec<_>>();
+        let mut mapped = query.map(|e| self.filter.hash_to_range(e, nm)).collect::<Vec<_>>();
         // sort
         mapped.sort_unstable();
         if mapped.is_empty() {
@@ -288,7 +328,7 @@ impl GCSFilterReader {
         let reader = &mut decoder;
         // map hashes to [0, n_elements << grp]
         let nm = n_elements.0 * self.m;
-        let mut mapped = query.map(|e| map_to_range(self.filter.hash(e), nm)).collect::<Vec<_>>();
+        let mut mapped = query.map(|e| self.filter.hash_to_range(e, nm)).collect::<Vec<_>>();
         // sort
         mapped.sort_unstable();
         mapped.dedup();
@@ -338,9 +378,18 @@ pub struct GCSFilterWriter<'a> {
 
 impl<'a> GCSFilterWriter<'a> {
     /// Create a new GCS writer wrapping a generic writer, with specific seed to siphash
+    ///
+    /// # Panics
+    ///
+    /// If `p` is 64 or more, see [`GCSFilter::new`].
     pub fn new(writer: &'a mut dyn io::Write, k0: u64, k1: u64, m: u64, p: u8) -> GCSFilterWriter<'a> {
+        GCSFilterWriter::with_filter(writer, GCSFilter::new(k0, k1, p).expect("invalid Golomb-Rice parameter"), m)
+    }
+
+    /// Create a new GCS writer wrapping a generic writer, encoding with `filter`
+    pub fn with_filter(writer: &'a mut dyn io::Write, filter: GCSFilter, m: u64) -> GCSFilterWriter<'a> {
         GCSFilterWriter {
-            filter: GCSFilter::new(k0, k1, p),
+            filter,
             writer,
             elements: HashSet::new(),
             m
@@ -360,7 +409,7 @@ impl<'a> GCSFilterWriter<'a> {
 
         // map hashes to [0, n_elements * M)
         let mut mapped: Vec<_> = self.elements.iter()
-            .map(|e| map_to_range(self.filter.hash(e.as_slice()), nm)).collect();
+            .map(|e| self.filter.hash_to_range(e.as_slice(), nm)).collect();
         mapped.sort_unstable();
 
         // write number of elements as varint
@@ -380,21 +429,36 @@ impl<'a> GCSFilterWriter<'a> {
     }
 }
 
-/// Golomb Coded Set Filter
-struct GCSFilter {
+/// Golomb Coded Set Filter: the element hashing and Golomb-Rice coding shared by the filter
+/// reader and writer, for custom filter encodings
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GCSFilter {
     k0: u64, // sip hash key
     k1: u64, // sip hash key
     p: u8
 }
 
 impl GCSFilter {
-    /// Create a new filter
-    fn new(k0: u64, k1: u64, p: u8) -> GCSFilter {
-        GCSFilter { k0, k1, p }
+    /// Create a new filter, the Golomb-Rice parameter `p` must be below 64
+    pub fn new(k0: u64, k1: u64, p: u8) -> Result<GCSFilter, Error> {
+        if p >= 64 {
+            return Err(Error::InvalidParameter(p));
+        }
+        Ok(GCSFilter { k0, k1, p })
+    }
+
+    /// The siphash keys of the filter
+    pub fn keys(&self) -> (u64, u64) {
+        (self.k0, self.k1)
+    }
+
+    /// The Golomb-Rice parameter of the filter
+    pub fn p(&self) -> u8 {
+        self.p
     }
 
     /// Golomb-Rice encode a number n to a bit stream (Parameter 2^k)
-    fn golomb_rice_encode(&self, writer: &mut BitStreamWriter, n: u64) -> Result<usize, io::Error> {
+    pub fn golomb_rice_encode(&self, writer: &mut BitStreamWriter, n: u64) -> Result<usize, io::Error> {
         let mut wrote = 0;
         let mut q = n >> self.p;
         while q > 0 {
@@ -408,7 +472,7 @@ impl GCSFilter {
     }
 
     /// Golomb-Rice decode a number from a bit stream (Parameter 2^k)
-    fn golomb_rice_decode(&self, reader: &mut BitStreamReader) -> Result<u64, io::Error> {
+    pub fn golomb_rice_decode(&self, reader: &mut BitStreamReader) -> Result<u64, io::Error> {
         let mut q = 0u64;
         while reader.read(1)? == 1 {
             q += 1;
@@ -418,8 +482,13 @@ impl GCSFilter {
     }
 
     /// Hash an arbitrary slice with siphash using parameters of this filter
-    fn hash(&self, element: &[u8]) -> u64 {
-        siphash24::Hash::hash_to_u64_with_keys(self.k0, self.k1, element)
+    pub fn hash(&self, element: &[u8]) -> u64 {
+        SipHasher::hash(self.k0, self.k1, element)
+    }
+
+    /// Hash an arbitrary slice to [0, nm), the range of the set of n elements with parameter m
+    pub fn hash_to_range(&self, element: &[u8], nm: u64) -> u64 {
+        map_to_range(self.hash(element), nm)
     }
 }
 
@@ -522,6 +591,8 @@ mod test {
     use self::serde_json::Value;
 
     use consensus::encode::deserialize;
+    use blockdata::constants::genesis_block;
+    use network::constants::Network;
     use std::collections::HashMap;
 
     #[test]
@@ -571,6 +642,16 @@ mod test {
                 }
             }
 
+            let mut scripts: Vec<Script> = txmap.values().filter(|s| !s.is_empty()).cloned().collect();
+            assert!(filter.match_all_script(block_hash, &scripts).unwrap());
+            let unrelated = Script::from(vec![0x6a, 0x01, 0xff]);
+            assert!(!filter.match_any_script(block_hash, ::core::slice::from_ref(&unrelated)).unwrap());
+            if !scripts.is_empty() {
+                scripts.push(unrelated);
+                assert!(filter.match_any_script(block_hash, &scripts).unwrap());
+                assert!(!filter.match_all_script(block_hash, &scripts).unwrap());
+            }
+
             assert_eq!(filter_header, filter.filter_header(&previous_filter_header));
         }
     }
@@ -646,6 +727,52 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_custom_filter() {
+        let filter = GCSFilter::new(1, 2, 5).unwrap();
+        let mut out = Vec::new();
+        {
+            let mut writer = BitStreamWriter::new(&mut out);
+            for n in &[0, 31, 32, 1000] {
+                filter.golomb_rice_encode(&mut writer, *n).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        let mut input = Cursor::new(out);
+        let mut reader = BitStreamReader::new(&mut input);
+        for n in &[0, 31, 32, 1000] {
+            assert_eq!(filter.golomb_rice_decode(&mut reader).unwrap(), *n);
+        }
+
+        // a filter of the txids of a block, with a higher false positive rate
+        let block = genesis_block(Network::Bitcoin);
+        let block_hash = block.block_hash();
+        let (k0, k1) = filter_keys(&block_hash);
+        assert_eq!(endian::u64_to_array_le(k0)[..], block_hash[0..8]);
+        assert_eq!(endian::u64_to_array_le(k1)[..], block_hash[8..16]);
+        let mut out = Vec::new();
+        {
+            let mut writer = BlockFilterWriter::with_params(&mut out, &block, 1 << 10, 10).unwrap();
+            for tx in &block.txdata {
+                writer.add_element(&tx.txid()[..]);
+            }
+            writer.finish().unwrap();
+        }
+        let filter = BlockFilter::new(&out);
+        let txid = block.txdata[0].txid();
+        let reader = BlockFilterReader::with_params(&block_hash, 1 << 10, 10).unwrap();
+        assert!(reader.match_any(&mut Cursor::new(&filter.content), &mut Some(&txid[..]).into_iter()).unwrap());
+        assert!(!reader.match_any(&mut Cursor::new(&filter.content), &mut Some(&[0u8; 32][..]).into_iter()).unwrap());
+
+        assert!(GCSFilter::new(1, 2, 63).is_ok());
+        match GCSFilter::new(1, 2, 64) {
+            Err(Error::InvalidParameter(64)) => (),
+            _ => panic!("the Golomb-Rice parameter must be below 64"),
+        }
+        assert!(BlockFilterReader::with_params(&block_hash, 1 << 10, 64).is_err());
+        assert!(BlockFilterWriter::with_params(&mut Vec::new(), &block, 1 << 10, 255).is_err());
+    }
+
     #[test]
     fn test_bit_stream() {
         let mut out = Vec::new();
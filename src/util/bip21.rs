@@ -0,0 +1,374 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP21 payment URIs.
+//!
+//! Implementation of the payment URI scheme defined at
+//! <https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki>, using
+//! the `texitcoin:` scheme. URIs with the `bitcoin:` scheme are accepted when
+//! parsing, but the address must still be valid on this chain.
+//!
+//! ```rust
+//! use bitcoin::util::bip21::Uri;
+//! use bitcoin::Amount;
+//!
+//! let uri: Uri = "texitcoin:TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod?amount=0.5&label=Luke-Jr".parse().unwrap();
+//! assert_eq!(uri.amount, Some(Amount::from_sat(50_000_000)));
+//! assert_eq!(uri.label.as_ref().map(String::as_str), Some("Luke-Jr"));
+//! assert_eq!(uri.to_string(), "texitcoin:TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod?amount=0.5&label=Luke-Jr");
+//! ```
+//!
+
+use prelude::*;
+
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")] use std::error;
+
+use util::address::{self, Address};
+use util::amount::{Amount, Denomination, ParseAmountError};
+
+/// The URI scheme used when serializing payment URIs.
+pub const URI_SCHEME: &str = "texitcoin";
+
+/// URI schemes accepted when parsing payment URIs.
+const ACCEPTED_SCHEMES: [&str; 2] = [URI_SCHEME, "bitcoin"];
+
+/// A BIP21 URI parsing error.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// The URI does not start with a known scheme.
+    InvalidScheme,
+    /// Error parsing the address.
+    Address(address::Error),
+    /// Error parsing the amount.
+    Amount(ParseAmountError),
+    /// Invalid percent-encoding or invalid UTF-8 in a parameter.
+    InvalidEncoding,
+    /// A parameter is not of the form `key=value`.
+    MalformedParameter(String),
+    /// A parameter appears more than once.
+    DuplicateParameter(String),
+    /// A `req-` parameter that is not understood, which makes the URI invalid.
+    UnknownRequiredParameter(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidScheme => write!(f, "invalid URI scheme, expected {}:", URI_SCHEME),
+            Error::Address(ref e) => write!(f, "invalid address: {}", e),
+            Error::Amount(ref e) => write!(f, "invalid amount: {}", e),
+            Error::InvalidEncoding => f.write_str("invalid percent-encoding"),
+            Error::MalformedParameter(ref p) => write!(f, "malformed parameter: {}", p),
+            Error::DuplicateParameter(ref p) => write!(f, "duplicate parameter: {}", p),
+            Error::UnknownRequiredParameter(ref p) => write!(f, "unknown required parameter: {}", p),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Address(ref e) => Some(e),
+            Error::Amount(ref e) => Some(e),
+            Error::InvalidScheme
+            | Error::InvalidEncoding
+            | Error::MalformedParameter(_)
+            | Error::DuplicateParameter(_)
+            | Error::UnknownRequiredParameter(_) => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<address::Error> for Error {
+    fn from(e: address::Error) -> Error {
+        Error::Address(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<ParseAmountError> for Error {
+    fn from(e: ParseAmountError) -> Error {
+        Error::Amount(e)
+    }
+}
+
+/// A BIP21 payment URI.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Uri {
+    /// The address to pay to.
+    pub address: Address,
+    /// The requested amount.
+    pub amount: Option<Amount>,
+    /// A label for the address, e.g. the name of the receiver.
+    pub label: Option<String>,
+    /// A message describing the payment.
+    pub message: Option<String>,
+    /// Additional optional parameters, in the order they appear in the URI.
+    ///
+    /// Parameters starting with `req-` are never stored here: unknown required
+    /// parameters make parsing fail.
+    pub extras: Vec<(String, String)>,
+}
+
+impl Uri {
+    /// Creates a new URI paying to `address` without any parameters.
+    pub fn new(address: Address) -> Uri {
+        Uri {
+            address,
+            amount: None,
+            label: None,
+            message: None,
+            extras: Vec::new(),
+        }
+    }
+
+    /// Sets the requested amount.
+    pub fn with_amount(mut self, amount: Amount) -> Uri {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Sets the label.
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Uri {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the message.
+    pub fn with_message<S: Into<String>>(mut self, message: S) -> Uri {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Returns the value of the extra parameter `key`, if present.
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        self.extras.iter().find(|kv| kv.0 == key).map(|kv| kv.1.as_str())
+    }
+}
+
+/// Returns the numeric value of a hex digit.
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes a percent-encoded string.
+fn percent_decode(s: &str) -> Result<String, Error> {
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if i + 2 >= bytes.len() {
+                return Err(Error::InvalidEncoding);
+            }
+            match (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => ret.push(hi << 4 | lo),
+                _ => return Err(Error::InvalidEncoding),
+            }
+            i += 3;
+        } else {
+            ret.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(ret).map_err(|_| Error::InvalidEncoding)
+}
+
+/// Writes `s` percent-encoding everything but RFC 3986 unreserved characters.
+fn percent_encode(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => write!(f, "{}", b as char)?,
+            _ => write!(f, "%{:02X}", b)?,
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Uri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Uri, Error> {
+        let colon = s.find(':').ok_or(Error::InvalidScheme)?;
+        let scheme = &s[..colon];
+        if !ACCEPTED_SCHEMES.iter().any(|known| scheme.eq_ignore_ascii_case(known)) {
+            return Err(Error::InvalidScheme);
+        }
+        let rest = &s[colon + 1..];
+        let (address, query) = match rest.find('?') {
+            Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+            None => (rest, None),
+        };
+
+        let mut uri = Uri::new(Address::from_str(address)?);
+        let query = match query {
+            Some(query) => query,
+            None => return Ok(uri),
+        };
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let eq = param.find('=').ok_or_else(|| Error::MalformedParameter(param.to_owned()))?;
+            let key = percent_decode(&param[..eq])?;
+            let value = &param[eq + 1..];
+            match key.as_str() {
+                "amount" => {
+                    if uri.amount.is_some() {
+                        return Err(Error::DuplicateParameter(key));
+                    }
+                    uri.amount = Some(Amount::from_str_in(value, Denomination::Bitcoin)?);
+                }
+                "label" => {
+                    if uri.label.is_some() {
+                        return Err(Error::DuplicateParameter(key));
+                    }
+                    uri.label = Some(percent_decode(value)?);
+                }
+                "message" => {
+                    if uri.message.is_some() {
+                        return Err(Error::DuplicateParameter(key));
+                    }
+                    uri.message = Some(percent_decode(value)?);
+                }
+                _ if key.starts_with("req-") => return Err(Error::UnknownRequiredParameter(key)),
+                _ => {
+                    if uri.extra(&key).is_some() {
+                        return Err(Error::DuplicateParameter(key));
+                    }
+                    let value = percent_decode(value)?;
+                    uri.extras.push((key, value));
+                }
+            }
+        }
+        Ok(uri)
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", URI_SCHEME, self.address)?;
+        let mut sep = '?';
+        if let Some(amount) = self.amount {
+            // BIP21 amounts are plain decimals, so drop the insignificant zeros
+            let mut amount = amount.to_string_in(Denomination::Bitcoin);
+            while amount.contains('.') && amount.ends_with('0') {
+                amount.pop();
+            }
+            if amount.ends_with('.') {
+                amount.pop();
+            }
+            write!(f, "{}amount={}", sep, amount)?;
+            sep = '&';
+        }
+        if let Some(ref label) = self.label {
+            write!(f, "{}label=", sep)?;
+            percent_encode(f, label)?;
+            sep = '&';
+        }
+        if let Some(ref message) = self.message {
+            write!(f, "{}message=", sep)?;
+            percent_encode(f, message)?;
+            sep = '&';
+        }
+        for kv in &self.extras {
+            write!(f, "{}", sep)?;
+            percent_encode(f, &kv.0)?;
+            f.write_str("=")?;
+            percent_encode(f, &kv.1)?;
+            sep = '&';
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "TbL41FXUjpmTnkWhx2AKPMjqoq6MeRHYod";
+
+    #[test]
+    fn test_parse() {
+        let uri = Uri::from_str(&format!("texitcoin:{}", ADDRESS)).unwrap();
+        assert_eq!(uri, Uri::new(Address::from_str(ADDRESS).unwrap()));
+
+        let uri = Uri::from_str(&format!(
+            "bitcoin:{}?amount=20.3&label=Luke%20Jr&message=Donation%20for%20project%20xyz&somethingyoudontunderstand=50",
+            ADDRESS
+        )).unwrap();
+        assert_eq!(uri.amount, Some(Amount::from_sat(2_030_000_000)));
+        assert_eq!(uri.label, Some("Luke Jr".to_owned()));
+        assert_eq!(uri.message, Some("Donation for project xyz".to_owned()));
+        assert_eq!(uri.extra("somethingyoudontunderstand"), Some("50"));
+
+        let uri = Uri::from_str("TEXITCOIN:TXC1QVZVKJN4Q3NSZQXRV3NRAGA2R822XJTY3A2QUQH?amount=1").unwrap();
+        assert_eq!(uri.address.to_string(), "txc1qvzvkjn4q3nszqxrv3nraga2r822xjty3a2quqh");
+        assert_eq!(uri.amount, Some(Amount::from_sat(100_000_000)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Uri::from_str(ADDRESS), Err(Error::InvalidScheme));
+        assert_eq!(Uri::from_str(&format!("litecoin:{}", ADDRESS)), Err(Error::InvalidScheme));
+        assert!(match Uri::from_str("texitcoin:132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM") {
+            Err(Error::Address(_)) => true,
+            _ => false,
+        });
+        assert!(match Uri::from_str(&format!("texitcoin:{}?amount=1,5", ADDRESS)) {
+            Err(Error::Amount(_)) => true,
+            _ => false,
+        });
+        assert_eq!(
+            Uri::from_str(&format!("texitcoin:{}?req-somethingyoudontunderstand=50", ADDRESS)),
+            Err(Error::UnknownRequiredParameter("req-somethingyoudontunderstand".to_owned()))
+        );
+        assert_eq!(
+            Uri::from_str(&format!("texitcoin:{}?amount=1&amount=2", ADDRESS)),
+            Err(Error::DuplicateParameter("amount".to_owned()))
+        );
+        assert_eq!(
+            Uri::from_str(&format!("texitcoin:{}?label", ADDRESS)),
+            Err(Error::MalformedParameter("label".to_owned()))
+        );
+        assert_eq!(Uri::from_str(&format!("texitcoin:{}?label=%2", ADDRESS)), Err(Error::InvalidEncoding));
+        assert_eq!(Uri::from_str(&format!("texitcoin:{}?label=%ff", ADDRESS)), Err(Error::InvalidEncoding));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let uri = Uri::new(Address::from_str(ADDRESS).unwrap())
+            .with_amount(Amount::from_sat(123_450_000))
+            .with_label("Café & Co")
+            .with_message("Order #42");
+        let s = uri.to_string();
+        assert_eq!(
+            s,
+            format!("texitcoin:{}?amount=1.2345&label=Caf%C3%A9%20%26%20Co&message=Order%20%2342", ADDRESS)
+        );
+        assert_eq!(Uri::from_str(&s).unwrap(), uri);
+
+        let mut uri = Uri::new(Address::from_str(ADDRESS).unwrap()).with_amount(Amount::from_sat(100_000_000));
+        uri.extras.push(("pj".to_owned(), "https://example.com/pj".to_owned()));
+        let s = uri.to_string();
+        assert_eq!(s, format!("texitcoin:{}?amount=1&pj=https%3A%2F%2Fexample.com%2Fpj", ADDRESS));
+        assert_eq!(Uri::from_str(&s).unwrap(), uri);
+    }
+}
@@ -21,9 +21,10 @@ use prelude::*;
 
 use io::Write;
 use core::{fmt, str::FromStr, default::Default};
-use core::ops::Index;
+use core::ops::{Index, Range};
 #[cfg(feature = "std")] use std::error;
 #[cfg(feature = "serde")] use serde;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 use hash_types::XpubIdentifier;
 use hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine, hex};
@@ -39,12 +40,28 @@ pub struct ChainCode([u8; 32]);
 impl_array_newtype!(ChainCode, u8, 32);
 impl_bytes_newtype!(ChainCode, 32);
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for ChainCode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ChainCode(u.arbitrary()?))
+    }
+}
+
 /// A fingerprint
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Fingerprint([u8; 4]);
 impl_array_newtype!(Fingerprint, u8, 4);
 impl_bytes_newtype!(Fingerprint, 4);
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Fingerprint {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Fingerprint(u.arbitrary()?))
+    }
+}
+
 /// Extended private key
 #[derive(Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -172,6 +189,14 @@ impl From<u32> for ChildNumber {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for ChildNumber {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ChildNumber::from(u32::arbitrary(u)?))
+    }
+}
+
 impl From<ChildNumber> for u32 {
     fn from(cnum: ChildNumber) -> Self {
         match cnum {
@@ -283,6 +308,14 @@ impl From<Vec<ChildNumber>> for DerivationPath {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for DerivationPath {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(DerivationPath(u.arbitrary()?))
+    }
+}
+
 impl Into<Vec<ChildNumber>> for DerivationPath {
     fn into(self) -> Vec<ChildNumber> {
         self.0
@@ -574,6 +607,13 @@ impl ExtendedPrivKey {
         Ok(sk)
     }
 
+    /// Attempts to derive an extended private key from a path using the global context.
+    #[cfg(feature = "global-context")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+    pub fn derive_priv_global<P: AsRef<[ChildNumber]>>(&self, path: &P) -> Result<ExtendedPrivKey, Error> {
+        self.derive_priv(secp256k1::SECP256K1, path)
+    }
+
     /// Private->Private child key derivation
     pub fn ckd_priv<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>, i: ChildNumber) -> Result<ExtendedPrivKey, Error> {
         let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(&self.chain_code[..]);
@@ -657,6 +697,22 @@ impl ExtendedPrivKey {
     }
 }
 
+// Only the networks distinguished by the encoding are generated.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for ExtendedPubKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ExtendedPubKey {
+            network: *u.choose(&[Network::Bitcoin, Network::Testnet])?,
+            depth: u.arbitrary()?,
+            parent_fingerprint: u.arbitrary()?,
+            child_number: u.arbitrary()?,
+            public_key: key::arbitrary_secp_public_key(u)?,
+            chain_code: u.arbitrary()?,
+        })
+    }
+}
+
 impl ExtendedPubKey {
     /// Derives a public key from a private key
     #[deprecated(since = "0.28.0", note = "use ExtendedPubKey::from_priv")]
@@ -676,6 +732,13 @@ impl ExtendedPubKey {
         }
     }
 
+    /// Derives a public key from a private key using the global context.
+    #[cfg(feature = "global-context")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+    pub fn from_priv_global(sk: &ExtendedPrivKey) -> ExtendedPubKey {
+        ExtendedPubKey::from_priv(secp256k1::SECP256K1, sk)
+    }
+
     /// Constructs ECDSA compressed public key matching internal public key representation.
     pub fn to_pub(&self) -> PublicKey {
         PublicKey {
@@ -705,6 +768,13 @@ impl ExtendedPubKey {
         Ok(pk)
     }
 
+    /// Attempts to derive an extended public key from a path using the global context.
+    #[cfg(feature = "global-context")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+    pub fn derive_pub_global<P: AsRef<[ChildNumber]>>(&self, path: &P) -> Result<ExtendedPubKey, Error> {
+        self.derive_pub(secp256k1::SECP256K1, path)
+    }
+
     /// Compute the scalar tweak added to this key to get a child key
     pub fn ckd_pub_tweak(&self, i: ChildNumber) -> Result<(secp256k1::SecretKey, ChainCode), Error> {
         match i {
@@ -730,6 +800,16 @@ impl ExtendedPubKey {
         &self,
         secp: &Secp256k1<C>,
         i: ChildNumber,
+    ) -> Result<ExtendedPubKey, Error> {
+        self.ckd_pub_with_fingerprint(secp, i, self.fingerprint())
+    }
+
+    /// Public->Public child key derivation, with the fingerprint of `self` already computed.
+    fn ckd_pub_with_fingerprint<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        i: ChildNumber,
+        fingerprint: Fingerprint,
     ) -> Result<ExtendedPubKey, Error> {
         let (sk, chain_code) = self.ckd_pub_tweak(i)?;
         let mut pk = self.public_key;
@@ -738,13 +818,73 @@ impl ExtendedPubKey {
         Ok(ExtendedPubKey {
             network: self.network,
             depth: self.depth + 1,
-            parent_fingerprint: self.fingerprint(),
+            parent_fingerprint: fingerprint,
             child_number: i,
             public_key: pk,
             chain_code,
         })
     }
 
+    /// Derives the normal children `range` of the key at `path_prefix`, e.g. the receive
+    /// addresses `0..100` of an account with the prefix `m/0`.
+    ///
+    /// The key at `path_prefix` is derived only once, which makes this much faster than
+    /// calling [`ExtendedPubKey::derive_pub`] for every child. Scripts and addresses are
+    /// obtained from the returned keys, e.g. with [`Address::p2wpkh`](::util::address::Address::p2wpkh)
+    /// and [`ExtendedPubKey::to_pub`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if `path_prefix` contains a hardened step or `range` reaches into the hardened
+    /// indexes.
+    pub fn derive_range<C: secp256k1::Verification, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &Secp256k1<C>,
+        path_prefix: &P,
+        range: Range<u32>,
+    ) -> Result<Vec<ExtendedPubKey>, Error> {
+        let parent = self.derive_pub(secp, path_prefix)?;
+        let fingerprint = parent.fingerprint();
+        range.map(|index| {
+            parent.ckd_pub_with_fingerprint(secp, ChildNumber::from_normal_idx(index)?, fingerprint)
+        }).collect()
+    }
+
+    /// Scans the normal children of the key at `path_prefix` for used keys, the way wallets
+    /// discover their addresses when restoring.
+    ///
+    /// Children are derived in order and passed to `is_used`, until `gap_limit` consecutive
+    /// children are unused. Returns the index of the last used child, or `None` if none of
+    /// the children checked is used. BIP44 recommends a gap limit of 20.
+    pub fn scan_gap_limit<C, P, F>(
+        &self,
+        secp: &Secp256k1<C>,
+        path_prefix: &P,
+        gap_limit: u32,
+        mut is_used: F,
+    ) -> Result<Option<u32>, Error>
+    where
+        C: secp256k1::Verification,
+        P: AsRef<[ChildNumber]>,
+        F: FnMut(&ExtendedPubKey) -> bool,
+    {
+        let parent = self.derive_pub(secp, path_prefix)?;
+        let fingerprint = parent.fingerprint();
+        let mut last_used = None;
+        let mut index = 0;
+        loop {
+            let first_unused = last_used.map_or(0, |last_used| last_used + 1);
+            if index - first_unused >= gap_limit {
+                return Ok(last_used);
+            }
+            let child = parent.ckd_pub_with_fingerprint(secp, ChildNumber::from_normal_idx(index)?, fingerprint)?;
+            if is_used(&child) {
+                last_used = Some(index);
+            }
+            index += 1;
+        }
+    }
+
     /// Decoding extended public key from binary data according to BIP 32
     pub fn decode(data: &[u8]) -> Result<ExtendedPubKey, Error> {
         if data.len() != 78 {
@@ -1096,6 +1236,74 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_derive_range() {
+        let secp = Secp256k1::verification_only();
+        let xpub = ExtendedPubKey::from_str("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8").unwrap();
+        let prefix = DerivationPath::from_str("m/0/1").unwrap();
+
+        let keys = xpub.derive_range(&secp, &prefix, 5..10).unwrap();
+        assert_eq!(keys.len(), 5);
+        for (index, key) in (5..10).zip(keys.iter()) {
+            let path = prefix.child(ChildNumber::from_normal_idx(index).unwrap());
+            assert_eq!(*key, xpub.derive_pub(&secp, &path).unwrap());
+        }
+        assert!(xpub.derive_range(&secp, &prefix, 3..3).unwrap().is_empty());
+
+        assert_eq!(
+            xpub.derive_range(&secp, &prefix, (1 << 31) - 1..(1 << 31) + 1),
+            Err(Error::InvalidChildNumber(1 << 31))
+        );
+        assert_eq!(
+            xpub.derive_range(&secp, &DerivationPath::from_str("m/0h").unwrap(), 0..1),
+            Err(Error::CannotDeriveFromHardenedKey)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "global-context")]
+    fn test_global_context() {
+        let secp = Secp256k1::new();
+        let seed = Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let sk = ExtendedPrivKey::new_master(Network::Bitcoin, &seed).unwrap();
+        let path = DerivationPath::from_str("m/0h/1/2h").unwrap();
+        let normal_path = DerivationPath::from_str("m/0/1").unwrap();
+
+        let child = sk.derive_priv_global(&path).unwrap();
+        assert_eq!(child, sk.derive_priv(&secp, &path).unwrap());
+        let pk = ExtendedPubKey::from_priv_global(&child);
+        assert_eq!(pk, ExtendedPubKey::from_priv(&secp, &child));
+        assert_eq!(pk.derive_pub_global(&normal_path).unwrap(), pk.derive_pub(&secp, &normal_path).unwrap());
+        assert_eq!(pk.derive_pub_global(&path), Err(Error::CannotDeriveFromHardenedKey));
+    }
+
+    #[test]
+    fn test_scan_gap_limit() {
+        let secp = Secp256k1::verification_only();
+        let xpub = ExtendedPubKey::from_str("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8").unwrap();
+        let prefix = DerivationPath::from_str("m/0").unwrap();
+        let used: Vec<_> = xpub.derive_range(&secp, &prefix, 0..30).unwrap()
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| index == 0 || index == 3 || index == 25)
+            .map(|(_, key)| key.public_key)
+            .collect();
+
+        let mut checked = 0;
+        let last_used = xpub.scan_gap_limit(&secp, &prefix, 20, |key| {
+            checked += 1;
+            used.contains(&key.public_key)
+        }).unwrap();
+        assert_eq!(last_used, Some(3));
+        assert_eq!(checked, 24);
+
+        let scan = |gap_limit| xpub.scan_gap_limit(&secp, &prefix, gap_limit, |key| used.contains(&key.public_key)).unwrap();
+        assert_eq!(scan(22), Some(25));
+        assert_eq!(scan(1), Some(0));
+        assert_eq!(scan(0), None);
+        assert_eq!(xpub.scan_gap_limit(&secp, &prefix, 20, |_| false).unwrap(), None);
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     pub fn encode_decode_childnumber() {
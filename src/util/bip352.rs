@@ -0,0 +1,588 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP352 silent payments.
+//!
+//! Implementation of silent payment addresses, sender output derivation and
+//! receiver scanning as defined at
+//! <https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki>.
+//!
+//! A silent payment address carries a scan and a spend public key. The sender
+//! tweaks the spend key with an ECDH secret shared between its input keys and
+//! the scan key, so that every payment lands on a fresh taproot output which
+//! only the receiver can detect by scanning transactions.
+//!
+
+use prelude::*;
+
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")] use std::error;
+
+use bech32::{self, FromBase32, ToBase32};
+use hashes::{hash160, sha256, Hash, HashEngine};
+use secp256k1::{self, PublicKey, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey};
+
+use blockdata::transaction::{OutPoint, TxIn, TxOut};
+use blockdata::script::{Instruction, Script};
+use consensus::encode::serialize;
+use network::constants::Network;
+use util::endian;
+use util::taproot::TAPROOT_ANNEX_PREFIX;
+
+/// Human-readable part of mainnet silent payment addresses.
+///
+/// Differs from the `sp` prefix used by bitcoin so that bitcoin silent payment
+/// addresses are not mistaken for texitcoin ones.
+pub const HRP_MAIN: &str = "txsp";
+/// Human-readable part of testnet and signet silent payment addresses.
+pub const HRP_TEST: &str = "tsp";
+/// Human-readable part of regtest silent payment addresses.
+pub const HRP_REGTEST: &str = "sprt";
+
+/// The x coordinate of the provably unspendable point `H` of BIP341; taproot
+/// inputs spent through a script path with this internal key are ignored.
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Silent payment error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Bech32m encoding error.
+    Bech32(bech32::Error),
+    /// The address was not encoded with bech32m.
+    InvalidVariant,
+    /// Unknown human-readable part.
+    UnknownHrp(String),
+    /// Unsupported address version.
+    InvalidVersion(u8),
+    /// Invalid address payload length.
+    InvalidLength(usize),
+    /// The transaction has no eligible inputs.
+    NoInputs,
+    /// Secp256k1 error, e.g. input keys summing up to zero.
+    Secp256k1(secp256k1::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Bech32(ref e) => write!(f, "bech32m error: {}", e),
+            Error::InvalidVariant => f.write_str("silent payment address is not bech32m encoded"),
+            Error::UnknownHrp(ref hrp) => write!(f, "unknown silent payment address prefix: {}", hrp),
+            Error::InvalidVersion(v) => write!(f, "invalid silent payment address version: {}", v),
+            Error::InvalidLength(l) => write!(f, "invalid silent payment address payload length: {}", l),
+            Error::NoInputs => f.write_str("no eligible inputs"),
+            Error::Secp256k1(ref e) => write!(f, "secp256k1 error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Bech32(ref e) => Some(e),
+            Error::Secp256k1(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<bech32::Error> for Error {
+    fn from(e: bech32::Error) -> Error {
+        Error::Bech32(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Error {
+        Error::Secp256k1(e)
+    }
+}
+
+/// A silent payment address.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SilentPaymentAddress {
+    /// The network the address is used on.
+    pub network: Network,
+    /// The key used by the receiver to scan for payments.
+    pub scan_key: PublicKey,
+    /// The key tweaked by the sender, optionally labelled.
+    pub spend_key: PublicKey,
+}
+
+impl SilentPaymentAddress {
+    /// Creates a new version 0 silent payment address.
+    pub fn new(scan_key: PublicKey, spend_key: PublicKey, network: Network) -> SilentPaymentAddress {
+        SilentPaymentAddress { network, scan_key, spend_key }
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hrp = match self.network {
+            Network::Bitcoin => HRP_MAIN,
+            Network::Testnet | Network::Signet => HRP_TEST,
+            Network::Regtest => HRP_REGTEST,
+        };
+        let mut payload = Vec::with_capacity(66);
+        payload.extend_from_slice(&self.scan_key.serialize());
+        payload.extend_from_slice(&self.spend_key.serialize());
+        let mut data = vec![bech32::u5::try_from_u8(0).expect("0 is a valid u5")];
+        data.extend(payload.to_base32());
+        let mut writer = bech32::Bech32Writer::new(hrp, bech32::Variant::Bech32m, f)?;
+        bech32::WriteBase32::write(&mut writer, &data)?;
+        writer.finalize()
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SilentPaymentAddress, Error> {
+        let (hrp, data, variant) = bech32::decode(s)?;
+        let network = match hrp.as_str() {
+            HRP_MAIN => Network::Bitcoin,
+            HRP_TEST => Network::Testnet,
+            HRP_REGTEST => Network::Regtest,
+            _ => return Err(Error::UnknownHrp(hrp)),
+        };
+        if variant != bech32::Variant::Bech32m {
+            return Err(Error::InvalidVariant);
+        }
+        let version = match data.first() {
+            Some(v) => v.to_u8(),
+            None => return Err(Error::InvalidLength(0)),
+        };
+        if version == 31 {
+            return Err(Error::InvalidVersion(version));
+        }
+        let payload = Vec::<u8>::from_base32(&data[1..])?;
+        // Future versions must keep the version 0 keys as a prefix.
+        if payload.len() < 66 || (version == 0 && payload.len() != 66) {
+            return Err(Error::InvalidLength(payload.len()));
+        }
+        Ok(SilentPaymentAddress {
+            network,
+            scan_key: PublicKey::from_slice(&payload[..33])?,
+            spend_key: PublicKey::from_slice(&payload[33..66])?,
+        })
+    }
+}
+
+/// The private key of a transaction input, as seen by the sender.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputKey {
+    /// The key of a P2PKH, P2WPKH or P2SH-P2WPKH input.
+    Ecdsa(SecretKey),
+    /// The (tweaked) key of a key path spent P2TR input.
+    Taproot(SecretKey),
+}
+
+/// Computes a BIP340-style tagged hash over the concatenation of `data`.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag_hash[..]);
+    engine.input(&tag_hash[..]);
+    for d in data {
+        engine.input(d);
+    }
+    sha256::Hash::from_engine(engine).into_inner()
+}
+
+/// Computes `hash_BIP0352/Inputs(outpoint_L || A)`.
+fn input_hash(outpoints: &[OutPoint], input_key: &PublicKey) -> Result<[u8; 32], Error> {
+    let smallest = outpoints.iter().map(serialize).min().ok_or(Error::NoInputs)?;
+    Ok(tagged_hash("BIP0352/Inputs", &[&smallest, &input_key.serialize()]))
+}
+
+/// Computes the tweak `t_k` for the `k`-th output paid to the same scan key.
+fn shared_secret_tweak(ecdh_shared_secret: &PublicKey, k: u32) -> [u8; 32] {
+    tagged_hash("BIP0352/SharedSecret", &[&ecdh_shared_secret.serialize(), &endian::u32_to_array_be(k)])
+}
+
+/// Computes the tweak of label `m` for the given scan key.
+fn label_tweak(scan_key: &SecretKey, m: u32) -> [u8; 32] {
+    tagged_hash("BIP0352/Label", &[&scan_key[..], &endian::u32_to_array_be(m)])
+}
+
+/// Lifts an x-only key to the full public key with even y coordinate.
+fn lift_x(key: &XOnlyPublicKey) -> PublicKey {
+    let mut bytes = [0x02; 33];
+    bytes[1..].copy_from_slice(&key.serialize());
+    PublicKey::from_slice(&bytes).expect("valid x-only key lifts to a valid point")
+}
+
+/// Extracts the public key of an input eligible for silent payments.
+///
+/// `prevout` is the output spent by `txin`. Returns `None` for inputs which
+/// do not contribute to the shared secret: non-standard types, uncompressed
+/// keys and taproot script path spends using the NUMS internal key.
+///
+/// For P2PKH inputs the last push of the scriptSig is used, i.e. malleated
+/// scriptSigs are not searched for the key.
+pub fn input_public_key(txin: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    fn compressed_key(bytes: &[u8]) -> Option<PublicKey> {
+        if bytes.len() == 33 && (bytes[0] == 0x02 || bytes[0] == 0x03) {
+            PublicKey::from_slice(bytes).ok()
+        } else {
+            None
+        }
+    }
+    fn last_push(script: &Script) -> Option<&[u8]> {
+        match script.instructions().last() {
+            Some(Ok(Instruction::PushBytes(bytes))) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    let spk = &prevout.script_pubkey;
+    if spk.is_p2pkh() {
+        let bytes = last_push(&txin.script_sig)?;
+        if hash160::Hash::hash(bytes)[..] != spk[3..23] {
+            return None;
+        }
+        compressed_key(bytes)
+    } else if spk.is_p2sh() {
+        if !Script::from(last_push(&txin.script_sig)?.to_vec()).is_v0_p2wpkh() {
+            return None;
+        }
+        compressed_key(txin.witness.last()?)
+    } else if spk.is_v0_p2wpkh() {
+        compressed_key(txin.witness.last()?)
+    } else if spk.is_v1_p2tr() {
+        let mut stack: Vec<&[u8]> = txin.witness.iter().collect();
+        if stack.len() > 1 && stack.last().map_or(false, |e| e.first() == Some(&TAPROOT_ANNEX_PREFIX)) {
+            stack.pop();
+        }
+        if stack.len() > 1 {
+            let control_block = stack.last()?;
+            if control_block.len() >= 33 && control_block[1..33] == NUMS_H[..] {
+                return None;
+            }
+        }
+        XOnlyPublicKey::from_slice(&spk[2..34]).ok().map(|key| lift_x(&key))
+    } else {
+        None
+    }
+}
+
+/// Sender: derives the taproot output keys paying the silent payment `recipients`.
+///
+/// `input_keys` are the private keys of all eligible inputs of the transaction
+/// and `outpoints` the outpoints of all its inputs. The returned keys are in
+/// the order of `recipients`; repeated recipients or recipients sharing a scan
+/// key get distinct outputs.
+pub fn sender_outputs<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    input_keys: &[InputKey],
+    outpoints: &[OutPoint],
+    recipients: &[SilentPaymentAddress],
+) -> Result<Vec<XOnlyPublicKey>, Error> {
+    let mut sum: Option<SecretKey> = None;
+    for key in input_keys {
+        let mut sk = match *key {
+            InputKey::Ecdsa(sk) => sk,
+            InputKey::Taproot(sk) => {
+                let mut sk = sk;
+                if PublicKey::from_secret_key(secp, &sk).serialize()[0] == 0x03 {
+                    sk.negate_assign();
+                }
+                sk
+            }
+        };
+        if let Some(prev) = sum {
+            sk.add_assign(&prev[..])?;
+        }
+        sum = Some(sk);
+    }
+    let mut tweak = sum.ok_or(Error::NoInputs)?;
+    let input_hash = input_hash(outpoints, &PublicKey::from_secret_key(secp, &tweak))?;
+    tweak.mul_assign(&input_hash)?;
+
+    let mut counters: BTreeMap<PublicKey, u32> = BTreeMap::new();
+    let mut ret = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let mut ecdh_shared_secret = recipient.scan_key;
+        ecdh_shared_secret.mul_assign(secp, &tweak[..])?;
+        let k = counters.entry(recipient.scan_key).or_insert(0);
+        let mut output = recipient.spend_key;
+        output.add_exp_assign(secp, &shared_secret_tweak(&ecdh_shared_secret, *k))?;
+        *k += 1;
+        ret.push(XOnlyPublicKey::from(output));
+    }
+    Ok(ret)
+}
+
+/// An output found by [`Receiver::scan`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ReceivedOutput {
+    /// The taproot output key paying the receiver.
+    pub output: XOnlyPublicKey,
+    /// The tweak to add to the spend private key to spend the output.
+    pub tweak: SecretKey,
+    /// The label the output was paid to, if any.
+    pub label: Option<u32>,
+}
+
+impl ReceivedOutput {
+    /// Computes the private key spending this output from the receiver's spend key.
+    pub fn spending_key(&self, spend_key: &SecretKey) -> Result<SecretKey, Error> {
+        let mut ret = *spend_key;
+        ret.add_assign(&self.tweak[..])?;
+        Ok(ret)
+    }
+}
+
+/// Silent payment receiver, scanning transactions for outputs it can spend.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Receiver {
+    scan_key: SecretKey,
+    spend_key: PublicKey,
+    /// Label tweak points `hash(b_scan || m)·G` mapped to `m` and the tweak.
+    labels: BTreeMap<PublicKey, (u32, SecretKey)>,
+}
+
+impl Receiver {
+    /// Creates a receiver from the scan private key and the spend public key.
+    pub fn new(scan_key: SecretKey, spend_key: PublicKey) -> Receiver {
+        Receiver { scan_key, spend_key, labels: BTreeMap::new() }
+    }
+
+    /// Returns the unlabelled silent payment address of this receiver.
+    pub fn address<C: Signing>(&self, secp: &Secp256k1<C>, network: Network) -> SilentPaymentAddress {
+        SilentPaymentAddress::new(PublicKey::from_secret_key(secp, &self.scan_key), self.spend_key, network)
+    }
+
+    /// Registers label `m` for scanning and returns the labelled address.
+    ///
+    /// BIP352 reserves label 0 for change.
+    pub fn add_label<C: Signing + Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        m: u32,
+        network: Network,
+    ) -> Result<SilentPaymentAddress, Error> {
+        let tweak = SecretKey::from_slice(&label_tweak(&self.scan_key, m))?;
+        let point = PublicKey::from_secret_key(secp, &tweak);
+        let spend_key = self.spend_key.combine(&point)?;
+        self.labels.insert(point, (m, tweak));
+        Ok(SilentPaymentAddress::new(PublicKey::from_secret_key(secp, &self.scan_key), spend_key, network))
+    }
+
+    /// Scans the taproot output keys of a transaction for payments to this receiver.
+    ///
+    /// `input_keys` are the keys of the eligible inputs as returned by
+    /// [`input_public_key`] and `outpoints` the outpoints of all inputs.
+    pub fn scan<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        input_keys: &[PublicKey],
+        outpoints: &[OutPoint],
+        outputs: &[XOnlyPublicKey],
+    ) -> Result<Vec<ReceivedOutput>, Error> {
+        if input_keys.is_empty() {
+            return Err(Error::NoInputs);
+        }
+        let input_key = PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>())?;
+        let mut ecdh_shared_secret = input_key;
+        ecdh_shared_secret.mul_assign(secp, &input_hash(outpoints, &input_key)?)?;
+        ecdh_shared_secret.mul_assign(secp, &self.scan_key[..])?;
+
+        let mut remaining: Vec<XOnlyPublicKey> = outputs.to_vec();
+        let mut ret = vec![];
+        let mut k = 0;
+        loop {
+            let t_k = SecretKey::from_slice(&shared_secret_tweak(&ecdh_shared_secret, k))?;
+            let mut p_k = self.spend_key;
+            p_k.add_exp_assign(secp, &t_k[..])?;
+            let mut neg_p_k = p_k;
+            neg_p_k.negate_assign(secp);
+
+            let mut found = None;
+            for (i, output) in remaining.iter().enumerate() {
+                if XOnlyPublicKey::from(p_k) == *output {
+                    found = Some((i, ReceivedOutput { output: *output, tweak: t_k, label: None }));
+                    break;
+                }
+                if self.labels.is_empty() {
+                    continue;
+                }
+                let mut neg_output = lift_x(output);
+                neg_output.negate_assign(secp);
+                let candidates = [lift_x(output).combine(&neg_p_k), neg_output.combine(&neg_p_k)];
+                let label = candidates.iter().filter_map(|c| c.as_ref().ok()).filter_map(|c| self.labels.get(c)).next();
+                if let Some(&(m, label_tweak)) = label {
+                    let mut tweak = t_k;
+                    tweak.add_assign(&label_tweak[..])?;
+                    found = Some((i, ReceivedOutput { output: *output, tweak, label: Some(m) }));
+                    break;
+                }
+            }
+            match found {
+                Some((i, received)) => {
+                    remaining.remove(i);
+                    ret.push(received);
+                    k += 1;
+                }
+                None => return Ok(ret),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hashes::hex::FromHex;
+    use blockdata::witness::Witness;
+    use hash_types::Txid;
+
+    fn secret_key(n: u8) -> SecretKey {
+        SecretKey::from_slice(&[n; 32]).unwrap()
+    }
+
+    fn outpoint(n: u8, vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), vout)
+    }
+
+    #[test]
+    fn test_address_roundtrip() {
+        let secp = Secp256k1::new();
+        let scan_key = PublicKey::from_secret_key(&secp, &secret_key(1));
+        let spend_key = PublicKey::from_secret_key(&secp, &secret_key(2));
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Regtest] {
+            let address = SilentPaymentAddress::new(scan_key, spend_key, network);
+            let s = address.to_string();
+            assert_eq!(s.parse::<SilentPaymentAddress>(), Ok(address));
+            assert_eq!(s.to_uppercase().parse::<SilentPaymentAddress>(), Ok(address));
+        }
+        let s = SilentPaymentAddress::new(scan_key, spend_key, Network::Bitcoin).to_string();
+        assert!(s.starts_with("txsp1q"));
+        assert_eq!(s.len(), 4 + 1 + 1 + 106 + 6);
+
+        // version 0 must have an exact payload length, future versions may extend it
+        let mut payload = scan_key.serialize().to_vec();
+        payload.extend_from_slice(&spend_key.serialize());
+        payload.push(0);
+        let encode = |version: u8, payload: &[u8]| {
+            let mut data = vec![bech32::u5::try_from_u8(version).unwrap()];
+            data.extend(payload.to_base32());
+            bech32::encode(HRP_TEST, data, bech32::Variant::Bech32m).unwrap()
+        };
+        assert_eq!(encode(0, &payload).parse::<SilentPaymentAddress>(), Err(Error::InvalidLength(67)));
+        assert_eq!(encode(1, &payload).parse::<SilentPaymentAddress>().unwrap().spend_key, spend_key);
+        assert_eq!(encode(31, &payload).parse::<SilentPaymentAddress>(), Err(Error::InvalidVersion(31)));
+
+        let bech32 = bech32::encode("sp", vec![bech32::u5::try_from_u8(0).unwrap()], bech32::Variant::Bech32m).unwrap();
+        assert_eq!(bech32.parse::<SilentPaymentAddress>(), Err(Error::UnknownHrp("sp".to_owned())));
+    }
+
+    #[test]
+    fn test_send_and_receive() {
+        let secp = Secp256k1::new();
+        let (scan_sk, spend_sk) = (secret_key(1), secret_key(2));
+        let mut receiver = Receiver::new(scan_sk, PublicKey::from_secret_key(&secp, &spend_sk));
+        let address = receiver.address(&secp, Network::Bitcoin);
+        let labelled = receiver.add_label(&secp, 7, Network::Bitcoin).unwrap();
+        assert_eq!(labelled.scan_key, address.scan_key);
+        assert_ne!(labelled.spend_key, address.spend_key);
+
+        let input_keys = [InputKey::Ecdsa(secret_key(3)), InputKey::Taproot(secret_key(4))];
+        let input_pubkeys = [
+            PublicKey::from_secret_key(&secp, &secret_key(3)),
+            lift_x(&XOnlyPublicKey::from(PublicKey::from_secret_key(&secp, &secret_key(4)))),
+        ];
+        let outpoints = [outpoint(0xff, 1), outpoint(0xff, 0)];
+        let other = SilentPaymentAddress::new(input_pubkeys[0], input_pubkeys[1], Network::Bitcoin);
+
+        let outputs = sender_outputs(&secp, &input_keys, &outpoints, &[address, other, labelled, address]).unwrap();
+        assert_eq!(outputs.len(), 4);
+        assert_ne!(outputs[0], outputs[3]);
+
+        // the receiver finds its three outputs regardless of the output order
+        let shuffled = [outputs[3], outputs[1], outputs[2], outputs[0]];
+        let received = receiver.scan(&secp, &input_pubkeys, &outpoints, &shuffled).unwrap();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received.iter().filter(|r| r.label == Some(7)).count(), 1);
+        for r in &received {
+            assert!(outputs.contains(&r.output));
+            let sk = r.spending_key(&spend_sk).unwrap();
+            assert_eq!(XOnlyPublicKey::from(PublicKey::from_secret_key(&secp, &sk)), r.output);
+        }
+
+        // the outpoint order does not matter but the outpoints do
+        let reordered = [outpoints[1], outpoints[0]];
+        assert_eq!(receiver.scan(&secp, &input_pubkeys, &reordered, &outputs).unwrap().len(), 3);
+        let other_outpoints = [outpoint(0xfe, 0)];
+        assert!(receiver.scan(&secp, &input_pubkeys, &other_outpoints, &outputs).unwrap().is_empty());
+
+        // without the label, scanning stops at the labelled output (k = 1)
+        let unlabelled = Receiver::new(scan_sk, PublicKey::from_secret_key(&secp, &spend_sk));
+        let received = unlabelled.scan(&secp, &input_pubkeys, &outpoints, &outputs).unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].output, outputs[0]);
+
+        assert_eq!(sender_outputs(&secp, &[], &outpoints, &[address]), Err(Error::NoInputs));
+        assert_eq!(receiver.scan(&secp, &[], &outpoints, &outputs), Err(Error::NoInputs));
+    }
+
+    #[test]
+    fn test_input_public_key() {
+        let secp = Secp256k1::new();
+        let pk = ::PublicKey::new(PublicKey::from_secret_key(&secp, &secret_key(5)));
+        let sig = Vec::<u8>::from_hex("3044022000000000000000000000000000000000000000000000000000000000000000010220000000000000000000000000000000000000000000000000000000000000000101").unwrap();
+        let txin = |script_sig: Script, witness: Vec<Vec<u8>>| TxIn {
+            previous_output: outpoint(1, 0),
+            script_sig,
+            sequence: 0xffffffff,
+            witness: Witness::from_vec(witness),
+        };
+        let prevout = |script_pubkey: Script| TxOut { value: 1000, script_pubkey };
+
+        let wpkh = Script::new_v0_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let p2wpkh = txin(Script::new(), vec![sig.clone(), pk.to_bytes()]);
+        assert_eq!(input_public_key(&p2wpkh, &prevout(wpkh.clone())), Some(pk.inner));
+
+        let nested = txin(::blockdata::script::Builder::new().push_slice(wpkh.as_bytes()).into_script(), vec![sig.clone(), pk.to_bytes()]);
+        assert_eq!(input_public_key(&nested, &prevout(Script::new_p2sh(&wpkh.script_hash()))), Some(pk.inner));
+
+        let p2pkh = txin(::blockdata::script::Builder::new().push_slice(&sig).push_key(&pk).into_script(), vec![]);
+        assert_eq!(input_public_key(&p2pkh, &prevout(Script::new_p2pkh(&pk.pubkey_hash()))), Some(pk.inner));
+        let other = ::PublicKey::new(PublicKey::from_secret_key(&secp, &secret_key(6)));
+        assert_eq!(input_public_key(&p2pkh, &prevout(Script::new_p2pkh(&other.pubkey_hash()))), None);
+
+        let mut uncompressed = pk;
+        uncompressed.compressed = false;
+        let p2pkh = txin(::blockdata::script::Builder::new().push_slice(&sig).push_key(&uncompressed).into_script(), vec![]);
+        assert_eq!(input_public_key(&p2pkh, &prevout(Script::new_p2pkh(&uncompressed.pubkey_hash()))), None);
+
+        let output_key = XOnlyPublicKey::from(pk.inner);
+        let tr = Script::new_v1_p2tr_tweaked(::util::schnorr::TweakedPublicKey::dangerous_assume_tweaked(output_key));
+        let keypath = txin(Script::new(), vec![vec![1; 64]]);
+        assert_eq!(input_public_key(&keypath, &prevout(tr.clone())), Some(lift_x(&output_key)));
+        let mut control_block = vec![0xc0];
+        control_block.extend_from_slice(&NUMS_H);
+        let nums = txin(Script::new(), vec![vec![1; 64], vec![0x51], control_block]);
+        assert_eq!(input_public_key(&nums, &prevout(tr)), None);
+    }
+}
@@ -0,0 +1,486 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP38 passphrase-protected private keys.
+//!
+//! Implementation of BIP38 encryption and decryption of private keys, as
+//! defined at <https://github.com/bitcoin/bips/blob/master/bip-0038.mediawiki>.
+//! Both the plain ("non-EC-multiply") mode and the EC-multiply mode using
+//! intermediate passphrase codes are supported.
+//!
+//! The address hash embedded in an encrypted key is computed over the base58
+//! P2PKH address of the key, so it depends on the network the key is used on.
+//!
+//! Passphrases are used as given; callers are responsible for applying Unicode
+//! NFC normalization as required by the BIP.
+//!
+
+use prelude::*;
+
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")] use std::error;
+
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use hashes::{sha256d, Hash, HashEngine};
+use secp256k1::{self, Secp256k1};
+
+use network::constants::Network;
+use util::address::Address;
+use util::base58;
+use util::key::{PrivateKey, PublicKey};
+
+/// Prefix of keys encrypted without EC multiplication ("6P..." keys).
+const NON_EC_PREFIX: [u8; 2] = [0x01, 0x42];
+/// Prefix of keys encrypted with EC multiplication.
+const EC_PREFIX: [u8; 2] = [0x01, 0x43];
+/// Magic bytes of an intermediate passphrase code, without the final lot/sequence byte.
+const INTERMEDIATE_MAGIC: [u8; 7] = [0x2c, 0xe9, 0xb3, 0xe1, 0xff, 0x39, 0xe2];
+/// Final magic byte of an intermediate code without lot and sequence numbers.
+const MAGIC_NO_LOT_SEQUENCE: u8 = 0x51;
+/// Final magic byte of an intermediate code with lot and sequence numbers.
+const MAGIC_LOT_SEQUENCE: u8 = 0x53;
+
+/// Flag bits which are always set for non-EC-multiplied keys.
+const FLAG_NON_EC: u8 = 0xc0;
+/// Flag bit set when the key corresponds to a compressed public key.
+const FLAG_COMPRESSED: u8 = 0x20;
+/// Flag bit set when the owner entropy includes lot and sequence numbers.
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+
+/// Length of a decoded encrypted key, excluding the base58 checksum.
+const ENCRYPTED_KEY_LEN: usize = 39;
+/// Length of a decoded intermediate code, excluding the base58 checksum.
+const INTERMEDIATE_CODE_LEN: usize = 49;
+
+/// Largest lot number which can be encoded in an intermediate code.
+pub const MAX_LOT: u32 = 1_048_575;
+/// Largest sequence number which can be encoded in an intermediate code.
+pub const MAX_SEQUENCE: u32 = 4095;
+
+/// A BIP38 error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Base58 decoding error.
+    Base58(base58::Error),
+    /// secp256k1-related error.
+    Secp256k1(secp256k1::Error),
+    /// The decoded data has an unexpected length.
+    InvalidLength(usize),
+    /// The decoded data does not start with a known BIP38 prefix.
+    InvalidPrefix,
+    /// The flag byte has reserved bits set.
+    InvalidFlag(u8),
+    /// The lot or sequence number is out of range.
+    InvalidLotSequence(u32, u32),
+    /// The decrypted key does not match the embedded address hash, usually
+    /// because of a wrong passphrase or network.
+    AddressHashMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Base58(ref e) => write!(f, "base58 error: {}", e),
+            Error::Secp256k1(ref e) => write!(f, "secp256k1 error: {}", e),
+            Error::InvalidLength(len) => write!(f, "invalid BIP38 data length: {}", len),
+            Error::InvalidPrefix => f.write_str("invalid BIP38 prefix"),
+            Error::InvalidFlag(flag) => write!(f, "invalid BIP38 flag byte: {:#04x}", flag),
+            Error::InvalidLotSequence(lot, seq) => write!(f, "invalid lot/sequence number: {}/{}", lot, seq),
+            Error::AddressHashMismatch => f.write_str("address hash mismatch (wrong passphrase or network?)"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Base58(ref e) => Some(e),
+            Error::Secp256k1(ref e) => Some(e),
+            Error::InvalidLength(_)
+            | Error::InvalidPrefix
+            | Error::InvalidFlag(_)
+            | Error::InvalidLotSequence(_, _)
+            | Error::AddressHashMismatch => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<base58::Error> for Error {
+    fn from(e: base58::Error) -> Error {
+        Error::Base58(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Error {
+        Error::Secp256k1(e)
+    }
+}
+
+/// Runs scrypt with the given cost parameters; all parameters used by BIP38 are valid.
+fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, output: &mut [u8]) {
+    let params = ::scrypt::Params::new(log_n, r, p).expect("valid scrypt parameters");
+    ::scrypt::scrypt(password, salt, &params, output).expect("valid scrypt output length");
+}
+
+/// Computes the first four bytes of the double-SHA256 of the P2PKH address of `pk`.
+fn address_hash(pk: &PublicKey, network: Network) -> [u8; 4] {
+    let address = Address::p2pkh(pk, network).to_string();
+    let hash = sha256d::Hash::hash(address.as_bytes());
+    let mut ret = [0; 4];
+    ret.copy_from_slice(&hash[..4]);
+    ret
+}
+
+/// XORs `block` with `mask` and encrypts it in place with AES-256.
+fn encrypt_block(cipher: &Aes256, block: &mut [u8], mask: &[u8]) {
+    for (b, m) in block.iter_mut().zip(mask.iter()) {
+        *b ^= *m;
+    }
+    cipher.encrypt_block(GenericArray::from_mut_slice(block));
+}
+
+/// Decrypts `block` in place with AES-256 and XORs it with `mask`.
+fn decrypt_block(cipher: &Aes256, block: &mut [u8], mask: &[u8]) {
+    cipher.decrypt_block(GenericArray::from_mut_slice(block));
+    for (b, m) in block.iter_mut().zip(mask.iter()) {
+        *b ^= *m;
+    }
+}
+
+/// Computes the passfactor from the passphrase and owner entropy.
+fn passfactor(passphrase: &str, owner_entropy: &[u8; 8], lot_sequence: bool) -> Result<secp256k1::SecretKey, Error> {
+    let owner_salt = if lot_sequence { &owner_entropy[..4] } else { &owner_entropy[..] };
+    let mut prefactor = [0; 32];
+    scrypt(passphrase.as_bytes(), owner_salt, 14, 8, 8, &mut prefactor);
+    if lot_sequence {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(&prefactor);
+        engine.input(owner_entropy);
+        let hash = sha256d::Hash::from_engine(engine);
+        Ok(secp256k1::SecretKey::from_slice(&hash[..])?)
+    } else {
+        Ok(secp256k1::SecretKey::from_slice(&prefactor)?)
+    }
+}
+
+/// Derives the two 32-byte halves used to encrypt an EC-multiplied key.
+fn ec_derived_halves(passpoint: &secp256k1::PublicKey, address_hash: &[u8; 4], owner_entropy: &[u8; 8]) -> [u8; 64] {
+    let mut salt = [0; 12];
+    salt[..4].copy_from_slice(address_hash);
+    salt[4..].copy_from_slice(owner_entropy);
+    let mut derived = [0; 64];
+    scrypt(&passpoint.serialize(), &salt, 10, 1, 1, &mut derived);
+    derived
+}
+
+/// Encrypts a private key with `passphrase`, without EC multiplication.
+///
+/// The resulting "6P..." string is bound to the P2PKH address of the key on the key's network.
+pub fn encrypt<C: secp256k1::Signing>(secp: &Secp256k1<C>, key: &PrivateKey, passphrase: &str) -> String {
+    let address_hash = address_hash(&key.public_key(secp), key.network);
+
+    let mut derived = [0; 64];
+    scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, &mut derived);
+    let cipher = Aes256::new(GenericArray::from_slice(&derived[32..]));
+
+    let mut ret = [0; ENCRYPTED_KEY_LEN];
+    ret[..2].copy_from_slice(&NON_EC_PREFIX);
+    ret[2] = FLAG_NON_EC | if key.compressed { FLAG_COMPRESSED } else { 0 };
+    ret[3..7].copy_from_slice(&address_hash);
+    ret[7..].copy_from_slice(&key.inner[..]);
+    encrypt_block(&cipher, &mut ret[7..23], &derived[..16]);
+    encrypt_block(&cipher, &mut ret[23..39], &derived[16..32]);
+    base58::check_encode_slice(&ret)
+}
+
+/// Decrypts a BIP38 encrypted key (in either mode) with `passphrase`.
+///
+/// The address hash is checked against the P2PKH address of the decrypted key on `network`.
+pub fn decrypt<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    encrypted: &str,
+    passphrase: &str,
+    network: Network,
+) -> Result<PrivateKey, Error> {
+    let data = base58::from_check(encrypted)?;
+    if data.len() != ENCRYPTED_KEY_LEN {
+        return Err(Error::InvalidLength(data.len()));
+    }
+    let flag = data[2];
+    let mut address_hash = [0; 4];
+    address_hash.copy_from_slice(&data[3..7]);
+
+    let inner = if data[..2] == NON_EC_PREFIX {
+        if flag & !FLAG_COMPRESSED != FLAG_NON_EC {
+            return Err(Error::InvalidFlag(flag));
+        }
+        let mut derived = [0; 64];
+        scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, &mut derived);
+        let cipher = Aes256::new(GenericArray::from_slice(&derived[32..]));
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&data[7..39]);
+        decrypt_block(&cipher, &mut key[..16], &derived[..16]);
+        decrypt_block(&cipher, &mut key[16..], &derived[16..32]);
+        secp256k1::SecretKey::from_slice(&key)?
+    } else if data[..2] == EC_PREFIX {
+        if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+            return Err(Error::InvalidFlag(flag));
+        }
+        let mut owner_entropy = [0; 8];
+        owner_entropy.copy_from_slice(&data[7..15]);
+        let mut passfactor = passfactor(passphrase, &owner_entropy, flag & FLAG_LOT_SEQUENCE != 0)?;
+        let passpoint = secp256k1::PublicKey::from_secret_key(secp, &passfactor);
+        let derived = ec_derived_halves(&passpoint, &address_hash, &owner_entropy);
+        let cipher = Aes256::new(GenericArray::from_slice(&derived[32..]));
+
+        // encryptedpart2 decrypts to the second half of encryptedpart1 followed by seedb[16..24]
+        let mut part2 = [0; 16];
+        part2.copy_from_slice(&data[23..39]);
+        decrypt_block(&cipher, &mut part2, &derived[16..32]);
+        let mut seedb = [0; 24];
+        seedb[..8].copy_from_slice(&data[15..23]);
+        seedb[8..16].copy_from_slice(&part2[..8]);
+        decrypt_block(&cipher, &mut seedb[..16], &derived[..16]);
+        seedb[16..].copy_from_slice(&part2[8..]);
+
+        let factorb = sha256d::Hash::hash(&seedb);
+        passfactor.mul_assign(&factorb[..])?;
+        passfactor
+    } else {
+        return Err(Error::InvalidPrefix);
+    };
+
+    let key = PrivateKey {
+        compressed: flag & FLAG_COMPRESSED != 0,
+        network,
+        inner,
+    };
+    if self::address_hash(&key.public_key(secp), network) != address_hash {
+        return Err(Error::AddressHashMismatch);
+    }
+    Ok(key)
+}
+
+/// An intermediate passphrase code, used to let a third party create
+/// EC-multiplied encrypted keys without learning the passphrase.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IntermediateCode {
+    owner_entropy: [u8; 8],
+    lot_sequence: bool,
+    passpoint: secp256k1::PublicKey,
+}
+
+impl IntermediateCode {
+    /// Creates an intermediate code without lot and sequence numbers from 8 random bytes of `owner_salt`.
+    pub fn new<C: secp256k1::Signing>(
+        secp: &Secp256k1<C>,
+        passphrase: &str,
+        owner_salt: [u8; 8],
+    ) -> Result<IntermediateCode, Error> {
+        let passfactor = passfactor(passphrase, &owner_salt, false)?;
+        Ok(IntermediateCode {
+            owner_entropy: owner_salt,
+            lot_sequence: false,
+            passpoint: secp256k1::PublicKey::from_secret_key(secp, &passfactor),
+        })
+    }
+
+    /// Creates an intermediate code encoding `lot` and `sequence` from 4 random bytes of `owner_salt`.
+    pub fn with_lot_sequence<C: secp256k1::Signing>(
+        secp: &Secp256k1<C>,
+        passphrase: &str,
+        owner_salt: [u8; 4],
+        lot: u32,
+        sequence: u32,
+    ) -> Result<IntermediateCode, Error> {
+        if lot > MAX_LOT || sequence > MAX_SEQUENCE {
+            return Err(Error::InvalidLotSequence(lot, sequence));
+        }
+        let lot_sequence = lot * 4096 + sequence;
+        let mut owner_entropy = [0; 8];
+        owner_entropy[..4].copy_from_slice(&owner_salt);
+        owner_entropy[4] = (lot_sequence >> 24) as u8;
+        owner_entropy[5] = (lot_sequence >> 16) as u8;
+        owner_entropy[6] = (lot_sequence >> 8) as u8;
+        owner_entropy[7] = lot_sequence as u8;
+
+        let passfactor = passfactor(passphrase, &owner_entropy, true)?;
+        Ok(IntermediateCode {
+            owner_entropy,
+            lot_sequence: true,
+            passpoint: secp256k1::PublicKey::from_secret_key(secp, &passfactor),
+        })
+    }
+
+    /// Returns the lot and sequence numbers, if the code has them.
+    pub fn lot_sequence(&self) -> Option<(u32, u32)> {
+        if !self.lot_sequence {
+            return None;
+        }
+        let e = &self.owner_entropy;
+        let n = (e[4] as u32) << 24 | (e[5] as u32) << 16 | (e[6] as u32) << 8 | e[7] as u32;
+        Some((n / 4096, n % 4096))
+    }
+
+    /// Creates a new encrypted key from this code and 24 random bytes of `seedb`.
+    ///
+    /// Returns the encrypted key together with the P2PKH address it controls on `network`.
+    pub fn encrypt<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        seedb: [u8; 24],
+        compressed: bool,
+        network: Network,
+    ) -> Result<(String, Address), Error> {
+        let factorb = sha256d::Hash::hash(&seedb);
+        let mut point = self.passpoint;
+        point.mul_assign(secp, &factorb[..])?;
+        let pk = PublicKey { compressed, inner: point };
+        let address_hash = address_hash(&pk, network);
+
+        let derived = ec_derived_halves(&self.passpoint, &address_hash, &self.owner_entropy);
+        let cipher = Aes256::new(GenericArray::from_slice(&derived[32..]));
+
+        let mut part1 = [0; 16];
+        part1.copy_from_slice(&seedb[..16]);
+        encrypt_block(&cipher, &mut part1, &derived[..16]);
+        let mut part2 = [0; 16];
+        part2[..8].copy_from_slice(&part1[8..]);
+        part2[8..].copy_from_slice(&seedb[16..]);
+        encrypt_block(&cipher, &mut part2, &derived[16..32]);
+
+        let mut ret = [0; ENCRYPTED_KEY_LEN];
+        ret[..2].copy_from_slice(&EC_PREFIX);
+        ret[2] = if compressed { FLAG_COMPRESSED } else { 0 }
+            | if self.lot_sequence { FLAG_LOT_SEQUENCE } else { 0 };
+        ret[3..7].copy_from_slice(&address_hash);
+        ret[7..15].copy_from_slice(&self.owner_entropy);
+        ret[15..23].copy_from_slice(&part1[..8]);
+        ret[23..].copy_from_slice(&part2);
+        Ok((base58::check_encode_slice(&ret), Address::p2pkh(&pk, network)))
+    }
+}
+
+impl fmt::Display for IntermediateCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ret = [0; INTERMEDIATE_CODE_LEN];
+        ret[..7].copy_from_slice(&INTERMEDIATE_MAGIC);
+        ret[7] = if self.lot_sequence { MAGIC_LOT_SEQUENCE } else { MAGIC_NO_LOT_SEQUENCE };
+        ret[8..16].copy_from_slice(&self.owner_entropy);
+        ret[16..].copy_from_slice(&self.passpoint.serialize());
+        base58::check_encode_slice_to_fmt(f, &ret)
+    }
+}
+
+impl FromStr for IntermediateCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IntermediateCode, Error> {
+        let data = base58::from_check(s)?;
+        if data.len() != INTERMEDIATE_CODE_LEN {
+            return Err(Error::InvalidLength(data.len()));
+        }
+        if data[..7] != INTERMEDIATE_MAGIC {
+            return Err(Error::InvalidPrefix);
+        }
+        let lot_sequence = match data[7] {
+            MAGIC_NO_LOT_SEQUENCE => false,
+            MAGIC_LOT_SEQUENCE => true,
+            _ => return Err(Error::InvalidPrefix),
+        };
+        let mut owner_entropy = [0; 8];
+        owner_entropy.copy_from_slice(&data[8..16]);
+        Ok(IntermediateCode {
+            owner_entropy,
+            lot_sequence,
+            passpoint: secp256k1::PublicKey::from_slice(&data[16..])?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::FromHex;
+
+    const KEY: &str = "cbf4b9f70470856bb4f40f80b87edb90865997ffee6df315ab166d713af433a5";
+
+    #[test]
+    fn test_non_ec_roundtrip() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&Vec::<u8>::from_hex(KEY).unwrap()).unwrap();
+
+        let key = PrivateKey::new_uncompressed(sk, Network::Bitcoin);
+        let encrypted = encrypt(&secp, &key, "TestingOneTwoThree");
+        assert_eq!(encrypted, "6PRMDu9qcLKYhTi3WRN1DbpVh8eeUUeiW7r1gbCntidPe1PPopcrquQRyC");
+        assert_eq!(decrypt(&secp, &encrypted, "TestingOneTwoThree", Network::Bitcoin).unwrap(), key);
+
+        let key = PrivateKey::new(sk, Network::Bitcoin);
+        let encrypted = encrypt(&secp, &key, "TestingOneTwoThree");
+        assert_eq!(encrypted, "6PYSCRm8cwQyJVXghootKaoTpZmFqo26453EaeVtAFfAx1kebfBSDVbZdf");
+        assert_eq!(decrypt(&secp, &encrypted, "TestingOneTwoThree", Network::Bitcoin).unwrap(), key);
+
+        assert_eq!(
+            decrypt(&secp, &encrypted, "Satoshi", Network::Bitcoin),
+            Err(Error::AddressHashMismatch)
+        );
+    }
+
+    #[test]
+    fn test_ec_multiply() {
+        let secp = Secp256k1::new();
+        let mut seedb = [0; 24];
+        for (i, b) in seedb.iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+
+        let salt = <[u8; 8]>::from_hex("a50dba6772cb9383").unwrap();
+        let code = IntermediateCode::new(&secp, "TestingOneTwoThree", salt).unwrap();
+        assert_eq!(code.to_string(), "passphrasecHVZZZKTr32eCVAXNXtq37A5t7HsDVCDUF5T3qUDZzHy1W3s83Qkf5ZP8yFWKQ");
+        assert_eq!(code.lot_sequence(), None);
+        assert_eq!(IntermediateCode::from_str(&code.to_string()).unwrap(), code);
+
+        let (encrypted, address) = code.encrypt(&secp, seedb, false, Network::Bitcoin).unwrap();
+        assert_eq!(encrypted, "6PfWYaZKf5froknp1hPoCsfAAQuqXg4pWtVWX36j8JQkin1D578ZLMaQxS");
+        assert_eq!(address.to_string(), "TgMFSqcjMpLiwzP9j7p5ceY4bjviojcPKZ");
+        let key = decrypt(&secp, &encrypted, "TestingOneTwoThree", Network::Bitcoin).unwrap();
+        assert!(!key.compressed);
+        assert_eq!(key.inner, secp256k1::SecretKey::from_str("0aee96ff3c53f5391a51d9d7f348afaf1d7d24d382958bbe0b64ec3e4f8e2b9e").unwrap());
+
+        let salt = <[u8; 4]>::from_hex("4fca5a97").unwrap();
+        let code = IntermediateCode::with_lot_sequence(&secp, "MOLON LABE", salt, 263183, 1).unwrap();
+        assert_eq!(code.to_string(), "passphrasenqu5A8C6HKe5V5ZXcKzRPMGdQZeXMcyMnBsxNcK4WXYuxT9kpYSE26jEJaPMsy");
+        assert_eq!(code.lot_sequence(), Some((263183, 1)));
+
+        let (encrypted, address) = code.encrypt(&secp, seedb, true, Network::Bitcoin).unwrap();
+        assert_eq!(encrypted, "6PoMtxNq9NiSJ8RREkSTGFoxiEJmzVBBSnAx2gPArAVQiNkmZZzBuCx2Kx");
+        assert_eq!(address.to_string(), "TcX5iTno8bDMGneq1ExiL2cxeeTEDhWWqr");
+        let key = decrypt(&secp, &encrypted, "MOLON LABE", Network::Bitcoin).unwrap();
+        assert!(key.compressed);
+        assert_eq!(key.inner, secp256k1::SecretKey::from_str("f476a6a32424d725bd5f6debdb2b59be7d7df1cce2aa44fb812b976053ffaeff").unwrap());
+
+        assert_eq!(
+            IntermediateCode::with_lot_sequence(&secp, "MOLON LABE", salt, MAX_LOT + 1, 0),
+            Err(Error::InvalidLotSequence(MAX_LOT + 1, 0))
+        );
+    }
+}
@@ -0,0 +1,339 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP85 deterministic entropy.
+//!
+//! Implementation of BIP85 deterministic entropy derivation from a BIP32 root
+//! key, as defined at <https://github.com/bitcoin/bips/blob/master/bip-0085.mediawiki>.
+//!
+//! A single master extended private key can be used to deterministically derive
+//! entropy for an unlimited number of independent child wallets (BIP39 seeds,
+//! WIF keys, extended private keys or raw hex entropy).
+//!
+
+use prelude::*;
+
+use core::fmt;
+#[cfg(feature = "std")] use std::error;
+
+use hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine};
+use secp256k1::{self, Secp256k1};
+
+use util::bip32::{self, ChainCode, ChildNumber, DerivationPath, ExtendedPrivKey, Fingerprint};
+use util::key::PrivateKey;
+
+/// The BIP85 purpose index, used as the (hardened) first path element of every derivation.
+pub const BIP85_PURPOSE: u32 = 83696968;
+
+/// Application number for BIP39 mnemonic entropy.
+pub const APP_BIP39: u32 = 39;
+/// Application number for HD-seed WIF private keys.
+pub const APP_WIF: u32 = 2;
+/// Application number for BIP32 extended private keys.
+pub const APP_XPRV: u32 = 32;
+/// Application number for raw hex entropy.
+pub const APP_HEX: u32 = 128169;
+
+/// HMAC key used to turn a derived private key into BIP85 entropy.
+const ENTROPY_HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+/// A BIP85 error.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// A BIP32 derivation error.
+    Bip32(bip32::Error),
+    /// The derivation path does not start with the hardened BIP85 purpose.
+    InvalidPurpose,
+    /// The derivation path contains a non-hardened child number.
+    NonHardenedPath(ChildNumber),
+    /// The requested number of BIP39 words is not one of 12, 15, 18, 21 or 24.
+    InvalidWordCount(u32),
+    /// The requested number of hex entropy bytes is not within [16, 64].
+    InvalidByteLength(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Bip32(ref e) => write!(f, "BIP32 error: {}", e),
+            Error::InvalidPurpose => write!(f, "derivation path does not start with {}'", BIP85_PURPOSE),
+            Error::NonHardenedPath(ref c) => write!(f, "non-hardened child number {} in BIP85 path", c),
+            Error::InvalidWordCount(n) => write!(f, "invalid BIP39 word count: {}", n),
+            Error::InvalidByteLength(n) => write!(f, "invalid hex entropy length: {} (must be within [16, 64])", n),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Bip32(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<bip32::Error> for Error {
+    fn from(e: bip32::Error) -> Error {
+        Error::Bip32(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Error {
+        Error::Bip32(bip32::Error::Secp256k1(e))
+    }
+}
+
+/// BIP39 wordlist languages, as numbered by BIP85.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Language {
+    /// English wordlist.
+    English,
+    /// Japanese wordlist.
+    Japanese,
+    /// Korean wordlist.
+    Korean,
+    /// Spanish wordlist.
+    Spanish,
+    /// Simplified Chinese wordlist.
+    ChineseSimplified,
+    /// Traditional Chinese wordlist.
+    ChineseTraditional,
+    /// French wordlist.
+    French,
+    /// Italian wordlist.
+    Italian,
+    /// Czech wordlist.
+    Czech,
+}
+
+impl Language {
+    /// Returns the BIP85 language code used as a derivation path element.
+    pub fn code(self) -> u32 {
+        match self {
+            Language::English => 0,
+            Language::Japanese => 1,
+            Language::Korean => 2,
+            Language::Spanish => 3,
+            Language::ChineseSimplified => 4,
+            Language::ChineseTraditional => 5,
+            Language::French => 6,
+            Language::Italian => 7,
+            Language::Czech => 8,
+        }
+    }
+}
+
+/// Builds the fully hardened path `m/83696968'/{app}'/{elements}'...`.
+fn app_path(app: u32, elements: &[u32]) -> Result<DerivationPath, Error> {
+    let mut path = Vec::with_capacity(elements.len() + 2);
+    path.push(ChildNumber::from_hardened_idx(BIP85_PURPOSE)?);
+    path.push(ChildNumber::from_hardened_idx(app)?);
+    for index in elements {
+        path.push(ChildNumber::from_hardened_idx(*index)?);
+    }
+    Ok(DerivationPath::from(path))
+}
+
+/// Derives the 64 bytes of BIP85 entropy at the given path.
+///
+/// The path must start with `83696968'` and consist only of hardened child numbers.
+pub fn derive_entropy<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    root: &ExtendedPrivKey,
+    path: &DerivationPath,
+) -> Result<[u8; 64], Error> {
+    match path.as_ref().first() {
+        Some(&ChildNumber::Hardened { index }) if index == BIP85_PURPOSE => {},
+        _ => return Err(Error::InvalidPurpose),
+    }
+    if let Some(cn) = path.into_iter().find(|cn| cn.is_normal()) {
+        return Err(Error::NonHardenedPath(*cn));
+    }
+
+    let derived = root.derive_priv(secp, path)?;
+    let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(ENTROPY_HMAC_KEY);
+    hmac_engine.input(&derived.private_key[..]);
+    Ok(Hmac::<sha512::Hash>::from_engine(hmac_engine).into_inner())
+}
+
+/// Derives the entropy for a BIP39 mnemonic of `word_count` words.
+///
+/// Uses the path `m/83696968'/39'/{language}'/{words}'/{index}'`. The returned entropy
+/// (16 to 32 bytes) is meant to be fed into a BIP39 implementation to obtain the mnemonic.
+pub fn bip39_entropy<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    root: &ExtendedPrivKey,
+    language: Language,
+    word_count: u32,
+    index: u32,
+) -> Result<Vec<u8>, Error> {
+    let len = match word_count {
+        12 => 16,
+        15 => 20,
+        18 => 24,
+        21 => 28,
+        24 => 32,
+        n => return Err(Error::InvalidWordCount(n)),
+    };
+    let path = app_path(APP_BIP39, &[language.code(), word_count, index])?;
+    let entropy = derive_entropy(secp, root, &path)?;
+    Ok(entropy[..len].to_vec())
+}
+
+/// Derives a compressed WIF private key for use as an HD-seed (e.g. Bitcoin Core's `sethdseed`).
+///
+/// Uses the path `m/83696968'/2'/{index}'`. The key is created for the network of `root`.
+pub fn wif<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    root: &ExtendedPrivKey,
+    index: u32,
+) -> Result<PrivateKey, Error> {
+    let path = app_path(APP_WIF, &[index])?;
+    let entropy = derive_entropy(secp, root, &path)?;
+    Ok(PrivateKey::new(secp256k1::SecretKey::from_slice(&entropy[..32])?, root.network))
+}
+
+/// Derives a master extended private key.
+///
+/// Uses the path `m/83696968'/32'/{index}'`. The first 32 bytes of entropy are used as the
+/// chain code and the last 32 bytes as the private key.
+pub fn xprv<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    root: &ExtendedPrivKey,
+    index: u32,
+) -> Result<ExtendedPrivKey, Error> {
+    let path = app_path(APP_XPRV, &[index])?;
+    let entropy = derive_entropy(secp, root, &path)?;
+    Ok(ExtendedPrivKey {
+        network: root.network,
+        depth: 0,
+        parent_fingerprint: Fingerprint::default(),
+        child_number: ChildNumber::from_normal_idx(0)?,
+        private_key: secp256k1::SecretKey::from_slice(&entropy[32..])?,
+        chain_code: ChainCode::from(&entropy[..32]),
+    })
+}
+
+/// Derives `num_bytes` of raw entropy, where `num_bytes` is within [16, 64].
+///
+/// Uses the path `m/83696968'/128169'/{num_bytes}'/{index}'`.
+pub fn hex<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    root: &ExtendedPrivKey,
+    num_bytes: u32,
+    index: u32,
+) -> Result<Vec<u8>, Error> {
+    if num_bytes < 16 || num_bytes > 64 {
+        return Err(Error::InvalidByteLength(num_bytes));
+    }
+    let path = app_path(APP_HEX, &[num_bytes, index])?;
+    let entropy = derive_entropy(secp, root, &path)?;
+    Ok(entropy[..num_bytes as usize].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+    use hashes::hex::{FromHex, ToHex};
+
+    const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    fn master() -> ExtendedPrivKey {
+        ExtendedPrivKey::from_str(MASTER).unwrap()
+    }
+
+    #[test]
+    fn test_derive_entropy() {
+        let secp = Secp256k1::new();
+
+        let path = DerivationPath::from_str("m/83696968'/0'/0'").unwrap();
+        assert_eq!(
+            derive_entropy(&secp, &master(), &path).unwrap().to_hex(),
+            "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+        );
+
+        let path = DerivationPath::from_str("m/83696968'/0'/1'").unwrap();
+        assert_eq!(
+            derive_entropy(&secp, &master(), &path).unwrap().to_hex(),
+            "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"
+        );
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        let secp = Secp256k1::new();
+
+        let path = DerivationPath::from_str("m/44'/0'/0'").unwrap();
+        assert_eq!(derive_entropy(&secp, &master(), &path), Err(Error::InvalidPurpose));
+
+        let path = DerivationPath::from_str("m/83696968'/0'/0").unwrap();
+        assert_eq!(
+            derive_entropy(&secp, &master(), &path),
+            Err(Error::NonHardenedPath(ChildNumber::Normal { index: 0 }))
+        );
+
+        assert_eq!(bip39_entropy(&secp, &master(), Language::English, 13, 0), Err(Error::InvalidWordCount(13)));
+        assert_eq!(hex(&secp, &master(), 15, 0), Err(Error::InvalidByteLength(15)));
+        assert_eq!(hex(&secp, &master(), 65, 0), Err(Error::InvalidByteLength(65)));
+    }
+
+    #[test]
+    fn test_bip39() {
+        let secp = Secp256k1::new();
+        let vectors = [
+            (12, "6250b68daf746d12a24d58b4787a714b"),
+            (18, "938033ed8b12698449d4bbca3c853c66b293ea1b1ce9d9dc"),
+            (24, "ae131e2312cdc61331542efe0d1077bac5ea803adf24b313a4f0e48e9c51f37f"),
+        ];
+        for &(words, expected) in vectors.iter() {
+            let entropy = bip39_entropy(&secp, &master(), Language::English, words, 0).unwrap();
+            assert_eq!(entropy, Vec::<u8>::from_hex(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_wif() {
+        let secp = Secp256k1::new();
+        let key = wif(&secp, &master(), 0).unwrap();
+        assert!(key.compressed);
+        assert_eq!(key.network, master().network);
+        assert_eq!(key.inner[..].to_hex(), "7040bb53104f27367f317558e78a994ada7296c6fde36a364e5baf206e502bb1");
+    }
+
+    #[test]
+    fn test_xprv() {
+        let secp = Secp256k1::new();
+        let key = xprv(&secp, &master(), 0).unwrap();
+        assert_eq!(
+            key.to_string(),
+            "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX"
+        );
+    }
+
+    #[test]
+    fn test_hex() {
+        let secp = Secp256k1::new();
+        assert_eq!(
+            hex(&secp, &master(), 64, 0).unwrap().to_hex(),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+    }
+}
@@ -0,0 +1,434 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP37 Bloom filters.
+//!
+//! This module implements the Bloom filters SPV clients load into their peers
+//! to have them relay only relevant transactions, as described in
+//! [BIP37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki).
+//!
+
+use prelude::*;
+
+use blockdata::opcodes;
+use blockdata::script::{Instruction, Script};
+use blockdata::transaction::{OutPoint, Transaction};
+use consensus::encode::serialize;
+use network::message_bloom::{BloomFlags, FilterLoad};
+
+/// The maximum size of a filter, in bytes.
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+
+/// The maximum number of hash functions of a filter.
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// The multiplier of the hash function index, giving each function its own seed.
+const SEED_MULTIPLIER: u32 = 0xfba4_c795;
+
+/// A BIP37 Bloom filter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BloomFilter {
+    data: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+    flags: BloomFlags,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter of `size` bytes using `hash_funcs` hash functions, both clamped
+    /// to their maximum, seeded with `tweak`.
+    pub fn new(size: usize, hash_funcs: u32, tweak: u32, flags: BloomFlags) -> BloomFilter {
+        BloomFilter {
+            data: vec![0; size.max(1).min(MAX_BLOOM_FILTER_SIZE)],
+            hash_funcs: hash_funcs.min(MAX_HASH_FUNCS),
+            tweak,
+            flags,
+        }
+    }
+
+    /// Creates an empty filter sized for `elements` elements with a false positive rate of
+    /// `fp_rate`, e.g. 0.0001, seeded with `tweak`, as Bitcoin Core's `CBloomFilter` does.
+    ///
+    /// The filter is clamped to the limits of BIP37, so the false positive rate may be higher
+    /// for a large number of elements.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn with_fp_rate(elements: u32, fp_rate: f64, tweak: u32, flags: BloomFlags) -> BloomFilter {
+        let elements = elements.max(1) as usize;
+        let ln2 = ::std::f64::consts::LN_2;
+        let bits = -1.0 / (ln2 * ln2) * elements as f64 * fp_rate.ln();
+        let size = bits.max(0.0).min((MAX_BLOOM_FILTER_SIZE * 8) as f64) as usize / 8;
+        let hash_funcs = ((size * 8 / elements) as f64 * ln2) as u32;
+        BloomFilter::new(size, hash_funcs, tweak, flags)
+    }
+
+    /// Estimates the current false positive rate from the fraction of bits set, which grows as
+    /// elements are inserted, including outpoints added by [`BloomFilter::is_relevant_and_update`].
+    ///
+    /// SPV clients can load a new filter into their peers once the rate exceeds the one the
+    /// filter was created for.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn false_positive_rate(&self) -> f64 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
+        let set = self.data.iter().map(|byte| byte.count_ones()).sum::<u32>();
+        let fill = set as f64 / (self.data.len() * 8) as f64;
+        fill.powi(self.hash_funcs as i32)
+    }
+
+    /// Returns the filter bits.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the number of hash functions.
+    pub fn hash_funcs(&self) -> u32 {
+        self.hash_funcs
+    }
+
+    /// Returns the tweak the hash functions are seeded with.
+    pub fn tweak(&self) -> u32 {
+        self.tweak
+    }
+
+    /// Returns how the filter is updated with matched outpoints.
+    pub fn flags(&self) -> BloomFlags {
+        self.flags
+    }
+
+    /// Returns whether the filter is within the size limits of BIP37, which peers check when
+    /// it is loaded.
+    pub fn is_within_size_constraints(&self) -> bool {
+        self.data.len() <= MAX_BLOOM_FILTER_SIZE && self.hash_funcs <= MAX_HASH_FUNCS
+    }
+
+    fn bit(&self, n: u32, element: &[u8]) -> usize {
+        let seed = n.wrapping_mul(SEED_MULTIPLIER).wrapping_add(self.tweak);
+        murmur3(seed, element) as usize % (self.data.len() * 8)
+    }
+
+    /// Adds `element` to the filter.
+    pub fn insert(&mut self, element: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for n in 0..self.hash_funcs {
+            let bit = self.bit(n, element);
+            self.data[bit >> 3] |= 1 << (bit & 7);
+        }
+    }
+
+    /// Adds `outpoint` to the filter, in its serialized form.
+    pub fn insert_outpoint(&mut self, outpoint: &OutPoint) {
+        self.insert(&serialize(outpoint));
+    }
+
+    /// Returns whether `element` may have been added to the filter.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        (0..self.hash_funcs).all(|n| {
+            let bit = self.bit(n, element);
+            self.data[bit >> 3] & (1 << (bit & 7)) != 0
+        })
+    }
+
+    /// Returns whether `outpoint` may have been added to the filter.
+    pub fn contains_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.contains(&serialize(outpoint))
+    }
+
+    /// Returns whether `tx` matches the filter the way a peer relaying to an SPV client matches
+    /// it, adding the outpoints of matched outputs as the filter's flags dictate so that
+    /// transactions spending them match too.
+    pub fn is_relevant_and_update(&mut self, tx: &Transaction) -> bool {
+        let txid = tx.txid();
+        let mut relevant = self.contains(&txid[..]);
+
+        for (vout, output) in tx.output.iter().enumerate() {
+            if !self.contains_push(&output.script_pubkey) {
+                continue;
+            }
+            relevant = true;
+            let update = match self.flags {
+                BloomFlags::None => false,
+                BloomFlags::All => true,
+                BloomFlags::PubkeyOnly => {
+                    output.script_pubkey.is_p2pk() || is_multisig(&output.script_pubkey)
+                }
+            };
+            if update {
+                self.insert_outpoint(&OutPoint::new(txid, vout as u32));
+            }
+        }
+        if relevant {
+            return true;
+        }
+
+        tx.input.iter().any(|input| {
+            self.contains_outpoint(&input.previous_output) || self.contains_push(&input.script_sig)
+        })
+    }
+
+    /// Returns whether a data push of `script` is in the filter.
+    fn contains_push(&self, script: &Script) -> bool {
+        for instruction in script.instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(data)) if !data.is_empty() && self.contains(data) => return true,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+impl From<FilterLoad> for BloomFilter {
+    fn from(filter: FilterLoad) -> BloomFilter {
+        BloomFilter {
+            data: filter.filter,
+            hash_funcs: filter.hash_funcs,
+            tweak: filter.tweak,
+            flags: filter.flags,
+        }
+    }
+}
+
+impl From<BloomFilter> for FilterLoad {
+    fn from(filter: BloomFilter) -> FilterLoad {
+        FilterLoad {
+            filter: filter.data,
+            hash_funcs: filter.hash_funcs,
+            tweak: filter.tweak,
+            flags: filter.flags,
+        }
+    }
+}
+
+/// Returns whether `script` is a bare multisig output script.
+fn is_multisig(script: &Script) -> bool {
+    let ops: Result<Vec<Instruction>, _> = script.instructions().collect();
+    let ops = match ops {
+        Ok(ops) => ops,
+        Err(_) => return false,
+    };
+    let pushnum = |op: &Instruction| match *op {
+        Instruction::Op(op) if op.into_u8() >= opcodes::all::OP_PUSHNUM_1.into_u8()
+            && op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8() => {
+            Some((op.into_u8() - opcodes::all::OP_PUSHNUM_1.into_u8() + 1) as usize)
+        }
+        _ => None,
+    };
+
+    if ops.len() < 4 || ops[ops.len() - 1] != Instruction::Op(opcodes::all::OP_CHECKMULTISIG) {
+        return false;
+    }
+    let keys = &ops[1..ops.len() - 2];
+    let all_keys = keys.iter().all(|op| match *op {
+        Instruction::PushBytes(key) => key.len() == 33 || key.len() == 65,
+        Instruction::Op(_) => false,
+    });
+    match (pushnum(&ops[0]), pushnum(&ops[ops.len() - 2])) {
+        (Some(required), Some(total)) => all_keys && total == keys.len() && required <= total,
+        _ => false,
+    }
+}
+
+/// Computes the 32-bit MurmurHash3 of `data`.
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut h1 = seed;
+    let mut chunks = data.chunks(4);
+    let tail = if data.len() % 4 == 0 { &[][..] } else { chunks.next_back().expect("data has a tail") };
+    for chunk in chunks {
+        let mut k1 = chunk[0] as u32 | (chunk[1] as u32) << 8 | (chunk[2] as u32) << 16 | (chunk[3] as u32) << 24;
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let mut k1 = 0u32;
+    for (i, byte) in tail.iter().enumerate() {
+        k1 ^= (*byte as u32) << (8 * i);
+    }
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85eb_ca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2_ae35);
+    h1 ^ (h1 >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::FromHex;
+    use blockdata::transaction::TxOut;
+    use consensus::encode::deserialize;
+
+    #[test]
+    fn murmur3_vectors() {
+        // from Bitcoin Core's hash_tests.cpp
+        let vectors: &[(u32, u32, &str)] = &[
+            (0x00000000, 0x00000000, ""),
+            (0x6a396f08, 0xFBA4C795, ""),
+            (0x81f16f39, 0xffffffff, ""),
+            (0x514e28b7, 0x00000000, "00"),
+            (0xea3f0b17, 0xFBA4C795, "00"),
+            (0xfd6cf10d, 0x00000000, "ff"),
+            (0x16c6b7ab, 0x00000000, "0011"),
+            (0x8eb51c3d, 0x00000000, "001122"),
+            (0xb4471bf8, 0x00000000, "00112233"),
+            (0xe2301fa8, 0x00000000, "0011223344"),
+            (0xfc2e4a15, 0x00000000, "001122334455"),
+            (0xb074502c, 0x00000000, "00112233445566"),
+            (0x8034d2a0, 0x00000000, "0011223344556677"),
+            (0xb4698def, 0x00000000, "001122334455667788"),
+        ];
+        for &(expected, seed, data) in vectors {
+            assert_eq!(murmur3(seed, &Vec::from_hex(data).unwrap()), expected, "data {}", data);
+        }
+    }
+
+    #[test]
+    fn bloom_filter() {
+        // bloom_create_insert_serialize from Bitcoin Core's bloom_tests.cpp, a filter for three
+        // elements with a false positive rate of 1%
+        let mut filter = BloomFilter::new(3, 5, 0, BloomFlags::All);
+        filter.insert(&Vec::from_hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap());
+        assert!(filter.contains(&Vec::from_hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap()));
+        assert!(!filter.contains(&Vec::from_hex("19108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap()));
+        filter.insert(&Vec::from_hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee").unwrap());
+        filter.insert(&Vec::from_hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5").unwrap());
+        assert!(filter.contains(&Vec::from_hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5").unwrap()));
+
+        let load = FilterLoad::from(filter.clone());
+        assert_eq!(serialize(&load), Vec::<u8>::from_hex("03614e9b050000000000000001").unwrap());
+        assert_eq!(BloomFilter::from(load), filter);
+
+        // bloom_create_insert_serialize_with_tweak
+        let mut filter = BloomFilter::new(3, 5, 2147483649, BloomFlags::All);
+        filter.insert(&Vec::from_hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap());
+        filter.insert(&Vec::from_hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee").unwrap());
+        filter.insert(&Vec::from_hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5").unwrap());
+        assert_eq!(serialize(&FilterLoad::from(filter)), Vec::<u8>::from_hex("03ce4299050000000100008001").unwrap());
+
+        let filter = BloomFilter::new(100_000, 100, 0, BloomFlags::None);
+        assert_eq!(filter.data().len(), MAX_BLOOM_FILTER_SIZE);
+        assert_eq!(filter.hash_funcs(), MAX_HASH_FUNCS);
+        assert!(filter.is_within_size_constraints());
+    }
+
+    #[test]
+    fn fp_rate() {
+        assert_eq!(BloomFilter::with_fp_rate(3, 0.01, 0, BloomFlags::All), BloomFilter::new(3, 5, 0, BloomFlags::All));
+        let filter = BloomFilter::with_fp_rate(1_000, 0.0001, 0, BloomFlags::All);
+        assert_eq!((filter.data().len(), filter.hash_funcs()), (2_396, 13));
+        let large = BloomFilter::with_fp_rate(1_000_000, 0.000001, 0, BloomFlags::All);
+        assert_eq!((large.data().len(), large.hash_funcs()), (MAX_BLOOM_FILTER_SIZE, 0));
+        // without hash functions everything matches
+        assert_eq!(large.false_positive_rate(), 1.0);
+        let tiny = BloomFilter::with_fp_rate(0, 1.0, 0, BloomFlags::All);
+        assert_eq!((tiny.data().len(), tiny.hash_funcs()), (1, 0));
+
+        // the estimate grows with insertions, reaching the target rate at the expected count
+        let mut filter = BloomFilter::with_fp_rate(1_000, 0.001, 7, BloomFlags::None);
+        assert_eq!(filter.false_positive_rate(), 0.0);
+        let mut last = 0.0;
+        for i in 0..2_000u32 {
+            filter.insert(&serialize(&i));
+            let rate = filter.false_positive_rate();
+            assert!(rate >= last);
+            last = rate;
+            if i == 499 {
+                assert!(rate < 0.0001);
+            }
+            if i == 999 {
+                assert!(rate > 0.0005 && rate < 0.002, "{}", rate);
+            }
+        }
+        assert!(last > 0.01);
+    }
+
+    #[test]
+    fn bloom_match() {
+        // bloom_match from Bitcoin Core's bloom_tests.cpp
+        let tx: Transaction = deserialize(&Vec::from_hex("01000000010b26e9b7735eb6aabdf358bab62f9816a21ba9ebdb719d5299e88607d722c190000000008b4830450220070aca44506c5cef3a16ed519d7c3c39f8aab192c4e1c90d065f37b8a4af6141022100a8e160b856c2d43d27d8fba71e5aef6405b8643ac4cb7cb3c462aced7f14711a0141046d11fee51b0e60666d5049a9101a72741df480b96ee26488a4d3466b95c9a40ac5eeef87e10a5cd336c19a84565f80fa6c547957b7700ff4dfbdefe76036c339ffffffff021bff3d11000000001976a91404943fdd508053c75000106d3bc6e2754dbcff1988ac2f15de00000000001976a914a266436d2965547608b9e15d9032a7b9d64fa43188ac00000000").unwrap()).unwrap();
+        let matches = |element: &str| {
+            let mut filter = BloomFilter::new(3, 5, 0, BloomFlags::All);
+            filter.insert(&Vec::from_hex(element).unwrap());
+            filter.is_relevant_and_update(&tx)
+        };
+
+        // txid, in internal byte order
+        assert!(matches("6bff7fcd4f8565ef406dd5d63d4ff94f318fe82027fd4dc451b04474019f74b4"));
+        // input signature and public key
+        assert!(matches("30450220070aca44506c5cef3a16ed519d7c3c39f8aab192c4e1c90d065f37b8a4af6141022100a8e160b856c2d43d27d8fba71e5aef6405b8643ac4cb7cb3c462aced7f14711a01"));
+        assert!(matches("046d11fee51b0e60666d5049a9101a72741df480b96ee26488a4d3466b95c9a40ac5eeef87e10a5cd336c19a84565f80fa6c547957b7700ff4dfbdefe76036c339"));
+        // output addresses
+        assert!(matches("04943fdd508053c75000106d3bc6e2754dbcff19"));
+        assert!(matches("a266436d2965547608b9e15d9032a7b9d64fa431"));
+        // the spent outpoint
+        let mut filter = BloomFilter::new(3, 5, 0, BloomFlags::All);
+        filter.insert_outpoint(&tx.input[0].previous_output);
+        assert!(filter.is_relevant_and_update(&tx));
+        // unrelated elements
+        assert!(!matches("00000009e784f32f62ef849763d4f45b98e07ba658647343b915ff832b110436"));
+        assert!(!matches("0000006d2965547608b9e15d9032a7b9d64fa431"));
+
+        // matching an output adds its outpoint to the filter when the flags allow
+        let update = |flags: BloomFlags| {
+            let mut filter = BloomFilter::new(3, 5, 0, flags);
+            filter.insert(&Vec::from_hex("04943fdd508053c75000106d3bc6e2754dbcff19").unwrap());
+            assert!(filter.is_relevant_and_update(&tx));
+            filter.contains_outpoint(&OutPoint::new(tx.txid(), 0))
+        };
+        assert!(update(BloomFlags::All));
+        assert!(!update(BloomFlags::None));
+        // P2PKH is neither P2PK nor bare multisig
+        assert!(!update(BloomFlags::PubkeyOnly));
+    }
+
+    #[test]
+    fn multisig() {
+        let key = "21020202020202020202020202020202020202020202020202020202020202020202";
+        let script = |hex: String| Script::from(Vec::from_hex(&hex).unwrap());
+        assert!(is_multisig(&script(format!("51{}{}52ae", key, key))));
+        assert!(!is_multisig(&script(format!("53{}{}52ae", key, key))));
+        assert!(!is_multisig(&script(format!("51{}{}53ae", key, key))));
+        assert!(!is_multisig(&script(format!("51{}52ae", key))));
+        assert!(!is_multisig(&script(format!("51{}{}52ac", key, key))));
+
+        let pubkey = Vec::from_hex(&key[2..]).unwrap();
+        let tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut { value: 1, script_pubkey: script(format!("51{}51ae", key)) }],
+        };
+        let mut filter = BloomFilter::new(10, 5, 0, BloomFlags::PubkeyOnly);
+        filter.insert(&pubkey);
+        assert!(filter.is_relevant_and_update(&tx));
+        assert!(filter.contains_outpoint(&OutPoint::new(tx.txid(), 0)));
+    }
+}
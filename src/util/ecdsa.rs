@@ -23,6 +23,7 @@ use hashes::hex::{self, FromHex};
 use blockdata::transaction::NonStandardSighashType;
 use secp256k1;
 use EcdsaSighashType;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// An ECDSA signature with the corresponding hash type.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -45,15 +46,62 @@ impl EcdsaSig {
 
     /// Deserialize from slice following the standardness rules for [`EcdsaSighashType`]
     pub fn from_slice(sl: &[u8]) -> Result<Self, EcdsaSigError> {
-        let (hash_ty, sig) = sl.split_last()
-            .ok_or(EcdsaSigError::EmptySignature)?;
-        let hash_ty = EcdsaSighashType::from_standard(*hash_ty as u32)
-            .map_err(|_| EcdsaSigError::NonStandardSighashType(*hash_ty as u32))?;
-        let sig = secp256k1::ecdsa::Signature::from_der(sig)
+        let hash_ty = EcdsaSig::extract_sighash_type(sl)?;
+        let sig = secp256k1::ecdsa::Signature::from_der(&sl[..sl.len() - 1])
             .map_err(EcdsaSigError::Secp256k1)?;
         Ok(EcdsaSig { sig, hash_ty })
     }
 
+    /// Deserialize from slice, accepting the signature encodings which are not strict DER but
+    /// were valid before BIP66, as found in historical chain data.
+    ///
+    /// The sighash type must still be standard, see [`EcdsaSig::extract_sighash_type`].
+    pub fn from_slice_lax(sl: &[u8]) -> Result<Self, EcdsaSigError> {
+        let hash_ty = EcdsaSig::extract_sighash_type(sl)?;
+        let sig = secp256k1::ecdsa::Signature::from_der_lax(&sl[..sl.len() - 1])
+            .map_err(EcdsaSigError::Secp256k1)?;
+        Ok(EcdsaSig { sig, hash_ty })
+    }
+
+    /// Returns the sighash type of a serialized signature, its last byte, without parsing the
+    /// signature itself.
+    ///
+    /// # Errors
+    ///
+    /// [`EcdsaSigError::EmptySignature`] if `sl` is empty and
+    /// [`EcdsaSigError::NonStandardSighashType`] with the byte found if it is not a standard
+    /// sighash type.
+    pub fn extract_sighash_type(sl: &[u8]) -> Result<EcdsaSighashType, EcdsaSigError> {
+        let hash_ty = *sl.last().ok_or(EcdsaSigError::EmptySignature)?;
+        EcdsaSighashType::from_standard(hash_ty as u32)
+            .map_err(|_| EcdsaSigError::NonStandardSighashType(hash_ty as u32))
+    }
+
+    /// Constructs a signature from its 64-byte compact encoding, `r` and `s` as big-endian
+    /// 32-byte integers.
+    pub fn from_compact(data: &[u8], hash_ty: EcdsaSighashType) -> Result<Self, EcdsaSigError> {
+        let sig = secp256k1::ecdsa::Signature::from_compact(data)?;
+        Ok(EcdsaSig { sig, hash_ty })
+    }
+
+    /// Serializes the signature in its 64-byte compact encoding, without the sighash type.
+    pub fn to_compact(&self) -> [u8; 64] {
+        self.sig.serialize_compact()
+    }
+
+    /// Normalizes the signature to its low-S form, which is the only one standardness rules
+    /// accept since BIP62. The normalized signature is as valid as the original one.
+    pub fn normalize_s(&mut self) {
+        self.sig.normalize_s()
+    }
+
+    /// Checks whether the signature is in low-S form, see [`EcdsaSig::normalize_s`].
+    pub fn is_low_s(&self) -> bool {
+        let mut normalized = self.sig;
+        normalized.normalize_s();
+        normalized == self.sig
+    }
+
     /// Serialize EcdsaSig
     pub fn to_vec(&self) -> Vec<u8> {
         // TODO: add support to serialize to a writer to SerializedSig
@@ -64,6 +112,18 @@ impl EcdsaSig {
     }
 }
 
+// Signatures are generated from their compact encoding, failing for the negligible share of
+// bytes which don't encode one.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for EcdsaSig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let sig = secp256k1::ecdsa::Signature::from_compact(u.bytes(64)?)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+        Ok(EcdsaSig { sig, hash_ty: u.arbitrary()? })
+    }
+}
+
 impl fmt::Display for EcdsaSig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         hex::format_hex(&self.sig.serialize_der(), f)?;
@@ -75,13 +135,7 @@ impl FromStr for EcdsaSig {
     type Err = EcdsaSigError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = Vec::from_hex(s)?;
-        let (sighash_byte, signature) = bytes.split_last()
-            .ok_or(EcdsaSigError::EmptySignature)?;
-        Ok(EcdsaSig {
-            sig: secp256k1::ecdsa::Signature::from_der(signature)?,
-            hash_ty: EcdsaSighashType::from_standard(*sighash_byte as u32)?
-        })
+        EcdsaSig::from_slice(&Vec::from_hex(s)?)
     }
 }
 
@@ -134,3 +188,73 @@ impl From<hex::Error> for EcdsaSigError {
         EcdsaSigError::HexEncoding(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashes::hex::ToHex;
+    use util::uint::Uint256;
+
+    // The order of the secp256k1 group.
+    const CURVE_ORDER: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+        0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+    ];
+
+    #[test]
+    fn lax_der() {
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&[0x01; 32]);
+        compact[32..].copy_from_slice(&[0x02; 32]);
+        let expected = EcdsaSig::from_compact(&compact, EcdsaSighashType::Single).unwrap();
+        assert_eq!(expected.to_compact()[..], compact[..]);
+
+        // `r` is padded with a superfluous zero byte
+        let mut lax = vec![0x30, 0x45, 0x02, 0x21, 0x00];
+        lax.extend(&[0x01; 32]);
+        lax.extend(&[0x02, 0x20]);
+        lax.extend(&[0x02; 32]);
+        lax.push(EcdsaSighashType::Single as u8);
+        assert!(EcdsaSig::from_slice(&lax).is_err());
+        assert_eq!(EcdsaSig::from_slice_lax(&lax).unwrap(), expected);
+
+        let strict = expected.to_vec();
+        assert_eq!(EcdsaSig::from_slice(&strict).unwrap(), expected);
+        assert_eq!(EcdsaSig::from_slice_lax(&strict).unwrap(), expected);
+        assert_eq!(strict.to_hex().parse::<EcdsaSig>().unwrap(), expected);
+    }
+
+    #[test]
+    fn sighash_type_extraction() {
+        let sig = EcdsaSig::from_str("3045022100bed2862e8a24ee6e2ac2d2de15dd75fbef07d30c8c1cabdb1c5a0fdbae2dc3d5022005a3d0a5d3e2a3e89ea8db1b9b31ce3fcaf7b91c4d8b3a30f3d7ca0ec17e79ee01").unwrap();
+        let mut bytes = sig.to_vec();
+        assert_eq!(EcdsaSig::extract_sighash_type(&bytes), Ok(EcdsaSighashType::All));
+
+        *bytes.last_mut().unwrap() = 0x04;
+        assert_eq!(EcdsaSig::extract_sighash_type(&bytes), Err(EcdsaSigError::NonStandardSighashType(4)));
+        assert_eq!(EcdsaSig::from_slice(&bytes), Err(EcdsaSigError::NonStandardSighashType(4)));
+        assert_eq!(EcdsaSig::from_slice_lax(&bytes), Err(EcdsaSigError::NonStandardSighashType(4)));
+
+        assert_eq!(EcdsaSig::extract_sighash_type(&[]), Err(EcdsaSigError::EmptySignature));
+        assert_eq!(EcdsaSig::from_slice_lax(&[]), Err(EcdsaSigError::EmptySignature));
+    }
+
+    #[test]
+    fn normalize_s() {
+        let low = EcdsaSig::from_str("3045022100bed2862e8a24ee6e2ac2d2de15dd75fbef07d30c8c1cabdb1c5a0fdbae2dc3d5022005a3d0a5d3e2a3e89ea8db1b9b31ce3fcaf7b91c4d8b3a30f3d7ca0ec17e79ee01").unwrap();
+        assert!(low.is_low_s());
+
+        // s' = n - s
+        let mut compact = low.to_compact();
+        let s = Uint256::from_be_slice(&compact[32..]).unwrap();
+        let high_s = Uint256::from_be_bytes(CURVE_ORDER) - s;
+        compact[32..].copy_from_slice(&high_s.to_be_bytes());
+        let mut high = EcdsaSig::from_compact(&compact, EcdsaSighashType::All).unwrap();
+        assert!(!high.is_low_s());
+        assert_ne!(high, low);
+
+        high.normalize_s();
+        assert!(high.is_low_s());
+        assert_eq!(high, low);
+    }
+}
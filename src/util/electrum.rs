@@ -0,0 +1,354 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Electrum protocol types.
+//!
+//! Requests, responses and notifications of the JSON-RPC protocol spoken by
+//! Electrum servers, described at
+//! <https://electrumx-spesmilo.readthedocs.io/en/latest/protocol.html>.
+//! Servers identify scripts by their [`ElectrumScriptHash`], computed by
+//! [`Script::electrum_script_hash`](::Script::electrum_script_hash).
+//! Sending the requests and matching the responses to them is left to the
+//! caller.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hashes::{sha256, Hash, HashEngine};
+use hashes::hex::ToHex;
+use serde::ser::{Serialize, Serializer, SerializeStruct, SerializeSeq};
+
+use blockdata::block::BlockHeader;
+use blockdata::transaction::Transaction;
+use consensus::encode::serialize_hex;
+use hash_types::{ElectrumScriptHash, Txid};
+use util::amount::{Amount, SignedAmount};
+
+/// Electrum protocol methods, with their parameters.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Method {
+    /// `server.version`: negotiates the protocol version, with the client name and the protocol
+    /// version it speaks
+    ServerVersion(String, String),
+    /// `server.ping`
+    ServerPing,
+    /// `blockchain.headers.subscribe`: returns the tip as a [`HeaderNotification`] and notifies
+    /// new tips
+    HeadersSubscribe,
+    /// `blockchain.block.header`: returns the hex of the header at the given height
+    BlockHeader(u32),
+    /// `blockchain.scripthash.get_balance`: returns a [`Balance`]
+    GetBalance(ElectrumScriptHash),
+    /// `blockchain.scripthash.get_history`: returns a list of [`HistoryItem`]s
+    GetHistory(ElectrumScriptHash),
+    /// `blockchain.scripthash.get_mempool`: returns a list of [`HistoryItem`]s
+    GetMempool(ElectrumScriptHash),
+    /// `blockchain.scripthash.listunspent`: returns a list of [`ListUnspentItem`]s
+    ListUnspent(ElectrumScriptHash),
+    /// `blockchain.scripthash.subscribe`: returns the [`script_status`] and notifies its changes
+    Subscribe(ElectrumScriptHash),
+    /// `blockchain.scripthash.unsubscribe`: returns whether the script hash was subscribed to
+    Unsubscribe(ElectrumScriptHash),
+    /// `blockchain.transaction.get`: returns the hex of the transaction
+    GetTransaction(Txid),
+    /// `blockchain.transaction.broadcast`: returns the txid of the broadcast transaction
+    Broadcast(Transaction),
+}
+
+impl Method {
+    /// Returns the name of the method.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Method::ServerVersion(..) => "server.version",
+            Method::ServerPing => "server.ping",
+            Method::HeadersSubscribe => "blockchain.headers.subscribe",
+            Method::BlockHeader(_) => "blockchain.block.header",
+            Method::GetBalance(_) => "blockchain.scripthash.get_balance",
+            Method::GetHistory(_) => "blockchain.scripthash.get_history",
+            Method::GetMempool(_) => "blockchain.scripthash.get_mempool",
+            Method::ListUnspent(_) => "blockchain.scripthash.listunspent",
+            Method::Subscribe(_) => "blockchain.scripthash.subscribe",
+            Method::Unsubscribe(_) => "blockchain.scripthash.unsubscribe",
+            Method::GetTransaction(_) => "blockchain.transaction.get",
+            Method::Broadcast(_) => "blockchain.transaction.broadcast",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Serializes the parameters of a method as a JSON array.
+struct Params<'a>(&'a Method);
+
+impl<'a> Serialize for Params<'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(None)?;
+        match *self.0 {
+            Method::ServerVersion(ref client, ref version) => {
+                seq.serialize_element(client)?;
+                seq.serialize_element(version)?;
+            }
+            Method::ServerPing | Method::HeadersSubscribe => {}
+            Method::BlockHeader(height) => seq.serialize_element(&height)?,
+            Method::GetBalance(ref hash)
+            | Method::GetHistory(ref hash)
+            | Method::GetMempool(ref hash)
+            | Method::ListUnspent(ref hash)
+            | Method::Subscribe(ref hash)
+            | Method::Unsubscribe(ref hash) => seq.serialize_element(&hash.to_hex())?,
+            Method::GetTransaction(ref txid) => seq.serialize_element(&txid.to_hex())?,
+            Method::Broadcast(ref tx) => seq.serialize_element(&serialize_hex(tx))?,
+        }
+        seq.end()
+    }
+}
+
+/// A JSON-RPC request to an Electrum server.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Request {
+    /// Identifier of the request, repeated in its response
+    pub id: u64,
+    /// Called method
+    pub method: Method,
+}
+
+impl Request {
+    /// Creates a request.
+    pub fn new(id: u64, method: Method) -> Request {
+        Request { id, method }
+    }
+}
+
+impl Serialize for Request {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut st = s.serialize_struct("Request", 4)?;
+        st.serialize_field("jsonrpc", "2.0")?;
+        st.serialize_field("id", &self.id)?;
+        st.serialize_field("method", self.method.name())?;
+        st.serialize_field("params", &Params(&self.method))?;
+        st.end()
+    }
+}
+
+/// Error returned by an Electrum server.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    /// Error code
+    pub code: i64,
+    /// Error message
+    pub message: String,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Electrum server error {}: {}", self.code, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for RpcError {}
+
+/// A JSON-RPC response from an Electrum server, whose result has type `T`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Response<T> {
+    /// Identifier of the request answered
+    pub id: u64,
+    /// Result of the request, `None` on errors or when the result is `null`
+    pub result: Option<T>,
+    /// Error the request failed with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl<T> Response<T> {
+    /// Returns the result of the request, or the error it failed with.
+    pub fn into_result(self) -> Result<Option<T>, RpcError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.result),
+        }
+    }
+}
+
+/// A notification sent by an Electrum server to a subscribed client.
+///
+/// New tips are notified by `blockchain.headers.subscribe` with parameters of type
+/// `(HeaderNotification,)`, script status changes by `blockchain.scripthash.subscribe` with
+/// parameters of type `(ElectrumScriptHash, Option<sha256::Hash>)`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Notification<P> {
+    /// Name of the method subscribed to
+    pub method: String,
+    /// Parameters of the notification
+    pub params: P,
+}
+
+/// A block header and its height, returned and notified by `blockchain.headers.subscribe`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HeaderNotification {
+    /// Height of the block
+    pub height: u32,
+    /// Header of the block
+    #[serde(rename = "hex", with = "::consensus::serde::With::<::consensus::serde::Hex>")]
+    pub header: BlockHeader,
+}
+
+/// Balance of a script hash, returned by `blockchain.scripthash.get_balance`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Balance {
+    /// Amount received in confirmed transactions
+    #[serde(with = "::util::amount::serde::as_sat")]
+    pub confirmed: Amount,
+    /// Amount received minus amount spent in unconfirmed transactions
+    #[serde(with = "::util::amount::serde::as_sat")]
+    pub unconfirmed: SignedAmount,
+}
+
+/// A transaction involving a script hash, returned by `blockchain.scripthash.get_history` and
+/// `blockchain.scripthash.get_mempool`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HistoryItem {
+    /// Height of the block confirming the transaction, 0 for unconfirmed transactions and -1 for
+    /// unconfirmed transactions with unconfirmed inputs
+    pub height: i32,
+    /// Txid of the transaction
+    pub tx_hash: Txid,
+    /// Fee of unconfirmed transactions
+    #[serde(default, with = "::util::amount::serde::as_sat::opt", skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Amount>,
+}
+
+/// An unspent output of a script hash, returned by `blockchain.scripthash.listunspent`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ListUnspentItem {
+    /// Height of the block confirming the transaction, 0 for unconfirmed transactions
+    pub height: u32,
+    /// Txid of the transaction
+    pub tx_hash: Txid,
+    /// Index of the output in the transaction
+    pub tx_pos: u32,
+    /// Value of the output
+    #[serde(with = "::util::amount::serde::as_sat")]
+    pub value: Amount,
+}
+
+/// Computes the status of a script hash, as returned and notified by
+/// `blockchain.scripthash.subscribe`, from its history as returned by
+/// `blockchain.scripthash.get_history`. Returns `None` for a script hash without history.
+///
+/// Comparing the status sent by the server to the status of the known history tells whether
+/// the history must be requested again.
+pub fn script_status(history: &[HistoryItem]) -> Option<sha256::Hash> {
+    if history.is_empty() {
+        return None;
+    }
+    let mut engine = sha256::Hash::engine();
+    for item in history {
+        engine.input(format!("{}:{}:", item.tx_hash, item.height).as_bytes());
+    }
+    Some(sha256::Hash::from_engine(engine))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use blockdata::constants::genesis_block;
+    use blockdata::script::Script;
+    use consensus::encode::serialize;
+    use hashes::hex::FromHex;
+    use network::constants::Network;
+
+    #[test]
+    fn requests() {
+        let script = Script::from(Vec::from_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap());
+        let request = Request::new(1, Method::GetBalance(script.electrum_script_hash()));
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"blockchain.scripthash.get_balance","params":["8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"]}"#
+        );
+
+        let request = Request::new(2, Method::HeadersSubscribe);
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":2,"method":"blockchain.headers.subscribe","params":[]}"#
+        );
+
+        let request = Request::new(3, Method::ServerVersion("wallet".into(), "1.4".into()));
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":3,"method":"server.version","params":["wallet","1.4"]}"#
+        );
+    }
+
+    #[test]
+    fn responses() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"result":{"confirmed":103873966,"unconfirmed":-23684192}}"#;
+        let response: Response<Balance> = serde_json::from_str(json).unwrap();
+        assert_eq!(response.id, 1);
+        assert_eq!(response.into_result().unwrap(), Some(Balance {
+            confirmed: Amount::from_sat(103873966),
+            unconfirmed: SignedAmount::from_sat(-23684192),
+        }));
+
+        let json = r#"{"jsonrpc":"2.0","id":2,"result":[
+            {"height":200004,"tx_hash":"acc3758bd2a26f869fcc67d48ff30b96464d476bca82c1cd6656e7d506816412"},
+            {"height":0,"tx_hash":"9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec","fee":20000}
+        ]}"#;
+        let response: Response<Vec<HistoryItem>> = serde_json::from_str(json).unwrap();
+        let history = response.into_result().unwrap().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].height, 200004);
+        assert_eq!(history[0].fee, None);
+        assert_eq!(history[1].fee, Some(Amount::from_sat(20000)));
+        assert_eq!(
+            script_status(&history).unwrap(),
+            sha256::Hash::hash(b"acc3758bd2a26f869fcc67d48ff30b96464d476bca82c1cd6656e7d506816412:200004:\
+                9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec:0:")
+        );
+        assert_eq!(script_status(&[]), None);
+
+        let json = r#"{"jsonrpc":"2.0","id":3,"result":[{"tx_pos":0,"value":45318048,"tx_hash":"9f2c45a12db0144909b5db269415f7319179105982ac70ed80d76ea79d923ebf","height":437146}]}"#;
+        let response: Response<Vec<ListUnspentItem>> = serde_json::from_str(json).unwrap();
+        let unspent = response.into_result().unwrap().unwrap();
+        assert_eq!(unspent[0].value, Amount::from_sat(45318048));
+        assert_eq!(unspent[0].tx_pos, 0);
+
+        let json = r#"{"jsonrpc":"2.0","id":4,"error":{"code":1,"message":"unknown method"}}"#;
+        let response: Response<Balance> = serde_json::from_str(json).unwrap();
+        assert_eq!(response.into_result(), Err(RpcError { code: 1, message: "unknown method".into() }));
+    }
+
+    #[test]
+    fn notifications() {
+        let header = genesis_block(Network::Bitcoin).header;
+        let tip = HeaderNotification { height: 0, header: header.clone() };
+        let json = serde_json::to_string(&tip).unwrap();
+        assert_eq!(json, format!(r#"{{"height":0,"hex":"{}"}}"#, serialize(&header).to_hex()));
+        assert_eq!(serde_json::from_str::<HeaderNotification>(&json).unwrap(), tip);
+
+        let json = r#"{"jsonrpc":"2.0","method":"blockchain.scripthash.subscribe","params":["8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161",null]}"#;
+        let notification: Notification<(ElectrumScriptHash, Option<sha256::Hash>)> = serde_json::from_str(json).unwrap();
+        assert_eq!(notification.method, "blockchain.scripthash.subscribe");
+        assert_eq!(notification.params.0, ElectrumScriptHash::from_hex("8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161").unwrap());
+        assert_eq!(notification.params.1, None);
+    }
+}
@@ -1,5 +1,18 @@
+//! Byte order conversions.
+//!
+//! Conversions between integers and their big-endian or little-endian byte
+//! representation. Consensus encoding uses little-endian integers, see
+//! [`WriteExt`](::consensus::encode::WriteExt) and
+//! [`ReadExt`](::consensus::encode::ReadExt) to encode and decode them
+//! directly from a writer or a reader.
+//!
+//! The functions decoding a slice panic if its length doesn't match the size
+//! of the integer.
+//!
+
 macro_rules! define_slice_to_be {
-    ($name: ident, $type: ty) => {
+    ($name: ident, $type: ty, $doc: expr) => {
+        #[doc = $doc]
         #[inline]
         pub fn $name(slice: &[u8]) -> $type {
             assert_eq!(slice.len(), ::core::mem::size_of::<$type>());
@@ -12,7 +25,8 @@ macro_rules! define_slice_to_be {
     }
 }
 macro_rules! define_slice_to_le {
-    ($name: ident, $type: ty) => {
+    ($name: ident, $type: ty, $doc: expr) => {
+        #[doc = $doc]
         #[inline]
         pub fn $name(slice: &[u8]) -> $type {
             assert_eq!(slice.len(), ::core::mem::size_of::<$type>());
@@ -25,7 +39,8 @@ macro_rules! define_slice_to_le {
     }
 }
 macro_rules! define_be_to_array {
-    ($name: ident, $type: ty, $byte_len: expr) => {
+    ($name: ident, $type: ty, $byte_len: expr, $doc: expr) => {
+        #[doc = $doc]
         #[inline]
         pub fn $name(val: $type) -> [u8; $byte_len] {
             debug_assert_eq!(::core::mem::size_of::<$type>(), $byte_len); // size_of isn't a constfn in 1.22
@@ -38,7 +53,8 @@ macro_rules! define_be_to_array {
     }
 }
 macro_rules! define_le_to_array {
-    ($name: ident, $type: ty, $byte_len: expr) => {
+    ($name: ident, $type: ty, $byte_len: expr, $doc: expr) => {
+        #[doc = $doc]
         #[inline]
         pub fn $name(val: $type) -> [u8; $byte_len] {
             debug_assert_eq!(::core::mem::size_of::<$type>(), $byte_len); // size_of isn't a constfn in 1.22
@@ -51,44 +67,53 @@ macro_rules! define_le_to_array {
     }
 }
 
-define_slice_to_be!(slice_to_u32_be, u32);
-define_slice_to_be!(slice_to_u64_be, u64);
-define_be_to_array!(u32_to_array_be, u32, 4);
-define_be_to_array!(u64_to_array_be, u64, 8);
-define_slice_to_le!(slice_to_u16_le, u16);
-define_slice_to_le!(slice_to_u32_le, u32);
-define_slice_to_le!(slice_to_u64_le, u64);
-define_le_to_array!(u16_to_array_le, u16, 2);
-define_le_to_array!(u32_to_array_le, u32, 4);
-define_le_to_array!(u64_to_array_le, u64, 8);
+define_slice_to_be!(slice_to_u16_be, u16, "Decodes a `u16` from 2 big-endian bytes.");
+define_slice_to_be!(slice_to_u32_be, u32, "Decodes a `u32` from 4 big-endian bytes.");
+define_slice_to_be!(slice_to_u64_be, u64, "Decodes a `u64` from 8 big-endian bytes.");
+define_be_to_array!(u16_to_array_be, u16, 2, "Encodes a `u16` as 2 big-endian bytes.");
+define_be_to_array!(u32_to_array_be, u32, 4, "Encodes a `u32` as 4 big-endian bytes.");
+define_be_to_array!(u64_to_array_be, u64, 8, "Encodes a `u64` as 8 big-endian bytes.");
+define_slice_to_le!(slice_to_u16_le, u16, "Decodes a `u16` from 2 little-endian bytes.");
+define_slice_to_le!(slice_to_u32_le, u32, "Decodes a `u32` from 4 little-endian bytes.");
+define_slice_to_le!(slice_to_u64_le, u64, "Decodes a `u64` from 8 little-endian bytes.");
+define_le_to_array!(u16_to_array_le, u16, 2, "Encodes a `u16` as 2 little-endian bytes.");
+define_le_to_array!(u32_to_array_le, u32, 4, "Encodes a `u32` as 4 little-endian bytes.");
+define_le_to_array!(u64_to_array_le, u64, 8, "Encodes a `u64` as 8 little-endian bytes.");
 
+/// Encodes an `i16` as 2 little-endian bytes.
 #[inline]
 pub fn i16_to_array_le(val: i16) -> [u8; 2] {
     u16_to_array_le(val as u16)
 }
+/// Decodes an `i16` from 2 little-endian bytes.
 #[inline]
 pub fn slice_to_i16_le(slice: &[u8]) -> i16 {
     slice_to_u16_le(slice) as i16
 }
+/// Decodes an `i32` from 4 little-endian bytes.
 #[inline]
 pub fn slice_to_i32_le(slice: &[u8]) -> i32 {
     slice_to_u32_le(slice) as i32
 }
+/// Encodes an `i32` as 4 little-endian bytes.
 #[inline]
 pub fn i32_to_array_le(val: i32) -> [u8; 4] {
     u32_to_array_le(val as u32)
 }
+/// Decodes an `i64` from 8 little-endian bytes.
 #[inline]
 pub fn slice_to_i64_le(slice: &[u8]) -> i64 {
     slice_to_u64_le(slice) as i64
 }
+/// Encodes an `i64` as 8 little-endian bytes.
 #[inline]
 pub fn i64_to_array_le(val: i64) -> [u8; 8] {
     u64_to_array_le(val as u64)
 }
 
 macro_rules! define_chunk_slice_to_int {
-    ($name: ident, $type: ty, $converter: ident) => {
+    ($name: ident, $type: ty, $converter: ident, $doc: expr) => {
+        #[doc = $doc]
         #[inline]
         pub fn $name(inp: &[u8], outp: &mut [$type]) {
             assert_eq!(inp.len(), outp.len() * ::core::mem::size_of::<$type>());
@@ -98,7 +123,8 @@ macro_rules! define_chunk_slice_to_int {
         }
     }
 }
-define_chunk_slice_to_int!(bytes_to_u64_slice_le, u64, slice_to_u64_le);
+define_chunk_slice_to_int!(bytes_to_u64_slice_le, u64, slice_to_u64_le,
+    "Decodes consecutive 8-byte little-endian chunks of `inp` into `outp`, which must hold exactly as many `u64`s as there are chunks.");
 
 #[cfg(test)]
 mod tests {
@@ -106,8 +132,10 @@ mod tests {
 
     #[test]
     fn endianness_test() {
+        assert_eq!(slice_to_u16_be(&[0xde, 0xad]), 0xdead);
         assert_eq!(slice_to_u32_be(&[0xde, 0xad, 0xbe, 0xef]), 0xdeadbeef);
         assert_eq!(slice_to_u64_be(&[0xde, 0xad, 0xbe, 0xef, 0x1b, 0xad, 0xca, 0xfe]), 0xdeadbeef1badcafe);
+        assert_eq!(u16_to_array_be(0xdead), [0xde, 0xad]);
         assert_eq!(u32_to_array_be(0xdeadbeef), [0xde, 0xad, 0xbe, 0xef]);
 
         assert_eq!(slice_to_u16_le(&[0xad, 0xde]), 0xdead);
@@ -0,0 +1,128 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Fee rates.
+//!
+//! This module introduces the [`FeeRate`] type, the fee paid per unit of
+//! transaction weight.
+//!
+
+use core::fmt;
+
+use blockdata::constants::WITNESS_SCALE_FACTOR;
+use util::amount::Amount;
+use util::weight::Weight;
+
+/// A fee rate, stored in satoshis per 1000 weight units.
+///
+/// One weight unit is a quarter of a virtual byte, so 1 sat/vB is 250 sat/kwu.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// The zero fee rate.
+    pub const ZERO: FeeRate = FeeRate(0);
+    /// The maximum representable fee rate.
+    pub const MAX: FeeRate = FeeRate(0xffff_ffff_ffff_ffff);
+    /// The minimum fee rate most nodes relay transactions at, 1 sat/vB.
+    pub const BROADCAST_MIN: FeeRate = FeeRate(250);
+
+    /// Creates a fee rate of `sat_kwu` satoshis per 1000 weight units.
+    pub fn from_sat_per_kwu(sat_kwu: u64) -> FeeRate {
+        FeeRate(sat_kwu)
+    }
+
+    /// Creates a fee rate of `sat_vb` satoshis per virtual byte, `None` on overflow.
+    pub fn from_sat_per_vb(sat_vb: u64) -> Option<FeeRate> {
+        // 1 vB = 4 wu, so 1 sat/vB = 1000 / 4 sat/kwu
+        sat_vb.checked_mul(1000 / WITNESS_SCALE_FACTOR as u64).map(FeeRate)
+    }
+
+    /// Creates a fee rate of `sat_kvb` satoshis per 1000 virtual bytes.
+    pub fn from_sat_per_kvb(sat_kvb: u64) -> FeeRate {
+        FeeRate(sat_kvb / WITNESS_SCALE_FACTOR as u64)
+    }
+
+    /// Returns the fee rate in satoshis per 1000 weight units.
+    pub fn to_sat_per_kwu(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the fee rate in satoshis per virtual byte, rounded down.
+    pub fn to_sat_per_vb_floor(self) -> u64 {
+        self.0 / (1000 / WITNESS_SCALE_FACTOR as u64)
+    }
+
+    /// Returns the fee rate in satoshis per virtual byte, rounded up.
+    pub fn to_sat_per_vb_ceil(self) -> u64 {
+        let per_vb = 1000 / WITNESS_SCALE_FACTOR as u64;
+        self.0 / per_vb + if self.0 % per_vb == 0 { 0 } else { 1 }
+    }
+
+    /// Returns the fee rate in satoshis per 1000 virtual bytes, `None` on overflow.
+    pub fn to_sat_per_kvb(self) -> Option<u64> {
+        self.0.checked_mul(WITNESS_SCALE_FACTOR as u64)
+    }
+
+    /// Computes the fee of a transaction of weight `weight`, rounded up, `None` on overflow.
+    pub fn fee_wu(self, weight: Weight) -> Option<Amount> {
+        let fee = self.0.checked_mul(weight.to_wu())?.checked_add(999)? / 1000;
+        Some(Amount::from_sat(fee))
+    }
+
+    /// Computes the fee of a transaction of `vsize` virtual bytes, `None` on overflow.
+    pub fn fee_vb(self, vsize: usize) -> Option<Amount> {
+        self.fee_wu(Weight::from_vb(vsize as u64)?)
+    }
+
+    /// Computes the fee rate of a transaction paying `fee` for weight `weight`, rounded down,
+    /// `None` on overflow or zero weight.
+    pub fn from_fee_and_weight(fee: Amount, weight: Weight) -> Option<FeeRate> {
+        if weight == Weight::ZERO {
+            return None;
+        }
+        fee.as_sat().checked_mul(1000).map(|sat| FeeRate(sat / weight.to_wu()))
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/kwu", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_rate() {
+        let rate = FeeRate::from_sat_per_vb(3).unwrap();
+        assert_eq!(rate, FeeRate::from_sat_per_kwu(750));
+        assert_eq!(rate, FeeRate::from_sat_per_kvb(3000));
+        assert_eq!(rate.to_sat_per_kvb(), Some(3000));
+        assert_eq!(FeeRate::from_sat_per_kwu(251).to_sat_per_vb_floor(), 1);
+        assert_eq!(FeeRate::from_sat_per_kwu(251).to_sat_per_vb_ceil(), 2);
+        assert_eq!(FeeRate::BROADCAST_MIN.to_sat_per_vb_ceil(), 1);
+        assert_eq!(FeeRate::from_sat_per_vb(u64::max_value()), None);
+        assert_eq!(FeeRate::MAX.to_sat_per_kvb(), None);
+
+        assert_eq!(rate.fee_vb(141), Some(Amount::from_sat(423)));
+        assert_eq!(FeeRate::from_sat_per_kwu(1).fee_wu(Weight::from_wu(1)), Some(Amount::from_sat(1)));
+        assert_eq!(FeeRate::MAX.fee_wu(Weight::from_wu(2)), None);
+        assert_eq!(FeeRate::from_fee_and_weight(Amount::from_sat(423), Weight::from_wu(564)), Some(rate));
+        assert_eq!(FeeRate::from_fee_and_weight(Amount::from_sat(1), Weight::ZERO), None);
+        assert_eq!(rate.to_string(), "750 sat/kwu");
+    }
+}
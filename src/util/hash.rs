@@ -25,8 +25,74 @@ use io;
 use core::cmp::min;
 
 use hashes::Hash;
+#[cfg(not(feature = "hw-sha256"))]
+use hashes::{sha256, sha256d, HashEngine};
+#[cfg(feature = "hw-sha256")]
+use sha2::Digest;
 use consensus::encode::Encodable;
 
+/// A double SHA256 engine for consensus hashing.
+///
+/// With the `hw-sha256` feature it uses the implementation of the `sha2` crate, which detects
+/// the SHA extensions of x86 and ARM CPUs at runtime, instead of the portable one of
+/// `bitcoin_hashes`.
+pub(crate) struct Sha256dEngine {
+    #[cfg(feature = "hw-sha256")]
+    engine: ::sha2::Sha256,
+    #[cfg(not(feature = "hw-sha256"))]
+    engine: sha256::HashEngine,
+}
+
+impl Sha256dEngine {
+    /// Creates an engine without data.
+    pub(crate) fn new() -> Sha256dEngine {
+        #[cfg(feature = "hw-sha256")]
+        let engine = ::sha2::Sha256::new();
+        #[cfg(not(feature = "hw-sha256"))]
+        let engine = sha256d::Hash::engine();
+        Sha256dEngine { engine }
+    }
+
+    /// Adds `data` to the hashed data.
+    pub(crate) fn input(&mut self, data: &[u8]) {
+        #[cfg(feature = "hw-sha256")]
+        self.engine.update(data);
+        #[cfg(not(feature = "hw-sha256"))]
+        self.engine.input(data);
+    }
+
+    /// Returns the hash of the data, as a hash type of double SHA256.
+    pub(crate) fn finish<T: Hash<Inner = [u8; 32]>>(self) -> T {
+        #[cfg(feature = "hw-sha256")]
+        {
+            let first = self.engine.finalize();
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&::sha2::Sha256::digest(first));
+            T::from_inner(hash)
+        }
+        #[cfg(not(feature = "hw-sha256"))]
+        T::from_inner(sha256d::Hash::from_engine(self.engine).into_inner())
+    }
+}
+
+impl io::Write for Sha256dEngine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Computes the double SHA256 of `data`.
+pub(crate) fn sha256d<T: Hash<Inner = [u8; 32]>>(data: &[u8]) -> T {
+    let mut engine = Sha256dEngine::new();
+    engine.input(data);
+    engine.finish()
+}
+
 /// Calculates the merkle root of a list of *hashes*, inline (in place) in `hashes`.
 ///
 /// In most cases, you'll want to use [bitcoin_merkle_root] instead.
@@ -84,6 +150,131 @@ where
     Some(merkle_root_r(&mut alloc))
 }
 
+/// Calculates the merkle root of an iterator of *hashes* in constant memory, keeping a single
+/// hash per level of the tree, e.g. for the txids of a block streamed from disk or network.
+///
+/// # Returns
+/// - `None` if `hashes` is empty. The merkle root of an empty tree of hashes is undefined.
+/// - `Some(merkle_root)` otherwise, the same root as [bitcoin_merkle_root].
+pub fn bitcoin_merkle_root_streaming<T, I>(hashes: I) -> Option<T>
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: io::Write,
+    I: IntoIterator<Item=T>,
+{
+    merkle_computation(hashes, None).map(|(root, _)| root)
+}
+
+/// Calculates the merkle branch of the hash at `position` in an iterator of *hashes*, in
+/// constant memory besides the branch: the sibling hashes from the leaf up to the root.
+///
+/// Returns `None` if `position` is out of range. The root follows from the branch with
+/// [merkle_root_from_branch].
+pub fn bitcoin_merkle_branch<T, I>(hashes: I, position: usize) -> Option<Vec<T>>
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: io::Write,
+    I: IntoIterator<Item=T>,
+{
+    merkle_computation(hashes, Some(position)).and_then(|(_, branch)| branch)
+}
+
+/// Calculates the merkle root of the tree in which `leaf` is at `position`, given its merkle
+/// `branch` as returned by [bitcoin_merkle_branch].
+pub fn merkle_root_from_branch<T>(leaf: T, branch: &[T], position: usize) -> T
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: io::Write,
+{
+    let mut hash = leaf;
+    let mut position = position;
+    for sibling in branch {
+        hash = if position & 1 == 1 { hash_pair(sibling, &hash) } else { hash_pair(&hash, sibling) };
+        position >>= 1;
+    }
+    hash
+}
+
+fn hash_pair<T>(left: &T, right: &T) -> T
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: io::Write,
+{
+    let mut encoder = T::engine();
+    left.consensus_encode(&mut encoder).expect("in-memory writers don't error");
+    right.consensus_encode(&mut encoder).expect("in-memory writers don't error");
+    T::from_engine(encoder)
+}
+
+// Bitcoin Core's `MerkleComputation`: `inner[level]` holds the root of the last complete subtree
+// of 2^level hashes not yet combined, and `match_level` the level of the subtree containing the
+// hash at `position`. Returns the root and, if `position` is in range, its branch.
+fn merkle_computation<T, I>(hashes: I, position: Option<usize>) -> Option<(T, Option<Vec<T>>)>
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: io::Write,
+    I: IntoIterator<Item=T>,
+{
+    let position = position.map(|position| position as u64);
+    let mut branch = Vec::new();
+    let mut inner: Vec<T> = Vec::new();
+    let mut match_level = None;
+    let mut count = 0u64;
+
+    for mut hash in hashes {
+        let mut matched = Some(count) == position;
+        count += 1;
+        let mut level = 0;
+        // combine the complete subtrees of the same size
+        while count & (1 << level) == 0 {
+            if matched {
+                branch.push(inner[level]);
+            } else if match_level == Some(level) {
+                branch.push(hash);
+                matched = true;
+            }
+            hash = hash_pair(&inner[level], &hash);
+            level += 1;
+        }
+        if level == inner.len() {
+            inner.push(hash);
+        } else {
+            inner[level] = hash;
+        }
+        if matched {
+            match_level = Some(level);
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+
+    // combine the incomplete subtrees, duplicating the last hash of each level as needed
+    let mut level = count.trailing_zeros() as usize;
+    let mut hash = inner[level];
+    let mut matched = match_level == Some(level);
+    while count != 1 << level {
+        if matched {
+            branch.push(hash);
+        }
+        hash = hash_pair(&hash, &hash);
+        count += 1 << level;
+        level += 1;
+        while count & (1 << level) == 0 {
+            if matched {
+                branch.push(inner[level]);
+            } else if match_level == Some(level) {
+                branch.push(hash);
+                matched = true;
+            }
+            hash = hash_pair(&inner[level], &hash);
+            level += 1;
+        }
+    }
+    let branch = if match_level.is_some() { Some(branch) } else { None };
+    Some((hash, branch))
+}
+
 // `hashes` must contain at least one hash.
 fn merkle_root_r<T>(hashes: &mut [T]) -> T
 where
@@ -133,4 +324,39 @@ mod tests {
         let from_array = bitcoin_merkle_root_inline(&mut hashes_array);
         assert_eq!(from_iter, from_array);
     }
+
+    #[test]
+    fn streaming_merkle_root_and_branch() {
+        // testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
+        let segwit_block = include_bytes!("../../test_data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw");
+        let block: Block = deserialize(&segwit_block[..]).expect("Failed to deserialize block");
+        let txids = block.txdata.iter().map(|tx| tx.txid());
+        assert_eq!(bitcoin_merkle_root_streaming(txids.clone()).unwrap().as_hash(), block.header.merkle_root.as_hash());
+
+        for len in 1..40u8 {
+            let hashes: Vec<sha256d::Hash> = (0..len).map(|i| sha256d::Hash::hash(&[i])).collect();
+            let root = bitcoin_merkle_root_streaming(hashes.iter().cloned()).unwrap();
+            assert_eq!(Some(root), bitcoin_merkle_root(hashes.iter().cloned()));
+            for (position, hash) in hashes.iter().enumerate() {
+                let branch = bitcoin_merkle_branch(hashes.iter().cloned(), position).unwrap();
+                assert_eq!(branch.len(), (hashes.len() as f64).log2().ceil() as usize);
+                assert_eq!(merkle_root_from_branch(*hash, &branch, position), root);
+            }
+            assert_eq!(bitcoin_merkle_branch(hashes.iter().cloned(), hashes.len()), None);
+        }
+        assert_eq!(bitcoin_merkle_root_streaming(Vec::<sha256d::Hash>::new()), None);
+        assert_eq!(bitcoin_merkle_branch(Vec::<sha256d::Hash>::new(), 0), None);
+    }
+
+    #[test]
+    fn sha256d_engine() {
+        let data = [0xabu8; 200];
+        assert_eq!(sha256d::<sha256d::Hash>(&data), sha256d::Hash::hash(&data));
+        assert_eq!(sha256d::<sha256d::Hash>(&[]), sha256d::Hash::hash(&[]));
+
+        let mut engine = Sha256dEngine::new();
+        io::Write::write_all(&mut engine, &data[..70]).unwrap();
+        io::Write::write_all(&mut engine, &data[70..]).unwrap();
+        assert_eq!(engine.finish::<sha256d::Hash>(), sha256d::Hash::hash(&data));
+    }
 }
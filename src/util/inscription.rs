@@ -0,0 +1,302 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Inscription envelopes.
+//!
+//! Ordinal inscriptions embed content in the tapscript revealed by a taproot
+//! script path spend, inside an envelope which is never executed:
+//!
+//! ```text
+//! OP_FALSE OP_IF "ord" <tag> <value> ... OP_0 <body chunk> ... OP_ENDIF
+//! ```
+//!
+//! This module detects these envelopes and extracts their fields and body,
+//! e.g. to classify transactions in a block explorer. It doesn't validate
+//! the content nor implement ordinal theory.
+//!
+
+use prelude::*;
+
+use core::str;
+
+use blockdata::opcodes;
+use blockdata::script::{Instruction, Script};
+use blockdata::transaction::Transaction;
+use blockdata::witness::Witness;
+use util::taproot::TAPROOT_ANNEX_PREFIX;
+
+/// The push identifying inscription envelopes.
+pub const PROTOCOL_ID: &[u8] = b"ord";
+
+/// The tag of the content type field.
+pub const CONTENT_TYPE_TAG: &[u8] = &[1];
+
+/// The tag of the content encoding field.
+pub const CONTENT_ENCODING_TAG: &[u8] = &[9];
+
+/// An inscription extracted from an envelope.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Inscription {
+    /// The tag and value pushes preceding the body, in order. A tag without a value at the
+    /// end of the envelope is dropped.
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+    /// The pushes of the body, `None` if the envelope has no body separator.
+    pub body: Option<Vec<Vec<u8>>>,
+}
+
+impl Inscription {
+    /// Returns the value of the first field with `tag`.
+    pub fn field(&self, tag: &[u8]) -> Option<&[u8]> {
+        self.fields.iter().find(|field| field.0 == tag).map(|field| &field.1[..])
+    }
+
+    /// Returns the MIME content type, `None` if missing or not UTF-8.
+    pub fn content_type(&self) -> Option<&str> {
+        self.field(CONTENT_TYPE_TAG).and_then(|value| str::from_utf8(value).ok())
+    }
+
+    /// Returns the content encoding, e.g. `br`, `None` if missing or not UTF-8.
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.field(CONTENT_ENCODING_TAG).and_then(|value| str::from_utf8(value).ok())
+    }
+
+    /// Returns the body, the concatenation of its pushes.
+    pub fn body(&self) -> Option<Vec<u8>> {
+        self.body.as_ref().map(|chunks| chunks.concat())
+    }
+
+    /// Returns the inscriptions of the envelopes in `script`, in order. Parsing stops at the
+    /// first malformed instruction.
+    pub fn from_tapscript(script: &Script) -> Vec<Inscription> {
+        let mut instructions = Vec::new();
+        for instruction in script.instructions() {
+            match instruction {
+                Ok(instruction) => instructions.push(instruction),
+                Err(_) => break,
+            }
+        }
+
+        let mut inscriptions = Vec::new();
+        let mut i = 0;
+        while i + 3 <= instructions.len() {
+            if instructions[i] == Instruction::PushBytes(&[])
+                && instructions[i + 1] == Instruction::Op(opcodes::all::OP_IF)
+                && instructions[i + 2] == Instruction::PushBytes(PROTOCOL_ID)
+            {
+                match parse_envelope(&instructions[i + 3..]) {
+                    Ok((inscription, len)) => {
+                        inscriptions.push(inscription);
+                        i += 3 + len;
+                    }
+                    // the next envelope can start at the push before the offending opcode at
+                    // the earliest
+                    Err(scanned) => i += 2 + scanned,
+                }
+                continue;
+            }
+            i += 1;
+        }
+        inscriptions
+    }
+
+    /// Returns the inscriptions revealed by the input with `witness`, if it is a taproot script
+    /// path spend.
+    pub fn from_witness(witness: &Witness) -> Vec<Inscription> {
+        tapscript(witness).map_or_else(Vec::new, |script| Inscription::from_tapscript(&script))
+    }
+
+    /// Returns the inscriptions revealed by `tx`, with the index of their input.
+    pub fn from_transaction(tx: &Transaction) -> Vec<(usize, Inscription)> {
+        tx.input.iter().enumerate().flat_map(|(index, input)| {
+            Inscription::from_witness(&input.witness).into_iter().map(move |inscription| (index, inscription))
+        }).collect()
+    }
+}
+
+/// Returns the tapscript of a witness spending a taproot output through a script path: the
+/// element before the control block, ignoring the annex.
+pub fn tapscript(witness: &Witness) -> Option<Script> {
+    let mut elements = witness.to_vec();
+    if elements.len() >= 2 && elements.last().and_then(|annex| annex.first()) == Some(&TAPROOT_ANNEX_PREFIX) {
+        elements.pop();
+    }
+    if elements.len() < 2 {
+        return None;
+    }
+    let script = elements.swap_remove(elements.len() - 2);
+    Some(Script::from(script))
+}
+
+/// Parses the pushes of an envelope up to `OP_ENDIF`, returning the inscription and the number
+/// of instructions consumed.
+///
+/// On failure, returns the index of the non-push opcode which isn't `OP_ENDIF`, or the number of
+/// instructions if the envelope is unterminated.
+fn parse_envelope(instructions: &[Instruction]) -> Result<(Inscription, usize), usize> {
+    let mut pushes = Vec::new();
+    let mut len = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match *instruction {
+            Instruction::PushBytes(data) => pushes.push(data.to_vec()),
+            Instruction::Op(opcodes::all::OP_ENDIF) => {
+                len = Some(i + 1);
+                break;
+            }
+            Instruction::Op(op) => match op.classify(opcodes::ClassifyContext::TapScript) {
+                opcodes::Class::PushNum(-1) => pushes.push(vec![0x81]),
+                opcodes::Class::PushNum(n) => pushes.push(vec![n as u8]),
+                _ => return Err(i),
+            },
+        }
+    }
+    let len = len.ok_or(instructions.len())?;
+
+    let mut inscription = Inscription::default();
+    let mut pushes = pushes.into_iter();
+    while let Some(tag) = pushes.next() {
+        if tag.is_empty() {
+            inscription.body = Some(pushes.collect());
+            break;
+        }
+        if let Some(value) = pushes.next() {
+            inscription.fields.push((tag, value));
+        }
+    }
+    Ok((inscription, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::script::Builder;
+    use blockdata::transaction::TxIn;
+
+    fn envelope(builder: Builder, content_type: &[u8], chunks: &[&[u8]]) -> Builder {
+        let mut builder = builder
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID)
+            .push_slice(CONTENT_TYPE_TAG)
+            .push_slice(content_type)
+            .push_slice(&[]);
+        for chunk in chunks {
+            builder = builder.push_slice(chunk);
+        }
+        builder.push_opcode(opcodes::all::OP_ENDIF)
+    }
+
+    fn reveal(script: &Script) -> Witness {
+        Witness::from_vec(vec![vec![0x01; 64], script.to_bytes(), vec![0xc0; 33]])
+    }
+
+    #[test]
+    fn tapscript_envelope() {
+        let key = Builder::new().push_slice(&[0x02; 32]).push_opcode(opcodes::all::OP_CHECKSIG);
+        let script = envelope(key, b"text/plain;charset=utf-8", &[b"Hello, ", &[0xab; 520], b"world!"]).into_script();
+        let inscriptions = Inscription::from_tapscript(&script);
+        assert_eq!(inscriptions.len(), 1);
+        let inscription = &inscriptions[0];
+        assert_eq!(inscription.content_type(), Some("text/plain;charset=utf-8"));
+        assert_eq!(inscription.content_encoding(), None);
+        assert_eq!(inscription.body.as_ref().unwrap().len(), 3);
+        let body = inscription.body().unwrap();
+        assert_eq!(body.len(), 7 + 520 + 6);
+        assert!(body.starts_with(b"Hello, ") && body.ends_with(b"world!"));
+
+        // two envelopes, and fields without a body
+        let no_body = Builder::new()
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID)
+            .push_int(9)
+            .push_slice(b"br")
+            .push_int(2)
+            .push_opcode(opcodes::all::OP_ENDIF);
+        let script = envelope(no_body, b"image/png", &[&[0x89, 0x50]]).into_script();
+        let inscriptions = Inscription::from_tapscript(&script);
+        assert_eq!(inscriptions.len(), 2);
+        assert_eq!(inscriptions[0].content_encoding(), Some("br"));
+        assert_eq!(inscriptions[0].fields.len(), 1);
+        assert_eq!(inscriptions[0].body(), None);
+        assert_eq!(inscriptions[1].content_type(), Some("image/png"));
+        assert_eq!(inscriptions[1].body(), Some(vec![0x89, 0x50]));
+    }
+
+    #[test]
+    fn not_envelopes() {
+        // unterminated, executed, other protocol and non-push envelopes
+        let unterminated = Builder::new()
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID)
+            .push_slice(&[])
+            .push_slice(b"body")
+            .into_script();
+        let executed = Builder::new()
+            .push_opcode(opcodes::OP_TRUE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID)
+            .push_slice(&[])
+            .push_slice(b"body")
+            .push_opcode(opcodes::all::OP_ENDIF)
+            .into_script();
+        let non_push = Builder::new()
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID)
+            .push_opcode(opcodes::all::OP_DROP)
+            .push_opcode(opcodes::all::OP_ENDIF)
+            .into_script();
+        for script in &[unterminated, executed, non_push, Script::new()] {
+            assert!(Inscription::from_tapscript(script).is_empty());
+        }
+
+        // an envelope starting inside a malformed one
+        let nested = Builder::new()
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF)
+            .push_slice(PROTOCOL_ID);
+        let script = envelope(nested, b"text/plain", &[b"nested"]).into_script();
+        let inscriptions = Inscription::from_tapscript(&script);
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].body(), Some(b"nested".to_vec()));
+    }
+
+    #[test]
+    fn witness_and_transaction() {
+        let script = envelope(Builder::new(), b"text/plain", &[b"ord"]).into_script();
+        let mut witness = reveal(&script);
+        assert_eq!(tapscript(&witness), Some(script.clone()));
+        assert_eq!(Inscription::from_witness(&witness).len(), 1);
+        witness.push([TAPROOT_ANNEX_PREFIX, 0x00]);
+        assert_eq!(tapscript(&witness), Some(script.clone()));
+
+        // a key path spend, with and without annex
+        let key_path = Witness::from_vec(vec![vec![0x01; 64]]);
+        assert_eq!(tapscript(&key_path), None);
+        let key_path = Witness::from_vec(vec![vec![0x01; 64], vec![TAPROOT_ANNEX_PREFIX]]);
+        assert_eq!(tapscript(&key_path), None);
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default(), TxIn { witness: reveal(&script), ..Default::default() }],
+            output: vec![],
+        };
+        let inscriptions = Inscription::from_transaction(&tx);
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].0, 1);
+        assert_eq!(inscriptions[0].1.body(), Some(b"ord".to_vec()));
+    }
+}
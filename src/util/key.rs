@@ -16,7 +16,10 @@
 //! This module provides keys used in Bitcoin that can be roundtrip
 //! (de)serialized.
 
-pub use secp256k1::{XOnlyPublicKey, KeyPair};
+pub use secp256k1::{XOnlyPublicKey, KeyPair, Parity};
+#[cfg(feature = "secp-recovery")]
+#[cfg_attr(docsrs, doc(cfg(feature = "secp-recovery")))]
+pub use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 
 use prelude::*;
 
@@ -27,9 +30,11 @@ use io;
 
 use secp256k1::{self, Secp256k1};
 use network::constants::Network;
+use blockdata::constants::{SECRET_KEY_PREFIX_MAIN, SECRET_KEY_PREFIX_TEST};
 use hashes::{Hash, hash160, hex, hex::FromHex};
 use hash_types::{PubkeyHash, WPubkeyHash};
 use util::base58;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// A key-related error.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -117,6 +122,32 @@ impl PublicKey {
         }
     }
 
+    /// Recovers the public key which created the recoverable signature `sig` of `msg`.
+    ///
+    /// The signature doesn't tell whether the key is used compressed, so a compressed key is
+    /// returned; set [`PublicKey::compressed`] as needed, e.g. from the header byte of a
+    /// [`MessageSignature`](::util::misc::MessageSignature).
+    #[cfg(feature = "secp-recovery")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secp-recovery")))]
+    pub fn recover_from<C: secp256k1::Verification>(
+        secp: &Secp256k1<C>,
+        msg: &secp256k1::Message,
+        sig: &RecoverableSignature,
+    ) -> Result<PublicKey, Error> {
+        Ok(PublicKey::new(secp.recover_ecdsa(msg, sig)?))
+    }
+
+    /// Recovers the public key which created `sig` of `msg` using the global context, see
+    /// [`PublicKey::recover_from`].
+    #[cfg(all(feature = "global-context", feature = "secp-recovery"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "global-context", feature = "secp-recovery"))))]
+    pub fn recover_from_global(
+        msg: &secp256k1::Message,
+        sig: &RecoverableSignature,
+    ) -> Result<PublicKey, Error> {
+        PublicKey::recover_from(secp256k1::SECP256K1, msg, sig)
+    }
+
     /// Returns bitcoin 160-bit hash of the public key
     pub fn pubkey_hash(&self) -> PubkeyHash {
         if self.compressed {
@@ -205,6 +236,30 @@ impl PublicKey {
     pub fn from_private_key<C: secp256k1::Signing>(secp: &Secp256k1<C>, sk: &PrivateKey) -> PublicKey {
         sk.public_key(secp)
     }
+
+    /// Returns the parity of the y-coordinate of the key, which is dropped when converting it
+    /// into an [`XOnlyPublicKey`].
+    pub fn parity(&self) -> Parity {
+        if self.inner.serialize()[0] == 0x03 {
+            Parity::Odd
+        } else {
+            Parity::Even
+        }
+    }
+
+    /// Adds `tweak` times the generator point to the key, as done for pay-to-contract commitments.
+    ///
+    /// The result matches the public key of [`PrivateKey::add_tweak`] with the same tweak. Errors
+    /// if the tweak isn't a valid scalar or if the result is the point at infinity.
+    pub fn add_tweak<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tweak: &[u8; 32],
+    ) -> Result<PublicKey, Error> {
+        let mut inner = self.inner;
+        inner.add_exp_assign(secp, &tweak[..])?;
+        Ok(PublicKey { compressed: self.compressed, inner })
+    }
 }
 
 impl fmt::Display for PublicKey {
@@ -233,6 +288,36 @@ impl FromStr for PublicKey {
     }
 }
 
+/// Generates a valid secp256k1 public key, hashing the x coordinate until it is on the curve.
+#[cfg(feature = "arbitrary")]
+pub(crate) fn arbitrary_secp_public_key(u: &mut Unstructured) -> arbitrary::Result<secp256k1::PublicKey> {
+    let mut key = [0u8; 33];
+    key[0] = if u.arbitrary()? { 0x03 } else { 0x02 };
+    key[1..].copy_from_slice(&<[u8; 32]>::arbitrary(u)?);
+    loop {
+        if let Ok(pk) = secp256k1::PublicKey::from_slice(&key) {
+            return Ok(pk);
+        }
+        let x = ::hashes::sha256::Hash::hash(&key[1..]);
+        key[1..].copy_from_slice(&x[..]);
+    }
+}
+
+/// Generates a valid x-only public key.
+#[cfg(feature = "arbitrary")]
+pub(crate) fn arbitrary_x_only_public_key(u: &mut Unstructured) -> arbitrary::Result<XOnlyPublicKey> {
+    let pk = arbitrary_secp_public_key(u)?;
+    Ok(XOnlyPublicKey::from_slice(&pk.serialize()[1..]).expect("x coordinate of a valid key"))
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for PublicKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(PublicKey { compressed: u.arbitrary()?, inner: arbitrary_secp_public_key(u)? })
+    }
+}
+
 /// A Bitcoin ECDSA private key
 #[derive(Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -274,6 +359,42 @@ impl PrivateKey {
         }
     }
 
+    /// Creates a public key from this private key using the global context.
+    #[cfg(feature = "global-context")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+    pub fn public_key_global(&self) -> PublicKey {
+        self.public_key(secp256k1::SECP256K1)
+    }
+
+    /// Signs `msg` with a signature the public key can be recovered from, see
+    /// [`PublicKey::recover_from`].
+    #[cfg(feature = "secp-recovery")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secp-recovery")))]
+    pub fn sign_recoverable<C: secp256k1::Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        msg: &secp256k1::Message,
+    ) -> RecoverableSignature {
+        secp.sign_ecdsa_recoverable(msg, &self.inner)
+    }
+
+    /// Signs `msg` with a recoverable signature using the global context, see
+    /// [`PrivateKey::sign_recoverable`].
+    #[cfg(all(feature = "global-context", feature = "secp-recovery"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "global-context", feature = "secp-recovery"))))]
+    pub fn sign_recoverable_global(&self, msg: &secp256k1::Message) -> RecoverableSignature {
+        self.sign_recoverable(secp256k1::SECP256K1, msg)
+    }
+
+    /// Adds `tweak` to the key modulo the curve order, keeping its network and compression.
+    ///
+    /// Errors if the tweak isn't a valid scalar or if the result is zero.
+    pub fn add_tweak(&self, tweak: &[u8; 32]) -> Result<PrivateKey, Error> {
+        let mut inner = self.inner;
+        inner.add_assign(&tweak[..])?;
+        Ok(PrivateKey { inner, ..*self })
+    }
+
     /// Serialize the private key to bytes
     pub fn to_bytes(&self) -> Vec<u8> {
         self.inner[..].to_vec()
@@ -288,8 +409,8 @@ impl PrivateKey {
     pub fn fmt_wif(&self, fmt: &mut dyn fmt::Write) -> fmt::Result {
         let mut ret = [0; 34];
         ret[0] = match self.network {
-            Network::Bitcoin => 0xc1,
-            Network::Testnet | Network::Signet | Network::Regtest => 239,
+            Network::Bitcoin => SECRET_KEY_PREFIX_MAIN,
+            Network::Testnet | Network::Signet | Network::Regtest => SECRET_KEY_PREFIX_TEST,
         };
         ret[1..33].copy_from_slice(&self.inner[..]);
         let privkey = if self.compressed {
@@ -322,8 +443,8 @@ impl PrivateKey {
         };
 
         let network = match data[0] {
-            128 => Network::Bitcoin,
-            239 => Network::Testnet,
+            SECRET_KEY_PREFIX_MAIN => Network::Bitcoin,
+            SECRET_KEY_PREFIX_TEST => Network::Testnet,
             x   => {
                 return Err(Error::Base58(base58::Error::InvalidAddressVersion(x)));
             }
@@ -335,6 +456,25 @@ impl PrivateKey {
             inner: secp256k1::SecretKey::from_slice(&data[1..33])?,
         })
     }
+
+    /// Encrypt the private key with a passphrase as per BIP38.
+    #[cfg(feature = "bip38")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "bip38")))]
+    pub fn to_bip38<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>, passphrase: &str) -> String {
+        ::util::bip38::encrypt(secp, self, passphrase)
+    }
+
+    /// Decrypt a BIP38 encrypted private key for use on the given network.
+    #[cfg(feature = "bip38")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "bip38")))]
+    pub fn from_bip38<C: secp256k1::Signing>(
+        secp: &Secp256k1<C>,
+        encrypted: &str,
+        passphrase: &str,
+        network: Network,
+    ) -> Result<PrivateKey, ::util::bip38::Error> {
+        ::util::bip38::decrypt(secp, encrypted, passphrase, network)
+    }
 }
 
 impl fmt::Display for PrivateKey {
@@ -484,6 +624,7 @@ impl<'de> ::serde::Deserialize<'de> for PublicKey {
 mod tests {
     use io;
     use super::{PrivateKey, PublicKey};
+    #[cfg(feature = "secp-recovery")] use super::RecoverableSignature;
     use secp256k1::Secp256k1;
     use std::str::FromStr;
     use hashes::hex::ToHex;
@@ -510,10 +651,10 @@ mod tests {
         assert_eq!(&sk.to_wif(), &sk_str.to_wif());
 
         // mainnet uncompressed
-        let sk = PrivateKey::from_wif("5JYkZjmN7PVMjJUfJWfRFwtuXTGB439XV6faajeHPAM9Z2PT2R3").unwrap();
+        let sk = PrivateKey::from_wif("7Uu92owSW3T5VdFWEC1NFnsF3sHrJovSdeGaSUMYzknMdWVHrhr").unwrap();
         assert_eq!(sk.network, Bitcoin);
         assert_eq!(sk.compressed, false);
-        assert_eq!(&sk.to_wif(), "5JYkZjmN7PVMjJUfJWfRFwtuXTGB439XV6faajeHPAM9Z2PT2R3");
+        assert_eq!(&sk.to_wif(), "7Uu92owSW3T5VdFWEC1NFnsF3sHrJovSdeGaSUMYzknMdWVHrhr");
 
         let secp = Secp256k1::new();
         let mut pk = sk.public_key(&secp);
@@ -521,7 +662,7 @@ mod tests {
         assert_eq!(&pk.to_string(), "042e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af191923a2964c177f5b5923ae500fca49e99492d534aa3759d6b25a8bc971b133");
         assert_eq!(pk, PublicKey::from_str("042e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af191923a2964c177f5b5923ae500fca49e99492d534aa3759d6b25a8bc971b133").unwrap());
         let addr = Address::p2pkh(&pk, sk.network);
-        assert_eq!(&addr.to_string(), "1GhQvF6dL8xa6wBxLnWmHcQsurx9RxiMc8");
+        assert_eq!(&addr.to_string(), "Tq1DuQmeC3YQ5YPfxUVoHtNoT9xQRMsEiq");
         pk.compressed = true;
         assert_eq!(&pk.to_string(), "032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af");
         assert_eq!(pk, PublicKey::from_str("032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af").unwrap());
@@ -589,6 +730,88 @@ mod tests {
         assert_tokens(&pk_u.readable(), &[Token::BorrowedStr(PK_STR_U)]);
     }
 
+    #[test]
+    #[cfg(feature = "secp-recovery")]
+    fn recover_from() {
+        use secp256k1::Message;
+        use super::RecoveryId;
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let pk = sk.public_key(&secp);
+
+        let sig = sk.sign_recoverable(&secp, &msg);
+        assert_eq!(PublicKey::recover_from(&secp, &msg, &sig), Ok(pk));
+        assert!(secp.verify_ecdsa(&msg, &sig.to_standard(), &pk.inner).is_ok());
+
+        // the key recovered from another message or with another recovery id is another key
+        let other_msg = Message::from_slice(&[0x43; 32]).unwrap();
+        assert_ne!(PublicKey::recover_from(&secp, &other_msg, &sig), Ok(pk));
+        let (recid, compact) = sig.serialize_compact();
+        let other_recid = RecoveryId::from_i32(recid.to_i32() ^ 1).unwrap();
+        let other_sig = RecoverableSignature::from_compact(&compact, other_recid).unwrap();
+        assert_ne!(PublicKey::recover_from(&secp, &msg, &other_sig), Ok(pk));
+
+        // recovered keys are compressed
+        let uncompressed = PrivateKey { compressed: false, ..sk };
+        let sig = uncompressed.sign_recoverable(&secp, &msg);
+        assert_eq!(PublicKey::recover_from(&secp, &msg, &sig), Ok(pk));
+    }
+
+    #[test]
+    fn add_tweak() {
+        use util::schnorr::TapTweak;
+        use super::{KeyPair, XOnlyPublicKey};
+
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let pk = sk.public_key(&secp);
+        let tweak = [0x42; 32];
+
+        let tweaked_sk = sk.add_tweak(&tweak).unwrap();
+        assert_eq!(tweaked_sk.network, sk.network);
+        assert_eq!(tweaked_sk.public_key(&secp), pk.add_tweak(&secp, &tweak).unwrap());
+        let uncompressed = PublicKey { compressed: false, ..pk };
+        assert!(!uncompressed.add_tweak(&secp, &tweak).unwrap().compressed);
+
+        // tweaks out of range or cancelling the key are rejected
+        assert!(sk.add_tweak(&[0xff; 32]).is_err());
+        assert!(pk.add_tweak(&secp, &[0xff; 32]).is_err());
+        let mut negated = sk.inner;
+        negated.negate_assign();
+        let mut negated_bytes = [0; 32];
+        negated_bytes.copy_from_slice(&negated[..]);
+        assert!(sk.add_tweak(&negated_bytes).is_err());
+        assert!(pk.add_tweak(&secp, &negated_bytes).is_err());
+
+        // the parity of the key is the one of its serialization
+        assert_eq!(pk.parity().to_u8() + 2, pk.to_bytes()[0]);
+        assert_eq!(uncompressed.parity().to_u8(), uncompressed.to_bytes()[64] & 1);
+
+        // the parity of a tweaked key pair matches the one of the tweaked x-only key
+        let keypair = KeyPair::from_secret_key(&secp, sk.inner);
+        let internal_key = XOnlyPublicKey::from_keypair(&keypair);
+        let (output_key, parity) = internal_key.tap_tweak(&secp, None);
+        assert_eq!(keypair.tap_tweak(&secp, None).public_parts(), (output_key, parity));
+    }
+
+    #[test]
+    #[cfg(all(feature = "global-context", feature = "secp-recovery"))]
+    fn global_context() {
+        use secp256k1::Message;
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let pk = sk.public_key_global();
+        assert_eq!(pk, sk.public_key(&secp));
+
+        let sig = sk.sign_recoverable_global(&msg);
+        assert_eq!(sig, sk.sign_recoverable(&secp, &msg));
+        assert_eq!(PublicKey::recover_from_global(&msg, &sig), Ok(pk));
+    }
+
     fn random_key(mut seed: u8) -> PublicKey {
         loop {
             let mut data = [0; 65];
@@ -64,12 +64,15 @@ use blockdata::constants::{MAX_BLOCK_WEIGHT, MIN_TRANSACTION_WEIGHT};
 use consensus::encode::{self, Decodable, Encodable, MAX_VEC_SIZE};
 use util::merkleblock::MerkleBlockError::*;
 use {Block, BlockHeader};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// An error when verifying the merkle block
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum MerkleBlockError {
     /// When header merkle root don't match to the root calculated from the partial merkle tree
     MerkleRootMismatch,
+    /// When the merkle block's header is not the expected one
+    HeaderMismatch,
     /// When partial merkle tree contains no transactions
     NoTransactions,
     /// When there are too many transactions
@@ -169,6 +172,28 @@ impl PartialMerkleTree {
         pmt
     }
 
+    /// Construct a partial merkle tree including the `txids` for which `match_txids` returns
+    /// true.
+    ///
+    /// Panics when `txids` is empty.
+    pub fn from_txids_with_predicate<F>(txids: &[Txid], match_txids: F) -> Self
+    where
+        F: FnMut(&Txid) -> bool
+    {
+        let matches: Vec<bool> = txids.iter().map(match_txids).collect();
+        PartialMerkleTree::from_txids(txids, &matches)
+    }
+
+    /// Extract the matching txid's represented by this partial merkle tree, each paired with
+    /// its index in the block, in block order.
+    /// returns the merkle root along with them, or error in case of failure
+    pub fn matched_txids(&self) -> Result<(TxMerkleNode, Vec<(u32, Txid)>), MerkleBlockError> {
+        let mut matches = vec![];
+        let mut indexes = vec![];
+        let merkle_root = self.extract_matches(&mut matches, &mut indexes)?;
+        Ok((merkle_root, indexes.into_iter().zip(matches).collect()))
+    }
+
     /// Extract the matching txid's represented by this partial merkle tree
     /// and their respective indices within the partial tree.
     /// returns the merkle root, or error in case of failure
@@ -378,6 +403,19 @@ impl Decodable for PartialMerkleTree {
     }
 }
 
+// The flag bits are generated a byte at a time, as they are encoded.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for PartialMerkleTree {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let num_transactions = u.arbitrary()?;
+        let hashes = u.arbitrary()?;
+        let bytes: Vec<u8> = u.arbitrary()?;
+        let bits = (0..bytes.len() * 8).map(|p| (bytes[p / 8] & (1 << (p % 8) as u8)) != 0).collect();
+        Ok(PartialMerkleTree { num_transactions, hashes, bits })
+    }
+}
+
 /// Data structure that represents a block header paired to a partial merkle tree.
 ///
 /// NOTE: This assumes that the given Block has *at least* 1 transaction. If the Block has 0 txs,
@@ -430,7 +468,7 @@ impl MerkleBlock {
     /// ```
     pub fn from_block_with_predicate<F>(block: &Block, match_txids: F) -> Self
     where
-        F: Fn(&Txid) -> bool
+        F: FnMut(&Txid) -> bool
     {
         let block_txids: Vec<_> = block.txdata.iter().map(Transaction::txid).collect();
         Self::from_header_txids_with_predicate(&block.header, &block_txids, match_txids)
@@ -454,14 +492,9 @@ impl MerkleBlock {
         match_txids: F,
     ) -> Self
     where
-        F: Fn(&Txid) -> bool
+        F: FnMut(&Txid) -> bool
     {
-        let matches: Vec<bool> = block_txids
-            .iter()
-            .map(match_txids)
-            .collect();
-
-        let pmt = PartialMerkleTree::from_txids(block_txids, &matches);
+        let pmt = PartialMerkleTree::from_txids_with_predicate(block_txids, match_txids);
         MerkleBlock {
             header: header.clone(),
             txn: pmt,
@@ -496,6 +529,28 @@ impl MerkleBlock {
             Err(MerkleRootMismatch)
         }
     }
+
+    /// Verify this merkle block proves transactions of the block with `header`, e.g. a proof
+    /// returned by Bitcoin Core's `gettxoutproof` for a header from the local chain.
+    ///
+    /// Returns the matching txid's, each paired with its index in the block, in block order.
+    pub fn verify_against_header(&self, header: &BlockHeader) -> Result<Vec<(u32, Txid)>, MerkleBlockError> {
+        if self.header.block_hash() != header.block_hash() {
+            return Err(HeaderMismatch);
+        }
+        self.matched_txids()
+    }
+
+    /// Extract the matching txid's represented by this merkle block, each paired with its index
+    /// in the block, in block order, after checking them against the header's merkle root.
+    pub fn matched_txids(&self) -> Result<Vec<(u32, Txid)>, MerkleBlockError> {
+        let (merkle_root, matches) = self.txn.matched_txids()?;
+        if merkle_root == self.header.merkle_root {
+            Ok(matches)
+        } else {
+            Err(MerkleRootMismatch)
+        }
+    }
 }
 
 impl Encodable for MerkleBlock {
@@ -515,6 +570,8 @@ impl Decodable for MerkleBlock {
     }
 }
 
+arbitrary_struct_impl!(MerkleBlock, header, txn);
+
 #[cfg(test)]
 mod tests {
     use core::cmp::min;
@@ -526,7 +583,7 @@ mod tests {
 
     use consensus::encode::{deserialize, serialize};
     use util::hash::bitcoin_merkle_root;
-    use util::merkleblock::{MerkleBlock, PartialMerkleTree};
+    use util::merkleblock::{MerkleBlock, MerkleBlockError, PartialMerkleTree};
     use Block;
 
     #[test]
@@ -646,6 +703,17 @@ mod tests {
         );
         // Serialize again and check that it matches the original bytes
         assert_eq!(mb_hex, serialize(&mb).to_hex().as_str());
+
+        // Building the proof from the block gives the same bytes
+        let block = get_block_13b8a();
+        let txid = Txid::from_hex("220ebc64e21abece964927322cba69180ed853bb187fbc6923bac7d010b9d87a").unwrap();
+        assert_eq!(mb.verify_against_header(&block.header), Ok(vec![(3, txid)]));
+        let built = MerkleBlock::from_block_with_predicate(&block, |t| *t == txid);
+        assert_eq!(mb_hex, serialize(&built).to_hex().as_str());
+
+        let mut other = block.header.clone();
+        other.nonce += 1;
+        assert_eq!(mb.verify_against_header(&other), Err(MerkleBlockError::HeaderMismatch));
     }
 
     /// Create a CMerkleBlock using a list of txids which will be found in the
@@ -690,6 +758,34 @@ mod tests {
         assert_eq!(index[1], 8);
     }
 
+    #[test]
+    fn merkleblock_matched_txids() {
+        let block = get_block_13b8a();
+        let txids: Vec<Txid> = block.txdata.iter().map(|tx| tx.txid()).collect();
+
+        // a stateful predicate selecting every third transaction
+        let mut count = 0;
+        let merkle_block = MerkleBlock::from_block_with_predicate(&block, |_| {
+            count += 1;
+            count % 3 == 0
+        });
+        let expected: Vec<(u32, Txid)> = txids.iter().enumerate()
+            .filter(|&(i, _)| i % 3 == 2)
+            .map(|(i, txid)| (i as u32, *txid))
+            .collect();
+        assert_eq!(merkle_block.matched_txids().unwrap(), expected);
+
+        let pmt = PartialMerkleTree::from_txids_with_predicate(&txids, |txid| *txid == txids[1]);
+        let matches: Vec<bool> = (0..txids.len()).map(|i| i == 1).collect();
+        assert_eq!(pmt, PartialMerkleTree::from_txids(&txids, &matches));
+        assert_eq!(pmt.matched_txids().unwrap(), (block.header.merkle_root, vec![(1, txids[1])]));
+
+        let mut header = block.header.clone();
+        header.merkle_root = Default::default();
+        let mismatch = MerkleBlock { header, txn: pmt };
+        assert_eq!(mismatch.matched_txids(), Err(MerkleBlockError::MerkleRootMismatch));
+    }
+
     /// Create a CMerkleBlock using a list of txids which will not be found in the given block
     #[test]
     fn merkleblock_construct_from_txids_not_found() {
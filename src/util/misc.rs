@@ -42,7 +42,8 @@ mod message_signing {
     use secp256k1;
     use secp256k1::ecdsa::{RecoveryId, RecoverableSignature};
 
-    use util::key::PublicKey;
+    use network::constants::Network;
+    use util::key::{PrivateKey, PublicKey};
     use util::address::{Address, AddressType};
 
     /// An error used for dealing with Bitcoin Signed Messages.
@@ -111,6 +112,31 @@ mod message_signing {
             }
         }
 
+        /// Sign a message hash with the given private key, as done by Bitcoin Core's `signmessage`.
+        ///
+        /// To get the message hash from a message, use [super::signed_msg_hash].
+        pub fn sign<C: secp256k1::Signing>(
+            secp_ctx: &secp256k1::Secp256k1<C>,
+            privkey: &PrivateKey,
+            msg_hash: sha256d::Hash
+        ) -> MessageSignature {
+            let msg = secp256k1::Message::from_slice(&msg_hash[..])
+                .expect("cannot fail");
+            MessageSignature {
+                signature: secp_ctx.sign_ecdsa_recoverable(&msg, &privkey.inner),
+                compressed: privkey.compressed,
+            }
+        }
+
+        /// Sign a message hash with the given private key using the global context.
+        ///
+        /// To get the message hash from a message, use [super::signed_msg_hash].
+        #[cfg(feature = "global-context")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+        pub fn sign_global(privkey: &PrivateKey, msg_hash: sha256d::Hash) -> MessageSignature {
+            MessageSignature::sign(secp256k1::SECP256K1, privkey, msg_hash)
+        }
+
         /// Serialize to bytes.
         pub fn serialize(&self) -> [u8; 65] {
             let (recid, raw) = self.signature.serialize_compact();
@@ -157,6 +183,19 @@ mod message_signing {
             })
         }
 
+        /// Recover the P2PKH address of the signer from the signature and the signed message.
+        ///
+        /// To get the message hash from a message, use [super::signed_msg_hash].
+        pub fn recover_address<C: secp256k1::Verification>(
+            &self,
+            secp_ctx: &secp256k1::Secp256k1<C>,
+            msg_hash: sha256d::Hash,
+            network: Network
+        ) -> Result<Address, MessageSignatureError> {
+            let pubkey = self.recover_pubkey(secp_ctx, msg_hash)?;
+            Ok(Address::p2pkh(&pubkey, network))
+        }
+
         /// Verify that the signature signs the message and was signed by the given address.
         ///
         /// To get the message hash from a message, use [super::signed_msg_hash].
@@ -168,14 +207,27 @@ mod message_signing {
         ) -> Result<bool, MessageSignatureError> {
             match address.address_type() {
                 Some(AddressType::P2pkh) => {
-                    let pubkey = self.recover_pubkey(secp_ctx, msg_hash)?;
-                    Ok(*address == Address::p2pkh(&pubkey, address.network))
+                    Ok(*address == self.recover_address(secp_ctx, msg_hash, address.network)?)
                 }
                 Some(address_type) => Err(MessageSignatureError::UnsupportedAddressType(address_type)),
                 None => Ok(false),
             }
         }
 
+        /// Verify that the signature signs the message and was signed by the given address,
+        /// using the global context.
+        ///
+        /// To get the message hash from a message, use [super::signed_msg_hash].
+        #[cfg(feature = "global-context")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "global-context")))]
+        pub fn is_signed_by_address_global(
+            &self,
+            address: &Address,
+            msg_hash: sha256d::Hash
+        ) -> Result<bool, MessageSignatureError> {
+            self.is_signed_by_address(secp256k1::SECP256K1, address, msg_hash)
+        }
+
         /// Convert a signature from base64 encoding.
         #[cfg(feature = "base64")]
         #[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
@@ -356,6 +408,45 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(all(feature = "secp-recovery", feature = "base64"))]
+    fn test_sign_message() {
+        use core::str::FromStr;
+        use secp256k1;
+        use util::key::PrivateKey;
+
+        let secp = secp256k1::Secp256k1::new();
+        let msg_hash = super::signed_msg_hash("rust-bitcoin MessageSignature test");
+
+        for privkey in &[
+            PrivateKey::from_str("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap(),
+            PrivateKey::from_str("92Pg46rUhgTT7romnV7iGW6W1gbGdeezqdbJCzShkCsYNzyyNcc").unwrap(),
+        ] {
+            let signature = super::MessageSignature::sign(&secp, privkey, msg_hash);
+            assert_eq!(signature.compressed, privkey.compressed);
+
+            let signature = super::MessageSignature::from_base64(&signature.to_base64()).unwrap();
+            let p2pkh = ::Address::p2pkh(&privkey.public_key(&secp), privkey.network);
+            assert_eq!(signature.recover_address(&secp, msg_hash, privkey.network), Ok(p2pkh.clone()));
+            assert_eq!(signature.is_signed_by_address(&secp, &p2pkh, msg_hash), Ok(true));
+            assert_eq!(signature.is_signed_by_address(&secp, &p2pkh, signed_msg_hash("other")), Ok(false));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "global-context", feature = "secp-recovery"))]
+    fn test_sign_message_global() {
+        use core::str::FromStr;
+        use util::key::PrivateKey;
+
+        let msg_hash = super::signed_msg_hash("rust-bitcoin MessageSignature test");
+        let privkey = PrivateKey::from_str("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let signature = super::MessageSignature::sign_global(&privkey, msg_hash);
+        let p2pkh = ::Address::p2pkh(&privkey.public_key_global(), privkey.network);
+        assert_eq!(signature.is_signed_by_address_global(&p2pkh, msg_hash), Ok(true));
+        assert_eq!(signature.is_signed_by_address_global(&p2pkh, signed_msg_hash("other")), Ok(false));
+    }
+
     #[test]
     #[cfg(all(feature = "secp-recovery", feature = "base64"))]
     fn test_incorrect_message_signature() {
@@ -22,19 +22,35 @@ pub mod ecdsa;
 pub mod schnorr;
 pub mod address;
 pub mod amount;
+pub mod fee_rate;
+pub mod weight;
 pub mod base58;
 pub mod bip32;
+pub mod bip85;
+#[cfg(feature = "bip38")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bip38")))]
+pub mod bip38;
 pub mod bip143;
+pub mod bip157;
+pub mod bip21;
+pub mod bip352;
 pub mod hash;
+pub mod inscription;
 pub mod merkleblock;
 pub mod misc;
 pub mod psbt;
 pub mod taproot;
 pub mod uint;
+pub mod pow;
 pub mod bip158;
+pub mod bloom;
 pub mod sighash;
-
-pub(crate) mod endian;
+pub mod siphash;
+pub mod signet;
+pub mod endian;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod electrum;
 
 use prelude::*;
 use io;
@@ -77,6 +93,8 @@ pub enum Error {
     BlockBadProofOfWork,
     /// The `target` field of a block header did not match the expected difficulty
     BlockBadTarget,
+    /// The proof-of-work hash function is not available in this build
+    UnsupportedPowAlgorithm,
 }
 
 impl fmt::Display for Error {
@@ -86,6 +104,7 @@ impl fmt::Display for Error {
             Error::Network(ref e) => fmt::Display::fmt(e, f),
             Error::BlockBadProofOfWork => f.write_str("block target correct but not attained"),
             Error::BlockBadTarget => f.write_str("block target incorrect"),
+            Error::UnsupportedPowAlgorithm => f.write_str("proof-of-work algorithm not supported by this build"),
         }
     }
 }
@@ -96,7 +115,7 @@ impl ::std::error::Error for Error {
         match *self {
             Error::Encode(ref e) => Some(e),
             Error::Network(ref e) => Some(e),
-            Error::BlockBadProofOfWork | Error::BlockBadTarget => None
+            Error::BlockBadProofOfWork | Error::BlockBadTarget | Error::UnsupportedPowAlgorithm => None
         }
     }
 }
@@ -0,0 +1,462 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Proof-of-work targets.
+//!
+//! Types for the proof-of-work target, both in its compact "nBits" encoding
+//! as found in block headers and as the full 256-bit value a block hash is
+//! compared against.
+//!
+
+use core::fmt;
+
+use io;
+use hashes::Hash;
+use hash_types::PowHash;
+use blockdata::block::BlockHeader;
+use consensus::encode::{self, Decodable, Encodable};
+use util::endian;
+use util::uint::Uint256;
+
+/// The proof-of-work target in the compact "nBits" encoding used by block headers.
+///
+/// The encoding is a floating point-like format, so not every [`Target`] can be represented
+/// exactly; converting a target to a compact target rounds it down.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompactTarget(u32);
+
+impl CompactTarget {
+    /// Creates a [`CompactTarget`] from the `nBits` value of a block header.
+    pub fn from_consensus(bits: u32) -> Self {
+        CompactTarget(bits)
+    }
+
+    /// Returns the `nBits` value as it is found in a block header.
+    pub fn to_consensus(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<CompactTarget> for Target {
+    fn from(compact: CompactTarget) -> Target {
+        Target::from_compact(compact)
+    }
+}
+
+impl fmt::LowerHex for CompactTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for CompactTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+impl Encodable for CompactTarget {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(w)
+    }
+}
+
+impl Decodable for CompactTarget {
+    fn consensus_decode<R: io::Read + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        u32::consensus_decode(r).map(CompactTarget)
+    }
+}
+
+/// The proof-of-work target: a block hash, read as a little-endian 256-bit number, must not
+/// exceed it for the block to be valid.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Target(pub(crate) Uint256);
+
+impl Target {
+    /// The zero target, which no block hash other than the all-zero one can meet.
+    pub const ZERO: Target = Target(Uint256([0, 0, 0, 0]));
+
+    /// The largest target, which any block hash meets.
+    pub const MAX: Target = Target(Uint256([::core::u64::MAX; 4]));
+
+    /// The target of difficulty one, `0x1d00ffff` in compact form, which difficulties are
+    /// relative to.
+    pub const DIFFICULTY_ONE: Target = Target(Uint256([0, 0, 0, 0xffff0000]));
+
+    /// Creates a [`Target`] from a 256-bit integer.
+    pub fn from_uint256(value: Uint256) -> Self {
+        Target(value)
+    }
+
+    /// Returns the target as a 256-bit integer.
+    pub fn to_uint256(self) -> Uint256 {
+        self.0
+    }
+
+    /// Expands a compact target into the full 256-bit target.
+    ///
+    /// Negative and overflowing compact encodings are not valid targets and yield
+    /// [`Target::ZERO`], so they can never be met.
+    ///
+    /// ```
+    /// use bitcoin::util::pow::{CompactTarget, Target};
+    ///
+    /// let compact = CompactTarget::from_consensus(0x1d00ffff);
+    /// assert_eq!(Target::from_compact(compact).to_compact_lossy(), compact);
+    /// ```
+    pub fn from_compact(compact: CompactTarget) -> Self {
+        let bits = compact.0;
+        // This is a floating-point "compact" encoding originally used by
+        // OpenSSL, which satoshi put into consensus code, so we're stuck
+        // with it. The exponent needs to have 3 subtracted from it, hence
+        // this goofy decoding code:
+        let (mant, expt) = {
+            let unshifted_expt = bits >> 24;
+            if unshifted_expt <= 3 {
+                ((bits & 0x7FFFFF) >> (8 * (3 - unshifted_expt as usize)), 0)
+            } else {
+                (bits & 0x7FFFFF, 8 * ((bits >> 24) - 3))
+            }
+        };
+
+        // The sign bit is tested on the unshifted bits, as Bitcoin Core does, and the value
+        // must fit in 256 bits.
+        let size = bits >> 24;
+        let negative = mant != 0 && bits & 0x0080_0000 != 0;
+        let overflow = mant != 0 && (size > 34 || (mant > 0xff && size > 33) || (mant > 0xffff && size > 32));
+        if negative || overflow {
+            Target::ZERO
+        } else {
+            Target(Uint256::from_u64(mant as u64).unwrap() << (expt as usize))
+        }
+    }
+
+    /// Encodes the target in compact form, discarding any precision the encoding cannot hold.
+    pub fn to_compact_lossy(self) -> CompactTarget {
+        let mut size = (self.0.bits() + 7) / 8;
+        let mut compact = if size <= 3 {
+            (self.0.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            let bn = self.0 >> (8 * (size - 3));
+            bn.low_u32()
+        };
+
+        if (compact & 0x00800000) != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+
+        CompactTarget(compact | (size << 24) as u32)
+    }
+
+    /// Returns whether `hash` is at or below this target, i.e. whether it is a valid
+    /// proof-of-work for the target.
+    ///
+    /// `hash` is the [`PowHash`] of a header, or its block hash on chains using double SHA256.
+    pub fn is_met_by<H: Hash<Inner = [u8; 32]>>(&self, hash: H) -> bool {
+        let mut ret = [0u64; 4];
+        endian::bytes_to_u64_slice_le(hash.as_inner(), &mut ret);
+        Uint256(ret) <= self.0
+    }
+
+    /// Returns the expected number of hashes needed to meet this target, which is the amount of
+    /// work a block with this target adds to its chain.
+    ///
+    /// This is `2**256 / (target + 1)`; the zero target contributes no work.
+    pub fn to_work(self) -> Uint256 {
+        if self == Target::ZERO {
+            return Uint256::default();
+        }
+        // 2**256 / (target + 1) == ~target / (target+1) + 1    (eqn shamelessly stolen from bitcoind)
+        let mut ret = !self.0;
+        let mut ret1 = self.0;
+        ret1.increment();
+        if ret1 == Uint256::default() {
+            // target + 1 == 2**256, so any hash meets the target.
+            return Uint256::from_u64(1).unwrap();
+        }
+        ret = ret / ret1;
+        ret.increment();
+        ret
+    }
+
+    /// Returns the difficulty of this target, see [`Difficulty`].
+    pub fn difficulty(self) -> Difficulty {
+        Difficulty(self)
+    }
+}
+
+/// The proof-of-work difficulty: how many times harder than [`Target::DIFFICULTY_ONE`] a
+/// target is to meet.
+///
+/// The difficulty keeps the exact target it was created from, so converting back and forth
+/// between the two is lossless and difficulties compare like their targets do, in reverse.
+/// [`Difficulty::to_f64`] and `Display` give the floating-point value block explorers and
+/// `getdifficulty` show.
+///
+/// ```
+/// use bitcoin::util::pow::{CompactTarget, Difficulty, Target};
+///
+/// let target = Target::from_compact(CompactTarget::from_consensus(0x1b0404cb));
+/// assert_eq!(target.difficulty().to_string(), "16307.420938523983");
+/// assert!(target.difficulty() > Difficulty::ONE);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Difficulty(Target);
+
+impl Difficulty {
+    /// The difficulty of [`Target::DIFFICULTY_ONE`].
+    pub const ONE: Difficulty = Difficulty(Target::DIFFICULTY_ONE);
+
+    /// Creates the difficulty of `target`.
+    pub fn from_target(target: Target) -> Self {
+        Difficulty(target)
+    }
+
+    /// Returns the target of this difficulty.
+    pub fn to_target(self) -> Target {
+        self.0
+    }
+
+    /// Creates a difficulty from its floating-point value, rounding its target.
+    ///
+    /// Difficulties of zero or less, or too small to have a target, yield the difficulty of
+    /// [`Target::MAX`].
+    pub fn from_f64(difficulty: f64) -> Self {
+        // The difficulty one target is 0xffff << 208.
+        let mut value = 0xffff as f64 / difficulty;
+        if difficulty.is_nan() || difficulty <= 0.0 || value >= (1u64 << 48) as f64 {
+            return Difficulty(Target::MAX);
+        }
+        let mut shift = 208;
+        while value < (1u64 << 52) as f64 && shift > 0 {
+            value *= 2.0;
+            shift -= 1;
+        }
+        Difficulty(Target(Uint256::from_u64(value as u64).unwrap() << shift))
+    }
+
+    /// Returns the floating-point value of the difficulty, infinite for [`Target::ZERO`].
+    pub fn to_f64(self) -> f64 {
+        let to_f64 = |value: Uint256| value.0.iter().rev().fold(0.0, |acc, &word| acc * 18446744073709551616.0 + word as f64);
+        to_f64(Target::DIFFICULTY_ONE.0) / to_f64((self.0).0)
+    }
+}
+
+impl From<Target> for Difficulty {
+    fn from(target: Target) -> Difficulty {
+        Difficulty(target)
+    }
+}
+
+impl From<Difficulty> for Target {
+    fn from(difficulty: Difficulty) -> Target {
+        difficulty.0
+    }
+}
+
+impl PartialOrd for Difficulty {
+    fn partial_cmp(&self, other: &Difficulty) -> Option<::core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Difficulty {
+    fn cmp(&self, other: &Difficulty) -> ::core::cmp::Ordering {
+        // A smaller target is harder to meet.
+        other.0.cmp(&self.0)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+/// Sums the work of a chain of headers.
+///
+/// Comparing the result for two competing chains that share a common ancestor tells which one
+/// carries the most work, and so which one a node should follow. The headers are not validated.
+pub fn chain_work<'a, I>(headers: I) -> Uint256
+where
+    I: IntoIterator<Item = &'a BlockHeader>,
+{
+    headers.into_iter().fold(Uint256::default(), |acc, header| acc + header.work())
+}
+
+impl From<Uint256> for Target {
+    fn from(value: Uint256) -> Target {
+        Target(value)
+    }
+}
+
+impl From<Target> for Uint256 {
+    fn from(target: Target) -> Uint256 {
+        target.0
+    }
+}
+
+impl fmt::LowerHex for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+/// A hash function block headers prove their work with.
+pub trait PowHasher {
+    /// Hashes a consensus-serialized block header, `None` if the hash function isn't available
+    /// in this build.
+    fn pow_hash(&self, header: &[u8]) -> Option<PowHash>;
+}
+
+/// The proof-of-work hash functions a chain can be configured with in its
+/// [`Params`](::consensus::Params).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PowAlgorithm {
+    /// Double SHA256, as used by Bitcoin, for which the proof-of-work hash is the block hash.
+    Sha256d,
+    /// scrypt with `N = 1024`, `r = 1` and `p = 1`, using the header as both password and salt,
+    /// as used by Litecoin.
+    ///
+    /// Hashing is only supported with the `scrypt-pow` feature.
+    Scrypt,
+}
+
+impl Default for PowAlgorithm {
+    fn default() -> Self {
+        PowAlgorithm::Sha256d
+    }
+}
+
+impl PowHasher for PowAlgorithm {
+    fn pow_hash(&self, header: &[u8]) -> Option<PowHash> {
+        match *self {
+            PowAlgorithm::Sha256d => Some(PowHash::hash(header)),
+            PowAlgorithm::Scrypt => scrypt_pow_hash(header),
+        }
+    }
+}
+
+#[cfg(feature = "scrypt-pow")]
+fn scrypt_pow_hash(header: &[u8]) -> Option<PowHash> {
+    let params = ::scrypt::Params::new(10, 1, 1).expect("valid scrypt parameters");
+    let mut hash = [0u8; 32];
+    ::scrypt::scrypt(header, header, &params, &mut hash).expect("valid scrypt output length");
+    Some(PowHash::from_inner(hash))
+}
+
+#[cfg(not(feature = "scrypt-pow"))]
+fn scrypt_pow_hash(_header: &[u8]) -> Option<PowHash> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash_types::BlockHash;
+    use blockdata::block::Version;
+
+    #[test]
+    fn compact_roundtrip() {
+        for &bits in &[0x1d00ffff, 0x1a06d450, 0x207fffff, 0x1b0404cb, 0x03123456, 0x01120000] {
+            let compact = CompactTarget::from_consensus(bits);
+            assert_eq!(Target::from_compact(compact).to_compact_lossy(), compact);
+        }
+        assert_eq!(
+            format!("{:x}", Target::from_compact(CompactTarget::from_consensus(0x1d00ffff))),
+            "00000000ffff0000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x03123456)).to_uint256().low_u64(), 0x123456);
+    }
+
+    #[test]
+    fn compact_invalid() {
+        // negative
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x04923456)), Target::ZERO);
+        // negative, with the sign bit shifted out of the mantissa
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x02800100)), Target::ZERO);
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x01803456)), Target::ZERO);
+        // the sign of zero is ignored
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x01800000)), Target::ZERO);
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x02000100)).to_uint256().low_u64(), 1);
+        // overflowing
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0xff123456)), Target::ZERO);
+        assert_eq!(Target::from_compact(CompactTarget::from_consensus(0x21010000)), Target::ZERO);
+        // largest non-overflowing exponent
+        assert_ne!(Target::from_compact(CompactTarget::from_consensus(0x20010000)), Target::ZERO);
+    }
+
+    #[test]
+    fn work() {
+        let target = Target::from_compact(CompactTarget::from_consensus(0x1d00ffff));
+        assert_eq!(target.to_work(), Uint256([0x100010001u64, 0, 0, 0]));
+        assert_eq!(Target::ZERO.to_work(), Uint256::default());
+        assert_eq!(Target::from_uint256(!Uint256::default()).to_work(), Uint256::from_u64(1).unwrap());
+
+        let header = BlockHeader {
+            version: Version::ONE,
+            prev_blockhash: Default::default(),
+            merkle_root: Default::default(),
+            time: 0,
+            bits: 0x1d00ffff,
+            nonce: 0,
+            aux_data: None,
+        };
+        let mut harder = header.clone();
+        harder.bits = 0x1c3fffc0;
+        assert_eq!(chain_work(&[header.clone(), header.clone()]), Uint256([0x200020002u64, 0, 0, 0]));
+        assert_eq!(chain_work(&[harder.clone()]), Uint256([0x400040004u64, 0, 0, 0]));
+        assert!(chain_work(&[harder]) > chain_work(&[header.clone(), header]));
+        assert_eq!(chain_work(&[]), Uint256::default());
+    }
+
+    #[test]
+    fn difficulty() {
+        let target = |bits| Target::from_compact(CompactTarget::from_consensus(bits));
+        assert_eq!(target(0x1d00ffff).difficulty(), Difficulty::ONE);
+        assert_eq!(Difficulty::ONE.to_f64(), 1.0);
+        assert_eq!(Difficulty::ONE.to_string(), "1");
+        assert_eq!(format!("{:.2}", target(0x1b0404cb).difficulty()), "16307.42");
+        assert_eq!(format!("{:.8}", target(0x207fffff).difficulty()), "0.00000000");
+        assert_eq!(Target::ZERO.difficulty().to_f64(), ::core::f64::INFINITY);
+
+        // ordering is the reverse of the targets'
+        assert!(target(0x1b0404cb).difficulty() > target(0x1d00ffff).difficulty());
+        assert!(Target::MAX.difficulty() < Difficulty::ONE);
+
+        // lossless conversion to and from targets
+        assert_eq!(Target::from(Difficulty::from(target(0x1a06d450))), target(0x1a06d450));
+        assert_eq!(Difficulty::from_f64(1.0), Difficulty::ONE);
+        assert_eq!(Difficulty::from_f64(2.0).to_target(), Target(Target::DIFFICULTY_ONE.0 >> 1));
+        assert_eq!(Difficulty::from_f64(16307.420938523983).to_target().to_compact_lossy(), CompactTarget::from_consensus(0x1b0404cb));
+        assert_eq!(Difficulty::from_f64(0.0), Target::MAX.difficulty());
+        assert_eq!(Difficulty::from_f64(-1.0), Target::MAX.difficulty());
+        assert_eq!(Difficulty::from_f64(::core::f64::NAN), Target::MAX.difficulty());
+        assert!(Difficulty::from_f64(1e-3) < Difficulty::ONE);
+    }
+
+    #[test]
+    fn target_is_met_by() {
+        let target = Target::from_compact(CompactTarget::from_consensus(0x1d00ffff));
+        let hash = |s: &str| s.parse::<BlockHash>().unwrap();
+        // mainnet genesis
+        assert!(target.is_met_by(hash("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")));
+        assert!(target.is_met_by(hash("00000000ffff0000000000000000000000000000000000000000000000000000")));
+        assert!(!target.is_met_by(hash("00000000ffff0000000000000000000000000000000000000000000000000001")));
+        assert!(!Target::ZERO.is_met_by(hash("0000000000000000000000000000000000000000000000000000000000000001")));
+    }
+}
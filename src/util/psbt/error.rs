@@ -21,6 +21,7 @@ use consensus::encode;
 use util::psbt::raw;
 
 use hashes;
+use util::amount::Amount;
 use util::bip32::ExtendedPubKey;
 
 /// Enum for marking psbt hash error.
@@ -77,8 +78,40 @@ pub enum Error {
     /// Conflicting data during combine procedure:
     /// global extended public key has inconsistent key sources
     CombineInconsistentKeySources(ExtendedPubKey),
+    /// Conflicting data during combine procedure:
+    /// both PSBTs hold different values for the same key
+    CombineConflict(raw::Key),
     /// Serialization error in bitcoin consensus-encoded structures
     ConsensusEncoding,
+    /// The input at the given index has no witness or non-witness UTXO
+    MissingUtxo(usize),
+    /// The UTXO data of the input at the given index does not match its
+    /// previous output
+    MismatchedUtxo(usize),
+    /// The input at the given index spends a non-witness output but only
+    /// carries a witness UTXO
+    MissingNonWitnessUtxo(usize),
+    /// The outputs spend more than the inputs
+    NegativeFee,
+    /// Summing up input or output values overflowed
+    ValueOverflow,
+    /// The fee exceeds the maximum fee accepted by the sanity check
+    AbsurdFee(Amount),
+    /// The input at the given index carries data for another kind of spend
+    /// than the output it spends, e.g. taproot signatures for a segwit v0
+    /// output
+    MixedSpends(usize),
+    /// The weight of the input at the given index can't be predicted before
+    /// it is finalized
+    UnknownInputWeight(usize),
+    /// The finalizer does not support the script spent by the input at the
+    /// given index
+    UnsupportedScript(usize),
+    /// The redeem or witness script of the input at the given index is missing
+    /// or does not match the spent script
+    MissingScript(usize),
+    /// The input at the given index lacks the signatures to be finalized
+    NotEnoughSignatures(usize),
 }
 
 impl fmt::Display for Error {
@@ -103,7 +136,19 @@ impl fmt::Display for Error {
                 write!(f, "Preimage {:?} does not match {:?} hash {:?}", preimage, hash_type, hash )
             }
             Error::CombineInconsistentKeySources(ref s) => { write!(f, "combine conflict: {}", s) }
+            Error::CombineConflict(ref rkey) => write!(f, "combine conflict: {}", rkey),
             Error::ConsensusEncoding => f.write_str("bitcoin consensus or BIP-174 encoding error"),
+            Error::MissingUtxo(i) => write!(f, "input {} has no UTXO information", i),
+            Error::MismatchedUtxo(i) => write!(f, "UTXO information of input {} does not match its previous output", i),
+            Error::MissingNonWitnessUtxo(i) => write!(f, "input {} spends a non-witness output without non-witness UTXO", i),
+            Error::NegativeFee => f.write_str("outputs exceed inputs"),
+            Error::ValueOverflow => f.write_str("value overflow"),
+            Error::AbsurdFee(fee) => write!(f, "absurdly high fee: {}", fee),
+            Error::MixedSpends(i) => write!(f, "input {} carries data of another kind of spend than its output", i),
+            Error::UnknownInputWeight(i) => write!(f, "cannot predict the weight of input {}", i),
+            Error::UnsupportedScript(i) => write!(f, "cannot finalize input {}: unsupported script", i),
+            Error::MissingScript(i) => write!(f, "cannot finalize input {}: missing or mismatched redeem or witness script", i),
+            Error::NotEnoughSignatures(i) => write!(f, "cannot finalize input {}: not enough signatures", i),
         }
     }
 }
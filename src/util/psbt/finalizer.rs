@@ -0,0 +1,293 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! PSBT finalizer.
+//!
+//! Implementation of the BIP174 Input Finalizer role for standard script
+//! types: P2PKH, P2WPKH, P2SH-P2WPKH, bare multisig wrapped in P2SH, P2WSH or
+//! P2SH-P2WSH and P2TR key path spends. Other scripts require a more general
+//! finalizer such as the one provided by rust-miniscript.
+//!
+
+use prelude::*;
+
+use core::mem;
+
+use blockdata::opcodes::{self, Class, ClassifyContext};
+use blockdata::script::{Builder, Instruction, Script};
+use blockdata::witness::Witness;
+use util::key::PublicKey;
+use util::psbt::{Error, Input, PartiallySignedTransaction};
+
+impl PartiallySignedTransaction {
+    /// Finalizes all inputs, see [`Self::finalize_input`].
+    pub fn finalize(&mut self) -> Result<(), Error> {
+        for index in 0..self.inputs.len() {
+            self.finalize_input(index)?;
+        }
+        Ok(())
+    }
+
+    /// Finalizes the input at `index` from its partial signatures.
+    ///
+    /// Builds the final scriptSig and script witness, then clears all other
+    /// fields of the input except the UTXOs, proprietary and unknown key-value
+    /// pairs, as required by BIP174. Inputs which are already finalized are
+    /// left untouched.
+    pub fn finalize_input(&mut self, index: usize) -> Result<(), Error> {
+        let script_pubkey = self.spent_utxo(index)?.script_pubkey.clone();
+        let input = &mut self.inputs[index];
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Ok(());
+        }
+
+        let (script_sig, witness) = final_scripts(index, &script_pubkey, input)?;
+        let old = mem::replace(input, Input::default());
+        *input = Input {
+            non_witness_utxo: old.non_witness_utxo,
+            witness_utxo: old.witness_utxo,
+            final_script_sig: if script_sig.is_empty() { None } else { Some(script_sig) },
+            final_script_witness: if witness.is_empty() { None } else { Some(Witness::from_vec(witness)) },
+            proprietary: old.proprietary,
+            unknown: old.unknown,
+            ..Default::default()
+        };
+        Ok(())
+    }
+}
+
+/// Builds the final scriptSig and witness stack spending `script_pubkey`.
+fn final_scripts(index: usize, script_pubkey: &Script, input: &Input) -> Result<(Script, Vec<Vec<u8>>), Error> {
+    if script_pubkey.is_p2pkh() {
+        let (pk, sig) = input.partial_sigs.iter()
+            .find(|kv| Script::new_p2pkh(&kv.0.pubkey_hash()) == *script_pubkey)
+            .ok_or(Error::NotEnoughSignatures(index))?;
+        let script_sig = Builder::new().push_slice(&sig.to_vec()).push_key(pk).into_script();
+        Ok((script_sig, vec![]))
+    } else if script_pubkey.is_v0_p2wpkh() {
+        Ok((Script::new(), p2wpkh_witness(index, script_pubkey, input)?))
+    } else if script_pubkey.is_v0_p2wsh() {
+        Ok((Script::new(), p2wsh_witness(index, script_pubkey, input)?))
+    } else if script_pubkey.is_p2sh() {
+        let redeem_script = match input.redeem_script {
+            Some(ref script) if Script::new_p2sh(&script.script_hash()) == *script_pubkey => script,
+            _ => return Err(Error::MissingScript(index)),
+        };
+        let script_sig = Builder::new().push_slice(redeem_script.as_bytes()).into_script();
+        if redeem_script.is_v0_p2wpkh() {
+            Ok((script_sig, p2wpkh_witness(index, redeem_script, input)?))
+        } else if redeem_script.is_v0_p2wsh() {
+            Ok((script_sig, p2wsh_witness(index, redeem_script, input)?))
+        } else {
+            let mut builder = Builder::new().push_opcode(opcodes::all::OP_PUSHBYTES_0);
+            for sig in multisig_signatures(index, redeem_script, input)? {
+                builder = builder.push_slice(&sig);
+            }
+            Ok((builder.push_slice(redeem_script.as_bytes()).into_script(), vec![]))
+        }
+    } else if script_pubkey.is_v1_p2tr() {
+        let sig = input.tap_key_sig.ok_or(Error::NotEnoughSignatures(index))?;
+        Ok((Script::new(), vec![sig.to_vec()]))
+    } else {
+        Err(Error::UnsupportedScript(index))
+    }
+}
+
+/// Builds the witness spending the P2WPKH `program`.
+fn p2wpkh_witness(index: usize, program: &Script, input: &Input) -> Result<Vec<Vec<u8>>, Error> {
+    let (pk, sig) = input.partial_sigs.iter()
+        .find(|kv| kv.0.wpubkey_hash().map(|hash| Script::new_v0_p2wpkh(&hash)).as_ref() == Some(program))
+        .ok_or(Error::NotEnoughSignatures(index))?;
+    Ok(vec![sig.to_vec(), pk.to_bytes()])
+}
+
+/// Builds the witness spending the P2WSH `program` with a multisig witness script.
+fn p2wsh_witness(index: usize, program: &Script, input: &Input) -> Result<Vec<Vec<u8>>, Error> {
+    let witness_script = match input.witness_script {
+        Some(ref script) if Script::new_v0_p2wsh(&script.wscript_hash()) == *program => script,
+        _ => return Err(Error::MissingScript(index)),
+    };
+    let mut witness = vec![vec![]];
+    witness.extend(multisig_signatures(index, witness_script, input)?);
+    witness.push(witness_script.to_bytes());
+    Ok(witness)
+}
+
+/// Returns the signatures satisfying the bare multisig `script`, in key order.
+fn multisig_signatures(index: usize, script: &Script, input: &Input) -> Result<Vec<Vec<u8>>, Error> {
+    let (threshold, keys) = parse_multisig(script).ok_or(Error::UnsupportedScript(index))?;
+    let sigs: Vec<Vec<u8>> = keys.iter()
+        .filter_map(|pk| input.partial_sigs.get(pk))
+        .take(threshold)
+        .map(|sig| sig.to_vec())
+        .collect();
+    if sigs.len() < threshold {
+        return Err(Error::NotEnoughSignatures(index));
+    }
+    Ok(sigs)
+}
+
+/// Parses a bare `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` script.
+fn parse_multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    fn push_num(instruction: Option<Result<Instruction, ::blockdata::script::Error>>) -> Option<usize> {
+        match instruction {
+            Some(Ok(Instruction::Op(op))) => match op.classify(ClassifyContext::Legacy) {
+                Class::PushNum(n) if n > 0 => Some(n as usize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    let mut instructions = script.instructions();
+    let threshold = push_num(instructions.next())?;
+    let mut keys = vec![];
+    let mut next = instructions.next();
+    while let Some(Ok(Instruction::PushBytes(bytes))) = next {
+        keys.push(PublicKey::from_slice(bytes).ok()?);
+        next = instructions.next();
+    }
+    let n = push_num(next)?;
+    match instructions.next() {
+        Some(Ok(Instruction::Op(opcodes::all::OP_CHECKMULTISIG))) => {}
+        _ => return None,
+    }
+    if instructions.next().is_some() || n != keys.len() || threshold > n {
+        return None;
+    }
+    Some((threshold, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hashes::Hash;
+    use hash_types::Txid;
+    use secp256k1::{Message, Secp256k1};
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+    use util::key::PrivateKey;
+    use util::schnorr::TweakedPublicKey;
+    use network::constants::Network;
+    use {EcdsaSig, EcdsaSighashType, KeyPair, SchnorrSig, SchnorrSighashType, XOnlyPublicKey};
+
+    fn multisig(keys: &[PublicKey]) -> Script {
+        let mut builder = Builder::new().push_int(2);
+        for key in keys {
+            builder = builder.push_key(key);
+        }
+        builder.push_int(keys.len() as i64).push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script()
+    }
+
+    #[test]
+    fn finalize_standard_inputs() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let keys: Vec<PrivateKey> = (1..4u8).map(|n| PrivateKey::from_slice(&[n; 32], Network::Bitcoin).unwrap()).collect();
+        let pks: Vec<PublicKey> = keys.iter().map(|k| k.public_key(&secp)).collect();
+        let sigs: Vec<EcdsaSig> = keys.iter()
+            .map(|k| EcdsaSig { sig: secp.sign_ecdsa(&msg, &k.inner), hash_ty: EcdsaSighashType::All })
+            .collect();
+
+        let p2wpkh = Script::new_v0_p2wpkh(&pks[0].wpubkey_hash().unwrap());
+        let ms = multisig(&pks);
+        let output_key = XOnlyPublicKey::from(pks[0].inner);
+        let script_pubkeys = vec![
+            Script::new_p2pkh(&pks[0].pubkey_hash()),
+            p2wpkh.clone(),
+            Script::new_p2sh(&p2wpkh.script_hash()),
+            Script::new_p2sh(&ms.script_hash()),
+            Script::new_v0_p2wsh(&ms.wscript_hash()),
+            Script::new_v1_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(output_key)),
+        ];
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: (0..script_pubkeys.len() as u32).map(|vout| TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), vout),
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: Witness::default(),
+            }).collect(),
+            output: vec![],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        for (input, spk) in psbt.inputs.iter_mut().zip(script_pubkeys) {
+            input.witness_utxo = Some(TxOut { value: 1000, script_pubkey: spk });
+            input.partial_sigs.insert(pks[0], sigs[0]);
+        }
+        psbt.inputs[2].redeem_script = Some(p2wpkh.clone());
+        psbt.inputs[3].redeem_script = Some(ms.clone());
+        psbt.inputs[4].witness_script = Some(ms.clone());
+        psbt.inputs[5].partial_sigs.clear();
+        psbt.inputs[5].tap_key_sig = Some(SchnorrSig {
+            sig: secp.sign_schnorr_no_aux_rand(&msg, &KeyPair::from_secret_key(&secp, keys[0].inner)),
+            hash_ty: SchnorrSighashType::Default,
+        });
+
+        // multisig inputs lack the second signature
+        assert_eq!(psbt.finalize(), Err(Error::NotEnoughSignatures(3)));
+        psbt.inputs[3].partial_sigs.insert(pks[2], sigs[2]);
+        psbt.inputs[4].partial_sigs.insert(pks[1], sigs[1]);
+        psbt.inputs[4].partial_sigs.insert(pks[2], sigs[2]);
+        psbt.finalize().unwrap();
+
+        for input in &psbt.inputs {
+            assert!(input.partial_sigs.is_empty() && input.redeem_script.is_none() && input.witness_script.is_none());
+            assert!(input.witness_utxo.is_some());
+        }
+        let witness = |i: usize| psbt.inputs[i].final_script_witness.clone().map(|w| w.to_vec());
+        let sig_bytes = |i: usize| sigs[i].to_vec();
+
+        let p2pkh_sig = Builder::new().push_slice(&sig_bytes(0)).push_key(&pks[0]).into_script();
+        assert_eq!(psbt.inputs[0].final_script_sig, Some(p2pkh_sig));
+        assert_eq!(witness(0), None);
+
+        assert_eq!(psbt.inputs[1].final_script_sig, None);
+        assert_eq!(witness(1), Some(vec![sig_bytes(0), pks[0].to_bytes()]));
+
+        let nested_sig = Builder::new().push_slice(p2wpkh.as_bytes()).into_script();
+        assert_eq!(psbt.inputs[2].final_script_sig, Some(nested_sig));
+        assert_eq!(witness(2), Some(vec![sig_bytes(0), pks[0].to_bytes()]));
+
+        let p2sh_sig = Builder::new()
+            .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+            .push_slice(&sig_bytes(0))
+            .push_slice(&sig_bytes(2))
+            .push_slice(ms.as_bytes())
+            .into_script();
+        assert_eq!(psbt.inputs[3].final_script_sig, Some(p2sh_sig));
+
+        // only the first two signatures in key order are used
+        assert_eq!(witness(4), Some(vec![vec![], sig_bytes(0), sig_bytes(1), ms.to_bytes()]));
+
+        assert_eq!(witness(5).unwrap().len(), 1);
+        assert_eq!(witness(5).unwrap()[0].len(), 64);
+    }
+
+    #[test]
+    fn finalize_errors() {
+        let secp = Secp256k1::new();
+        let pk = PrivateKey::from_slice(&[1; 32], Network::Bitcoin).unwrap().public_key(&secp);
+        let ms = multisig(&[pk, pk]);
+        assert_eq!(parse_multisig(&ms), Some((2, vec![pk, pk])));
+        assert_eq!(parse_multisig(&Script::new_p2pkh(&pk.pubkey_hash())), None);
+
+        let input = Input { redeem_script: Some(ms.clone()), ..Default::default() };
+        let wrong_spk = Script::new_p2sh(&Script::new().script_hash());
+        assert_eq!(final_scripts(0, &wrong_spk, &input), Err(Error::MissingScript(0)));
+        assert_eq!(final_scripts(0, &Script::new_p2sh(&ms.script_hash()), &input), Err(Error::NotEnoughSignatures(0)));
+        assert_eq!(final_scripts(0, &Script::new_op_return(&[]), &input), Err(Error::UnsupportedScript(0)));
+    }
+}
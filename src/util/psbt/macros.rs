@@ -18,9 +18,13 @@ macro_rules! hex_psbt {
 }
 
 macro_rules! combine {
-    ($thing:ident, $slf:ident, $other:ident) => {
-        if let (&None, Some($thing)) = (&$slf.$thing, $other.$thing) {
-            $slf.$thing = Some($thing);
+    ($thing:ident, $slf:ident, $other:ident, $type_value:expr) => {
+        match (&$slf.$thing, $other.$thing) {
+            (&None, Some($thing)) => $slf.$thing = Some($thing),
+            (&Some(ref a), Some(ref b)) if a != b => {
+                return Err(Error::CombineConflict(raw::Key { type_value: $type_value, key: vec![] }));
+            }
+            _ => {}
         }
     };
 }
@@ -92,6 +96,50 @@ macro_rules! impl_psbtmap_consensus_enc_dec_oding {
     };
 }
 
+macro_rules! impl_psbt_proprietary_access {
+    ($thing:ty) => {
+        impl $thing {
+            /// Returns the value of the proprietary key made of `prefix`,
+            /// `subtype` and `key`.
+            pub fn get_proprietary(&self, prefix: &[u8], subtype: u8, key: &[u8]) -> Option<&[u8]> {
+                let key = $crate::util::psbt::raw::ProprietaryKey { prefix: prefix.to_vec(), subtype, key: key.to_vec() };
+                self.proprietary.get(&key).map(|value| &value[..])
+            }
+
+            /// Sets the value of the proprietary key made of `prefix`,
+            /// `subtype` and `key`, returning the previous value if any.
+            pub fn insert_proprietary(&mut self, prefix: &[u8], subtype: u8, key: &[u8], value: Vec<u8>) -> Option<Vec<u8>> {
+                let key = $crate::util::psbt::raw::ProprietaryKey { prefix: prefix.to_vec(), subtype, key: key.to_vec() };
+                self.proprietary.insert(key, value)
+            }
+
+            /// Removes the proprietary key made of `prefix`, `subtype` and
+            /// `key`, returning its value if any.
+            pub fn remove_proprietary(&mut self, prefix: &[u8], subtype: u8, key: &[u8]) -> Option<Vec<u8>> {
+                let key = $crate::util::psbt::raw::ProprietaryKey { prefix: prefix.to_vec(), subtype, key: key.to_vec() };
+                self.proprietary.remove(&key)
+            }
+
+            /// Iterates over the proprietary key-value pairs in the `prefix`
+            /// namespace, ordered by subtype and key data.
+            pub fn iter_proprietary<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = (&'a $crate::util::psbt::raw::ProprietaryKey, &'a [u8])> + 'a {
+                self.proprietary.iter()
+                    .filter(move |kv| kv.0.prefix == prefix)
+                    .map(|kv| (kv.0, &kv.1[..]))
+            }
+
+            /// Removes all proprietary key-value pairs in the `prefix`
+            /// namespace and returns them.
+            pub fn remove_proprietary_prefix(&mut self, prefix: &[u8]) -> Vec<($crate::util::psbt::raw::ProprietaryKey, Vec<u8>)> {
+                let keys: Vec<_> = self.proprietary.keys().filter(|key| key.prefix == prefix).cloned().collect();
+                keys.into_iter()
+                    .filter_map(|key| self.proprietary.remove(&key).map(|value| (key, value)))
+                    .collect()
+            }
+        }
+    };
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 macro_rules! impl_psbt_insert_pair {
     ($slf:ident.$unkeyed_name:ident <= <$raw_key:ident: _>|<$raw_value:ident: $unkeyed_value_type:ty>) => {
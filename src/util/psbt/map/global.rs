@@ -99,6 +99,8 @@ impl Map for PartiallySignedTransaction {
     }
 }
 
+impl_psbt_proprietary_access!(PartiallySignedTransaction);
+
 impl PartiallySignedTransaction {
     pub(crate) fn consensus_decode_global<D: io::Read>(d: D) -> Result<Self, encode::Error> {
         let mut d = d.take(MAX_VEC_SIZE as u64);
@@ -26,7 +26,7 @@ use hashes::{self, hash160, ripemd160, sha256, sha256d};
 use secp256k1::XOnlyPublicKey;
 use util::bip32::KeySource;
 use util::psbt;
-use util::psbt::map::Map;
+use util::psbt::map::{combine_map, typed_key, Map};
 use util::psbt::raw;
 use util::psbt::serialize::Deserialize;
 use util::psbt::{Error, error};
@@ -35,6 +35,9 @@ use util::key::PublicKey;
 use util::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapBranchHash};
 use util::sighash;
 use {EcdsaSighashType, SchnorrSighashType, EcdsaSig, SchnorrSig};
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+#[cfg(feature = "arbitrary")] use util::key::{arbitrary_secp_public_key, arbitrary_x_only_public_key};
+#[cfg(feature = "arbitrary")] use util::psbt::map::{arbitrary_map, arbitrary_preimages};
 
 /// Type: Non-Witness UTXO PSBT_IN_NON_WITNESS_UTXO = 0x00
 const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
@@ -157,6 +160,8 @@ pub struct PsbtSighashType {
     pub (in ::util::psbt) inner: u32,
 }
 
+arbitrary_struct_impl!(PsbtSighashType, inner);
+
 impl fmt::Display for PsbtSighashType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.schnorr_hash_ty() {
@@ -377,33 +382,79 @@ impl Input {
     }
 
     /// Combines this [`Input`] with `other` `Input` (as described by BIP 174).
-    pub fn combine(&mut self, other: Self) {
-        combine!(non_witness_utxo, self, other);
-
-        if let (&None, Some(witness_utxo)) = (&self.witness_utxo, other.witness_utxo) {
-            self.witness_utxo = Some(witness_utxo);
-            self.non_witness_utxo = None; // Clear out any non-witness UTXO when we set a witness one
+    ///
+    /// Fails with [`Error::CombineConflict`] if both inputs hold different
+    /// values for the same key, e.g. two different partial signatures for the
+    /// same public key.
+    pub fn combine(&mut self, other: Self) -> Result<(), Error> {
+        combine!(non_witness_utxo, self, other, PSBT_IN_NON_WITNESS_UTXO);
+
+        if let Some(witness_utxo) = other.witness_utxo {
+            match self.witness_utxo {
+                None => {
+                    self.witness_utxo = Some(witness_utxo);
+                    self.non_witness_utxo = None; // Clear out any non-witness UTXO when we set a witness one
+                }
+                Some(ref utxo) if *utxo != witness_utxo => {
+                    return Err(Error::CombineConflict(raw::Key { type_value: PSBT_IN_WITNESS_UTXO, key: vec![] }));
+                }
+                Some(_) => {}
+            }
         }
 
-        self.partial_sigs.extend(other.partial_sigs);
-        self.bip32_derivation.extend(other.bip32_derivation);
-        self.ripemd160_preimages.extend(other.ripemd160_preimages);
-        self.sha256_preimages.extend(other.sha256_preimages);
-        self.hash160_preimages.extend(other.hash160_preimages);
-        self.hash256_preimages.extend(other.hash256_preimages);
-        self.tap_script_sigs.extend(other.tap_script_sigs);
-        self.tap_scripts.extend(other.tap_scripts);
-        self.tap_key_origins.extend(other.tap_key_origins);
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
-
-        combine!(redeem_script, self, other);
-        combine!(witness_script, self, other);
-        combine!(final_script_sig, self, other);
-        combine!(final_script_witness, self, other);
-        combine!(tap_key_sig, self, other);
-        combine!(tap_internal_key, self, other);
-        combine!(tap_merkle_root, self, other);
+        combine_map(&mut self.partial_sigs, other.partial_sigs, typed_key(PSBT_IN_PARTIAL_SIG))?;
+        combine_map(&mut self.bip32_derivation, other.bip32_derivation, typed_key(PSBT_IN_BIP32_DERIVATION))?;
+        combine_map(&mut self.ripemd160_preimages, other.ripemd160_preimages, typed_key(PSBT_IN_RIPEMD160))?;
+        combine_map(&mut self.sha256_preimages, other.sha256_preimages, typed_key(PSBT_IN_SHA256))?;
+        combine_map(&mut self.hash160_preimages, other.hash160_preimages, typed_key(PSBT_IN_HASH160))?;
+        combine_map(&mut self.hash256_preimages, other.hash256_preimages, typed_key(PSBT_IN_HASH256))?;
+        combine_map(&mut self.tap_script_sigs, other.tap_script_sigs, typed_key(PSBT_IN_TAP_SCRIPT_SIG))?;
+        combine_map(&mut self.tap_scripts, other.tap_scripts, typed_key(PSBT_IN_TAP_LEAF_SCRIPT))?;
+        combine_map(&mut self.tap_key_origins, other.tap_key_origins, typed_key(PSBT_IN_TAP_BIP32_DERIVATION))?;
+        combine_map(&mut self.proprietary, other.proprietary, raw::ProprietaryKey::to_key)?;
+        combine_map(&mut self.unknown, other.unknown, raw::Key::clone)?;
+
+        combine!(sighash_type, self, other, PSBT_IN_SIGHASH_TYPE);
+        combine!(redeem_script, self, other, PSBT_IN_REDEEM_SCRIPT);
+        combine!(witness_script, self, other, PSBT_IN_WITNESS_SCRIPT);
+        combine!(final_script_sig, self, other, PSBT_IN_FINAL_SCRIPTSIG);
+        combine!(final_script_witness, self, other, PSBT_IN_FINAL_SCRIPTWITNESS);
+        combine!(tap_key_sig, self, other, PSBT_IN_TAP_KEY_SIG);
+        combine!(tap_internal_key, self, other, PSBT_IN_TAP_INTERNAL_KEY);
+        combine!(tap_merkle_root, self, other, PSBT_IN_TAP_MERKLE_ROOT);
+        Ok(())
+    }
+}
+
+// Signatures and scripts for taproot script paths are left out, as their control blocks are
+// only meaningful for actual taproot trees.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Input {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Input {
+            non_witness_utxo: u.arbitrary()?,
+            witness_utxo: u.arbitrary()?,
+            partial_sigs: u.arbitrary()?,
+            sighash_type: u.arbitrary()?,
+            redeem_script: u.arbitrary()?,
+            witness_script: u.arbitrary()?,
+            bip32_derivation: arbitrary_map(u, arbitrary_secp_public_key)?,
+            final_script_sig: u.arbitrary()?,
+            final_script_witness: u.arbitrary()?,
+            ripemd160_preimages: arbitrary_preimages(u)?,
+            sha256_preimages: arbitrary_preimages(u)?,
+            hash160_preimages: arbitrary_preimages(u)?,
+            hash256_preimages: arbitrary_preimages(u)?,
+            tap_key_sig: u.arbitrary()?,
+            tap_script_sigs: BTreeMap::new(),
+            tap_scripts: BTreeMap::new(),
+            tap_key_origins: arbitrary_map(u, arbitrary_x_only_public_key)?,
+            tap_internal_key: if u.arbitrary()? { Some(arbitrary_x_only_public_key(u)?) } else { None },
+            tap_merkle_root: u.arbitrary()?,
+            proprietary: u.arbitrary()?,
+            unknown: raw::arbitrary_unknown(u)?,
+        })
     }
 }
 
@@ -505,6 +556,7 @@ impl Map for Input {
 }
 
 impl_psbtmap_consensus_enc_dec_oding!(Input);
+impl_psbt_proprietary_access!(Input);
 
 fn psbt_insert_hash_pair<H>(
     map: &mut BTreeMap<H, Vec<u8>>,
@@ -18,6 +18,10 @@ use io;
 
 use consensus::encode;
 use util::psbt::raw;
+use util::psbt::serialize::Serialize;
+use util::psbt::Error;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+#[cfg(feature = "arbitrary")] use hashes::Hash;
 
 mod global;
 mod input;
@@ -41,3 +45,61 @@ pub(super) trait Map {
         Ok(len + encode::Encodable::consensus_encode(&0x00_u8, s)?)
     }
 }
+
+/// Merges the key-value pairs of `other` into `map`.
+///
+/// Fails with [`Error::CombineConflict`] if both maps hold different values
+/// for the same key, reporting the raw key returned by `to_key`.
+/// Generates a map whose keys are generated by `key`, for key types which don't implement
+/// `Arbitrary`.
+#[cfg(feature = "arbitrary")]
+fn arbitrary_map<'a, K, V, F>(u: &mut Unstructured<'a>, key: F) -> arbitrary::Result<BTreeMap<K, V>>
+where
+    K: Ord,
+    V: Arbitrary<'a>,
+    F: Fn(&mut Unstructured<'a>) -> arbitrary::Result<K>,
+{
+    let mut map = BTreeMap::new();
+    while u.arbitrary()? {
+        let k = key(u)?;
+        map.insert(k, u.arbitrary()?);
+    }
+    Ok(map)
+}
+
+/// Generates preimages along with their hashes.
+#[cfg(feature = "arbitrary")]
+fn arbitrary_preimages<H: Hash + Ord>(u: &mut Unstructured) -> arbitrary::Result<BTreeMap<H, Vec<u8>>> {
+    let mut map = BTreeMap::new();
+    while u.arbitrary()? {
+        let preimage: Vec<u8> = u.arbitrary()?;
+        map.insert(<H as Hash>::hash(&preimage), preimage);
+    }
+    Ok(map)
+}
+
+pub(super) fn combine_map<K, V, F>(map: &mut BTreeMap<K, V>, other: BTreeMap<K, V>, to_key: F) -> Result<(), Error>
+where
+    K: Ord,
+    V: PartialEq,
+    F: Fn(&K) -> raw::Key,
+{
+    for (key, value) in other {
+        match map.entry(key) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            btree_map::Entry::Occupied(entry) => {
+                if *entry.get() != value {
+                    return Err(Error::CombineConflict(to_key(entry.key())));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns a function building the raw key of type `type_value` for a typed key.
+pub(super) fn typed_key<K: Serialize>(type_value: u8) -> impl Fn(&K) -> raw::Key {
+    move |key| raw::Key { type_value, key: key.serialize() }
+}
@@ -22,9 +22,12 @@ use consensus::encode;
 use secp256k1::XOnlyPublicKey;
 use util::bip32::KeySource;
 use secp256k1;
-use util::psbt::map::Map;
+use util::psbt::map::{combine_map, typed_key, Map};
 use util::psbt::raw;
 use util::psbt::Error;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+#[cfg(feature = "arbitrary")] use util::key::{arbitrary_secp_public_key, arbitrary_x_only_public_key};
+#[cfg(feature = "arbitrary")] use util::psbt::map::arbitrary_map;
 
 use util::taproot::{ScriptLeaf, TapLeafHash};
 
@@ -257,16 +260,38 @@ impl Output {
     }
 
     /// Combines this [`Output`] with `other` `Output` (as described by BIP 174).
-    pub fn combine(&mut self, other: Self) {
-        self.bip32_derivation.extend(other.bip32_derivation);
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
-        self.tap_key_origins.extend(other.tap_key_origins);
-
-        combine!(redeem_script, self, other);
-        combine!(witness_script, self, other);
-        combine!(tap_internal_key, self, other);
-        combine!(tap_tree, self, other);
+    ///
+    /// Fails with [`Error::CombineConflict`] if both outputs hold different
+    /// values for the same key.
+    pub fn combine(&mut self, other: Self) -> Result<(), Error> {
+        combine_map(&mut self.bip32_derivation, other.bip32_derivation, typed_key(PSBT_OUT_BIP32_DERIVATION))?;
+        combine_map(&mut self.proprietary, other.proprietary, raw::ProprietaryKey::to_key)?;
+        combine_map(&mut self.unknown, other.unknown, raw::Key::clone)?;
+        combine_map(&mut self.tap_key_origins, other.tap_key_origins, typed_key(PSBT_OUT_TAP_BIP32_DERIVATION))?;
+
+        combine!(redeem_script, self, other, PSBT_OUT_REDEEM_SCRIPT);
+        combine!(witness_script, self, other, PSBT_OUT_WITNESS_SCRIPT);
+        combine!(tap_internal_key, self, other, PSBT_OUT_TAP_INTERNAL_KEY);
+        combine!(tap_tree, self, other, PSBT_OUT_TAP_TREE);
+        Ok(())
+    }
+}
+
+// The taproot tree is left out, as it has to be built from valid leaves.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for Output {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Output {
+            redeem_script: u.arbitrary()?,
+            witness_script: u.arbitrary()?,
+            bip32_derivation: arbitrary_map(u, arbitrary_secp_public_key)?,
+            tap_internal_key: if u.arbitrary()? { Some(arbitrary_x_only_public_key(u)?) } else { None },
+            tap_tree: None,
+            tap_key_origins: arbitrary_map(u, arbitrary_x_only_public_key)?,
+            proprietary: u.arbitrary()?,
+            unknown: raw::arbitrary_unknown(u)?,
+        })
     }
 }
 
@@ -317,3 +342,4 @@ impl Map for Output {
 }
 
 impl_psbtmap_consensus_enc_dec_oding!(Output);
+impl_psbt_proprietary_access!(Output);
@@ -22,9 +22,12 @@
 use core::cmp;
 
 use blockdata::script::Script;
-use blockdata::transaction::Transaction;
+use blockdata::transaction::{self, bip69_input_order, bip69_output_order, InputWeightPrediction, Transaction, TxOut};
 use consensus::{encode, Encodable, Decodable};
 use consensus::encode::MAX_VEC_SIZE;
+use util::amount::Amount;
+use util::sighash::SchnorrSighashType;
+use util::weight::Weight;
 
 use prelude::*;
 
@@ -44,7 +47,13 @@ mod map;
 pub use self::map::{Input, Output, TapTree, PsbtSighashType, IncompleteTapTree};
 use self::map::Map;
 
+mod finalizer;
+
+pub mod payjoin;
+
 use util::bip32::{ExtendedPubKey, KeySource};
+#[cfg(feature = "arbitrary")] use blockdata::transaction::TxIn;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// A Partially Signed Transaction.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -120,9 +129,125 @@ impl PartiallySignedTransaction {
         tx
     }
 
+    /// Returns the output spent by the input at `index`, taken from the
+    /// witness UTXO if present and from the non-witness UTXO otherwise.
+    pub fn spent_utxo(&self, index: usize) -> Result<&TxOut, Error> {
+        let (txin, input) = match (self.unsigned_tx.input.get(index), self.inputs.get(index)) {
+            (Some(txin), Some(input)) => (txin, input),
+            _ => return Err(Error::MissingUtxo(index)),
+        };
+        if let Some(ref utxo) = input.witness_utxo {
+            return Ok(utxo);
+        }
+        match input.non_witness_utxo {
+            Some(ref tx) if tx.txid() == txin.previous_output.txid => {
+                tx.output.get(txin.previous_output.vout as usize).ok_or(Error::MismatchedUtxo(index))
+            }
+            Some(_) => Err(Error::MismatchedUtxo(index)),
+            None => Err(Error::MissingUtxo(index)),
+        }
+    }
+
+    /// Calculates the fee paid by the transaction.
+    ///
+    /// Requires the witness or non-witness UTXO of every input.
+    pub fn fee(&self) -> Result<Amount, Error> {
+        let mut input_value = 0u64;
+        for index in 0..self.unsigned_tx.input.len() {
+            let value = self.spent_utxo(index)?.value;
+            input_value = input_value.checked_add(value).ok_or(Error::ValueOverflow)?;
+        }
+        let mut output_value = 0u64;
+        for txout in &self.unsigned_tx.output {
+            output_value = output_value.checked_add(txout.value).ok_or(Error::ValueOverflow)?;
+        }
+        input_value.checked_sub(output_value).map(Amount::from_sat).ok_or(Error::NegativeFee)
+    }
+
+    /// Predicts the weight of the transaction once all inputs are finalized.
+    ///
+    /// Finalized inputs weigh what they do; the others are predicted from the
+    /// output they spend, which must be P2PKH, P2WPKH, P2WPKH nested in P2SH
+    /// or P2TR spent along the key path. The prediction assumes compressed
+    /// keys and is an upper bound for ECDSA signatures.
+    pub fn predict_weight(&self) -> Result<Weight, Error> {
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for (index, input) in self.inputs.iter().enumerate() {
+            if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+                let script_sig_len = input.final_script_sig.as_ref().map_or(0, |script| script.len());
+                let witness = input.final_script_witness.clone().unwrap_or_default();
+                inputs.push(InputWeightPrediction::new(script_sig_len, witness.iter().map(|element| element.len())));
+                continue;
+            }
+            let spk = &self.spent_utxo(index)?.script_pubkey;
+            let prediction = if spk.is_p2pkh() {
+                InputWeightPrediction::P2PKH_COMPRESSED_MAX
+            } else if spk.is_v0_p2wpkh() {
+                InputWeightPrediction::P2WPKH_MAX
+            } else if spk.is_p2sh() && input.redeem_script.as_ref().map_or(false, |script| script.is_v0_p2wpkh()) {
+                InputWeightPrediction::NESTED_P2WPKH_MAX
+            } else if spk.is_v1_p2tr() && input.tap_script_sigs.is_empty() {
+                match input.tap_key_sig {
+                    Some(sig) if sig.hash_ty != SchnorrSighashType::Default => InputWeightPrediction::P2TR_KEY_NON_DEFAULT_SIGHASH,
+                    _ => InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH,
+                }
+            } else {
+                return Err(Error::UnknownInputWeight(index));
+            };
+            inputs.push(prediction);
+        }
+        let output_script_lens = self.unsigned_tx.output.iter().map(|output| output.script_pubkey.len());
+        Ok(transaction::predict_weight(inputs, output_script_lens))
+    }
+
+    /// Calculates the fee rate in satoshis per virtual byte.
+    ///
+    /// The size is the one predicted by [`Self::predict_weight`], so the fee
+    /// rate accounts for the signatures and witnesses still missing.
+    pub fn fee_rate(&self) -> Result<f64, Error> {
+        let fee = self.fee()?;
+        Ok(fee.as_sat() as f64 / self.predict_weight()?.to_vbytes_ceil() as f64)
+    }
+
+    /// Performs the sanity checks done before signing.
+    ///
+    /// Checks that every input carries UTXO data consistent with its previous
+    /// output, that inputs spending non-witness outputs carry the full
+    /// previous transaction, that no input mixes data of legacy, segwit v0
+    /// and taproot spends and that the fee does not exceed `max_fee`.
+    pub fn sanity_check(&self, max_fee: Amount) -> Result<(), Error> {
+        for (index, input) in self.inputs.iter().enumerate() {
+            let utxo = self.spent_utxo(index)?;
+            if is_mixed_spend(&utxo.script_pubkey, input) {
+                return Err(Error::MixedSpends(index));
+            }
+            if let Some(ref tx) = input.non_witness_utxo {
+                let prevout = self.unsigned_tx.input[index].previous_output;
+                if tx.txid() != prevout.txid || tx.output.get(prevout.vout as usize) != Some(utxo) {
+                    return Err(Error::MismatchedUtxo(index));
+                }
+            } else {
+                let spk = &utxo.script_pubkey;
+                let is_witness = spk.is_witness_program() || (spk.is_p2sh() && input.redeem_script.as_ref().map_or(true, |s| s.is_witness_program()));
+                if !is_witness {
+                    return Err(Error::MissingNonWitnessUtxo(index));
+                }
+            }
+        }
+        let fee = self.fee()?;
+        if fee > max_fee {
+            return Err(Error::AbsurdFee(fee));
+        }
+        Ok(())
+    }
+
     /// Combines this [`PartiallySignedTransaction`] with `other` PSBT as described by BIP 174.
     ///
     /// In accordance with BIP 174 this function is commutative i.e., `A.combine(B) == B.combine(A)`
+    ///
+    /// Conflicting values for the same key are reported as
+    /// [`Error::CombineConflict`] rather than picked arbitrarily, in which case
+    /// `self` is left unchanged.
     pub fn combine(&mut self, other: Self) -> Result<(), Error> {
         if self.unsigned_tx != other.unsigned_tx {
             return Err(Error::UnexpectedUnsignedTx {
@@ -133,13 +258,15 @@ impl PartiallySignedTransaction {
 
         // BIP 174: The Combiner must remove any duplicate key-value pairs, in accordance with
         //          the specification. It can pick arbitrarily when conflicts occur.
+        // We report conflicts instead, working on a copy to leave `self` untouched on error.
+        let mut combined = self.clone();
 
         // Keeping the highest version
-        self.version = cmp::max(self.version, other.version);
+        combined.version = cmp::max(combined.version, other.version);
 
         // Merging xpubs
         for (xpub, (fingerprint1, derivation1)) in other.xpub {
-            match self.xpub.entry(xpub) {
+            match combined.xpub.entry(xpub) {
                 btree_map::Entry::Vacant(entry) => {
                     entry.insert((fingerprint1, derivation1));
                 },
@@ -170,19 +297,62 @@ impl PartiallySignedTransaction {
             }
         }
 
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
+        map::combine_map(&mut combined.proprietary, other.proprietary, raw::ProprietaryKey::to_key)?;
+        map::combine_map(&mut combined.unknown, other.unknown, raw::Key::clone)?;
 
-        for (self_input, other_input) in self.inputs.iter_mut().zip(other.inputs.into_iter()) {
-            self_input.combine(other_input);
+        for (self_input, other_input) in combined.inputs.iter_mut().zip(other.inputs) {
+            self_input.combine(other_input)?;
         }
 
-        for (self_output, other_output) in self.outputs.iter_mut().zip(other.outputs.into_iter()) {
-            self_output.combine(other_output);
+        for (self_output, other_output) in combined.outputs.iter_mut().zip(other.outputs) {
+            self_output.combine(other_output)?;
         }
 
+        *self = combined;
         Ok(())
     }
+
+    /// Sorts the inputs and outputs of the unsigned transaction as specified by BIP69, see
+    /// [`Transaction::sort_bip69`], moving the input and output maps along with them.
+    ///
+    /// Maps which don't match the transaction in number are left unchanged.
+    pub fn sort_bip69(&mut self) {
+        let tx = &mut self.unsigned_tx;
+        let mut input_order: Vec<usize> = (0..tx.input.len()).collect();
+        input_order.sort_by(|&a, &b| bip69_input_order(&tx.input[a], &tx.input[b]));
+        let mut output_order: Vec<usize> = (0..tx.output.len()).collect();
+        output_order.sort_by(|&a, &b| bip69_output_order(&tx.output[a], &tx.output[b]));
+
+        reorder(&mut tx.input, &input_order);
+        reorder(&mut tx.output, &output_order);
+        if self.inputs.len() == input_order.len() {
+            reorder(&mut self.inputs, &input_order);
+        }
+        if self.outputs.len() == output_order.len() {
+            reorder(&mut self.outputs, &output_order);
+        }
+    }
+}
+
+/// Reorders `items` so that the `i`-th item is the `order[i]`-th one before.
+fn reorder<T>(items: &mut Vec<T>, order: &[usize]) {
+    let mut old: Vec<Option<T>> = items.drain(..).map(Some).collect();
+    items.extend(order.iter().map(|&i| old[i].take().expect("order is a permutation")));
+}
+
+/// Returns whether `input` carries data of another kind of spend than the one of the output
+/// with `spk`: ECDSA data for a taproot output, taproot data for another output, or a witness
+/// for a legacy output.
+fn is_mixed_spend(spk: &Script, input: &Input) -> bool {
+    if spk.is_v1_p2tr() {
+        return !input.partial_sigs.is_empty() || input.redeem_script.is_some() || input.witness_script.is_some();
+    }
+    let has_taproot_data = input.tap_key_sig.is_some() || !input.tap_script_sigs.is_empty()
+        || !input.tap_scripts.is_empty() || input.tap_internal_key.is_some() || input.tap_merkle_root.is_some();
+    let is_legacy = !spk.is_witness_program()
+        && (!spk.is_p2sh() || input.redeem_script.as_ref().map_or(false, |script| !script.is_witness_program()));
+    let has_witness = input.final_script_witness.as_ref().map_or(false, |witness| !witness.is_empty());
+    has_taproot_data || (is_legacy && has_witness)
 }
 
 #[cfg(feature = "base64")]
@@ -192,6 +362,11 @@ mod display_from_str {
     use core::str::FromStr;
     use consensus::encode::{Error, self};
     use ::base64::display::Base64Display;
+    use hashes::hex::FromHex;
+    use prelude::*;
+
+    /// The PSBT magic bytes `psbt` followed by the `0xff` separator, hex encoded.
+    const HEX_MAGIC: &[u8] = b"70736274ff";
 
     /// Error happening during PSBT decoding from Base64 string
     #[derive(Debug)]
@@ -200,7 +375,9 @@ mod display_from_str {
         /// Error in internal PSBT data structure
         PsbtEncoding(Error),
         /// Error in PSBT Base64 encoding
-        Base64Encoding(::base64::DecodeError)
+        Base64Encoding(::base64::DecodeError),
+        /// Error in PSBT hex encoding
+        HexEncoding(::hashes::hex::Error),
     }
 
     impl Display for PsbtParseError {
@@ -208,6 +385,7 @@ mod display_from_str {
             match self {
                 PsbtParseError::PsbtEncoding(err) => Display::fmt(err, f),
                 PsbtParseError::Base64Encoding(err) => Display::fmt(err, f),
+                PsbtParseError::HexEncoding(err) => Display::fmt(err, f),
             }
         }
     }
@@ -227,8 +405,15 @@ mod display_from_str {
     impl FromStr for PartiallySignedTransaction {
         type Err = PsbtParseError;
 
+        /// Parses a base64 encoded PSBT, falling back to hex if the string
+        /// starts with the hex encoded PSBT magic.
         fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let data = ::base64::decode(s).map_err(PsbtParseError::Base64Encoding)?;
+            let is_hex = s.len() >= HEX_MAGIC.len() && s.as_bytes()[..HEX_MAGIC.len()].eq_ignore_ascii_case(HEX_MAGIC);
+            let data = if is_hex {
+                Vec::<u8>::from_hex(s).map_err(PsbtParseError::HexEncoding)?
+            } else {
+                ::base64::decode(s).map_err(PsbtParseError::Base64Encoding)?
+            };
             Ok(encode::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)?)
         }
     }
@@ -237,6 +422,34 @@ mod display_from_str {
 #[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
 pub use self::display_from_str::PsbtParseError;
 
+// The unsigned transaction is generated with at least one input and without signatures, and
+// the version is 0, the only one supported.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for PartiallySignedTransaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut unsigned_tx = Transaction::arbitrary(u)?;
+        if unsigned_tx.input.is_empty() {
+            unsigned_tx.input.push(TxIn { previous_output: u.arbitrary()?, ..Default::default() });
+        }
+        for input in &mut unsigned_tx.input {
+            input.script_sig = Script::new();
+            input.witness.clear();
+        }
+        let inputs = (0..unsigned_tx.input.len()).map(|_| u.arbitrary()).collect::<Result<_, _>>()?;
+        let outputs = (0..unsigned_tx.output.len()).map(|_| u.arbitrary()).collect::<Result<_, _>>()?;
+        Ok(PartiallySignedTransaction {
+            unsigned_tx,
+            version: 0,
+            xpub: u.arbitrary()?,
+            proprietary: u.arbitrary()?,
+            unknown: raw::arbitrary_unknown(u)?,
+            inputs,
+            outputs,
+        })
+    }
+}
+
 impl Encodable for PartiallySignedTransaction {
     fn consensus_encode<W: io::Write + ?Sized>(&self, s: &mut W) -> Result<usize, io::Error> {
         let mut len = 0;
@@ -324,6 +537,8 @@ mod tests {
 
     use std::collections::BTreeMap;
     use blockdata::witness::Witness;
+    use core::str::FromStr;
+    use XOnlyPublicKey;
 
     #[test]
     fn trivial_psbt() {
@@ -561,6 +776,174 @@ mod tests {
         assert_eq!(psbt, decoded);
     }
 
+    #[test]
+    fn proprietary_access() {
+        let tx = Transaction { version: 2, lock_time: 0, input: vec![], output: vec![TxOut::default()] };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+
+        assert_eq!(psbt.insert_proprietary(b"vendor", 1, b"a", vec![1]), None);
+        assert_eq!(psbt.insert_proprietary(b"vendor", 1, b"a", vec![2]), Some(vec![1]));
+        psbt.insert_proprietary(b"vendor", 0, b"b", vec![3]);
+        psbt.insert_proprietary(b"other", 1, b"a", vec![4]);
+        psbt.outputs[0].insert_proprietary(b"vendor", 2, &[], vec![5]);
+
+        assert_eq!(psbt.get_proprietary(b"vendor", 1, b"a"), Some(&[2][..]));
+        assert_eq!(psbt.get_proprietary(b"vendor", 2, b"a"), None);
+        let vendor: Vec<_> = psbt.iter_proprietary(b"vendor").map(|(key, value)| (key.subtype, value)).collect();
+        assert_eq!(vendor, vec![(0, &[3][..]), (1, &[2][..])]);
+
+        let decoded: PartiallySignedTransaction = deserialize(&serialize(&psbt)).unwrap();
+        assert_eq!(decoded, psbt);
+        assert_eq!(decoded.outputs[0].get_proprietary(b"vendor", 2, &[]), Some(&[5][..]));
+
+        assert_eq!(psbt.remove_proprietary(b"other", 1, b"a"), Some(vec![4]));
+        assert_eq!(psbt.remove_proprietary(b"other", 1, b"a"), None);
+        assert_eq!(psbt.remove_proprietary_prefix(b"vendor").len(), 2);
+        assert!(psbt.proprietary.is_empty());
+        assert_eq!(psbt.outputs[0].iter_proprietary(b"vendor").count(), 1);
+    }
+
+    #[test]
+    fn fee_and_sanity_check() {
+        let txin = |previous_output| TxIn {
+            previous_output,
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: Witness::default(),
+        };
+        let prev_tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![txin(OutPoint::null())],
+            output: vec![
+                TxOut { value: 10_000, script_pubkey: hex_script!("76a914d0c59903c5bac2868760e90fd521a4665aa7652088ac") },
+                TxOut { value: 20_000, script_pubkey: hex_script!("0014d85c2b71d0060b09c9886aeb815e50991dda124d") },
+            ],
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(OutPoint::new(prev_tx.txid(), 0)), txin(OutPoint::new(prev_tx.txid(), 1))],
+            output: vec![TxOut { value: 29_000, script_pubkey: hex_script!("0014d85c2b71d0060b09c9886aeb815e50991dda124d") }],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        assert_eq!(psbt.fee(), Err(Error::MissingUtxo(0)));
+
+        psbt.inputs[0].non_witness_utxo = Some(prev_tx.clone());
+        psbt.inputs[1].witness_utxo = Some(prev_tx.output[1].clone());
+        assert_eq!(psbt.fee(), Ok(Amount::from_sat(1_000)));
+        // a P2PKH and a P2WPKH input yet to be signed
+        assert_eq!(psbt.predict_weight(), Ok(Weight::from_wu(1_031)));
+        assert_eq!(psbt.fee_rate(), Ok(1_000.0 / 258.0));
+        assert_eq!(psbt.sanity_check(Amount::from_sat(1_000)), Ok(()));
+        assert_eq!(psbt.sanity_check(Amount::from_sat(999)), Err(Error::AbsurdFee(Amount::from_sat(1_000))));
+
+        let mut bad = psbt.clone();
+        bad.unsigned_tx.output[0].value = 31_000;
+        assert_eq!(bad.fee(), Err(Error::NegativeFee));
+
+        let mut bad = psbt.clone();
+        bad.inputs[0].non_witness_utxo.as_mut().unwrap().lock_time = 1;
+        assert_eq!(bad.fee(), Err(Error::MismatchedUtxo(0)));
+
+        let mut bad = psbt.clone();
+        bad.inputs[0].non_witness_utxo = None;
+        bad.inputs[0].witness_utxo = Some(prev_tx.output[0].clone());
+        assert_eq!(bad.fee(), Ok(Amount::from_sat(1_000)));
+        assert_eq!(bad.sanity_check(Amount::from_sat(1_000)), Err(Error::MissingNonWitnessUtxo(0)));
+
+        let mut bad = psbt.clone();
+        bad.inputs[1].non_witness_utxo = Some(prev_tx.clone());
+        bad.inputs[1].witness_utxo.as_mut().unwrap().value = 30_000;
+        assert_eq!(bad.sanity_check(Amount::from_sat(1_000_000)), Err(Error::MismatchedUtxo(1)));
+
+        // finalized inputs weigh what they do
+        let mut finalized = psbt.clone();
+        finalized.inputs[0].final_script_sig = Some(Script::from(vec![0x01; 107]));
+        finalized.inputs[1].final_script_witness = Some(Witness::from_vec(vec![vec![0x01; 72], vec![0x02; 33]]));
+        assert_eq!(finalized.predict_weight(), Ok(finalized.clone().extract_tx().weight()));
+        assert_eq!(finalized.sanity_check(Amount::from_sat(1_000)), Ok(()));
+
+        let mut unknown = psbt.clone();
+        unknown.inputs[1].witness_utxo.as_mut().unwrap().script_pubkey =
+            hex_script!("0020d85c2b71d0060b09c9886aeb815e50991dda124dd85c2b71d0060b09c9886a");
+        assert_eq!(unknown.fee(), Ok(Amount::from_sat(1_000)));
+        assert_eq!(unknown.fee_rate(), Err(Error::UnknownInputWeight(1)));
+
+        // taproot data on a segwit v0 input, and a witness on a legacy input
+        let key = XOnlyPublicKey::from_str("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+        let mut mixed = psbt.clone();
+        mixed.inputs[1].tap_internal_key = Some(key);
+        assert_eq!(mixed.sanity_check(Amount::from_sat(1_000)), Err(Error::MixedSpends(1)));
+        let mut mixed = psbt.clone();
+        mixed.inputs[0].final_script_witness = Some(Witness::from_vec(vec![vec![0x01; 72]]));
+        assert_eq!(mixed.sanity_check(Amount::from_sat(1_000)), Err(Error::MixedSpends(0)));
+
+        // ECDSA data on a taproot input
+        let mut taproot = psbt.clone();
+        taproot.inputs[1].witness_utxo.as_mut().unwrap().script_pubkey =
+            hex_script!("5120d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        assert_eq!(taproot.sanity_check(Amount::from_sat(1_000)), Ok(()));
+        assert_eq!(taproot.predict_weight(), Ok(Weight::from_wu(1_031 - 108 + 66)));
+        taproot.inputs[1].witness_script = Some(Script::new());
+        assert_eq!(taproot.sanity_check(Amount::from_sat(1_000)), Err(Error::MixedSpends(1)));
+    }
+
+    #[test]
+    fn sort_bip69() {
+        let txid = Txid::from_hex("9fbed79a1e970343fcd39f4a2d830a6bde6de0754ed2da70f489d0303ed558ec").unwrap();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                TxIn { previous_output: OutPoint::new(txid, 1), ..Default::default() },
+                TxIn { previous_output: OutPoint::new(txid, 0), ..Default::default() },
+            ],
+            output: vec![
+                TxOut { value: 2_000, script_pubkey: Script::new() },
+                TxOut { value: 1_000, script_pubkey: Script::new() },
+            ],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: 1, script_pubkey: Script::new() });
+        psbt.outputs[0].redeem_script = Some(hex_script!("51"));
+
+        psbt.sort_bip69();
+        assert!(psbt.unsigned_tx.is_bip69_sorted());
+        assert_eq!(psbt.unsigned_tx.input[1].previous_output.vout, 1);
+        assert_eq!(psbt.inputs[0].witness_utxo, None);
+        assert_eq!(psbt.inputs[1].witness_utxo.as_ref().map(|utxo| utxo.value), Some(1));
+        assert_eq!(psbt.unsigned_tx.output[1].value, 2_000);
+        assert_eq!(psbt.outputs[0].redeem_script, None);
+        assert_eq!(psbt.outputs[1].redeem_script, Some(hex_script!("51")));
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn psbt_string_roundtrip() {
+        use super::PsbtParseError;
+
+        let tx: Transaction = deserialize(&Vec::from_hex("020000000258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd750000000000ffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d0100000000ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f00000000").unwrap()).unwrap();
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+
+        let base64 = psbt.to_string();
+        assert!(base64.starts_with("cHNidP8"));
+        assert_eq!(base64.parse::<PartiallySignedTransaction>().unwrap(), psbt);
+
+        let hex = serialize_hex(&psbt);
+        assert_eq!(hex.parse::<PartiallySignedTransaction>().unwrap(), psbt);
+        assert_eq!(hex.to_uppercase().parse::<PartiallySignedTransaction>().unwrap(), psbt);
+
+        match "70736274ffzz".parse::<PartiallySignedTransaction>() {
+            Err(PsbtParseError::HexEncoding(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match "not a psbt".parse::<PartiallySignedTransaction>() {
+            Err(PsbtParseError::Base64Encoding(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     mod bip_vectors {
         #[cfg(feature = "base64")]
         use std::str::FromStr;
@@ -608,7 +991,8 @@ mod tests {
                 // This weird thing is necessary since rustc 0.29 prints out I/O error in a different format than later versions
                 .map_err(|err| match err {
                     PsbtParseError::PsbtEncoding(err) => err,
-                    PsbtParseError::Base64Encoding(_) => panic!("PSBT Base64 decoding failed")
+                    PsbtParseError::Base64Encoding(_) => panic!("PSBT Base64 decoding failed"),
+                    PsbtParseError::HexEncoding(_) => panic!("PSBT hex decoding failed"),
                 })
                 .map_err(Error::from)
                 .unwrap();
@@ -1102,4 +1486,55 @@ mod tests {
 
         assert_eq!(psbt1, psbt2);
     }
+
+    #[test]
+    fn combine_psbts_conflict() {
+        let psbt = hex_psbt!(include_str!("../../../test_data/psbt2.hex")).unwrap();
+        let key = raw::Key { type_value: 0xaa, key: vec![1, 2, 3] };
+
+        let mut psbt1 = psbt.clone();
+        psbt1.inputs[0].unknown.insert(key.clone(), vec![1]);
+        let mut psbt2 = psbt.clone();
+        psbt2.inputs[0].unknown.insert(key.clone(), vec![2]);
+        let unchanged = psbt1.clone();
+        assert_eq!(psbt1.combine(psbt2), Err(Error::CombineConflict(key.clone())));
+        assert_eq!(psbt1, unchanged);
+
+        let mut psbt2 = psbt.clone();
+        psbt2.inputs[0].unknown.insert(key, vec![1]);
+        psbt1.combine(psbt2).expect("equal values do not conflict");
+        assert_eq!(psbt1, unchanged);
+
+        let mut psbt2 = psbt.clone();
+        psbt2.inputs[0].redeem_script = Some(Script::from(vec![0x51]));
+        psbt1.inputs[0].redeem_script = Some(Script::from(vec![0x52]));
+        assert_eq!(psbt1.combine(psbt2), Err(Error::CombineConflict(raw::Key { type_value: 0x04, key: vec![] })));
+
+        let mut psbt1 = psbt.clone();
+        let mut psbt2 = psbt.clone();
+        let proprietary = raw::ProprietaryKey { prefix: b"test".to_vec(), subtype: 0, key: vec![] };
+        psbt1.outputs[0].proprietary.insert(proprietary.clone(), vec![1]);
+        psbt2.outputs[0].proprietary.insert(proprietary.clone(), vec![2]);
+        assert_eq!(psbt1.combine(psbt2), Err(Error::CombineConflict(proprietary.to_key())));
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary")]
+    fn arbitrary_roundtrip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut generated = 0;
+        for seed in 0..200u32 {
+            let data: Vec<u8> = (0..64u32)
+                .flat_map(|i| sha256::Hash::hash(&serialize(&(seed, i))).into_inner().to_vec())
+                .collect();
+            let psbt = match PartiallySignedTransaction::arbitrary(&mut Unstructured::new(&data)) {
+                Ok(psbt) => psbt,
+                Err(_) => continue,
+            };
+            assert_eq!(deserialize::<PartiallySignedTransaction>(&serialize(&psbt)).unwrap(), psbt);
+            generated += 1;
+        }
+        assert!(generated > 100);
+    }
 }
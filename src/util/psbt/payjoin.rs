@@ -0,0 +1,745 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Payjoin.
+//!
+//! PSBT validation helpers for both roles of the Payjoin protocol as defined
+//! at <https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki>.
+//!
+//! The sender posts a finalized *original PSBT* to the receiver, which checks
+//! it with [`check_original_psbt`], turns it into a *payjoin proposal* with
+//! [`prepare_proposal`] and [`add_receiver_input`], and may take part of the
+//! fee from the sender's change up to [`max_fee_contribution`]. The sender
+//! then verifies the proposal with [`check_proposal`] before signing it, and
+//! may check the signed result with [`check_signed_proposal`].
+//!
+//! Transport (HTTP, URI `pj=` parameter) is out of scope of this module.
+//!
+
+use prelude::*;
+
+use core::fmt;
+#[cfg(feature = "std")] use std::error;
+
+use blockdata::script::{Instruction, Script};
+use blockdata::transaction::{OutPoint, TxIn, TxOut};
+use util::amount::Amount;
+use util::psbt::{Input, PartiallySignedTransaction};
+
+/// The only protocol version defined by BIP78.
+pub const VERSION: u32 = 1;
+
+/// A payjoin error.
+///
+/// Errors detected by the receiver can be reported to the sender using the
+/// code returned by [`Error::error_code`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Unsupported protocol version in the parameters.
+    UnsupportedVersion(u32),
+    /// Invalid or malformed query parameter.
+    InvalidParameter(String),
+    /// The input at the given index is not finalized, although it must be.
+    InputNotFinalized(usize),
+    /// The input at the given index is finalized, although it must not be.
+    InputFinalized(usize),
+    /// The input at the given index has no UTXO information.
+    MissingUtxo(usize),
+    /// The input at the given index has an unsupported script type.
+    UnsupportedInputType(usize),
+    /// The inputs use different script types.
+    MixedInputTypes,
+    /// The input at the given index spends an output of the receiver.
+    InputOwnedByReceiver(usize),
+    /// The transaction pays more than it spends.
+    NegativeFee,
+    /// The sum of the input or output values overflows.
+    ValueOverflow,
+    /// Key derivation paths or partial signatures leak in the proposal.
+    KeypathsPresent,
+    /// The proposal changed the transaction version.
+    VersionChanged,
+    /// The proposal changed the transaction lock time.
+    LockTimeChanged,
+    /// The sequence number of the sender's input spending the outpoint was changed.
+    SequenceChanged(OutPoint),
+    /// A sender input of the original PSBT is missing from the proposal.
+    MissingSenderInput(OutPoint),
+    /// A sender output of the original PSBT is missing or was modified.
+    MissingSenderOutput(TxOut),
+    /// The payment output was substituted or decreased although output substitution is disabled.
+    OutputSubstituted,
+    /// The additional fee output index is out of range or points to the payment output.
+    InvalidFeeOutputIndex(usize),
+    /// The receiver took more from the fee output than allowed.
+    FeeContributionTooHigh {
+        /// The amount taken from the fee output.
+        actual: Amount,
+        /// The maximum amount that could be taken.
+        max: Amount,
+    },
+    /// The receiver took more from the fee output than it added to the fee.
+    FeeContributionNotForFees,
+    /// The fee rate of the payjoin transaction is below the requested minimum.
+    FeeRateTooLow,
+}
+
+impl Error {
+    /// Returns the BIP78 well-known error code to report this error to the sender.
+    pub fn error_code(&self) -> &'static str {
+        match *self {
+            Error::UnsupportedVersion(_) => "version-unsupported",
+            _ => "original-psbt-rejected",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnsupportedVersion(v) => write!(f, "unsupported payjoin version: {}", v),
+            Error::InvalidParameter(ref p) => write!(f, "invalid payjoin parameter: {}", p),
+            Error::InputNotFinalized(i) => write!(f, "input {} is not finalized", i),
+            Error::InputFinalized(i) => write!(f, "input {} must not be finalized", i),
+            Error::MissingUtxo(i) => write!(f, "input {} has no UTXO information", i),
+            Error::UnsupportedInputType(i) => write!(f, "input {} has an unsupported script type", i),
+            Error::MixedInputTypes => f.write_str("inputs have mixed script types"),
+            Error::InputOwnedByReceiver(i) => write!(f, "input {} belongs to the receiver", i),
+            Error::NegativeFee => f.write_str("outputs exceed inputs"),
+            Error::ValueOverflow => f.write_str("input or output values overflow"),
+            Error::KeypathsPresent => f.write_str("proposal contains key paths or partial signatures"),
+            Error::VersionChanged => f.write_str("transaction version changed"),
+            Error::LockTimeChanged => f.write_str("transaction lock time changed"),
+            Error::SequenceChanged(ref o) => write!(f, "sequence of input {} changed", o),
+            Error::MissingSenderInput(ref o) => write!(f, "sender input {} missing from proposal", o),
+            Error::MissingSenderOutput(ref o) => write!(f, "sender output {} missing from proposal", o.script_pubkey),
+            Error::OutputSubstituted => f.write_str("payment output substituted although disabled"),
+            Error::InvalidFeeOutputIndex(i) => write!(f, "invalid additional fee output index: {}", i),
+            Error::FeeContributionTooHigh { actual, max } => write!(f, "fee contribution of {} exceeds {}", actual, max),
+            Error::FeeContributionNotForFees => f.write_str("fee contribution does not go to fees"),
+            Error::FeeRateTooLow => f.write_str("fee rate below the minimum"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for Error {}
+
+/// Script types of inputs supported by payjoin.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum InputType {
+    /// Pay to public key hash.
+    P2pkh,
+    /// Pay to witness public key hash nested in pay to script hash.
+    P2shP2wpkh,
+    /// Pay to witness public key hash.
+    P2wpkh,
+    /// Pay to taproot.
+    P2tr,
+}
+
+impl InputType {
+    /// Determines the type of the input spending `utxo`.
+    ///
+    /// Nested segwit is detected from the redeem script or, for finalized
+    /// inputs, from the last push of the final script sig.
+    pub fn from_input(utxo: &TxOut, input: &Input) -> Option<InputType> {
+        let spk = &utxo.script_pubkey;
+        if spk.is_p2pkh() {
+            Some(InputType::P2pkh)
+        } else if spk.is_v0_p2wpkh() {
+            Some(InputType::P2wpkh)
+        } else if spk.is_v1_p2tr() {
+            Some(InputType::P2tr)
+        } else if spk.is_p2sh() {
+            let redeem_script = match input.redeem_script {
+                Some(ref script) => script.clone(),
+                None => match input.final_script_sig.as_ref().and_then(|s| s.instructions().last()) {
+                    Some(Ok(Instruction::PushBytes(bytes))) => Script::from(bytes.to_vec()),
+                    _ => return None,
+                },
+            };
+            if redeem_script.is_v0_p2wpkh() {
+                Some(InputType::P2shP2wpkh)
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// The expected virtual size of a signed input of this type, used for fee
+    /// contribution accounting as specified by BIP78.
+    pub fn expected_vsize(self) -> u64 {
+        match self {
+            InputType::P2pkh => 148,
+            InputType::P2shP2wpkh => 91,
+            InputType::P2wpkh => 68,
+            InputType::P2tr => 58,
+        }
+    }
+}
+
+/// Optional parameters sent by the sender along with the original PSBT.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Params {
+    /// Whether the receiver is forbidden to change the payment output.
+    pub disable_output_substitution: bool,
+    /// The index of the output the receiver may decrease to pay for its inputs.
+    pub additional_fee_output_index: Option<usize>,
+    /// The maximum amount the receiver may take from the fee output.
+    pub max_additional_fee_contribution: Amount,
+    /// The minimum fee rate of the payjoin transaction in sat/vB.
+    pub min_fee_rate: Option<f64>,
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            disable_output_substitution: false,
+            additional_fee_output_index: None,
+            max_additional_fee_contribution: Amount::from_sat(0),
+            min_fee_rate: None,
+        }
+    }
+}
+
+impl Params {
+    /// Parses the parameters from the query string of a payjoin request.
+    ///
+    /// Unknown parameters are ignored as required by BIP78.
+    pub fn from_query(query: &str) -> Result<Params, Error> {
+        let mut params = Params::default();
+        let mut version = VERSION;
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = match param.find('=') {
+                Some(pos) => (&param[..pos], &param[pos + 1..]),
+                None => return Err(Error::InvalidParameter(param.to_owned())),
+            };
+            let invalid = || Error::InvalidParameter(param.to_owned());
+            match key {
+                "v" => version = value.parse().map_err(|_| invalid())?,
+                "disableoutputsubstitution" => {
+                    params.disable_output_substitution = value.parse().map_err(|_| invalid())?
+                }
+                "additionalfeeoutputindex" => {
+                    params.additional_fee_output_index = Some(value.parse().map_err(|_| invalid())?)
+                }
+                "maxadditionalfeecontribution" => {
+                    params.max_additional_fee_contribution = Amount::from_sat(value.parse().map_err(|_| invalid())?)
+                }
+                "minfeerate" => {
+                    let rate: f64 = value.parse().map_err(|_| invalid())?;
+                    if rate.is_nan() || rate < 0.0 {
+                        return Err(invalid());
+                    }
+                    params.min_fee_rate = Some(rate)
+                }
+                _ => {}
+            }
+        }
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        Ok(params)
+    }
+
+    /// Serializes the parameters into the query string of a payjoin request.
+    pub fn to_query(&self) -> String {
+        let mut ret = format!("v={}", VERSION);
+        if self.disable_output_substitution {
+            ret.push_str("&disableoutputsubstitution=true");
+        }
+        if let Some(index) = self.additional_fee_output_index {
+            ret.push_str(&format!(
+                "&additionalfeeoutputindex={}&maxadditionalfeecontribution={}",
+                index,
+                self.max_additional_fee_contribution.as_sat()
+            ));
+        }
+        if let Some(rate) = self.min_fee_rate {
+            ret.push_str(&format!("&minfeerate={}", rate));
+        }
+        ret
+    }
+}
+
+/// Returns the output spent by the input at `index`, if known.
+fn spent_utxo(psbt: &PartiallySignedTransaction, index: usize) -> Option<&TxOut> {
+    psbt.spent_utxo(index).ok()
+}
+
+/// Returns whether the input carries a final script sig or witness.
+fn is_finalized(input: &Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
+/// Computes the absolute fee paid by the PSBT.
+fn fee(psbt: &PartiallySignedTransaction) -> Result<u64, Error> {
+    let mut input_value = 0u64;
+    for i in 0..psbt.inputs.len() {
+        let value = spent_utxo(psbt, i).ok_or(Error::MissingUtxo(i))?.value;
+        input_value = input_value.checked_add(value).ok_or(Error::ValueOverflow)?;
+    }
+    let mut output_value = 0u64;
+    for output in &psbt.unsigned_tx.output {
+        output_value = output_value.checked_add(output.value).ok_or(Error::ValueOverflow)?;
+    }
+    input_value.checked_sub(output_value).ok_or(Error::NegativeFee)
+}
+
+/// Computes the virtual size of a PSBT whose inputs are all finalized.
+fn finalized_vsize(psbt: &PartiallySignedTransaction) -> u64 {
+    psbt.clone().extract_tx().vsize() as u64
+}
+
+/// Determines the common type of all inputs of the PSBT.
+fn common_input_type(psbt: &PartiallySignedTransaction) -> Result<InputType, Error> {
+    let mut ret = None;
+    for (i, input) in psbt.inputs.iter().enumerate() {
+        let utxo = spent_utxo(psbt, i).ok_or(Error::MissingUtxo(i))?;
+        let input_type = InputType::from_input(utxo, input).ok_or(Error::UnsupportedInputType(i))?;
+        match ret {
+            Some(t) if t != input_type => return Err(Error::MixedInputTypes),
+            _ => ret = Some(input_type),
+        }
+    }
+    ret.ok_or(Error::MissingSenderInput(OutPoint::null()))
+}
+
+/// Receiver: checks the original PSBT posted by the sender.
+///
+/// The original PSBT must be fully finalized (broadcastable), must carry UTXO
+/// information for every input, must not spend any output for which
+/// `is_receiver_script` returns true and all its inputs must be of the same,
+/// supported type, which is returned.
+pub fn check_original_psbt<F>(original: &PartiallySignedTransaction, is_receiver_script: F) -> Result<InputType, Error>
+where
+    F: Fn(&Script) -> bool,
+{
+    for (i, input) in original.inputs.iter().enumerate() {
+        if !is_finalized(input) {
+            return Err(Error::InputNotFinalized(i));
+        }
+        let utxo = spent_utxo(original, i).ok_or(Error::MissingUtxo(i))?;
+        if is_receiver_script(&utxo.script_pubkey) {
+            return Err(Error::InputOwnedByReceiver(i));
+        }
+    }
+    fee(original)?;
+    common_input_type(original)
+}
+
+/// Receiver: creates a payjoin proposal from the original PSBT.
+///
+/// The sender's inputs are stripped of their final scripts and signatures so
+/// the sender can sign them again, and all key origin information is removed.
+pub fn prepare_proposal(original: &PartiallySignedTransaction) -> PartiallySignedTransaction {
+    let mut proposal = original.clone();
+    proposal.xpub.clear();
+    for input in &mut proposal.inputs {
+        input.final_script_sig = None;
+        input.final_script_witness = None;
+        input.partial_sigs.clear();
+        input.bip32_derivation.clear();
+        input.tap_key_sig = None;
+        input.tap_script_sigs.clear();
+        input.tap_key_origins.clear();
+    }
+    for output in &mut proposal.outputs {
+        output.bip32_derivation.clear();
+        output.tap_key_origins.clear();
+    }
+    proposal
+}
+
+/// Receiver: inserts one of the receiver's inputs into the proposal at `index`.
+///
+/// The input must be finalized, must carry UTXO information and must be of
+/// `sender_type`, the type of the sender's inputs. The scriptSig and witness
+/// of `txin` are cleared since the unsigned transaction must not carry them.
+pub fn add_receiver_input(
+    proposal: &mut PartiallySignedTransaction,
+    index: usize,
+    mut txin: TxIn,
+    input: Input,
+    sender_type: InputType,
+) -> Result<(), Error> {
+    if index > proposal.inputs.len() {
+        return Err(Error::InvalidParameter(format!("input index {} out of range", index)));
+    }
+    if !is_finalized(&input) {
+        return Err(Error::InputNotFinalized(index));
+    }
+    if !input.bip32_derivation.is_empty() || !input.partial_sigs.is_empty() {
+        return Err(Error::KeypathsPresent);
+    }
+    txin.script_sig = Script::new();
+    txin.witness.clear();
+    proposal.unsigned_tx.input.insert(index, txin);
+    proposal.inputs.insert(index, input);
+
+    let input_type = spent_utxo(proposal, index)
+        .ok_or(Error::MissingUtxo(index))
+        .and_then(|utxo| InputType::from_input(utxo, &proposal.inputs[index]).ok_or(Error::UnsupportedInputType(index)));
+    match input_type {
+        Ok(t) if t == sender_type => Ok(()),
+        res => {
+            proposal.unsigned_tx.input.remove(index);
+            proposal.inputs.remove(index);
+            Err(res.err().unwrap_or(Error::MixedInputTypes))
+        }
+    }
+}
+
+/// Receiver: computes how much may be taken from the sender's fee output to
+/// pay for `added_inputs` receiver inputs of `sender_type`.
+///
+/// This is the smaller of the sender's `maxadditionalfeecontribution` and the
+/// fee the added inputs cost at the fee rate of the original PSBT.
+pub fn max_fee_contribution(
+    original: &PartiallySignedTransaction,
+    params: &Params,
+    sender_type: InputType,
+    added_inputs: usize,
+) -> Result<Amount, Error> {
+    if params.additional_fee_output_index.is_none() {
+        return Ok(Amount::from_sat(0));
+    }
+    let original_fee = fee(original)?;
+    let original_vsize = finalized_vsize(original);
+    let for_inputs = original_fee.saturating_mul(sender_type.expected_vsize()).saturating_mul(added_inputs as u64) / original_vsize;
+    Ok(Amount::from_sat(::core::cmp::min(for_inputs, params.max_additional_fee_contribution.as_sat())))
+}
+
+/// Sender: checks the payjoin proposal returned by the receiver before signing it.
+///
+/// `payment_index` is the index of the output paying the receiver in the
+/// original PSBT; all other outputs of the original PSBT belong to the sender.
+pub fn check_proposal(
+    original: &PartiallySignedTransaction,
+    proposal: &PartiallySignedTransaction,
+    params: &Params,
+    payment_index: usize,
+) -> Result<(), Error> {
+    let original_tx = &original.unsigned_tx;
+    let proposal_tx = &proposal.unsigned_tx;
+    if proposal_tx.version != original_tx.version {
+        return Err(Error::VersionChanged);
+    }
+    if proposal_tx.lock_time != original_tx.lock_time {
+        return Err(Error::LockTimeChanged);
+    }
+    if !proposal.xpub.is_empty() {
+        return Err(Error::KeypathsPresent);
+    }
+
+    let sender_type = common_input_type(original)?;
+    let mut sender_inputs = original_tx.input.iter().peekable();
+    for (i, (txin, input)) in proposal_tx.input.iter().zip(proposal.inputs.iter()).enumerate() {
+        if !input.bip32_derivation.is_empty() || !input.partial_sigs.is_empty() || !input.tap_key_origins.is_empty() {
+            return Err(Error::KeypathsPresent);
+        }
+        match sender_inputs.peek() {
+            Some(original_txin) if original_txin.previous_output == txin.previous_output => {
+                if original_txin.sequence != txin.sequence {
+                    return Err(Error::SequenceChanged(txin.previous_output));
+                }
+                if is_finalized(input) {
+                    return Err(Error::InputFinalized(i));
+                }
+                sender_inputs.next();
+            }
+            _ => {
+                if !is_finalized(input) {
+                    return Err(Error::InputNotFinalized(i));
+                }
+                let utxo = spent_utxo(proposal, i).ok_or(Error::MissingUtxo(i))?;
+                if InputType::from_input(utxo, input) != Some(sender_type) {
+                    return Err(Error::MixedInputTypes);
+                }
+            }
+        }
+    }
+    if let Some(missing) = sender_inputs.next() {
+        return Err(Error::MissingSenderInput(missing.previous_output));
+    }
+
+    for output in &proposal.outputs {
+        if !output.bip32_derivation.is_empty() || !output.tap_key_origins.is_empty() {
+            return Err(Error::KeypathsPresent);
+        }
+    }
+
+    if payment_index >= original_tx.output.len() {
+        return Err(Error::InvalidFeeOutputIndex(payment_index));
+    }
+    let fee_index = match params.additional_fee_output_index {
+        Some(i) if i >= original_tx.output.len() || i == payment_index => return Err(Error::InvalidFeeOutputIndex(i)),
+        i => i,
+    };
+
+    // Match the sender's outputs in order; whatever is left belongs to the receiver.
+    let mut proposal_outputs = proposal_tx.output.iter();
+    let mut contribution = 0;
+    for (i, original_out) in original_tx.output.iter().enumerate() {
+        if i == payment_index {
+            if params.disable_output_substitution {
+                let found = proposal_tx.output.iter().any(|o| {
+                    o.script_pubkey == original_out.script_pubkey && o.value >= original_out.value
+                });
+                if !found {
+                    return Err(Error::OutputSubstituted);
+                }
+            }
+            continue;
+        }
+        let found = proposal_outputs.by_ref().find(|o| o.script_pubkey == original_out.script_pubkey);
+        match found {
+            Some(out) if out.value == original_out.value => {}
+            Some(out) if Some(i) == fee_index && out.value < original_out.value => {
+                contribution = original_out.value - out.value;
+            }
+            _ => return Err(Error::MissingSenderOutput(original_out.clone())),
+        }
+    }
+
+    if contribution > 0 {
+        let max = params.max_additional_fee_contribution.as_sat();
+        if contribution > max {
+            return Err(Error::FeeContributionTooHigh {
+                actual: Amount::from_sat(contribution),
+                max: Amount::from_sat(max),
+            });
+        }
+        let original_fee = fee(original)?;
+        let proposal_fee = fee(proposal)?;
+        if proposal_fee < original_fee || contribution > proposal_fee - original_fee {
+            return Err(Error::FeeContributionNotForFees);
+        }
+        let added_inputs = proposal_tx.input.len() - original_tx.input.len();
+        let allowed = original_fee.saturating_mul(sender_type.expected_vsize()).saturating_mul(added_inputs as u64) / finalized_vsize(original);
+        if contribution > allowed {
+            return Err(Error::FeeContributionTooHigh {
+                actual: Amount::from_sat(contribution),
+                max: Amount::from_sat(allowed),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Sender: checks the payjoin transaction once all inputs are finalized.
+///
+/// Verifies that the fee rate is not below the `minfeerate` parameter.
+pub fn check_signed_proposal(proposal: &PartiallySignedTransaction, params: &Params) -> Result<(), Error> {
+    for (i, input) in proposal.inputs.iter().enumerate() {
+        if !is_finalized(input) {
+            return Err(Error::InputNotFinalized(i));
+        }
+    }
+    let fee = fee(proposal)?;
+    if let Some(min_fee_rate) = params.min_fee_rate {
+        if (fee as f64) < min_fee_rate * finalized_vsize(proposal) as f64 {
+            return Err(Error::FeeRateTooLow);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hashes::hex::FromHex;
+    use hashes::Hash;
+    use blockdata::witness::Witness;
+    use hash_types::{PubkeyHash, Txid, WPubkeyHash};
+    use util::psbt::Output;
+
+    fn p2wpkh(n: u8) -> Script {
+        Script::new_v0_p2wpkh(&WPubkeyHash::from_slice(&[n; 20]).unwrap())
+    }
+
+    fn witness() -> Witness {
+        let sig = Vec::<u8>::from_hex("3044022000000000000000000000000000000000000000000000000000000000000000010220000000000000000000000000000000000000000000000000000000000000000101").unwrap();
+        let pk = vec![2u8; 33];
+        Witness::from_vec(vec![sig, pk])
+    }
+
+    fn input(n: u8, value: u64) -> (TxIn, Input) {
+        let txin = TxIn {
+            previous_output: OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), 0),
+            script_sig: Script::new(),
+            sequence: 0xfffffffd,
+            witness: Witness::default(),
+        };
+        let input = Input {
+            witness_utxo: Some(TxOut { value, script_pubkey: p2wpkh(n) }),
+            final_script_witness: Some(witness()),
+            ..Default::default()
+        };
+        (txin, input)
+    }
+
+    /// Original PSBT with one sender input of 100_000 sat paying 50_000 to the
+    /// receiver (output 0) and 49_000 change (output 1), i.e. a 1_000 sat fee.
+    fn original() -> PartiallySignedTransaction {
+        let (txin, input) = input(1, 100_000);
+        let tx = ::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin],
+            output: vec![
+                TxOut { value: 50_000, script_pubkey: p2wpkh(0xaa) },
+                TxOut { value: 49_000, script_pubkey: p2wpkh(0xbb) },
+            ],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0] = input;
+        psbt.outputs = vec![Output::default(), Output::default()];
+        psbt
+    }
+
+    fn params() -> Params {
+        Params {
+            disable_output_substitution: false,
+            additional_fee_output_index: Some(1),
+            max_additional_fee_contribution: Amount::from_sat(1_000),
+            min_fee_rate: Some(1.0),
+        }
+    }
+
+    /// Builds a proposal adding a 20_000 sat receiver input and taking `contribution` from the change.
+    fn proposal(original: &PartiallySignedTransaction, contribution: u64) -> PartiallySignedTransaction {
+        let mut proposal = prepare_proposal(original);
+        let (txin, input) = input(2, 20_000);
+        add_receiver_input(&mut proposal, 1, txin, input, InputType::P2wpkh).unwrap();
+        proposal.unsigned_tx.output[0].value += 20_000;
+        proposal.unsigned_tx.output[1].value -= contribution;
+        proposal
+    }
+
+    #[test]
+    fn test_params_query() {
+        let params = params();
+        let query = params.to_query();
+        assert_eq!(query, "v=1&additionalfeeoutputindex=1&maxadditionalfeecontribution=1000&minfeerate=1");
+        assert_eq!(Params::from_query(&query).unwrap(), params);
+        assert_eq!(Params::from_query("v=2"), Err(Error::UnsupportedVersion(2)));
+        assert_eq!(Params::from_query("disableoutputsubstitution=maybe"), Err(Error::InvalidParameter("disableoutputsubstitution=maybe".to_owned())));
+        assert!(Params::from_query("v=1&unknown=1&disableoutputsubstitution=true").unwrap().disable_output_substitution);
+    }
+
+    #[test]
+    fn test_check_original_psbt() {
+        let original = original();
+        assert_eq!(check_original_psbt(&original, |_| false), Ok(InputType::P2wpkh));
+        assert_eq!(check_original_psbt(&original, |s| *s == p2wpkh(1)), Err(Error::InputOwnedByReceiver(0)));
+
+        let mut unfinalized = original.clone();
+        unfinalized.inputs[0].final_script_witness = None;
+        assert_eq!(check_original_psbt(&unfinalized, |_| false), Err(Error::InputNotFinalized(0)));
+
+        let mut overspending = original.clone();
+        overspending.unsigned_tx.output[1].value = 60_000;
+        assert_eq!(check_original_psbt(&overspending, |_| false), Err(Error::NegativeFee));
+
+        let mut overflowing = original.clone();
+        overflowing.unsigned_tx.output[0].value = u64::max_value();
+        assert_eq!(check_original_psbt(&overflowing, |_| false), Err(Error::ValueOverflow));
+    }
+
+    #[test]
+    fn test_add_receiver_input() {
+        let original = original();
+        let mut proposal = prepare_proposal(&original);
+        assert!(proposal.inputs[0].final_script_witness.is_none());
+
+        let (txin, mut input) = input(2, 20_000);
+        input.witness_utxo = Some(TxOut { value: 20_000, script_pubkey: Script::new_p2pkh(&PubkeyHash::from_slice(&[2; 20]).unwrap()) });
+        assert_eq!(add_receiver_input(&mut proposal, 0, txin.clone(), input, InputType::P2wpkh), Err(Error::MixedInputTypes));
+        assert_eq!(proposal.inputs.len(), 1);
+
+        let (_, mut input) = self::input(2, 20_000);
+        input.final_script_witness = None;
+        assert_eq!(add_receiver_input(&mut proposal, 0, txin, input, InputType::P2wpkh), Err(Error::InputNotFinalized(0)));
+        assert_eq!(proposal.unsigned_tx.input.len(), 1);
+    }
+
+    #[test]
+    fn test_fee_contribution() {
+        let original = original();
+        // original vsize is 141 vB, so 1_000 sat pay ~7.09 sat/vB and a P2WPKH input costs 482 sat
+        assert_eq!(finalized_vsize(&original), 141);
+        assert_eq!(max_fee_contribution(&original, &params(), InputType::P2wpkh, 1), Ok(Amount::from_sat(482)));
+        assert_eq!(max_fee_contribution(&original, &Params::default(), InputType::P2wpkh, 1), Ok(Amount::from_sat(0)));
+    }
+
+    #[test]
+    fn test_check_proposal() {
+        let original = original();
+        let params = params();
+
+        let proposal = proposal(&original, 482);
+        assert_eq!(check_proposal(&original, &proposal, &params, 0), Ok(()));
+
+        let bad = self::proposal(&original, 483);
+        assert_eq!(
+            check_proposal(&original, &bad, &params, 0),
+            Err(Error::FeeContributionTooHigh { actual: Amount::from_sat(483), max: Amount::from_sat(482) })
+        );
+
+        let mut bad = proposal.clone();
+        bad.unsigned_tx.input[0].sequence = 0xffffffff;
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Err(Error::SequenceChanged(bad.unsigned_tx.input[0].previous_output)));
+
+        let mut bad = proposal.clone();
+        bad.unsigned_tx.lock_time = 1;
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Err(Error::LockTimeChanged));
+
+        let mut bad = proposal.clone();
+        bad.unsigned_tx.output[1].script_pubkey = p2wpkh(0xcc);
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Err(Error::MissingSenderOutput(original.unsigned_tx.output[1].clone())));
+
+        let mut bad = proposal.clone();
+        bad.unsigned_tx.output[0].script_pubkey = p2wpkh(0xcc);
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Ok(()));
+        let no_substitution = Params { disable_output_substitution: true, ..params.clone() };
+        assert_eq!(check_proposal(&original, &bad, &no_substitution, 0), Err(Error::OutputSubstituted));
+
+        let mut bad = proposal.clone();
+        bad.inputs[0].final_script_witness = Some(witness());
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Err(Error::InputFinalized(0)));
+
+        let mut bad = proposal.clone();
+        bad.unsigned_tx.input.remove(0);
+        bad.inputs.remove(0);
+        assert_eq!(check_proposal(&original, &bad, &params, 0), Err(Error::MissingSenderInput(original.unsigned_tx.input[0].previous_output)));
+    }
+
+    #[test]
+    fn test_check_signed_proposal() {
+        let original = original();
+        let mut proposal = proposal(&original, 482);
+        assert_eq!(check_signed_proposal(&proposal, &params()), Err(Error::InputNotFinalized(0)));
+
+        proposal.inputs[0].final_script_witness = Some(witness());
+        assert_eq!(check_signed_proposal(&proposal, &params()), Ok(()));
+        let params = Params { min_fee_rate: Some(20.0), ..params() };
+        assert_eq!(check_signed_proposal(&proposal, &params), Err(Error::FeeRateTooLow));
+    }
+}
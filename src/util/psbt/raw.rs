@@ -26,6 +26,7 @@ use consensus::encode::{self, ReadExt, WriteExt, Decodable, Encodable, VarInt, s
 use hashes::hex;
 use util::psbt::Error;
 use util::read_to_end;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// A PSBT key in its raw byte form.
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Ord, PartialOrd)]
@@ -68,6 +69,31 @@ pub struct ProprietaryKey<Subtype=ProprietaryType> where Subtype: Copy + From<u8
     pub key: Vec<u8>,
 }
 
+arbitrary_struct_impl!(Key, type_value, key);
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a, Subtype> Arbitrary<'a> for ProprietaryKey<Subtype> where Subtype: Copy + From<u8> + Into<u8> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProprietaryKey {
+            prefix: u.arbitrary()?,
+            subtype: Subtype::from(u.arbitrary()?),
+            key: u.arbitrary()?,
+        })
+    }
+}
+
+/// Generates unknown key-value pairs, with key types none of the maps defines.
+#[cfg(feature = "arbitrary")]
+pub(crate) fn arbitrary_unknown(u: &mut Unstructured) -> arbitrary::Result<BTreeMap<Key, Vec<u8>>> {
+    let mut unknown = BTreeMap::new();
+    while u.arbitrary()? {
+        let key = Key { type_value: u.int_in_range(0x20..=0xFA)?, key: u.arbitrary()? };
+        unknown.insert(key, u.arbitrary()?);
+    }
+    Ok(unknown)
+}
+
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "type: {:#x}, key: ", self.type_value)?;
@@ -355,7 +355,7 @@ impl Deserialize for TapTree {
             builder = builder.add_leaf_with_ver(*depth, script, leaf_version)
                 .map_err(|_| encode::Error::ParseFailed("Tree not in DFS order"))?;
         }
-        if builder.is_finalized() || !builder.has_hidden_nodes() {
+        if builder.is_finalized() && !builder.has_hidden_nodes() {
             Ok(TapTree(builder))
         } else {
             Err(encode::Error::ParseFailed("Incomplete taproot Tree"))
@@ -405,6 +405,22 @@ mod tests {
         assert_eq!(tree, tree_prime);
     }
 
+    #[test]
+    fn taptree_deserialize() {
+        // empty tree
+        assert!(TapTree::deserialize(&[]).is_err());
+        // a single leaf at depth 1 is missing its sibling
+        assert!(TapTree::deserialize(&Vec::from_hex("01c00151").unwrap()).is_err());
+        // leaves at depths 1, 2 miss a leaf at depth 2
+        assert!(TapTree::deserialize(&Vec::from_hex("01c0015102c00152").unwrap()).is_err());
+
+        let bytes = Vec::from_hex("01c0015102c0015202c00153").unwrap();
+        let tree = TapTree::deserialize(&bytes).unwrap();
+        assert_eq!(tree.serialize(), bytes);
+        let leaves = tree.script_leaves().map(|leaf| leaf.script().to_owned()).collect::<Vec<_>>();
+        assert_eq!(leaves, vec![Script::from_hex("51").unwrap(), Script::from_hex("52").unwrap(), Script::from_hex("53").unwrap()]);
+    }
+
     #[test]
     fn can_deserialize_non_standard_psbt_sighash_type() {
         let non_standard_sighash = [222u8, 0u8, 0u8, 0u8]; // 32 byte value.
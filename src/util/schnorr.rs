@@ -26,6 +26,7 @@ use secp256k1::{self, Secp256k1, Verification, constants};
 use hashes::Hash;
 use util::taproot::{TapBranchHash, TapTweakHash};
 use SchnorrSighashType;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// Deprecated re-export of [`secp256k1::XOnlyPublicKey`]
 #[deprecated(since = "0.28.0", note = "Please use `util::key::XOnlyPublicKey` instead")]
@@ -197,6 +198,14 @@ impl TweakedKeyPair {
     pub fn into_inner(self) -> ::KeyPair {
         self.0
     }
+
+    /// Returns the tweaked public key and its parity, as committed to in the control block of
+    /// script path spends.
+    #[inline]
+    pub fn public_parts(&self) -> (TweakedPublicKey, secp256k1::Parity) {
+        let pubkey = ::PublicKey::new(secp256k1::PublicKey::from_keypair(&self.0));
+        (TweakedPublicKey(::XOnlyPublicKey::from(pubkey.inner)), pubkey.parity())
+    }
 }
 
 impl From<TweakedPublicKey> for ::XOnlyPublicKey {
@@ -223,6 +232,16 @@ pub struct SchnorrSig {
     pub hash_ty: SchnorrSighashType,
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for SchnorrSig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let sig = secp256k1::schnorr::Signature::from_slice(u.bytes(64)?)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+        Ok(SchnorrSig { sig, hash_ty: u.arbitrary()? })
+    }
+}
+
 impl SchnorrSig {
     /// Deserialize from slice
     pub fn from_slice(sl: &[u8]) -> Result<Self, SchnorrSigError> {
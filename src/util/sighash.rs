@@ -25,6 +25,7 @@ use prelude::*;
 pub use blockdata::transaction::{EcdsaSighashType, SighashTypeParseError};
 use blockdata::witness::Witness;
 use consensus::{encode, Encodable};
+use consensus::params::Params;
 use core::{str, fmt};
 use core::ops::{Deref, DerefMut};
 use core::borrow::Borrow;
@@ -35,6 +36,11 @@ use Sighash;
 use {Script, Transaction, TxOut};
 
 use super::taproot::LeafVersion;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
+
+/// Flag of the sighash type of replay protected signatures on chains with a fork id, see
+/// [`SighashCache::forkid_signature_hash`].
+pub const SIGHASH_FORKID: u32 = 0x40;
 
 /// Efficiently calculates signature hash message for legacy, segwit and taproot inputs.
 #[derive(Debug)]
@@ -131,6 +137,23 @@ pub enum SchnorrSighashType {
 }
 serde_string_impl!(SchnorrSighashType, "a SchnorrSighashType data");
 
+// `Reserved` is left out, as it is not a valid signature hash type.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for SchnorrSighashType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            SchnorrSighashType::Default,
+            SchnorrSighashType::All,
+            SchnorrSighashType::None,
+            SchnorrSighashType::Single,
+            SchnorrSighashType::AllPlusAnyoneCanPay,
+            SchnorrSighashType::NonePlusAnyoneCanPay,
+            SchnorrSighashType::SinglePlusAnyoneCanPay,
+        ])?)
+    }
+}
+
 impl fmt::Display for SchnorrSighashType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
@@ -543,12 +566,25 @@ impl<R: Deref<Target=Transaction>> SighashCache<R> {
     /// Encode the BIP143 signing data for any flag type into a given object implementing a
     /// [`std::io::Write`] trait.
     pub fn segwit_encode_signing_data_to<Write: io::Write>(
+        &mut self,
+        writer: Write,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<(), Error> {
+        self.bip143_encode_signing_data_to(writer, input_index, script_code, value, sighash_type, sighash_type.to_u32())
+    }
+
+    /// Encodes the BIP143 signing data, committing to `hash_type` as the sighash type.
+    fn bip143_encode_signing_data_to<Write: io::Write>(
         &mut self,
         mut writer: Write,
         input_index: usize,
         script_code: &Script,
         value: u64,
         sighash_type: EcdsaSighashType,
+        hash_type: u32,
     ) -> Result<(), Error> {
         let zero_hash = sha256d::Hash::default();
 
@@ -601,7 +637,7 @@ impl<R: Deref<Target=Transaction>> SighashCache<R> {
         }
 
         self.tx.lock_time.consensus_encode(&mut writer)?;
-        sighash_type.to_u32().consensus_encode(&mut writer)?;
+        hash_type.consensus_encode(&mut writer)?;
         Ok(())
     }
 
@@ -624,6 +660,60 @@ impl<R: Deref<Target=Transaction>> SighashCache<R> {
         Ok(Sighash::from_engine(enc))
     }
 
+    /// Encode the replay protected signing data of chains with a fork id into a given object
+    /// implementing a [`std::io::Write`] trait.
+    ///
+    /// This is the BIP143 signing data, used for all inputs, with [`SIGHASH_FORKID`] and the fork
+    /// id in the upper 24 bits of the committed sighash type. Only the lower 24 bits of `fork_id`
+    /// are used.
+    pub fn forkid_encode_signing_data_to<Write: io::Write>(
+        &mut self,
+        writer: Write,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: EcdsaSighashType,
+        fork_id: u32,
+    ) -> Result<(), Error> {
+        let hash_type = sighash_type.to_u32() | SIGHASH_FORKID | (fork_id & 0x00ff_ffff) << 8;
+        self.bip143_encode_signing_data_to(writer, input_index, script_code, value, sighash_type, hash_type)
+    }
+
+    /// Computes the replay protected sighash of chains with a fork id for any flag type.
+    ///
+    /// The signature must be followed by the sighash type byte with [`SIGHASH_FORKID`] set.
+    pub fn forkid_signature_hash(
+        &mut self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: EcdsaSighashType,
+        fork_id: u32,
+    ) -> Result<Sighash, Error> {
+        let mut enc = Sighash::engine();
+        self.forkid_encode_signing_data_to(&mut enc, input_index, script_code, value, sighash_type, fork_id)?;
+        Ok(Sighash::from_engine(enc))
+    }
+
+    /// Computes the sighash of a non-witness input on the chain described by `params`.
+    ///
+    /// This is the [`SighashCache::forkid_signature_hash`] if the chain has a
+    /// [`Params::fork_id`], and the [`SighashCache::legacy_signature_hash`] otherwise, in which
+    /// case `value` is not used.
+    pub fn non_witness_signature_hash(
+        &mut self,
+        params: &Params,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<Sighash, Error> {
+        match params.fork_id {
+            Some(fork_id) => self.forkid_signature_hash(input_index, script_code, value, sighash_type, fork_id),
+            None => self.legacy_signature_hash(input_index, script_code, sighash_type.to_u32()),
+        }
+    }
+
     /// Encode the legacy signing data for any flag type into a given object implementing a
     /// [`std::io::Write`] trait. Internally calls [`Transaction::encode_signing_data_to`]
     pub fn legacy_encode_signing_data_to<Write: io::Write, U: Into<u32>>(
@@ -800,7 +890,44 @@ mod tests {
     use secp256k1::{self, SecretKey, XOnlyPublicKey};
     extern crate serde_json;
 
-    use {Script, Transaction, TxIn, TxOut};
+    use {Network, Script, Transaction, TxIn, TxOut};
+
+    #[test]
+    fn test_forkid_sighash() {
+        let tx: Transaction = deserialize(&Vec::from_hex("0200000001cf309ee0839b8aaa3fbc84f8bd32e9c6357e99b49bf6a3af90308c68e762f1d70100000000feffffff0288528c61000000001600146e8d9e07c543a309dcdeba8b50a14a991a658c5be0aebb0000000000160014698d8419804a5d5994704d47947889ff7620c004db000000").unwrap()).unwrap();
+        let script_code = Script::from(Vec::from_hex("76a91462744660c6b5133ddeaacbc57d2dc2d7b14d0b0688ac").unwrap());
+        let value = 1648888940;
+        let mut cache = SighashCache::new(&tx);
+
+        // the preimage is the BIP143 one, except for the committed sighash type
+        let mut segwit = vec![];
+        cache.segwit_encode_signing_data_to(&mut segwit, 0, &script_code, value, EcdsaSighashType::All).unwrap();
+        let mut forkid = vec![];
+        cache.forkid_encode_signing_data_to(&mut forkid, 0, &script_code, value, EcdsaSighashType::All, 0).unwrap();
+        let (segwit_data, forkid_data) = (&segwit[..segwit.len() - 4], &forkid[..forkid.len() - 4]);
+        assert_eq!(segwit_data, forkid_data);
+        assert_eq!(forkid[forkid.len() - 4..], [0x41, 0, 0, 0]);
+
+        let mut forkid = vec![];
+        cache.forkid_encode_signing_data_to(&mut forkid, 0, &script_code, value, EcdsaSighashType::AllPlusAnyoneCanPay, 0xabcdef).unwrap();
+        assert_eq!(forkid[forkid.len() - 4..], [0xc1, 0xef, 0xcd, 0xab]);
+        assert_eq!(
+            cache.forkid_signature_hash(0, &script_code, value, EcdsaSighashType::AllPlusAnyoneCanPay, 0xabcdef).unwrap(),
+            Sighash::hash(&forkid)
+        );
+
+        // the sighash of non-witness inputs follows the chain parameters
+        let mut params = Params::new(Network::Bitcoin);
+        assert_eq!(
+            cache.non_witness_signature_hash(&params, 0, &script_code, value, EcdsaSighashType::All).unwrap(),
+            cache.legacy_signature_hash(0, &script_code, 0x01).unwrap()
+        );
+        params.fork_id = Some(0);
+        assert_eq!(
+            cache.non_witness_signature_hash(&params, 0, &script_code, value, EcdsaSighashType::All).unwrap(),
+            cache.forkid_signature_hash(0, &script_code, value, EcdsaSighashType::All, 0).unwrap()
+        );
+    }
 
     #[test]
     fn test_tap_sighash_hash() {
@@ -0,0 +1,379 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP325 signet.
+//!
+//! A signet is a test network where blocks are only valid if, in addition to
+//! the proof-of-work, they carry a solution to the network's challenge script.
+//! The solution is stored in the coinbase witness commitment output and signs
+//! a commitment to the block which excludes the solution itself.
+//!
+//! To sign a block, set an empty solution with [`set_solution`], sign the
+//! `to_sign` transaction of [`SignetTxs::new`] as a spend of the `to_spend`
+//! output, then set the resulting solution and grind the nonce.
+//!
+
+use prelude::*;
+
+use core::fmt;
+
+use hashes::{sha256d, Hash};
+use blockdata::block::{Block, WITNESS_COMMITMENT_HEADER};
+use blockdata::opcodes;
+use blockdata::script::{Builder, Instruction, Script};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use blockdata::witness::Witness;
+use consensus::encode::{self, serialize};
+use util::endian;
+
+/// The bytes preceding the solution in the witness commitment output.
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// An error in a signet block.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The block has no coinbase transaction.
+    NoCoinbase,
+    /// The coinbase has no witness commitment output to hold the solution.
+    NoWitnessCommitment,
+    /// The signet solution could not be parsed.
+    InvalidSolution,
+    /// The solution does not satisfy the challenge.
+    Verification(::blockdata::script::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoCoinbase => f.write_str("block has no coinbase transaction"),
+            Error::NoWitnessCommitment => f.write_str("coinbase has no witness commitment"),
+            Error::InvalidSolution => f.write_str("invalid signet solution encoding"),
+            Error::Verification(ref e) => write!(f, "signet solution verification failed: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Verification(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The challenge script a signet block solution must satisfy.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SignetChallenge(Script);
+
+impl SignetChallenge {
+    /// Creates a challenge from its script.
+    pub fn new(script: Script) -> Self {
+        SignetChallenge(script)
+    }
+
+    /// Returns the challenge script.
+    pub fn script(&self) -> &Script {
+        &self.0
+    }
+
+    /// Returns the network magic of a signet using this challenge, as used by
+    /// [`Network::magic`](::network::constants::Network::magic).
+    pub fn magic(&self) -> u32 {
+        let hash = sha256d::Hash::hash(&serialize(&self.0));
+        endian::slice_to_u32_le(&hash[0..4])
+    }
+}
+
+/// The solution to a signet challenge: the script sig and witness spending it.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SignetSolution {
+    /// The script sig of the spend.
+    pub script_sig: Script,
+    /// The witness of the spend.
+    pub witness: Witness,
+}
+
+impl_consensus_encoding!(SignetSolution, script_sig, witness);
+
+impl SignetSolution {
+    /// Extracts the solution from the coinbase of `block`, if it has one.
+    pub fn from_block(block: &Block) -> Result<Option<SignetSolution>, Error> {
+        let coinbase = block.txdata.first().ok_or(Error::NoCoinbase)?;
+        let index = witness_commitment_index(coinbase).ok_or(Error::NoWitnessCommitment)?;
+        match fetch_and_clear_solution(&coinbase.output[index].script_pubkey) {
+            Some((_, solution)) => {
+                encode::deserialize(&solution).map(Some).map_err(|_| Error::InvalidSolution)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Stores `solution` in the coinbase witness commitment output of `block`, replacing any
+/// previous solution, and updates the merkle root of the header.
+pub fn set_solution(block: &mut Block, solution: &SignetSolution) -> Result<(), Error> {
+    {
+        let coinbase = block.txdata.first_mut().ok_or(Error::NoCoinbase)?;
+        let index = witness_commitment_index(coinbase).ok_or(Error::NoWitnessCommitment)?;
+        let output = &mut coinbase.output[index];
+        let mut data = SIGNET_HEADER.to_vec();
+        data.extend(serialize(solution));
+        output.script_pubkey = match replace_solution(&output.script_pubkey, &data) {
+            Some((replaced, _)) => replaced,
+            None => Builder::from(output.script_pubkey.to_bytes()).push_slice(&data).into_script(),
+        };
+    }
+    block.header.merkle_root = block.compute_merkle_root().expect("block has a coinbase");
+    Ok(())
+}
+
+/// The virtual transactions whose spend proves a signet block valid.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SignetTxs {
+    /// Transaction with a single output locked by the challenge, committing to the block.
+    pub to_spend: Transaction,
+    /// Transaction spending the output of `to_spend` with the signet solution.
+    pub to_sign: Transaction,
+}
+
+impl SignetTxs {
+    /// Builds the signet transactions for `block` and `challenge`.
+    ///
+    /// The block commitment excludes the solution, so the transactions are the same before and
+    /// after [`set_solution`] replaces it; `to_sign` carries the current solution, if any.
+    pub fn new(block: &Block, challenge: &SignetChallenge) -> Result<SignetTxs, Error> {
+        let coinbase = block.txdata.first().ok_or(Error::NoCoinbase)?;
+        let index = witness_commitment_index(coinbase).ok_or(Error::NoWitnessCommitment)?;
+
+        let mut modified_cb = coinbase.clone();
+        let mut solution = SignetSolution::default();
+        // A missing solution is allowed, to support trivially true challenges.
+        if let Some((stripped, bytes)) = fetch_and_clear_solution(&coinbase.output[index].script_pubkey) {
+            solution = encode::deserialize(&bytes).map_err(|_| Error::InvalidSolution)?;
+            modified_cb.output[index].script_pubkey = stripped;
+        }
+
+        let hashes = Some(modified_cb.txid().as_hash()).into_iter()
+            .chain(block.txdata.iter().skip(1).map(|tx| tx.txid().as_hash()));
+        let signet_merkle_root = ::util::hash::bitcoin_merkle_root(hashes).expect("block has a coinbase");
+
+        let mut block_data = Vec::with_capacity(72);
+        block_data.extend(serialize(&block.header.version));
+        block_data.extend(serialize(&block.header.prev_blockhash));
+        block_data.extend(serialize(&signet_merkle_root));
+        block_data.extend(serialize(&block.header.time));
+
+        let to_spend = Transaction {
+            version: 0,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new()
+                    .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                    .push_slice(&block_data)
+                    .into_script(),
+                sequence: 0,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut { value: 0, script_pubkey: challenge.script().clone() }],
+        };
+        let to_sign = Transaction {
+            version: 0,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(to_spend.txid(), 0),
+                script_sig: solution.script_sig,
+                sequence: 0,
+                witness: solution.witness,
+            }],
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script(),
+            }],
+        };
+        Ok(SignetTxs { to_spend, to_sign })
+    }
+
+    /// Returns the solution carried by the input of `to_sign`.
+    pub fn solution(&self) -> SignetSolution {
+        SignetSolution {
+            script_sig: self.to_sign.input[0].script_sig.clone(),
+            witness: self.to_sign.input[0].witness.clone(),
+        }
+    }
+}
+
+/// Checks that the solution of `block` satisfies `challenge`.
+///
+/// The genesis block is exempt from this check and must not be passed here.
+#[cfg(feature = "bitcoinconsensus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bitcoinconsensus")))]
+pub fn verify(block: &Block, challenge: &SignetChallenge) -> Result<(), Error> {
+    let flags = ::bitcoinconsensus::VERIFY_P2SH
+        | ::bitcoinconsensus::VERIFY_WITNESS
+        | ::bitcoinconsensus::VERIFY_DERSIG
+        | ::bitcoinconsensus::VERIFY_NULLDUMMY;
+    let txs = SignetTxs::new(block, challenge)?;
+    let spent = txs.to_spend.output[0].clone();
+    txs.to_sign.verify_with_flags(|_| Some(spent.clone()), flags).map_err(Error::Verification)
+}
+
+/// Returns the index of the witness commitment output of `coinbase`, as Bitcoin Core's
+/// `GetWitnessCommitmentIndex`.
+fn witness_commitment_index(coinbase: &Transaction) -> Option<usize> {
+    coinbase.output.iter().rposition(|o| {
+        let script = o.script_pubkey.as_bytes();
+        script.len() >= 38 && script[0..6] == WITNESS_COMMITMENT_HEADER
+    })
+}
+
+/// Finds the first push of `script` starting with [`SIGNET_HEADER`] followed by more data, and
+/// returns the script with that push cut down to the header alongside the data after it.
+///
+/// This is Bitcoin Core's `FetchAndClearCommitmentSection`.
+fn fetch_and_clear_solution(script: &Script) -> Option<(Script, Vec<u8>)> {
+    replace_solution(script, &SIGNET_HEADER)
+}
+
+/// Replaces the first push of `script` starting with [`SIGNET_HEADER`] followed by more data
+/// with `push`, returning the new script and the data that followed the header.
+fn replace_solution(script: &Script, push: &[u8]) -> Option<(Script, Vec<u8>)> {
+    let mut replacement = Builder::new();
+    let mut solution = None;
+    for instruction in script.instructions() {
+        replacement = match instruction {
+            Ok(Instruction::PushBytes(data)) if !data.is_empty() => {
+                if solution.is_none() && data.len() > SIGNET_HEADER.len() && data[..4] == SIGNET_HEADER {
+                    solution = Some(data[SIGNET_HEADER.len()..].to_vec());
+                    replacement.push_slice(push)
+                } else {
+                    replacement.push_slice(data)
+                }
+            }
+            Ok(Instruction::PushBytes(_)) => replacement.push_opcode(opcodes::all::OP_PUSHBYTES_0),
+            Ok(Instruction::Op(op)) => replacement.push_opcode(op),
+            Err(_) => break,
+        };
+    }
+    solution.map(|solution| (replacement.into_script(), solution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::block::Block;
+    use blockdata::constants::genesis_block;
+    use blockdata::transaction::EcdsaSighashType;
+    use hash_types::WitnessCommitment;
+    use network::constants::Network;
+    use secp256k1::{Message, Secp256k1, SecretKey};
+    use util::ecdsa::EcdsaSig;
+    use util::key::PublicKey;
+
+    #[test]
+    fn challenge_magic() {
+        // the challenge of Bitcoin's default signet
+        let challenge = SignetChallenge::new(Script::from(vec![
+            0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28,
+            0xa3, 0xd4, 0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40,
+            0x4f, 0x2d, 0x1e, 0x08, 0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96,
+            0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05, 0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88,
+            0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f, 0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52,
+            0xae,
+        ]));
+        assert_eq!(challenge.magic(), Network::Signet.magic());
+    }
+
+    #[test]
+    fn solution_roundtrip() {
+        let mut block = genesis_block(Network::Signet);
+        assert_eq!(SignetSolution::from_block(&block), Err(Error::NoWitnessCommitment));
+        assert_eq!(set_solution(&mut block, &SignetSolution::default()), Err(Error::NoWitnessCommitment));
+
+        let commitment = Block::witness_commitment_script(&WitnessCommitment::default());
+        block.txdata[0].output.push(TxOut { value: 0, script_pubkey: commitment.clone() });
+        assert_eq!(SignetSolution::from_block(&block), Ok(None));
+
+        let solution = SignetSolution {
+            script_sig: Builder::new().push_slice(&[1, 2, 3]).into_script(),
+            witness: Witness::from_vec(vec![vec![4, 5], vec![]]),
+        };
+        set_solution(&mut block, &solution).unwrap();
+        assert!(block.check_merkle_root());
+        assert_eq!(SignetSolution::from_block(&block), Ok(Some(solution)));
+        let cb_script = block.txdata[0].output[1].script_pubkey.clone();
+        assert_eq!(cb_script.as_bytes()[..38], commitment.as_bytes()[..]);
+
+        // replacing a solution doesn't stack them up
+        set_solution(&mut block, &SignetSolution::default()).unwrap();
+        assert_eq!(SignetSolution::from_block(&block), Ok(Some(SignetSolution::default())));
+        assert_eq!(block.txdata[0].output[1].script_pubkey.len(), 38 + 1 + 4 + 2);
+    }
+
+    #[test]
+    fn sign_block() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let pk = PublicKey::new(secp256k1::PublicKey::from_secret_key(&secp, &sk));
+        let challenge = SignetChallenge::new(
+            Builder::new()
+                .push_int(1)
+                .push_key(&pk)
+                .push_int(1)
+                .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+                .into_script(),
+        );
+
+        let mut block = genesis_block(Network::Signet);
+        let commitment = Block::witness_commitment_script(&WitnessCommitment::default());
+        block.txdata[0].output.push(TxOut { value: 0, script_pubkey: commitment });
+        set_solution(&mut block, &SignetSolution::default()).unwrap();
+
+        let txs = SignetTxs::new(&block, &challenge).unwrap();
+        assert_eq!(txs.to_spend.input[0].script_sig.len(), 1 + 1 + 72);
+        assert_eq!(txs.to_spend.output[0].script_pubkey, *challenge.script());
+        assert_eq!(txs.to_sign.input[0].previous_output, OutPoint::new(txs.to_spend.txid(), 0));
+
+        let sighash = txs.to_sign.signature_hash(0, challenge.script(), EcdsaSighashType::All as u32);
+        let sig = EcdsaSig {
+            sig: secp.sign_ecdsa(&Message::from_slice(&sighash[..]).unwrap(), &sk),
+            hash_ty: EcdsaSighashType::All,
+        };
+        let solution = SignetSolution {
+            script_sig: Builder::new()
+                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                .push_slice(&sig.to_vec())
+                .into_script(),
+            witness: Witness::default(),
+        };
+        set_solution(&mut block, &solution).unwrap();
+
+        // the commitment doesn't depend on the solution
+        let signed = SignetTxs::new(&block, &challenge).unwrap();
+        assert_eq!(signed.to_spend, txs.to_spend);
+        assert_eq!(signed.solution(), solution);
+        assert_eq!(signed.to_sign.input[0].previous_output, txs.to_sign.input[0].previous_output);
+
+        #[cfg(feature = "bitcoinconsensus")]
+        {
+            assert_eq!(verify(&block, &challenge), Ok(()));
+            let mut tampered = block.clone();
+            tampered.header.time += 1;
+            assert!(verify(&tampered, &challenge).is_err());
+        }
+    }
+}
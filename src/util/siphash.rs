@@ -0,0 +1,106 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Keyed SipHash-2-4.
+//!
+//! This module provides [`SipHasher`], the SipHash-2-4 hasher Bitcoin Core
+//! uses with secret or per-object keys: the short IDs of compact blocks, the
+//! hashes of BIP158 filter elements, and salted hashes such as address
+//! manager buckets or Erlay sketches.
+//!
+
+use core::hash::Hasher;
+
+use hashes::{siphash24, HashEngine};
+use util::endian;
+
+/// A keyed SipHash-2-4 hasher, equivalent to Bitcoin Core's `CSipHasher`.
+#[derive(Clone, Debug)]
+pub struct SipHasher {
+    engine: siphash24::HashEngine,
+}
+
+impl SipHasher {
+    /// Creates a hasher keyed with `k0` and `k1`.
+    pub fn new(k0: u64, k1: u64) -> SipHasher {
+        SipHasher { engine: siphash24::HashEngine::with_keys(k0, k1) }
+    }
+
+    /// Returns the keys of the hasher.
+    pub fn keys(&self) -> (u64, u64) {
+        self.engine.keys()
+    }
+
+    /// Adds `data` to the hashed data.
+    pub fn write(&mut self, data: &[u8]) -> &mut SipHasher {
+        self.engine.input(data);
+        self
+    }
+
+    /// Adds `n`, little-endian, to the hashed data.
+    pub fn write_u64(&mut self, n: u64) -> &mut SipHasher {
+        self.write(&endian::u64_to_array_le(n))
+    }
+
+    /// Returns the hash of the data written so far; more data can be written afterwards.
+    pub fn finalize(&self) -> u64 {
+        siphash24::Hash::from_engine_to_u64(self.engine.clone())
+    }
+
+    /// Returns the hash of `data` with keys `k0` and `k1`.
+    pub fn hash(k0: u64, k1: u64, data: &[u8]) -> u64 {
+        siphash24::Hash::hash_to_u64_with_keys(k0, k1, data)
+    }
+}
+
+impl Hasher for SipHasher {
+    fn finish(&self) -> u64 {
+        self.finalize()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        SipHasher::write(self, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siphash_vectors() {
+        // test vectors of Bitcoin Core's hash_tests.cpp
+        let mut hasher = SipHasher::new(0x0706050403020100, 0x0F0E0D0C0B0A0908);
+        assert_eq!(hasher.finalize(), 0x726fdb47dd0e0e31);
+        hasher.write(&[0]);
+        assert_eq!(hasher.finalize(), 0x74f839c593dc67fd);
+        hasher.write(&[1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(hasher.finalize(), 0x93f5f5799a932462);
+        hasher.write_u64(0x0F0E0D0C0B0A0908);
+        assert_eq!(hasher.finalize(), 0x3f2acc7f57c29bdb);
+        hasher.write(&[16, 17]);
+        assert_eq!(hasher.finalize(), 0x4bc1b3f0968dd39c);
+        hasher.write(&[18, 19, 20, 21, 22, 23, 24, 25, 26]);
+        assert_eq!(hasher.finalize(), 0x2f2e6163076bcfad);
+        hasher.write(&[27, 28, 29, 30, 31]);
+        assert_eq!(hasher.finalize(), 0x7127512f72f27cce);
+        hasher.write_u64(0x2726252423222120).write_u64(0x2F2E2D2C2B2A2928);
+        assert_eq!(hasher.finalize(), 0xe612a3cb9ecba951);
+        assert_eq!(hasher.keys(), (0x0706050403020100, 0x0F0E0D0C0B0A0908));
+
+        let data: Vec<u8> = (0..48).collect();
+        assert_eq!(SipHasher::hash(0x0706050403020100, 0x0F0E0D0C0B0A0908, &data), 0xe612a3cb9ecba951);
+        assert_eq!(Hasher::finish(&hasher), 0xe612a3cb9ecba951);
+    }
+}
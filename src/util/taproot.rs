@@ -31,6 +31,7 @@ use util::key::XOnlyPublicKey;
 use Script;
 
 use consensus::Encodable;
+#[cfg(feature = "arbitrary")] use arbitrary::{self, Arbitrary, Unstructured};
 
 /// The SHA-256 midstate value for the TapLeaf hash.
 const MIDSTATE_TAPLEAF: [u8; 32] = [
@@ -98,6 +99,22 @@ sha256t_hash_newtype!(TapSighashHash, TapSighashTag, MIDSTATE_TAPSIGHASH, 64,
     doc="Taproot-tagged hash for the taproot signature hash", false
 );
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for TapLeafHash {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(TapLeafHash::from_inner(u.arbitrary()?))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> Arbitrary<'a> for TapBranchHash {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(TapBranchHash::from_inner(u.arbitrary()?))
+    }
+}
+
 impl TapTweakHash {
     /// Creates a new BIP341 [`TapTweakHash`] from key and tweak. Produces `H_taptweak(P||R)` where
     /// `P` is the internal key and `R` is the merkle root.
@@ -530,7 +547,8 @@ impl TaprootBuilder {
                 // Can't propagate further up than the root
                 return Err(TaprootBuilderError::OverCompleteTree);
             }
-            node = NodeInfo::combine(node, child)?;
+            // The sibling inserted earlier goes first, keeping the leaves in DFS order.
+            node = NodeInfo::combine(child, node)?;
             // Propagate to combine nodes at a lower depth
             depth -= 1;
         }
@@ -1097,6 +1115,7 @@ impl error::Error for TaprootError {}
 #[cfg(test)]
 mod test {
     use {Address, Network};
+    use blockdata::constants::BECH32_HRP_MAIN;
     use schnorr::TapTweak;
 
     use super::*;
@@ -1372,7 +1391,9 @@ mod test {
             let expected_output_key = XOnlyPublicKey::from_str(arr["intermediary"]["tweakedPubkey"].as_str().unwrap()).unwrap();
             let expected_tweak = TapTweakHash::from_str(arr["intermediary"]["tweak"].as_str().unwrap()).unwrap();
             let expected_spk = Script::from_str(arr["expected"]["scriptPubKey"].as_str().unwrap()).unwrap();
-            let expected_addr = Address::from_str(arr["expected"]["bip350Address"].as_str().unwrap()).unwrap();
+            // The vectors use Bitcoin's "bc" prefix, re-encode them with the texitcoin one.
+            let (_, expected_data, variant) = ::bech32::decode(arr["expected"]["bip350Address"].as_str().unwrap()).unwrap();
+            let expected_addr = ::bech32::encode(BECH32_HRP_MAIN, expected_data, variant).unwrap();
 
             let tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root);
             let (output_key, _parity) = internal_key.tap_tweak(&secp, merkle_root);
@@ -1381,7 +1402,7 @@ mod test {
 
             assert_eq!(expected_output_key, output_key.to_inner());
             assert_eq!(expected_tweak, tweak);
-            assert_eq!(expected_addr, addr);
+            assert_eq!(expected_addr, addr.to_string());
             assert_eq!(expected_spk, spk);
         }
     }
@@ -127,6 +127,76 @@ macro_rules! construct_uint {
                 res
             }
 
+            /// Creates big integer value from a byte array using
+            /// little-endian encoding
+            pub fn from_le_bytes(bytes: [u8; $n_words * 8]) -> $name {
+                use super::endian::slice_to_u64_le;
+                let mut slice = [0u64; $n_words];
+                slice.iter_mut()
+                    .zip(bytes.chunks(8))
+                    .for_each(|(word, bytes)| *word = slice_to_u64_le(bytes));
+                $name(slice)
+            }
+
+            /// Convert a big integer into a byte array using little-endian encoding
+            pub fn to_le_bytes(&self) -> [u8; $n_words * 8] {
+                use super::endian::u64_to_array_le;
+                let mut res = [0; $n_words * 8];
+                for i in 0..$n_words {
+                    let start = i * 8;
+                    res[start..start+8].copy_from_slice(&u64_to_array_le(self.0[i]));
+                }
+                res
+            }
+
+            /// The largest value the type can hold
+            #[inline]
+            pub fn max_value() -> $name {
+                $name([u64::max_value(); $n_words])
+            }
+
+            /// Checked addition, returns `None` on overflow
+            pub fn checked_add(self, other: $name) -> Option<$name> {
+                let ret = self + other;
+                if ret < self { None } else { Some(ret) }
+            }
+
+            /// Checked subtraction, returns `None` if `other` is larger than `self`
+            pub fn checked_sub(self, other: $name) -> Option<$name> {
+                if other > self { None } else { Some(self - other) }
+            }
+
+            /// Checked multiplication, returns `None` on overflow
+            pub fn checked_mul(self, other: $name) -> Option<$name> {
+                // The product needs at least `bits - 1` bits, so only the borderline
+                // case has to be checked by dividing back.
+                let bits = self.bits() + other.bits();
+                if bits > $n_words * 64 + 1 {
+                    return None;
+                }
+                let ret = self * other;
+                if bits == $n_words * 64 + 1 && ret / self != other {
+                    None
+                } else {
+                    Some(ret)
+                }
+            }
+
+            /// Saturating addition, returns the largest value on overflow
+            pub fn saturating_add(self, other: $name) -> $name {
+                self.checked_add(other).unwrap_or_else($name::max_value)
+            }
+
+            /// Saturating subtraction, returns zero if `other` is larger than `self`
+            pub fn saturating_sub(self, other: $name) -> $name {
+                self.checked_sub(other).unwrap_or_default()
+            }
+
+            /// Saturating multiplication, returns the largest value on overflow
+            pub fn saturating_mul(self, other: $name) -> $name {
+                self.checked_mul(other).unwrap_or_else($name::max_value)
+            }
+
             // divmod like operation, returns (quotient, remainder)
             #[inline]
             fn div_rem(self, other: Self) -> (Self, Self) {
@@ -417,6 +487,60 @@ macro_rules! construct_uint {
 
         display_from_debug!($name);
 
+        impl ::core::fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                if f.alternate() {
+                    write!(f, "0x")?;
+                }
+                for word in self.0.iter().rev() {
+                    write!(f, "{:016x}", word)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl ::core::fmt::UpperHex for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                if f.alternate() {
+                    write!(f, "0x")?;
+                }
+                for word in self.0.iter().rev() {
+                    write!(f, "{:016X}", word)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl $crate::hashes::hex::FromHex for $name {
+            fn from_byte_iter<I>(iter: I) -> Result<Self, $crate::hashes::hex::Error>
+            where
+                I: Iterator<Item = Result<u8, $crate::hashes::hex::Error>>
+                    + ExactSizeIterator
+                    + DoubleEndedIterator,
+            {
+                if iter.len() != $n_words * 8 {
+                    return Err($crate::hashes::hex::Error::InvalidLength($n_words * 8 * 2, iter.len() * 2));
+                }
+                let mut bytes = [0u8; $n_words * 8];
+                for (byte, hex) in bytes.iter_mut().zip(iter) {
+                    *byte = hex?;
+                }
+                Ok($name::from_be_bytes(bytes))
+            }
+        }
+
+        impl ::core::str::FromStr for $name {
+            type Err = $crate::hashes::hex::Error;
+
+            /// Parses a big-endian hex string as printed by `Display`, with or without the
+            /// `0x` prefix.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                use $crate::hashes::hex::FromHex;
+                let s = if s.starts_with("0x") { &s[2..] } else { s };
+                $name::from_hex(s)
+            }
+        }
+
         impl $crate::consensus::Encodable for $name {
             #[inline]
             fn consensus_encode<S: $crate::io::Write>(
@@ -537,6 +661,22 @@ impl Uint256 {
         let &Uint256(data) = self;
         Uint128([data[0], data[1]])
     }
+
+    /// Expands the compact "nBits" encoding of a proof-of-work target.
+    ///
+    /// See [`Target::from_compact`](::util::pow::Target::from_compact) for how invalid
+    /// encodings are handled.
+    pub fn from_compact(bits: u32) -> Uint256 {
+        use util::pow::{CompactTarget, Target};
+        Target::from_compact(CompactTarget::from_consensus(bits)).to_uint256()
+    }
+
+    /// Encodes the number in the compact "nBits" form, discarding any precision the
+    /// encoding cannot hold.
+    pub fn to_compact_lossy(&self) -> u32 {
+        use util::pow::Target;
+        Target::from_uint256(*self).to_compact_lossy().to_consensus()
+    }
 }
 
 #[cfg(test)]
@@ -659,6 +799,74 @@ mod tests {
         // TODO: bit inversion
     }
 
+    #[test]
+    pub fn uint_le_bytes() {
+        let num = Uint256([0x11fed2bad1c0ffe0, 0xbaadf00ddefaceda, 0xdeafbabe2bedfeed, 0x1badcafedeadbeef]);
+        let mut le = num.to_be_bytes();
+        le.reverse();
+        assert_eq!(num.to_le_bytes(), le);
+        assert_eq!(Uint256::from_le_bytes(le), num);
+    }
+
+    #[test]
+    pub fn uint256_checked_saturating_test() {
+        let max = Uint256::max_value();
+        let one = Uint256::from_u64(1).unwrap();
+        let two = Uint256::from_u64(2).unwrap();
+        let half = max >> 1;
+
+        assert_eq!(one.checked_add(two), Some(Uint256::from_u64(3).unwrap()));
+        assert_eq!(max.checked_add(one), None);
+        assert_eq!(max.saturating_add(one), max);
+        assert_eq!(half.checked_add(half + one), Some(max));
+
+        assert_eq!(two.checked_sub(one), Some(one));
+        assert_eq!(one.checked_sub(two), None);
+        assert_eq!(one.saturating_sub(two), Uint256::default());
+
+        assert_eq!(half.checked_mul(two), Some(max - one));
+        assert_eq!((half + one).checked_mul(two), None);
+        assert_eq!((half + one).saturating_mul(two), max);
+        assert_eq!(max.checked_mul(one), Some(max));
+        assert_eq!(max.checked_mul(Uint256::default()), Some(Uint256::default()));
+        let low = Uint256([0, 0, 1, 0]);
+        assert_eq!(low.checked_mul(low), None);
+        assert_eq!(Uint256([0, 1, 0, 0]).checked_mul(low), Some(Uint256([0, 0, 0, 1])));
+
+        assert_eq!(Uint128::max_value().checked_add(Uint128::from_u64(1).unwrap()), None);
+    }
+
+    #[test]
+    pub fn uint256_hex_test() {
+        use hashes::hex::{FromHex, ToHex};
+
+        let num = Uint256([0x11fed2bad1c0ffe0, 0xbaadf00ddefaceda, 0xdeafbabe2bedfeed, 0x1badcafedeadbeef]);
+        let hex = "1badcafedeadbeefdeafbabe2bedfeedbaadf00ddefaceda11fed2bad1c0ffe0";
+        assert_eq!(num.to_hex(), hex);
+        assert_eq!(format!("{:#x}", num), format!("0x{}", hex));
+        assert_eq!(format!("{:X}", num), hex.to_uppercase());
+        assert_eq!(Uint256::from_hex(hex).unwrap(), num);
+        assert_eq!(Uint256::from_hex(&hex.to_uppercase()).unwrap(), num);
+        assert!(Uint256::from_hex(&hex[2..]).is_err());
+        assert!(Uint256::from_hex(&hex[1..]).is_err());
+
+        assert_eq!(num.to_string().parse::<Uint256>().unwrap(), num);
+        assert_eq!(hex.parse::<Uint256>().unwrap(), num);
+        assert!("0x1234".parse::<Uint256>().is_err());
+    }
+
+    #[test]
+    pub fn uint256_compact_test() {
+        let target = Uint256::from_compact(0x1d00ffff);
+        assert_eq!(target, Uint256([0, 0, 0, 0xffff0000]));
+        assert_eq!(target.to_compact_lossy(), 0x1d00ffff);
+        assert_eq!(Uint256::from_compact(0x03123456), Uint256::from_u64(0x123456).unwrap());
+        assert_eq!(Uint256::from_u64(0x80).unwrap().to_compact_lossy(), 0x02008000);
+        // negative and overflowing encodings
+        assert_eq!(Uint256::from_compact(0x04923456), Uint256::default());
+        assert_eq!(Uint256::from_compact(0xff123456), Uint256::default());
+    }
+
     #[test]
     pub fn mul_u32_test() {
         let u64_val = Uint256::from_u64(0xDEADBEEFDEADBEEF).unwrap();
@@ -0,0 +1,185 @@
+// Rust Bitcoin Library
+// Written by
+//   The Rust Bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Transaction weight.
+//!
+//! This module introduces the [`Weight`] type, the size of transactions and
+//! blocks in weight units as defined by BIP141.
+//!
+
+use core::{fmt, ops};
+
+use blockdata::constants::{MAX_BLOCK_WEIGHT, WITNESS_SCALE_FACTOR};
+
+/// The weight of a transaction or block, in weight units.
+///
+/// Non-witness data weighs [`WITNESS_SCALE_FACTOR`] weight units per byte, witness data one
+/// weight unit per byte; a virtual byte is [`WITNESS_SCALE_FACTOR`] weight units.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Weight(u64);
+
+impl Weight {
+    /// The zero weight.
+    pub const ZERO: Weight = Weight(0);
+    /// The maximum representable weight.
+    pub const MAX: Weight = Weight(0xffff_ffff_ffff_ffff);
+    /// The maximum weight of a block.
+    pub const MAX_BLOCK: Weight = Weight(MAX_BLOCK_WEIGHT as u64);
+
+    /// Creates a weight of `wu` weight units.
+    pub fn from_wu(wu: u64) -> Weight {
+        Weight(wu)
+    }
+
+    /// Creates the weight of `vb` virtual bytes, `None` on overflow.
+    pub fn from_vb(vb: u64) -> Option<Weight> {
+        vb.checked_mul(WITNESS_SCALE_FACTOR as u64).map(Weight)
+    }
+
+    /// Creates the weight of `size` bytes of non-witness data, `None` on overflow.
+    pub fn from_non_witness_data_size(size: u64) -> Option<Weight> {
+        Weight::from_vb(size)
+    }
+
+    /// Creates the weight of `size` bytes of witness data.
+    pub fn from_witness_data_size(size: u64) -> Weight {
+        Weight(size)
+    }
+
+    /// Returns the weight in weight units.
+    pub fn to_wu(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the weight in virtual bytes, rounded down.
+    pub fn to_vbytes_floor(self) -> u64 {
+        self.0 / WITNESS_SCALE_FACTOR as u64
+    }
+
+    /// Returns the weight in virtual bytes, rounded up as virtual sizes are.
+    pub fn to_vbytes_ceil(self) -> u64 {
+        let scale = WITNESS_SCALE_FACTOR as u64;
+        self.0 / scale + if self.0 % scale == 0 { 0 } else { 1 }
+    }
+
+    /// Checked addition, `None` on overflow.
+    pub fn checked_add(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_add(rhs.0).map(Weight)
+    }
+
+    /// Checked subtraction, `None` on underflow.
+    pub fn checked_sub(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_sub(rhs.0).map(Weight)
+    }
+
+    /// Checked multiplication, `None` on overflow.
+    pub fn checked_mul(self, rhs: u64) -> Option<Weight> {
+        self.0.checked_mul(rhs).map(Weight)
+    }
+
+    /// Checked division, `None` if `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<Weight> {
+        self.0.checked_div(rhs).map(Weight)
+    }
+}
+
+impl fmt::Display for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} wu", self.0)
+    }
+}
+
+impl From<Weight> for u64 {
+    fn from(weight: Weight) -> u64 {
+        weight.0
+    }
+}
+
+impl ops::Add for Weight {
+    type Output = Weight;
+
+    fn add(self, rhs: Weight) -> Self::Output {
+        self.checked_add(rhs).expect("Weight addition error")
+    }
+}
+
+impl ops::AddAssign for Weight {
+    fn add_assign(&mut self, other: Weight) {
+        *self = *self + other
+    }
+}
+
+impl ops::Sub for Weight {
+    type Output = Weight;
+
+    fn sub(self, rhs: Weight) -> Self::Output {
+        self.checked_sub(rhs).expect("Weight subtraction error")
+    }
+}
+
+impl ops::SubAssign for Weight {
+    fn sub_assign(&mut self, other: Weight) {
+        *self = *self - other
+    }
+}
+
+impl ops::Mul<u64> for Weight {
+    type Output = Weight;
+
+    fn mul(self, rhs: u64) -> Self::Output {
+        self.checked_mul(rhs).expect("Weight multiplication error")
+    }
+}
+
+impl ops::Div<u64> for Weight {
+    type Output = Weight;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        self.checked_div(rhs).expect("Weight division error")
+    }
+}
+
+impl ::core::iter::Sum for Weight {
+    fn sum<I: Iterator<Item = Weight>>(iter: I) -> Self {
+        iter.fold(Weight::ZERO, |sum, weight| sum + weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight() {
+        let weight = Weight::from_vb(141).unwrap();
+        assert_eq!(weight, Weight::from_wu(564));
+        assert_eq!(weight.to_vbytes_floor(), 141);
+        assert_eq!(weight.to_vbytes_ceil(), 141);
+        assert_eq!(Weight::from_wu(565).to_vbytes_floor(), 141);
+        assert_eq!(Weight::from_wu(565).to_vbytes_ceil(), 142);
+        assert_eq!(Weight::from_vb(u64::max_value()), None);
+        assert_eq!(Weight::MAX_BLOCK.to_vbytes_floor(), 1_000_000);
+
+        let tx = Weight::from_non_witness_data_size(100).unwrap() + Weight::from_witness_data_size(110);
+        assert_eq!(tx.to_wu(), 510);
+        assert_eq!(tx.to_string(), "510 wu");
+        assert_eq!(tx - Weight::from_wu(10), Weight::from_wu(500));
+        assert_eq!(tx * 2 / 4, Weight::from_wu(255));
+        assert_eq!(vec![tx, tx].into_iter().sum::<Weight>(), Weight::from_wu(1020));
+        assert_eq!(Weight::MAX.checked_add(Weight::from_wu(1)), None);
+        assert_eq!(Weight::ZERO.checked_sub(Weight::from_wu(1)), None);
+        assert_eq!(tx.checked_div(0), None);
+        assert_eq!(u64::from(tx), 510);
+    }
+}